@@ -0,0 +1,102 @@
+//! Echo throughput benchmark.
+//!
+//! Send a large payload on every connection while reading it back,
+//! and print the echoed bytes per second. Run the server in release
+//! mode, with and without the `splice` feature, and then:
+//!
+//! ```sh
+//! cargo run --release --bin echo-bench -- --port 10000 --megabytes 4096
+//! ```
+use std::time::Instant;
+
+use clap::Parser;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::task::JoinSet;
+
+#[derive(clap::Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    #[arg(long, default_value = "127.0.0.1")]
+    address: String,
+
+    #[arg(long, default_value_t = 10000)]
+    port: u16,
+
+    /// Megabytes sent on every connection
+    #[arg(long, default_value_t = 1024)]
+    megabytes: u64,
+
+    /// Number of concurrent connections
+    #[arg(long, default_value_t = 1)]
+    connections: usize,
+}
+
+const CHUNK_LEN: usize = 64 * 1024;
+
+async fn run(address: String, len: u64) -> Result<u64, anyhow::Error> {
+    let mut stream = TcpStream::connect(address).await?;
+    let (mut read_half, mut write_half) = stream.split();
+
+    let write = async {
+        let chunk = vec![0xa5; CHUNK_LEN];
+        let mut sent = 0;
+        while sent < len {
+            let n = CHUNK_LEN.min(usize::try_from(len - sent)?);
+            write_half.write_all(&chunk[..n]).await?;
+            sent += n as u64;
+        }
+        write_half.shutdown().await?;
+        Ok::<_, anyhow::Error>(())
+    };
+
+    let read = async {
+        let mut buffer = vec![0; CHUNK_LEN];
+        let mut received = 0;
+        loop {
+            match read_half.read(&mut buffer).await? {
+                0 => break,
+                n => received += n as u64,
+            }
+        }
+        Ok::<_, anyhow::Error>(received)
+    };
+
+    let ((), received) = tokio::try_join!(write, read)?;
+
+    Ok(received)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), anyhow::Error> {
+    tracing_subscriber::fmt::init();
+
+    let args = Args::parse();
+
+    let address = format!("{}:{}", args.address, args.port);
+    let len = args.megabytes * 1024 * 1024;
+
+    let start = Instant::now();
+
+    let mut connections = JoinSet::new();
+    for _ in 0..args.connections {
+        connections.spawn(run(address.clone(), len));
+    }
+
+    let mut received = 0;
+    while let Some(result) = connections.join_next().await {
+        received += result??;
+    }
+
+    let elapsed = start.elapsed();
+
+    #[allow(clippy::cast_precision_loss)]
+    let throughput = received as f64 / elapsed.as_secs_f64() / 1e9;
+    println!(
+        "echoed {received} bytes on {} connections in {elapsed:?}: {throughput:.2} GB/s",
+        args.connections
+    );
+
+    Ok(())
+}
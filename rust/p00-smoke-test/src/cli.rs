@@ -0,0 +1,107 @@
+//! The echo server flags: UDP or TLS instead of plain TCP, and the
+//! caps on the bytes, the time and the bandwidth of the connections.
+use std::sync::Arc;
+use std::time::Duration;
+
+use protohackers_metrics::Registry;
+use protohackers_server::ServerArgs;
+use protohackers_tls::TlsArgs;
+
+use crate::{Metrics, TokenBucket};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[command(flatten)]
+    pub server: ServerArgs,
+
+    /// Echo the UDP datagrams instead of the TCP streams
+    #[arg(long, conflicts_with = "tls_cert")]
+    pub udp: bool,
+
+    #[command(flatten)]
+    pub tls: TlsArgs,
+
+    /// Close a connection after echoing this number of bytes
+    #[arg(long)]
+    pub max_bytes: Option<u64>,
+
+    /// Close a connection after this number of seconds
+    #[arg(long)]
+    pub max_duration: Option<u64>,
+
+    /// The size of the echo buffer
+    #[arg(long)]
+    pub buffer_size: Option<usize>,
+
+    /// Wait this number of milliseconds after every read
+    #[arg(long)]
+    pub read_delay: Option<u64>,
+
+    /// Echo at most this number of bytes per second on every
+    /// connection
+    #[arg(long)]
+    pub bandwidth: Option<u64>,
+
+    /// The bytes a connection can echo in a burst over the bandwidth
+    #[arg(long, requires = "bandwidth")]
+    pub burst: Option<u64>,
+
+    /// Echo at most this number of bytes per second on all the
+    /// connections
+    #[arg(long)]
+    pub global_bandwidth: Option<u64>,
+
+    /// The bytes all the connections can echo in a burst over the
+    /// global bandwidth
+    #[arg(long, requires = "global_bandwidth")]
+    pub global_burst: Option<u64>,
+}
+
+/// Run the server configured by `args`.
+///
+/// # Errors
+/// * Error when the server can not be started or fails.
+pub async fn run(args: Args) -> Result<(), anyhow::Error> {
+    args.server.init_tracing();
+
+    let registry = Registry::new();
+    let metrics = Arc::new(Metrics::register(&registry));
+
+    if args.udp {
+        args.server.metrics.spawn(&registry).await?;
+        let socket = args.server.bind_udp().await?;
+        #[cfg(unix)]
+        protohackers_server::signals::dump_on_signal(None)?;
+        return protohackers_server::signals::until_shutdown(crate::udp_echo_with_metrics(
+            socket, &metrics,
+        ))
+        .await
+        .unwrap_or(Ok(()));
+    }
+
+    let acceptor = args.tls.acceptor()?;
+
+    let config = crate::Config {
+        max_connections: args.server.max_connections,
+        max_bytes: args.max_bytes,
+        max_duration: args.max_duration.map(Duration::from_secs),
+        buffer_size: args.buffer_size,
+        read_delay: args.read_delay.map(Duration::from_millis),
+        bandwidth: args.bandwidth,
+        burst: args.burst,
+        global_shaping: args
+            .global_bandwidth
+            .map(|bandwidth| Arc::new(TokenBucket::new(bandwidth, args.global_burst.unwrap_or(0)))),
+        metrics,
+    };
+
+    crate::serve_with(
+        args.server
+            .server_with_metrics(&registry)
+            .await?
+            .with_problem(env!("CARGO_PKG_NAME")),
+        config,
+        acceptor,
+    )
+    .await
+}
@@ -22,34 +22,261 @@
 //! server in the cloud instead).
 //!
 //! Your program will implement the TCP Echo Service from RFC 862.
-use tracing::debug;
+use tracing::{debug, info};
 
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpStream;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio_rustls::TlsAcceptor;
+
+use protohackers_server::{IdleTimeout, Listeners, Server};
+
+pub mod cli;
+pub mod metrics;
+pub mod shaping;
+#[cfg(all(target_os = "linux", feature = "splice"))]
+mod splice;
+pub mod tls;
+
+pub use metrics::Metrics;
+pub use shaping::TokenBucket;
+
+/// The default size of the echo buffer.
+pub const BUFFER_SIZE: usize = 1024;
+
+/// The default idle timeout of the connections.
+pub const IDLE_TIMEOUT: Duration = Duration::from_mins(1);
+
+/// The echo configuration: the caps of a publicly exposed server, so
+/// that it cannot be used as a free bandwidth reflector, and the
+/// knobs to simulate a slow backend.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    /// Close the connections over this number of concurrent ones
+    /// right after the accept.
+    pub max_connections: Option<usize>,
+
+    /// Close a connection after echoing this number of bytes.
+    pub max_bytes: Option<u64>,
+
+    /// Close a connection after this time.
+    pub max_duration: Option<Duration>,
+
+    /// The size of the echo buffer, [`BUFFER_SIZE`] when `None`.
+    pub buffer_size: Option<usize>,
+
+    /// Wait this time after every read, before echoing.
+    pub read_delay: Option<Duration>,
+
+    /// Echo at most this number of bytes per second on every
+    /// connection.
+    pub bandwidth: Option<u64>,
+
+    /// The bytes a connection can echo in a burst over `bandwidth`,
+    /// none when `None`.
+    pub burst: Option<u64>,
+
+    /// The bucket shared by all the connections.
+    pub global_shaping: Option<Arc<TokenBucket>>,
+
+    pub metrics: Arc<Metrics>,
+}
+
+/// Accept the connections and echo them, with TLS when an acceptor
+/// is given.
+///
+/// # Errors
+/// * Error when the listener returns an error.
+pub async fn serve(
+    listeners: impl Into<Listeners>,
+    config: Config,
+    acceptor: Option<TlsAcceptor>,
+) -> Result<(), anyhow::Error> {
+    let server = Server::new(listeners).with_max_connections(config.max_connections);
+    serve_with(server, config, acceptor).await
+}
+
+/// Echo the connections accepted by `server`, with TLS when an
+/// acceptor is given; the connections cap is the one of the server,
+/// `config.max_connections` is ignored.
+///
+/// # Errors
+/// * Error when the listener returns an error.
+pub async fn serve_with(
+    server: Server,
+    config: Config,
+    acceptor: Option<TlsAcceptor>,
+) -> Result<(), anyhow::Error> {
+    let (rejected, crashed) = (config.metrics.clone(), config.metrics.clone());
+    server
+        .with_default_idle_timeout(IDLE_TIMEOUT)
+        .with_on_reject(move |_| rejected.reject())
+        .with_on_crash(move |_| crashed.crash())
+        .serve(move |socket, _| {
+            let (acceptor, config) = (acceptor.clone(), config.clone());
+            async move {
+                let metrics = config.metrics.clone();
+                metrics.open();
+                let result = if let Some(acceptor) = acceptor {
+                    tls::echo_with_config(socket, acceptor, config).await
+                } else {
+                    echo_with_config(socket, config).await
+                };
+                metrics.close();
+                result
+            }
+        })
+        .await?;
+
+    Ok(())
+}
 
 /// A simple echo.
 ///
 /// # Errors
 /// * Error when the under socket returns an error.
+pub async fn echo(stream: IdleTimeout<TcpStream>) -> Result<(), anyhow::Error> {
+    echo_with_config(stream, Config::default()).await
+}
+
+/// A simple echo, within the caps of the given configuration.
+///
+/// With the `splice` feature on Linux the data never leaves the
+/// kernel, unless the echo is slowed down, elsewhere it is copied
+/// through a user space buffer; the kernel copy is not seen by the
+/// idle timeout of the stream.
+///
+/// # Errors
+/// * Error when the under socket returns an error.
 #[tracing::instrument(skip(stream))]
-pub async fn echo(mut stream: TcpStream) -> Result<(), anyhow::Error> {
+pub async fn echo_with_config(
+    mut stream: IdleTimeout<TcpStream>,
+    config: Config,
+) -> Result<(), anyhow::Error> {
     debug!("start");
 
-    let (mut read_half, mut write_half) = stream.split();
+    #[cfg(all(target_os = "linux", feature = "splice"))]
+    let echoed = if config.read_delay.is_none()
+        && config.bandwidth.is_none()
+        && config.global_shaping.is_none()
+    {
+        with_max_duration(
+            config.max_duration,
+            splice::echo(
+                stream.get_ref(),
+                config.max_bytes.unwrap_or(u64::MAX),
+                config.buffer_size.unwrap_or(splice::PIPE_CAPACITY),
+                &config.metrics,
+            ),
+        )
+        .await?
+    } else {
+        with_max_duration(config.max_duration, copy(&mut stream, &config)).await?
+    };
 
-    let mut buffer = [0; 1024];
+    #[cfg(not(all(target_os = "linux", feature = "splice")))]
+    let echoed = with_max_duration(config.max_duration, copy(&mut stream, &config)).await?;
+
+    stream.flush().await?;
+    stream.shutdown().await?;
+
+    debug!("end: {echoed:?} bytes");
+
+    Ok(())
+}
+
+/// A datagram echo: every datagram is sent back verbatim to its
+/// source, it never returns on success.
+///
+/// # Errors
+/// * Error when the under socket returns an error.
+pub async fn udp_echo(socket: UdpSocket) -> Result<(), anyhow::Error> {
+    udp_echo_with_metrics(socket, &Metrics::new()).await
+}
+
+/// A datagram echo, accounting the echoed bytes.
+///
+/// # Errors
+/// * Error when the under socket returns an error.
+#[tracing::instrument(skip(socket, metrics))]
+pub async fn udp_echo_with_metrics(
+    socket: UdpSocket,
+    metrics: &Metrics,
+) -> Result<(), anyhow::Error> {
+    debug!("start");
+
+    // the largest UDP payload
+    let mut buffer = vec![0; 65_507];
     loop {
-        match read_half.read(&mut buffer).await {
-            Ok(0) => break,
-            Ok(n) => write_half.write_all(&buffer[..n]).await?,
-            Err(err) => return Err(err.into()),
+        let (n, source) = socket.recv_from(&mut buffer).await?;
+
+        debug!("echo {n} bytes to {source}");
+
+        socket.send_to(&buffer[..n], source).await?;
+        metrics.echoed(n);
+    }
+}
+
+/// Run the echo up to `max_duration`, the number of bytes is `None`
+/// when the time is over.
+async fn with_max_duration(
+    max_duration: Option<Duration>,
+    echo: impl std::future::Future<Output = Result<u64, std::io::Error>>,
+) -> Result<Option<u64>, std::io::Error> {
+    if let Some(max_duration) = max_duration {
+        if let Ok(echoed) = tokio::time::timeout(max_duration, echo).await {
+            echoed.map(Some)
+        } else {
+            info!("closing after {max_duration:?}");
+            Ok(None)
         }
+    } else {
+        echo.await.map(Some)
     }
+}
 
-    write_half.flush().await?;
-    write_half.shutdown().await?;
+/// Echo through a user space buffer until the end of file or
+/// `max_bytes`, returning the number of bytes.
+async fn copy<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    config: &Config,
+) -> Result<u64, std::io::Error> {
+    let max_bytes = config.max_bytes.unwrap_or(u64::MAX);
+    let mut buffer = vec![0; config.buffer_size.unwrap_or(BUFFER_SIZE).max(1)];
 
-    debug!("end");
+    let shaping = config
+        .bandwidth
+        .map(|bandwidth| TokenBucket::new(bandwidth, config.burst.unwrap_or(0)));
 
-    Ok(())
+    let mut echoed = 0;
+    while echoed < max_bytes {
+        let len =
+            usize::try_from(max_bytes - echoed).map_or(buffer.len(), |len| len.min(buffer.len()));
+        let n = stream.read(&mut buffer[..len]).await?;
+        if n == 0 {
+            break;
+        }
+
+        if let Some(read_delay) = config.read_delay {
+            tokio::time::sleep(read_delay).await;
+        }
+
+        // wait for both the connection and the global buckets
+        let shaping_delay = [shaping.as_ref(), config.global_shaping.as_deref()]
+            .into_iter()
+            .flatten()
+            .map(|shaping| shaping.reserve(n))
+            .max();
+        if let Some(shaping_delay) = shaping_delay {
+            tokio::time::sleep(shaping_delay).await;
+        }
+
+        stream.write_all(&buffer[..n]).await?;
+        config.metrics.echoed(n);
+        echoed += n as u64;
+    }
+
+    Ok(echoed)
 }
@@ -0,0 +1,160 @@
+use std::fmt;
+use std::time::Duration;
+
+use protohackers_metrics::{Counter, Gauge, Registry};
+
+/// The server metrics, the aggregation of all the connections.
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    connections: Counter,
+    active: Gauge,
+    rejected: Counter,
+    crashed: Counter,
+    bytes: Counter,
+}
+
+impl Metrics {
+    /// The metrics in a registry of their own.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::register(&Registry::new())
+    }
+
+    #[must_use]
+    pub fn register(registry: &Registry) -> Self {
+        Self {
+            connections: registry.counter("connections_total", "The accepted connections"),
+            active: registry.gauge("connections_active", "The connections still open"),
+            rejected: registry.counter(
+                "connections_rejected_total",
+                "The connections closed over the connections cap",
+            ),
+            crashed: registry.counter(
+                "connections_crashed_total",
+                "The connections whose handler panicked",
+            ),
+            bytes: registry.counter("echoed_bytes_total", "The echoed bytes"),
+        }
+    }
+
+    pub fn open(&self) {
+        self.connections.inc();
+        self.active.inc();
+    }
+
+    pub fn close(&self) {
+        self.active.dec();
+    }
+
+    /// Account a connection closed over the connections cap.
+    pub fn reject(&self) {
+        self.rejected.inc();
+    }
+
+    /// Account an open connection whose handler panicked, never
+    /// closed.
+    pub fn crash(&self) {
+        self.crashed.inc();
+        self.active.dec();
+    }
+
+    pub fn echoed(&self, bytes: usize) {
+        self.bytes.add(bytes as u64);
+    }
+
+    #[must_use]
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            connections: self.connections.get(),
+            active: u64::try_from(self.active.get()).unwrap_or_default(),
+            rejected: self.rejected.get(),
+            crashed: self.crashed.get(),
+            bytes: self.bytes.get(),
+        }
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    pub connections: u64,
+
+    /// The connections still open.
+    pub active: u64,
+
+    pub rejected: u64,
+
+    pub crashed: u64,
+
+    /// The echoed bytes, on all the connections.
+    pub bytes: u64,
+}
+
+impl Snapshot {
+    /// The bytes echoed per second since a previous snapshot, taken
+    /// `elapsed` before.
+    #[must_use]
+    pub fn throughput(&self, previous: &Snapshot, elapsed: Duration) -> f64 {
+        #[allow(clippy::cast_precision_loss)]
+        let bytes = (self.bytes - previous.bytes) as f64;
+        bytes / elapsed.as_secs_f64()
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "connections: {} active: {} rejected: {} crashed: {} bytes: {}",
+            self.connections, self.active, self.rejected, self.crashed, self.bytes
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics() {
+        let registry = Registry::new();
+        let metrics = Metrics::register(&registry);
+        metrics.open();
+        metrics.open();
+        metrics.open();
+        metrics.close();
+        metrics.crash();
+        metrics.reject();
+        metrics.echoed(100);
+        metrics.echoed(50);
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(
+            Snapshot {
+                connections: 3,
+                active: 1,
+                rejected: 1,
+                crashed: 1,
+                bytes: 150,
+            },
+            snapshot
+        );
+        assert_eq!(
+            "connections_total: 3 connections_active: 1 connections_rejected_total: 1 connections_crashed_total: 1 echoed_bytes_total: 150",
+            registry.snapshot().to_string()
+        );
+
+        #[allow(clippy::float_cmp)]
+        {
+            assert_eq!(
+                75.0,
+                snapshot.throughput(&Snapshot::default(), Duration::from_secs(2))
+            );
+        }
+    }
+}
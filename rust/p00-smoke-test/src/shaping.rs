@@ -0,0 +1,76 @@
+//! Token bucket traffic shaping, on a [`Bucket`] of the rate limiter.
+//!
+//! The bucket fills at `rate` bytes per second up to `burst` bytes,
+//! every echo reserves its bytes in advance and waits until the
+//! bucket is back to zero: with a `burst` of zero the echo is paced
+//! exactly at `rate`.
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use protohackers_ratelimit::{Bucket, Quota};
+
+#[derive(Debug)]
+pub struct TokenBucket {
+    bucket: Mutex<Bucket>,
+}
+
+impl TokenBucket {
+    /// A full bucket of `burst` bytes, filled at `rate` bytes per
+    /// second.
+    #[must_use]
+    pub fn new(rate: u64, burst: u64) -> Self {
+        let quota = Quota::per_second(rate).with_burst(burst);
+        Self {
+            bucket: Mutex::new(Bucket::new(quota, Instant::now())),
+        }
+    }
+
+    /// Reserve `bytes`, returning the time to wait before sending
+    /// them.
+    ///
+    /// # Panics
+    /// * Panics when the lock is poisoned.
+    pub fn reserve(&self, bytes: usize) -> Duration {
+        self.reserve_at(bytes, Instant::now())
+    }
+
+    fn reserve_at(&self, bytes: usize, now: Instant) -> Duration {
+        let bytes = u64::try_from(bytes).unwrap_or(u64::MAX);
+        self.bucket.lock().unwrap().reserve_at(bytes, now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_burst() {
+        let bucket = TokenBucket::new(1_000, 3_000);
+        let now = Instant::now();
+
+        assert_eq!(Duration::ZERO, bucket.reserve_at(2_000, now));
+        assert_eq!(Duration::ZERO, bucket.reserve_at(1_000, now));
+        assert_eq!(Duration::from_millis(500), bucket.reserve_at(500, now));
+
+        // refilled up to the burst
+        let later = now + Duration::from_secs(10);
+        assert_eq!(Duration::ZERO, bucket.reserve_at(3_000, later));
+        assert_eq!(Duration::from_secs(1), bucket.reserve_at(1_000, later));
+    }
+
+    #[test]
+    fn test_pacing() {
+        let bucket = TokenBucket::new(10_000, 0);
+        let now = Instant::now();
+
+        assert_eq!(Duration::from_millis(100), bucket.reserve_at(1_000, now));
+        assert_eq!(Duration::from_millis(200), bucket.reserve_at(1_000, now));
+        assert_eq!(
+            Duration::from_millis(100),
+            bucket.reserve_at(1_000, now + Duration::from_millis(200))
+        );
+    }
+}
@@ -0,0 +1,97 @@
+//! The Linux zero copy echo: the received bytes are moved from the
+//! socket to a pipe and from the pipe back to the socket with
+//! `splice(2)`, without ever reaching the user space.
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::ptr;
+
+use tokio::io::Interest;
+use tokio::net::TcpStream;
+
+use crate::Metrics;
+
+/// The default capacity of a Linux pipe.
+pub(crate) const PIPE_CAPACITY: usize = 64 * 1024;
+
+struct Pipe {
+    read: OwnedFd,
+    write: OwnedFd,
+}
+
+impl Pipe {
+    fn new() -> io::Result<Self> {
+        let mut fds = [0; 2];
+
+        // SAFETY: `fds` has room for the two descriptors
+        if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_NONBLOCK | libc::O_CLOEXEC) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // SAFETY: the descriptors were just opened and nobody else
+        // owns them
+        Ok(unsafe {
+            Self {
+                read: OwnedFd::from_raw_fd(fds[0]),
+                write: OwnedFd::from_raw_fd(fds[1]),
+            }
+        })
+    }
+}
+
+fn splice(from: RawFd, to: RawFd, len: usize) -> io::Result<usize> {
+    // SAFETY: both descriptors are open, the offsets are null as
+    // sockets and pipes have none
+    let n = unsafe {
+        libc::splice(
+            from,
+            ptr::null_mut(),
+            to,
+            ptr::null_mut(),
+            len,
+            libc::SPLICE_F_MOVE | libc::SPLICE_F_NONBLOCK,
+        )
+    };
+
+    usize::try_from(n).map_err(|_| io::Error::last_os_error())
+}
+
+/// Echo until the end of file or `max_bytes`, moving at most
+/// `chunk_len` bytes at a time, returning the number of bytes.
+pub(crate) async fn echo(
+    stream: &TcpStream,
+    max_bytes: u64,
+    chunk_len: usize,
+    metrics: &Metrics,
+) -> io::Result<u64> {
+    let pipe = Pipe::new()?;
+    let chunk_len = chunk_len.clamp(1, PIPE_CAPACITY);
+
+    let mut echoed = 0;
+    while echoed < max_bytes {
+        let len = usize::try_from(max_bytes - echoed).map_or(chunk_len, |len| len.min(chunk_len));
+
+        // the pipe is empty here, so only the socket can block
+        let n = stream
+            .async_io(Interest::READABLE, || {
+                splice(stream.as_raw_fd(), pipe.write.as_raw_fd(), len)
+            })
+            .await?;
+        if n == 0 {
+            return Ok(echoed);
+        }
+
+        let mut pending = n;
+        while pending > 0 {
+            pending -= stream
+                .async_io(Interest::WRITABLE, || {
+                    splice(pipe.read.as_raw_fd(), stream.as_raw_fd(), pending)
+                })
+                .await?;
+        }
+
+        metrics.echoed(n);
+        echoed += n as u64;
+    }
+
+    Ok(echoed)
+}
@@ -0,0 +1,72 @@
+//! TLS echo: the plaintext is echoed back over the encrypted channel.
+use std::path::Path;
+use std::time::Instant;
+
+use tracing::debug;
+
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+
+use protohackers_server::IdleTimeout;
+use protohackers_tls::TlsAcceptor;
+
+pub use protohackers_tls::Error;
+
+use crate::Config;
+
+/// Build an acceptor from the PEM certificate chain and private key.
+///
+/// # Errors
+/// * Error when the files cannot be read or do not contain a valid
+///   certificate chain and private key.
+pub fn acceptor(cert: &Path, key: &Path) -> Result<TlsAcceptor, Error> {
+    protohackers_tls::acceptor(cert, key, None)
+}
+
+/// A simple echo over TLS.
+///
+/// # Errors
+/// * Error when the handshake fails or the under socket returns an
+///   error.
+pub async fn echo(
+    stream: IdleTimeout<TcpStream>,
+    acceptor: TlsAcceptor,
+) -> Result<(), anyhow::Error> {
+    echo_with_config(stream, acceptor, Config::default()).await
+}
+
+/// A simple echo over TLS, within the caps of the given
+/// configuration. The handshake counts toward `max_duration`.
+///
+/// # Errors
+/// * Error when the handshake fails or the under socket returns an
+///   error.
+#[tracing::instrument(skip(stream, acceptor))]
+pub async fn echo_with_config(
+    stream: IdleTimeout<TcpStream>,
+    acceptor: TlsAcceptor,
+    config: Config,
+) -> Result<(), anyhow::Error> {
+    debug!("start");
+
+    let start = Instant::now();
+
+    let handshake = protohackers_tls::wrap_listener(&acceptor, stream);
+    let mut stream = if let Some(max_duration) = config.max_duration {
+        tokio::time::timeout(max_duration, handshake).await??
+    } else {
+        handshake.await?
+    };
+
+    let max_duration = config
+        .max_duration
+        .map(|max_duration| max_duration.saturating_sub(start.elapsed()));
+    let echoed = super::with_max_duration(max_duration, super::copy(&mut stream, &config)).await?;
+
+    stream.flush().await?;
+    stream.shutdown().await?;
+
+    debug!("end: {echoed:?} bytes");
+
+    Ok(())
+}
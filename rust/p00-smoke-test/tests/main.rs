@@ -1,9 +1,16 @@
-use std::sync::Once;
+use std::sync::{Arc, Once};
+use std::time::{Duration, Instant};
 
 use tracing::info;
 
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::{TcpListener, TcpStream};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+
+use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName};
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+use p00_smoke_test::{metrics, Config, Metrics, TokenBucket};
 
 #[tokio::test]
 async fn simple_echo() {
@@ -39,9 +46,346 @@ async fn simple_echo() {
     assert_eq!(payload, &buffer[0..end]);
 }
 
+#[tokio::test]
+async fn large_echo() {
+    let (address, port) = spawn_app().await;
+
+    let mut stream = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+    let (mut read_half, mut write_half) = stream.split();
+
+    let payload = (0..4 * 1024 * 1024)
+        .map(|i: u32| i.to_le_bytes()[0] ^ i.to_le_bytes()[1])
+        .collect::<Vec<_>>();
+
+    let write = async {
+        write_half.write_all(&payload).await.unwrap();
+        write_half.shutdown().await.unwrap();
+    };
+
+    let mut buffer = vec![];
+    let read = read_half.read_to_end(&mut buffer);
+
+    let ((), n) = tokio::join!(write, read);
+
+    assert_eq!(payload.len(), n.unwrap());
+    assert!(payload == buffer);
+}
+
+#[tokio::test]
+async fn tls_echo() {
+    let certificate = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+
+    let directory = std::env::temp_dir().join(format!("p00-tls-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let (cert, key) = (directory.join("cert.pem"), directory.join("key.pem"));
+    std::fs::write(&cert, certificate.serialize_pem().unwrap()).unwrap();
+    std::fs::write(&key, certificate.serialize_private_key_pem()).unwrap();
+
+    let acceptor = p00_smoke_test::tls::acceptor(&cert, &key).expect("cannot load certificate");
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    let (address, port) = spawn_tls_app(acceptor).await;
+
+    let mut roots = RootCertStore::empty();
+    roots
+        .add(CertificateDer::from(certificate.serialize_der().unwrap()))
+        .unwrap();
+    let connector = TlsConnector::from(Arc::new(
+        ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth(),
+    ));
+
+    let stream = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+    let mut stream = connector
+        .connect(ServerName::try_from("localhost").unwrap(), stream)
+        .await
+        .expect("cannot handshake");
+
+    let payload = b"ciccio cunicio";
+    stream.write_all(payload).await.unwrap();
+    stream.flush().await.unwrap();
+
+    let mut buffer = [0; 14];
+    stream.read_exact(&mut buffer).await.unwrap();
+    assert_eq!(payload, &buffer);
+
+    stream.shutdown().await.unwrap();
+
+    let mut rest = vec![];
+    stream.read_to_end(&mut rest).await.unwrap();
+    assert!(rest.is_empty());
+}
+
+#[test]
+fn tls_missing_key() {
+    let directory = std::env::temp_dir().join(format!("p00-tls-key-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let (cert, key) = (directory.join("cert.pem"), directory.join("key.pem"));
+    let certificate = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    std::fs::write(&cert, certificate.serialize_pem().unwrap()).unwrap();
+    std::fs::write(&key, "").unwrap();
+
+    let result = p00_smoke_test::tls::acceptor(&cert, &key);
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    assert!(matches!(
+        result,
+        Err(p00_smoke_test::tls::Error::NoPrivateKey(_))
+    ));
+}
+
+#[tokio::test]
+async fn udp_echo() {
+    let address = spawn_udp_app().await;
+
+    let alice = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let bob = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+    alice.send_to(b"ciccio", &address).await.unwrap();
+    bob.send_to(b"cunicio", &address).await.unwrap();
+    alice.send_to(&[0, 255, 10, 13], &address).await.unwrap();
+
+    let mut buffer = [0; 1024];
+
+    let (n, source) = bob.recv_from(&mut buffer).await.unwrap();
+    assert_eq!(address, source.to_string());
+    assert_eq!(b"cunicio", &buffer[..n]);
+
+    let n = alice.recv(&mut buffer).await.unwrap();
+    assert_eq!(b"ciccio", &buffer[..n]);
+    let n = alice.recv(&mut buffer).await.unwrap();
+    assert_eq!(&[0, 255, 10, 13], &buffer[..n]);
+}
+
+#[tokio::test]
+async fn max_bytes() {
+    let (address, port) = spawn_app_with_config(Config {
+        max_bytes: Some(4),
+        ..Config::default()
+    })
+    .await;
+
+    let mut stream = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+
+    stream.write_all(b"ciccio").await.unwrap();
+
+    let mut buffer = vec![];
+    stream.read_to_end(&mut buffer).await.unwrap();
+
+    assert_eq!(b"cicc", &buffer[..]);
+}
+
+#[tokio::test]
+async fn max_duration() {
+    let (address, port) = spawn_app_with_config(Config {
+        max_duration: Some(Duration::from_millis(100)),
+        ..Config::default()
+    })
+    .await;
+
+    let mut stream = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+
+    stream.write_all(b"ciccio").await.unwrap();
+
+    let mut buffer = vec![];
+    tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut buffer))
+        .await
+        .expect("not closed")
+        .unwrap();
+
+    assert_eq!(b"ciccio", &buffer[..]);
+}
+
+#[tokio::test]
+async fn max_connections() {
+    let (address, port) = spawn_app_with_config(Config {
+        max_connections: Some(1),
+        ..Config::default()
+    })
+    .await;
+
+    let mut first = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+    first.write_all(b"ciccio").await.unwrap();
+    let mut buffer = [0; 6];
+    first.read_exact(&mut buffer).await.unwrap();
+
+    // closed right after the accept
+    let mut second = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+    let mut buffer = vec![];
+    assert!(matches!(
+        second.read_to_end(&mut buffer).await,
+        Ok(0) | Err(_)
+    ));
+
+    first.shutdown().await.unwrap();
+    first.read_to_end(&mut buffer).await.unwrap();
+
+    // the permit is released when the first session ends, right
+    // after its shutdown
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let mut third = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+    third.write_all(b"cunicio").await.unwrap();
+    third.shutdown().await.unwrap();
+    let mut buffer = vec![];
+    third.read_to_end(&mut buffer).await.unwrap();
+    assert_eq!(b"cunicio", &buffer[..]);
+}
+
+#[tokio::test]
+async fn metrics() {
+    let metrics = Arc::new(Metrics::new());
+    let (address, port) = spawn_app_with_config(Config {
+        metrics: metrics.clone(),
+        ..Config::default()
+    })
+    .await;
+
+    for payload in [&b"ciccio"[..], b"cunicio"] {
+        let mut stream = TcpStream::connect(&format!("{address}:{port}"))
+            .await
+            .expect("cannot connect");
+        stream.write_all(payload).await.unwrap();
+        stream.shutdown().await.unwrap();
+        let mut buffer = vec![];
+        stream.read_to_end(&mut buffer).await.unwrap();
+    }
+
+    // the connections are closed right after the shutdown
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    assert_eq!(
+        metrics::Snapshot {
+            connections: 2,
+            active: 0,
+            rejected: 0,
+            crashed: 0,
+            bytes: 13,
+        },
+        metrics.snapshot()
+    );
+}
+
+#[tokio::test]
+async fn read_delay() {
+    let (address, port) = spawn_app_with_config(Config {
+        read_delay: Some(Duration::from_millis(200)),
+        ..Config::default()
+    })
+    .await;
+
+    let mut stream = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+
+    let start = Instant::now();
+
+    stream.write_all(b"ciccio").await.unwrap();
+    let mut buffer = [0; 6];
+    stream.read_exact(&mut buffer).await.unwrap();
+
+    assert!(start.elapsed() >= Duration::from_millis(200));
+    assert_eq!(b"ciccio", &buffer);
+}
+
+#[tokio::test]
+async fn bandwidth() {
+    let (address, port) = spawn_app_with_config(Config {
+        buffer_size: Some(1_000),
+        bandwidth: Some(10_000),
+        ..Config::default()
+    })
+    .await;
+
+    let mut stream = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+
+    let start = Instant::now();
+
+    let payload = [b'x'; 3_000];
+    stream.write_all(&payload).await.unwrap();
+    stream.shutdown().await.unwrap();
+    let mut buffer = vec![];
+    stream.read_to_end(&mut buffer).await.unwrap();
+
+    // at least two chunks of 1000 bytes are paced at 10 kB/s
+    assert!(start.elapsed() >= Duration::from_millis(200));
+    assert_eq!(&payload[..], &buffer[..]);
+}
+
+#[tokio::test]
+async fn burst() {
+    let (address, port) = spawn_app_with_config(Config {
+        buffer_size: Some(1_000),
+        bandwidth: Some(1_000),
+        burst: Some(3_000),
+        ..Config::default()
+    })
+    .await;
+
+    let mut stream = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+
+    let start = Instant::now();
+
+    let payload = [b'x'; 3_000];
+    stream.write_all(&payload).await.unwrap();
+    stream.shutdown().await.unwrap();
+    let mut buffer = vec![];
+    stream.read_to_end(&mut buffer).await.unwrap();
+
+    // within the burst, far from the 3 s at 1 kB/s
+    assert!(start.elapsed() < Duration::from_secs(2));
+    assert_eq!(&payload[..], &buffer[..]);
+}
+
+#[tokio::test]
+async fn global_shaping() {
+    let (address, port) = spawn_app_with_config(Config {
+        buffer_size: Some(1_000),
+        global_shaping: Some(Arc::new(TokenBucket::new(10_000, 0))),
+        ..Config::default()
+    })
+    .await;
+
+    let start = Instant::now();
+
+    let echo = |payload: &'static [u8]| {
+        let address = format!("{address}:{port}");
+        async move {
+            let mut stream = TcpStream::connect(address).await.expect("cannot connect");
+            stream.write_all(payload).await.unwrap();
+            stream.shutdown().await.unwrap();
+            let mut buffer = vec![];
+            stream.read_to_end(&mut buffer).await.unwrap();
+            assert_eq!(payload, &buffer[..]);
+        }
+    };
+
+    tokio::join!(echo(&[b'x'; 2_000]), echo(&[b'y'; 2_000]));
+
+    // the 4000 bytes of both the connections are paced at 10 kB/s
+    assert!(start.elapsed() >= Duration::from_millis(350));
+}
+
 async fn spawn_app() -> (String, u16) {
-    static TRACING_SUBSCRIBER_INIT: Once = Once::new();
-    TRACING_SUBSCRIBER_INIT.call_once(tracing_subscriber::fmt::init);
+    init_tracing();
 
     let address = "127.0.0.1";
 
@@ -54,7 +398,7 @@ async fn spawn_app() -> (String, u16) {
         loop {
             let (socket, _) = listener.accept().await.expect("cannot accept");
 
-            p00_smoke_test::echo(socket).await.unwrap();
+            p00_smoke_test::echo(socket.into()).await.unwrap();
         }
     });
 
@@ -62,3 +406,66 @@ async fn spawn_app() -> (String, u16) {
 
     (address.to_string(), port)
 }
+
+async fn spawn_tls_app(acceptor: TlsAcceptor) -> (String, u16) {
+    init_tracing();
+
+    let address = "127.0.0.1";
+
+    let listener = TcpListener::bind(&format!("{address}:0"))
+        .await
+        .expect("cannot bind");
+    let port = listener.local_addr().expect("cannot get local addr").port();
+
+    tokio::spawn(async move {
+        loop {
+            let (socket, _) = listener.accept().await.expect("cannot accept");
+
+            p00_smoke_test::tls::echo(socket.into(), acceptor.clone())
+                .await
+                .unwrap();
+        }
+    });
+
+    info!("spawned tls app {address}:{port}");
+
+    (address.to_string(), port)
+}
+
+async fn spawn_app_with_config(config: Config) -> (String, u16) {
+    init_tracing();
+
+    let address = "127.0.0.1";
+
+    let listener = TcpListener::bind(&format!("{address}:0"))
+        .await
+        .expect("cannot bind");
+    let port = listener.local_addr().expect("cannot get local addr").port();
+
+    info!("spawned app {address}:{port} with {config:?}");
+
+    tokio::spawn(p00_smoke_test::serve(listener, config, None));
+
+    (address.to_string(), port)
+}
+
+async fn spawn_udp_app() -> String {
+    init_tracing();
+
+    let socket = UdpSocket::bind("127.0.0.1:0").await.expect("cannot bind");
+    let address = socket
+        .local_addr()
+        .expect("cannot get local addr")
+        .to_string();
+
+    tokio::spawn(p00_smoke_test::udp_echo(socket));
+
+    info!("spawned udp app {address}");
+
+    address
+}
+
+fn init_tracing() {
+    static TRACING_SUBSCRIBER_INIT: Once = Once::new();
+    TRACING_SUBSCRIBER_INIT.call_once(tracing_subscriber::fmt::init);
+}
@@ -0,0 +1,100 @@
+//! Prime time benchmarks.
+//!
+//! Run with `cargo bench` for the default `serde_json` parser and with
+//! `cargo bench --features simd-json` for the SIMD one. Reference
+//! numbers of the `json` group, from a `x86_64` Linux box with
+//! `--quick`:
+//!
+//! | benchmark                | `serde_json` | `simd-json` |
+//! |--------------------------|-------------:|------------:|
+//! | parse in place           |       167 ns |      220 ns |
+//! | serialize                |        51 ns |       51 ns |
+//! | serialize reusing buffer |        31 ns |       31 ns |
+//!
+//! The request lines are short, so the SIMD parser setup is not
+//! amortized: the reusable output buffer is the actual gain.
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use p01_prime_time::{Registry, Request, Response};
+
+const SMALL_COMPOSITES: [u64; 4] = [4, 91, 561 * 3, 9_999];
+
+// The first primes after 2^32, 2^40 and 2^48, larger values are too
+// slow for the current trial division.
+const LARGE_PRIMES: [u64; 3] = [4_294_967_311, 1_099_511_627_791, 281_474_976_710_677];
+
+const CARMICHAEL_NUMBERS: [u64; 4] = [561, 41_041, 825_265, 321_197_185];
+
+fn is_prime(c: &mut Criterion) {
+    let mut group = c.benchmark_group("is_prime");
+    group.sample_size(10);
+
+    for (name, numbers) in [
+        ("small composites", SMALL_COMPOSITES.as_slice()),
+        ("large primes", LARGE_PRIMES.as_slice()),
+        ("carmichael numbers", CARMICHAEL_NUMBERS.as_slice()),
+    ] {
+        for number in numbers {
+            group.bench_with_input(BenchmarkId::new(name, number), number, |b, number| {
+                b.iter(|| Request::IsPrime((*number).into()).evaluate());
+            });
+        }
+    }
+
+    group.finish();
+}
+
+#[cfg(feature = "simd-json")]
+const PARSE_MUT: &str = "parse in place (simd-json)";
+
+#[cfg(not(feature = "simd-json"))]
+const PARSE_MUT: &str = "parse in place (serde_json)";
+
+fn json(c: &mut Criterion) {
+    let mut group = c.benchmark_group("json");
+
+    let line = br#"{"method":"isPrime","number":1000000000000000003,"ignored":"a field to skip"}"#;
+    group.bench_function("parse", |b| {
+        b.iter(|| Request::parse(black_box(line)).unwrap());
+    });
+
+    group.bench_function("parse float", |b| {
+        b.iter(|| Request::parse(black_box(br#"{"method":"isPrime","number":3.5}"#)).unwrap());
+    });
+
+    group.bench_function("parse malformed", |b| {
+        b.iter(|| Request::parse(black_box(br#"{"method":"isPrime","number":"3"}"#)).is_err());
+    });
+
+    let registry = Registry::new();
+    let mut buffer = line.to_vec();
+    group.bench_function(PARSE_MUT, |b| {
+        b.iter(|| {
+            buffer.copy_from_slice(line);
+            registry.parse_mut(black_box(&mut buffer)).unwrap()
+        });
+    });
+
+    let response = Response {
+        method: "isPrime",
+        prime: true,
+    };
+    group.bench_function("serialize", |b| {
+        b.iter(|| serde_json::to_vec(black_box(&response)).unwrap());
+    });
+
+    let mut output = vec![];
+    group.bench_function("serialize reusing buffer", |b| {
+        b.iter(|| {
+            output.clear();
+            serde_json::to_writer(&mut output, black_box(&response)).unwrap();
+        });
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, is_prime, json);
+criterion_main!(benches);
@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::metrics::Metrics;
+
+/// A bounded cache of the primality results, shared between all the
+/// connections. When full it is simply emptied.
+#[derive(Debug)]
+pub struct Cache {
+    capacity: usize,
+    entries: Mutex<HashMap<u64, bool>>,
+    metrics: Arc<Metrics>,
+}
+
+impl Cache {
+    #[must_use]
+    pub fn new(capacity: usize, metrics: Arc<Metrics>) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(HashMap::with_capacity(capacity)),
+            metrics,
+        }
+    }
+
+    /// Get the cached result or compute it, outside of the lock.
+    ///
+    /// # Panics
+    /// * Panics if the lock is poisoned.
+    pub fn get_or_insert_with(&self, n: u64, f: impl FnOnce(u64) -> bool) -> bool {
+        if let Some(result) = self.entries.lock().unwrap().get(&n) {
+            self.metrics.cache_hit();
+            return *result;
+        }
+
+        self.metrics.cache_miss();
+
+        let result = f(n);
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.capacity {
+            entries.clear();
+        }
+        entries.insert(n, result);
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache() {
+        let metrics = Arc::new(Metrics::new());
+        let cache = Cache::new(2, metrics.clone());
+
+        assert!(cache.get_or_insert_with(3, |_| true));
+        assert!(cache.get_or_insert_with(3, |_| unreachable!()));
+        assert!(!cache.get_or_insert_with(4, |_| false));
+        assert!(cache.get_or_insert_with(5, |_| true));
+        assert!(cache.get_or_insert_with(3, |_| true));
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(1, snapshot.cache_hits);
+        assert_eq!(4, snapshot.cache_misses);
+    }
+}
@@ -0,0 +1,102 @@
+//! The prime time flags: the answer to the malformed requests, the
+//! limits of a connection, the extra methods, the large numbers checked
+//! on the blocking pool and the cache of the results.
+use std::sync::Arc;
+use std::time::Duration;
+
+use tracing::info;
+
+use protohackers_server::ServerArgs;
+
+use crate::{
+    protocol, Cache, Config, Limits, MalformedPolicy, Method, Metrics, Offload, Registry,
+    DEFAULT_OFFLOAD_THRESHOLD,
+};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[command(flatten)]
+    pub server: ServerArgs,
+
+    #[arg(long, value_enum, default_value_t = MalformedPolicy::Disconnect)]
+    pub malformed_policy: MalformedPolicy,
+
+    /// Maximum number of requests per connection
+    #[arg(long)]
+    pub max_requests: Option<u64>,
+
+    /// Maximum number of requests per second per connection
+    #[arg(long)]
+    pub max_requests_per_second: Option<u32>,
+
+    /// Maximum lifetime of a connection, in seconds
+    #[arg(long)]
+    pub max_lifetime: Option<u64>,
+
+    /// Enable an extra method, beside the standard `isPrime`
+    #[arg(long = "method", value_enum)]
+    pub methods: Vec<Method>,
+
+    /// Maximum number of Pollard's rho iterations for a `factor` request
+    #[arg(long, default_value_t = protocol::DEFAULT_FACTOR_WORK_LIMIT)]
+    pub factor_work_limit: u64,
+
+    /// Check the numbers above this threshold on the blocking pool
+    #[arg(long, default_value_t = DEFAULT_OFFLOAD_THRESHOLD)]
+    pub offload_threshold: u64,
+
+    /// Maximum time to wait for a check on the blocking pool, in
+    /// milliseconds
+    #[arg(long)]
+    pub offload_budget: Option<u64>,
+
+    /// Cache the `isPrime` results, up to this number of entries
+    #[arg(long)]
+    pub cache_capacity: Option<usize>,
+}
+
+/// Run the server configured by `args`.
+///
+/// # Errors
+/// * Error when the server can not be started or fails.
+pub async fn run(args: Args) -> Result<(), anyhow::Error> {
+    args.server.init_tracing();
+
+    info!("start");
+
+    let metrics_registry = protohackers_metrics::Registry::new();
+    let metrics = Arc::new(Metrics::register(&metrics_registry));
+
+    let mut registry = args.methods.into_iter().fold(
+        Registry::new().with_factor_work_limit(args.factor_work_limit),
+        Registry::with_method,
+    );
+    if let Some(capacity) = args.cache_capacity {
+        registry = registry.with_cache(Arc::new(Cache::new(capacity, metrics.clone())));
+    }
+
+    let config = Config {
+        malformed_policy: args.malformed_policy,
+        limits: Limits {
+            max_requests: args.max_requests,
+            max_requests_per_second: args.max_requests_per_second,
+            max_lifetime: args.max_lifetime.map(Duration::from_secs),
+        },
+        registry,
+        offload: Offload {
+            threshold: args.offload_threshold,
+            budget: args.offload_budget.map(Duration::from_millis),
+        },
+        metrics,
+    };
+
+    args.server
+        .server_with_metrics(&metrics_registry)
+        .await?
+        .with_problem(env!("CARGO_PKG_NAME"))
+        .with_default_idle_timeout(crate::IDLE_TIMEOUT)
+        .serve(move |socket, _| crate::handler_with_config(socket, config.clone()))
+        .await?;
+
+    Ok(())
+}
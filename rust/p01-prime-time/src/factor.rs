@@ -0,0 +1,198 @@
+//! Prime factorization of 64 bit numbers with Pollard's rho.
+
+/// The small primes, also enough witnesses for a deterministic
+/// Miller-Rabin test of a 64 bit number.
+const SMALL_PRIMES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Factorization {
+    /// The factors in ascending order, the product is always the
+    /// factorized number.
+    pub factors: Vec<u64>,
+
+    /// `false` when the work limit was reached: in that case some of
+    /// the factors are composite.
+    pub complete: bool,
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn mul_mod(a: u64, b: u64, m: u64) -> u64 {
+    (u128::from(a) * u128::from(b) % u128::from(m)) as u64
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn add_mod(a: u64, b: u64, m: u64) -> u64 {
+    ((u128::from(a) + u128::from(b)) % u128::from(m)) as u64
+}
+
+fn pow_mod(mut base: u64, mut exp: u64, m: u64) -> u64 {
+    let mut result = 1;
+    base %= m;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = mul_mod(result, base, m);
+        }
+        base = mul_mod(base, base, m);
+        exp >>= 1;
+    }
+    result
+}
+
+/// Deterministic Miller-Rabin for 64 bit numbers.
+#[must_use]
+pub fn is_prime(n: u64) -> bool {
+    if n < 2 {
+        return false;
+    }
+
+    for p in SMALL_PRIMES {
+        if n.is_multiple_of(p) {
+            return n == p;
+        }
+    }
+
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+
+    'witness: for a in SMALL_PRIMES {
+        let mut x = pow_mod(a, d, n);
+        if x == 1 || x == n - 1 {
+            continue;
+        }
+        for _ in 1..s {
+            x = mul_mod(x, x, n);
+            if x == n - 1 {
+                continue 'witness;
+            }
+        }
+        return false;
+    }
+
+    true
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Find a non trivial divisor of the odd composite `n`, spending at
+/// most `work` iterations.
+fn rho(n: u64, work: &mut u64) -> Option<u64> {
+    for increment in 1_u64.. {
+        let next = |value: u64| add_mod(mul_mod(value, value, n), increment, n);
+
+        let (mut tortoise, mut hare, mut divisor) = (2, 2, 1);
+        while divisor == 1 {
+            if *work == 0 {
+                return None;
+            }
+            *work -= 1;
+
+            tortoise = next(tortoise);
+            hare = next(next(hare));
+            divisor = gcd(tortoise.abs_diff(hare), n);
+        }
+
+        if divisor != n {
+            return Some(divisor);
+        }
+    }
+
+    unreachable!()
+}
+
+/// Factorize `n`, spending at most `work_limit` Pollard's rho
+/// iterations.
+#[must_use]
+pub fn factorize(mut n: u64, work_limit: u64) -> Factorization {
+    let mut factors = vec![];
+    let mut complete = true;
+
+    if n < 2 {
+        return Factorization { factors, complete };
+    }
+
+    for p in SMALL_PRIMES {
+        while n.is_multiple_of(p) {
+            factors.push(p);
+            n /= p;
+        }
+    }
+
+    let mut work = work_limit;
+    let mut pending = vec![n];
+    while let Some(n) = pending.pop() {
+        if n == 1 {
+            continue;
+        }
+
+        if is_prime(n) {
+            factors.push(n);
+        } else if let Some(divisor) = rho(n, &mut work) {
+            pending.push(divisor);
+            pending.push(n / divisor);
+        } else {
+            complete = false;
+            factors.push(n);
+        }
+    }
+
+    factors.sort_unstable();
+
+    Factorization { factors, complete }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_prime() {
+        let expected = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47];
+        assert_eq!(
+            expected.as_slice(),
+            (0..50).filter(|n| is_prime(*n)).collect::<Vec<_>>()
+        );
+
+        assert!(is_prime(18_446_744_073_709_551_557));
+        assert!(!is_prime(561));
+        assert!(!is_prime(3_215_031_751));
+    }
+
+    #[test]
+    fn test_factorize_small() {
+        assert_eq!(
+            Factorization {
+                factors: vec![2, 2, 3, 5],
+                complete: true
+            },
+            factorize(60, 1_000)
+        );
+        assert_eq!(
+            Factorization {
+                factors: vec![],
+                complete: true
+            },
+            factorize(1, 1_000)
+        );
+    }
+
+    #[test]
+    fn test_factorize_large() {
+        let Factorization { factors, complete } =
+            factorize(4_294_967_291 * 4_294_967_279, 1_000_000);
+        assert!(complete);
+        assert_eq!(vec![4_294_967_279, 4_294_967_291], factors);
+    }
+
+    #[test]
+    fn test_factorize_work_limit() {
+        let n = 4_294_967_291 * 4_294_967_279;
+        let Factorization { factors, complete } = factorize(n, 1);
+        assert!(!complete);
+        assert_eq!(vec![n], factors);
+    }
+}
@@ -58,62 +58,189 @@
 //! malformed response, and disconnect the client.
 //!
 //! Make sure you can handle at least 5 simultaneous clients.
+use std::sync::Arc;
+use std::time::Duration;
+
 use tracing::{debug, warn};
 
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::TcpStream;
+use tokio::time::{self, Instant};
+
+use protohackers_server::IdleTimeout;
+
+pub mod cache;
+pub mod cli;
+pub mod factor;
+pub mod limits;
+pub mod metrics;
+pub mod protocol;
+
+pub use cache::Cache;
+pub use limits::{LimitExceeded, Limits};
+pub use metrics::Metrics;
+pub use protocol::{
+    ErrorResponse, FactorResponse, Method, Registry, Reply, Request, RequestError, Response, Tagged,
+};
+
+/// The default idle timeout of the connections.
+pub const IDLE_TIMEOUT: Duration = Duration::from_mins(1);
 
-const METHOD: &str = "isPrime";
+/// What to do when a malformed request is received.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum MalformedPolicy {
+    /// Send back a malformed response and disconnect the client, as
+    /// requested by the spec.
+    #[default]
+    Disconnect,
 
-#[derive(serde::Serialize, serde::Deserialize)]
-pub struct Request<'a> {
-    pub method: &'a str,
-    pub number: serde_json::Number,
+    /// Send back a `{"error":...}` object and wait for another
+    /// request.
+    Error,
 }
 
-#[derive(serde::Serialize, serde::Deserialize)]
-pub struct Response<'a> {
-    pub method: &'a str,
-    pub prime: bool,
+/// When to evaluate a request on the blocking pool instead of the
+/// reactor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Offload {
+    /// The numbers above this threshold are checked on the blocking
+    /// pool, `factor` requests always are.
+    pub threshold: u64,
+
+    /// The maximum time a connection waits for an offloaded
+    /// evaluation: when exceeded the client is disconnected. The
+    /// evaluation itself can not be interrupted and runs to
+    /// completion on its blocking thread.
+    pub budget: Option<Duration>,
+}
+
+pub const DEFAULT_OFFLOAD_THRESHOLD: u64 = 1 << 32;
+
+impl Default for Offload {
+    fn default() -> Self {
+        Self {
+            threshold: DEFAULT_OFFLOAD_THRESHOLD,
+            budget: None,
+        }
+    }
+}
+
+impl Offload {
+    fn is_heavy(&self, request: &Request) -> bool {
+        match request {
+            Request::IsPrime(number) => number.as_u64().is_some_and(|n| n > self.threshold),
+            Request::Factor(_) => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    pub malformed_policy: MalformedPolicy,
+    pub limits: Limits,
+    pub registry: Registry,
+    pub offload: Offload,
+    pub metrics: Arc<Metrics>,
 }
 
 /// Check if the messages are valid and primes.
 ///
 /// # Errors
 /// * Error when socket returns an error.
+pub async fn handler(stream: IdleTimeout<TcpStream>) -> Result<(), anyhow::Error> {
+    handler_with_config(stream, Config::default()).await
+}
+
+/// Check if the messages are valid and primes, using the given
+/// configuration.
+///
+/// # Errors
+/// * Error when socket returns an error.
 #[tracing::instrument(skip(stream))]
-pub async fn handler(mut stream: TcpStream) -> Result<(), anyhow::Error> {
+pub async fn handler_with_config(
+    mut stream: IdleTimeout<TcpStream>,
+    config: Config,
+) -> Result<(), anyhow::Error> {
     debug!("start");
 
     let (read_half, mut write_half) = stream.split();
     let mut read_half = BufReader::new(read_half);
 
+    let mut usage = limits::Usage::new(config.limits);
+
     let mut buffer = vec![];
+    let mut output = vec![];
     loop {
         buffer.clear();
-        match read_half.read_until(b'\n', &mut buffer).await {
+        let read = read_half.read_until(b'\n', &mut buffer);
+        let read = if let Some(deadline) = usage.deadline() {
+            if let Ok(read) = time::timeout_at(deadline, read).await {
+                read
+            } else {
+                warn!("connection lifetime exceeded");
+                break;
+            }
+        } else {
+            read.await
+        };
+
+        match read {
             Ok(0) => break,
             Ok(n) => {
+                if let Err(err) = usage.request() {
+                    warn!("limit exceeded: {err}");
+                    break;
+                }
+
+                let start = Instant::now();
+
                 let end = if buffer[n - 1] == b'\n' { n - 1 } else { n };
-                match check(&buffer[0..end]) {
-                    Ok(is_prime) => {
-                        write_half
-                            .write_all(
-                                &serde_json::to_vec(&Response {
-                                    method: METHOD,
-                                    prime: is_prime,
-                                })
-                                .unwrap(),
-                            )
-                            .await?;
-                        write_half.write_u8(b'\n').await?;
+
+                output.clear();
+                match config.registry.parse_tagged_mut(&mut buffer[0..end]) {
+                    Ok(request) => {
+                        let reply = if config.offload.is_heavy(&request.value) {
+                            let registry = config.registry.clone();
+                            let reply = tokio::task::spawn_blocking(move || {
+                                registry.evaluate_tagged(&request)
+                            });
+
+                            if let Some(budget) = config.offload.budget {
+                                if let Ok(reply) = time::timeout(budget, reply).await {
+                                    reply?
+                                } else {
+                                    warn!("evaluation budget exceeded");
+                                    break;
+                                }
+                            } else {
+                                reply.await?
+                            }
+                        } else {
+                            config.registry.evaluate_tagged(&request)
+                        };
+
+                        serde_json::to_writer(&mut output, &reply)?;
                     }
                     Err(err) => {
-                        warn!("invalid request");
-                        write_half.write_all(err.as_bytes()).await?;
-                        break;
+                        warn!("invalid request: {err}");
+                        config.metrics.malformed();
+                        match config.malformed_policy {
+                            MalformedPolicy::Disconnect => {
+                                write_half.write_all(b"MALFORMED").await?;
+                                config.metrics.request(start.elapsed());
+                                break;
+                            }
+                            MalformedPolicy::Error => {
+                                serde_json::to_writer(&mut output, &ErrorResponse::from(&err))?;
+                            }
+                        }
                     }
                 }
+                output.push(b'\n');
+
+                write_half.write_all(&output).await?;
+
+                config.metrics.request(start.elapsed());
             }
             Err(err) => return Err(err.into()),
         }
@@ -126,19 +253,3 @@ pub async fn handler(mut stream: TcpStream) -> Result<(), anyhow::Error> {
 
     Ok(())
 }
-
-fn check(buffer: &[u8]) -> Result<bool, &'static str> {
-    let request = serde_json::from_slice::<Request>(buffer).map_err(|_| "MALFORMED")?;
-    match (request.method, request.number.as_u64()) {
-        ("isPrime", Some(n)) => {
-            let result = primes::is_prime(n);
-            debug!("isPrime for {n}: {result}");
-            Ok(result)
-        }
-        ("isPrime", None) => {
-            debug!("isPrime for a non u64 number");
-            Ok(false)
-        }
-        _ => Err("MALFORMED"),
-    }
-}
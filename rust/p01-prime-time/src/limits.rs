@@ -0,0 +1,128 @@
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use thiserror::Error;
+
+/// Per connection limits, `None` means unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    pub max_requests: Option<u64>,
+    pub max_requests_per_second: Option<u32>,
+    pub max_lifetime: Option<Duration>,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum LimitExceeded {
+    #[error("too many requests")]
+    Requests,
+
+    #[error("too many requests per second")]
+    RequestsPerSecond,
+}
+
+/// The state of the limits of a single connection.
+#[derive(Debug)]
+pub(crate) struct Usage {
+    limits: Limits,
+    deadline: Option<Instant>,
+    requests: u64,
+    window_start: Instant,
+    window_requests: u32,
+}
+
+impl Usage {
+    pub(crate) fn new(limits: Limits) -> Self {
+        let now = Instant::now();
+        Self {
+            limits,
+            deadline: limits.max_lifetime.map(|lifetime| now + lifetime),
+            requests: 0,
+            window_start: now,
+            window_requests: 0,
+        }
+    }
+
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Account a new request.
+    pub(crate) fn request(&mut self) -> Result<(), LimitExceeded> {
+        self.requests += 1;
+        if self
+            .limits
+            .max_requests
+            .is_some_and(|max_requests| self.requests > max_requests)
+        {
+            return Err(LimitExceeded::Requests);
+        }
+
+        if let Some(max_requests_per_second) = self.limits.max_requests_per_second {
+            let now = Instant::now();
+            if now.duration_since(self.window_start) >= Duration::from_secs(1) {
+                self.window_start = now;
+                self.window_requests = 0;
+            }
+
+            self.window_requests += 1;
+            if self.window_requests > max_requests_per_second {
+                return Err(LimitExceeded::RequestsPerSecond);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unlimited() {
+        let mut usage = Usage::new(Limits::default());
+        assert_eq!(None, usage.deadline());
+        for _ in 0..1000 {
+            assert_eq!(Ok(()), usage.request());
+        }
+    }
+
+    #[test]
+    fn test_max_requests() {
+        let mut usage = Usage::new(Limits {
+            max_requests: Some(2),
+            ..Limits::default()
+        });
+        assert_eq!(Ok(()), usage.request());
+        assert_eq!(Ok(()), usage.request());
+        assert_eq!(Err(LimitExceeded::Requests), usage.request());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_max_requests_per_second() {
+        let mut usage = Usage::new(Limits {
+            max_requests_per_second: Some(2),
+            ..Limits::default()
+        });
+        assert_eq!(Ok(()), usage.request());
+        assert_eq!(Ok(()), usage.request());
+        assert_eq!(Err(LimitExceeded::RequestsPerSecond), usage.request());
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+
+        assert_eq!(Ok(()), usage.request());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_max_lifetime() {
+        let usage = Usage::new(Limits {
+            max_lifetime: Some(Duration::from_secs(10)),
+            ..Limits::default()
+        });
+        assert_eq!(
+            Some(Instant::now() + Duration::from_secs(10)),
+            usage.deadline()
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use std::fmt;
+use std::time::Duration;
+
+use protohackers_metrics::{Counter, Histogram, Registry};
+
+pub use protohackers_metrics::LATENCY_BUCKETS;
+
+/// The server metrics, shared between all the connections.
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    requests: Counter,
+    malformed: Counter,
+    cache_hits: Counter,
+    cache_misses: Counter,
+    latency: Histogram,
+}
+
+impl Metrics {
+    /// The metrics in a registry of their own.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::register(&Registry::new())
+    }
+
+    #[must_use]
+    pub fn register(registry: &Registry) -> Self {
+        Self {
+            requests: registry.counter("requests_total", "The answered requests"),
+            malformed: registry.counter("malformed_requests_total", "The malformed requests"),
+            cache_hits: registry.counter("cache_hits_total", "The isPrime results from the cache"),
+            cache_misses: registry.counter(
+                "cache_misses_total",
+                "The isPrime results missing from the cache",
+            ),
+            latency: registry.histogram(
+                "request_latency_seconds",
+                "The time to answer a request",
+                &LATENCY_BUCKETS,
+            ),
+        }
+    }
+
+    pub fn request(&self, latency: Duration) {
+        self.requests.inc();
+        self.latency.record(latency);
+    }
+
+    pub fn malformed(&self) {
+        self.malformed.inc();
+    }
+
+    pub fn cache_hit(&self) {
+        self.cache_hits.inc();
+    }
+
+    pub fn cache_miss(&self) {
+        self.cache_misses.inc();
+    }
+
+    #[must_use]
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            requests: self.requests.get(),
+            malformed: self.malformed.get(),
+            cache_hits: self.cache_hits.get(),
+            cache_misses: self.cache_misses.get(),
+            latency: self.latency.snapshot().counts,
+        }
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub requests: u64,
+    pub malformed: u64,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    pub latency: Vec<u64>,
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "requests: {} malformed: {} cache hits: {} cache misses: {} latency:",
+            self.requests, self.malformed, self.cache_hits, self.cache_misses
+        )?;
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(&self.latency) {
+            write!(f, " <={bound:?}: {count}")?;
+        }
+        write!(
+            f,
+            " >{:?}: {}",
+            LATENCY_BUCKETS[LATENCY_BUCKETS.len() - 1],
+            self.latency[LATENCY_BUCKETS.len()]
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram() {
+        let metrics = Metrics::new();
+        metrics.request(Duration::from_micros(1));
+        metrics.request(Duration::from_micros(10));
+        metrics.request(Duration::from_micros(11));
+        metrics.request(Duration::from_secs(2));
+
+        assert_eq!(vec![2, 1, 0, 0, 0, 0, 1], metrics.snapshot().latency);
+    }
+
+    #[test]
+    fn test_metrics() {
+        let metrics = Metrics::new();
+        metrics.request(Duration::from_millis(5));
+        metrics.malformed();
+        metrics.cache_hit();
+        metrics.cache_miss();
+        metrics.cache_miss();
+
+        assert_eq!(
+            Snapshot {
+                requests: 1,
+                malformed: 1,
+                cache_hits: 1,
+                cache_misses: 2,
+                latency: vec![0, 0, 0, 1, 0, 0, 0],
+            },
+            metrics.snapshot()
+        );
+    }
+}
@@ -0,0 +1,460 @@
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use thiserror::Error;
+
+use tracing::debug;
+
+use crate::cache::Cache;
+use crate::factor::{self, Factorization};
+
+pub const METHOD: &str = "isPrime";
+
+pub const DEFAULT_FACTOR_WORK_LIMIT: u64 = 1_000_000;
+
+#[cfg(feature = "simd-json")]
+thread_local! {
+    /// The simd-json scratch buffers, reused between the requests.
+    static SIMD_JSON_BUFFERS: std::cell::RefCell<simd_json::Buffers> =
+        std::cell::RefCell::new(simd_json::Buffers::default());
+}
+
+#[derive(Error, Debug)]
+pub enum RequestError {
+    #[error("invalid json: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[cfg(feature = "simd-json")]
+    #[error("invalid json: {0}")]
+    SimdJson(#[from] simd_json::Error),
+
+    #[error("unknown method: {0}")]
+    UnknownMethod(String),
+
+    #[error("invalid number for {0}: {1}")]
+    InvalidNumber(&'static str, serde_json::Number),
+}
+
+/// The methods a server can answer to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, clap::ValueEnum)]
+pub enum Method {
+    IsPrime,
+    Factor,
+}
+
+impl Method {
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Method::IsPrime => METHOD,
+            Method::Factor => "factor",
+        }
+    }
+}
+
+/// The registry of the enabled methods, `isPrime` is always enabled.
+#[derive(Debug, Clone)]
+pub struct Registry {
+    methods: Vec<Method>,
+    factor_work_limit: u64,
+    cache: Option<Arc<Cache>>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Registry {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            methods: vec![Method::IsPrime],
+            factor_work_limit: DEFAULT_FACTOR_WORK_LIMIT,
+            cache: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_method(mut self, method: Method) -> Self {
+        if !self.methods.contains(&method) {
+            self.methods.push(method);
+        }
+        self
+    }
+
+    #[must_use]
+    pub fn with_factor_work_limit(mut self, factor_work_limit: u64) -> Self {
+        self.factor_work_limit = factor_work_limit;
+        self
+    }
+
+    /// Cache the `isPrime` results, the cache is shared by all the
+    /// clones of the registry.
+    #[must_use]
+    pub fn with_cache(mut self, cache: Arc<Cache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    #[must_use]
+    pub fn lookup(&self, name: &str) -> Option<Method> {
+        self.methods
+            .iter()
+            .find(|method| method.name() == name)
+            .copied()
+    }
+
+    /// Parse and validate a single request line, without the
+    /// trailing new line.
+    ///
+    /// # Errors
+    /// * Error when the line is not a conforming request or the
+    ///   method is not enabled.
+    pub fn parse(&self, line: &[u8]) -> Result<Request, RequestError> {
+        self.validate(serde_json::from_slice(line)?)
+            .map(|request| request.value)
+    }
+
+    /// Parse and validate a single request line, like
+    /// [`Registry::parse`].
+    ///
+    /// With the `simd-json` feature the line is parsed in place with
+    /// SIMD instructions, so the content of `line` is undefined
+    /// after the call.
+    ///
+    /// # Errors
+    /// * Error when the line is not a conforming request or the
+    ///   method is not enabled.
+    pub fn parse_mut(&self, line: &mut [u8]) -> Result<Request, RequestError> {
+        self.parse_tagged_mut(line).map(|request| request.value)
+    }
+
+    /// Parse and validate a single request line, like
+    /// [`Registry::parse_mut`], keeping the optional correlation id.
+    ///
+    /// # Errors
+    /// * Error when the line is not a conforming request or the
+    ///   method is not enabled.
+    pub fn parse_tagged_mut(&self, line: &mut [u8]) -> Result<Tagged<Request>, RequestError> {
+        #[cfg(feature = "simd-json")]
+        let request = SIMD_JSON_BUFFERS
+            .with_borrow_mut(|buffers| simd_json::serde::from_slice_with_buffers(line, buffers))?;
+
+        #[cfg(not(feature = "simd-json"))]
+        let request = serde_json::from_slice(line)?;
+
+        self.validate(request)
+    }
+
+    fn validate(
+        &self,
+        RawRequest { method, number, id }: RawRequest,
+    ) -> Result<Tagged<Request>, RequestError> {
+        let value = match self.lookup(&method) {
+            Some(Method::IsPrime) => Request::IsPrime(number),
+            Some(Method::Factor) => number
+                .as_u64()
+                .map(Request::Factor)
+                .ok_or(RequestError::InvalidNumber(Method::Factor.name(), number))?,
+            None => return Err(RequestError::UnknownMethod(method.into_owned())),
+        };
+
+        Ok(Tagged { value, id })
+    }
+
+    /// Evaluate a request, echoing back the correlation id.
+    #[must_use]
+    pub fn evaluate_tagged(&self, request: &Tagged<Request>) -> Tagged<Reply> {
+        Tagged {
+            value: self.evaluate(&request.value),
+            id: request.id.clone(),
+        }
+    }
+
+    #[must_use]
+    pub fn evaluate(&self, request: &Request) -> Reply {
+        match request {
+            Request::IsPrime(number) => {
+                let prime = if let Some(n) = number.as_u64() {
+                    let result = if let Some(cache) = &self.cache {
+                        cache.get_or_insert_with(n, primes::is_prime)
+                    } else {
+                        primes::is_prime(n)
+                    };
+                    debug!("isPrime for {n}: {result}");
+                    result
+                } else {
+                    debug!("isPrime for a non u64 number");
+                    false
+                };
+
+                Reply::IsPrime(Response {
+                    method: METHOD,
+                    prime,
+                })
+            }
+
+            Request::Factor(n) => {
+                let Factorization { factors, complete } =
+                    factor::factorize(*n, self.factor_work_limit);
+                debug!("factor for {n}: {factors:?} complete: {complete}");
+
+                Reply::Factor(FactorResponse {
+                    method: Method::Factor.name(),
+                    factors,
+                    complete,
+                })
+            }
+        }
+    }
+}
+
+/// The request as it is on the wire, before the validation.
+#[derive(serde::Deserialize)]
+struct RawRequest<'a> {
+    #[serde(borrow)]
+    method: Cow<'a, str>,
+    number: serde_json::Number,
+    id: Option<serde_json::Value>,
+}
+
+/// A request or a response with the optional correlation id sent by
+/// the client, a pipelining client can use it to match the
+/// responses. Without an id the response is exactly the one
+/// requested by the spec.
+#[derive(serde::Serialize, Debug, PartialEq)]
+pub struct Tagged<T> {
+    #[serde(flatten)]
+    pub value: T,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    IsPrime(serde_json::Number),
+
+    Factor(u64),
+}
+
+impl Request {
+    /// Parse and validate a single request line with only the
+    /// standard methods enabled.
+    ///
+    /// # Errors
+    /// * Error when the line is not a conforming request.
+    pub fn parse(line: &[u8]) -> Result<Self, RequestError> {
+        Registry::default().parse(line)
+    }
+
+    #[must_use]
+    pub fn evaluate(&self) -> Reply {
+        Registry::default().evaluate(self)
+    }
+}
+
+#[derive(serde::Serialize, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum Reply {
+    IsPrime(Response<'static>),
+
+    Factor(FactorResponse<'static>),
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+pub struct Response<'a> {
+    pub method: &'a str,
+    pub prime: bool,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+pub struct FactorResponse<'a> {
+    pub method: &'a str,
+    pub factors: Vec<u64>,
+    pub complete: bool,
+}
+
+/// The structured response sent back for a malformed request when
+/// the server is not following the spec.
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+pub struct ErrorResponse {
+    pub error: String,
+}
+
+impl From<&RequestError> for ErrorResponse {
+    fn from(err: &RequestError) -> Self {
+        Self {
+            error: err.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_is_prime() {
+        assert_eq!(
+            Request::IsPrime(3.into()),
+            Request::parse(br#"{"method":"isPrime","number":3}"#).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_ignore_field() {
+        assert_eq!(
+            Request::IsPrime(3.into()),
+            Request::parse(br#"{"number":3,"ignored":[1,2],"method":"isPrime"}"#).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_escaped_method() {
+        assert_eq!(
+            Request::IsPrime(3.into()),
+            Request::parse(br#"{"method":"is\u0050rime","number":3}"#).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_unknown_method() {
+        assert!(matches!(
+            Request::parse(br#"{"method":"isOdd","number":3}"#),
+            Err(RequestError::UnknownMethod(method)) if method == "isOdd"
+        ));
+    }
+
+    #[test]
+    fn test_parse_missing_field() {
+        assert!(matches!(
+            Request::parse(br#"{"method":"isPrime"}"#),
+            Err(RequestError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_not_a_number() {
+        assert!(matches!(
+            Request::parse(br#"{"method":"isPrime","number":"3"}"#),
+            Err(RequestError::Json(_))
+        ));
+    }
+
+    fn is_prime(number: serde_json::Number) -> bool {
+        match Request::IsPrime(number).evaluate() {
+            Reply::IsPrime(Response { prime, .. }) => prime,
+            reply @ Reply::Factor(_) => panic!("invalid reply {reply:?}"),
+        }
+    }
+
+    #[test]
+    fn test_evaluate() {
+        assert!(is_prime(7.into()));
+        assert!(!is_prime(8.into()));
+        assert!(!is_prime(serde_json::Number::from_f64(7.0).unwrap()));
+        assert!(!is_prime((-7).into()));
+    }
+
+    #[test]
+    fn test_tagged_without_id() {
+        let registry = Registry::new();
+
+        let mut line = br#"{"method":"isPrime","number":7}"#.to_vec();
+        let request = registry.parse_tagged_mut(&mut line).unwrap();
+        assert_eq!(None, request.id);
+
+        assert_eq!(
+            r#"{"method":"isPrime","prime":true}"#,
+            serde_json::to_string(&registry.evaluate_tagged(&request)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_tagged_with_id() {
+        let registry = Registry::new();
+
+        let mut line = br#"{"id":"a-1","method":"isPrime","number":8}"#.to_vec();
+        let request = registry.parse_tagged_mut(&mut line).unwrap();
+        assert_eq!(Some(serde_json::Value::from("a-1")), request.id);
+
+        assert_eq!(
+            r#"{"method":"isPrime","prime":false,"id":"a-1"}"#,
+            serde_json::to_string(&registry.evaluate_tagged(&request)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_mut_big_number() {
+        let registry = Registry::new();
+
+        let mut line = br#"{"method":"isPrime","number":123456789012345678901234567890}"#.to_vec();
+        let request = registry.parse_mut(&mut line).unwrap();
+        assert!(matches!(&request, Request::IsPrime(number) if number.as_u64().is_none()));
+
+        assert_eq!(
+            Reply::IsPrime(Response {
+                method: METHOD,
+                prime: false
+            }),
+            registry.evaluate(&request)
+        );
+    }
+
+    #[test]
+    fn test_factor_not_enabled() {
+        assert!(matches!(
+            Request::parse(br#"{"method":"factor","number":12}"#),
+            Err(RequestError::UnknownMethod(method)) if method == "factor"
+        ));
+    }
+
+    #[test]
+    fn test_factor() {
+        let registry = Registry::new().with_method(Method::Factor);
+
+        let request = registry
+            .parse(br#"{"method":"factor","number":12}"#)
+            .unwrap();
+        assert_eq!(Request::Factor(12), request);
+
+        assert_eq!(
+            r#"{"method":"factor","factors":[2,2,3],"complete":true}"#,
+            serde_json::to_string(&registry.evaluate(&request)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_cache() {
+        let metrics = Arc::new(crate::metrics::Metrics::new());
+        let registry = Registry::new().with_cache(Arc::new(Cache::new(16, metrics.clone())));
+
+        let request = Request::IsPrime(7.into());
+        for _ in 0..3 {
+            assert_eq!(
+                Reply::IsPrime(Response {
+                    method: METHOD,
+                    prime: true
+                }),
+                registry.clone().evaluate(&request)
+            );
+        }
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(2, snapshot.cache_hits);
+        assert_eq!(1, snapshot.cache_misses);
+    }
+
+    #[test]
+    fn test_factor_invalid_number() {
+        let registry = Registry::new().with_method(Method::Factor);
+
+        assert!(matches!(
+            registry.parse(br#"{"method":"factor","number":-12}"#),
+            Err(RequestError::InvalidNumber("factor", _))
+        ));
+    }
+}
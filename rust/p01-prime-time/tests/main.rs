@@ -1,9 +1,11 @@
 use std::sync::Once;
+use std::time::Duration;
 
 use tracing::info;
 
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
 use tokio::net::{TcpListener, TcpStream};
+use tokio::time;
 
 #[tokio::test]
 async fn test_invalid_number_float() {
@@ -163,7 +165,296 @@ async fn test_not_is_prime() {
     assert!(!response.prime);
 }
 
+#[tokio::test]
+async fn test_malformed_policy_error() {
+    let (address, port) = spawn_app_with_config(p01_prime_time::Config {
+        malformed_policy: p01_prime_time::MalformedPolicy::Error,
+        ..p01_prime_time::Config::default()
+    })
+    .await;
+
+    let mut stream = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+    let (read_half, mut write_half) = stream.split();
+    let mut read_half = BufReader::new(read_half);
+
+    let payload = br#"{"method":"bho","number":3}"#;
+    write_half.write_all(payload).await.unwrap();
+    write_half.write_u8(b'\n').await.unwrap();
+
+    let payload = br#"{"method":"isPrime","number":3}"#;
+    write_half.write_all(payload).await.unwrap();
+    write_half.write_u8(b'\n').await.unwrap();
+    write_half.shutdown().await.unwrap();
+
+    let mut buffer = vec![];
+    let n = read_half.read_until(b'\n', &mut buffer).await.unwrap();
+    assert!(n > 0);
+
+    let response: p01_prime_time::ErrorResponse =
+        serde_json::from_slice(&buffer[0..n - 1]).unwrap();
+    assert_eq!("unknown method: bho", response.error);
+
+    buffer.clear();
+    let n = read_half.read_until(b'\n', &mut buffer).await.unwrap();
+    assert!(n > 0);
+
+    let response: p01_prime_time::Response = serde_json::from_slice(&buffer[0..n - 1]).unwrap();
+    assert!(response.prime);
+}
+
+#[tokio::test]
+async fn test_max_requests() {
+    let (address, port) = spawn_app_with_config(p01_prime_time::Config {
+        limits: p01_prime_time::Limits {
+            max_requests: Some(1),
+            ..p01_prime_time::Limits::default()
+        },
+        ..p01_prime_time::Config::default()
+    })
+    .await;
+
+    let mut stream = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+    let (read_half, mut write_half) = stream.split();
+    let mut read_half = BufReader::new(read_half);
+
+    let payload = br#"{"method":"isPrime","number":3}"#;
+    for _ in 0..2 {
+        write_half.write_all(payload).await.unwrap();
+        write_half.write_u8(b'\n').await.unwrap();
+    }
+
+    let mut buffer = vec![];
+    let n = read_half.read_until(b'\n', &mut buffer).await.unwrap();
+    assert!(n > 0);
+
+    let response: p01_prime_time::Response = serde_json::from_slice(&buffer[0..n - 1]).unwrap();
+    assert!(response.prime);
+
+    buffer.clear();
+    let n = read_half.read_until(b'\n', &mut buffer).await.unwrap();
+    assert_eq!(0, n);
+}
+
+#[tokio::test]
+async fn test_max_lifetime() {
+    let (address, port) = spawn_app_with_config(p01_prime_time::Config {
+        limits: p01_prime_time::Limits {
+            max_lifetime: Some(Duration::from_millis(100)),
+            ..p01_prime_time::Limits::default()
+        },
+        ..p01_prime_time::Config::default()
+    })
+    .await;
+
+    let mut stream = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+
+    let mut buffer = vec![];
+    let n = time::timeout(Duration::from_secs(5), stream.read_to_end(&mut buffer))
+        .await
+        .expect("connection not closed")
+        .unwrap();
+    assert_eq!(0, n);
+}
+
+#[tokio::test]
+async fn test_factor() {
+    let (address, port) = spawn_app_with_config(p01_prime_time::Config {
+        registry: p01_prime_time::Registry::new().with_method(p01_prime_time::Method::Factor),
+        ..p01_prime_time::Config::default()
+    })
+    .await;
+
+    let mut stream = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+    let (read_half, mut write_half) = stream.split();
+    let mut read_half = BufReader::new(read_half);
+
+    let payload = br#"{"method":"factor","number":18446743979220271189}"#;
+    write_half.write_all(payload).await.unwrap();
+    write_half.write_u8(b'\n').await.unwrap();
+    write_half.shutdown().await.unwrap();
+
+    let mut buffer = vec![];
+    let n = read_half.read_until(b'\n', &mut buffer).await.unwrap();
+    assert!(n > 0);
+
+    let response: p01_prime_time::FactorResponse =
+        serde_json::from_slice(&buffer[0..n - 1]).unwrap();
+    assert_eq!("factor", response.method);
+    assert_eq!(vec![4_294_967_279, 4_294_967_291], response.factors);
+    assert!(response.complete);
+}
+
+#[tokio::test]
+async fn test_pipelined_ids() {
+    let (address, port) = spawn_app().await;
+
+    let mut stream = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+    let (read_half, mut write_half) = stream.split();
+    let mut read_half = BufReader::new(read_half);
+
+    let payload = [
+        br#"{"method":"isPrime","number":3,"id":1}"#.as_slice(),
+        br#"{"method":"isPrime","number":4,"id":"two"}"#.as_slice(),
+        br#"{"method":"isPrime","number":5}"#.as_slice(),
+    ];
+    for request in payload {
+        write_half.write_all(request).await.unwrap();
+        write_half.write_u8(b'\n').await.unwrap();
+    }
+    write_half.shutdown().await.unwrap();
+
+    let mut lines = vec![];
+    let mut buffer = vec![];
+    while read_half.read_until(b'\n', &mut buffer).await.unwrap() > 0 {
+        lines.push(String::from_utf8(buffer.clone()).unwrap());
+        buffer.clear();
+    }
+
+    assert_eq!(
+        vec![
+            "{\"method\":\"isPrime\",\"prime\":true,\"id\":1}\n",
+            "{\"method\":\"isPrime\",\"prime\":false,\"id\":\"two\"}\n",
+            "{\"method\":\"isPrime\",\"prime\":true}\n",
+        ],
+        lines
+    );
+}
+
+#[tokio::test]
+async fn test_offload() {
+    let (address, port) = spawn_app_with_config(p01_prime_time::Config {
+        offload: p01_prime_time::Offload {
+            threshold: 0,
+            budget: Some(Duration::from_secs(30)),
+        },
+        ..p01_prime_time::Config::default()
+    })
+    .await;
+
+    let mut stream = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+    let (read_half, mut write_half) = stream.split();
+    let mut read_half = BufReader::new(read_half);
+
+    let payload = br#"{"method":"isPrime","number":4294967311}"#;
+    write_half.write_all(payload).await.unwrap();
+    write_half.write_u8(b'\n').await.unwrap();
+    write_half.shutdown().await.unwrap();
+
+    let mut buffer = vec![];
+    let n = read_half.read_until(b'\n', &mut buffer).await.unwrap();
+    assert!(n > 0);
+
+    let response: p01_prime_time::Response = serde_json::from_slice(&buffer[0..n - 1]).unwrap();
+    assert!(response.prime);
+}
+
+#[tokio::test]
+async fn test_offload_budget_exceeded() {
+    let (address, port) = spawn_app_with_config(p01_prime_time::Config {
+        offload: p01_prime_time::Offload {
+            threshold: 0,
+            budget: Some(Duration::from_millis(1)),
+        },
+        ..p01_prime_time::Config::default()
+    })
+    .await;
+
+    let mut stream = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+    let (mut read_half, mut write_half) = stream.split();
+
+    let payload = br#"{"method":"isPrime","number":281474976710677}"#;
+    write_half.write_all(payload).await.unwrap();
+    write_half.write_u8(b'\n').await.unwrap();
+
+    let mut buffer = vec![];
+    let n = time::timeout(Duration::from_secs(5), read_half.read_to_end(&mut buffer))
+        .await
+        .expect("connection not closed")
+        .unwrap();
+    assert_eq!(0, n);
+}
+
+#[tokio::test]
+async fn test_metrics() {
+    let metrics = std::sync::Arc::new(p01_prime_time::Metrics::new());
+    let cache = p01_prime_time::Cache::new(16, metrics.clone());
+    let (address, port) = spawn_app_with_config(p01_prime_time::Config {
+        malformed_policy: p01_prime_time::MalformedPolicy::Error,
+        registry: p01_prime_time::Registry::new().with_cache(std::sync::Arc::new(cache)),
+        metrics: metrics.clone(),
+        ..p01_prime_time::Config::default()
+    })
+    .await;
+
+    let mut stream = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+
+    let payload = b"{\"method\":\"isPrime\",\"number\":7}\n\
+        {\"method\":\"isPrime\",\"number\":7}\n\
+        {\"method\":\"isOdd\",\"number\":7}\n";
+    stream.write_all(payload).await.unwrap();
+    stream.shutdown().await.unwrap();
+
+    let mut buffer = String::new();
+    stream.read_to_string(&mut buffer).await.unwrap();
+    assert_eq!(3, buffer.lines().count());
+
+    let snapshot = metrics.snapshot();
+    assert_eq!(3, snapshot.requests);
+    assert_eq!(1, snapshot.malformed);
+    assert_eq!(1, snapshot.cache_hits);
+    assert_eq!(1, snapshot.cache_misses);
+    assert_eq!(3, snapshot.latency.iter().sum::<u64>());
+}
+
+#[tokio::test]
+async fn test_metrics_malformed_disconnect() {
+    let metrics = std::sync::Arc::new(p01_prime_time::Metrics::new());
+    let (address, port) = spawn_app_with_config(p01_prime_time::Config {
+        metrics: metrics.clone(),
+        ..p01_prime_time::Config::default()
+    })
+    .await;
+
+    let mut stream = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+
+    let payload = b"{\"method\":\"isPrime\",\"number\":7}\n\
+        {\"method\":\"isOdd\",\"number\":7}\n\
+        {\"method\":\"isPrime\",\"number\":7}\n";
+    stream.write_all(payload).await.unwrap();
+
+    let mut buffer = String::new();
+    stream.read_to_string(&mut buffer).await.unwrap();
+    assert_eq!(2, buffer.lines().count());
+
+    let snapshot = metrics.snapshot();
+    assert_eq!(2, snapshot.requests);
+    assert_eq!(1, snapshot.malformed);
+    assert_eq!(2, snapshot.latency.iter().sum::<u64>());
+}
+
 async fn spawn_app() -> (String, u16) {
+    spawn_app_with_config(p01_prime_time::Config::default()).await
+}
+
+async fn spawn_app_with_config(config: p01_prime_time::Config) -> (String, u16) {
     static TRACING_SUBSCRIBER_INIT: Once = Once::new();
     TRACING_SUBSCRIBER_INIT.call_once(tracing_subscriber::fmt::init);
 
@@ -181,7 +472,9 @@ async fn spawn_app() -> (String, u16) {
         loop {
             let (socket, _) = listener.accept().await.expect("cannot accept");
 
-            p01_prime_time::handler(socket).await.unwrap();
+            p01_prime_time::handler_with_config(socket.into(), config.clone())
+                .await
+                .unwrap();
         }
     });
 
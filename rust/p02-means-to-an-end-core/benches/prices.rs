@@ -0,0 +1,93 @@
+//! Means to an end price store benchmarks.
+//!
+//! Run with `cargo bench -p p02-means-to-an-end-core`. Reference
+//! numbers of a query over 100k prices, from a `x86_64` Linux box
+//! with `--quick`:
+//!
+//! | range  | `BTreeMap` scan |  treap | snapshot |
+//! |--------|----------------:|-------:|---------:|
+//! | narrow |          256 ns | 133 ns |    60 ns |
+//! | wide   |          319 µs | 113 ns |    58 ns |
+//!
+//! A rebuild of the snapshot after an insert takes about 2 ms, so
+//! the snapshot only wins on the read-mostly sessions.
+use std::collections::BTreeMap;
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use p02_means_to_an_end_core::{Prices, SnapshotPrices, Store};
+
+const PRICES: i32 = 100_000;
+
+const RANGES: [(&str, i32, i32); 2] = [("narrow", 50_000, 50_100), ("wide", 0, PRICES)];
+
+fn btree_mean(prices: &BTreeMap<i32, i32>, mintime: i32, maxtime: i32) -> i32 {
+    let (count, sum) = prices
+        .range(mintime..=maxtime)
+        .fold((0_i64, 0_i64), |(count, sum), (_, price)| {
+            (count + 1, sum + i64::from(*price))
+        });
+
+    if count > 0 {
+        i32::try_from(sum / count).unwrap()
+    } else {
+        0
+    }
+}
+
+fn query(c: &mut Criterion) {
+    let mut group = c.benchmark_group("query");
+
+    let mut btree = BTreeMap::new();
+    let mut treap = Prices::new();
+    let mut snapshot = SnapshotPrices::new(1);
+    for timestamp in 0..PRICES {
+        let price = timestamp % 1_000;
+        btree.insert(timestamp, price);
+        treap.insert(timestamp, price);
+        snapshot.insert(timestamp, price).unwrap();
+    }
+
+    for (name, mintime, maxtime) in RANGES {
+        group.bench_with_input(BenchmarkId::new("btree scan", name), &name, |b, _| {
+            b.iter(|| btree_mean(&btree, black_box(mintime), black_box(maxtime)));
+        });
+
+        group.bench_with_input(BenchmarkId::new("treap", name), &name, |b, _| {
+            b.iter(|| treap.mean(black_box(mintime), black_box(maxtime)));
+        });
+
+        group.bench_with_input(BenchmarkId::new("snapshot", name), &name, |b, _| {
+            b.iter(|| {
+                snapshot
+                    .mean(black_box(mintime), black_box(maxtime))
+                    .unwrap()
+            });
+        });
+    }
+
+    group.finish();
+}
+
+fn rebuild(c: &mut Criterion) {
+    let mut group = c.benchmark_group("rebuild");
+    group.sample_size(10);
+
+    let mut snapshot = SnapshotPrices::new(1);
+    for timestamp in 0..PRICES {
+        snapshot.insert(timestamp, timestamp % 1_000).unwrap();
+    }
+
+    group.bench_function("insert and query", |b| {
+        b.iter(|| {
+            snapshot.insert(black_box(PRICES / 2), 0).unwrap();
+            snapshot.mean(0, PRICES).unwrap()
+        });
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, query, rebuild);
+criterion_main!(benches);
@@ -0,0 +1,129 @@
+//! The recent query results of a session.
+
+/// A small cache of the recent `(mintime, maxtime) -> mean` results,
+/// the oldest entry is evicted when full. An insert invalidates only
+/// the entries whose interval contains its timestamp.
+#[derive(Debug, Clone)]
+pub struct QueryCache {
+    capacity: usize,
+    entries: Vec<((i32, i32), i32)>,
+    hits: u64,
+    misses: u64,
+}
+
+impl QueryCache {
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Vec::with_capacity(capacity),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn get(&mut self, mintime: i32, maxtime: i32) -> Option<i32> {
+        let mean = self
+            .entries
+            .iter()
+            .find(|(interval, _)| *interval == (mintime, maxtime))
+            .map(|(_, mean)| *mean);
+
+        if mean.is_some() {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+        }
+
+        mean
+    }
+
+    pub fn insert(&mut self, mintime: i32, maxtime: i32, mean: i32) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.entries.len() == self.capacity {
+            self.entries.remove(0);
+        }
+        self.entries.push(((mintime, maxtime), mean));
+    }
+
+    /// Invalidate the entries affected by a price at `timestamp`.
+    pub fn invalidate(&mut self, timestamp: i32) {
+        self.entries
+            .retain(|((mintime, maxtime), _)| timestamp < *mintime || *maxtime < timestamp);
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    #[must_use]
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    #[must_use]
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get() {
+        let mut cache = QueryCache::new(2);
+        assert_eq!(None, cache.get(1, 10));
+
+        cache.insert(1, 10, 5);
+        assert_eq!(Some(5), cache.get(1, 10));
+        assert_eq!(None, cache.get(1, 11));
+
+        assert_eq!(1, cache.hits());
+        assert_eq!(2, cache.misses());
+    }
+
+    #[test]
+    fn test_evict_oldest() {
+        let mut cache = QueryCache::new(2);
+        cache.insert(1, 10, 1);
+        cache.insert(2, 10, 2);
+        cache.insert(3, 10, 3);
+
+        assert_eq!(2, cache.len());
+        assert_eq!(None, cache.get(1, 10));
+        assert_eq!(Some(2), cache.get(2, 10));
+        assert_eq!(Some(3), cache.get(3, 10));
+    }
+
+    #[test]
+    fn test_invalidate() {
+        let mut cache = QueryCache::new(4);
+        cache.insert(1, 10, 1);
+        cache.insert(5, 20, 2);
+        cache.insert(11, 20, 3);
+
+        cache.invalidate(10);
+
+        assert_eq!(None, cache.get(1, 10));
+        assert_eq!(None, cache.get(5, 20));
+        assert_eq!(Some(3), cache.get(11, 20));
+    }
+
+    #[test]
+    fn test_disabled() {
+        let mut cache = QueryCache::new(0);
+        cache.insert(1, 10, 1);
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,1206 @@
+//! Means to an end, the runtime agnostic core.
+//!
+//! The message model, the parsing, the price stores and the session
+//! loop, written against the [`protohackers_runtime`] traits: the
+//! tokio and the WASI servers only accept the connections.
+use std::io;
+use std::mem;
+
+use thiserror::Error;
+
+mod cache;
+mod prices;
+mod server;
+mod shared;
+mod snapshot;
+pub mod spill;
+
+pub use cache::QueryCache;
+pub use prices::Prices;
+pub use server::{handle_received, serve, Handler};
+pub use shared::SharedPrices;
+pub use snapshot::SnapshotPrices;
+pub use spill::{SpillConfig, SpillPrices};
+
+/// The length of every standard message sent by a client.
+pub const MESSAGE_LEN: usize = 1 + 2 * mem::size_of::<i32>();
+
+/// The type of the batch insert extension message: the type, the
+/// number of pairs as a big endian `u16` and the (timestamp, price)
+/// pairs.
+///
+/// ```raw
+/// Byte:  |  0  |  1     2  |  3 ... 6  |  7 ... 10 | ...
+/// Type:  |char |  uint16   |   int32   |   int32   | ...
+/// Value: | 'B' |   count   | timestamp |   price   | ...
+/// ```
+pub const BATCH_INSERT: u8 = b'B';
+
+/// The type of the hello message, optionally sent as the first
+/// message to negotiate the extensions: the protocol version and the
+/// bits of the requested [`Extensions`].
+///
+/// ```raw
+/// Byte:  |  0  |  1     2     3     4  |  5     6     7     8  |
+/// Type:  |char |         int32         |         int32         |
+/// Value: | 'H' |        version        |      extensions       |
+/// ```
+///
+/// The server answers with the bits of the accepted extensions as a
+/// single `int32`, 0 for an unknown version.
+pub const HELLO: u8 = b'H';
+
+/// The only protocol version with extensions.
+pub const PROTOCOL_VERSION: i32 = 1;
+
+/// A set of protocol extensions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Extensions(u32);
+
+impl Extensions {
+    pub const NONE: Self = Self(0);
+
+    /// The [`BATCH_INSERT`] messages.
+    pub const BATCH_INSERT: Self = Self(1);
+
+    /// The [`Aggregate`] queries.
+    pub const AGGREGATES: Self = Self(1 << 1);
+
+    pub const ALL: Self = Self(Self::BATCH_INSERT.0 | Self::AGGREGATES.0);
+
+    /// The extensions of `bits`, the unknown ones are dropped.
+    #[must_use]
+    pub fn from_bits(bits: u32) -> Self {
+        Self(bits & Self::ALL.0)
+    }
+
+    #[must_use]
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+
+    #[must_use]
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    #[must_use]
+    pub fn intersection(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+
+    #[must_use]
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+/// The aggregate query extensions, laid out as the standard query
+/// and answered with a single `int32`, 0 when there are no prices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregate {
+    /// The minimum price, type `m`.
+    Min,
+
+    /// The maximum price, type `M`.
+    Max,
+
+    /// The number of prices, type `C`, saturating at `i32::MAX`.
+    Count,
+}
+
+impl Aggregate {
+    /// The aggregate of a message type, if any.
+    #[must_use]
+    pub fn from_kind(kind: u8) -> Option<Self> {
+        match kind {
+            b'm' => Some(Self::Min),
+            b'M' => Some(Self::Max),
+            b'C' => Some(Self::Count),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn kind(self) -> u8 {
+        match self {
+            Self::Min => b'm',
+            Self::Max => b'M',
+            Self::Count => b'C',
+        }
+    }
+}
+
+const BATCH_HEADER_LEN: usize = 1 + mem::size_of::<u16>();
+
+const PAIR_LEN: usize = 2 * mem::size_of::<i32>();
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Insert {
+        timestamp: i32,
+        price: i32,
+    },
+
+    Query {
+        mintime: i32,
+        maxtime: i32,
+    },
+
+    /// The batch insert extension.
+    BatchInsert(Vec<(i32, i32)>),
+
+    /// The aggregate query extensions.
+    Aggregate {
+        aggregate: Aggregate,
+        mintime: i32,
+        maxtime: i32,
+    },
+
+    /// The negotiation of the extensions, with the ones both
+    /// requested by the client and offered by the decoder.
+    Hello {
+        version: i32,
+        accepted: Extensions,
+    },
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("message invalid: type {0:#04x}")]
+    MessageInvalid(u8),
+
+    #[error("duplicate timestamp: {0}")]
+    DuplicateTimestamp(i32),
+
+    #[error("too many prices: more than {0}")]
+    TooManyPrices(usize),
+
+    #[error("i/o error: {0}")]
+    Io(#[from] io::Error),
+}
+
+fn be_i32(bytes: &[u8]) -> i32 {
+    let mut value = [0; mem::size_of::<i32>()];
+    value.copy_from_slice(bytes);
+    i32::from_be_bytes(value)
+}
+
+/// Split the messages out of the received bytes, the standard 9
+/// bytes messages and the enabled extensions.
+///
+/// The extensions are either enabled from the start or negotiated by
+/// a leading [`HELLO`] message: a client not sending it gets the
+/// standard protocol.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MessageDecoder {
+    extensions: Extensions,
+
+    /// The extensions a client can request with a hello.
+    offered: Extensions,
+
+    /// A message has been decoded, a hello is too late.
+    started: bool,
+}
+
+impl MessageDecoder {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enable all the extensions from the start.
+    #[must_use]
+    pub fn with_extensions(extensions: bool) -> Self {
+        Self {
+            extensions: if extensions {
+                Extensions::ALL
+            } else {
+                Extensions::NONE
+            },
+            ..Self::default()
+        }
+    }
+
+    /// Let a client enable the `offered` extensions with a hello.
+    #[must_use]
+    pub fn with_negotiation(offered: Extensions) -> Self {
+        Self {
+            offered,
+            ..Self::default()
+        }
+    }
+
+    /// The extensions enabled, from the start or negotiated.
+    #[must_use]
+    pub fn extensions(&self) -> Extensions {
+        self.extensions
+    }
+
+    /// Decode the first message of `src`, returning it with its
+    /// length, or `None` when more bytes are needed.
+    ///
+    /// # Errors
+    /// * Error when the type is unknown.
+    pub fn decode(&mut self, src: &[u8]) -> Result<Option<(Message, usize)>, Error> {
+        let decoded = self.decode_message(src);
+        if matches!(decoded, Ok(Some(_))) {
+            self.started = true;
+        }
+        decoded
+    }
+
+    fn decode_message(&mut self, src: &[u8]) -> Result<Option<(Message, usize)>, Error> {
+        match src.first() {
+            None => Ok(None),
+
+            Some(&HELLO) if !self.started && !self.offered.is_empty() => {
+                if src.len() < MESSAGE_LEN {
+                    return Ok(None);
+                }
+
+                let version = be_i32(&src[1..=mem::size_of::<i32>()]);
+                let requested = be_i32(&src[1 + mem::size_of::<i32>()..MESSAGE_LEN]);
+                let accepted = if version == PROTOCOL_VERSION {
+                    Extensions::from_bits(requested.cast_unsigned()).intersection(self.offered)
+                } else {
+                    Extensions::NONE
+                };
+                self.extensions = accepted;
+
+                Ok(Some((Message::Hello { version, accepted }, MESSAGE_LEN)))
+            }
+
+            Some(&BATCH_INSERT) if self.extensions.contains(Extensions::BATCH_INSERT) => {
+                if src.len() < BATCH_HEADER_LEN {
+                    return Ok(None);
+                }
+
+                let count = usize::from(u16::from_be_bytes([src[1], src[2]]));
+                let len = BATCH_HEADER_LEN + count * PAIR_LEN;
+                if src.len() < len {
+                    return Ok(None);
+                }
+
+                let pairs = src[BATCH_HEADER_LEN..len]
+                    .chunks_exact(PAIR_LEN)
+                    .map(|pair| {
+                        let (timestamp, price) = pair.split_at(mem::size_of::<i32>());
+                        (be_i32(timestamp), be_i32(price))
+                    })
+                    .collect();
+
+                Ok(Some((Message::BatchInsert(pairs), len)))
+            }
+
+            Some(_) if src.len() < MESSAGE_LEN => Ok(None),
+
+            Some(_) => {
+                let mut message = [0; MESSAGE_LEN];
+                message.copy_from_slice(&src[..MESSAGE_LEN]);
+                let message = if self.extensions.contains(Extensions::AGGREGATES) {
+                    Message::parse_extension(&message)?
+                } else {
+                    Message::parse(&message)?
+                };
+                Ok(Some((message, MESSAGE_LEN)))
+            }
+        }
+    }
+}
+
+/// What to do with an insert at an already used timestamp, the spec
+/// leaves it undefined.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// The new price replaces the old one.
+    #[default]
+    Overwrite,
+
+    /// The new price is discarded.
+    Ignore,
+
+    /// The session is erroneous and must be closed.
+    Close,
+}
+
+/// What to do with an insert beyond the price limit of a session.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LimitPolicy {
+    /// The price with the lowest timestamp is removed.
+    #[default]
+    EvictOldest,
+
+    /// The session is closed.
+    Close,
+}
+
+/// The maximum number of prices of a session, so that a client can
+/// not exhaust the memory of the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PriceLimit {
+    pub max_prices: usize,
+    pub policy: LimitPolicy,
+}
+
+impl Message {
+    /// Parse a single message.
+    ///
+    /// # Errors
+    /// * Error when the type is neither `I` nor `Q`.
+    pub fn parse(message: &[u8; MESSAGE_LEN]) -> Result<Self, Error> {
+        match (
+            message[0],
+            be_i32(&message[1..=mem::size_of::<i32>()]),
+            be_i32(&message[1 + mem::size_of::<i32>()..]),
+        ) {
+            (b'I', timestamp, price) => Ok(Message::Insert { timestamp, price }),
+            (b'Q', mintime, maxtime) => Ok(Message::Query { mintime, maxtime }),
+            (kind, _, _) => Err(Error::MessageInvalid(kind)),
+        }
+    }
+
+    /// Parse a single message, the standard ones and the aggregate
+    /// query extensions.
+    ///
+    /// # Errors
+    /// * Error when the type is unknown.
+    pub fn parse_extension(message: &[u8; MESSAGE_LEN]) -> Result<Self, Error> {
+        if let Some(aggregate) = Aggregate::from_kind(message[0]) {
+            Ok(Message::Aggregate {
+                aggregate,
+                mintime: be_i32(&message[1..=mem::size_of::<i32>()]),
+                maxtime: be_i32(&message[1 + mem::size_of::<i32>()..]),
+            })
+        } else {
+            Self::parse(message)
+        }
+    }
+
+    /// Append the message to `dst` as a client sends it, a hello with
+    /// its accepted extensions as the requested ones.
+    ///
+    /// # Panics
+    /// * When a batch has more than `u16::MAX` pairs.
+    pub fn write_to(&self, dst: &mut Vec<u8>) {
+        let (kind, first, second) = match *self {
+            Message::Insert { timestamp, price } => (b'I', timestamp, price),
+            Message::Query { mintime, maxtime } => (b'Q', mintime, maxtime),
+            Message::Aggregate {
+                aggregate,
+                mintime,
+                maxtime,
+            } => (aggregate.kind(), mintime, maxtime),
+            Message::Hello { version, accepted } => (HELLO, version, accepted.bits().cast_signed()),
+            Message::BatchInsert(ref pairs) => {
+                let count = u16::try_from(pairs.len()).expect("too many pairs in the batch");
+                dst.push(BATCH_INSERT);
+                dst.extend_from_slice(&count.to_be_bytes());
+                for (timestamp, price) in pairs {
+                    dst.extend_from_slice(&timestamp.to_be_bytes());
+                    dst.extend_from_slice(&price.to_be_bytes());
+                }
+                return;
+            }
+        };
+
+        dst.push(kind);
+        dst.extend_from_slice(&first.to_be_bytes());
+        dst.extend_from_slice(&second.to_be_bytes());
+    }
+}
+
+/// The mean of `count` prices summing to `sum`, 0 when empty,
+/// rounded towards zero.
+///
+/// The mean of valid prices always fits in an `i32`, but a store
+/// returning inconsistent totals gets the mean clamped to the `i32`
+/// range rather than wrapped.
+fn mean(count: u64, sum: i128) -> i32 {
+    if count == 0 {
+        return 0;
+    }
+
+    let mean = sum / i128::from(count);
+    i32::try_from(mean).unwrap_or(if mean < 0 { i32::MIN } else { i32::MAX })
+}
+
+/// Where the prices of a session are kept.
+pub trait Store {
+    /// The price at `timestamp`, if any.
+    ///
+    /// # Errors
+    /// * Error when the store can not be read.
+    fn get(&self, timestamp: i32) -> io::Result<Option<i32>>;
+
+    /// Insert a price, overwriting the one at the same timestamp.
+    ///
+    /// # Errors
+    /// * Error when the store can not be written.
+    fn insert(&mut self, timestamp: i32, price: i32) -> io::Result<()>;
+
+    /// The count and the sum of the prices in the closed interval
+    /// `[mintime, maxtime]`.
+    ///
+    /// # Errors
+    /// * Error when the store can not be read.
+    fn totals(&self, mintime: i32, maxtime: i32) -> io::Result<(u64, i128)>;
+
+    /// The minimum and the maximum price in the closed interval
+    /// `[mintime, maxtime]`, `None` when empty.
+    ///
+    /// # Errors
+    /// * Error when the store can not be read.
+    fn extremes(&self, mintime: i32, maxtime: i32) -> io::Result<Option<(i32, i32)>>;
+
+    /// Remove the price with the lowest timestamp.
+    ///
+    /// # Errors
+    /// * Error when the store can not be written.
+    fn pop_first(&mut self) -> io::Result<Option<(i32, i32)>>;
+
+    /// The number of prices.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The mean of the prices in the closed interval `[mintime,
+    /// maxtime]`, 0 when empty, rounded towards zero.
+    ///
+    /// # Errors
+    /// * Error when the store can not be read.
+    fn mean(&self, mintime: i32, maxtime: i32) -> io::Result<i32> {
+        self.totals(mintime, maxtime)
+            .map(|(count, sum)| mean(count, sum))
+    }
+}
+
+impl Store for Prices {
+    fn get(&self, timestamp: i32) -> io::Result<Option<i32>> {
+        Ok(Prices::get(self, timestamp))
+    }
+
+    fn insert(&mut self, timestamp: i32, price: i32) -> io::Result<()> {
+        Prices::insert(self, timestamp, price);
+        Ok(())
+    }
+
+    fn totals(&self, mintime: i32, maxtime: i32) -> io::Result<(u64, i128)> {
+        Ok(Prices::totals(self, mintime, maxtime))
+    }
+
+    fn extremes(&self, mintime: i32, maxtime: i32) -> io::Result<Option<(i32, i32)>> {
+        Ok(Prices::extremes(self, mintime, maxtime))
+    }
+
+    fn pop_first(&mut self) -> io::Result<Option<(i32, i32)>> {
+        Ok(Prices::pop_first(self))
+    }
+
+    fn len(&self) -> usize {
+        Prices::len(self)
+    }
+}
+
+/// The counters of a session.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// The prices received, also the ones of the batch inserts.
+    pub inserts: u64,
+
+    pub queries: u64,
+
+    /// The queries with `mintime` after `maxtime`.
+    pub invalid_range_queries: u64,
+
+    /// The prices removed to stay within the price limit.
+    pub evicted: u64,
+}
+
+/// The state of a client session.
+#[derive(Debug, Default)]
+pub struct Session<S = Prices> {
+    prices: S,
+    duplicate_policy: DuplicatePolicy,
+    query_cache: Option<QueryCache>,
+    price_limit: Option<PriceLimit>,
+    stats: Stats,
+}
+
+impl Session {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with_duplicate_policy(duplicate_policy: DuplicatePolicy) -> Self {
+        Self::with_store(Prices::new(), duplicate_policy)
+    }
+}
+
+impl<S: Store> Session<S> {
+    #[must_use]
+    pub fn with_store(prices: S, duplicate_policy: DuplicatePolicy) -> Self {
+        Self {
+            prices,
+            duplicate_policy,
+            query_cache: None,
+            price_limit: None,
+            stats: Stats::default(),
+        }
+    }
+
+    /// Cache up to `capacity` recent query results.
+    #[must_use]
+    pub fn with_query_cache(mut self, capacity: usize) -> Self {
+        self.query_cache = Some(QueryCache::new(capacity));
+        self
+    }
+
+    /// Limit the number of prices, with a shared store the limit is
+    /// on all the prices of the store.
+    #[must_use]
+    pub fn with_price_limit(mut self, price_limit: PriceLimit) -> Self {
+        self.price_limit = Some(price_limit);
+        self
+    }
+
+    #[must_use]
+    pub fn query_cache(&self) -> Option<&QueryCache> {
+        self.query_cache.as_ref()
+    }
+
+    #[must_use]
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    /// The number of prices in the store.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.prices.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.prices.is_empty()
+    }
+
+    /// Handle a message, returning the response to send back if any.
+    ///
+    /// # Errors
+    /// * Error on a duplicate timestamp with the
+    ///   [`DuplicatePolicy::Close`] policy, on a price beyond the
+    ///   limit with the [`LimitPolicy::Close`] policy, or when the
+    ///   store fails: the session must be closed.
+    pub fn handle(&mut self, message: Message) -> Result<Option<i32>, Error> {
+        match message {
+            Message::Insert { timestamp, price } => {
+                self.insert(timestamp, price)?;
+                Ok(None)
+            }
+
+            Message::BatchInsert(pairs) => {
+                for (timestamp, price) in pairs {
+                    self.insert(timestamp, price)?;
+                }
+                Ok(None)
+            }
+
+            Message::Query { mintime, maxtime } => self.query(mintime, maxtime).map(Some),
+
+            Message::Aggregate {
+                aggregate,
+                mintime,
+                maxtime,
+            } => self.aggregate(aggregate, mintime, maxtime).map(Some),
+
+            Message::Hello { accepted, .. } => Ok(Some(accepted.bits().cast_signed())),
+        }
+    }
+
+    fn count_query(&mut self, mintime: i32, maxtime: i32) {
+        self.stats.queries += 1;
+        if maxtime < mintime {
+            self.stats.invalid_range_queries += 1;
+        }
+    }
+
+    fn aggregate(
+        &mut self,
+        aggregate: Aggregate,
+        mintime: i32,
+        maxtime: i32,
+    ) -> Result<i32, Error> {
+        self.count_query(mintime, maxtime);
+
+        Ok(match aggregate {
+            Aggregate::Min => self
+                .prices
+                .extremes(mintime, maxtime)?
+                .map_or(0, |(min, _)| min),
+            Aggregate::Max => self
+                .prices
+                .extremes(mintime, maxtime)?
+                .map_or(0, |(_, max)| max),
+            Aggregate::Count => {
+                let (count, _) = self.prices.totals(mintime, maxtime)?;
+                i32::try_from(count).unwrap_or(i32::MAX)
+            }
+        })
+    }
+
+    fn query(&mut self, mintime: i32, maxtime: i32) -> Result<i32, Error> {
+        self.count_query(mintime, maxtime);
+
+        if let Some(mean) = self
+            .query_cache
+            .as_mut()
+            .and_then(|cache| cache.get(mintime, maxtime))
+        {
+            return Ok(mean);
+        }
+
+        let mean = self.prices.mean(mintime, maxtime)?;
+
+        if let Some(cache) = &mut self.query_cache {
+            cache.insert(mintime, maxtime, mean);
+        }
+
+        Ok(mean)
+    }
+
+    fn insert(&mut self, timestamp: i32, price: i32) -> Result<(), Error> {
+        self.stats.inserts += 1;
+
+        if let Some(PriceLimit { max_prices, policy }) = self.price_limit {
+            if self.prices.len() >= max_prices && self.prices.get(timestamp)?.is_none() {
+                match policy {
+                    LimitPolicy::EvictOldest => {
+                        if let Some((evicted, _)) = self.prices.pop_first()? {
+                            self.stats.evicted += 1;
+                            if let Some(cache) = &mut self.query_cache {
+                                cache.invalidate(evicted);
+                            }
+                        }
+                    }
+                    LimitPolicy::Close => return Err(Error::TooManyPrices(max_prices)),
+                }
+            }
+        }
+
+        match self.duplicate_policy {
+            DuplicatePolicy::Overwrite => self.prices.insert(timestamp, price)?,
+            _ if self.prices.get(timestamp)?.is_none() => {
+                self.prices.insert(timestamp, price)?;
+            }
+            DuplicatePolicy::Ignore => return Ok(()),
+            DuplicatePolicy::Close => return Err(Error::DuplicateTimestamp(timestamp)),
+        }
+
+        if let Some(cache) = &mut self.query_cache {
+            cache.invalidate(timestamp);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mean() {
+        assert_eq!(0, mean(0, 0));
+        assert_eq!(-2, mean(2, -5));
+        assert_eq!(
+            i32::MIN,
+            mean(u64::MAX, i128::from(u64::MAX) * i128::from(i32::MIN))
+        );
+        assert_eq!(
+            i32::MAX,
+            mean(u64::MAX, i128::from(u64::MAX) * i128::from(i32::MAX))
+        );
+
+        // inconsistent totals are clamped
+        assert_eq!(i32::MAX, mean(1, i128::MAX));
+        assert_eq!(i32::MIN, mean(1, i128::MIN));
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Message::Insert {
+                timestamp: 12345,
+                price: 101
+            },
+            Message::parse(&[0x49, 0x00, 0x00, 0x30, 0x39, 0x00, 0x00, 0x00, 0x65]).unwrap()
+        );
+        assert_eq!(
+            Message::Query {
+                mintime: 1000,
+                maxtime: 100_000
+            },
+            Message::parse(&[0x51, 0x00, 0x00, 0x03, 0xe8, 0x00, 0x01, 0x86, 0xa0]).unwrap()
+        );
+        assert_eq!(
+            Message::Insert {
+                timestamp: -1,
+                price: i32::MIN
+            },
+            Message::parse(&[0x49, 0xff, 0xff, 0xff, 0xff, 0x80, 0x00, 0x00, 0x00]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(matches!(
+            Message::parse(&[b'X', 0, 0, 0, 0, 0, 0, 0, 0]),
+            Err(Error::MessageInvalid(b'X'))
+        ));
+    }
+
+    #[test]
+    fn test_decode_standard() {
+        let mut decoder = MessageDecoder::new();
+
+        let src = [0x49, 0x00, 0x00, 0x30, 0x39, 0x00, 0x00, 0x00, 0x65, 0x51];
+        assert_eq!(None, decoder.decode(&src[..8]).unwrap());
+        assert_eq!(
+            Some((
+                Message::Insert {
+                    timestamp: 12345,
+                    price: 101
+                },
+                MESSAGE_LEN
+            )),
+            decoder.decode(&src).unwrap()
+        );
+
+        assert!(matches!(
+            decoder.decode(&[BATCH_INSERT, 0, 0, 0, 0, 0, 0, 0, 0]),
+            Err(Error::MessageInvalid(BATCH_INSERT))
+        ));
+    }
+
+    #[test]
+    fn test_decode_batch_insert() {
+        let mut decoder = MessageDecoder::with_extensions(true);
+
+        let src = [
+            BATCH_INSERT,
+            0x00,
+            0x02,
+            0x00,
+            0x00,
+            0x30,
+            0x39,
+            0x00,
+            0x00,
+            0x00,
+            0x65,
+            0x00,
+            0x00,
+            0x30,
+            0x3a,
+            0xff,
+            0xff,
+            0xff,
+            0xff,
+        ];
+        assert_eq!(None, decoder.decode(&src[..2]).unwrap());
+        assert_eq!(None, decoder.decode(&src[..18]).unwrap());
+        assert_eq!(
+            Some((Message::BatchInsert(vec![(12345, 101), (12346, -1)]), 19)),
+            decoder.decode(&src).unwrap()
+        );
+
+        assert_eq!(
+            Some((Message::BatchInsert(vec![]), 3)),
+            decoder.decode(&[BATCH_INSERT, 0, 0]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_session_batch_insert() {
+        let mut session = Session::new();
+        assert_eq!(
+            None,
+            session
+                .handle(Message::BatchInsert(vec![
+                    (12345, 101),
+                    (12346, 102),
+                    (12347, 100),
+                    (40960, 5)
+                ]))
+                .unwrap()
+        );
+
+        assert_eq!(
+            Some(101),
+            session
+                .handle(Message::Query {
+                    mintime: 12288,
+                    maxtime: 16384
+                })
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_decode_aggregate() {
+        let src = [b'm', 0x00, 0x00, 0x03, 0xe8, 0x00, 0x01, 0x86, 0xa0];
+
+        assert_eq!(
+            Some((
+                Message::Aggregate {
+                    aggregate: Aggregate::Min,
+                    mintime: 1000,
+                    maxtime: 100_000
+                },
+                MESSAGE_LEN
+            )),
+            MessageDecoder::with_extensions(true).decode(&src).unwrap()
+        );
+
+        assert!(matches!(
+            MessageDecoder::new().decode(&src),
+            Err(Error::MessageInvalid(b'm'))
+        ));
+
+        for aggregate in [Aggregate::Min, Aggregate::Max, Aggregate::Count] {
+            assert_eq!(Some(aggregate), Aggregate::from_kind(aggregate.kind()));
+        }
+        assert_eq!(None, Aggregate::from_kind(b'Q'));
+    }
+
+    #[test]
+    fn test_decode_hello() {
+        let hello = [HELLO, 0, 0, 0, 1, 0, 0, 0, 0x03];
+        let batch_insert = [BATCH_INSERT, 0, 0];
+        let min = [b'm', 0, 0, 0, 0, 0, 0, 0, 0];
+
+        let mut decoder = MessageDecoder::with_negotiation(Extensions::BATCH_INSERT);
+        assert_eq!(None, decoder.decode(&hello[..8]).unwrap());
+        assert_eq!(
+            Some((
+                Message::Hello {
+                    version: PROTOCOL_VERSION,
+                    accepted: Extensions::BATCH_INSERT
+                },
+                MESSAGE_LEN
+            )),
+            decoder.decode(&hello).unwrap()
+        );
+        assert_eq!(Extensions::BATCH_INSERT, decoder.extensions());
+        assert_eq!(
+            Some((Message::BatchInsert(vec![]), 3)),
+            decoder.decode(&batch_insert).unwrap()
+        );
+        assert!(matches!(
+            decoder.decode(&min),
+            Err(Error::MessageInvalid(b'm'))
+        ));
+        assert!(matches!(
+            decoder.decode(&hello),
+            Err(Error::MessageInvalid(HELLO))
+        ));
+    }
+
+    #[test]
+    fn test_decode_hello_not_first() {
+        let mut decoder = MessageDecoder::with_negotiation(Extensions::ALL);
+        decoder
+            .decode(&[b'Q', 0, 0, 0, 0, 0, 0, 0, 0])
+            .unwrap()
+            .unwrap();
+        assert!(matches!(
+            decoder.decode(&[HELLO, 0, 0, 0, 1, 0, 0, 0, 0x03]),
+            Err(Error::MessageInvalid(HELLO))
+        ));
+        assert!(matches!(
+            decoder.decode(&[BATCH_INSERT, 0, 0, 0, 0, 0, 0, 0, 0]),
+            Err(Error::MessageInvalid(BATCH_INSERT))
+        ));
+    }
+
+    #[test]
+    fn test_decode_hello_unknown_version() {
+        let mut decoder = MessageDecoder::with_negotiation(Extensions::ALL);
+        assert_eq!(
+            Some((
+                Message::Hello {
+                    version: 2,
+                    accepted: Extensions::NONE
+                },
+                MESSAGE_LEN
+            )),
+            decoder.decode(&[HELLO, 0, 0, 0, 2, 0, 0, 0, 0x03]).unwrap()
+        );
+        assert!(decoder.extensions().is_empty());
+    }
+
+    #[test]
+    fn test_decode_hello_not_offered() {
+        assert!(matches!(
+            MessageDecoder::with_extensions(true).decode(&[HELLO, 0, 0, 0, 1, 0, 0, 0, 0x03]),
+            Err(Error::MessageInvalid(HELLO))
+        ));
+    }
+
+    #[test]
+    fn test_session_hello() {
+        assert_eq!(
+            Some(3),
+            Session::new()
+                .handle(Message::Hello {
+                    version: PROTOCOL_VERSION,
+                    accepted: Extensions::ALL
+                })
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_session_aggregate() {
+        let mut session = Session::new();
+        for (timestamp, price) in [(12345, 101), (12346, 102), (12347, 100), (40960, 5)] {
+            session
+                .handle(Message::Insert { timestamp, price })
+                .unwrap();
+        }
+
+        let mut aggregate = |aggregate, mintime, maxtime| {
+            session
+                .handle(Message::Aggregate {
+                    aggregate,
+                    mintime,
+                    maxtime,
+                })
+                .unwrap()
+        };
+
+        assert_eq!(Some(100), aggregate(Aggregate::Min, 12288, 16384));
+        assert_eq!(Some(102), aggregate(Aggregate::Max, 12288, 16384));
+        assert_eq!(Some(3), aggregate(Aggregate::Count, 12288, 16384));
+        assert_eq!(Some(5), aggregate(Aggregate::Min, i32::MIN, i32::MAX));
+        assert_eq!(Some(0), aggregate(Aggregate::Min, 16384, 12288));
+        assert_eq!(Some(0), aggregate(Aggregate::Max, 0, 10));
+        assert_eq!(Some(0), aggregate(Aggregate::Count, 0, 10));
+
+        assert_eq!(7, session.stats().queries);
+        assert_eq!(1, session.stats().invalid_range_queries);
+    }
+
+    #[test]
+    fn test_session() {
+        let mut session = Session::new();
+        for (timestamp, price) in [(12345, 101), (12346, 102), (12347, 100), (40960, 5)] {
+            assert_eq!(
+                None,
+                session
+                    .handle(Message::Insert { timestamp, price })
+                    .unwrap()
+            );
+        }
+
+        assert_eq!(
+            Some(101),
+            session
+                .handle(Message::Query {
+                    mintime: 12288,
+                    maxtime: 16384
+                })
+                .unwrap()
+        );
+    }
+
+    fn insert_duplicate(session: &mut Session<impl Store>) -> Result<Option<i32>, Error> {
+        session.handle(Message::Insert {
+            timestamp: 1,
+            price: 10,
+        })?;
+        session.handle(Message::Insert {
+            timestamp: 1,
+            price: 20,
+        })?;
+        session.handle(Message::Query {
+            mintime: 1,
+            maxtime: 1,
+        })
+    }
+
+    #[test]
+    fn test_duplicate_overwrite() {
+        let mut session = Session::with_duplicate_policy(DuplicatePolicy::Overwrite);
+        assert_eq!(Some(20), insert_duplicate(&mut session).unwrap());
+    }
+
+    #[test]
+    fn test_duplicate_ignore() {
+        let mut session = Session::with_duplicate_policy(DuplicatePolicy::Ignore);
+        assert_eq!(Some(10), insert_duplicate(&mut session).unwrap());
+    }
+
+    #[test]
+    fn test_spill_session() {
+        let mut session = Session::with_store(
+            SpillPrices::new(SpillConfig {
+                directory: std::env::temp_dir(),
+                budget: 1,
+            }),
+            DuplicatePolicy::Ignore,
+        );
+        assert_eq!(Some(10), insert_duplicate(&mut session).unwrap());
+    }
+
+    #[test]
+    fn test_query_cache() {
+        let mut session = Session::new().with_query_cache(4);
+        let query = |session: &mut Session, mintime, maxtime| {
+            session
+                .handle(Message::Query { mintime, maxtime })
+                .unwrap()
+                .unwrap()
+        };
+
+        session
+            .handle(Message::BatchInsert(vec![(1, 10), (20, 30)]))
+            .unwrap();
+        assert_eq!(10, query(&mut session, 0, 10));
+        assert_eq!(10, query(&mut session, 0, 10));
+        assert_eq!(30, query(&mut session, 11, 20));
+
+        session
+            .handle(Message::Insert {
+                timestamp: 2,
+                price: 20,
+            })
+            .unwrap();
+        assert_eq!(15, query(&mut session, 0, 10));
+        assert_eq!(30, query(&mut session, 11, 20));
+
+        let cache = session.query_cache().unwrap();
+        assert_eq!(2, cache.hits());
+        assert_eq!(3, cache.misses());
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut session = Session::new();
+        session
+            .handle(Message::BatchInsert(vec![(1, 10), (2, 20)]))
+            .unwrap();
+        session
+            .handle(Message::Insert {
+                timestamp: 1,
+                price: 30,
+            })
+            .unwrap();
+        session
+            .handle(Message::Query {
+                mintime: 1,
+                maxtime: 2,
+            })
+            .unwrap();
+        session
+            .handle(Message::Query {
+                mintime: 2,
+                maxtime: 1,
+            })
+            .unwrap();
+
+        assert_eq!(
+            Stats {
+                inserts: 3,
+                queries: 2,
+                invalid_range_queries: 1,
+                evicted: 0,
+            },
+            session.stats()
+        );
+        assert_eq!(2, session.len());
+    }
+
+    #[test]
+    fn test_price_limit_evict_oldest() {
+        let mut session = Session::new()
+            .with_query_cache(4)
+            .with_price_limit(PriceLimit {
+                max_prices: 2,
+                policy: LimitPolicy::EvictOldest,
+            });
+
+        for (timestamp, price) in [(3, 30), (1, 10)] {
+            session
+                .handle(Message::Insert { timestamp, price })
+                .unwrap();
+        }
+        let query = Message::Query {
+            mintime: i32::MIN,
+            maxtime: i32::MAX,
+        };
+        assert_eq!(Some(20), session.handle(query.clone()).unwrap());
+
+        session
+            .handle(Message::Insert {
+                timestamp: 3,
+                price: 50,
+            })
+            .unwrap();
+        assert_eq!(2, session.len());
+        assert_eq!(0, session.stats().evicted);
+
+        session
+            .handle(Message::Insert {
+                timestamp: 2,
+                price: 20,
+            })
+            .unwrap();
+        assert_eq!(2, session.len());
+        assert_eq!(1, session.stats().evicted);
+        assert_eq!(Some(35), session.handle(query).unwrap());
+    }
+
+    #[test]
+    fn test_price_limit_close() {
+        let mut session = Session::new().with_price_limit(PriceLimit {
+            max_prices: 1,
+            policy: LimitPolicy::Close,
+        });
+
+        session
+            .handle(Message::Insert {
+                timestamp: 1,
+                price: 10,
+            })
+            .unwrap();
+        session
+            .handle(Message::Insert {
+                timestamp: 1,
+                price: 20,
+            })
+            .unwrap();
+        assert!(matches!(
+            session.handle(Message::Insert {
+                timestamp: 2,
+                price: 20,
+            }),
+            Err(Error::TooManyPrices(1))
+        ));
+        assert_eq!(1, session.len());
+    }
+
+    #[test]
+    fn test_duplicate_close() {
+        let mut session = Session::with_duplicate_policy(DuplicatePolicy::Close);
+        assert!(matches!(
+            insert_duplicate(&mut session),
+            Err(Error::DuplicateTimestamp(1))
+        ));
+    }
+}
@@ -0,0 +1,615 @@
+//! The prices of a session, in a treap keyed by timestamp where every
+//! node keeps the count, the sum, the minimum and the maximum of its
+//! subtree: both the inserts and the range aggregates are
+//! logarithmic.
+//!
+//! The priorities are drawn from a random seed per store and the
+//! updates are iterative: no order of the inserts can make the tree a
+//! deep chain, nor overflow the stack.
+use std::cmp::Ordering;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+
+const NIL: u32 = u32::MAX;
+
+#[derive(Debug, Clone)]
+struct Node {
+    timestamp: i32,
+    price: i32,
+    priority: u32,
+    left: u32,
+    right: u32,
+    count: u32,
+    sum: i128,
+    min: i32,
+    max: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Prices {
+    nodes: Vec<Node>,
+
+    /// The nodes of the removed prices, reused by the next inserts.
+    free: Vec<u32>,
+    root: u32,
+    seed: u32,
+}
+
+impl Default for Prices {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Prices {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![],
+            free: vec![],
+            root: NIL,
+            seed: random_seed(),
+        }
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.nodes.len() - self.free.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.root == NIL
+    }
+
+    /// The price at `timestamp`, if any.
+    #[must_use]
+    pub fn get(&self, timestamp: i32) -> Option<i32> {
+        let mut current = self.root;
+        while current != NIL {
+            let node = &self.nodes[current as usize];
+            current = match timestamp.cmp(&node.timestamp) {
+                Ordering::Less => node.left,
+                Ordering::Greater => node.right,
+                Ordering::Equal => return Some(node.price),
+            };
+        }
+        None
+    }
+
+    /// Insert a price, overwriting the one at the same timestamp.
+    ///
+    /// # Panics
+    /// * Panics when there are more than `u32::MAX - 1` prices.
+    pub fn insert(&mut self, timestamp: i32, price: i32) {
+        // the path from the root to the parent of the new node
+        let mut path = vec![];
+        let mut current = self.root;
+        while current != NIL {
+            let node = &mut self.nodes[current as usize];
+            path.push(current);
+            current = match timestamp.cmp(&node.timestamp) {
+                Ordering::Less => node.left,
+                Ordering::Greater => node.right,
+                Ordering::Equal => {
+                    node.price = price;
+                    for index in path.into_iter().rev() {
+                        self.update(index);
+                    }
+                    return;
+                }
+            };
+        }
+
+        // rotate the new node above the parents of lower priority, it
+        // is linked to the remaining one at the end
+        let index = self.allocate(timestamp, price);
+        while let Some(&parent) = path.last() {
+            if self.nodes[index as usize].priority <= self.nodes[parent as usize].priority {
+                break;
+            }
+            path.pop();
+
+            if timestamp < self.nodes[parent as usize].timestamp {
+                self.nodes[parent as usize].left = self.nodes[index as usize].right;
+                self.nodes[index as usize].right = parent;
+            } else {
+                self.nodes[parent as usize].right = self.nodes[index as usize].left;
+                self.nodes[index as usize].left = parent;
+            }
+            self.update(parent);
+        }
+        self.update(index);
+
+        match path.last() {
+            Some(&parent) if timestamp < self.nodes[parent as usize].timestamp => {
+                self.nodes[parent as usize].left = index;
+            }
+            Some(&parent) => self.nodes[parent as usize].right = index,
+            None => self.root = index,
+        }
+        for index in path.into_iter().rev() {
+            self.update(index);
+        }
+    }
+
+    /// Remove the price with the lowest timestamp.
+    pub fn pop_first(&mut self) -> Option<(i32, i32)> {
+        let mut path = vec![];
+        let mut current = self.root;
+        while current != NIL && self.nodes[current as usize].left != NIL {
+            path.push(current);
+            current = self.nodes[current as usize].left;
+        }
+        if current == NIL {
+            return None;
+        }
+
+        // the first node has no left child, its right child takes its
+        // place and keeps the heap order
+        let node = &self.nodes[current as usize];
+        let (first, right) = ((node.timestamp, node.price), node.right);
+        match path.last() {
+            Some(parent) => self.nodes[*parent as usize].left = right,
+            None => self.root = right,
+        }
+        self.free.push(current);
+
+        for index in path.into_iter().rev() {
+            self.update(index);
+        }
+
+        Some(first)
+    }
+
+    /// The mean of the prices in the closed interval `[mintime,
+    /// maxtime]`, 0 when empty, rounded towards zero.
+    #[must_use]
+    pub fn mean(&self, mintime: i32, maxtime: i32) -> i32 {
+        let (count, sum) = self.totals(mintime, maxtime);
+        crate::mean(count, sum)
+    }
+
+    /// The count and the sum of the prices in the closed interval
+    /// `[mintime, maxtime]`.
+    #[must_use]
+    pub fn totals(&self, mintime: i32, maxtime: i32) -> (u64, i128) {
+        if maxtime < mintime {
+            return (0, 0);
+        }
+
+        let (count_max, sum_max) = self.prefix(maxtime, true);
+        let (count_min, sum_min) = self.prefix(mintime, false);
+
+        (u64::from(count_max - count_min), sum_max - sum_min)
+    }
+
+    /// The minimum and the maximum price in the closed interval
+    /// `[mintime, maxtime]`, `None` when empty.
+    #[must_use]
+    pub fn extremes(&self, mintime: i32, maxtime: i32) -> Option<(i32, i32)> {
+        // descend to the first node inside the interval, the rest of
+        // the interval is in its two subtrees, bounded on one side
+        let mut current = self.root;
+        while current != NIL {
+            let node = &self.nodes[current as usize];
+            if node.timestamp < mintime {
+                current = node.right;
+            } else if node.timestamp > maxtime {
+                current = node.left;
+            } else {
+                return combine(
+                    combine(
+                        self.bounded(node.left, mintime, true),
+                        Some((node.price, node.price)),
+                    ),
+                    self.bounded(node.right, maxtime, false),
+                );
+            }
+        }
+        None
+    }
+
+    /// The prices in ascending timestamp order.
+    pub fn iter(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        let mut stack = vec![];
+        let mut current = self.root;
+        std::iter::from_fn(move || {
+            while current != NIL {
+                stack.push(current);
+                current = self.nodes[current as usize].left;
+            }
+
+            let node = &self.nodes[stack.pop()? as usize];
+            current = node.right;
+            Some((node.timestamp, node.price))
+        })
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.free.clear();
+        self.root = NIL;
+    }
+
+    /// The count and the sum of the prices before `timestamp`,
+    /// included when `inclusive`.
+    fn prefix(&self, timestamp: i32, inclusive: bool) -> (u32, i128) {
+        let (mut count, mut sum) = (0, 0);
+
+        let mut current = self.root;
+        while current != NIL {
+            let node = &self.nodes[current as usize];
+            if node.timestamp < timestamp || inclusive && node.timestamp == timestamp {
+                let (left_count, left_sum) = self.subtree(node.left);
+                count += left_count + 1;
+                sum += left_sum + i128::from(node.price);
+                current = node.right;
+            } else {
+                current = node.left;
+            }
+        }
+
+        (count, sum)
+    }
+
+    /// The minimum and the maximum price of the subtree at `index`
+    /// from `timestamp` onwards when `from`, up to it otherwise.
+    fn bounded(&self, mut index: u32, timestamp: i32, from: bool) -> Option<(i32, i32)> {
+        let mut extremes = None;
+        while index != NIL {
+            let node = &self.nodes[index as usize];
+            let (inside, outside) = if from {
+                (node.right, node.left)
+            } else {
+                (node.left, node.right)
+            };
+
+            if from && node.timestamp >= timestamp || !from && node.timestamp <= timestamp {
+                extremes = combine(extremes, Some((node.price, node.price)));
+                extremes = combine(extremes, self.extremes_of(inside));
+                index = outside;
+            } else {
+                index = inside;
+            }
+        }
+        extremes
+    }
+
+    fn extremes_of(&self, index: u32) -> Option<(i32, i32)> {
+        (index != NIL).then(|| {
+            let node = &self.nodes[index as usize];
+            (node.min, node.max)
+        })
+    }
+
+    fn subtree(&self, index: u32) -> (u32, i128) {
+        if index == NIL {
+            (0, 0)
+        } else {
+            let node = &self.nodes[index as usize];
+            (node.count, node.sum)
+        }
+    }
+
+    fn update(&mut self, index: u32) {
+        let node = &self.nodes[index as usize];
+        let (left_count, left_sum) = self.subtree(node.left);
+        let (right_count, right_sum) = self.subtree(node.right);
+
+        let (min, max) = [self.extremes_of(node.left), self.extremes_of(node.right)]
+            .into_iter()
+            .flatten()
+            .fold(
+                (node.price, node.price),
+                |(min, max), (other_min, other_max)| (min.min(other_min), max.max(other_max)),
+            );
+
+        let node = &mut self.nodes[index as usize];
+        node.count = left_count + right_count + 1;
+        node.sum = left_sum + right_sum + i128::from(node.price);
+        node.min = min;
+        node.max = max;
+    }
+
+    fn next_priority(&mut self) -> u32 {
+        // xorshift32, the priorities just need to look random
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        self.seed
+    }
+
+    fn allocate(&mut self, timestamp: i32, price: i32) -> u32 {
+        let node = Node {
+            timestamp,
+            price,
+            priority: self.next_priority(),
+            left: NIL,
+            right: NIL,
+            count: 1,
+            sum: i128::from(price),
+            min: price,
+            max: price,
+        };
+
+        if let Some(index) = self.free.pop() {
+            self.nodes[index as usize] = node;
+            return index;
+        }
+
+        let index = u32::try_from(self.nodes.len())
+            .ok()
+            .filter(|index| *index != NIL)
+            .expect("too many prices");
+        self.nodes.push(node);
+        index
+    }
+}
+
+/// A seed of the priorities, from the random keys of the hash maps,
+/// never 0 that xorshift does not leave.
+fn random_seed() -> u32 {
+    let [a, b, c, d, ..] = RandomState::new().hash_one(NIL).to_ne_bytes();
+    u32::from_ne_bytes([a, b, c, d]) | 1
+}
+
+/// The minimum and the maximum of two optional pairs.
+pub(crate) fn combine(a: Option<(i32, i32)>, b: Option<(i32, i32)>) -> Option<(i32, i32)> {
+    match (a, b) {
+        (Some((a_min, a_max)), Some((b_min, b_max))) => Some((a_min.min(b_min), a_max.max(b_max))),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn naive_mean(items: &BTreeMap<i32, i32>, mintime: i32, maxtime: i32) -> i32 {
+        if maxtime < mintime {
+            return 0;
+        }
+
+        let (count, sum) = items
+            .range(mintime..=maxtime)
+            .fold((0, 0), |(count, sum), (_, price)| {
+                (count + 1, sum + i64::from(*price))
+            });
+
+        if count > 0 {
+            i32::try_from(sum / count).unwrap()
+        } else {
+            0
+        }
+    }
+
+    fn naive_extremes(
+        items: &BTreeMap<i32, i32>,
+        mintime: i32,
+        maxtime: i32,
+    ) -> Option<(i32, i32)> {
+        if maxtime < mintime {
+            return None;
+        }
+
+        let prices = items.range(mintime..=maxtime).map(|(_, price)| *price);
+        prices.clone().min().zip(prices.max())
+    }
+
+    #[test]
+    fn test_extremes() {
+        let mut prices = Prices::new();
+        assert_eq!(None, prices.extremes(i32::MIN, i32::MAX));
+
+        for (timestamp, price) in [(3, 30), (1, -10), (4, 5), (2, 20), (5, i32::MIN)] {
+            prices.insert(timestamp, price);
+        }
+        assert_eq!(Some((i32::MIN, 30)), prices.extremes(i32::MIN, i32::MAX));
+        assert_eq!(Some((5, 30)), prices.extremes(2, 4));
+        assert_eq!(Some((20, 20)), prices.extremes(2, 2));
+        assert_eq!(None, prices.extremes(4, 2));
+        assert_eq!(None, prices.extremes(6, 10));
+
+        prices.insert(3, 0);
+        assert_eq!(Some((0, 20)), prices.extremes(2, 4));
+    }
+
+    #[test]
+    fn test_mean() {
+        let mut prices = Prices::new();
+        assert_eq!(0, prices.mean(i32::MIN, i32::MAX));
+
+        prices.insert(1, i32::MAX);
+        prices.insert(2, i32::MAX);
+        prices.insert(3, -1);
+        assert_eq!(i32::MAX, prices.mean(1, 2));
+        assert_eq!(1_431_655_764, prices.mean(1, 3));
+        assert_eq!(0, prices.mean(3, 1));
+        assert_eq!(0, prices.mean(4, 10));
+    }
+
+    #[test]
+    fn test_iter() {
+        let mut prices = Prices::new();
+        for timestamp in [5, 1, 4, 2, 3] {
+            prices.insert(timestamp, -timestamp);
+        }
+
+        assert_eq!(
+            vec![(1, -1), (2, -2), (3, -3), (4, -4), (5, -5)],
+            prices.iter().collect::<Vec<_>>()
+        );
+
+        prices.clear();
+        assert!(prices.is_empty());
+        assert_eq!(None, prices.iter().next());
+    }
+
+    #[test]
+    fn test_pop_first() {
+        let mut prices = Prices::new();
+        assert_eq!(None, prices.pop_first());
+
+        for timestamp in [5, 1, 4, 2, 3] {
+            prices.insert(timestamp, timestamp * 10);
+        }
+
+        assert_eq!(Some((1, 10)), prices.pop_first());
+        assert_eq!(Some((2, 20)), prices.pop_first());
+        assert_eq!(3, prices.len());
+        assert_eq!(None, prices.get(1));
+        assert_eq!(40, prices.mean(i32::MIN, i32::MAX));
+        assert_eq!(Some((30, 50)), prices.extremes(i32::MIN, i32::MAX));
+
+        prices.insert(0, 0);
+        assert_eq!(4, prices.len());
+        assert_eq!(
+            vec![0, 3, 4, 5],
+            prices
+                .iter()
+                .map(|(timestamp, _)| timestamp)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(Some((0, 0)), prices.pop_first());
+
+        while prices.pop_first().is_some() {}
+        assert!(prices.is_empty());
+        assert_eq!(0, prices.len());
+        assert_eq!((0, 0), prices.totals(i32::MIN, i32::MAX));
+    }
+
+    #[test]
+    fn test_overwrite() {
+        let mut prices = Prices::new();
+        prices.insert(1, 10);
+        prices.insert(1, 20);
+
+        assert_eq!(1, prices.len());
+        assert_eq!(Some(20), prices.get(1));
+        assert_eq!(20, prices.mean(i32::MIN, i32::MAX));
+    }
+
+    #[test]
+    fn test_same_as_naive() {
+        let mut prices = Prices::new();
+        let mut items = BTreeMap::new();
+
+        let mut seed = 12345_u32;
+        let mut next = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            i32::from_ne_bytes(seed.to_ne_bytes())
+        };
+
+        for _ in 0..10_000 {
+            let (timestamp, price) = (next() % 5_000, next());
+            prices.insert(timestamp, price);
+            items.insert(timestamp, price);
+
+            if next() % 8 == 0 {
+                assert_eq!(items.pop_first(), prices.pop_first());
+            }
+
+            let (mintime, maxtime) = (next() % 6_000, next() % 6_000);
+            assert_eq!(
+                naive_mean(&items, mintime, maxtime),
+                prices.mean(mintime, maxtime),
+                "mean {mintime} {maxtime}"
+            );
+            assert_eq!(
+                naive_extremes(&items, mintime, maxtime),
+                prices.extremes(mintime, maxtime),
+                "extremes {mintime} {maxtime}"
+            );
+        }
+
+        assert_eq!(items.len(), prices.len());
+    }
+
+    #[test]
+    fn test_extreme_prices() {
+        let mut prices = Prices::new();
+        for timestamp in 0..100_000 {
+            prices.insert(timestamp, i32::MIN);
+        }
+        assert_eq!(i32::MIN, prices.mean(i32::MIN, i32::MAX));
+        assert_eq!(
+            (100_000, 100_000 * i128::from(i32::MIN)),
+            prices.totals(i32::MIN, i32::MAX)
+        );
+
+        for timestamp in 100_000..200_000 {
+            prices.insert(timestamp, i32::MAX);
+        }
+        assert_eq!(i32::MAX, prices.mean(100_000, 199_999));
+        assert_eq!(0, prices.mean(i32::MIN, i32::MAX));
+        assert_eq!(0, prices.mean(99_999, 100_000));
+        assert_eq!(715_827_882, prices.mean(99_999, 100_001));
+    }
+
+    /// The number of nodes of the longest path from the root.
+    fn height(prices: &Prices) -> usize {
+        let (mut height, mut stack) = (0, vec![(prices.root, 1)]);
+        while let Some((index, depth)) = stack.pop() {
+            if index != NIL {
+                let node = &prices.nodes[index as usize];
+                height = height.max(depth);
+                stack.push((node.left, depth + 1));
+                stack.push((node.right, depth + 1));
+            }
+        }
+        height
+    }
+
+    #[test]
+    fn test_adversarial_inserts() {
+        // the timestamps in the order of the priorities of the old
+        // fixed seed made a chain, 200k of them overflowed the stack
+        let mut seed = 0x9e37_79b9_u32;
+        let mut priorities = (0..200_000)
+            .map(|insert| {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                (seed, insert)
+            })
+            .collect::<Vec<_>>();
+        priorities.sort_unstable();
+        let mut timestamps = vec![0; priorities.len()];
+        for (timestamp, (_, insert)) in priorities.into_iter().enumerate() {
+            timestamps[insert] = i32::try_from(timestamp).unwrap();
+        }
+
+        let mut prices = Prices::new();
+        for timestamp in timestamps {
+            prices.insert(timestamp, 1);
+        }
+
+        assert_eq!(200_000, prices.len());
+        assert!(height(&prices) < 100, "height {}", height(&prices));
+        assert_eq!(1, prices.mean(0, 199_999));
+
+        let mut sorted = Prices::new();
+        for timestamp in 0..200_000 {
+            sorted.insert(timestamp, 1);
+        }
+        assert!(height(&sorted) < 100, "height {}", height(&sorted));
+    }
+
+    #[test]
+    fn test_many_sorted_inserts() {
+        let mut prices = Prices::new();
+        for timestamp in 0..200_000 {
+            prices.insert(timestamp, timestamp % 100);
+        }
+
+        assert_eq!(200_000, prices.len());
+        assert_eq!(49, prices.mean(0, 199_999));
+        assert_eq!(0, prices.mean(100, 100));
+    }
+}
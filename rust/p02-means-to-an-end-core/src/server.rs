@@ -0,0 +1,229 @@
+//! The session loop, over the runtime traits.
+use protohackers_runtime::{Read, Write};
+
+use crate::{Error, Message, MessageDecoder, Session, Store};
+
+/// The bytes read at once.
+const READ_LEN: usize = 4 * 1024;
+
+/// How [`serve`] handles a message, the hook for the metrics of a
+/// server.
+pub trait Handler<S: Store> {
+    /// Handle `message` in `session`, by default with the session only.
+    ///
+    /// # Errors
+    /// * Error when the session must be closed, see
+    ///   [`Session::handle`].
+    fn handle(&mut self, session: &mut Session<S>, message: Message) -> Result<Option<i32>, Error> {
+        session.handle(message)
+    }
+}
+
+impl<S: Store> Handler<S> for () {}
+
+/// Serve a client session: decode the messages read, handle them and
+/// write back the responses.
+///
+/// The responses of the messages already received are written at
+/// once, so a pipelined burst of queries takes a single write. The
+/// responses are flushed before returning, also on an invalid
+/// message; a partial message at the end of the stream is dropped.
+///
+/// # Errors
+/// * Error when the stream fails, a message is invalid or the session
+///   must be closed.
+pub async fn serve<S: Store>(
+    mut read: impl Read,
+    mut write: impl Write,
+    mut decoder: MessageDecoder,
+    session: &mut Session<S>,
+    handler: &mut impl Handler<S>,
+) -> Result<(), Error> {
+    let mut received = Vec::with_capacity(READ_LEN);
+    let mut responses = vec![];
+
+    loop {
+        let (len, result) =
+            handle_received(&mut decoder, &received, session, handler, &mut responses);
+        received.drain(..len);
+
+        if !responses.is_empty() {
+            write.write_all(&responses).await?;
+            write.flush().await?;
+            responses.clear();
+        }
+        result?;
+
+        let len = received.len();
+        received.resize(len + READ_LEN, 0);
+        let read = read.read(&mut received[len..]).await?;
+        received.truncate(len + read);
+        if read == 0 {
+            return Ok(());
+        }
+    }
+}
+
+/// Handle the messages at the start of `received`, appending their
+/// responses to `responses`: the length of the messages handled, and
+/// the error closing the session, if any.
+///
+/// The step of [`serve`] without I/O, for a server handling the
+/// messages of a blocking store out of its reactor.
+pub fn handle_received<S: Store>(
+    decoder: &mut MessageDecoder,
+    received: &[u8],
+    session: &mut Session<S>,
+    handler: &mut impl Handler<S>,
+    responses: &mut Vec<u8>,
+) -> (usize, Result<(), Error>) {
+    let mut start = 0;
+    let result = loop {
+        match decoder.decode(&received[start..]) {
+            Ok(Some((message, len))) => {
+                start += len;
+                match handler.handle(session, message) {
+                    Ok(Some(response)) => responses.extend_from_slice(&response.to_be_bytes()),
+                    Ok(None) => {}
+                    Err(err) => break Err(err),
+                }
+            }
+            Ok(None) => break Ok(()),
+            Err(err) => break Err(err),
+        }
+    };
+    (start, result)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::Stats;
+
+    fn insert(timestamp: i32, price: i32) -> Vec<u8> {
+        let mut message = vec![b'I'];
+        message.extend_from_slice(&timestamp.to_be_bytes());
+        message.extend_from_slice(&price.to_be_bytes());
+        message
+    }
+
+    fn query(mintime: i32, maxtime: i32) -> Vec<u8> {
+        let mut message = vec![b'Q'];
+        message.extend_from_slice(&mintime.to_be_bytes());
+        message.extend_from_slice(&maxtime.to_be_bytes());
+        message
+    }
+
+    #[test]
+    fn test_serve() {
+        let request = [
+            insert(12345, 101),
+            insert(12346, 102),
+            insert(12347, 100),
+            insert(40960, 5),
+            query(12288, 16384),
+            query(16384, 12288),
+        ]
+        .concat();
+
+        let mut session = Session::new();
+        let mut response = vec![];
+        block_on(serve(
+            request.as_slice(),
+            &mut response,
+            MessageDecoder::new(),
+            &mut session,
+            &mut (),
+        ))
+        .unwrap();
+
+        assert_eq!(
+            [101_i32.to_be_bytes(), 0_i32.to_be_bytes()].concat(),
+            response
+        );
+        assert_eq!(
+            Stats {
+                inserts: 4,
+                queries: 2,
+                invalid_range_queries: 1,
+                evicted: 0,
+            },
+            session.stats()
+        );
+    }
+
+    #[test]
+    fn test_serve_invalid() {
+        let request = [
+            insert(1, 10),
+            query(0, 2),
+            b"X12345678".to_vec(),
+            query(0, 2),
+        ]
+        .concat();
+
+        let mut session = Session::new();
+        let mut response = vec![];
+        let result = block_on(serve(
+            request.as_slice(),
+            &mut response,
+            MessageDecoder::new(),
+            &mut session,
+            &mut (),
+        ));
+
+        assert!(matches!(result, Err(Error::MessageInvalid(b'X'))));
+        assert_eq!(10_i32.to_be_bytes().as_slice(), response);
+    }
+
+    #[test]
+    fn test_serve_partial() {
+        let request = [insert(1, 10), query(0, 2), b"Q1234".to_vec()].concat();
+
+        let mut response = vec![];
+        block_on(serve(
+            request.as_slice(),
+            &mut response,
+            MessageDecoder::new(),
+            &mut Session::new(),
+            &mut (),
+        ))
+        .unwrap();
+
+        assert_eq!(10_i32.to_be_bytes().as_slice(), response);
+    }
+
+    #[test]
+    fn test_serve_handler() {
+        struct Count(usize);
+
+        impl<S: Store> Handler<S> for Count {
+            fn handle(
+                &mut self,
+                session: &mut Session<S>,
+                message: Message,
+            ) -> Result<Option<i32>, Error> {
+                self.0 += 1;
+                session.handle(message)
+            }
+        }
+
+        let request = [insert(1, 10), insert(2, 20), query(0, 2)].concat();
+
+        let mut count = Count(0);
+        let mut response = vec![];
+        block_on(serve(
+            request.as_slice(),
+            &mut response,
+            MessageDecoder::new(),
+            &mut Session::new(),
+            &mut count,
+        ))
+        .unwrap();
+
+        assert_eq!(3, count.0);
+        assert_eq!(15_i32.to_be_bytes().as_slice(), response);
+    }
+}
@@ -0,0 +1,126 @@
+//! A price store shared between sessions.
+
+use std::io;
+use std::sync::{Arc, RwLock};
+
+use crate::Store;
+
+/// A store shared by all the sessions: the queries run concurrently,
+/// the inserts are exclusive.
+///
+/// The duplicate policies other than overwrite check and insert in
+/// two steps, so two sessions inserting the same timestamp at the
+/// same time can both succeed.
+#[derive(Clone)]
+pub struct SharedPrices {
+    store: Arc<RwLock<dyn Store + Send + Sync>>,
+}
+
+impl std::fmt::Debug for SharedPrices {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        f.debug_struct("SharedPrices").finish_non_exhaustive()
+    }
+}
+
+fn poisoned<T>(_: T) -> io::Error {
+    io::Error::other("shared store poisoned")
+}
+
+impl SharedPrices {
+    pub fn new(store: impl Store + Send + Sync + 'static) -> Self {
+        Self {
+            store: Arc::new(RwLock::new(store)),
+        }
+    }
+}
+
+impl Store for SharedPrices {
+    fn get(&self, timestamp: i32) -> io::Result<Option<i32>> {
+        self.store.read().map_err(poisoned)?.get(timestamp)
+    }
+
+    fn insert(&mut self, timestamp: i32, price: i32) -> io::Result<()> {
+        self.store
+            .write()
+            .map_err(poisoned)?
+            .insert(timestamp, price)
+    }
+
+    fn totals(&self, mintime: i32, maxtime: i32) -> io::Result<(u64, i128)> {
+        self.store
+            .read()
+            .map_err(poisoned)?
+            .totals(mintime, maxtime)
+    }
+
+    fn extremes(&self, mintime: i32, maxtime: i32) -> io::Result<Option<(i32, i32)>> {
+        self.store
+            .read()
+            .map_err(poisoned)?
+            .extremes(mintime, maxtime)
+    }
+
+    fn pop_first(&mut self) -> io::Result<Option<(i32, i32)>> {
+        self.store.write().map_err(poisoned)?.pop_first()
+    }
+
+    fn len(&self) -> usize {
+        self.store.read().map_or(0, |store| store.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Message, Prices, Session};
+
+    #[test]
+    fn test_shared_between_sessions() {
+        let shared = SharedPrices::new(Prices::new());
+        let mut first = Session::with_store(shared.clone(), crate::DuplicatePolicy::Overwrite);
+        let mut second = Session::with_store(shared.clone(), crate::DuplicatePolicy::Overwrite);
+
+        first
+            .handle(Message::Insert {
+                timestamp: 1,
+                price: 10,
+            })
+            .unwrap();
+        second
+            .handle(Message::Insert {
+                timestamp: 2,
+                price: 20,
+            })
+            .unwrap();
+
+        let query = Message::Query {
+            mintime: 1,
+            maxtime: 2,
+        };
+        assert_eq!(Some(15), first.handle(query.clone()).unwrap());
+        assert_eq!(Some(15), second.handle(query).unwrap());
+        assert_eq!(2, shared.len());
+    }
+
+    #[test]
+    fn test_concurrent_inserts() {
+        let shared = SharedPrices::new(Prices::new());
+
+        let handles = (0..4)
+            .map(|thread| {
+                let mut shared = shared.clone();
+                std::thread::spawn(move || {
+                    for timestamp in 0..1_000 {
+                        shared.insert(thread * 1_000 + timestamp, 1).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(4_000, shared.len());
+        assert_eq!((4_000, 4_000), shared.totals(i32::MIN, i32::MAX).unwrap());
+    }
+}
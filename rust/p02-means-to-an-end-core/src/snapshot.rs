@@ -0,0 +1,181 @@
+//! A price store for the read-mostly sessions.
+//!
+//! The prices are kept in a [`Prices`] treap, and after `threshold`
+//! queries without inserts in between a sorted snapshot with the
+//! prefix sums is built: the next queries are two binary searches
+//! and a subtraction. An insert or a removal drops the snapshot, it
+//! is rebuilt lazily when the queries burst again.
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+use crate::{Prices, Store};
+
+#[derive(Debug)]
+struct Snapshot {
+    timestamps: Vec<i32>,
+
+    /// The sum of the prices before every timestamp, one more than
+    /// the timestamps.
+    prefix_sums: Vec<i128>,
+}
+
+impl Snapshot {
+    fn new(prices: &Prices) -> Self {
+        let mut timestamps = Vec::with_capacity(prices.len());
+        let mut prefix_sums = Vec::with_capacity(prices.len() + 1);
+
+        let mut prefix_sum = 0;
+        prefix_sums.push(prefix_sum);
+        for (timestamp, price) in prices.iter() {
+            timestamps.push(timestamp);
+            prefix_sum += i128::from(price);
+            prefix_sums.push(prefix_sum);
+        }
+
+        Self {
+            timestamps,
+            prefix_sums,
+        }
+    }
+
+    fn totals(&self, mintime: i32, maxtime: i32) -> (u64, i128) {
+        let min = self.timestamps.partition_point(|t| *t < mintime);
+        let max = self.timestamps.partition_point(|t| *t <= maxtime);
+
+        (
+            (max - min) as u64,
+            self.prefix_sums[max] - self.prefix_sums[min],
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct SnapshotPrices {
+    prices: Prices,
+    threshold: usize,
+    queries: AtomicUsize,
+    snapshot: OnceLock<Snapshot>,
+}
+
+impl SnapshotPrices {
+    /// Build the snapshot after `threshold` queries in a row.
+    #[must_use]
+    pub fn new(threshold: usize) -> Self {
+        Self {
+            prices: Prices::new(),
+            threshold,
+            queries: AtomicUsize::new(0),
+            snapshot: OnceLock::new(),
+        }
+    }
+
+    /// True when the queries are answered by the snapshot.
+    #[must_use]
+    pub fn has_snapshot(&self) -> bool {
+        self.snapshot.get().is_some()
+    }
+}
+
+impl Store for SnapshotPrices {
+    fn get(&self, timestamp: i32) -> io::Result<Option<i32>> {
+        Ok(self.prices.get(timestamp))
+    }
+
+    fn insert(&mut self, timestamp: i32, price: i32) -> io::Result<()> {
+        self.prices.insert(timestamp, price);
+        self.snapshot.take();
+        *self.queries.get_mut() = 0;
+        Ok(())
+    }
+
+    fn totals(&self, mintime: i32, maxtime: i32) -> io::Result<(u64, i128)> {
+        if maxtime < mintime {
+            return Ok((0, 0));
+        }
+
+        if let Some(snapshot) = self.snapshot.get() {
+            return Ok(snapshot.totals(mintime, maxtime));
+        }
+
+        if self.queries.fetch_add(1, Ordering::Relaxed) + 1 >= self.threshold {
+            let snapshot = self.snapshot.get_or_init(|| Snapshot::new(&self.prices));
+            return Ok(snapshot.totals(mintime, maxtime));
+        }
+
+        Ok(self.prices.totals(mintime, maxtime))
+    }
+
+    fn extremes(&self, mintime: i32, maxtime: i32) -> io::Result<Option<(i32, i32)>> {
+        Ok(self.prices.extremes(mintime, maxtime))
+    }
+
+    fn pop_first(&mut self) -> io::Result<Option<(i32, i32)>> {
+        self.snapshot.take();
+        *self.queries.get_mut() = 0;
+        Ok(self.prices.pop_first())
+    }
+
+    fn len(&self) -> usize {
+        self.prices.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot() {
+        let mut prices = SnapshotPrices::new(2);
+        for timestamp in [5, 1, 4, 2, 3] {
+            prices.insert(timestamp, timestamp * 10).unwrap();
+        }
+
+        assert_eq!(30, prices.mean(2, 4).unwrap());
+        assert!(!prices.has_snapshot());
+
+        assert_eq!((5, 150), prices.totals(i32::MIN, i32::MAX).unwrap());
+        assert!(prices.has_snapshot());
+        assert_eq!(30, prices.mean(2, 4).unwrap());
+        assert_eq!(0, prices.mean(4, 2).unwrap());
+        assert_eq!(0, prices.mean(6, 10).unwrap());
+
+        prices.insert(6, 60).unwrap();
+        assert!(!prices.has_snapshot());
+        assert_eq!(35, prices.mean(1, 6).unwrap());
+        assert_eq!(50, prices.mean(4, 6).unwrap());
+        assert!(prices.has_snapshot());
+    }
+
+    #[test]
+    fn test_same_as_treap() {
+        let mut snapshot = SnapshotPrices::new(3);
+        let mut prices = Prices::new();
+
+        let mut seed = 24680_u32;
+        let mut next = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            i32::from_ne_bytes(seed.to_ne_bytes())
+        };
+
+        for _ in 0..2_000 {
+            for _ in 0..next().rem_euclid(5) {
+                let (timestamp, price) = (next() % 2_000, next());
+                snapshot.insert(timestamp, price).unwrap();
+                prices.insert(timestamp, price);
+            }
+
+            for _ in 0..next().rem_euclid(8) {
+                let (mintime, maxtime) = (next() % 2_500, next() % 2_500);
+                assert_eq!(
+                    prices.totals(mintime, maxtime),
+                    snapshot.totals(mintime, maxtime).unwrap(),
+                    "totals {mintime} {maxtime}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,595 @@
+//! A price store that spills to disk the older prices of a session
+//! when it exceeds its in-memory budget.
+//!
+//! The prices in memory are written to a new immutable sorted run of
+//! fixed size records with the prefix sums, plus a sparse in-memory
+//! index of the first timestamp of every block: a lookup reads a
+//! single block of a run. The runs are tiered, a run is merged with
+//! the previous one as soon as it is as large: there are
+//! logarithmically many runs and every price is written a
+//! logarithmic number of times.
+//!
+//! A price is live in a single place, the memory or a run. A newer
+//! price at a spilled timestamp stays in memory, and the run of the
+//! spilled price it overwrites remembers it to be subtracted from the
+//! totals until the run is merged. A removed spilled price is
+//! remembered the same way. When the remembered prices exceed half of
+//! the budget all the runs are merged.
+//!
+//! The minimum and the maximum are not indexed on disk: they read
+//! all the spilled blocks of the interval.
+//!
+//! The file I/O is blocking, an async server runs the store out of
+//! its reactor.
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::iter::Peekable;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::prices::combine;
+use crate::{Prices, Store};
+
+/// The size of a record: timestamp, price and prefix sum.
+const RECORD_LEN: usize = 2 * mem::size_of::<i32>() + mem::size_of::<i128>();
+
+/// The number of records of an indexed block.
+const BLOCK_LEN: usize = 256;
+
+static RUN_ID: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpillConfig {
+    /// Where the runs are written.
+    pub directory: PathBuf,
+
+    /// The maximum number of prices kept in memory.
+    pub budget: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Record {
+    timestamp: i32,
+    price: i32,
+    prefix_sum: i128,
+}
+
+impl Record {
+    fn read(buffer: &[u8]) -> Self {
+        let (timestamp, rest) = buffer.split_at(mem::size_of::<i32>());
+        let (price, prefix_sum) = rest.split_at(mem::size_of::<i32>());
+        Self {
+            timestamp: i32::from_be_bytes(timestamp.try_into().unwrap()),
+            price: i32::from_be_bytes(price.try_into().unwrap()),
+            prefix_sum: i128::from_be_bytes(prefix_sum.try_into().unwrap()),
+        }
+    }
+
+    fn write(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_all(&self.timestamp.to_be_bytes())?;
+        writer.write_all(&self.price.to_be_bytes())?;
+        writer.write_all(&self.prefix_sum.to_be_bytes())
+    }
+}
+
+/// An immutable sorted run on disk, removed when dropped.
+#[derive(Debug)]
+struct Run {
+    path: PathBuf,
+    file: File,
+    len: usize,
+    index: Vec<i32>,
+
+    /// The last timestamp, so that the later ones read nothing.
+    last: i32,
+
+    /// The prices of the run overwritten or removed since it was
+    /// written.
+    removed: Prices,
+}
+
+impl Drop for Run {
+    fn drop(&mut self) {
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+impl Run {
+    fn write(
+        directory: &Path,
+        prices: impl Iterator<Item = io::Result<(i32, i32)>>,
+    ) -> io::Result<Self> {
+        let path = directory.join(format!(
+            "p02-{}-{}.run",
+            std::process::id(),
+            RUN_ID.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+
+        let mut run = Self {
+            path,
+            file,
+            len: 0,
+            index: vec![],
+            last: i32::MIN,
+            removed: Prices::new(),
+        };
+
+        let mut writer = BufWriter::new(&run.file);
+        let mut prefix_sum = 0;
+        for price in prices {
+            let (timestamp, price) = price?;
+            if run.len.is_multiple_of(BLOCK_LEN) {
+                run.index.push(timestamp);
+            }
+            prefix_sum += i128::from(price);
+            Record {
+                timestamp,
+                price,
+                prefix_sum,
+            }
+            .write(&mut writer)?;
+            run.len += 1;
+            run.last = timestamp;
+        }
+        writer.flush()?;
+        drop(writer);
+
+        Ok(run)
+    }
+
+    /// The number of prices neither overwritten nor removed.
+    fn live(&self) -> usize {
+        self.len - self.removed.len()
+    }
+
+    fn read_block(&self, block: usize) -> io::Result<Vec<Record>> {
+        let start = block * BLOCK_LEN;
+        let len = BLOCK_LEN.min(self.len - start);
+
+        let mut buffer = vec![0; len * RECORD_LEN];
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start((start * RECORD_LEN) as u64))?;
+        file.read_exact(&mut buffer)?;
+
+        Ok(buffer.chunks_exact(RECORD_LEN).map(Record::read).collect())
+    }
+
+    /// The live price at `timestamp`, if any.
+    fn get(&self, timestamp: i32) -> io::Result<Option<i32>> {
+        let block = self.index.partition_point(|first| *first <= timestamp);
+        if block == 0 || timestamp > self.last || self.removed.get(timestamp).is_some() {
+            return Ok(None);
+        }
+
+        let records = self.read_block(block - 1)?;
+        Ok(records
+            .binary_search_by_key(&timestamp, |record| record.timestamp)
+            .ok()
+            .map(|position| records[position].price))
+    }
+
+    /// The count and the sum of the prices before `timestamp`,
+    /// included when `inclusive`, also the removed ones.
+    fn prefix(&self, timestamp: i32, inclusive: bool) -> io::Result<(u64, i128)> {
+        let before = |other: i32| other < timestamp || inclusive && other == timestamp;
+
+        let block = self.index.partition_point(|first| before(*first));
+        if block == 0 {
+            return Ok((0, 0));
+        }
+
+        let records = self.read_block(block - 1)?;
+        let position = records.partition_point(|record| before(record.timestamp));
+
+        Ok((
+            ((block - 1) * BLOCK_LEN + position) as u64,
+            records[position - 1].prefix_sum,
+        ))
+    }
+
+    /// The count and the sum of the live prices in the closed interval
+    /// `[mintime, maxtime]`.
+    fn totals(&self, mintime: i32, maxtime: i32) -> io::Result<(u64, i128)> {
+        let (count_max, sum_max) = self.prefix(maxtime, true)?;
+        let (count_min, sum_min) = self.prefix(mintime, false)?;
+        let (removed_count, removed_sum) = self.removed.totals(mintime, maxtime);
+
+        Ok((
+            count_max - count_min - removed_count,
+            sum_max - sum_min - removed_sum,
+        ))
+    }
+
+    /// The minimum and the maximum live price in the closed interval
+    /// `[mintime, maxtime]`.
+    fn extremes(&self, mintime: i32, maxtime: i32) -> io::Result<Option<(i32, i32)>> {
+        let mut extremes = None;
+
+        let first = self
+            .index
+            .partition_point(|first| *first <= mintime)
+            .saturating_sub(1);
+        for block in (first..self.index.len()).take_while(|block| self.index[*block] <= maxtime) {
+            for record in self.read_block(block)? {
+                if (mintime..=maxtime).contains(&record.timestamp)
+                    && self.removed.get(record.timestamp).is_none()
+                {
+                    extremes = combine(extremes, Some((record.price, record.price)));
+                }
+            }
+        }
+
+        Ok(extremes)
+    }
+
+    /// The live prices in ascending timestamp order.
+    fn records(&self) -> io::Result<impl Iterator<Item = io::Result<(i32, i32)>> + '_> {
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(0))?;
+
+        let mut reader = BufReader::new(file);
+        let mut remaining = self.len;
+        let records = std::iter::from_fn(move || {
+            if remaining == 0 {
+                return None;
+            }
+            remaining -= 1;
+
+            let mut buffer = [0; RECORD_LEN];
+            Some(reader.read_exact(&mut buffer).map(|()| {
+                let record = Record::read(&buffer);
+                (record.timestamp, record.price)
+            }))
+        });
+
+        Ok(records.filter(|record| {
+            !matches!(record, Ok((timestamp, _)) if self.removed.get(*timestamp).is_some())
+        }))
+    }
+}
+
+/// Merge the sorted `older` and `newer` prices, the newer ones win.
+fn merge<O, N>(
+    mut older: Peekable<O>,
+    mut newer: Peekable<N>,
+) -> impl Iterator<Item = io::Result<(i32, i32)>>
+where
+    O: Iterator<Item = io::Result<(i32, i32)>>,
+    N: Iterator<Item = io::Result<(i32, i32)>>,
+{
+    std::iter::from_fn(move || match (older.peek(), newer.peek()) {
+        (Some(Ok((older_timestamp, _))), Some(Ok((newer_timestamp, _)))) => {
+            if older_timestamp < newer_timestamp {
+                older.next()
+            } else {
+                if older_timestamp == newer_timestamp {
+                    older.next();
+                }
+                newer.next()
+            }
+        }
+        (Some(Err(_)), _) | (_, None) => older.next(),
+        (_, Some(_)) => newer.next(),
+    })
+}
+
+#[derive(Debug)]
+pub struct SpillPrices {
+    config: SpillConfig,
+    memory: Prices,
+
+    /// The runs from the oldest, and largest, to the newest.
+    runs: Vec<Run>,
+}
+
+impl SpillPrices {
+    #[must_use]
+    pub fn new(config: SpillConfig) -> Self {
+        Self {
+            config,
+            memory: Prices::new(),
+            runs: vec![],
+        }
+    }
+
+    /// The number of prices on disk, including the overwritten ones.
+    #[must_use]
+    pub fn spilled(&self) -> usize {
+        self.runs.iter().map(|run| run.len).sum()
+    }
+
+    /// The number of prices of the runs overwritten or removed.
+    fn removed(&self) -> usize {
+        self.runs.iter().map(|run| run.removed.len()).sum()
+    }
+
+    fn spill_over_budget(&mut self) -> io::Result<()> {
+        if self.memory.len() + self.removed() <= self.config.budget {
+            return Ok(());
+        }
+
+        if !self.memory.is_empty() {
+            let run = Run::write(&self.config.directory, self.memory.iter().map(Ok))?;
+            self.runs.push(run);
+            self.memory.clear();
+        }
+
+        while let [.., older, newer] = self.runs.as_slice() {
+            if newer.live() < older.live() {
+                break;
+            }
+            self.merge_last()?;
+        }
+
+        // the overwritten and removed prices take memory too
+        if self.removed() > self.config.budget / 2 {
+            while self.runs.len() > 1 {
+                self.merge_last()?;
+            }
+            if let Some(run) = self.runs.pop() {
+                let compacted = Run::write(&self.config.directory, run.records()?)?;
+                self.runs.push(compacted);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Merge the two newest runs.
+    fn merge_last(&mut self) -> io::Result<()> {
+        let newer = self.runs.pop().expect("no newer run");
+        let older = self.runs.pop().expect("no older run");
+        let run = Run::write(
+            &self.config.directory,
+            merge(older.records()?.peekable(), newer.records()?.peekable()),
+        )?;
+        self.runs.push(run);
+
+        Ok(())
+    }
+
+    /// The run with the live price at `timestamp` and the price.
+    fn spilled_at(&mut self, timestamp: i32) -> io::Result<Option<(&mut Run, i32)>> {
+        for run in self.runs.iter_mut().rev() {
+            if let Some(price) = run.get(timestamp)? {
+                return Ok(Some((run, price)));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl Store for SpillPrices {
+    fn len(&self) -> usize {
+        self.memory.len() + self.runs.iter().map(Run::live).sum::<usize>()
+    }
+
+    fn get(&self, timestamp: i32) -> io::Result<Option<i32>> {
+        if let Some(price) = self.memory.get(timestamp) {
+            return Ok(Some(price));
+        }
+
+        for run in self.runs.iter().rev() {
+            if let Some(price) = run.get(timestamp)? {
+                return Ok(Some(price));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn insert(&mut self, timestamp: i32, price: i32) -> io::Result<()> {
+        if self.memory.get(timestamp).is_none() {
+            if let Some((run, spilled)) = self.spilled_at(timestamp)? {
+                run.removed.insert(timestamp, spilled);
+            }
+        }
+
+        self.memory.insert(timestamp, price);
+
+        self.spill_over_budget()
+    }
+
+    fn totals(&self, mintime: i32, maxtime: i32) -> io::Result<(u64, i128)> {
+        if maxtime < mintime {
+            return Ok((0, 0));
+        }
+
+        let (mut count, mut sum) = self.memory.totals(mintime, maxtime);
+        for run in &self.runs {
+            let (run_count, run_sum) = run.totals(mintime, maxtime)?;
+            count += run_count;
+            sum += run_sum;
+        }
+
+        Ok((count, sum))
+    }
+
+    fn pop_first(&mut self) -> io::Result<Option<(i32, i32)>> {
+        let mut spilled: Option<(usize, (i32, i32))> = None;
+        for (position, run) in self.runs.iter().enumerate() {
+            if let Some(first) = run.records()?.next().transpose()? {
+                if spilled.is_none_or(|(_, (timestamp, _))| first.0 < timestamp) {
+                    spilled = Some((position, first));
+                }
+            }
+        }
+
+        let first = self.memory.iter().next().map(|(timestamp, _)| timestamp);
+        match spilled {
+            Some((position, (timestamp, price)))
+                if first.is_none_or(|first| timestamp < first) =>
+            {
+                self.runs[position].removed.insert(timestamp, price);
+                self.spill_over_budget()?;
+                Ok(Some((timestamp, price)))
+            }
+            _ => Ok(self.memory.pop_first()),
+        }
+    }
+
+    fn extremes(&self, mintime: i32, maxtime: i32) -> io::Result<Option<(i32, i32)>> {
+        if maxtime < mintime {
+            return Ok(None);
+        }
+
+        let mut extremes = self.memory.extremes(mintime, maxtime);
+        for run in &self.runs {
+            extremes = combine(extremes, run.extremes(mintime, maxtime)?);
+        }
+
+        Ok(extremes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(budget: usize) -> SpillConfig {
+        SpillConfig {
+            directory: std::env::temp_dir(),
+            budget,
+        }
+    }
+
+    #[test]
+    fn test_spill() {
+        let mut prices = SpillPrices::new(config(2));
+        for timestamp in [5, 1, 4, 2, 3] {
+            prices.insert(timestamp, timestamp * 10).unwrap();
+        }
+
+        assert_eq!(3, prices.spilled());
+        assert_eq!(5, prices.len());
+        assert_eq!(Some(30), prices.get(3).unwrap());
+        assert_eq!(Some(40), prices.get(4).unwrap());
+        assert_eq!(None, prices.get(6).unwrap());
+
+        assert_eq!((5, 150), prices.totals(i32::MIN, i32::MAX).unwrap());
+        assert_eq!(30, prices.mean(2, 4).unwrap());
+        assert_eq!(0, prices.mean(4, 2).unwrap());
+    }
+
+    #[test]
+    fn test_overwrite_spilled() {
+        let mut prices = SpillPrices::new(config(2));
+        for timestamp in 1..=3 {
+            prices.insert(timestamp, 10).unwrap();
+        }
+        assert_eq!(3, prices.spilled());
+
+        prices.insert(2, 40).unwrap();
+        assert_eq!(Some(40), prices.get(2).unwrap());
+        assert_eq!((3, 60), prices.totals(1, 3).unwrap());
+        assert_eq!(3, prices.len());
+
+        prices.insert(4, 10).unwrap();
+        assert_eq!(4, prices.spilled());
+        assert_eq!((4, 70), prices.totals(1, 4).unwrap());
+    }
+
+    #[test]
+    fn test_same_as_memory() {
+        let mut spill = SpillPrices::new(config(1_000));
+        let mut memory = Prices::new();
+
+        let mut seed = 54321_u32;
+        let mut next = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            i32::from_ne_bytes(seed.to_ne_bytes())
+        };
+
+        for _ in 0..20_000 {
+            let (timestamp, price) = (next() % 8_000, next());
+            spill.insert(timestamp, price).unwrap();
+            memory.insert(timestamp, price);
+
+            if next() % 8 == 0 {
+                assert_eq!(memory.pop_first(), spill.pop_first().unwrap());
+            }
+
+            let (mintime, maxtime) = (next() % 9_000, next() % 9_000);
+            assert_eq!(
+                memory.totals(mintime, maxtime),
+                spill.totals(mintime, maxtime).unwrap(),
+                "totals {mintime} {maxtime}"
+            );
+            assert_eq!(
+                memory.extremes(mintime, maxtime),
+                spill.extremes(mintime, maxtime).unwrap(),
+                "extremes {mintime} {maxtime}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_pop_first() {
+        let mut prices = SpillPrices::new(config(2));
+        for timestamp in [5, 1, 4, 2, 3] {
+            prices.insert(timestamp, timestamp * 10).unwrap();
+        }
+        prices.insert(1, 15).unwrap();
+
+        assert_eq!(Some((1, 15)), prices.pop_first().unwrap());
+        assert_eq!(Some((2, 20)), prices.pop_first().unwrap());
+        assert_eq!(None, prices.get(2).unwrap());
+        assert_eq!(3, prices.len());
+        assert_eq!((3, 120), prices.totals(i32::MIN, i32::MAX).unwrap());
+        assert_eq!(Some((30, 50)), prices.extremes(i32::MIN, i32::MAX).unwrap());
+
+        prices.insert(2, 20).unwrap();
+        assert_eq!(Some(20), prices.get(2).unwrap());
+        assert_eq!(Some((2, 20)), prices.pop_first().unwrap());
+
+        while prices.pop_first().unwrap().is_some() {}
+        assert_eq!(0, prices.len());
+        assert_eq!((0, 0), prices.totals(i32::MIN, i32::MAX).unwrap());
+    }
+
+    #[test]
+    fn test_tiered_runs() {
+        let mut prices = SpillPrices::new(config(10));
+        for timestamp in 0..10_000 {
+            prices.insert(timestamp, 1).unwrap();
+        }
+
+        assert!(prices.runs.len() <= 11, "{} runs", prices.runs.len());
+        assert!(prices
+            .runs
+            .windows(2)
+            .all(|runs| runs[0].live() > runs[1].live()));
+        assert_eq!((10_000, 10_000), prices.totals(0, 9_999).unwrap());
+
+        // the overwritten prices are merged away
+        for timestamp in 0..1_000 {
+            prices.insert(timestamp, 2).unwrap();
+            assert!(prices.removed() <= 10);
+        }
+        assert_eq!((10_000, 11_000), prices.totals(0, 9_999).unwrap());
+        assert_eq!(Some((1, 2)), prices.extremes(0, 9_999).unwrap());
+    }
+
+    #[test]
+    fn test_run_removed() {
+        let mut prices = SpillPrices::new(config(1));
+        prices.insert(1, 1).unwrap();
+        prices.insert(2, 2).unwrap();
+
+        let path = prices.runs[0].path.clone();
+        assert!(path.exists());
+
+        drop(prices);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,147 @@
+//! Wire format properties: the written messages decode to the same
+//! messages whatever the chunks they arrive in, and decoding any bytes
+//! never panics.
+use proptest::prelude::*;
+
+use p02_means_to_an_end_core::{
+    Aggregate, Extensions, Message, MessageDecoder, MESSAGE_LEN, PROTOCOL_VERSION,
+};
+
+use protohackers_proptest::{bytes, mutated, split};
+
+fn message() -> impl Strategy<Value = Message> {
+    let aggregate = prop_oneof![
+        Just(Aggregate::Min),
+        Just(Aggregate::Max),
+        Just(Aggregate::Count),
+    ];
+
+    prop_oneof![
+        any::<(i32, i32)>().prop_map(|(timestamp, price)| Message::Insert { timestamp, price }),
+        any::<(i32, i32)>().prop_map(|(mintime, maxtime)| Message::Query { mintime, maxtime }),
+        prop::collection::vec(any::<(i32, i32)>(), 0..64).prop_map(Message::BatchInsert),
+        (aggregate, any::<(i32, i32)>()).prop_map(|(aggregate, (mintime, maxtime))| {
+            Message::Aggregate {
+                aggregate,
+                mintime,
+                maxtime,
+            }
+        }),
+    ]
+}
+
+fn extensions() -> impl Strategy<Value = Extensions> {
+    any::<u32>().prop_map(Extensions::from_bits)
+}
+
+fn write(messages: &[Message]) -> Vec<u8> {
+    let mut data = vec![];
+    for message in messages {
+        message.write_to(&mut data);
+    }
+    data
+}
+
+/// Decode the messages of `chunks`, received one at a time.
+fn decode(decoder: &mut MessageDecoder, chunks: &[Vec<u8>]) -> (Vec<Message>, usize) {
+    let mut src = vec![];
+    let mut messages = vec![];
+    for chunk in chunks {
+        src.extend_from_slice(chunk);
+        while let Some((message, len)) = decoder.decode(&src).unwrap() {
+            messages.push(message);
+            src.drain(..len);
+        }
+    }
+    (messages, src.len())
+}
+
+/// Decode `src` until it ends or a message is invalid, checking that
+/// the lengths are within it.
+fn decode_all(mut decoder: MessageDecoder, mut src: &[u8]) -> Result<(), TestCaseError> {
+    while let Ok(Some((_, len))) = decoder.decode(src) {
+        prop_assert!(0 < len && len <= src.len(), "{} of {}", len, src.len());
+        src = &src[len..];
+    }
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn test_round_trip(
+        (messages, chunks) in prop::collection::vec(message(), 0..16)
+            .prop_flat_map(|messages| {
+                let data = write(&messages);
+                (Just(messages), split(data))
+            }),
+    ) {
+        let (decoded, left) = decode(&mut MessageDecoder::with_extensions(true), &chunks);
+        prop_assert_eq!(messages, decoded);
+        prop_assert_eq!(0, left);
+    }
+
+    #[test]
+    fn test_round_trip_standard(
+        (timestamp, price, mintime, maxtime) in any::<(i32, i32, i32, i32)>(),
+    ) {
+        for message in [Message::Insert { timestamp, price }, Message::Query { mintime, maxtime }] {
+            let data = write(std::slice::from_ref(&message));
+            let data = <&[u8; MESSAGE_LEN]>::try_from(data.as_slice()).unwrap();
+            prop_assert_eq!(&message, &Message::parse(data).unwrap());
+            prop_assert_eq!(&message, &Message::parse_extension(data).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_round_trip_hello(
+        (messages, chunks) in (extensions(), prop::collection::vec(message(), 0..8))
+            .prop_flat_map(|(accepted, messages)| {
+                let hello = Message::Hello { version: PROTOCOL_VERSION, accepted };
+                // the extensions not accepted are not sent
+                let messages = [hello]
+                    .into_iter()
+                    .chain(messages.into_iter().filter(|message| match message {
+                        Message::BatchInsert(_) => accepted.contains(Extensions::BATCH_INSERT),
+                        Message::Aggregate { .. } => accepted.contains(Extensions::AGGREGATES),
+                        _ => true,
+                    }))
+                    .collect::<Vec<_>>();
+                let data = write(&messages);
+                (Just(messages), split(data))
+            }),
+    ) {
+        let mut decoder = MessageDecoder::with_negotiation(Extensions::ALL);
+        let (decoded, left) = decode(&mut decoder, &chunks);
+        prop_assert_eq!(&messages, &decoded);
+        prop_assert_eq!(0, left);
+        let Message::Hello { accepted, .. } = messages[0] else {
+            unreachable!("the hello first");
+        };
+        prop_assert_eq!(accepted, decoder.extensions());
+    }
+
+    #[test]
+    fn test_decode_never_panics(data in bytes(256)) {
+        for decoder in [
+            MessageDecoder::new(),
+            MessageDecoder::with_extensions(true),
+            MessageDecoder::with_negotiation(Extensions::ALL),
+        ] {
+            decode_all(decoder, &data)?;
+        }
+
+        for message in data.chunks_exact(MESSAGE_LEN) {
+            let message = message.try_into().unwrap();
+            let _ = Message::parse(message);
+            let _ = Message::parse_extension(message);
+        }
+    }
+
+    #[test]
+    fn test_decode_mutated_never_panics(
+        data in prop::collection::vec(message(), 1..8).prop_flat_map(|messages| mutated(write(&messages))),
+    ) {
+        decode_all(MessageDecoder::with_extensions(true), &data)?;
+        decode_all(MessageDecoder::with_negotiation(Extensions::ALL), &data)?;
+    }
+}
@@ -0,0 +1,263 @@
+//! Model based tests: random sessions run against every store and
+//! against a naive model, the responses must be the same.
+use proptest::prelude::*;
+
+use p02_means_to_an_end_core::{
+    Aggregate, DuplicatePolicy, Error, LimitPolicy, Message, PriceLimit, Prices, Session,
+    SharedPrices, SnapshotPrices, SpillConfig, SpillPrices, Store,
+};
+
+/// The reference: the inserted prices in a plain vector, scanned on
+/// every query.
+#[derive(Debug, Default)]
+struct Model {
+    prices: Vec<(i32, i32)>,
+    duplicate_policy: DuplicatePolicy,
+    price_limit: Option<PriceLimit>,
+}
+
+impl Model {
+    fn new(duplicate_policy: DuplicatePolicy, price_limit: Option<PriceLimit>) -> Self {
+        Self {
+            prices: vec![],
+            duplicate_policy,
+            price_limit,
+        }
+    }
+
+    fn range(&self, mintime: i32, maxtime: i32) -> impl Iterator<Item = i32> + '_ {
+        self.prices
+            .iter()
+            .filter(move |(timestamp, _)| (mintime..=maxtime).contains(timestamp))
+            .map(|(_, price)| *price)
+    }
+
+    /// The response, `Err(())` when the session must be closed.
+    fn handle(&mut self, message: &Message) -> Result<Option<i32>, ()> {
+        match *message {
+            Message::Insert { timestamp, price } => {
+                self.insert(timestamp, price)?;
+                Ok(None)
+            }
+
+            Message::BatchInsert(ref pairs) => {
+                for (timestamp, price) in pairs {
+                    self.insert(*timestamp, *price)?;
+                }
+                Ok(None)
+            }
+
+            Message::Query { mintime, maxtime } => {
+                let (count, sum) = self
+                    .range(mintime, maxtime)
+                    .fold((0_i128, 0_i128), |(count, sum), price| {
+                        (count + 1, sum + i128::from(price))
+                    });
+                Ok(Some(if count == 0 {
+                    0
+                } else {
+                    i32::try_from(sum / count).unwrap()
+                }))
+            }
+
+            Message::Aggregate {
+                aggregate,
+                mintime,
+                maxtime,
+            } => Ok(Some(match aggregate {
+                Aggregate::Min => self.range(mintime, maxtime).min().unwrap_or(0),
+                Aggregate::Max => self.range(mintime, maxtime).max().unwrap_or(0),
+                Aggregate::Count => i32::try_from(self.range(mintime, maxtime).count()).unwrap(),
+            })),
+
+            Message::Hello { accepted, .. } => Ok(Some(i32::try_from(accepted.bits()).unwrap())),
+        }
+    }
+
+    fn insert(&mut self, timestamp: i32, price: i32) -> Result<(), ()> {
+        match self
+            .prices
+            .iter_mut()
+            .find(|(other, _)| *other == timestamp)
+        {
+            None => {
+                if let Some(PriceLimit { max_prices, policy }) = self.price_limit {
+                    if self.prices.len() >= max_prices {
+                        match policy {
+                            LimitPolicy::EvictOldest => {
+                                if let Some(oldest) =
+                                    (0..self.prices.len()).min_by_key(|index| self.prices[*index].0)
+                                {
+                                    self.prices.swap_remove(oldest);
+                                }
+                            }
+                            LimitPolicy::Close => return Err(()),
+                        }
+                    }
+                }
+                self.prices.push((timestamp, price));
+            }
+            Some(entry) => match self.duplicate_policy {
+                DuplicatePolicy::Overwrite => entry.1 = price,
+                DuplicatePolicy::Ignore => {}
+                DuplicatePolicy::Close => return Err(()),
+            },
+        }
+        Ok(())
+    }
+}
+
+fn duplicate_policy() -> impl Strategy<Value = DuplicatePolicy> {
+    prop_oneof![
+        Just(DuplicatePolicy::Overwrite),
+        Just(DuplicatePolicy::Ignore),
+        Just(DuplicatePolicy::Close),
+    ]
+}
+
+// few timestamps, so that the duplicates are frequent, and any price,
+// so that the extreme ones are tried
+fn message() -> impl Strategy<Value = Message> {
+    let timestamp = -40..40;
+    let time = -50..50;
+    let aggregate = prop_oneof![
+        Just(Aggregate::Min),
+        Just(Aggregate::Max),
+        Just(Aggregate::Count),
+    ];
+
+    prop_oneof![
+        4 => (timestamp.clone(), any::<i32>())
+            .prop_map(|(timestamp, price)| Message::Insert { timestamp, price }),
+        1 => prop::collection::vec((timestamp, any::<i32>()), 0..8).prop_map(Message::BatchInsert),
+        3 => (time.clone(), time.clone())
+            .prop_map(|(mintime, maxtime)| Message::Query { mintime, maxtime }),
+        1 => (aggregate, time.clone(), time).prop_map(|(aggregate, mintime, maxtime)| {
+            Message::Aggregate {
+                aggregate,
+                mintime,
+                maxtime,
+            }
+        }),
+    ]
+}
+
+fn price_limit() -> impl Strategy<Value = PriceLimit> {
+    (
+        1_usize..16,
+        prop_oneof![Just(LimitPolicy::EvictOldest), Just(LimitPolicy::Close)],
+    )
+        .prop_map(|(max_prices, policy)| PriceLimit { max_prices, policy })
+}
+
+fn check(
+    session: Session<impl Store>,
+    duplicate_policy: DuplicatePolicy,
+    messages: &[Message],
+) -> Result<(), TestCaseError> {
+    check_model(session, Model::new(duplicate_policy, None), messages)
+}
+
+fn check_model(
+    mut session: Session<impl Store>,
+    mut model: Model,
+    messages: &[Message],
+) -> Result<(), TestCaseError> {
+    let duplicate_policy = model.duplicate_policy;
+
+    for message in messages {
+        match (model.handle(message), session.handle(message.clone())) {
+            (Ok(expected), Ok(response)) => prop_assert_eq!(expected, response, "{:?}", message),
+            (Err(()), Err(Error::DuplicateTimestamp(_) | Error::TooManyPrices(_))) => {
+                return Ok(());
+            }
+            (expected, response) => {
+                prop_assert!(false, "{message:?}: {expected:?} {response:?}");
+            }
+        }
+    }
+
+    prop_assert_eq!(
+        model.prices.len(),
+        session.len(),
+        "prices with {:?}",
+        duplicate_policy
+    );
+
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn test_prices(
+        duplicate_policy in duplicate_policy(),
+        messages in prop::collection::vec(message(), 0..200),
+    ) {
+        check(Session::with_store(Prices::new(), duplicate_policy), duplicate_policy, &messages)?;
+    }
+
+    #[test]
+    fn test_spill_prices(
+        duplicate_policy in duplicate_policy(),
+        messages in prop::collection::vec(message(), 0..200),
+    ) {
+        let store = SpillPrices::new(SpillConfig {
+            directory: std::env::temp_dir(),
+            budget: 8,
+        });
+        check(Session::with_store(store, duplicate_policy), duplicate_policy, &messages)?;
+    }
+
+    #[test]
+    fn test_snapshot_prices(
+        duplicate_policy in duplicate_policy(),
+        messages in prop::collection::vec(message(), 0..200),
+    ) {
+        let store = SnapshotPrices::new(2);
+        check(Session::with_store(store, duplicate_policy), duplicate_policy, &messages)?;
+    }
+
+    #[test]
+    fn test_shared_prices(
+        duplicate_policy in duplicate_policy(),
+        messages in prop::collection::vec(message(), 0..200),
+    ) {
+        let store = SharedPrices::new(Prices::new());
+        check(Session::with_store(store, duplicate_policy), duplicate_policy, &messages)?;
+    }
+
+    #[test]
+    fn test_price_limit(
+        duplicate_policy in duplicate_policy(),
+        price_limit in price_limit(),
+        messages in prop::collection::vec(message(), 0..200),
+    ) {
+        let session = Session::with_store(Prices::new(), duplicate_policy)
+            .with_query_cache(4)
+            .with_price_limit(price_limit);
+        check_model(session, Model::new(duplicate_policy, Some(price_limit)), &messages)?;
+    }
+
+    #[test]
+    fn test_spill_price_limit(
+        duplicate_policy in duplicate_policy(),
+        price_limit in price_limit(),
+        messages in prop::collection::vec(message(), 0..200),
+    ) {
+        let store = SpillPrices::new(SpillConfig {
+            directory: std::env::temp_dir(),
+            budget: 4,
+        });
+        let session = Session::with_store(store, duplicate_policy).with_price_limit(price_limit);
+        check_model(session, Model::new(duplicate_policy, Some(price_limit)), &messages)?;
+    }
+
+    #[test]
+    fn test_query_cache(
+        duplicate_policy in duplicate_policy(),
+        messages in prop::collection::vec(message(), 0..200),
+    ) {
+        let session = Session::with_store(Prices::new(), duplicate_policy).with_query_cache(4);
+        check(session, duplicate_policy, &messages)?;
+    }
+}
@@ -0,0 +1,118 @@
+//! The price store flags: spilling to disk, the protocol extensions,
+//! the query cache and snapshots, the cap on the prices of a session
+//! and the store shared by all the connections.
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use tracing::info;
+
+use protohackers_metrics::Registry;
+use protohackers_server::ServerArgs;
+
+use crate::{
+    Config, LimitPolicy, Metrics, PriceLimit, Prices, SharedPrices, SpillConfig, SpillPrices,
+};
+
+#[derive(clap::Args, Debug)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Args {
+    #[command(flatten)]
+    pub server: ServerArgs,
+
+    /// Spill the older prices of a session to this directory when it
+    /// exceeds the spill budget
+    #[arg(long)]
+    pub spill_directory: Option<PathBuf>,
+
+    /// Maximum number of prices of a session kept in memory when
+    /// spilling
+    #[arg(long, default_value_t = 1_000_000)]
+    pub spill_budget: usize,
+
+    /// Accept the protocol extensions: the batch inserts and the
+    /// min, max and count queries
+    #[arg(long)]
+    pub extensions: bool,
+
+    /// Let the clients negotiate the protocol extensions with a
+    /// leading hello
+    #[arg(long)]
+    pub negotiation: bool,
+
+    /// Cache up to this number of recent query results per session
+    #[arg(long)]
+    pub query_cache: Option<usize>,
+
+    /// Answer the queries of a session from a sorted snapshot after
+    /// this number of queries without inserts
+    #[arg(long)]
+    pub snapshot_after: Option<usize>,
+
+    /// Maximum number of prices of a session, the oldest ones are
+    /// evicted beyond it
+    #[arg(long)]
+    pub max_prices: Option<usize>,
+
+    /// Close the sessions beyond the maximum number of prices instead
+    /// of evicting the oldest ones
+    #[arg(long)]
+    pub close_over_limit: bool,
+
+    /// Share a single store between all the connections instead of
+    /// isolating them
+    #[arg(long)]
+    pub shared: bool,
+}
+
+/// Run the server configured by `args`.
+///
+/// # Errors
+/// * Error when the server can not be started or fails.
+pub async fn run(args: Args) -> Result<(), anyhow::Error> {
+    args.server.init_tracing();
+
+    info!("start");
+
+    let registry = Registry::new();
+    let metrics = Arc::new(Metrics::register(&registry));
+
+    let spill = args.spill_directory.map(|directory| SpillConfig {
+        directory,
+        budget: args.spill_budget,
+    });
+
+    let shared = match (args.shared, &spill) {
+        (false, _) => None,
+        (true, None) => Some(SharedPrices::new(Prices::new())),
+        (true, Some(spill)) => Some(SharedPrices::new(SpillPrices::new(spill.clone()))),
+    };
+
+    let config = Config {
+        spill,
+        shared,
+        extensions: args.extensions,
+        negotiation: args.negotiation,
+        query_cache: args.query_cache,
+        snapshot: args.snapshot_after,
+        price_limit: args.max_prices.map(|max_prices| PriceLimit {
+            max_prices,
+            policy: if args.close_over_limit {
+                LimitPolicy::Close
+            } else {
+                LimitPolicy::EvictOldest
+            },
+        }),
+        metrics,
+        ..Config::default()
+    };
+
+    args.server
+        .server_with_metrics(&registry)
+        .await?
+        .with_problem(env!("CARGO_PKG_NAME"))
+        .with_default_idle_timeout(crate::IDLE_TIMEOUT)
+        .serve(move |socket, _| crate::handler_with_config(socket, config.clone()))
+        .await?;
+
+    Ok(())
+}
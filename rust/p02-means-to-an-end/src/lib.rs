@@ -65,7 +65,7 @@
 //! * While rare, prices can go negative.
 //!
 //! * Behaviour is undefined if there are multiple prices with the
-//! same timestamp from the same client.
+//!   same timestamp from the same client.
 //!
 //! For example, to insert a price of 101 pence at timestamp 12345, a
 //! client would send:
@@ -152,66 +152,334 @@
 //! Where a client triggers undefined behaviour, the server can do
 //! anything it likes for that client, but must not adversely affect
 //! other clients that did not trigger undefined behaviour.
-use std::collections::BTreeMap;
-use std::mem;
+use tracing::{debug, info, warn};
 
-use tracing::{debug, warn};
+use std::panic;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
+use tokio::task;
+
+use protohackers_runtime::tokio::Compat;
+use protohackers_server::IdleTimeout;
+
+pub mod cli;
+pub mod metrics;
+
+pub use metrics::Metrics;
+pub use p02_means_to_an_end_core::{
+    handle_received, Aggregate, DuplicatePolicy, Error, Extensions, Handler, LimitPolicy, Message,
+    MessageDecoder, PriceLimit, Prices, QueryCache, Session, SharedPrices, SnapshotPrices,
+    SpillConfig, SpillPrices, Stats, Store, BATCH_INSERT, HELLO, MESSAGE_LEN, PROTOCOL_VERSION,
+};
+
+/// The bytes read at once.
+const READ_LEN: usize = 4 * 1024;
+
+/// The default idle timeout of the connections.
+pub const IDLE_TIMEOUT: Duration = Duration::from_mins(1);
+
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    pub duplicate_policy: DuplicatePolicy,
+
+    /// Spill the older prices of the large sessions to disk, the
+    /// sessions are kept in memory when `None`.
+    pub spill: Option<SpillConfig>,
+
+    /// Accept the protocol extensions: the batch inserts and the
+    /// aggregate queries.
+    pub extensions: bool,
+
+    /// Let the clients negotiate the protocol extensions with a
+    /// leading hello, ignored when `extensions` is set.
+    pub negotiation: bool,
+
+    /// Cache up to this number of recent query results per session.
+    pub query_cache: Option<usize>,
+
+    /// Answer the queries from a sorted snapshot with the prefix sums
+    /// after this number of queries without inserts, ignored when
+    /// spilling.
+    pub snapshot: Option<usize>,
+
+    /// Limit the number of prices of every session, with a shared
+    /// store the limit is on all the prices.
+    pub price_limit: Option<PriceLimit>,
+
+    /// Share a single store between all the sessions instead of
+    /// isolating them, `query_cache` is ignored and `spill` only tells
+    /// that the store spills.
+    pub shared: Option<SharedPrices>,
+
+    pub metrics: Arc<Metrics>,
+}
 
 /// Handle a client session.
 ///
 /// # Errors
 /// * Error when socket returns and error.
+pub async fn handler(stream: IdleTimeout<TcpStream>) -> Result<(), anyhow::Error> {
+    handler_with_config(stream, Config::default()).await
+}
+
+/// Handle a client session, using the given configuration.
+///
+/// # Errors
+/// * Error when socket returns and error.
 #[tracing::instrument(skip(stream))]
-pub async fn handler(mut stream: TcpStream) -> Result<(), anyhow::Error> {
+pub async fn handler_with_config(
+    stream: IdleTimeout<TcpStream>,
+    config: Config,
+) -> Result<(), anyhow::Error> {
+    let decoder = if config.negotiation && !config.extensions {
+        MessageDecoder::with_negotiation(Extensions::ALL)
+    } else {
+        MessageDecoder::with_extensions(config.extensions)
+    };
+    if let Some(shared) = config.shared {
+        let session = Session::with_store(shared, config.duplicate_policy);
+        run(
+            stream,
+            decoder,
+            with_price_limit(session, config.price_limit),
+            &config.metrics,
+            true,
+            config.spill.is_some(),
+        )
+        .await
+    } else if let Some(spill) = config.spill {
+        let session = Session::with_store(SpillPrices::new(spill), config.duplicate_policy);
+        run(
+            stream,
+            decoder,
+            with_price_limit(
+                with_query_cache(session, config.query_cache),
+                config.price_limit,
+            ),
+            &config.metrics,
+            false,
+            true,
+        )
+        .await
+    } else if let Some(threshold) = config.snapshot {
+        let session = Session::with_store(SnapshotPrices::new(threshold), config.duplicate_policy);
+        run(
+            stream,
+            decoder,
+            with_price_limit(
+                with_query_cache(session, config.query_cache),
+                config.price_limit,
+            ),
+            &config.metrics,
+            false,
+            false,
+        )
+        .await
+    } else {
+        let session = Session::with_store(Prices::new(), config.duplicate_policy);
+        run(
+            stream,
+            decoder,
+            with_price_limit(
+                with_query_cache(session, config.query_cache),
+                config.price_limit,
+            ),
+            &config.metrics,
+            false,
+            false,
+        )
+        .await
+    }
+}
+
+fn with_query_cache<S: Store>(session: Session<S>, capacity: Option<usize>) -> Session<S> {
+    if let Some(capacity) = capacity {
+        session.with_query_cache(capacity)
+    } else {
+        session
+    }
+}
+
+fn with_price_limit<S: Store>(session: Session<S>, price_limit: Option<PriceLimit>) -> Session<S> {
+    if let Some(price_limit) = price_limit {
+        session.with_price_limit(price_limit)
+    } else {
+        session
+    }
+}
+
+/// Run a session, on the blocking threads when its store does
+/// blocking file I/O.
+async fn run<S: Store + Send + 'static>(
+    mut stream: IdleTimeout<TcpStream>,
+    decoder: MessageDecoder,
+    mut session: Session<S>,
+    metrics: &Arc<Metrics>,
+    shared: bool,
+    blocking: bool,
+) -> Result<(), anyhow::Error> {
     debug!("start");
 
-    let mut items = BTreeMap::new();
+    metrics.session();
 
-    let (read_half, mut write_half) = stream.split();
-    let mut read_half = BufReader::new(read_half);
+    let result = if blocking {
+        let (served, result) =
+            serve_blocking(&mut stream, decoder, session, Arc::clone(metrics), shared).await;
+        session = served;
+        result
+    } else {
+        serve(&mut stream, decoder, &mut session, metrics, shared).await
+    };
 
-    let mut command = [0; 9];
-    loop {
-        read_half.read_exact(&mut command).await?;
+    if !shared {
+        metrics.prices(session.len(), 0);
+    }
 
-        match (
-            command[0],
-            i32::from_be_bytes(command[1..=mem::size_of::<i32>()].try_into().unwrap()),
-            i32::from_be_bytes(command[1 + mem::size_of::<i32>()..].try_into().unwrap()),
-        ) {
-            (b'I', timestamp, price) => {
-                debug!("I {timestamp} {price}");
-                items.insert(timestamp, price);
-            }
-            (b'Q', mintime, maxtime) if maxtime >= mintime => {
-                let mut count = 0;
-                let mut sum = 0;
-                for (_, value) in items.range(mintime..=maxtime) {
-                    count += 1;
-                    sum += i64::from(*value);
-                }
-                let mean = if count > 0 { sum / count } else { 0 };
-                debug!("Q {mintime} {maxtime}: {mean}");
-                write_half.write_i32(i32::try_from(mean)?).await?;
-            }
-            (b'Q', _, _) => {
-                debug!("Q invalid range");
-                write_half.write_i32(0).await?;
+    let stats = session.stats();
+    info!(
+        "session stats: inserts: {} queries: {} invalid range queries: {} evicted: {} prices: {}",
+        stats.inserts,
+        stats.queries,
+        stats.invalid_range_queries,
+        stats.evicted,
+        session.len()
+    );
+
+    result?;
+
+    stream.flush().await?;
+    stream.shutdown().await?;
+
+    debug!("done");
+
+    Ok(())
+}
+
+/// The session metrics, around the handling of every message.
+struct MetricsHandler<'a> {
+    metrics: &'a Metrics,
+    shared: bool,
+}
+
+impl<S: Store> Handler<S> for MetricsHandler<'_> {
+    fn handle(&mut self, session: &mut Session<S>, message: Message) -> Result<Option<i32>, Error> {
+        debug!("{message:?}");
+
+        let (stats, len, start) = (session.stats(), session.len(), Instant::now());
+
+        let is_query = matches!(message, Message::Query { .. } | Message::Aggregate { .. });
+        let result = session.handle(message);
+        if is_query {
+            self.metrics.query(start.elapsed());
+        }
+
+        self.metrics.stats(stats, session.stats());
+        if self.shared {
+            self.metrics.set_prices(session.len());
+        } else {
+            self.metrics.prices(len, session.len());
+        }
+
+        if let Ok(Some(mean)) = result {
+            debug!("mean: {mean}");
+        }
+
+        result
+    }
+}
+
+async fn serve(
+    stream: &mut IdleTimeout<TcpStream>,
+    decoder: MessageDecoder,
+    session: &mut Session<impl Store>,
+    metrics: &Metrics,
+    shared: bool,
+) -> Result<(), anyhow::Error> {
+    let (read_half, write_half) = stream.split();
+    let mut handler = MetricsHandler { metrics, shared };
+
+    closed(
+        p02_means_to_an_end_core::serve(
+            Compat::new(read_half),
+            Compat::new(write_half),
+            decoder,
+            session,
+            &mut handler,
+        )
+        .await,
+    )
+}
+
+/// [`serve`] with the messages of every read handled on the blocking
+/// threads, giving back the session.
+async fn serve_blocking<S: Store + Send + 'static>(
+    stream: &mut IdleTimeout<TcpStream>,
+    mut decoder: MessageDecoder,
+    session: Session<S>,
+    metrics: Arc<Metrics>,
+    shared: bool,
+) -> (Session<S>, Result<(), anyhow::Error>) {
+    let mut session = Some(session);
+    let result = async {
+        let mut received = Vec::with_capacity(READ_LEN);
+        let mut responses = vec![];
+
+        loop {
+            let len = received.len();
+            received.resize(len + READ_LEN, 0);
+            let read = stream.read(&mut received[len..]).await?;
+            received.truncate(len + read);
+            if read == 0 {
+                return Ok(());
             }
-            _ => {
-                warn!("invalid request");
-                break;
+
+            let (mut owned, metrics) = (session.take().expect("session"), Arc::clone(&metrics));
+            let (len, result);
+            (decoder, owned, received, responses, (len, result)) =
+                task::spawn_blocking(move || {
+                    let mut handler = MetricsHandler {
+                        metrics: &metrics,
+                        shared,
+                    };
+                    let step = handle_received(
+                        &mut decoder,
+                        &received,
+                        &mut owned,
+                        &mut handler,
+                        &mut responses,
+                    );
+                    (decoder, owned, received, responses, step)
+                })
+                .await
+                .unwrap_or_else(|err| panic::resume_unwind(err.into_panic()));
+            session = Some(owned);
+            received.drain(..len);
+
+            if !responses.is_empty() {
+                stream.write_all(&responses).await?;
+                stream.flush().await?;
+                responses.clear();
             }
+            closed(result)?;
         }
     }
+    .await;
 
-    write_half.flush().await?;
-    write_half.shutdown().await?;
-
-    debug!("done");
+    (session.expect("session"), result)
+}
 
-    Ok(())
+/// The result of a session closed by `result`: an invalid request is
+/// not an error of the server.
+fn closed(result: Result<(), Error>) -> Result<(), anyhow::Error> {
+    match result {
+        Err(Error::Io(err)) => Err(err.into()),
+        Err(err) => {
+            warn!("invalid request: {err}");
+            Ok(())
+        }
+        Ok(()) => Ok(()),
+    }
 }
@@ -0,0 +1,178 @@
+use std::fmt;
+use std::time::Duration;
+
+use protohackers_metrics::{Counter, Gauge, Histogram, Registry};
+
+use p02_means_to_an_end_core::Stats;
+
+pub use protohackers_metrics::LATENCY_BUCKETS;
+
+/// The server metrics, the aggregation of all the sessions.
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    sessions: Counter,
+    inserts: Counter,
+    queries: Counter,
+    invalid_range_queries: Counter,
+    evicted: Counter,
+    prices: Gauge,
+    query_latency: Histogram,
+}
+
+impl Metrics {
+    /// The metrics in a registry of their own.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::register(&Registry::new())
+    }
+
+    #[must_use]
+    pub fn register(registry: &Registry) -> Self {
+        Self {
+            sessions: registry.counter("sessions_total", "The sessions"),
+            inserts: registry.counter("inserts_total", "The inserted prices"),
+            queries: registry.counter("queries_total", "The answered queries"),
+            invalid_range_queries: registry.counter(
+                "invalid_range_queries_total",
+                "The queries with the minimum time after the maximum one",
+            ),
+            evicted: registry.counter("evicted_prices_total", "The prices evicted over the limit"),
+            prices: registry.gauge("prices", "The prices stored"),
+            query_latency: registry.histogram(
+                "query_latency_seconds",
+                "The time to answer a query",
+                &LATENCY_BUCKETS,
+            ),
+        }
+    }
+
+    pub fn session(&self) {
+        self.sessions.inc();
+    }
+
+    /// Account the difference between two stats of the same session.
+    pub fn stats(&self, before: Stats, after: Stats) {
+        self.inserts.add(after.inserts - before.inserts);
+        self.queries.add(after.queries - before.queries);
+        self.invalid_range_queries
+            .add(after.invalid_range_queries - before.invalid_range_queries);
+        self.evicted.add(after.evicted - before.evicted);
+    }
+
+    /// Account the change of the number of prices stored by a
+    /// session.
+    pub fn prices(&self, before: usize, after: usize) {
+        if after > before {
+            self.prices.add(to_i64(after - before));
+        } else {
+            self.prices.sub(to_i64(before - after));
+        }
+    }
+
+    /// Set the number of prices of the store shared by all the
+    /// sessions.
+    pub fn set_prices(&self, prices: usize) {
+        self.prices.set(to_i64(prices));
+    }
+
+    pub fn query(&self, latency: Duration) {
+        self.query_latency.record(latency);
+    }
+
+    #[must_use]
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            sessions: self.sessions.get(),
+            inserts: self.inserts.get(),
+            queries: self.queries.get(),
+            invalid_range_queries: self.invalid_range_queries.get(),
+            evicted: self.evicted.get(),
+            prices: u64::try_from(self.prices.get()).unwrap_or_default(),
+            query_latency: self.query_latency.snapshot().counts,
+        }
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn to_i64(n: usize) -> i64 {
+    i64::try_from(n).unwrap_or(i64::MAX)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub sessions: u64,
+    pub inserts: u64,
+    pub queries: u64,
+    pub invalid_range_queries: u64,
+    pub evicted: u64,
+
+    /// The prices stored by the open sessions.
+    pub prices: u64,
+
+    pub query_latency: Vec<u64>,
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "sessions: {} inserts: {} queries: {} invalid range queries: {} evicted: {} prices: {} query latency:",
+            self.sessions,
+            self.inserts,
+            self.queries,
+            self.invalid_range_queries,
+            self.evicted,
+            self.prices
+        )?;
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(&self.query_latency) {
+            write!(f, " <={bound:?}: {count}")?;
+        }
+        write!(
+            f,
+            " >{:?}: {}",
+            LATENCY_BUCKETS[LATENCY_BUCKETS.len() - 1],
+            self.query_latency[LATENCY_BUCKETS.len()]
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics() {
+        let metrics = Metrics::new();
+        metrics.session();
+        metrics.stats(
+            Stats::default(),
+            Stats {
+                inserts: 3,
+                queries: 2,
+                invalid_range_queries: 1,
+                evicted: 2,
+            },
+        );
+        metrics.prices(0, 3);
+        metrics.prices(3, 1);
+        metrics.query(Duration::from_micros(50));
+
+        assert_eq!(
+            Snapshot {
+                sessions: 1,
+                inserts: 3,
+                queries: 2,
+                invalid_range_queries: 1,
+                evicted: 2,
+                prices: 1,
+                query_latency: vec![0, 1, 0, 0, 0, 0, 0],
+            },
+            metrics.snapshot()
+        );
+    }
+}
@@ -41,7 +41,422 @@ async fn test_session() {
     assert_eq!(result, 101);
 }
 
+#[tokio::test]
+async fn test_pipelined_queries() {
+    let (address, port) = spawn_app().await;
+
+    let mut stream = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+
+    let mut buffer = vec![];
+    for timestamp in 0..1_000 {
+        buffer.push(b'I');
+        buffer.extend(i32::to_be_bytes(timestamp));
+        buffer.extend(i32::to_be_bytes(timestamp * 2));
+    }
+    for timestamp in 0..1_000 {
+        buffer.push(b'Q');
+        buffer.extend(i32::to_be_bytes(timestamp));
+        buffer.extend(i32::to_be_bytes(timestamp));
+    }
+    stream.write_all(&buffer).await.unwrap();
+    stream.shutdown().await.unwrap();
+
+    let mut responses = vec![];
+    stream.read_to_end(&mut responses).await.unwrap();
+
+    assert_eq!(
+        (0..1_000)
+            .map(|timestamp| timestamp * 2)
+            .collect::<Vec<_>>(),
+        responses
+            .chunks_exact(4)
+            .map(|response| i32::from_be_bytes(response.try_into().unwrap()))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[tokio::test]
+async fn test_session_spill() {
+    let (address, port) = spawn_app_with_config(p02_means_to_an_end::Config {
+        spill: Some(p02_means_to_an_end::SpillConfig {
+            directory: std::env::temp_dir(),
+            budget: 2,
+        }),
+        ..p02_means_to_an_end::Config::default()
+    })
+    .await;
+
+    let mut stream = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+    let (read_half, write_half) = stream.split();
+    let mut read_half = BufReader::new(read_half);
+    let mut write_half = BufWriter::new(write_half);
+
+    for (timestamp, price) in [(12345, 101), (12346, 102), (12347, 100), (40960, 5)] {
+        Request(b'I', timestamp, price)
+            .write(&mut write_half)
+            .await
+            .unwrap();
+    }
+    Request(b'Q', 12288, 16384)
+        .write(&mut write_half)
+        .await
+        .unwrap();
+
+    let result = read_half.read_i32().await.unwrap();
+    assert_eq!(result, 101);
+}
+
+#[tokio::test]
+async fn test_session_query_cache() {
+    let (address, port) = spawn_app_with_config(p02_means_to_an_end::Config {
+        query_cache: Some(8),
+        ..p02_means_to_an_end::Config::default()
+    })
+    .await;
+
+    let mut stream = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+    let (read_half, write_half) = stream.split();
+    let mut read_half = BufReader::new(read_half);
+    let mut write_half = BufWriter::new(write_half);
+
+    for request in [
+        Request(b'I', 12345, 101),
+        Request(b'Q', 12288, 16384),
+        Request(b'Q', 12288, 16384),
+        Request(b'I', 12346, 103),
+        Request(b'Q', 12288, 16384),
+    ] {
+        request.write(&mut write_half).await.unwrap();
+    }
+
+    for expected in [101, 101, 102] {
+        assert_eq!(expected, read_half.read_i32().await.unwrap());
+    }
+}
+
+#[tokio::test]
+async fn test_session_snapshot() {
+    let (address, port) = spawn_app_with_config(p02_means_to_an_end::Config {
+        snapshot: Some(1),
+        ..p02_means_to_an_end::Config::default()
+    })
+    .await;
+
+    let mut stream = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+    let (read_half, write_half) = stream.split();
+    let mut read_half = BufReader::new(read_half);
+    let mut write_half = BufWriter::new(write_half);
+
+    for request in [
+        Request(b'I', 12345, 101),
+        Request(b'Q', 12288, 16384),
+        Request(b'I', 12346, 103),
+        Request(b'Q', 12288, 16384),
+        Request(b'Q', 12346, 16384),
+    ] {
+        request.write(&mut write_half).await.unwrap();
+    }
+
+    for expected in [101, 102, 103] {
+        assert_eq!(expected, read_half.read_i32().await.unwrap());
+    }
+}
+
+#[tokio::test]
+async fn test_price_limit() {
+    let (address, port) = spawn_app_with_config(p02_means_to_an_end::Config {
+        price_limit: Some(p02_means_to_an_end::PriceLimit {
+            max_prices: 2,
+            policy: p02_means_to_an_end::LimitPolicy::EvictOldest,
+        }),
+        ..p02_means_to_an_end::Config::default()
+    })
+    .await;
+
+    let mut stream = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+    let (read_half, write_half) = stream.split();
+    let mut read_half = BufReader::new(read_half);
+    let mut write_half = BufWriter::new(write_half);
+
+    for request in [
+        Request(b'I', 12345, 101),
+        Request(b'I', 12346, 102),
+        Request(b'I', 12347, 100),
+        Request(b'Q', 12288, 16384),
+    ] {
+        request.write(&mut write_half).await.unwrap();
+    }
+
+    assert_eq!(101, read_half.read_i32().await.unwrap());
+}
+
+#[tokio::test]
+async fn test_price_limit_close() {
+    let (address, port) = spawn_app_with_config(p02_means_to_an_end::Config {
+        price_limit: Some(p02_means_to_an_end::PriceLimit {
+            max_prices: 1,
+            policy: p02_means_to_an_end::LimitPolicy::Close,
+        }),
+        ..p02_means_to_an_end::Config::default()
+    })
+    .await;
+
+    let mut stream = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+
+    for request in [
+        Request(b'I', 12345, 101),
+        Request(b'I', 12346, 102),
+        Request(b'Q', 12288, 16384),
+    ] {
+        request.write(&mut stream).await.unwrap();
+    }
+
+    let mut response = vec![];
+    stream.read_to_end(&mut response).await.unwrap();
+    assert!(response.is_empty());
+}
+
+#[tokio::test]
+async fn test_batch_insert() {
+    let (address, port) = spawn_app_with_config(p02_means_to_an_end::Config {
+        extensions: true,
+        ..p02_means_to_an_end::Config::default()
+    })
+    .await;
+
+    let mut stream = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+    let (read_half, write_half) = stream.split();
+    let mut read_half = BufReader::new(read_half);
+    let mut write_half = BufWriter::new(write_half);
+
+    let pairs = [(12345, 101), (12346, 102), (12347, 100), (40960, 5)];
+    write_half
+        .write_u8(p02_means_to_an_end::BATCH_INSERT)
+        .await
+        .unwrap();
+    write_half
+        .write_u16(u16::try_from(pairs.len()).unwrap())
+        .await
+        .unwrap();
+    for (timestamp, price) in pairs {
+        write_half.write_i32(timestamp).await.unwrap();
+        write_half.write_i32(price).await.unwrap();
+    }
+    Request(b'Q', 12288, 16384)
+        .write(&mut write_half)
+        .await
+        .unwrap();
+
+    let result = read_half.read_i32().await.unwrap();
+    assert_eq!(result, 101);
+}
+
+#[tokio::test]
+async fn test_aggregate() {
+    let (address, port) = spawn_app_with_config(p02_means_to_an_end::Config {
+        extensions: true,
+        ..p02_means_to_an_end::Config::default()
+    })
+    .await;
+
+    let mut stream = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+    let (read_half, write_half) = stream.split();
+    let mut read_half = BufReader::new(read_half);
+    let mut write_half = BufWriter::new(write_half);
+
+    for request in [
+        Request(b'I', 12345, 101),
+        Request(b'I', 12346, 102),
+        Request(b'I', 12347, 100),
+        Request(b'I', 40960, 5),
+        Request(b'm', 12288, 16384),
+        Request(b'M', 12288, 16384),
+        Request(b'C', 12288, 16384),
+        Request(b'Q', 12288, 16384),
+    ] {
+        request.write(&mut write_half).await.unwrap();
+    }
+
+    for expected in [100, 102, 3, 101] {
+        assert_eq!(expected, read_half.read_i32().await.unwrap());
+    }
+}
+
+#[tokio::test]
+async fn test_negotiation() {
+    let (address, port) = spawn_app_with_config(p02_means_to_an_end::Config {
+        negotiation: true,
+        ..p02_means_to_an_end::Config::default()
+    })
+    .await;
+
+    let mut stream = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+    let (read_half, write_half) = stream.split();
+    let mut read_half = BufReader::new(read_half);
+    let mut write_half = BufWriter::new(write_half);
+
+    Request(
+        p02_means_to_an_end::HELLO,
+        p02_means_to_an_end::PROTOCOL_VERSION,
+        i32::try_from(p02_means_to_an_end::Extensions::AGGREGATES.bits()).unwrap(),
+    )
+    .write(&mut write_half)
+    .await
+    .unwrap();
+    assert_eq!(
+        p02_means_to_an_end::Extensions::AGGREGATES,
+        p02_means_to_an_end::Extensions::from_bits(read_half.read_u32().await.unwrap())
+    );
+
+    for request in [
+        Request(b'I', 12345, 101),
+        Request(b'I', 12346, 102),
+        Request(b'M', 12288, 16384),
+    ] {
+        request.write(&mut write_half).await.unwrap();
+    }
+    assert_eq!(102, read_half.read_i32().await.unwrap());
+}
+
+#[tokio::test]
+async fn test_negotiation_skipped() {
+    let (address, port) = spawn_app_with_config(p02_means_to_an_end::Config {
+        negotiation: true,
+        ..p02_means_to_an_end::Config::default()
+    })
+    .await;
+
+    let mut stream = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+
+    for request in [
+        Request(b'I', 12345, 101),
+        Request(b'Q', 12288, 16384),
+        Request(b'M', 12288, 16384),
+    ] {
+        request.write(&mut stream).await.unwrap();
+    }
+
+    let mut response = vec![];
+    stream.read_to_end(&mut response).await.unwrap();
+    assert_eq!(101_i32.to_be_bytes().as_slice(), response);
+}
+
+#[tokio::test]
+async fn test_batch_insert_not_enabled() {
+    let (address, port) = spawn_app().await;
+
+    let mut stream = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+
+    stream
+        .write_all(&[p02_means_to_an_end::BATCH_INSERT, 0, 0, 0, 0, 0, 0, 0, 0])
+        .await
+        .unwrap();
+
+    let mut buffer = vec![];
+    stream.read_to_end(&mut buffer).await.unwrap();
+    assert!(buffer.is_empty());
+}
+
+#[tokio::test]
+async fn test_shared() {
+    let (address, port) = spawn_app_with_config(p02_means_to_an_end::Config {
+        shared: Some(p02_means_to_an_end::SharedPrices::new(
+            p02_means_to_an_end::Prices::new(),
+        )),
+        ..p02_means_to_an_end::Config::default()
+    })
+    .await;
+
+    let mut writer = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+    let mut write_half = BufWriter::new(&mut writer);
+    Request(b'I', 12345, 101)
+        .write(&mut write_half)
+        .await
+        .unwrap();
+    Request(b'Q', 12345, 12345)
+        .write(&mut write_half)
+        .await
+        .unwrap();
+    assert_eq!(101, writer.read_i32().await.unwrap());
+    writer.shutdown().await.unwrap();
+
+    let mut reader = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+    let mut write_half = BufWriter::new(&mut reader);
+    Request(b'Q', 12288, 16384)
+        .write(&mut write_half)
+        .await
+        .unwrap();
+    assert_eq!(101, reader.read_i32().await.unwrap());
+}
+
+#[tokio::test]
+async fn test_metrics() {
+    let metrics = std::sync::Arc::new(p02_means_to_an_end::Metrics::new());
+    let (address, port) = spawn_app_with_config(p02_means_to_an_end::Config {
+        metrics: metrics.clone(),
+        ..p02_means_to_an_end::Config::default()
+    })
+    .await;
+
+    let mut stream = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .expect("cannot connect");
+
+    let mut write_half = BufWriter::new(&mut stream);
+    for request in [
+        Request(b'I', 1, 10),
+        Request(b'I', 2, 20),
+        Request(b'Q', 1, 2),
+        Request(b'Q', 2, 1),
+        Request(b'X', 0, 0),
+    ] {
+        request.write(&mut write_half).await.unwrap();
+    }
+
+    let mut buffer = vec![];
+    stream.read_to_end(&mut buffer).await.unwrap();
+    assert_eq!(vec![0, 0, 0, 15, 0, 0, 0, 0], buffer);
+
+    let snapshot = metrics.snapshot();
+    assert_eq!(1, snapshot.sessions);
+    assert_eq!(2, snapshot.inserts);
+    assert_eq!(2, snapshot.queries);
+    assert_eq!(1, snapshot.invalid_range_queries);
+    assert_eq!(0, snapshot.prices);
+    assert_eq!(2, snapshot.query_latency.iter().sum::<u64>());
+}
+
 async fn spawn_app() -> (String, u16) {
+    spawn_app_with_config(p02_means_to_an_end::Config::default()).await
+}
+
+async fn spawn_app_with_config(config: p02_means_to_an_end::Config) -> (String, u16) {
     static TRACING_SUBSCRIBER_INIT: Once = Once::new();
     TRACING_SUBSCRIBER_INIT.call_once(tracing_subscriber::fmt::init);
 
@@ -59,7 +474,9 @@ async fn spawn_app() -> (String, u16) {
         loop {
             let (socket, _) = listener.accept().await.expect("cannot accept");
 
-            p02_means_to_an_end::handler(socket).await.unwrap();
+            p02_means_to_an_end::handler_with_config(socket.into(), config.clone())
+                .await
+                .unwrap();
         }
     });
 
@@ -0,0 +1,146 @@
+//! Budget chat, the runtime agnostic core.
+//!
+//! The room membership and the texts of the protocol, without any
+//! network I/O: the tokio and the WASI servers only move the lines
+//! between the clients.
+use std::sync::Arc;
+
+use thiserror::Error;
+
+pub mod text;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum JoinError {
+    #[error("invalid username")]
+    InvalidUsername,
+
+    #[error("username already taken")]
+    UsernameTaken,
+
+    #[error("client already joined")]
+    AlreadyJoined,
+}
+
+/// A username must be non empty and alphanumeric.
+#[must_use]
+pub fn is_username_valid(username: &str) -> bool {
+    !username.is_empty() && username.chars().all(char::is_alphanumeric)
+}
+
+/// The joined clients, in join order.
+#[derive(Debug)]
+pub struct Room<Id> {
+    members: Vec<(Id, Arc<str>)>,
+}
+
+impl<Id> Default for Room<Id> {
+    fn default() -> Self {
+        Self { members: vec![] }
+    }
+}
+
+impl<Id: Copy + PartialEq> Room<Id> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Join the client `id` as `username`.
+    ///
+    /// # Errors
+    /// * Error when the username is invalid or already taken, or the
+    ///   client is already joined.
+    pub fn join(&mut self, id: Id, username: &str) -> Result<Arc<str>, JoinError> {
+        if !is_username_valid(username) {
+            return Err(JoinError::InvalidUsername);
+        }
+        if self.is_joined(id) {
+            return Err(JoinError::AlreadyJoined);
+        }
+        if self.members.iter().any(|(_, other)| **other == *username) {
+            return Err(JoinError::UsernameTaken);
+        }
+
+        let username = Arc::<str>::from(username);
+        self.members.push((id, username.clone()));
+
+        Ok(username)
+    }
+
+    /// Remove the client `id`, returning its username when joined.
+    pub fn leave(&mut self, id: Id) -> Option<Arc<str>> {
+        let index = self.members.iter().position(|(other, _)| *other == id)?;
+        Some(self.members.remove(index).1)
+    }
+
+    #[must_use]
+    pub fn username(&self, id: Id) -> Option<&Arc<str>> {
+        self.members
+            .iter()
+            .find_map(|(other, username)| (*other == id).then_some(username))
+    }
+
+    #[must_use]
+    pub fn is_joined(&self, id: Id) -> bool {
+        self.username(id).is_some()
+    }
+
+    /// The joined clients but `id`.
+    pub fn others(&self, id: Id) -> impl Iterator<Item = (Id, &Arc<str>)> {
+        self.members
+            .iter()
+            .filter(move |(other, _)| *other != id)
+            .map(|(other, username)| (*other, username))
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_username_valid() {
+        assert!(is_username_valid("bob"));
+        assert!(is_username_valid("Bob42"));
+        assert!(!is_username_valid(""));
+        assert!(!is_username_valid("bob smith"));
+        assert!(!is_username_valid("bob!"));
+    }
+
+    #[test]
+    fn test_join_and_leave() {
+        let mut room = Room::new();
+
+        assert_eq!(Ok("bob".into()), room.join(1, "bob"));
+        assert_eq!(Ok("alice".into()), room.join(2, "alice"));
+
+        assert_eq!(Err(JoinError::UsernameTaken), room.join(3, "bob"));
+        assert_eq!(Err(JoinError::InvalidUsername), room.join(3, ""));
+        assert_eq!(Err(JoinError::AlreadyJoined), room.join(1, "dave"));
+
+        assert_eq!(
+            vec!["alice"],
+            room.others(1)
+                .map(|(_, username)| &**username)
+                .collect::<Vec<_>>()
+        );
+
+        assert_eq!(Some("bob".into()), room.leave(1));
+        assert_eq!(None, room.leave(1));
+        assert!(!room.is_joined(1));
+        assert_eq!(1, room.len());
+
+        // the username is free again
+        assert_eq!(Ok("bob".into()), room.join(3, "bob"));
+    }
+}
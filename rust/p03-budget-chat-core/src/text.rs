@@ -0,0 +1,53 @@
+//! The lines sent to the clients, without the trailing `\n`.
+
+pub const WELCOME: &str = "Welcome to budgetchat! What shall I call you?";
+
+pub const INVALID_USERNAME: &str = "Invalid username";
+
+/// The presence notification, for the client just joined.
+#[must_use]
+pub fn room_contains<'a>(usernames: impl IntoIterator<Item = &'a str>) -> String {
+    let mut line = String::from("* The room contains:");
+    for (i, username) in usernames.into_iter().enumerate() {
+        line.push_str(if i == 0 { " " } else { ", " });
+        line.push_str(username);
+    }
+    line
+}
+
+#[must_use]
+pub fn entered(username: &str) -> String {
+    format!("* {username} has entered the room")
+}
+
+#[must_use]
+pub fn left(username: &str) -> String {
+    format!("* {username} has left the room")
+}
+
+#[must_use]
+pub fn message(username: &str, message: &str) -> String {
+    format!("[{username}] {message}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_room_contains() {
+        assert_eq!("* The room contains:", room_contains([]));
+        assert_eq!("* The room contains: bob", room_contains(["bob"]));
+        assert_eq!(
+            "* The room contains: bob, charlie, dave",
+            room_contains(["bob", "charlie", "dave"])
+        );
+    }
+
+    #[test]
+    fn test_notifications() {
+        assert_eq!("* bob has entered the room", entered("bob"));
+        assert_eq!("* bob has left the room", left("bob"));
+        assert_eq!("[bob] hi alice", message("bob", "hi alice"));
+    }
+}
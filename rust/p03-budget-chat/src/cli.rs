@@ -0,0 +1,28 @@
+//! The chat server takes only the standard [`ServerArgs`].
+use tracing::info;
+
+use protohackers_server::ServerArgs;
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[command(flatten)]
+    pub server: ServerArgs,
+}
+
+/// Run the server configured by `args`.
+///
+/// # Errors
+/// * Error when the server can not be started or fails.
+pub async fn run(args: Args) -> Result<(), anyhow::Error> {
+    args.server.init_tracing();
+
+    info!("start");
+
+    crate::serve(
+        args.server
+            .server()
+            .await?
+            .with_problem(env!("CARGO_PKG_NAME")),
+    )
+    .await
+}
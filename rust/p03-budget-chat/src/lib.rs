@@ -152,26 +152,39 @@
 //!
 //! - Make sure you support at least 10 simultaneous clients.
 use std::collections::HashMap;
-use std::fmt::Write;
 use std::marker::PhantomData;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::{
     tcp::{ReadHalf, WriteHalf},
-    TcpListener, TcpStream,
+    TcpStream,
 };
+use tokio::sync::mpsc::error::SendError;
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 
 use tracing::{debug, error, info, warn};
 
 use thiserror::Error;
 
+use protohackers_server::{IdleTimeout, Listeners, Server};
+
+use p03_budget_chat_core::{text, Room};
+
+pub mod cli;
+
+/// The default idle timeout of the connections, a quiet room is not
+/// a dead connection.
+pub const IDLE_TIMEOUT: Duration = Duration::from_mins(10);
+
 type ID = usize;
 type Username = String;
 type Message = String;
 
 enum ClientMessage {
+    Connected(ID, UnboundedSender<ServerMessage>),
     SetUsername(ID, Username),
     Joined(ID),
     Message(ID, Arc<Message>),
@@ -183,22 +196,10 @@ enum ServerMessage {
     Welcome,
     UsernameAccepted,
     UsernameInvalid,
-    AnnounceUser(Arc<Username>),
-    Users(Vec<Arc<Username>>),
-    Message(Arc<Username>, Arc<Message>),
-    Disconnected(Arc<Username>),
-}
-
-struct ClientInfo {
-    id: ID,
-    sender: UnboundedSender<ServerMessage>,
-    username: Option<Arc<String>>,
-}
-
-impl ClientInfo {
-    fn is_joined(&self) -> bool {
-        self.username.is_some()
-    }
+    AnnounceUser(Arc<str>),
+    Users(Vec<Arc<str>>),
+    Message(Arc<str>, Arc<Message>),
+    Disconnected(Arc<str>),
 }
 
 /// Run the main loop.
@@ -207,108 +208,117 @@ impl ClientInfo {
 ///
 /// # Errors
 /// * Error when socket returns an error.
+#[tracing::instrument(skip(listeners))]
+pub async fn run(listeners: impl Into<Listeners>) -> Result<(), anyhow::Error> {
+    serve(Server::new(listeners)).await
+}
+
+/// Run the main loop on a [`Server`]: the chat on its own task, the
+/// clients on the server.
 ///
-/// # Panics
-/// * None
-#[tracing::instrument(skip(listener))]
-pub async fn run(listener: TcpListener) -> Result<(), anyhow::Error> {
-    let mut id = 0;
-    let mut clients = HashMap::new();
-    let (server_sender, mut receiver) = unbounded_channel();
-    loop {
-        debug!("main loop");
-        tokio::select! {
-            biased;
-
-            socket_addr = listener.accept() => {
+/// # Errors
+/// * Error when socket returns an error.
+#[tracing::instrument(skip(server))]
+pub async fn serve(server: Server) -> Result<(), anyhow::Error> {
+    let (server_sender, receiver) = unbounded_channel();
+
+    let mut chat = protohackers_server::task::spawn("chat", chat(receiver));
+
+    let ids = AtomicUsize::new(1);
+    let clients = server
+        .with_default_idle_timeout(IDLE_TIMEOUT)
+        .serve(move |socket, _| {
+            let id = ids.fetch_add(1, Ordering::Relaxed);
+            let server_sender = server_sender.clone();
+            async move {
                 debug!("new client");
 
-                let (socket, _) = socket_addr?;
-
-                id += 1;
-
                 let (sender, receiver) = unbounded_channel();
+                server_sender.send(ClientMessage::Connected(id, sender))?;
 
-                tokio::spawn(handle_client(id, socket, server_sender.clone(), receiver));
+                handle_client(id, socket, server_sender, receiver).await;
 
-                sender.send(ServerMessage::Welcome)?;
-
-                clients.insert(id,
-                               ClientInfo {
-                                   id,
-                                   sender,
-                                   username: None,
-                               });
+                Ok::<_, SendError<ClientMessage>>(())
             }
+        });
 
-            client_message = receiver.recv() => {
-                debug!("client message");
+    tokio::select! {
+        result = clients => Ok(result?),
+        result = &mut chat => Ok(result?),
+    }
+}
 
-                // nobody closes this channel
-                match client_message.expect("invalid state") {
-                    ClientMessage::SetUsername(id, username) => {
-                        if is_username_valid(&clients, &username) {
-                            let client = clients.get_mut(&id).unwrap();
+/// The chat loop, until all the senders are gone.
+async fn chat(mut receiver: UnboundedReceiver<ClientMessage>) {
+    let mut clients = HashMap::<ID, UnboundedSender<ServerMessage>>::new();
+    let mut room = Room::new();
 
-                            assert!(client.username.is_none());
+    while let Some(client_message) = receiver.recv().await {
+        debug!("client message");
 
-                            client.username = Some(Arc::new(username));
-                            client.sender.send(ServerMessage::UsernameAccepted)?;
-                        } else {
-                            clients[&id].sender.send(ServerMessage::UsernameInvalid)?;
-                        }
-                    }
-                    ClientMessage::Joined(id) => {
-                        if let Some(user) = &clients[&id].username {
-                            let mut users = vec![];
-                            for ClientInfo { username, sender, .. } in clients.values().filter(|c| c.is_joined() && c.id != id) {
-                                // username must be set
-                                users.push(username.as_ref().unwrap().clone());
-                                sender.send(ServerMessage::AnnounceUser(user.clone()))?;
-                            }
-                            clients[&id].sender.send(ServerMessage::Users(users))?;
-                        } else {
-                            warn!("joined from invalid id {id}");
-                        }
+        match client_message {
+            ClientMessage::Connected(id, sender) => {
+                send(&sender, id, ServerMessage::Welcome);
+                clients.insert(id, sender);
+            }
+            ClientMessage::SetUsername(id, username) => match room.join(id, &username) {
+                Ok(_) => send(&clients[&id], id, ServerMessage::UsernameAccepted),
+                Err(err) => {
+                    debug!("client {id}: {err}");
+                    send(&clients[&id], id, ServerMessage::UsernameInvalid);
+                }
+            },
+            ClientMessage::Joined(id) => {
+                if let Some(user) = room.username(id) {
+                    let mut users = vec![];
+                    for (other, username) in room.others(id) {
+                        users.push(username.clone());
+                        send(
+                            &clients[&other],
+                            other,
+                            ServerMessage::AnnounceUser(user.clone()),
+                        );
                     }
-                    ClientMessage::Message(id, message) => {
-                        if let Some(user) = &clients[&id].username {
-                            for ClientInfo { sender, .. } in clients.values().filter(|c| c.is_joined() && c.id != id) {
-                                sender.send(ServerMessage::Message(user.clone(), message.clone()))?;
-                            }
-                        } else {
-                            warn!("message from invalid id {id}");
-                        }
+                    send(&clients[&id], id, ServerMessage::Users(users));
+                } else {
+                    warn!("joined from invalid id {id}");
+                }
+            }
+            ClientMessage::Message(id, message) => {
+                if let Some(user) = room.username(id) {
+                    for (other, _) in room.others(id) {
+                        send(
+                            &clients[&other],
+                            other,
+                            ServerMessage::Message(user.clone(), message.clone()),
+                        );
                     }
-                    ClientMessage::Disconnect(id) => {
-                        if let Some(client_info) = clients.get(&id) {
-                            if let Some(user) = &client_info.username {
-                                for ClientInfo { sender, .. } in clients.values().filter(|c| c.is_joined() && c.id != id) {
-                                    sender.send(ServerMessage::Disconnected(user.clone()))?;
-                                }
-                            }
-                            clients.remove(&id);
-                        }
+                } else {
+                    warn!("message from invalid id {id}");
+                }
+            }
+            ClientMessage::Disconnect(id) => {
+                if let Some(user) = room.leave(id) {
+                    for (other, _) in room.others(id) {
+                        send(
+                            &clients[&other],
+                            other,
+                            ServerMessage::Disconnected(user.clone()),
+                        );
                     }
                 }
+                clients.remove(&id);
             }
         }
     }
 }
 
-fn is_username_valid(clients: &HashMap<ID, ClientInfo>, username: &str) -> bool {
-    !username.is_empty()
-        && username.chars().all(char::is_alphanumeric)
-        && clients.values().all(
-            |ClientInfo {
-                 username: other_username,
-                 ..
-             }| {
-                other_username
-                    .as_ref()
-                    .map_or(true, |other_username| **other_username != username)
-            },
-        )
+/// Send `message` to the client `id`; the client may be gone already,
+/// its disconnection still on the way.
+fn send(client: &UnboundedSender<ServerMessage>, id: ID, message: ServerMessage) {
+    if client.send(message).is_err() {
+        debug!("client {id} gone");
+    }
 }
 
 #[derive(Error, Debug)]
@@ -327,8 +337,8 @@ struct Chatting;
 
 struct Client<'a, T> {
     id: ID,
-    read: BufReader<ReadHalf<'a>>,
-    write: WriteHalf<'a>,
+    read: BufReader<IdleTimeout<ReadHalf<'a>>>,
+    write: IdleTimeout<WriteHalf<'a>>,
     server: &'a mut UnboundedSender<ClientMessage>,
     #[allow(clippy::struct_field_names)]
     client: UnboundedReceiver<ServerMessage>,
@@ -346,6 +356,17 @@ impl<'a, T> Client<'a, T> {
             _state: PhantomData,
         }
     }
+
+    async fn write_line(&mut self, line: &str) -> Result<(), ClientError> {
+        let mut data = Vec::with_capacity(line.len() + 1);
+        data.extend_from_slice(line.as_bytes());
+        data.push(b'\n');
+
+        self.write
+            .write_all(&data)
+            .await
+            .map_err(|e| ClientError::InternalError(e.into()))
+    }
 }
 
 impl<'a> Client<'a, WaitingWelcome> {
@@ -355,10 +376,7 @@ impl<'a> Client<'a, WaitingWelcome> {
         match self.client.recv().await {
             Some(ServerMessage::Welcome) => {
                 debug!("got welcome message");
-                self.write
-                    .write_all(b"Welcome to budgetchat! What shall I call you?\n")
-                    .await
-                    .map_err(|e| ClientError::InternalError(e.into()))?;
+                self.write_line(text::WELCOME).await?;
                 Ok(Some(self.into()))
             }
             None => Ok(None),
@@ -393,10 +411,7 @@ impl<'a> Client<'a, Joining> {
                 Ok(Some(self.into()))
             }
             Some(ServerMessage::UsernameInvalid) => {
-                self.write
-                    .write_all(b"Invalid username\n")
-                    .await
-                    .map_err(|e| ClientError::InternalError(e.into()))?;
+                self.write_line(text::INVALID_USERNAME).await?;
                 Err(ClientError::InvalidUsername)
             }
             None => Ok(None),
@@ -411,23 +426,8 @@ impl<'a> Client<'a, Joined> {
         debug!("state: joined");
         match self.client.recv().await {
             Some(ServerMessage::Users(users)) => {
-                let mut message = String::new();
-                message.push_str("* The room contains:");
-                if !users.is_empty() {
-                    message.push(' ');
-                    let mut iter = users.iter();
-                    message.push_str(iter.next().unwrap());
-                    for user in iter {
-                        message.push_str(", ");
-                        message.push_str(user);
-                    }
-                }
-                message.push('\n');
-
-                self.write
-                    .write_all(message.as_bytes())
-                    .await
-                    .map_err(|e| ClientError::InternalError(e.into()))?;
+                self.write_line(&text::room_contains(users.iter().map(|user| &**user)))
+                    .await?;
                 Ok(Some(self.into()))
             }
             None => Ok(None),
@@ -436,7 +436,7 @@ impl<'a> Client<'a, Joined> {
     }
 }
 
-impl<'a> Client<'a, Chatting> {
+impl Client<'_, Chatting> {
     #[tracing::instrument(skip(self))]
     async fn chatting(mut self) -> Result<(), ClientError> {
         debug!("state: main loop");
@@ -456,19 +456,13 @@ impl<'a> Client<'a, Chatting> {
                 message = self.client.recv() => {
                     match message {
                         Some(ServerMessage::AnnounceUser(user)) => {
-                            let mut message = String::new();
-                            writeln!(&mut message, "* {} has entered the room", *user).ok();
-                            self.write.write_all(message.as_bytes()).await.map_err(|e| ClientError::InternalError(e.into()))?;
+                            self.write_line(&text::entered(&user)).await?;
                         }
                         Some(ServerMessage::Message(user, msg)) => {
-                            let mut message = String::new();
-                            writeln!(&mut message, "[{}] {}", *user, *msg).ok();
-                            self.write.write_all(message.as_bytes()).await.map_err(|e| ClientError::InternalError(e.into()))?;
+                            self.write_line(&text::message(&user, &msg)).await?;
                         }
                         Some(ServerMessage::Disconnected(user)) => {
-                            let mut message = String::new();
-                            writeln!(&mut message, "* {} has left the room", *user).ok();
-                            self.write.write_all(message.as_bytes()).await.map_err(|e| ClientError::InternalError(e.into()))?;
+                            self.write_line(&text::left(&user)).await?;
                         }
                         None => break,
                         message => unreachable!("invalid server message {:?}", message),
@@ -490,7 +484,7 @@ impl<'a> Client<'a, Chatting> {
 #[tracing::instrument(skip(stream, server, client))]
 async fn handle_client(
     id: ID,
-    mut stream: TcpStream,
+    mut stream: IdleTimeout<TcpStream>,
     mut server: UnboundedSender<ClientMessage>,
     client: UnboundedReceiver<ServerMessage>,
 ) {
@@ -1,27 +1,6 @@
-use clap::Parser;
-use tokio::net::TcpListener;
-
-use tracing::info;
-
-#[derive(clap::Parser, Debug)]
-#[command(version, about, long_about = None)]
-struct Args {
-    #[arg(long, default_value = "0.0.0.0")]
-    address: String,
-
-    #[arg(long, default_value_t = 10000)]
-    port: u16,
-}
+use p03_budget_chat::cli;
 
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
-    tracing_subscriber::fmt::init();
-
-    let args = Args::parse();
-
-    info!("start");
-
-    let listener = TcpListener::bind(&format!("{}:{}", args.address, args.port)).await?;
-
-    p03_budget_chat::run(listener).await
+    cli::run(protohackers_config::parse(clap::command!(), "p03")).await
 }
@@ -0,0 +1,141 @@
+//! Unusual database program, the runtime agnostic core.
+//!
+//! The request parsing and the key-value store, without any network
+//! I/O: the tokio and the WASI servers only move the datagrams.
+use std::collections::HashMap;
+
+/// Requests and responses must be shorter than this.
+pub const MAX_MESSAGE_LEN: usize = 1000;
+
+pub const VERSION: &[u8] = b"unusual-database-program 1.0.0";
+
+const VERSION_KEY: &[u8] = b"version";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Request<'a> {
+    Insert { key: &'a [u8], value: &'a [u8] },
+    Retrieve { key: &'a [u8] },
+}
+
+impl<'a> Request<'a> {
+    /// The first equals sign separates the key from the value, so the
+    /// key never contains it; without one it is a retrieve.
+    #[must_use]
+    pub fn parse(packet: &'a [u8]) -> Self {
+        match packet.iter().position(|c| *c == b'=') {
+            Some(position) => Request::Insert {
+                key: &packet[..position],
+                value: &packet[position + 1..],
+            },
+            None => Request::Retrieve { key: packet },
+        }
+    }
+}
+
+/// The key-value store, with the immutable `version` key.
+#[derive(Debug, Default)]
+pub struct Database {
+    data: HashMap<Vec<u8>, Vec<u8>>,
+}
+
+impl Database {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle a packet, returning the response, if any; the packets
+    /// too long and the responses too long are dropped.
+    pub fn handle(&mut self, packet: &[u8]) -> Option<Vec<u8>> {
+        if packet.len() >= MAX_MESSAGE_LEN {
+            return None;
+        }
+
+        match Request::parse(packet) {
+            Request::Insert { key, .. } if key == VERSION_KEY => None,
+            Request::Insert { key, value } => {
+                self.data.insert(key.to_vec(), value.to_vec());
+                None
+            }
+            Request::Retrieve { key } => {
+                let value = if key == VERSION_KEY {
+                    VERSION
+                } else {
+                    self.data.get(key)?
+                };
+
+                let response = [key, value].join(&b'=');
+                (response.len() < MAX_MESSAGE_LEN).then_some(response)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Request::Insert {
+                key: b"foo",
+                value: b"bar=baz"
+            },
+            Request::parse(b"foo=bar=baz")
+        );
+        assert_eq!(
+            Request::Insert {
+                key: b"foo",
+                value: b"=="
+            },
+            Request::parse(b"foo===")
+        );
+        assert_eq!(
+            Request::Insert {
+                key: b"",
+                value: b""
+            },
+            Request::parse(b"=")
+        );
+        assert_eq!(Request::Retrieve { key: b"" }, Request::parse(b""));
+    }
+
+    #[test]
+    fn test_empty_key_and_value() {
+        let mut database = Database::new();
+
+        assert_eq!(None, database.handle(b""));
+
+        database.handle(b"=foo");
+        assert_eq!(Some(b"=foo".to_vec()), database.handle(b""));
+
+        database.handle(b"foo=");
+        assert_eq!(Some(b"foo=".to_vec()), database.handle(b"foo"));
+    }
+
+    #[test]
+    fn test_version() {
+        let mut database = Database::new();
+
+        database.handle(b"version=ignored");
+        assert_eq!(
+            Some(b"version=unusual-database-program 1.0.0".to_vec()),
+            database.handle(b"version")
+        );
+    }
+
+    #[test]
+    fn test_too_long() {
+        let mut database = Database::new();
+
+        let mut packet = b"foo=".to_vec();
+        packet.resize(MAX_MESSAGE_LEN, b'x');
+        database.handle(&packet);
+        assert_eq!(None, database.handle(b"foo"));
+
+        packet.pop();
+        database.handle(&packet);
+        assert_eq!(Some(packet), database.handle(b"foo"));
+    }
+}
@@ -0,0 +1,51 @@
+//! A UDP server: the address and port are its own flags, without the
+//! TCP ones of [`ServerArgs`](protohackers_server::ServerArgs).
+#[cfg(unix)]
+use std::path::PathBuf;
+
+use tracing::info;
+
+use protohackers_server::{LogFormat, DUAL_STACK_ADDRESS, LOG_FORMAT_ENV};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[arg(long, default_value = DUAL_STACK_ADDRESS)]
+    pub address: String,
+
+    #[arg(long, default_value_t = 10000)]
+    pub port: u16,
+
+    #[arg(long, env = LOG_FORMAT_ENV, value_enum, default_value_t = LogFormat::Full)]
+    pub log_format: LogFormat,
+
+    /// On SIGUSR2, replace the log filter with the directives of this
+    /// file, the `RUST_LOG` ones when it is empty or missing
+    #[cfg(unix)]
+    #[arg(long)]
+    pub log_filter_file: Option<PathBuf>,
+}
+
+/// Run the server configured by `args`.
+///
+/// # Errors
+/// * Error when the server can not be started or fails.
+pub async fn run(args: Args) -> Result<(), anyhow::Error> {
+    protohackers_server::init_tracing(args.log_format);
+    #[cfg(unix)]
+    if let Some(path) = args.log_filter_file {
+        protohackers_server::log_filter::reload_on_signal(path)?;
+    }
+
+    info!("start");
+
+    let address = protohackers_server::socket_address(&args.address, args.port);
+    let socket = protohackers_server::bind_udp(&address).await?;
+    #[cfg(unix)]
+    protohackers_server::signals::dump_on_signal(None)?;
+
+    Ok(
+        protohackers_server::signals::until_shutdown(crate::run(socket))
+            .await
+            .unwrap_or(Ok(()))?,
+    )
+}
@@ -38,7 +38,7 @@
 //! - `foo=bar=baz` will insert a key foo with value "bar=baz".
 //!
 //! - `foo=` will insert a key foo with value "" (i.e. the empty
-//! string).
+//!   string).
 //!
 //! - `foo===` will insert a key foo with value "==".
 //!
@@ -107,13 +107,21 @@
 //! Issues related to UDP packets being dropped, delayed, or reordered
 //! are considered to be the client's problem. The server should act
 //! as if it assumes that UDP works reliably.
-use std::collections::HashMap;
 use std::io;
 
 use tokio::net::UdpSocket;
 
 use tracing::debug;
 
+#[cfg(feature = "bin")]
+pub mod cli;
+
+pub use p04_unusual_database_program_core::{Database, Request, MAX_MESSAGE_LEN, VERSION};
+
+/// Serve the requests, dropping the ones too long.
+///
+/// # Errors
+/// * Error when socket returns an error.
 #[tracing::instrument(skip(socket))]
 pub async fn run(socket: UdpSocket) -> Result<(), io::Error> {
     debug!(
@@ -122,40 +130,22 @@ pub async fn run(socket: UdpSocket) -> Result<(), io::Error> {
         socket.ttl()
     );
 
-    let mut data = HashMap::new();
+    let mut database = Database::new();
 
-    let mut buffer = [0; 1024];
+    // a byte more to detect the packets too long
+    let mut buffer = [0; MAX_MESSAGE_LEN + 1];
     loop {
         let (len, addr) = socket.recv_from(&mut buffer).await?;
 
-        let buffer = &buffer[0..len];
-        let mut iter = buffer.iter();
-        if let Some(position) = iter.position(|c| *c == b'=') {
-            let key = &buffer[0..position];
-            if key != b"version" {
-                let value = iter.as_slice();
-                data.insert(key.to_vec(), value.to_vec());
-                debug!(
-                    "[{:?}] insert key: {:?} value: {:?}",
-                    addr,
-                    std::str::from_utf8(key),
-                    std::str::from_utf8(value)
-                );
-            }
-        } else {
-            let key = buffer;
-            debug!("[{:?}] retrieve key: {:?}", addr, std::str::from_utf8(key));
-            if key == b"version" {
-                socket
-                    .send_to(b"version=unusual-database-program 1.0.0", addr)
-                    .await?;
-            } else if let Some(value) = data.get(key) {
-                debug!("[{:?}] value: {:?}", addr, std::str::from_utf8(value));
-
-                socket.send_to(&[key, value].join(&b"="[..]), addr).await?;
-            } else {
-                debug!("[{:?}] no value", addr);
-            }
+        let packet = &buffer[0..len];
+        debug!("[{:?}] request: {:?}", addr, std::str::from_utf8(packet));
+        if let Some(response) = database.handle(packet) {
+            debug!(
+                "[{:?}] response: {:?}",
+                addr,
+                std::str::from_utf8(&response)
+            );
+            socket.send_to(&response, addr).await?;
         }
     }
 }
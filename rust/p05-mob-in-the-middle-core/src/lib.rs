@@ -0,0 +1,340 @@
+//! Mob in the middle, the runtime agnostic core.
+//!
+//! The rewriting of the proxied messages, without any network I/O: a
+//! list of [`Rule`]s, each one replacing the words matching a regex,
+//! where the words are delimited by the boundary characters or by the
+//! ends of the message. The Boguscoin address replacement is just the
+//! default rule; other rules can be loaded from a TOML file:
+//!
+//! ```toml
+//! [[rule]]
+//! name = "boguscoin"
+//! pattern = "7[[:alnum:]]{25,34}"
+//! replacement = "7YWHMfk9JZe0LM0g1ZauHuiSxhI"
+//!
+//! [[rule]]
+//! name = "greeting"
+//! pattern = "(?i)hi"
+//! replacement = "hello"
+//! boundary = " ,"
+//! ```
+use std::borrow::Cow;
+
+use regex::Regex;
+use serde::Deserialize;
+use thiserror::Error;
+
+/// Tony's Boguscoin address.
+pub const BOGUSCOIN: &str = "7YWHMfk9JZe0LM0g1ZauHuiSxhI";
+
+/// A Boguscoin address: a 7 followed by 25 to 34 alphanumeric
+/// characters.
+pub const BOGUSCOIN_PATTERN: &str = "7[[:alnum:]]{25,34}";
+
+const DEFAULT_BOUNDARY: &str = " ";
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("invalid pattern of rule {name}: {source}")]
+    Pattern { name: String, source: regex::Error },
+
+    #[error("empty boundary of rule {0}")]
+    EmptyBoundary(String),
+
+    #[error("invalid rules: {0}")]
+    Config(#[from] toml::de::Error),
+}
+
+/// A rule as written in the configuration.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RuleConfig {
+    pub name: String,
+
+    /// The regex a whole word must match.
+    pub pattern: String,
+
+    /// The replacement of the word, `$1` or `$name` expand to the
+    /// groups of the pattern.
+    pub replacement: String,
+
+    /// The characters delimiting the words, a space by default.
+    #[serde(default)]
+    pub boundary: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct RulesConfig {
+    #[serde(default)]
+    rule: Vec<RuleConfig>,
+}
+
+/// A compiled rule.
+#[derive(Debug, Clone)]
+pub struct Rule {
+    name: String,
+    pattern: Regex,
+    replacement: String,
+    boundary: Vec<char>,
+}
+
+impl Rule {
+    /// Compile a rule.
+    ///
+    /// # Errors
+    /// * [`Error::Pattern`] when the pattern is not a valid regex.
+    /// * [`Error::EmptyBoundary`] when the boundary has no characters.
+    pub fn new(config: RuleConfig) -> Result<Self, Error> {
+        // the whole word must match
+        let pattern =
+            Regex::new(&format!("^(?:{})$", config.pattern)).map_err(|source| Error::Pattern {
+                name: config.name.clone(),
+                source,
+            })?;
+
+        let boundary = config
+            .boundary
+            .as_deref()
+            .unwrap_or(DEFAULT_BOUNDARY)
+            .chars()
+            .collect::<Vec<_>>();
+        if boundary.is_empty() {
+            return Err(Error::EmptyBoundary(config.name));
+        }
+
+        Ok(Self {
+            name: config.name,
+            pattern,
+            replacement: config.replacement,
+            boundary,
+        })
+    }
+
+    /// The rule replacing the Boguscoin addresses with `address`.
+    ///
+    /// # Panics
+    /// * Never, the pattern is valid.
+    #[must_use]
+    pub fn boguscoin(address: &str) -> Self {
+        Self::new(RuleConfig {
+            name: "boguscoin".to_string(),
+            pattern: BOGUSCOIN_PATTERN.to_string(),
+            replacement: address.replace('$', "$$"),
+            boundary: None,
+        })
+        .expect("valid boguscoin rule")
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Replace the matching words of `message`, the boundaries are
+    /// kept as they are.
+    #[must_use]
+    pub fn apply<'a>(&self, message: &'a str) -> Cow<'a, str> {
+        let mut result = String::new();
+        let mut replaced = false;
+        let mut copied = 0;
+        let mut start = 0;
+        for (end, c) in message
+            .char_indices()
+            .filter(|(_, c)| self.boundary.contains(c))
+            .chain([(message.len(), ' ')])
+        {
+            let word = &message[start..end];
+            if !word.is_empty() && self.pattern.is_match(word) {
+                result.push_str(&message[copied..start]);
+                result.push_str(&self.pattern.replace(word, &self.replacement));
+                copied = end;
+                replaced = true;
+            }
+            start = end + c.len_utf8();
+        }
+
+        if replaced {
+            result.push_str(&message[copied..]);
+            Cow::Owned(result)
+        } else {
+            Cow::Borrowed(message)
+        }
+    }
+}
+
+/// The rules, applied in order, each one to the result of the
+/// previous one.
+#[derive(Debug, Clone, Default)]
+pub struct Rules(Vec<Rule>);
+
+impl Rules {
+    #[must_use]
+    pub fn new(rules: Vec<Rule>) -> Self {
+        Self(rules)
+    }
+
+    /// The Boguscoin address replacement only.
+    #[must_use]
+    pub fn boguscoin(address: &str) -> Self {
+        Self(vec![Rule::boguscoin(address)])
+    }
+
+    /// The rules from the `[[rule]]` tables of a TOML document.
+    ///
+    /// # Errors
+    /// * [`Error::Config`] when the document is invalid.
+    /// * Error when a rule is invalid.
+    pub fn from_toml(document: &str) -> Result<Self, Error> {
+        let config = toml::from_str::<RulesConfig>(document)?;
+        Ok(Self(
+            config
+                .rule
+                .into_iter()
+                .map(Rule::new)
+                .collect::<Result<_, _>>()?,
+        ))
+    }
+
+    #[must_use]
+    pub fn rules(&self) -> &[Rule] {
+        &self.0
+    }
+
+    #[must_use]
+    pub fn apply<'a>(&self, message: &'a str) -> Cow<'a, str> {
+        self.0.iter().fold(Cow::Borrowed(message), |message, rule| {
+            match rule.apply(&message) {
+                Cow::Borrowed(_) => message,
+                Cow::Owned(result) => Cow::Owned(result),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(pattern: &str, replacement: &str, boundary: Option<&str>) -> Rule {
+        Rule::new(RuleConfig {
+            name: "test".to_string(),
+            pattern: pattern.to_string(),
+            replacement: replacement.to_string(),
+            boundary: boundary.map(str::to_string),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_boguscoin() {
+        let rule = Rule::boguscoin(BOGUSCOIN);
+
+        for (message, expected) in [
+            (
+                "Hi alice, please send payment to 7iKDZEwPZSqIvDnHvVN2r0hUWXD5rHX",
+                "Hi alice, please send payment to 7YWHMfk9JZe0LM0g1ZauHuiSxhI",
+            ),
+            (
+                "7F1u3wSD5RbOHQmupo9nx4TnhQ",
+                "7YWHMfk9JZe0LM0g1ZauHuiSxhI",
+            ),
+            (
+                "7iKDZEwPZSqIvDnHvVN2r0hUWXD5rHX 7LOrwbDlS8NujgjddyogWgIM93MV5N2VR",
+                "7YWHMfk9JZe0LM0g1ZauHuiSxhI 7YWHMfk9JZe0LM0g1ZauHuiSxhI",
+            ),
+            (
+                "Please pay the ticket price of 15 Boguscoins to one of these addresses: 7YWHMfk9JZe0LM0g1ZauHuiSxhI 7iKDZEwPZSqIvDnHvVN2r0hUWXD5rHX  7LOrwbDlS8NujgjddyogWgIM93MV5N2VR",
+                "Please pay the ticket price of 15 Boguscoins to one of these addresses: 7YWHMfk9JZe0LM0g1ZauHuiSxhI 7YWHMfk9JZe0LM0g1ZauHuiSxhI  7YWHMfk9JZe0LM0g1ZauHuiSxhI",
+            ),
+        ] {
+            assert_eq!(expected, rule.apply(message));
+        }
+
+        for message in [
+            "hello",
+            // too short, too long
+            "7F1u3wSD5RbOHQmupo9nx4Tnh",
+            "7iKDZEwPZSqIvDnHvVN2r0hUWXD5rHXabcde",
+            // not at a boundary
+            "x7iKDZEwPZSqIvDnHvVN2r0hUWXD5rHX",
+            "7iKDZEwPZSqIvDnHvVN2r0hUWXD5rHX-1234",
+            "This is a product ID, not a Boguscoin: 7S1JBWdDkLy6X9XwobRaVtLcD1bEmX-PGPtQqQn5N2KgXa9MuDbYjf2ejc-1234",
+        ] {
+            assert!(matches!(rule.apply(message), Cow::Borrowed(_)), "{message}");
+        }
+    }
+
+    #[test]
+    fn test_groups_and_boundary() {
+        let rule = rule("(?<user>[a-z]+)@example", "$user@example.org", Some(" ,"));
+        assert_eq!(
+            "to alice@example.org,bob@example.org, carol@other",
+            rule.apply("to alice@example,bob@example, carol@other")
+        );
+    }
+
+    #[test]
+    fn test_unicode() {
+        let rule = rule("caffè", "tea", Some("·"));
+        assert_eq!("tea·tea·latte", rule.apply("caffè·caffè·latte"));
+    }
+
+    #[test]
+    fn test_rules() {
+        let rules = Rules::from_toml(
+            r#"
+            [[rule]]
+            name = "boguscoin"
+            pattern = "7[[:alnum:]]{25,34}"
+            replacement = "7YWHMfk9JZe0LM0g1ZauHuiSxhI"
+
+            [[rule]]
+            name = "tony"
+            pattern = "7YWHMfk9JZe0LM0g1ZauHuiSxhI"
+            replacement = "Tony"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            vec!["boguscoin", "tony"],
+            rules.rules().iter().map(Rule::name).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            "pay Tony",
+            rules.apply("pay 7iKDZEwPZSqIvDnHvVN2r0hUWXD5rHX")
+        );
+        assert!(matches!(rules.apply("hello"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        assert!(matches!(
+            Rules::from_toml(
+                r#"
+                [[rule]]
+                name = "broken"
+                pattern = "("
+                replacement = ""
+                "#
+            ),
+            Err(Error::Pattern { name, .. }) if name == "broken"
+        ));
+        assert!(matches!(
+            Rules::from_toml(
+                r#"
+                [[rule]]
+                name = "no boundary"
+                pattern = "a"
+                replacement = "b"
+                boundary = ""
+                "#
+            ),
+            Err(Error::EmptyBoundary(name)) if name == "no boundary"
+        ));
+        assert!(matches!(
+            Rules::from_toml("[[rules]]"),
+            Err(Error::Config(_))
+        ));
+    }
+}
@@ -0,0 +1,63 @@
+//! The proxy flags: the upstream chat server, how to dial it, and the
+//! Boguscoin address or the file of rules the messages are rewritten
+//! with.
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use tracing::info;
+
+use protohackers_server::{DialArgs, ServerArgs};
+
+use crate::{Rules, BOGUSCOIN};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[command(flatten)]
+    pub server: ServerArgs,
+
+    #[arg(long, default_value = "chat.protohackers.com")]
+    pub chat_address: String,
+
+    #[arg(long, default_value_t = 16963)]
+    pub chat_port: u16,
+
+    #[command(flatten)]
+    pub dial: DialArgs,
+
+    #[arg(long, default_value_t = BOGUSCOIN.to_string())]
+    pub boguscoin: String,
+
+    /// The TOML file of the rewriting rules, instead of the Boguscoin
+    /// address replacement
+    #[arg(long, conflicts_with = "boguscoin")]
+    pub rules: Option<PathBuf>,
+}
+
+/// Run the server configured by `args`.
+///
+/// # Errors
+/// * Error when the server can not be started or fails.
+pub async fn run(args: Args) -> Result<(), anyhow::Error> {
+    args.server.init_tracing();
+
+    info!("start");
+
+    let rules = if let Some(path) = args.rules {
+        info!("rules: {}", path.display());
+        Rules::from_toml(&std::fs::read_to_string(path)?)?
+    } else {
+        Rules::boguscoin(&args.boguscoin)
+    };
+
+    crate::serve(
+        args.server
+            .server()
+            .await?
+            .with_problem(env!("CARGO_PKG_NAME")),
+        args.dial.dialer(),
+        args.chat_address,
+        args.chat_port,
+        Arc::new(rules),
+    )
+    .await
+}
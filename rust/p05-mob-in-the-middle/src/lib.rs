@@ -106,70 +106,93 @@
 //! ```raw
 //! 7YWHMfk9JZe0LM0g1ZauHuiSxhI
 //! ```
-use std::borrow::Cow;
+use std::sync::Arc;
+use std::time::Duration;
 
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
-use tokio::net::{TcpListener, TcpStream};
+use tokio::net::TcpStream;
 
 use tracing::debug;
 
-pub const BOGUSCOIN: &str = "7YWHMfk9JZe0LM0g1ZauHuiSxhI";
+use protohackers_server::{Dialer, IdleTimeout, Listeners, Server};
 
-#[tracing::instrument(skip(listener, chat_address, chat_port, boguscoin))]
+pub mod cli;
+
+pub use p05_mob_in_the_middle_core::{Error, Rule, RuleConfig, Rules, BOGUSCOIN};
+
+/// The default idle timeout of the client connections, as the chat
+/// room they are proxied to.
+pub const IDLE_TIMEOUT: Duration = Duration::from_mins(10);
+
+/// Run the proxy, rewriting the messages in both directions with
+/// `rules`.
+///
+/// # Errors
+/// * Error when the listener fails.
+#[tracing::instrument(skip(listeners, chat_address, chat_port, rules))]
 pub async fn run(
-    listener: TcpListener,
+    listeners: impl Into<Listeners>,
     chat_address: String,
     chat_port: u16,
-    boguscoin: String,
+    rules: Arc<Rules>,
 ) -> Result<(), anyhow::Error> {
-    loop {
-        let (stream, _) = listener.accept().await?;
-        let chat_address = chat_address.clone();
-        let boguscoin = boguscoin.clone();
-        tokio::spawn(async move {
-            handle(stream, chat_address, chat_port, boguscoin)
-                .await
-                .ok();
-        });
-    }
+    serve(
+        Server::new(listeners),
+        Dialer::direct(),
+        chat_address,
+        chat_port,
+        rules,
+    )
+    .await
+}
+
+/// Run the proxy on a [`Server`], connecting to the chat server with
+/// `dialer`.
+///
+/// # Errors
+/// * Error when the listener fails.
+#[tracing::instrument(skip(server, dialer, chat_address, chat_port, rules))]
+pub async fn serve(
+    server: Server,
+    dialer: Dialer,
+    chat_address: String,
+    chat_port: u16,
+    rules: Arc<Rules>,
+) -> Result<(), anyhow::Error> {
+    server
+        .with_default_idle_timeout(IDLE_TIMEOUT)
+        .serve(move |stream, _| {
+            handle(
+                stream,
+                dialer.clone(),
+                chat_address.clone(),
+                chat_port,
+                rules.clone(),
+            )
+        })
+        .await?;
+
+    Ok(())
 }
 
-#[tracing::instrument(skip(stream, chat_address, chat_port, boguscoin))]
+#[tracing::instrument(skip(stream, dialer, chat_address, chat_port, rules))]
 async fn handle(
-    mut stream: TcpStream,
+    mut stream: IdleTimeout<TcpStream>,
+    dialer: Dialer,
     chat_address: String,
     chat_port: u16,
-    boguscoin: String,
+    rules: Arc<Rules>,
 ) -> Result<(), anyhow::Error> {
     let trasform = |message: &[u8]| -> String {
-        String::from_utf8_lossy(message)
-            .split_ascii_whitespace()
-            .map(|word| {
-                if word.starts_with('7')
-                    && (26..=35).contains(&word.len())
-                    && word.chars().all(char::is_alphanumeric)
-                {
-                    Cow::Borrowed(boguscoin.as_str())
-                } else {
-                    Cow::Borrowed(word)
-                }
-            })
-            .fold(String::new(), |mut s, word| {
-                if s.is_empty() {
-                    word.into_owned()
-                } else {
-                    s.push(' ');
-                    s.push_str(&word);
-                    s
-                }
-            })
+        let message = String::from_utf8_lossy(message.strip_suffix(b"\n").unwrap_or(message));
+        rules.apply(&message).into_owned()
     };
 
     let (client_read, client_write) = stream.split();
     let mut client_read = BufReader::new(client_read);
     let mut client_write = BufWriter::new(client_write);
 
-    let mut chat_stream = TcpStream::connect(&format!("{chat_address}:{chat_port}")).await?;
+    let mut chat_stream = dialer.connect(&chat_address, chat_port).await?;
     let (chat_read, chat_write) = chat_stream.split();
     let mut chat_read = BufReader::new(chat_read);
     let mut chat_write = BufWriter::new(chat_write);
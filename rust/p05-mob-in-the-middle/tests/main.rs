@@ -1,4 +1,4 @@
-use std::sync::Once;
+use std::sync::{Arc, Once};
 use std::time::Duration;
 
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
@@ -7,14 +7,15 @@ use tokio::time::timeout;
 
 use tracing::info;
 
-use p05_mob_in_the_middle::{run, BOGUSCOIN};
+use p05_mob_in_the_middle::{run, Rules, BOGUSCOIN};
 
 #[tokio::test]
 async fn test_session() {
     init_logging();
 
     let (chat_address, chat_port) = spawn_budget_chat_app().await;
-    let (address, port) = spawn_app(chat_address.clone(), chat_port).await;
+    let (address, port) =
+        spawn_app(chat_address.clone(), chat_port, Rules::boguscoin(BOGUSCOIN)).await;
 
     let mut stream_alice = TcpStream::connect(&format!("{chat_address}:{port}"))
         .await
@@ -61,7 +62,8 @@ async fn test_not_joining() {
     init_logging();
 
     let (chat_address, chat_port) = spawn_budget_chat_app().await;
-    let (address, port) = spawn_app(chat_address.clone(), chat_port).await;
+    let (address, port) =
+        spawn_app(chat_address.clone(), chat_port, Rules::boguscoin(BOGUSCOIN)).await;
 
     let mut stream_alice = TcpStream::connect(&format!("{chat_address}:{port}"))
         .await
@@ -105,6 +107,66 @@ async fn test_not_joining() {
     }
 }
 
+#[tokio::test]
+async fn test_custom_rules() {
+    init_logging();
+
+    let rules = Rules::from_toml(
+        r#"
+        [[rule]]
+        name = "greeting"
+        pattern = "(?i)hi"
+        replacement = "Hello"
+
+        [[rule]]
+        name = "name"
+        pattern = "alice"
+        replacement = "eve"
+        boundary = " ,"
+        "#,
+    )
+    .unwrap();
+
+    let (chat_address, chat_port) = spawn_budget_chat_app().await;
+    let (address, port) = spawn_app(chat_address, chat_port, rules).await;
+
+    let mut stream_alice = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .unwrap();
+    let (read_alice, mut write_alice) = stream_alice.split();
+    let mut read_alice = BufReader::new(read_alice).split(b'\n');
+    write_alice.write_all(b"carol\n").await.unwrap();
+
+    let mut stream_bob = TcpStream::connect(&format!("{address}:{port}"))
+        .await
+        .unwrap();
+    let (read_bob, mut write_bob) = stream_bob.split();
+    let mut read_bob = BufReader::new(read_bob).split(b'\n');
+    write_bob.write_all(b"bob\n").await.unwrap();
+
+    let _step_0 = read_bob.next_segment().await.unwrap(); // Welcome...
+    let _step_1 = read_bob.next_segment().await.unwrap(); // * The room contains...
+
+    write_bob
+        .write_all(b"hi alice, this is bob  hi\n")
+        .await
+        .unwrap();
+
+    let _step_0 = read_alice.next_segment().await.unwrap(); // Welcome...
+    let _step_1 = read_alice.next_segment().await.unwrap(); // * The room contains...
+    let _step_2 = read_alice.next_segment().await.unwrap(); // * join
+
+    let result = timeout(Duration::from_millis(500), read_alice.next_segment())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        std::str::from_utf8(&result).unwrap(),
+        "[bob] Hello eve, this is bob  Hello"
+    );
+}
+
 fn init_logging() {
     static TRACING_SUBSCRIBER_INIT: Once = Once::new();
     TRACING_SUBSCRIBER_INIT.call_once(tracing_subscriber::fmt::init);
@@ -130,7 +192,7 @@ async fn spawn_budget_chat_app() -> (String, u16) {
     (address.to_string(), port)
 }
 
-async fn spawn_app(chat_address: String, chat_port: u16) -> (String, u16) {
+async fn spawn_app(chat_address: String, chat_port: u16, rules: Rules) -> (String, u16) {
     let address = "127.0.0.1";
 
     let listener = TcpListener::bind(&format!("{address}:0"))
@@ -142,7 +204,7 @@ async fn spawn_app(chat_address: String, chat_port: u16) -> (String, u16) {
         .port();
 
     tokio::spawn(async move {
-        run(listener, chat_address, chat_port, BOGUSCOIN.to_string())
+        run(listener, chat_address, chat_port, Arc::new(rules))
             .await
             .expect("run failed");
     });
@@ -455,6 +455,7 @@
 //! speed. Fortunately nobody on Freedom Island has a fast enough car,
 //! so you don't need to worry about it.
 use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
 use std::future;
 use std::sync::{atomic, Arc, Mutex};
 use tokio::time::{Duration, Instant};
@@ -462,13 +463,16 @@ use tokio::time::{Duration, Instant};
 use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader, BufWriter};
 use tokio::net::{
     tcp::{ReadHalf, WriteHalf},
-    TcpListener, TcpStream,
+    TcpStream,
 };
 use tokio::sync::mpsc;
 use tokio::time;
 
 use tracing::{debug, info, warn};
 
+use protohackers_server::{IdleTimeout, Listeners, Server};
+
+pub mod cli;
 pub mod controller;
 pub mod wire;
 
@@ -548,55 +552,71 @@ impl Dispatchers {
 ///
 /// # Errors
 /// * Error when socket returns an error.
-#[tracing::instrument(skip(listener))]
-pub async fn run(listener: TcpListener) -> Result<(), anyhow::Error> {
-    let mut controller = Controller::default();
-    let mut dispatchers = Dispatchers::default();
+#[tracing::instrument(skip(listeners))]
+pub async fn run(listeners: impl Into<Listeners>) -> Result<(), anyhow::Error> {
+    serve(Server::new(listeners)).await
+}
 
+/// Run the main loop on a [`Server`]: the controller on its own
+/// task, the clients on the server. There is no default idle timeout:
+/// a dispatcher without heartbeats waits for its tickets in silence.
+///
+/// # Errors
+/// * Error when socket returns an error.
+#[tracing::instrument(skip(server))]
+pub async fn serve(server: Server) -> Result<(), anyhow::Error> {
     let cameras = Arc::new(Mutex::new(HashMap::new()));
 
-    let (controller_sender, mut controller_receiver) = mpsc::unbounded_channel();
+    let (controller_sender, controller_receiver) = mpsc::unbounded_channel();
 
-    loop {
-        tokio::select! {
-            handler = listener.accept() => {
-                let (socket, _) = handler?;
-
-                tokio::spawn(handle_client(
-                    socket,
-                    controller_sender.clone(),
-                    cameras.clone(),
-                ));
-            }
+    let mut controller = protohackers_server::task::spawn("controller", control(controller_receiver));
 
-            message = controller_receiver.recv() => {
-                match message {
-                    Some(ControllerMessage::AddDispatcher(id, roads, ticket_sender)) => {
-                        debug!("adding dispatcher {id} roads {roads:?}");
-                        dispatchers.add_dispatcher(id, roads, ticket_sender)?;
-                    }
-                    Some(ControllerMessage::RemoveDispatcher(id)) => {
-                        debug!("removing dispatcher {id}");
-                        dispatchers.remove_dispatcher(id);
-                    }
-                    Some(ControllerMessage::Plate(plate)) => {
-                        info!("handling plate: {plate:?}");
-                        let tickets = controller.signal(plate);
-                        debug!("tickets: {tickets:?}");
-                        dispatchers.send_tickets(tickets)?;
-                    }
-                    None => {
-                        warn!("empty controller message");
-                    }
-                }
+    let clients = server.serve(move |socket, _| {
+        let client = handle_client(socket, controller_sender.clone(), cameras.clone());
+        async move {
+            client.await;
+            Ok::<_, Infallible>(())
+        }
+    });
+
+    tokio::select! {
+        result = clients => Ok(result?),
+        result = &mut controller => result?,
+    }
+}
+
+/// The controller loop, until all the senders are gone.
+async fn control(
+    mut controller_receiver: mpsc::UnboundedReceiver<ControllerMessage>,
+) -> Result<(), anyhow::Error> {
+    let mut controller = Controller::default();
+    let mut dispatchers = Dispatchers::default();
+
+    while let Some(message) = controller_receiver.recv().await {
+        match message {
+            ControllerMessage::AddDispatcher(id, roads, ticket_sender) => {
+                debug!("adding dispatcher {id} roads {roads:?}");
+                dispatchers.add_dispatcher(id, roads, ticket_sender)?;
+            }
+            ControllerMessage::RemoveDispatcher(id) => {
+                debug!("removing dispatcher {id}");
+                dispatchers.remove_dispatcher(id);
+            }
+            ControllerMessage::Plate(plate) => {
+                info!("handling plate: {plate:?}");
+                let tickets = controller.signal(plate);
+                debug!("tickets: {tickets:?}");
+                dispatchers.send_tickets(tickets)?;
             }
         }
     }
+
+    Ok(())
 }
 
 #[tracing::instrument(skip(socket, controller_sender, cameras))]
 async fn handle_client(
-    mut socket: TcpStream,
+    mut socket: IdleTimeout<TcpStream>,
     controller_sender: mpsc::UnboundedSender<ControllerMessage>,
     cameras: Cameras,
 ) {
@@ -782,8 +802,8 @@ async fn handle_camera<'a>(
     controller_sender: mpsc::UnboundedSender<ControllerMessage>,
     i_am_camera: wire::IAmCamera,
     mut heartbeat: Heartbeat,
-    read: &mut BufReader<ReadHalf<'a>>,
-    write: &mut BufWriter<WriteHalf<'a>>,
+    read: &mut BufReader<IdleTimeout<ReadHalf<'a>>>,
+    write: &mut BufWriter<IdleTimeout<WriteHalf<'a>>>,
 ) -> Result<(), anyhow::Error> {
     debug!("start {i_am_camera:?}");
 
@@ -833,8 +853,8 @@ async fn handle_dispatcher<'a>(
     controller_sender: mpsc::UnboundedSender<ControllerMessage>,
     i_am_dispatcher: wire::IAmDispatcher,
     mut heartbeat: Heartbeat,
-    read: &mut BufReader<ReadHalf<'a>>,
-    write: &mut BufWriter<WriteHalf<'a>>,
+    read: &mut BufReader<IdleTimeout<ReadHalf<'a>>>,
+    write: &mut BufWriter<IdleTimeout<WriteHalf<'a>>>,
 ) -> Result<(), anyhow::Error> {
     debug!("start {i_am_dispatcher:?}");
 
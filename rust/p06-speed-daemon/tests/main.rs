@@ -75,7 +75,7 @@ async fn test_session() {
             .await
             .unwrap();
         let ticket = timeout(
-            Duration::from_millis(1000),
+            Duration::from_secs(1),
             p06_speed_daemon::wire::Ticket::read_from(&mut read),
         )
         .await
@@ -154,7 +154,7 @@ async fn test_heartbeat_camera() {
 
     for i in 0..10 {
         if let Ok(Ok(p06_speed_daemon::wire::Heartbeat)) = timeout(
-            Duration::from_millis(1000),
+            Duration::from_secs(1),
             p06_speed_daemon::wire::Heartbeat::read_from(&mut read),
         )
         .await
@@ -187,7 +187,7 @@ async fn test_heartbeat_dispatcher() {
 
     for i in 0..10 {
         if let Ok(Ok(p06_speed_daemon::wire::Heartbeat)) = timeout(
-            Duration::from_millis(1000),
+            Duration::from_secs(1),
             p06_speed_daemon::wire::Heartbeat::read_from(&mut read),
         )
         .await
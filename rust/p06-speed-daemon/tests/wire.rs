@@ -0,0 +1,142 @@
+//! Wire format properties: every written message reads back the same,
+//! and reading any bytes never panics.
+use std::fmt;
+
+use futures::executor::block_on;
+
+use proptest::prelude::*;
+
+use p06_speed_daemon::wire::{
+    Error, Heartbeat, IAmCamera, IAmDispatcher, Plate, ReadFrom, Ticket, WantHeartbeat, WriteTo,
+};
+
+use protohackers_proptest::{bytes, mutated, string};
+
+/// The longest string, its length is a `u8`.
+const MAX_STR_LEN: usize = u8::MAX as usize;
+
+fn error() -> impl Strategy<Value = Error> {
+    string(MAX_STR_LEN).prop_map(|msg| Error { msg })
+}
+
+fn plate() -> impl Strategy<Value = Plate> {
+    (string(MAX_STR_LEN), any::<u32>()).prop_map(|(plate, timestamp)| Plate { plate, timestamp })
+}
+
+fn ticket() -> impl Strategy<Value = Ticket> {
+    (string(MAX_STR_LEN), any::<(u16, u16, u32, u16, u32, u16)>()).prop_map(
+        |(plate, (road, mile1, timestamp1, mile2, timestamp2, speed))| Ticket {
+            plate,
+            road,
+            mile1,
+            timestamp1,
+            mile2,
+            timestamp2,
+            speed,
+        },
+    )
+}
+
+fn want_heartbeat() -> impl Strategy<Value = WantHeartbeat> {
+    any::<u32>().prop_map(|interval| WantHeartbeat { interval })
+}
+
+fn i_am_camera() -> impl Strategy<Value = IAmCamera> {
+    any::<(u16, u16, u16)>().prop_map(|(road, mile, limit)| IAmCamera { road, mile, limit })
+}
+
+fn i_am_dispatcher() -> impl Strategy<Value = IAmDispatcher> {
+    prop::collection::vec(any::<u16>(), 0..=MAX_STR_LEN).prop_map(|roads| IAmDispatcher { roads })
+}
+
+fn write<M: WriteTo>(message: &M) -> Vec<u8> {
+    let mut data = vec![];
+    block_on(message.write_to(&mut data)).unwrap();
+    data
+}
+
+/// The message written and read back, with all the bytes read.
+fn round_trip<M: WriteTo + ReadFrom + PartialEq + fmt::Debug>(
+    message: &M,
+) -> Result<(), TestCaseError> {
+    let data = write(message);
+    let mut src = data.as_slice();
+    let read = block_on(M::read_from(&mut src)).unwrap();
+    prop_assert_eq!(message, &read);
+    prop_assert!(src.is_empty(), "{} bytes left", src.len());
+    Ok(())
+}
+
+/// Read `data` as every message type, checking that what is read
+/// is within it.
+fn read_all(data: &[u8]) -> Result<(), TestCaseError> {
+    fn read<M: ReadFrom>(data: &[u8]) -> Result<(), TestCaseError> {
+        let mut src = data;
+        if block_on(M::read_from(&mut src)).is_ok() {
+            prop_assert!(src.len() < data.len());
+        }
+        Ok(())
+    }
+
+    read::<Error>(data)?;
+    read::<Plate>(data)?;
+    read::<Ticket>(data)?;
+    read::<WantHeartbeat>(data)?;
+    read::<Heartbeat>(data)?;
+    read::<IAmCamera>(data)?;
+    read::<IAmDispatcher>(data)
+}
+
+#[test]
+fn test_round_trip_heartbeat() {
+    round_trip(&Heartbeat).unwrap();
+}
+
+proptest! {
+    #[test]
+    fn test_round_trip_error(message in error()) {
+        round_trip(&message)?;
+    }
+
+    #[test]
+    fn test_round_trip_plate(message in plate()) {
+        round_trip(&message)?;
+    }
+
+    #[test]
+    fn test_round_trip_ticket(message in ticket()) {
+        round_trip(&message)?;
+    }
+
+    #[test]
+    fn test_round_trip_want_heartbeat(message in want_heartbeat()) {
+        round_trip(&message)?;
+    }
+
+    #[test]
+    fn test_round_trip_i_am_camera(message in i_am_camera()) {
+        round_trip(&message)?;
+    }
+
+    #[test]
+    fn test_round_trip_i_am_dispatcher(message in i_am_dispatcher()) {
+        round_trip(&message)?;
+    }
+
+    #[test]
+    fn test_read_never_panics(data in bytes(512)) {
+        read_all(&data)?;
+    }
+
+    #[test]
+    fn test_read_mutated_never_panics(
+        data in prop_oneof![
+            plate().prop_map(|message| write(&message)),
+            ticket().prop_map(|message| write(&message)),
+            i_am_dispatcher().prop_map(|message| write(&message)),
+        ]
+        .prop_flat_map(mutated),
+    ) {
+        read_all(&data)?;
+    }
+}
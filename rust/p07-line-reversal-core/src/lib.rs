@@ -0,0 +1,51 @@
+//! Line reversal, the runtime agnostic core.
+//!
+//! The LRCP packets, the server session state machine and the line
+//! reversal, without any network I/O or timer: the tokio and the WASI
+//! servers only move the datagrams and tick the sessions.
+pub mod packets;
+pub mod session;
+
+pub use packets::{Packet, PacketError};
+pub use session::Session;
+
+/// The application: every complete line is sent back reversed.
+#[derive(Debug, Default)]
+pub struct LineReversal {
+    line: Vec<u8>,
+}
+
+impl LineReversal {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed the received data, returning the reversed complete lines.
+    pub fn feed(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut output = vec![];
+        for &b in data {
+            if b == b'\n' {
+                output.extend(self.line.drain(..).rev());
+                output.push(b'\n');
+            } else {
+                self.line.push(b);
+            }
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LineReversal;
+
+    #[test]
+    fn test_line_reversal() {
+        let mut line_reversal = LineReversal::new();
+
+        assert_eq!(b"".to_vec(), line_reversal.feed(b"hel"));
+        assert_eq!(b"olleh\n".to_vec(), line_reversal.feed(b"lo\nab"));
+        assert_eq!(b"cba\n\n".to_vec(), line_reversal.feed(b"c\n\n"));
+    }
+}
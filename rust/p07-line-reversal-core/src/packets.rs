@@ -25,18 +25,19 @@ pub trait SyncWrite<T>: io::Write {
 }
 
 #[derive(Debug, PartialEq, Copy, Clone, Hash, Eq)]
-pub struct Numeric(pub(crate) u32);
+pub struct Numeric(pub u32);
 
 impl Numeric {
     fn parse(mut buffer: &[u8]) -> Result<(Self, &[u8]), PacketError> {
-        let mut result = 0;
+        let mut result: u32 = 0;
         loop {
             match buffer.split_first() {
                 Some((value, b)) if value.is_ascii_digit() => {
-                    result = result * 10 + u32::from(value - b'0');
-                    if result >= 2_147_483_648 {
-                        return Err(PacketError::NumericOverflow);
-                    }
+                    result = result
+                        .checked_mul(10)
+                        .and_then(|result| result.checked_add(u32::from(value - b'0')))
+                        .filter(|result| *result < 2_147_483_648)
+                        .ok_or(PacketError::NumericOverflow)?;
                     buffer = b;
                 }
                 Some((b'/', _)) => {
@@ -81,10 +82,11 @@ pub type Position = Numeric;
 pub type Length = Numeric;
 
 #[derive(Debug, PartialEq, Clone)]
-pub struct Payload(pub(crate) String);
+pub struct Payload(pub String);
 
 impl Payload {
-    pub(crate) fn new(buffer: &[u8]) -> Option<Payload> {
+    #[must_use]
+    pub fn new(buffer: &[u8]) -> Option<Payload> {
         if buffer.is_empty() {
             None
         } else {
@@ -136,12 +138,12 @@ impl Payload {
     }
 
     #[allow(clippy::cast_possible_truncation)]
-    pub(crate) fn write(&self, buffer: &mut Vec<u8>, skip: u32) -> u32 {
+    pub fn write(&self, buffer: &mut Vec<u8>, skip: u32) -> u32 {
         for b in self.0.bytes().skip(skip as usize) {
             buffer.push(b);
         }
 
-        self.0.bytes().len() as u32 - skip
+        self.0.len() as u32 - skip
     }
 }
 
@@ -443,4 +445,13 @@ mod tests {
 
         assert_eq!(Packet::try_from(buffer), Err(PacketError::InvalidPacket));
     }
+
+    #[test]
+    fn test_read_overflow() {
+        let buffer = b"/connect/2147483648/".as_slice();
+        assert_eq!(Packet::try_from(buffer), Err(PacketError::NumericOverflow));
+
+        let buffer = b"/connect/66666666666/".as_slice();
+        assert_eq!(Packet::try_from(buffer), Err(PacketError::NumericOverflow));
+    }
 }
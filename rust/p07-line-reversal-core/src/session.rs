@@ -0,0 +1,287 @@
+use std::cmp;
+use std::time::{Duration, Instant};
+
+use crate::packets::{Numeric, Packet, Payload, Session as SessionId};
+
+pub const RETRANSMISSION_TIMEOUT: Duration = Duration::from_secs(3);
+pub const SESSION_EXPIRY_TIMEOUT: Duration = Duration::from_mins(1);
+
+/// The server side of an LRCP session, without I/O: the received
+/// packets and the timer ticks go in, the packets to send come out.
+#[derive(Debug)]
+pub struct Session {
+    id: SessionId,
+    retransmission_timeout: Duration,
+    expiry_timeout: Duration,
+
+    /// The in order data received, not read yet.
+    input: Vec<u8>,
+    received: u32,
+
+    /// The data written from the acked position on.
+    output: Vec<u8>,
+    acked: u32,
+    sent: u32,
+
+    last_transmission: Instant,
+    last_progress: Instant,
+    closed: bool,
+}
+
+impl Session {
+    #[must_use]
+    pub fn new(id: SessionId, now: Instant) -> Self {
+        Self::with_timeouts(id, now, RETRANSMISSION_TIMEOUT, SESSION_EXPIRY_TIMEOUT)
+    }
+
+    #[must_use]
+    pub fn with_timeouts(
+        id: SessionId,
+        now: Instant,
+        retransmission_timeout: Duration,
+        expiry_timeout: Duration,
+    ) -> Self {
+        Self {
+            id,
+            retransmission_timeout,
+            expiry_timeout,
+            input: vec![],
+            received: 0,
+            output: vec![],
+            acked: 0,
+            sent: 0,
+            last_transmission: now,
+            last_progress: now,
+            closed: false,
+        }
+    }
+
+    #[must_use]
+    pub fn id(&self) -> SessionId {
+        self.id
+    }
+
+    #[must_use]
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Handle a packet of the session, returning the packets to send.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn handle(&mut self, packet: Packet, now: Instant) -> Vec<Packet> {
+        if self.closed {
+            return vec![self.close_packet()];
+        }
+
+        match packet {
+            Packet::Connect { .. } => vec![self.ack_packet()],
+
+            Packet::Data { pos, data, .. } => {
+                let len = data.0.len() as u32;
+                if pos.0 <= self.received && pos.0 + len > self.received {
+                    let start = (self.received - pos.0) as usize;
+                    self.input.extend_from_slice(&data.0.as_bytes()[start..]);
+                    self.received = pos.0 + len;
+                }
+
+                // a duplicate ack for the data out of order
+                vec![self.ack_packet()]
+            }
+
+            Packet::Ack { length, .. } => match length.0.cmp(&self.sent) {
+                _ if length.0 <= self.acked => vec![],
+                cmp::Ordering::Greater => {
+                    self.closed = true;
+                    vec![self.close_packet()]
+                }
+                ordering => {
+                    self.output.drain(..(length.0 - self.acked) as usize);
+                    self.acked = length.0;
+                    self.last_progress = now;
+
+                    if ordering == cmp::Ordering::Less {
+                        self.sent = self.acked;
+                        self.transmit(now)
+                    } else {
+                        vec![]
+                    }
+                }
+            },
+
+            Packet::Close { .. } => {
+                self.closed = true;
+                vec![self.close_packet()]
+            }
+        }
+    }
+
+    /// Take the data received so far.
+    pub fn read(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.input)
+    }
+
+    /// Queue `data`, returning the packets to send.
+    pub fn write(&mut self, data: &[u8], now: Instant) -> Vec<Packet> {
+        if self.closed || data.is_empty() {
+            return vec![];
+        }
+
+        if self.sent == self.acked {
+            self.last_progress = now;
+        }
+        self.output.extend_from_slice(data);
+        self.transmit(now)
+    }
+
+    /// Check the timers, returning the packets to retransmit; a
+    /// session without acks for too long is closed.
+    pub fn tick(&mut self, now: Instant) -> Vec<Packet> {
+        if self.closed || self.sent == self.acked {
+            return vec![];
+        }
+
+        if now.duration_since(self.last_progress) >= self.expiry_timeout {
+            self.closed = true;
+            vec![self.close_packet()]
+        } else if now.duration_since(self.last_transmission) >= self.retransmission_timeout {
+            self.sent = self.acked;
+            self.transmit(now)
+        } else {
+            vec![]
+        }
+    }
+
+    /// The data packets from the sent position on.
+    #[allow(clippy::cast_possible_truncation)]
+    fn transmit(&mut self, now: Instant) -> Vec<Packet> {
+        let mut packets = vec![];
+        while let Some(data) = Payload::new(&self.output[(self.sent - self.acked) as usize..]) {
+            let pos = Numeric(self.sent);
+            self.sent += data.0.len() as u32;
+            packets.push(Packet::Data {
+                session: self.id,
+                pos,
+                data,
+            });
+        }
+
+        if !packets.is_empty() {
+            self.last_transmission = now;
+        }
+
+        packets
+    }
+
+    fn ack_packet(&self) -> Packet {
+        Packet::Ack {
+            session: self.id,
+            length: Numeric(self.received),
+        }
+    }
+
+    fn close_packet(&self) -> Packet {
+        Packet::Close { session: self.id }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: Numeric = Numeric(7);
+
+    fn data(pos: u32, data: &str) -> Packet {
+        Packet::Data {
+            session: ID,
+            pos: Numeric(pos),
+            data: Payload(data.to_string()),
+        }
+    }
+
+    fn ack(length: u32) -> Packet {
+        Packet::Ack {
+            session: ID,
+            length: Numeric(length),
+        }
+    }
+
+    #[test]
+    fn test_receive() {
+        let now = Instant::now();
+        let mut session = Session::new(ID, now);
+
+        assert_eq!(
+            vec![ack(0)],
+            session.handle(Packet::Connect { session: ID }, now)
+        );
+        assert_eq!(vec![ack(6)], session.handle(data(0, "hello\n"), now));
+
+        // out of order, then overlapping
+        assert_eq!(vec![ack(6)], session.handle(data(10, "later"), now));
+        assert_eq!(vec![ack(9)], session.handle(data(3, "lo\nabc"), now));
+
+        assert_eq!(b"hello\nabc".to_vec(), session.read());
+        assert!(session.read().is_empty());
+    }
+
+    #[test]
+    fn test_send_and_ack() {
+        let now = Instant::now();
+        let mut session = Session::new(ID, now);
+
+        assert_eq!(vec![data(0, "olleh\n")], session.write(b"olleh\n", now));
+        assert_eq!(vec![data(6, "cba\n")], session.write(b"cba\n", now));
+
+        // a partial ack retransmits the rest
+        assert_eq!(vec![data(3, "eh\ncba\n")], session.handle(ack(3), now));
+        assert!(session.handle(ack(10), now).is_empty());
+        assert!(session.handle(ack(4), now).is_empty());
+
+        // an ack of data never sent closes the session
+        assert_eq!(
+            vec![Packet::Close { session: ID }],
+            session.handle(ack(20), now)
+        );
+        assert!(session.is_closed());
+    }
+
+    #[test]
+    fn test_escaped_chunks() {
+        let now = Instant::now();
+        let mut session = Session::new(ID, now);
+
+        let line = "/".repeat(1000);
+        let packets = session.write(line.as_bytes(), now);
+        assert!(packets.len() > 1);
+
+        let len = packets
+            .iter()
+            .map(|packet| match packet {
+                Packet::Data { data, .. } => data.0.len(),
+                _ => unreachable!(),
+            })
+            .sum::<usize>();
+        assert_eq!(1000, len);
+    }
+
+    #[test]
+    fn test_timers() {
+        let now = Instant::now();
+        let mut session =
+            Session::with_timeouts(ID, now, Duration::from_secs(1), Duration::from_secs(5));
+
+        assert!(session.tick(now + Duration::from_secs(10)).is_empty());
+
+        session.write(b"abc\n", now);
+        assert!(session.tick(now).is_empty());
+        assert_eq!(
+            vec![data(0, "abc\n")],
+            session.tick(now + Duration::from_secs(1))
+        );
+        assert_eq!(
+            vec![Packet::Close { session: ID }],
+            session.tick(now + Duration::from_secs(5))
+        );
+        assert!(session.is_closed());
+    }
+}
@@ -0,0 +1,63 @@
+//! LRCP properties: every written packet parses back the same, and
+//! parsing any bytes never panics.
+use proptest::prelude::*;
+
+use p07_line_reversal_core::packets::{Numeric, Packet, Payload, SyncWrite};
+
+use protohackers_proptest::{ascii, bytes, mutated};
+
+/// The numerics are below 2^31.
+fn numeric() -> impl Strategy<Value = Numeric> {
+    (0..2_147_483_648_u32).prop_map(Numeric)
+}
+
+/// The payloads are ASCII, the bytes are parsed as chars; at most
+/// 450 chars, 900 bytes once escaped, so that a packet fits in 1000
+/// bytes.
+fn payload() -> impl Strategy<Value = Payload> {
+    prop_oneof![
+        ascii(450),
+        prop::collection::vec(prop_oneof![Just('/'), Just('\\'), Just('a')], 0..=450)
+            .prop_map(|chars| chars.into_iter().collect()),
+    ]
+    .prop_map(Payload)
+}
+
+fn packet() -> impl Strategy<Value = Packet> {
+    prop_oneof![
+        numeric().prop_map(|session| Packet::Connect { session }),
+        (numeric(), numeric(), payload()).prop_map(|(session, pos, data)| Packet::Data {
+            session,
+            pos,
+            data
+        }),
+        (numeric(), numeric()).prop_map(|(session, length)| Packet::Ack { session, length }),
+        numeric().prop_map(|session| Packet::Close { session }),
+    ]
+}
+
+fn write(packet: &Packet) -> Vec<u8> {
+    let mut data = vec![];
+    let len = data.write_value(packet).unwrap();
+    assert_eq!(len, data.len());
+    data
+}
+
+proptest! {
+    #[test]
+    fn test_round_trip(packet in packet()) {
+        let data = write(&packet);
+        prop_assert!(data.len() < 1000, "{} bytes", data.len());
+        prop_assert_eq!(packet, Packet::try_from(data.as_slice()).unwrap());
+    }
+
+    #[test]
+    fn test_parse_never_panics(data in bytes(1024)) {
+        let _ = Packet::try_from(data.as_slice());
+    }
+
+    #[test]
+    fn test_parse_mutated_never_panics(data in packet().prop_flat_map(|packet| mutated(write(&packet)))) {
+        let _ = Packet::try_from(data.as_slice());
+    }
+}
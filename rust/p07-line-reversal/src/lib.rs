@@ -44,25 +44,25 @@
 //! the packet instead of interpreting it as LRCP.
 //!
 //! 1. Packet contents must begin with a forward slash, end with a
-//! forward slash, have a valid message type, and have the correct
-//! number of fields for the message type.
+//!    forward slash, have a valid message type, and have the correct
+//!    number of fields for the message type.
 //!
 //! 2. Numeric field values must be smaller than 2147483648. This
-//! means sessions are limited to 2 billion bytes of data transferred
-//! in each direction.
+//!    means sessions are limited to 2 billion bytes of data transferred
+//!    in each direction.
 //!
 //! 3. LRCP messages must be smaller than 1000 bytes. You might have
-//! to break up data into multiple data messages in order to fit it
-//! below this limit.
+//!    to break up data into multiple data messages in order to fit it
+//!    below this limit.
 //!
 //! ### Parameters
 //!
 //! - retransmission timeout: the time to wait before retransmitting a
-//! message. Suggested default value: **3 seconds**.
+//!   message. Suggested default value: **3 seconds**.
 //!
 //! - session expiry timeout: the time to wait before accepting that a
-//! peer has disappeared, in the event that no responses are being
-//! received. Suggested default value: **60 seconds**.
+//!   peer has disappeared, in the event that no responses are being
+//!   received. Suggested default value: **60 seconds**.
 //!
 //! ## 1. `/connect/SESSION/`
 //!
@@ -81,12 +81,12 @@
 //! When you receive a connect message
 //!
 //! 1. If no session with this token is open: open one, and associate
-//! it with the IP address and port number that the UDP packet
-//! originated from.
+//!    it with the IP address and port number that the UDP packet
+//!    originated from.
 //!
 //! 2. Send /ack/SESSION/0/ to let the client know that the session is
-//! open (do this even if it is a duplicate connect, because the first
-//! ack may have been dropped).
+//!    open (do this even if it is a duplicate connect, because the first
+//!    ack may have been dropped).
 //!
 //! ### Example: open session number 1234567:
 //!
@@ -126,15 +126,15 @@
 //! - If the session is not open: `send /close/SESSION/` and stop.
 //!
 //! - If you've already received everything up to POS: unescape "\\"
-//! and "\/", find the total LENGTH of unescaped data that you've
-//! already received (including the data in this message, if any),
-//! send `/ack/SESSION/LENGTH/`, and pass on the new data (if any) to
-//! the application layer.
+//!   and "\/", find the total LENGTH of unescaped data that you've
+//!   already received (including the data in this message, if any),
+//!   send `/ack/SESSION/LENGTH/`, and pass on the new data (if any) to
+//!   the application layer.
 //!
 //! - If you have not received everything up to POS: send a duplicate
-//! of your previous ack (or `/ack/SESSION/0/` if none), saying how much
-//! you have received, to provoke the other side to retransmit
-//! whatever you're missing.
+//!   of your previous ack (or `/ack/SESSION/0/` if none), saying how much
+//!   you have received, to provoke the other side to retransmit
+//!   whatever you're missing.
 //!
 //! ### Example: transmit "hello", starting at the very start of session 1234567:
 //!
@@ -161,18 +161,18 @@
 //! - If the SESSION is not open: `send /close/SESSION/` and stop.
 //!
 //! - If the LENGTH value is not larger than the largest LENGTH value
-//! in any ack message you've received on this session so far: do
-//! nothing and stop (assume it's a duplicate ack that got delayed).
+//!   in any ack message you've received on this session so far: do
+//!   nothing and stop (assume it's a duplicate ack that got delayed).
 //!
 //! - If the LENGTH value is larger than the total amount of payload
-//! you've sent: the peer is misbehaving, close the session.
+//!   you've sent: the peer is misbehaving, close the session.
 //!
 //! - If the LENGTH value is smaller than the total amount of payload
-//! you've sent: retransmit all payload data after the first LENGTH
-//! bytes.
+//!   you've sent: retransmit all payload data after the first LENGTH
+//!   bytes.
 //!
 //! - If the LENGTH value is equal to the total amount of payload
-//! you've sent: don't send any reply.
+//!   you've sent: don't send any reply.
 //!
 //! ### Example: acknowledge reading the first 1024 bytes of content, on session 1234567:
 //!
@@ -256,24 +256,34 @@
 //! <-- /close/12345/
 //! --> /close/12345/
 //! ```
+use std::collections::HashMap;
 use std::io;
 use std::net::SocketAddr;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use tracing::{debug, info, warn};
+use tracing::{debug, warn};
 
-use tokio::io::{split, AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
 use tokio::net::UdpSocket;
-use tokio::sync::mpsc;
+use tokio::time;
 
+use p07_line_reversal_core::{LineReversal, Session};
+
+#[cfg(feature = "bin")]
+pub mod cli;
 pub mod lrcp;
 
 use lrcp::packets::SyncWrite;
-use lrcp::protocol::{Endpoint, Packet, Socket, SocketHandler, Stream};
+use lrcp::protocol::{Packet, SocketHandler};
 
 //const RETRASMISSION_TIMEOUT: Duration = Duration::from_secs(3);
 const RETRASMISSION_TIMEOUT: Duration = Duration::from_millis(500);
-const SESSION_EXPIRE_TIMEOUT: Duration = Duration::from_secs(60);
+const SESSION_EXPIRE_TIMEOUT: Duration = Duration::from_mins(1);
+
+/// How often the sessions check their timers.
+const TICK_PERIOD: Duration = Duration::from_millis(100);
+
+/// The LRCP messages are smaller than 1000 bytes.
+const DATAGRAM_LEN: usize = 1024;
 
 pub struct DefaultSocketHandler;
 
@@ -282,65 +292,25 @@ impl SocketHandler for DefaultSocketHandler {
     const SESSION_EXPIRE_TIMEOUT: Duration = SESSION_EXPIRE_TIMEOUT;
 }
 
-pub struct UdpEndpoint(UdpSocket);
-
-impl
-    Endpoint<
-        (SocketAddr, Packet),
-        mpsc::UnboundedReceiver<(SocketAddr, Packet)>,
-        mpsc::UnboundedSender<(SocketAddr, Packet)>,
-    > for UdpEndpoint
-{
-    fn split(
-        self,
-    ) -> (
-        mpsc::UnboundedReceiver<(SocketAddr, Packet)>,
-        mpsc::UnboundedSender<(SocketAddr, Packet)>,
-    ) {
-        let (upstream_sender, upstream_receiver) = mpsc::unbounded_channel();
-        let (downstream_sender, mut downstream_receiver) = mpsc::unbounded_channel();
-
-        tokio::spawn(async move {
-            let mut buffer = [0; 1024];
-            loop {
-                tokio::select! {
-                    Ok((len, addr)) = self.0.recv_from(&mut buffer) => {
-                        let buffer = &buffer[0..len];
-
-                        if let Ok(packet) = Packet::try_from(buffer) {
-                            info!("server --> send upstream packet {packet:?} from: {addr}");
-                            if let Err(e) = upstream_sender.send((addr, packet)) {
-                                warn!("error on send: {e:?}");
-                            }
-                        } else {
-                            warn!("invalid packet: {buffer:?} [{:?}]", String::from_utf8_lossy(buffer));
-                        }
-                    }
-
-                    Some((addr, packet)) = downstream_receiver.recv() => {
-                        info!("server <-- send downstream packet {packet:?} to: {addr}");
-
-                        let mut buffer = [0_u8; 1024];
-                        let mut b = buffer.as_mut_slice();
-
-                        let len = b.write_value(&packet).unwrap();
-
-                        self.0.send_to(&buffer[..len], addr).await.unwrap();
-                    }
-                }
-            }
-        });
-
-        (upstream_receiver, downstream_sender)
-    }
-}
-
 #[derive(thiserror::Error, Debug)]
 pub enum LineReversalError {
     #[error("io internal error")]
     IoError(#[from] io::Error),
 }
 
+struct Connection {
+    address: SocketAddr,
+    session: Session,
+    line_reversal: LineReversal,
+}
+
+/// Serve the line reversal sessions on `socket`, the protocol is in
+/// the runtime agnostic core shared with the WASI server, with the
+/// timeouts of `H`.
+///
+/// # Errors
+/// * Error when a packet can not be encoded, the socket errors are
+///   logged.
 #[tracing::instrument(skip(socket))]
 pub async fn run<H: SocketHandler + Send>(socket: UdpSocket) -> Result<(), LineReversalError> {
     debug!(
@@ -349,38 +319,95 @@ pub async fn run<H: SocketHandler + Send>(socket: UdpSocket) -> Result<(), LineR
         socket.ttl(),
     );
 
-    let endpoint = UdpEndpoint(socket);
+    let mut connections = HashMap::<(SocketAddr, u32), Connection>::new();
 
-    let mut listener = Socket::<H>::listener(endpoint)?;
+    let mut ticks = time::interval(TICK_PERIOD);
+    let mut buffer = [0; DATAGRAM_LEN];
     loop {
-        let stream = listener.accept().await?;
-        tokio::spawn(async move {
-            handle(stream).await.ok();
-        });
-    }
-}
+        tokio::select! {
+            datagram = socket.recv_from(&mut buffer) => {
+                let (len, address) = match datagram {
+                    Ok(datagram) => datagram,
+                    Err(err) => {
+                        warn!("cannot receive packet: {err}");
+                        continue;
+                    }
+                };
 
-#[tracing::instrument(skip(stream))]
-async fn handle<R: Unpin, W: Unpin>(stream: Stream<R, W>) -> Result<(), LineReversalError> {
-    let (read, write) = split(stream);
-    let mut read = BufReader::new(read);
-    let mut write = BufWriter::new(write);
+                let packet = match Packet::try_from(&buffer[..len]) {
+                    Ok(packet) => packet,
+                    Err(err) => {
+                        debug!("[{address}] ignored packet: {err}");
+                        continue;
+                    }
+                };
 
-    let mut line = String::new();
-    loop {
-        debug!("handle");
+                let key = (address, packet.session().0);
+                let connection = match connections.get_mut(&key) {
+                    Some(connection) => connection,
+                    None if matches!(packet, Packet::Connect { .. }) => {
+                        debug!("[{address}] new session {}", key.1);
+                        connections.entry(key).or_insert_with(|| Connection {
+                            address,
+                            session: Session::with_timeouts(
+                                packet.session(),
+                                Instant::now(),
+                                H::RETRASMISSION_TIMEOUT,
+                                H::SESSION_EXPIRE_TIMEOUT,
+                            ),
+                            line_reversal: LineReversal::new(),
+                        })
+                    }
+                    None => {
+                        let close = Packet::Close {
+                            session: packet.session(),
+                        };
+                        send(&socket, address, &[close]).await?;
+                        continue;
+                    }
+                };
+
+                let now = Instant::now();
+                let mut packets = connection.session.handle(packet, now);
+
+                let input = connection.session.read();
+                let output = connection.line_reversal.feed(&input);
+                packets.extend(connection.session.write(&output, now));
+
+                send(&socket, connection.address, &packets).await?;
+
+                if connection.session.is_closed() {
+                    debug!("[{address}] closed session {}", key.1);
+                    connections.remove(&key);
+                }
+            }
 
-        line.clear();
-        let len = read.read_line(&mut line).await?;
-        info!("working on {line}");
-        if len > 0 {
-            for b in line.bytes().take(len - 1).rev() {
-                write.write_u8(b).await?;
+            _ = ticks.tick() => {
+                let now = Instant::now();
+                for connection in connections.values_mut() {
+                    let packets = connection.session.tick(now);
+                    send(&socket, connection.address, &packets).await?;
+                }
+
+                connections.retain(|_, connection| !connection.session.is_closed());
             }
-            write.write_u8(b'\n').await?;
-            write.flush().await?;
-        } else {
-            warn!("line is empty");
         }
     }
 }
+
+async fn send(
+    socket: &UdpSocket,
+    address: SocketAddr,
+    packets: &[Packet],
+) -> Result<(), LineReversalError> {
+    for packet in packets {
+        let mut data = vec![];
+        data.write_value(packet)?;
+
+        if let Err(err) = socket.send_to(&data, address).await {
+            warn!("cannot send packet: {err}");
+        }
+    }
+
+    Ok(())
+}
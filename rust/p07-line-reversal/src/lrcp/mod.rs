@@ -1,2 +1,3 @@
-pub mod packets;
 pub mod protocol;
+
+pub use p07_line_reversal_core::packets;
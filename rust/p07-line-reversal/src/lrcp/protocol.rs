@@ -1,4 +1,5 @@
 use std::cmp;
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::future::{self, Future};
 use std::hash::Hash;
@@ -177,7 +178,11 @@ pub trait SocketHandler {
     const RETRASMISSION_TIMEOUT: Duration;
     const SESSION_EXPIRE_TIMEOUT: Duration;
 
-    #[allow(clippy::cast_possible_truncation, clippy::too_many_arguments)]
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::too_many_arguments,
+        clippy::too_many_lines
+    )]
     fn lrcp_handler<R, W>(
         start_connection: bool,
         handler_session: Numeric,
@@ -607,6 +612,8 @@ pub struct Listener {
 }
 
 impl Listener {
+    /// # Errors
+    /// * Error when the socket is closed.
     #[tracing::instrument]
     pub async fn accept(
         &mut self,
@@ -630,6 +637,8 @@ impl<H> Socket<H> {
 }
 
 impl<H: SocketHandler + Send> Socket<H> {
+    /// # Errors
+    /// * Never, the sessions are served on their own task.
     #[tracing::instrument(skip(endpoint))]
     pub fn listener<ADDR, R, W, E>(endpoint: E) -> Result<Listener, io::Error>
     where
@@ -640,7 +649,7 @@ impl<H: SocketHandler + Send> Socket<H> {
     {
         debug!("listener");
 
-        let (mut receiver, downstream_sender) = endpoint.split();
+        let (mut receiver, mut downstream_sender) = endpoint.split();
 
         let (listener_sender, listener_receiver) = mpsc::unbounded_channel();
 
@@ -651,19 +660,30 @@ impl<H: SocketHandler + Send> Socket<H> {
                     Ok(Some((addr, packet))) => {
                         let session = packet.session();
 
-                        let result = connections
-                            .entry((addr, session))
-                            .or_insert_with(|| {
+                        let connection = match connections.entry((addr, session)) {
+                            Entry::Occupied(entry) => entry.into_mut(),
+                            Entry::Vacant(entry) if matches!(packet, Packet::Connect { .. }) => {
                                 debug!("added connection ({addr:?}, {session:?})");
-                                Connection::new::<H, ADDR, W>(
+                                entry.insert(Connection::new::<H, ADDR, W>(
                                     addr,
                                     session,
                                     downstream_sender.clone(),
                                     &listener_sender,
-                                )
-                            })
-                            .upstream_sender
-                            .send(packet);
+                                ))
+                            }
+                            Entry::Vacant(_) => {
+                                debug!("no connection ({addr:?}, {session:?}), closing");
+                                if let Err(e) = downstream_sender
+                                    .send((addr, Packet::Close { session }))
+                                    .await
+                                {
+                                    warn!("sending downstream failed: {e}");
+                                }
+                                continue;
+                            }
+                        };
+
+                        let result = connection.upstream_sender.send(packet);
                         if let Err(e) = result {
                             warn!("sending upstream failed: {e}");
                         }
@@ -681,6 +701,8 @@ impl<H: SocketHandler + Send> Socket<H> {
         Ok(Listener { listener_receiver })
     }
 
+    /// # Errors
+    /// * Never, the session is served on its own task.
     #[tracing::instrument(skip(endpoint))]
     pub async fn connect<R, W, E>(endpoint: E) -> Result<Stream<R, W>, io::Error>
     where
@@ -756,7 +778,7 @@ mod tests {
     use super::*;
 
     const RETRASMISSION_TIMEOUT: Duration = Duration::from_millis(100);
-    const SESSION_EXPIRE_TIMEOUT: Duration = Duration::from_millis(1000);
+    const SESSION_EXPIRE_TIMEOUT: Duration = Duration::from_secs(1);
     const DELAY: Duration = Duration::from_millis(50);
 
     const _: () =
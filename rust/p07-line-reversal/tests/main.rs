@@ -19,8 +19,8 @@ use p07_line_reversal::{
 
 const BUFFER: &[u8] = b"abcdefghijklmnopqrstuvxyz0123456789ABCDEFGHIJKLMNOPQRSTUVXYZ0123456789 !";
 
-const TIMEOUT: Duration = Duration::from_millis(1000);
-const LONG_TIMEOUT: Duration = Duration::from_secs(60);
+const TIMEOUT: Duration = Duration::from_secs(1);
+const LONG_TIMEOUT: Duration = Duration::from_mins(1);
 
 fn init_tracing_subscriber() {
     static TRACING_SUBSCRIBER_INIT: parking_lot::Once = parking_lot::Once::new();
@@ -0,0 +1,285 @@
+//! The compiled cipher.
+//!
+//! Every operation is a permutation of the byte values, depending at
+//! most on the low byte of the position, so a spec is a table of 256
+//! bytes, or one table per position modulo 256 when positional. The
+//! operations are first fused, so that the common specs end up as a
+//! single xor or add, or as nothing at all.
+use std::fmt;
+use std::sync::Arc;
+
+use crate::simd;
+use crate::spec::{Operation, Spec};
+
+/// How the bytes are transformed, the decoder is the inverse of the
+/// encoder.
+#[derive(Clone, PartialEq, Eq)]
+pub(crate) enum Kernel {
+    Identity,
+    Xor(u8),
+    Add(u8),
+    Table(Arc<[u8; 256]>),
+    Positional(Arc<[[u8; 256]]>),
+}
+
+impl Kernel {
+    /// The kernel encoding like `operations`.
+    pub(crate) fn encoder(operations: &[Operation]) -> Self {
+        let operations = fuse(operations);
+        match operations.as_slice() {
+            [] => Kernel::Identity,
+            [Operation::Xor(n)] => Kernel::Xor(*n),
+            [Operation::Add(n)] => Kernel::Add(*n),
+            _ if operations.iter().any(|operation| operation.is_positional()) => {
+                Kernel::positional(
+                    (0..256)
+                        .map(|position| table(&operations, position))
+                        .collect(),
+                )
+            }
+            _ => Kernel::table(table(&operations, 0)),
+        }
+    }
+
+    fn table(table: [u8; 256]) -> Self {
+        if is_identity(&table) {
+            Kernel::Identity
+        } else {
+            Kernel::Table(Arc::new(table))
+        }
+    }
+
+    fn positional(tables: Arc<[[u8; 256]]>) -> Self {
+        if tables.iter().all(|table| *table == tables[0]) {
+            Kernel::table(tables[0])
+        } else {
+            Kernel::Positional(tables)
+        }
+    }
+
+    pub(crate) fn inverse(&self) -> Self {
+        match self {
+            Kernel::Identity => Kernel::Identity,
+            Kernel::Xor(n) => Kernel::Xor(*n),
+            Kernel::Add(n) => Kernel::Add(n.wrapping_neg()),
+            Kernel::Table(table) => Kernel::Table(Arc::new(inverse(table))),
+            Kernel::Positional(tables) => Kernel::Positional(tables.iter().map(inverse).collect()),
+        }
+    }
+
+    pub(crate) fn is_identity(&self) -> bool {
+        matches!(self, Kernel::Identity)
+    }
+
+    fn apply(&self, position: usize, data: &mut [u8]) {
+        match self {
+            Kernel::Identity => {}
+            Kernel::Xor(n) => simd::xor(data, *n),
+            Kernel::Add(n) => simd::add(data, *n),
+            Kernel::Table(table) => {
+                for value in data {
+                    *value = table[usize::from(*value)];
+                }
+            }
+            Kernel::Positional(tables) => {
+                for (i, value) in data.iter_mut().enumerate() {
+                    *value = tables[position.wrapping_add(i) & 0xff][usize::from(*value)];
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Kernel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Kernel::Identity => write!(f, "Identity"),
+            Kernel::Xor(n) => write!(f, "Xor({n})"),
+            Kernel::Add(n) => write!(f, "Add({n})"),
+            Kernel::Table(_) => write!(f, "Table"),
+            Kernel::Positional(_) => write!(f, "Positional"),
+        }
+    }
+}
+
+/// Merge the adjacent operations of the same kind, and drop the ones
+/// undoing each other or doing nothing.
+fn fuse(operations: &[Operation]) -> Vec<Operation> {
+    let mut fused = vec![];
+    for &operation in operations {
+        match (fused.last().copied(), operation) {
+            (_, Operation::Xor(0) | Operation::Add(0)) => {}
+            (Some(Operation::Reversebits), Operation::Reversebits)
+            | (Some(Operation::Xorpos), Operation::Xorpos) => {
+                fused.pop();
+            }
+            (Some(Operation::Xor(a)), Operation::Xor(b)) => {
+                fused.pop();
+                if a != b {
+                    fused.push(Operation::Xor(a ^ b));
+                }
+            }
+            (Some(Operation::Add(a)), Operation::Add(b)) => {
+                fused.pop();
+                if a.wrapping_add(b) != 0 {
+                    fused.push(Operation::Add(a.wrapping_add(b)));
+                }
+            }
+            _ => fused.push(operation),
+        }
+    }
+    fused
+}
+
+fn table(operations: &[Operation], position: usize) -> [u8; 256] {
+    let mut table = [0; 256];
+    for (value, entry) in (0..=u8::MAX).zip(table.iter_mut()) {
+        *entry = operations
+            .iter()
+            .fold(value, |value, operation| operation.encode(position, value));
+    }
+    table
+}
+
+fn inverse(table: &[u8; 256]) -> [u8; 256] {
+    let mut inverse = [0; 256];
+    for (value, &entry) in (0..=u8::MAX).zip(table.iter()) {
+        inverse[usize::from(entry)] = value;
+    }
+    inverse
+}
+
+fn is_identity(table: &[u8; 256]) -> bool {
+    (0..=u8::MAX)
+        .zip(table.iter())
+        .all(|(value, &entry)| value == entry)
+}
+
+/// A [`Spec`] compiled for ciphering buffers: each direction keeps
+/// its own position, starting from 0.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Cipher {
+    spec: Spec,
+    encoder: Kernel,
+    decoder: Kernel,
+}
+
+impl Cipher {
+    #[must_use]
+    pub fn new(spec: Spec) -> Self {
+        let encoder = Kernel::encoder(spec.operations());
+        let decoder = encoder.inverse();
+        Self {
+            spec,
+            encoder,
+            decoder,
+        }
+    }
+
+    #[must_use]
+    pub fn spec(&self) -> &Spec {
+        &self.spec
+    }
+
+    /// Encode `data` in place, the first byte at `position`.
+    pub fn encode(&self, position: usize, data: &mut [u8]) {
+        self.encoder.apply(position, data);
+    }
+
+    /// Decode `data` in place, the first byte at `position`.
+    pub fn decode(&self, position: usize, data: &mut [u8]) {
+        self.decoder.apply(position, data);
+    }
+}
+
+impl From<Spec> for Cipher {
+    fn from(spec: Spec) -> Self {
+        Self::new(spec)
+    }
+}
+
+impl fmt::Debug for Cipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cipher")
+            .field("spec", &self.spec)
+            .field("encoder", &self.encoder)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(operations: Vec<Operation>) -> Cipher {
+        Cipher::new(Spec::new(operations).unwrap())
+    }
+
+    #[test]
+    fn test_fuse() {
+        assert_eq!(
+            vec![Operation::Xor(0xab ^ 0x01), Operation::Addpos],
+            fuse(&[
+                Operation::Reversebits,
+                Operation::Reversebits,
+                Operation::Xor(0xab),
+                Operation::Add(0),
+                Operation::Xor(0x01),
+                Operation::Xorpos,
+                Operation::Xorpos,
+                Operation::Addpos,
+            ])
+        );
+        assert_eq!(
+            Vec::<Operation>::new(),
+            fuse(&[Operation::Add(0x10), Operation::Add(0xf0)])
+        );
+    }
+
+    #[test]
+    fn test_kernels() {
+        assert_eq!(
+            Kernel::Xor(0x7b),
+            compile(vec![Operation::Xor(0x70), Operation::Xor(0x0b)]).encoder
+        );
+        assert_eq!(
+            Kernel::Add(0x01),
+            compile(vec![Operation::Add(0x01)]).encoder
+        );
+        assert_eq!(
+            Kernel::Add(0xff),
+            compile(vec![Operation::Add(0x01)]).decoder
+        );
+        assert!(matches!(
+            compile(vec![Operation::Xor(0x01), Operation::Reversebits]).encoder,
+            Kernel::Table(_)
+        ));
+        assert!(matches!(
+            compile(vec![Operation::Xor(0x7b), Operation::Addpos]).encoder,
+            Kernel::Positional(_)
+        ));
+    }
+
+    #[test]
+    fn test_examples() {
+        let cipher = compile(vec![Operation::Xor(0x01), Operation::Reversebits]);
+        let mut data = *b"hello";
+        cipher.encode(0, &mut data);
+        assert_eq!([0x96, 0x26, 0xb6, 0xb6, 0x76], data);
+
+        let cipher = compile(vec![
+            Operation::Xor(123),
+            Operation::Addpos,
+            Operation::Reversebits,
+        ]);
+        let mut data = [
+            0xf2, 0x20, 0xba, 0x44, 0x18, 0x84, 0xba, 0xaa, 0xd0, 0x26, 0x44, 0xa4, 0xa8, 0x7e,
+        ];
+        cipher.decode(0, &mut data);
+        assert_eq!(*b"4x dog,5x car\n", data);
+
+        let mut data = *b"3x rat\n";
+        cipher.encode(7, &mut data);
+        assert_eq!([0xf2, 0xd0, 0x26, 0xc8, 0xa4, 0xd8, 0x7e], data);
+    }
+}
@@ -0,0 +1,127 @@
+//! The streaming adapters over [`std::io`].
+use std::io::{self, Read, Write};
+
+use crate::cipher::Cipher;
+
+/// A reader decoding the data read from the inner reader.
+#[derive(Debug)]
+pub struct DecodeReader<R> {
+    inner: R,
+    cipher: Cipher,
+    position: usize,
+}
+
+impl<R> DecodeReader<R> {
+    pub fn new(inner: R, cipher: impl Into<Cipher>) -> Self {
+        Self {
+            inner,
+            cipher: cipher.into(),
+            position: 0,
+        }
+    }
+
+    /// The position of the next byte read.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for DecodeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.cipher.decode(self.position, &mut buf[..len]);
+        self.position = self.position.wrapping_add(len);
+        Ok(len)
+    }
+}
+
+/// A writer encoding the data written to the inner writer.
+#[derive(Debug)]
+pub struct EncodeWriter<W> {
+    inner: W,
+    cipher: Cipher,
+    position: usize,
+    buffer: Vec<u8>,
+}
+
+impl<W> EncodeWriter<W> {
+    pub fn new(inner: W, cipher: impl Into<Cipher>) -> Self {
+        Self {
+            inner,
+            cipher: cipher.into(),
+            position: 0,
+            buffer: vec![],
+        }
+    }
+
+    /// The position of the next byte written.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for EncodeWriter<W> {
+    /// The data not taken by the inner writer is encoded again on the
+    /// next write, at the same position.
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buffer.clear();
+        self.buffer.extend_from_slice(data);
+        self.cipher.encode(self.position, &mut self.buffer);
+
+        let len = self.inner.write(&self.buffer)?;
+        self.position = self.position.wrapping_add(len);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::spec::{Operation, Spec};
+
+    use super::*;
+
+    /// A writer taking at most 3 bytes at a time.
+    struct Slow(Vec<u8>);
+
+    impl Write for Slow {
+        fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+            let len = data.len().min(3);
+            self.0.extend_from_slice(&data[..len]);
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let spec = Spec::new(vec![Operation::Xor(123), Operation::Addpos]).unwrap();
+
+        let mut writer = EncodeWriter::new(Slow(vec![]), spec.clone());
+        writer.write_all(b"4x dog,5x car\n").unwrap();
+        writer.write_all(b"3x rat,2x cat\n").unwrap();
+        assert_eq!(28, writer.position());
+
+        let encoded = writer.into_inner().0;
+        assert_ne!(b"4x dog,5x car\n".as_slice(), &encoded[..14]);
+
+        let mut reader = DecodeReader::new(encoded.as_slice(), spec);
+        let mut decoded = String::new();
+        reader.read_to_string(&mut decoded).unwrap();
+        assert_eq!("4x dog,5x car\n3x rat,2x cat\n", decoded);
+    }
+}
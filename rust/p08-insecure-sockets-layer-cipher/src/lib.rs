@@ -0,0 +1,17 @@
+//! Insecure Sockets Layer cipher, the runtime agnostic engine.
+//!
+//! The cipher [`Spec`] as sent by the clients, and the [`Cipher`]
+//! compiled from it: the operations are fused and turned into byte
+//! tables, so that a buffer is ciphered with a lookup per byte, or
+//! with a single xor or add, vectorized with the `simd` feature.
+//!
+//! The [`io`] adapters cipher any [`std::io`] stream; the servers, the
+//! checker and the fuzzers share the same engine.
+pub mod cipher;
+pub mod io;
+pub mod spec;
+
+mod simd;
+
+pub use cipher::Cipher;
+pub use spec::{Error, Operation, Spec};
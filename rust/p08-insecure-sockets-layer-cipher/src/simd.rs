@@ -0,0 +1,88 @@
+//! The xor and the add of a constant, 16 bytes at a time with SSE2
+//! when the `simd` feature is enabled on `x86_64`, a byte at a time
+//! otherwise.
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+pub(crate) fn xor(data: &mut [u8], n: u8) {
+    use std::arch::x86_64::{_mm_set1_epi8, _mm_xor_si128};
+
+    let rest = sse2::apply(data, |chunk| {
+        // SAFETY: SSE2 is always there on x86_64
+        unsafe { _mm_xor_si128(chunk, _mm_set1_epi8(i8::from_ne_bytes([n]))) }
+    });
+    scalar_xor(rest, n);
+}
+
+#[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+pub(crate) fn xor(data: &mut [u8], n: u8) {
+    scalar_xor(data, n);
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+pub(crate) fn add(data: &mut [u8], n: u8) {
+    use std::arch::x86_64::{_mm_add_epi8, _mm_set1_epi8};
+
+    let rest = sse2::apply(data, |chunk| {
+        // SAFETY: SSE2 is always there on x86_64
+        unsafe { _mm_add_epi8(chunk, _mm_set1_epi8(i8::from_ne_bytes([n]))) }
+    });
+    scalar_add(rest, n);
+}
+
+#[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+pub(crate) fn add(data: &mut [u8], n: u8) {
+    scalar_add(data, n);
+}
+
+fn scalar_xor(data: &mut [u8], n: u8) {
+    for value in data {
+        *value ^= n;
+    }
+}
+
+fn scalar_add(data: &mut [u8], n: u8) {
+    for value in data {
+        *value = value.wrapping_add(n);
+    }
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+mod sse2 {
+    use std::arch::x86_64::{__m128i, _mm_loadu_si128, _mm_storeu_si128};
+
+    /// Apply `f` to the chunks of 16 bytes, returning the remainder.
+    #[allow(clippy::cast_ptr_alignment)]
+    pub(super) fn apply(data: &mut [u8], f: impl Fn(__m128i) -> __m128i) -> &mut [u8] {
+        let mut chunks = data.chunks_exact_mut(16);
+        for chunk in &mut chunks {
+            // SAFETY: the chunk is 16 bytes long, the unaligned load and
+            // store do not need any alignment
+            unsafe {
+                let value = _mm_loadu_si128(chunk.as_ptr().cast::<__m128i>());
+                _mm_storeu_si128(chunk.as_mut_ptr().cast::<__m128i>(), f(value));
+            }
+        }
+        chunks.into_remainder()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kernels() {
+        let data = (0..=u8::MAX).cycle().take(1000).collect::<Vec<_>>();
+
+        let mut xored = data.clone();
+        xor(&mut xored, 0x5a);
+        assert!(data.iter().zip(&xored).all(|(a, b)| a ^ 0x5a == *b));
+
+        let mut added = data.clone();
+        add(&mut added, 0xf0);
+        assert!(data
+            .iter()
+            .zip(&added)
+            .all(|(a, b)| a.wrapping_add(0xf0) == *b));
+    }
+}
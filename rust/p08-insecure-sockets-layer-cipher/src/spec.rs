@@ -0,0 +1,236 @@
+//! The cipher spec: the operations and their wire format.
+use thiserror::Error;
+
+use crate::cipher::Kernel;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    #[error("Invalid opcode {0}")]
+    InvalidOpcode(u8),
+
+    #[error("Invalid spec {0:?}")]
+    Invalid(Vec<Operation>),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Operation {
+    Reversebits,
+    Xor(u8),
+    Xorpos,
+    Add(u8),
+    Addpos,
+}
+
+impl Operation {
+    /// Only the low byte of the position matters.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn encode(self, position: usize, value: u8) -> u8 {
+        match self {
+            Operation::Reversebits => value.reverse_bits(),
+            Operation::Xor(n) => value ^ n,
+            Operation::Xorpos => value ^ position as u8,
+            Operation::Add(n) => value.wrapping_add(n),
+            Operation::Addpos => value.wrapping_add(position as u8),
+        }
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn decode(self, position: usize, value: u8) -> u8 {
+        match self {
+            Operation::Reversebits => value.reverse_bits(),
+            Operation::Xor(n) => value ^ n,
+            Operation::Xorpos => value ^ position as u8,
+            Operation::Add(n) => value.wrapping_sub(n),
+            Operation::Addpos => value.wrapping_sub(position as u8),
+        }
+    }
+
+    pub(crate) fn is_positional(self) -> bool {
+        matches!(self, Operation::Xorpos | Operation::Addpos)
+    }
+}
+
+/// A valid cipher spec, one that changes some byte at some position.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Spec(Vec<Operation>);
+
+impl Spec {
+    /// A cipher spec from its operations.
+    ///
+    /// # Errors
+    /// * [`Error::Invalid`] when the spec leaves the data unchanged.
+    pub fn new(operations: Vec<Operation>) -> Result<Self, Error> {
+        if Kernel::encoder(&operations).is_identity() {
+            Err(Error::Invalid(operations))
+        } else {
+            Ok(Self(operations))
+        }
+    }
+
+    /// Parse a spec from the start of `data`, returning it with the
+    /// length of its encoding, `None` until the ending `00` is there.
+    ///
+    /// # Errors
+    /// * [`Error::InvalidOpcode`] on an unknown operation.
+    /// * [`Error::Invalid`] when the spec leaves the data unchanged.
+    pub fn parse(data: &[u8]) -> Result<Option<(Self, usize)>, Error> {
+        let mut operations = vec![];
+        let mut iter = data.iter().enumerate();
+        while let Some((index, &opcode)) = iter.next() {
+            let operation = match opcode {
+                0x00 => return Self::new(operations).map(|spec| Some((spec, index + 1))),
+                0x01 => Operation::Reversebits,
+                0x02 => match iter.next() {
+                    Some((_, &n)) => Operation::Xor(n),
+                    None => break,
+                },
+                0x03 => Operation::Xorpos,
+                0x04 => match iter.next() {
+                    Some((_, &n)) => Operation::Add(n),
+                    None => break,
+                },
+                0x05 => Operation::Addpos,
+                opcode => return Err(Error::InvalidOpcode(opcode)),
+            };
+            operations.push(operation);
+        }
+
+        Ok(None)
+    }
+
+    /// The wire format of the spec, with the ending `00`.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        for operation in &self.0 {
+            match operation {
+                Operation::Reversebits => bytes.push(0x01),
+                Operation::Xor(n) => bytes.extend([0x02, *n]),
+                Operation::Xorpos => bytes.push(0x03),
+                Operation::Add(n) => bytes.extend([0x04, *n]),
+                Operation::Addpos => bytes.push(0x05),
+            }
+        }
+        bytes.push(0x00);
+        bytes
+    }
+
+    #[must_use]
+    pub fn operations(&self) -> &[Operation] {
+        &self.0
+    }
+
+    /// Encode a byte applying the operations one by one, the
+    /// reference for the compiled [`Cipher`](crate::Cipher).
+    #[must_use]
+    pub fn encode(&self, position: usize, mut value: u8) -> u8 {
+        for operation in &self.0 {
+            value = operation.encode(position, value);
+        }
+        value
+    }
+
+    /// Decode a byte applying the inverse operations in reverse order.
+    #[must_use]
+    pub fn decode(&self, position: usize, mut value: u8) -> u8 {
+        for operation in self.0.iter().rev() {
+            value = operation.decode(position, value);
+        }
+        value
+    }
+
+    /// Encode `data` in place, the first byte at `position`.
+    pub fn encode_buf(&self, position: usize, data: &mut [u8]) {
+        for (i, value) in data.iter_mut().enumerate() {
+            *value = self.encode(position.wrapping_add(i), *value);
+        }
+    }
+
+    /// Decode `data` in place, the first byte at `position`.
+    pub fn decode_buf(&self, position: usize, data: &mut [u8]) {
+        for (i, value) in data.iter_mut().enumerate() {
+            *value = self.decode(position.wrapping_add(i), *value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Ok(None), Spec::parse(&[]));
+        assert_eq!(Ok(None), Spec::parse(&[0x02]));
+        assert_eq!(Ok(None), Spec::parse(&[0x02, 0x01, 0x01]));
+        assert_eq!(
+            Ok(Some((
+                Spec(vec![Operation::Xor(0x01), Operation::Reversebits]),
+                4
+            ))),
+            Spec::parse(&[0x02, 0x01, 0x01, 0x00, 0xff])
+        );
+        assert_eq!(Err(Error::InvalidOpcode(0x07)), Spec::parse(&[0x07, 0x00]));
+    }
+
+    #[test]
+    fn test_noop_ciphers() {
+        for (data, operations) in [
+            (vec![0x00], vec![]),
+            (vec![0x02, 0x00, 0x00], vec![Operation::Xor(0)]),
+            (
+                vec![0x02, 0xab, 0x02, 0xab, 0x00],
+                vec![Operation::Xor(0xab), Operation::Xor(0xab)],
+            ),
+            (
+                vec![0x01, 0x01, 0x00],
+                vec![Operation::Reversebits, Operation::Reversebits],
+            ),
+            (
+                vec![0x02, 0xa0, 0x02, 0x0b, 0x02, 0xab, 0x00],
+                vec![
+                    Operation::Xor(0xa0),
+                    Operation::Xor(0x0b),
+                    Operation::Xor(0xab),
+                ],
+            ),
+            (
+                vec![0x03, 0x04, 0x01, 0x04, 0xff, 0x03, 0x00],
+                vec![
+                    Operation::Xorpos,
+                    Operation::Add(1),
+                    Operation::Add(0xff),
+                    Operation::Xorpos,
+                ],
+            ),
+        ] {
+            assert_eq!(Err(Error::Invalid(operations)), Spec::parse(&data));
+        }
+
+        // the first byte only is unchanged
+        assert!(Spec::new(vec![Operation::Xorpos]).is_ok());
+    }
+
+    #[test]
+    fn test_to_bytes() {
+        let data = [0x02, 0x7b, 0x05, 0x01, 0x03, 0x04, 0x10, 0x00];
+        let (spec, len) = Spec::parse(&data).unwrap().unwrap();
+        assert_eq!(data.len(), len);
+        assert_eq!(data.as_slice(), spec.to_bytes());
+    }
+
+    #[test]
+    fn test_encode() {
+        let spec = Spec::new(vec![Operation::Xor(0x01), Operation::Reversebits]).unwrap();
+        let mut data = *b"hello";
+        spec.encode_buf(0, &mut data);
+        assert_eq!([0x96, 0x26, 0xb6, 0xb6, 0x76], data);
+
+        let spec = Spec::new(vec![Operation::Addpos, Operation::Addpos]).unwrap();
+        let mut data = *b"hello";
+        spec.encode_buf(0, &mut data);
+        assert_eq!([0x68, 0x67, 0x70, 0x72, 0x77], data);
+        spec.decode_buf(0, &mut data);
+        assert_eq!(*b"hello", data);
+    }
+}
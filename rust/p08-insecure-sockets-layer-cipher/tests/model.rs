@@ -0,0 +1,145 @@
+//! Model based tests: the compiled cipher must cipher like the spec
+//! applying its operations one by one.
+use std::io::{Read, Write};
+
+use proptest::prelude::*;
+
+use p08_insecure_sockets_layer_cipher::io::{DecodeReader, EncodeWriter};
+use p08_insecure_sockets_layer_cipher::{Cipher, Error, Operation, Spec};
+
+fn operation() -> impl Strategy<Value = Operation> {
+    prop_oneof![
+        Just(Operation::Reversebits),
+        any::<u8>().prop_map(Operation::Xor),
+        Just(Operation::Xorpos),
+        any::<u8>().prop_map(Operation::Add),
+        Just(Operation::Addpos),
+    ]
+}
+
+fn operations() -> impl Strategy<Value = Vec<Operation>> {
+    prop::collection::vec(operation(), 0..8)
+}
+
+/// The operation applied as in the problem statement.
+#[allow(clippy::cast_possible_truncation)]
+fn encode(operation: Operation, position: usize, value: u8) -> u8 {
+    match operation {
+        Operation::Reversebits => value.reverse_bits(),
+        Operation::Xor(n) => value ^ n,
+        Operation::Xorpos => ((position ^ usize::from(value)) % 256) as u8,
+        Operation::Add(n) => ((usize::from(value) + usize::from(n)) % 256) as u8,
+        Operation::Addpos => ((position % 256 + usize::from(value)) % 256) as u8,
+    }
+}
+
+fn to_bytes(operations: &[Operation]) -> Vec<u8> {
+    let mut bytes = vec![];
+    for operation in operations {
+        match operation {
+            Operation::Reversebits => bytes.push(0x01),
+            Operation::Xor(n) => bytes.extend([0x02, *n]),
+            Operation::Xorpos => bytes.push(0x03),
+            Operation::Add(n) => bytes.extend([0x04, *n]),
+            Operation::Addpos => bytes.push(0x05),
+        }
+    }
+    bytes.push(0x00);
+    bytes
+}
+
+fn is_noop(operations: &[Operation]) -> bool {
+    (0..256).all(|position| {
+        (0..=u8::MAX).all(|value| {
+            value
+                == operations.iter().fold(value, |value, operation| {
+                    encode(*operation, position, value)
+                })
+        })
+    })
+}
+
+proptest! {
+    #[test]
+    fn test_model(
+        operations in operations(),
+        position in any::<usize>(),
+        data in prop::collection::vec(any::<u8>(), 0..600),
+    ) {
+        let Ok(spec) = Spec::new(operations.clone()) else {
+            prop_assert!(is_noop(&operations));
+            return Ok(());
+        };
+        prop_assert!(!is_noop(&operations));
+
+        let expected = data
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                operations.iter().fold(*value, |value, operation| {
+                    encode(*operation, position.wrapping_add(i), value)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut encoded = data.clone();
+        spec.encode_buf(position, &mut encoded);
+        prop_assert_eq!(&expected, &encoded);
+
+        let cipher = Cipher::new(spec);
+        let mut encoded = data.clone();
+        cipher.encode(position, &mut encoded);
+        prop_assert_eq!(&expected, &encoded);
+
+        cipher.decode(position, &mut encoded);
+        prop_assert_eq!(data, encoded);
+    }
+
+    #[test]
+    fn test_parse(operations in operations(), rest in prop::collection::vec(any::<u8>(), 0..8)) {
+        let mut data = to_bytes(&operations);
+        let len = data.len();
+        for end in 0..len {
+            prop_assert_eq!(Ok(None), Spec::parse(&data[..end]));
+        }
+
+        data.extend(rest);
+        match Spec::new(operations.clone()) {
+            Ok(spec) => {
+                prop_assert_eq!(&data[..len], spec.to_bytes());
+                prop_assert_eq!(Ok(Some((spec, len))), Spec::parse(&data));
+            }
+            Err(err) => {
+                prop_assert_eq!(Error::Invalid(operations), err.clone());
+                prop_assert_eq!(Err(err), Spec::parse(&data));
+            }
+        }
+    }
+
+    #[test]
+    fn test_streams(
+        operations in operations(),
+        chunks in prop::collection::vec(prop::collection::vec(any::<u8>(), 0..64), 0..16),
+    ) {
+        let Ok(spec) = Spec::new(operations) else {
+            return Ok(());
+        };
+
+        let mut writer = EncodeWriter::new(vec![], spec.clone());
+        for chunk in &chunks {
+            writer.write_all(chunk).unwrap();
+        }
+        let data = chunks.concat();
+        prop_assert_eq!(data.len(), writer.position());
+
+        let encoded = writer.into_inner();
+        let mut expected = data.clone();
+        spec.encode_buf(0, &mut expected);
+        prop_assert_eq!(&expected, &encoded);
+
+        let mut reader = DecodeReader::new(encoded.as_slice(), spec);
+        let mut decoded = vec![];
+        reader.read_to_end(&mut decoded).unwrap();
+        prop_assert_eq!(data, decoded);
+    }
+}
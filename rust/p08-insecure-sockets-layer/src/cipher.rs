@@ -8,19 +8,19 @@ use bytes::{Buf, BufMut, BytesMut};
 
 use thiserror::Error;
 
-const CHECK_PHRASE: &[u8] = b"hello";
+pub use p08_insecure_sockets_layer_cipher::{Cipher, Operation, Spec};
 
 #[derive(Debug, PartialEq)]
 pub enum Codec {
-    Incomplete(Vec<Operation>),
-    Complete(Spec, usize, usize),
+    Incomplete,
+    Complete(Cipher, usize, usize),
     SpecError(SpecError),
 }
 
 impl Codec {
     #[must_use]
     pub fn new() -> Self {
-        Self::Incomplete(vec![])
+        Self::Incomplete
     }
 }
 
@@ -41,65 +41,27 @@ impl Decoder for Codec {
                 Codec::SpecError(err) => {
                     return Err(SpecError::Previous(err.to_string()));
                 }
-                Codec::Incomplete(operations) => {
-                    if src.is_empty() {
+                Codec::Incomplete => match Spec::parse(src) {
+                    Ok(Some((spec, len))) => {
+                        src.advance(len);
+                        *self = Codec::Complete(Cipher::new(spec), 0, 0);
+                        debug!("got codec: {self:?}");
+                    }
+                    Ok(None) => {
                         src.reserve(1);
                         return Ok(None);
                     }
-
-                    let mut iter = src[0..src.len()].iter().enumerate();
-                    while let Some((advance, b)) = iter.next() {
-                        match b {
-                            0x00 => {
-                                src.advance(advance + 1);
-                                let spec = Spec(operations.clone());
-                                if spec.is_valid() {
-                                    *self = Codec::Complete(spec, 0, 0);
-                                    debug!("got codec: {self:?}");
-                                    break;
-                                }
-
-                                let operations = operations.clone();
-                                *self = Codec::SpecError(SpecError::Invalid(operations.clone()));
-                                return Err(SpecError::Invalid(operations));
-                            }
-                            0x01 => {
-                                operations.push(Operation::Reversebits);
-                            }
-                            0x02 => {
-                                if let Some((_, b)) = iter.next() {
-                                    operations.push(Operation::Xor(*b));
-                                } else {
-                                    src.advance(advance + 1);
-                                    src.reserve(2);
-                                    return Ok(None);
-                                }
-                            }
-                            0x03 => operations.push(Operation::Xorpos),
-                            0x04 => {
-                                if let Some((_, b)) = iter.next() {
-                                    operations.push(Operation::Add(*b));
-                                } else {
-                                    src.advance(advance + 1);
-                                    src.reserve(2);
-                                    return Ok(None);
-                                }
-                            }
-                            0x05 => operations.push(Operation::Addpos),
-                            c => {
-                                let c = *c;
-                                src.advance(advance + 1);
-                                *self = Codec::SpecError(SpecError::InvalidOpcode(c));
-                                return Err(SpecError::InvalidOpcode(c));
-                            }
-                        }
+                    Err(err) => {
+                        *self = Codec::SpecError(err.clone().into());
+                        return Err(err.into());
                     }
-                }
-                Codec::Complete(spec, upstream_position, _) => {
+                },
+                Codec::Complete(cipher, upstream_position, _) => {
                     if src.remaining() > 0 {
-                        let r = Ok(Some(spec.decode(*upstream_position, src.get_u8())));
+                        let mut value = [src.get_u8()];
+                        cipher.decode(*upstream_position, &mut value);
                         *upstream_position += 1;
-                        return r;
+                        return Ok(Some(value[0]));
                     }
 
                     src.reserve(1);
@@ -117,10 +79,12 @@ impl Encoder<u8> for Codec {
     fn encode(&mut self, value: u8, dst: &mut BytesMut) -> Result<(), Self::Error> {
         match self {
             Codec::SpecError(err) => Err(SpecError::Previous(err.to_string())),
-            Codec::Incomplete(_) => Err(SpecError::Previous("incomplete".to_string())),
-            Codec::Complete(spec, _, downstream_position) => {
+            Codec::Incomplete => Err(SpecError::Previous("incomplete".to_string())),
+            Codec::Complete(cipher, _, downstream_position) => {
+                let mut value = [value];
+                cipher.encode(*downstream_position, &mut value);
                 dst.reserve(1);
-                dst.put_u8(spec.encode(*downstream_position, value));
+                dst.put_u8(value[0]);
                 *downstream_position += 1;
                 Ok(())
             }
@@ -128,42 +92,6 @@ impl Encoder<u8> for Codec {
     }
 }
 
-#[derive(Debug, PartialEq)]
-pub struct Spec(Vec<Operation>);
-
-#[derive(Debug, PartialEq, Clone)]
-pub enum Operation {
-    Reversebits,
-    Xor(u8),
-    Xorpos,
-    Add(u8),
-    Addpos,
-}
-
-impl Operation {
-    #[allow(clippy::cast_possible_truncation)]
-    fn encode(&self, position: usize, value: u8) -> u8 {
-        match self {
-            Operation::Reversebits => value.reverse_bits(),
-            Operation::Xor(n) => value ^ n,
-            Operation::Xorpos => ((position ^ usize::from(value)) % 256) as u8,
-            Operation::Add(n) => value.wrapping_add(*n),
-            Operation::Addpos => ((position + usize::from(value)) % 256) as u8,
-        }
-    }
-
-    #[allow(clippy::cast_possible_truncation)]
-    fn decode(&self, position: usize, value: u8) -> u8 {
-        match self {
-            Operation::Reversebits => value.reverse_bits(),
-            Operation::Xor(n) => value ^ n,
-            Operation::Xorpos => ((position ^ usize::from(value)) % 256) as u8,
-            Operation::Add(n) => value.wrapping_sub(*n),
-            Operation::Addpos => ((usize::from(value).wrapping_sub(position)) % 256) as u8,
-        }
-    }
-}
-
 #[derive(Error, Debug)]
 pub enum SpecError {
     #[error("End of Stream")]
@@ -182,6 +110,19 @@ pub enum SpecError {
     Previous(String),
 }
 
+impl From<p08_insecure_sockets_layer_cipher::Error> for SpecError {
+    fn from(err: p08_insecure_sockets_layer_cipher::Error) -> Self {
+        match err {
+            p08_insecure_sockets_layer_cipher::Error::InvalidOpcode(opcode) => {
+                SpecError::InvalidOpcode(opcode)
+            }
+            p08_insecure_sockets_layer_cipher::Error::Invalid(operations) => {
+                SpecError::Invalid(operations)
+            }
+        }
+    }
+}
+
 impl PartialEq for SpecError {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
@@ -195,33 +136,6 @@ impl PartialEq for SpecError {
     }
 }
 
-impl Spec {
-    fn is_valid(&self) -> bool {
-        CHECK_PHRASE
-            != CHECK_PHRASE
-                .iter()
-                .enumerate()
-                .map(|(position, &value)| self.encode(position, value))
-                .collect::<Vec<u8>>()
-    }
-
-    #[must_use]
-    pub fn encode(&self, position: usize, mut value: u8) -> u8 {
-        for operation in &self.0 {
-            value = operation.encode(position, value);
-        }
-        value
-    }
-
-    #[must_use]
-    pub fn decode(&self, position: usize, mut value: u8) -> u8 {
-        for operation in self.0.iter().rev() {
-            value = operation.decode(position, value);
-        }
-        value
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use std::pin::{pin, Pin};
@@ -286,7 +200,7 @@ mod tests {
 
         assert_eq!(
             &Codec::Complete(
-                Spec(vec![Operation::Xor(0x01), Operation::Reversebits]),
+                Cipher::new(Spec::new(vec![Operation::Xor(0x01), Operation::Reversebits]).unwrap()),
                 0,
                 0
             ),
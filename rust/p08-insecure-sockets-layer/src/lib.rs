@@ -55,22 +55,22 @@
 //! - `00`: End of cipher spec.
 //!
 //! - `01`: `reversebits`: Reverse the order of bits in the byte, so
-//! the least-significant bit becomes the most-significant bit, the
-//! 2nd-least-significant becomes the 2nd-most-significant, and so on.
+//!   the least-significant bit becomes the most-significant bit, the
+//!   2nd-least-significant becomes the 2nd-most-significant, and so on.
 //!
 //! - `02 N`: `xor(N)`: XOR the byte by the value N. Note that 0 is a
-//! valid value for N.
+//!   valid value for N.
 //!
 //! - `03`: `xorpos`: XOR the byte by its position in the stream,
-//! starting from 0.
+//!   starting from 0.
 //!
 //! - `04 N`: `add(N)`: Add N to the byte, modulo 256. Note that 0 is
-//! a valid value for N, and addition wraps, so that 255+1=0, 255+2=1,
-//! and so on.
+//!   a valid value for N, and addition wraps, so that 255+1=0, 255+2=1,
+//!   and so on.
 //!
 //! - `05`: `addpos`: Add the position in the stream to the byte,
-//! modulo 256, starting from 0. Addition wraps, so that 255+1=0,
-//! 255+2=1, and so on.
+//!   modulo 256, starting from 0. Addition wraps, so that 255+1=0,
+//!   255+2=1, and so on.
 //!
 //! For the purposes of the xorpos and addpos operations, note that
 //! there is a separate stream position counter for the client-sent
@@ -196,38 +196,55 @@
 //! pesky edge cases. In response to invalid input the server is free
 //! to do nothing, crash, or vanish in a puff of logic.
 use std::io;
+use std::time::Duration;
 
 use futures::{SinkExt, Stream, StreamExt, TryStreamExt};
 
-use tokio::net::{TcpListener, TcpStream};
+use tokio::net::TcpStream;
 
 use tokio_util::codec::Framed;
 
 use tracing::{debug, instrument};
 
+use protohackers_server::{IdleTimeout, Listeners, Server};
+
 pub mod cipher;
+#[cfg(feature = "bin")]
+pub mod cli;
+pub mod stream;
 
 use cipher::SpecError;
 
 pub const MAX_LINE_LENGTH: usize = 5000;
 
+/// The default idle timeout of the connections.
+pub const IDLE_TIMEOUT: Duration = Duration::from_mins(1);
+
 /// Run the main loop.
 ///
 /// Listen for clients.
 ///
 /// # Errors
 /// * Error when socket returns an error.
-#[instrument(skip(listener))]
-pub async fn run(listener: TcpListener) -> Result<(), io::Error> {
-    loop {
-        let (socket, _) = listener.accept().await?;
+#[instrument(skip(listeners))]
+pub async fn run(listeners: impl Into<Listeners>) -> Result<(), io::Error> {
+    serve(Server::new(listeners)).await
+}
 
-        tokio::spawn(handle_client(socket));
-    }
+/// Run the main loop on a [`Server`].
+///
+/// # Errors
+/// * Error when socket returns an error.
+#[instrument(skip(server))]
+pub async fn serve(server: Server) -> Result<(), io::Error> {
+    server
+        .with_default_idle_timeout(IDLE_TIMEOUT)
+        .serve(|socket, _| handle_client(socket))
+        .await
 }
 
 #[instrument(skip(stream))]
-async fn handle_client(stream: TcpStream) -> Result<(), SpecError> {
+async fn handle_client(stream: IdleTimeout<TcpStream>) -> Result<(), SpecError> {
     debug!("start");
 
     let (mut sink, mut stream) = Framed::new(stream, cipher::Codec::new()).split();
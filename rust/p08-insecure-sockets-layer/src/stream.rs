@@ -0,0 +1,148 @@
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::cipher::Cipher;
+
+/// A stream ciphered with a [`Cipher`], once the spec is known: the read
+/// data is decoded and the written data is encoded, each direction
+/// with its own position.
+#[derive(Debug)]
+pub struct CipherStream<S> {
+    inner: S,
+    cipher: Cipher,
+    read_position: usize,
+    write_position: usize,
+
+    /// The encoded data not yet written to the inner stream.
+    pending: Vec<u8>,
+    pending_start: usize,
+}
+
+impl<S> CipherStream<S> {
+    pub fn new(inner: S, cipher: impl Into<Cipher>) -> Self {
+        Self {
+            inner,
+            cipher: cipher.into(),
+            read_position: 0,
+            write_position: 0,
+            pending: vec![],
+            pending_start: 0,
+        }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// The inner stream, the encoded data not written yet is lost.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: AsyncWrite + Unpin> CipherStream<S> {
+    fn poll_write_pending(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        while self.pending_start < self.pending.len() {
+            let len = ready!(
+                Pin::new(&mut self.inner).poll_write(cx, &self.pending[self.pending_start..])
+            )?;
+            if len == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.pending_start += len;
+        }
+
+        self.pending.clear();
+        self.pending_start = 0;
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for CipherStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<Result<(), io::Error>> {
+        let this = self.get_mut();
+
+        let start = buf.filled().len();
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+
+        let data = &mut buf.filled_mut()[start..];
+        this.cipher.decode(this.read_position, data);
+        this.read_position += data.len();
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for CipherStream<S> {
+    /// The data is accepted whole once the previous one is written, so
+    /// that the positions never go back.
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        data: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.get_mut();
+
+        ready!(this.poll_write_pending(cx))?;
+
+        this.pending.extend_from_slice(data);
+        this.cipher.encode(this.write_position, &mut this.pending);
+        this.write_position += data.len();
+
+        // a first attempt right away, the rest waits for the next call
+        if let Poll::Ready(Err(err)) = this.poll_write_pending(cx) {
+            return Poll::Ready(Err(err));
+        }
+
+        Poll::Ready(Ok(data.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_pending(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_pending(cx))?;
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use crate::cipher::{Operation, Spec};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let spec = || Spec::new(vec![Operation::Xor(0x7b), Operation::Addpos]).unwrap();
+
+        let (client, server) = tokio::io::duplex(4);
+        let mut client = CipherStream::new(client, spec());
+        let mut server = CipherStream::new(server, spec());
+
+        let writer = tokio::spawn(async move {
+            client.write_all(b"4x dog,5x car\n").await.unwrap();
+            client.shutdown().await.unwrap();
+        });
+
+        let mut received = vec![];
+        server.read_to_end(&mut received).await.unwrap();
+        writer.await.unwrap();
+
+        assert_eq!(b"4x dog,5x car\n".as_slice(), received);
+    }
+}
@@ -0,0 +1,83 @@
+//! Property tests: every valid cipher spec decodes what it encodes,
+//! byte by byte and through the cipher stream.
+use proptest::prelude::*;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use p08_insecure_sockets_layer::cipher::{Operation, Spec};
+use p08_insecure_sockets_layer::stream::CipherStream;
+
+fn operation() -> impl Strategy<Value = Operation> {
+    prop_oneof![
+        Just(Operation::Reversebits),
+        any::<u8>().prop_map(Operation::Xor),
+        Just(Operation::Xorpos),
+        any::<u8>().prop_map(Operation::Add),
+        Just(Operation::Addpos),
+    ]
+}
+
+fn spec() -> impl Strategy<Value = Vec<Operation>> {
+    prop::collection::vec(operation(), 0..8).prop_filter("no-op spec", |operations| {
+        Spec::new(operations.clone()).is_ok()
+    })
+}
+
+proptest! {
+    #[test]
+    fn test_round_trip(
+        operations in spec(),
+        position in 0..100_000usize,
+        data in prop::collection::vec(any::<u8>(), 0..256),
+    ) {
+        let spec = Spec::new(operations).unwrap();
+
+        let mut buffer = data.clone();
+        spec.encode_buf(position, &mut buffer);
+        spec.decode_buf(position, &mut buffer);
+
+        prop_assert_eq!(data, buffer);
+    }
+
+    #[test]
+    fn test_noop_detected(data in prop::collection::vec(any::<u8>(), 1..64)) {
+        // a xor undone by the same xor is a no-op
+        let operations = data
+            .iter()
+            .flat_map(|&b| [Operation::Xor(b), Operation::Xor(b)])
+            .collect::<Vec<_>>();
+
+        prop_assert!(Spec::new(operations).is_err());
+    }
+
+    #[test]
+    fn test_stream_round_trip(
+        operations in spec(),
+        chunks in prop::collection::vec(prop::collection::vec(any::<u8>(), 0..64), 0..16),
+    ) {
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+
+        let received = runtime.block_on(async {
+            let (client, server) = tokio::io::duplex(16);
+            let mut client = CipherStream::new(client, Spec::new(operations.clone()).unwrap());
+            let mut server = CipherStream::new(server, Spec::new(operations).unwrap());
+
+            let writer = async {
+                for chunk in &chunks {
+                    client.write_all(chunk).await.unwrap();
+                }
+                client.shutdown().await.unwrap();
+            };
+
+            let reader = async {
+                let mut received = vec![];
+                server.read_to_end(&mut received).await.unwrap();
+                received
+            };
+
+            tokio::join!(writer, reader).1
+        });
+
+        prop_assert_eq!(chunks.concat(), received);
+    }
+}
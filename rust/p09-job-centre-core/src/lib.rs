@@ -0,0 +1,440 @@
+//! Job centre, the runtime agnostic core.
+//!
+//! The named priority queues, the leases of the jobs being worked on
+//! and the waiting workers, behind a synchronous API without any
+//! network I/O: the servers only carry the requests and wake up the
+//! workers the jobs are [`Assigned`] to.
+//!
+//! The centre is generic over the job `J`, opaque to it, and over the
+//! worker id `W`, e.g. the connection of a client.
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+
+use thiserror::Error;
+
+pub type JobId = u32;
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    #[error("no job {0}")]
+    NoJob(JobId),
+
+    #[error("job {0} not leased by the worker")]
+    NotLeased(JobId),
+}
+
+/// A job, borrowed from the centre.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Job<'a, J> {
+    pub id: JobId,
+    pub queue: &'a str,
+    pub priority: u32,
+    pub job: &'a J,
+}
+
+/// A job given to a waiting worker, already leased by it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Assigned<W> {
+    pub worker: W,
+    pub id: JobId,
+}
+
+#[derive(Debug)]
+struct Entry<J, W> {
+    queue: String,
+    priority: u32,
+    job: J,
+
+    /// The worker working on the job, `None` while queued.
+    leased: Option<W>,
+}
+
+/// The queues of the jobs.
+///
+/// The highest priority job of the requested queues comes first, the
+/// oldest one between the same priorities. A job put in a queue a
+/// worker is waiting on goes straight to the worker, the longest
+/// waiting one first.
+#[derive(Debug)]
+pub struct JobCentre<J, W> {
+    next_id: JobId,
+    jobs: HashMap<JobId, Entry<J, W>>,
+
+    /// The queued jobs, the deleted ones are dropped lazily when on
+    /// top.
+    queues: HashMap<String, BinaryHeap<(u32, Reverse<JobId>)>>,
+
+    leases: HashMap<W, HashSet<JobId>>,
+    waiters: VecDeque<(W, Vec<String>)>,
+    assigned: Vec<Assigned<W>>,
+}
+
+impl<J, W> Default for JobCentre<J, W> {
+    fn default() -> Self {
+        Self {
+            next_id: 1,
+            jobs: HashMap::new(),
+            queues: HashMap::new(),
+            leases: HashMap::new(),
+            waiters: VecDeque::new(),
+            assigned: vec![],
+        }
+    }
+}
+
+impl<J, W: Copy + Eq + Hash> JobCentre<J, W> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of jobs, queued or leased.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    #[must_use]
+    pub fn job(&self, id: JobId) -> Option<Job<'_, J>> {
+        self.jobs.get(&id).map(|entry| Job {
+            id,
+            queue: &entry.queue,
+            priority: entry.priority,
+            job: &entry.job,
+        })
+    }
+
+    /// The worker working on the job `id`, if any.
+    #[must_use]
+    pub fn leased_by(&self, id: JobId) -> Option<W> {
+        self.jobs.get(&id).and_then(|entry| entry.leased)
+    }
+
+    /// The jobs `worker` is working on.
+    pub fn leases(&self, worker: W) -> impl Iterator<Item = JobId> + '_ {
+        self.leases.get(&worker).into_iter().flatten().copied()
+    }
+
+    #[must_use]
+    pub fn is_waiting(&self, worker: W) -> bool {
+        self.waiters.iter().any(|(waiter, _)| *waiter == worker)
+    }
+
+    /// Put a job in `queue`, returning its id.
+    pub fn put(&mut self, queue: impl Into<String>, job: J, priority: u32) -> JobId {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.jobs.insert(
+            id,
+            Entry {
+                queue: queue.into(),
+                priority,
+                job,
+                leased: None,
+            },
+        );
+        self.enqueue(id);
+
+        id
+    }
+
+    /// Lease to `worker` the highest priority job of `queues`, if any.
+    pub fn get(&mut self, worker: W, queues: &[impl AsRef<str>]) -> Option<Job<'_, J>> {
+        let id = self.pop(queues)?;
+        self.lease(worker, id);
+        self.job(id)
+    }
+
+    /// Lease to `worker` the highest priority job of `queues` or, when
+    /// none, make it wait for the next job put in them: a waiting
+    /// worker replaces its previous wait.
+    pub fn get_or_wait(&mut self, worker: W, queues: &[impl AsRef<str>]) -> Option<Job<'_, J>> {
+        if let Some(id) = self.pop(queues) {
+            self.lease(worker, id);
+            return self.job(id);
+        }
+
+        self.cancel_wait(worker);
+        self.waiters.push_back((
+            worker,
+            queues
+                .iter()
+                .map(|queue| queue.as_ref().to_string())
+                .collect(),
+        ));
+
+        None
+    }
+
+    /// Stop `worker` waiting, returning whether it was.
+    pub fn cancel_wait(&mut self, worker: W) -> bool {
+        let len = self.waiters.len();
+        self.waiters.retain(|(waiter, _)| *waiter != worker);
+        self.waiters.len() != len
+    }
+
+    /// The jobs given to the waiting workers since the last call.
+    pub fn assigned(&mut self) -> impl Iterator<Item = Assigned<W>> + '_ {
+        self.assigned.drain(..)
+    }
+
+    /// Delete the job `id`, queued or leased by any worker.
+    ///
+    /// # Errors
+    /// * [`Error::NoJob`] when the job does not exist.
+    pub fn delete(&mut self, id: JobId) -> Result<J, Error> {
+        let entry = self.jobs.remove(&id).ok_or(Error::NoJob(id))?;
+        if let Some(worker) = entry.leased {
+            self.unlease(worker, id);
+        }
+        Ok(entry.job)
+    }
+
+    /// Put the job `id` leased by `worker` back in its queue.
+    ///
+    /// # Errors
+    /// * [`Error::NoJob`] when the job does not exist.
+    /// * [`Error::NotLeased`] when `worker` is not working on the job.
+    pub fn abort(&mut self, worker: W, id: JobId) -> Result<(), Error> {
+        let entry = self.jobs.get_mut(&id).ok_or(Error::NoJob(id))?;
+        if entry.leased != Some(worker) {
+            return Err(Error::NotLeased(id));
+        }
+
+        entry.leased = None;
+        self.unlease(worker, id);
+        self.enqueue(id);
+
+        Ok(())
+    }
+
+    /// Forget `worker`, e.g. on disconnection: the wait is cancelled
+    /// and its jobs are put back in their queues.
+    pub fn release(&mut self, worker: W) {
+        self.cancel_wait(worker);
+
+        let mut ids = self
+            .leases
+            .remove(&worker)
+            .unwrap_or_default()
+            .into_iter()
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+
+        for id in ids {
+            if let Some(entry) = self.jobs.get_mut(&id) {
+                entry.leased = None;
+                self.enqueue(id);
+            }
+        }
+    }
+
+    /// Queue the job `id` or give it to the first worker waiting on
+    /// its queue.
+    fn enqueue(&mut self, id: JobId) {
+        let entry = self.jobs.get_mut(&id).expect("job not found");
+
+        let waiter = self
+            .waiters
+            .iter()
+            .position(|(_, queues)| queues.contains(&entry.queue));
+        if let Some((worker, _)) = waiter.and_then(|position| self.waiters.remove(position)) {
+            entry.leased = Some(worker);
+            self.leases.entry(worker).or_default().insert(id);
+            self.assigned.push(Assigned { worker, id });
+        } else {
+            let queue = match self.queues.get_mut(&entry.queue) {
+                Some(queue) => queue,
+                None => self.queues.entry(entry.queue.clone()).or_default(),
+            };
+            queue.push((entry.priority, Reverse(id)));
+        }
+    }
+
+    /// Remove from its queue the highest priority job of `queues`.
+    fn pop(&mut self, queues: &[impl AsRef<str>]) -> Option<JobId> {
+        let mut best: Option<(&str, (u32, Reverse<JobId>))> = None;
+        for name in queues {
+            let name = name.as_ref();
+            let Some(queue) = self.queues.get_mut(name) else {
+                continue;
+            };
+
+            while let Some(&(_, Reverse(id))) = queue.peek() {
+                if self.jobs.contains_key(&id) {
+                    break;
+                }
+                queue.pop();
+            }
+
+            match queue.peek() {
+                Some(&top) if best.is_none_or(|(_, best)| top > best) => {
+                    best = Some((name, top));
+                }
+                Some(_) => {}
+                None => {
+                    self.queues.remove(name);
+                }
+            }
+        }
+
+        let (name, _) = best?;
+        let queue = self.queues.get_mut(name)?;
+        let (_, Reverse(id)) = queue.pop()?;
+        if queue.is_empty() {
+            self.queues.remove(name);
+        }
+
+        Some(id)
+    }
+
+    fn lease(&mut self, worker: W, id: JobId) {
+        if let Some(entry) = self.jobs.get_mut(&id) {
+            entry.leased = Some(worker);
+            self.leases.entry(worker).or_default().insert(id);
+        }
+    }
+
+    fn unlease(&mut self, worker: W, id: JobId) {
+        if let Some(leases) = self.leases.get_mut(&worker) {
+            leases.remove(&id);
+            if leases.is_empty() {
+                self.leases.remove(&worker);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(
+        centre: &mut JobCentre<&'static str, u32>,
+        worker: u32,
+        queues: &[&str],
+    ) -> Option<JobId> {
+        centre.get(worker, queues).map(|job| job.id)
+    }
+
+    #[test]
+    fn test_priority() {
+        let mut centre = JobCentre::new();
+
+        let id1 = centre.put("queue1", "job1", 123);
+        let id2 = centre.put("queue1", "job2", 100);
+        let id3 = centre.put("queue2", "job3", 200);
+        let id4 = centre.put("queue2", "job4", 100);
+
+        assert_eq!(
+            Some(Job {
+                id: id3,
+                queue: "queue2",
+                priority: 200,
+                job: &"job3"
+            }),
+            centre.get(1, &["queue1", "queue2"])
+        );
+        assert_eq!(Some(id1), get(&mut centre, 1, &["queue1", "queue2"]));
+        assert_eq!(Some(id2), get(&mut centre, 1, &["queue1", "queue2"]));
+        assert_eq!(None, get(&mut centre, 1, &["queue1"]));
+        assert_eq!(Some(id4), get(&mut centre, 1, &["queue2", "queue3"]));
+        assert_eq!(None, get(&mut centre, 1, &["queue1", "queue2"]));
+
+        let mut leases = centre.leases(1).collect::<Vec<_>>();
+        leases.sort_unstable();
+        assert_eq!(vec![id1, id2, id3, id4], leases);
+        assert_eq!(4, centre.len());
+    }
+
+    #[test]
+    fn test_delete() {
+        let mut centre = JobCentre::<_, u32>::new();
+
+        let id1 = centre.put("queue1", "job1", 1);
+        let id2 = centre.put("queue1", "job2", 2);
+
+        assert_eq!(Ok("job2"), centre.delete(id2));
+        assert_eq!(Err(Error::NoJob(id2)), centre.delete(id2));
+        assert_eq!(Some(id1), get(&mut centre, 1, &["queue1"]));
+
+        // a leased job can be deleted by any worker
+        assert_eq!(Ok("job1"), centre.delete(id1));
+        assert_eq!(None, centre.leases(1).next());
+        assert!(centre.is_empty());
+    }
+
+    #[test]
+    fn test_abort() {
+        let mut centre = JobCentre::new();
+
+        let id = centre.put("queue1", "job", 1);
+        assert_eq!(Err(Error::NotLeased(id)), centre.abort(1, id));
+
+        assert_eq!(Some(id), get(&mut centre, 1, &["queue1"]));
+        assert_eq!(Err(Error::NotLeased(id)), centre.abort(2, id));
+        assert_eq!(Ok(()), centre.abort(1, id));
+        assert_eq!(Err(Error::NotLeased(id)), centre.abort(1, id));
+        assert_eq!(Err(Error::NoJob(42)), centre.abort(1, 42));
+
+        assert_eq!(Some(id), get(&mut centre, 2, &["queue1"]));
+        assert_eq!(Some(2), centre.leased_by(id));
+    }
+
+    #[test]
+    fn test_wait() {
+        let mut centre = JobCentre::new();
+
+        assert_eq!(None, centre.get_or_wait(1, &["queue1"]));
+        assert_eq!(None, centre.get_or_wait(2, &["queue1", "queue2"]));
+        assert!(centre.is_waiting(1));
+
+        let id1 = centre.put("queue2", "job1", 1);
+        let id2 = centre.put("queue1", "job2", 1);
+        let id3 = centre.put("queue1", "job3", 1);
+
+        assert_eq!(
+            vec![
+                Assigned { worker: 2, id: id1 },
+                Assigned { worker: 1, id: id2 }
+            ],
+            centre.assigned().collect::<Vec<_>>()
+        );
+        assert!(!centre.is_waiting(1));
+        assert_eq!(Some(1), centre.leased_by(id2));
+        assert_eq!(
+            Some(id3),
+            centre.get_or_wait(3, &["queue1"]).map(|job| job.id)
+        );
+    }
+
+    #[test]
+    fn test_release() {
+        let mut centre = JobCentre::new();
+
+        let id1 = centre.put("queue1", "job1", 1);
+        let id2 = centre.put("queue2", "job2", 2);
+        assert_eq!(Some(id2), get(&mut centre, 1, &["queue1", "queue2"]));
+        assert_eq!(Some(id1), get(&mut centre, 1, &["queue1", "queue2"]));
+
+        assert_eq!(None, centre.get_or_wait(2, &["queue2"]));
+        assert_eq!(None, centre.get_or_wait(3, &["queue3"]));
+        centre.release(3);
+        assert!(!centre.is_waiting(3));
+
+        centre.release(1);
+        assert_eq!(
+            vec![Assigned { worker: 2, id: id2 }],
+            centre.assigned().collect::<Vec<_>>()
+        );
+        assert_eq!(None, centre.leased_by(id1));
+        assert_eq!(Some(id1), get(&mut centre, 4, &["queue1"]));
+    }
+}
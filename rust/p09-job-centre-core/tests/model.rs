@@ -0,0 +1,260 @@
+//! Model based tests: random operations run against the centre and
+//! against a naive model, the results must be the same.
+use proptest::prelude::*;
+
+use p09_job_centre_core::{Assigned, Error, JobCentre, JobId};
+
+const QUEUES: [&str; 3] = ["queue0", "queue1", "queue2"];
+
+#[derive(Debug, Clone)]
+enum Op {
+    Put { queue: usize, priority: u32 },
+    Get { worker: u8, queues: Vec<usize> },
+    GetOrWait { worker: u8, queues: Vec<usize> },
+    CancelWait { worker: u8 },
+    Delete { id: JobId },
+    Abort { worker: u8, id: JobId },
+    Release { worker: u8 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Queued,
+    Leased(u8),
+}
+
+/// The reference: the jobs in a plain vector, scanned on every get.
+#[derive(Debug, Default)]
+struct Model {
+    puts: JobId,
+    jobs: Vec<(JobId, &'static str, u32, State)>,
+    waiters: Vec<(u8, Vec<&'static str>)>,
+    assigned: Vec<Assigned<u8>>,
+}
+
+impl Model {
+    fn put(&mut self, queue: &'static str, priority: u32) -> JobId {
+        self.puts += 1;
+        let id = self.puts;
+        self.jobs.push((id, queue, priority, State::Queued));
+        self.enqueue(id);
+        id
+    }
+
+    fn get(&mut self, worker: u8, queues: &[&'static str]) -> Option<JobId> {
+        let (id, _, _, state) = self
+            .jobs
+            .iter_mut()
+            .filter(|(_, queue, _, state)| *state == State::Queued && queues.contains(queue))
+            // the highest priority, the oldest first
+            .max_by_key(|(id, _, priority, _)| (*priority, std::cmp::Reverse(*id)))?;
+        *state = State::Leased(worker);
+        Some(*id)
+    }
+
+    fn get_or_wait(&mut self, worker: u8, queues: &[&'static str]) -> Option<JobId> {
+        let id = self.get(worker, queues);
+        if id.is_none() {
+            self.cancel_wait(worker);
+            self.waiters.push((worker, queues.to_vec()));
+        }
+        id
+    }
+
+    fn cancel_wait(&mut self, worker: u8) -> bool {
+        let len = self.waiters.len();
+        self.waiters.retain(|(waiter, _)| *waiter != worker);
+        self.waiters.len() != len
+    }
+
+    fn delete(&mut self, id: JobId) -> Result<(), Error> {
+        let len = self.jobs.len();
+        self.jobs.retain(|job| job.0 != id);
+        if self.jobs.len() == len {
+            Err(Error::NoJob(id))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn abort(&mut self, worker: u8, id: JobId) -> Result<(), Error> {
+        let job = self
+            .jobs
+            .iter_mut()
+            .find(|job| job.0 == id)
+            .ok_or(Error::NoJob(id))?;
+        if job.3 != State::Leased(worker) {
+            return Err(Error::NotLeased(id));
+        }
+        job.3 = State::Queued;
+        self.enqueue(id);
+        Ok(())
+    }
+
+    fn release(&mut self, worker: u8) {
+        self.cancel_wait(worker);
+
+        let ids = self
+            .jobs
+            .iter()
+            .filter(|job| job.3 == State::Leased(worker))
+            .map(|job| job.0)
+            .collect::<Vec<_>>();
+        for id in ids {
+            self.jobs.iter_mut().find(|job| job.0 == id).unwrap().3 = State::Queued;
+            self.enqueue(id);
+        }
+    }
+
+    fn enqueue(&mut self, id: JobId) {
+        let job = self.jobs.iter_mut().find(|job| job.0 == id).unwrap();
+        if let Some(position) = self
+            .waiters
+            .iter()
+            .position(|(_, queues)| queues.contains(&job.1))
+        {
+            let (worker, _) = self.waiters.remove(position);
+            job.3 = State::Leased(worker);
+            self.assigned.push(Assigned { worker, id });
+        }
+    }
+
+    fn leases(&self, worker: u8) -> Vec<JobId> {
+        self.jobs
+            .iter()
+            .filter(|job| job.3 == State::Leased(worker))
+            .map(|job| job.0)
+            .collect()
+    }
+}
+
+fn queues() -> impl Strategy<Value = Vec<usize>> {
+    prop::collection::vec(0..QUEUES.len(), 0..=QUEUES.len())
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        3 => (0..QUEUES.len(), 0..4_u32).prop_map(|(queue, priority)| Op::Put { queue, priority }),
+        2 => (0..4_u8, queues()).prop_map(|(worker, queues)| Op::Get { worker, queues }),
+        2 => (0..4_u8, queues()).prop_map(|(worker, queues)| Op::GetOrWait { worker, queues }),
+        1 => (0..4_u8).prop_map(|worker| Op::CancelWait { worker }),
+        1 => (0..24_u32).prop_map(|id| Op::Delete { id }),
+        2 => (0..4_u8, 0..24_u32).prop_map(|(worker, id)| Op::Abort { worker, id }),
+        1 => (0..4_u8).prop_map(|worker| Op::Release { worker }),
+    ]
+}
+
+fn names(queues: &[usize]) -> Vec<&'static str> {
+    queues.iter().map(|queue| QUEUES[*queue]).collect()
+}
+
+proptest! {
+    #[test]
+    fn test_model(ops in prop::collection::vec(op(), 0..64)) {
+        let mut centre = JobCentre::new();
+        let mut model = Model::default();
+
+        for op in ops {
+            match op {
+                Op::Put { queue, priority } => {
+                    let id = centre.put(QUEUES[queue], (queue, priority), priority);
+                    prop_assert_eq!(model.put(QUEUES[queue], priority), id);
+                }
+
+                Op::Get { worker, queues } => {
+                    let queues = names(&queues);
+                    let job = centre.get(worker, &queues);
+                    if let Some(job) = &job {
+                        prop_assert_eq!(QUEUES[job.job.0], job.queue);
+                        prop_assert_eq!(job.job.1, job.priority);
+                    }
+                    prop_assert_eq!(model.get(worker, &queues), job.map(|job| job.id));
+                }
+
+                Op::GetOrWait { worker, queues } => {
+                    let queues = names(&queues);
+                    let id = centre.get_or_wait(worker, &queues).map(|job| job.id);
+                    prop_assert_eq!(model.get_or_wait(worker, &queues), id);
+                }
+
+                Op::CancelWait { worker } => {
+                    prop_assert_eq!(model.cancel_wait(worker), centre.cancel_wait(worker));
+                }
+
+                Op::Delete { id } => {
+                    prop_assert_eq!(model.delete(id), centre.delete(id).map(|_| ()));
+                }
+
+                Op::Abort { worker, id } => {
+                    prop_assert_eq!(model.abort(worker, id), centre.abort(worker, id));
+                }
+
+                Op::Release { worker } => {
+                    centre.release(worker);
+                    model.release(worker);
+                }
+            }
+
+            prop_assert_eq!(
+                std::mem::take(&mut model.assigned),
+                centre.assigned().collect::<Vec<_>>()
+            );
+            prop_assert_eq!(model.jobs.len(), centre.len());
+            for worker in 0..4 {
+                let mut leases = centre.leases(worker).collect::<Vec<_>>();
+                leases.sort_unstable();
+                prop_assert_eq!(model.leases(worker), leases);
+                prop_assert_eq!(
+                    model.waiters.iter().any(|(waiter, _)| *waiter == worker),
+                    centre.is_waiting(worker)
+                );
+            }
+        }
+    }
+
+    /// No job is lost: once all the workers are gone every job is
+    /// back in its queue.
+    #[test]
+    fn test_no_lost_jobs(ops in prop::collection::vec(op(), 0..64)) {
+        let mut centre = JobCentre::new();
+
+        for op in ops {
+            match op {
+                Op::Put { queue, priority } => {
+                    centre.put(QUEUES[queue], (), priority);
+                }
+                Op::Get { worker, queues } => {
+                    centre.get(worker, &names(&queues));
+                }
+                Op::GetOrWait { worker, queues } => {
+                    centre.get_or_wait(worker, &names(&queues));
+                }
+                Op::CancelWait { worker } => {
+                    centre.cancel_wait(worker);
+                }
+                Op::Delete { id } => {
+                    centre.delete(id).ok();
+                }
+                Op::Abort { worker, id } => {
+                    centre.abort(worker, id).ok();
+                }
+                Op::Release { worker } => centre.release(worker),
+            }
+            centre.assigned().for_each(drop);
+        }
+
+        for worker in 0..4 {
+            centre.cancel_wait(worker);
+        }
+        for worker in 0..4 {
+            centre.release(worker);
+        }
+        prop_assert_eq!(0, centre.assigned().count());
+
+        let mut drained = 0;
+        while centre.get(0, &QUEUES).is_some() {
+            drained += 1;
+        }
+        prop_assert_eq!(centre.len(), drained);
+    }
+}
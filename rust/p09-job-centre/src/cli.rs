@@ -0,0 +1,29 @@
+//! Only the standard server flags: the queues live in memory, with no
+//! persistence to configure.
+use tracing::info;
+
+use protohackers_server::ServerArgs;
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[command(flatten)]
+    pub server: ServerArgs,
+}
+
+/// Run the server configured by `args`.
+///
+/// # Errors
+/// * Error when the server can not be started or fails.
+pub async fn run(args: Args) -> Result<(), anyhow::Error> {
+    args.server.init_tracing();
+
+    info!("start");
+
+    Ok(crate::serve(
+        args.server
+            .server()
+            .await?
+            .with_problem(env!("CARGO_PKG_NAME")),
+    )
+    .await?)
+}
@@ -1,20 +1,26 @@
-use std::collections::{BinaryHeap, HashMap};
-use std::sync::{Arc, Weak};
+use std::collections::HashMap;
+use std::sync::Arc;
 
 use parking_lot::Mutex;
 
 use thiserror::Error;
 
-use tokio::sync::Notify;
+use tokio::sync::oneshot;
 
-use tracing::warn;
+use p09_job_centre_core::{Assigned, JobId};
 
 type Job = serde_json::Value;
-type JobId = u32;
+type WorkerId = u64;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct JobRef(pub JobId, pub Job, pub u32, pub String);
 
+impl From<p09_job_centre_core::Job<'_, Job>> for JobRef {
+    fn from(job: p09_job_centre_core::Job<'_, Job>) -> Self {
+        Self(job.id, job.job.clone(), job.priority, job.queue.to_string())
+    }
+}
+
 #[derive(Error, Debug, PartialEq)]
 pub enum WorkerError {
     #[error("no-job")]
@@ -27,7 +33,7 @@ pub enum WorkerError {
 /// The worker.
 pub struct Worker {
     job_centre: JobCentre,
-    jobs: Vec<Weak<JobRef>>,
+    id: WorkerId,
 }
 
 impl Worker {
@@ -35,7 +41,10 @@ impl Worker {
     /// priority.
     #[must_use]
     pub fn put(&mut self, queue: String, job: Job, priority: u32) -> JobId {
-        self.job_centre.put(queue, job, priority)
+        let mut inner = self.job_centre.0.lock();
+        let id = inner.centre.put(queue, job, priority);
+        inner.wake();
+        id
     }
 
     /// Retrieve the highest-priority job that is currently waiting in
@@ -48,13 +57,33 @@ impl Worker {
         queues: &[Q],
         wait: bool,
     ) -> Result<JobRef, WorkerError> {
-        let job_ref = self.job_centre.get(queues, wait).await?;
-        self.jobs.push(job_ref.clone());
-        if let Some(mut job_ref) = job_ref.upgrade() {
-            Ok(Arc::make_mut(&mut job_ref).clone())
-        } else {
-            warn!("job deallocated");
-            Err(WorkerError::NoJob)
+        loop {
+            let receiver = {
+                let mut inner = self.job_centre.0.lock();
+                if !wait {
+                    return inner
+                        .centre
+                        .get(self.id, queues)
+                        .map(JobRef::from)
+                        .ok_or(WorkerError::NoJob);
+                }
+
+                if let Some(job) = inner.centre.get_or_wait(self.id, queues) {
+                    return Ok(job.into());
+                }
+
+                let (sender, receiver) = oneshot::channel();
+                inner.waiters.insert(self.id, sender);
+                receiver
+            };
+
+            if let Ok(id) = receiver.await {
+                if let Some(job) = self.job_centre.0.lock().centre.job(id) {
+                    return Ok(job.into());
+                }
+            }
+
+            // the job has been deleted before being picked up
         }
     }
 
@@ -65,7 +94,13 @@ impl Worker {
     /// # Errors
     /// - when no-job
     pub fn delete(&mut self, id: JobId) -> Result<(), WorkerError> {
-        self.job_centre.delete(id)
+        self.job_centre
+            .0
+            .lock()
+            .centre
+            .delete(id)
+            .map(drop)
+            .map_err(|_| WorkerError::NoJob)
     }
 
     /// Put the job with the given `id` back in its queue. This
@@ -74,109 +109,54 @@ impl Worker {
     /// to abort a job that is not currently working on.
     ///
     /// # Errors
+    /// - when no-job
     /// - when requesting abort of a not-owned job
     pub fn abort(&mut self, id: JobId) -> Result<(), WorkerError> {
-        if let Some(job_ref) = self.get_from_working_jobs(id) {
-            self.job_centre.abort(&job_ref);
-            Ok(())
-        } else {
-            Err(WorkerError::InvalidRequest)
-        }
-    }
-
-    fn get_from_working_jobs(&mut self, job_id: JobId) -> Option<Arc<JobRef>> {
-        let mut r = None;
-        self.jobs.retain_mut(|job_ref| {
-            if let Some(job_ref) = job_ref.upgrade() {
-                if job_ref.0 == job_id {
-                    r = Some(job_ref.clone());
-                    return false;
-                }
-                true
-            } else {
-                false
-            }
-        });
-        r
+        let mut inner = self.job_centre.0.lock();
+        inner.centre.abort(self.id, id).map_err(|err| match err {
+            p09_job_centre_core::Error::NoJob(_) => WorkerError::NoJob,
+            p09_job_centre_core::Error::NotLeased(_) => WorkerError::InvalidRequest,
+        })?;
+        inner.wake();
+        Ok(())
     }
 }
 
 impl Drop for Worker {
     fn drop(&mut self) {
-        self.job_centre.remove_worker(&self.jobs);
+        let mut inner = self.job_centre.0.lock();
+        inner.waiters.remove(&self.id);
+        inner.centre.release(self.id);
+        inner.wake();
     }
 }
 
 struct JobCentreInner {
-    next_job_id: JobId,
-    queues: HashMap<String, BinaryHeap<(u32, JobId)>>,
-    jobs: HashMap<JobId, (Job, u32, String)>,
-    running_jobs: Vec<Arc<JobRef>>,
+    centre: p09_job_centre_core::JobCentre<Job, WorkerId>,
+    next_worker_id: WorkerId,
+
+    /// The workers waiting for a job.
+    waiters: HashMap<WorkerId, oneshot::Sender<JobId>>,
 }
 
 impl JobCentreInner {
-    fn next_job_id(&mut self) -> u32 {
-        let r = self.next_job_id;
-        self.next_job_id += 1;
-        r
-    }
-
-    fn put(&mut self, queue: String, job: Job, priority: u32) -> u32 {
-        let job_id = self.next_job_id();
-        self.jobs.insert(job_id, (job, priority, queue.clone()));
-        let queue = self.queues.entry(queue).or_default();
-        queue.push((priority, job_id));
-        job_id
-    }
-
-    fn get<Q: AsRef<str>>(&mut self, queues: &[Q]) -> Result<Weak<JobRef>, WorkerError> {
-        let r = queues
-            .iter()
-            .filter_map(|queue_name| {
-                if let Some(queue) = self.queues.get(queue_name.as_ref()) {
-                    if let Some((priority, _)) = queue.peek() {
-                        return Some((*priority, queue_name));
-                    }
-                }
-                None
-            })
-            .max_by_key(|(priority, _)| *priority);
-
-        if let Some((_, queue)) = r {
-            let (_, id) = self.queues.get_mut(queue.as_ref()).unwrap().pop().unwrap();
-            let (job, priority, queue) = self.jobs.get(&id).unwrap();
-            let job_ref = Arc::new(JobRef(id, job.clone(), *priority, queue.clone()));
-            let r = Arc::downgrade(&job_ref);
-            self.running_jobs.push(job_ref);
-            return Ok(r);
-        }
-
-        Err(WorkerError::NoJob)
-    }
-
-    fn delete(&mut self, job_id: JobId) -> Result<(), WorkerError> {
-        if let Some(job) = self.jobs.remove(&job_id) {
-            self.running_jobs.retain(|job_ref| job_ref.0 != job_id);
-            if let Some(queue) = self.queues.get_mut(&job.2) {
-                queue.retain(|(_, id)| *id != job_id);
+    /// Hand the assigned jobs to the waiting workers, a job of a
+    /// worker no longer waiting goes back in its queue.
+    fn wake(&mut self) {
+        loop {
+            let assigned = self.centre.assigned().collect::<Vec<_>>();
+            if assigned.is_empty() {
+                break;
             }
-            Ok(())
-        } else {
-            Err(WorkerError::NoJob)
-        }
-    }
 
-    fn abort(&mut self, job_ref: &Arc<JobRef>) {
-        self.running_jobs
-            .retain(|running_job_ref| running_job_ref.0 != job_ref.0);
-        let queue = self.queues.entry(job_ref.3.clone()).or_default();
-        queue.push((job_ref.2, job_ref.0));
-    }
-
-    fn remove_worker(&mut self, job_refs: &[Weak<JobRef>]) {
-        for job_ref in job_refs {
-            if let Some(job_ref) = job_ref.upgrade() {
-                self.abort(&job_ref);
+            for Assigned { worker, id } in assigned {
+                let delivered = self
+                    .waiters
+                    .remove(&worker)
+                    .is_some_and(|sender| sender.send(id).is_ok());
+                if !delivered {
+                    self.centre.abort(worker, id).ok();
+                }
             }
         }
     }
@@ -184,7 +164,7 @@ impl JobCentreInner {
 
 /// The Job Centre
 #[derive(Clone)]
-pub struct JobCentre(Arc<(Mutex<JobCentreInner>, Notify)>);
+pub struct JobCentre(Arc<Mutex<JobCentreInner>>);
 
 impl Default for JobCentre {
     fn default() -> Self {
@@ -195,59 +175,24 @@ impl Default for JobCentre {
 impl JobCentre {
     #[must_use]
     pub fn new() -> Self {
-        Self(Arc::new((
-            Mutex::new(JobCentreInner {
-                next_job_id: 1,
-                queues: HashMap::new(),
-                jobs: HashMap::new(),
-                running_jobs: vec![],
-            }),
-            Notify::new(),
-        )))
+        Self(Arc::new(Mutex::new(JobCentreInner {
+            centre: p09_job_centre_core::JobCentre::new(),
+            next_worker_id: 0,
+            waiters: HashMap::new(),
+        })))
     }
 
     #[must_use]
     pub fn make_worker(&self) -> Worker {
+        let mut inner = self.0.lock();
+        let id = inner.next_worker_id;
+        inner.next_worker_id += 1;
+
         Worker {
             job_centre: self.clone(),
-            jobs: vec![],
+            id,
         }
     }
-
-    fn remove_worker(&self, job_refs: &[Weak<JobRef>]) {
-        self.0 .0.lock().remove_worker(job_refs);
-        self.0 .1.notify_waiters();
-    }
-
-    fn put(&self, queue: String, job: Job, priority: u32) -> u32 {
-        let r = self.0 .0.lock().put(queue, job, priority);
-        self.0 .1.notify_waiters();
-        r
-    }
-
-    async fn get<'a, Q: AsRef<str> + 'a>(
-        &'a self,
-        queues: &'a [Q],
-        wait: bool,
-    ) -> Result<Weak<JobRef>, WorkerError> {
-        loop {
-            let r = { self.0 .0.lock().get(queues) };
-            if r.is_err() && wait {
-                self.0 .1.notified().await;
-            } else {
-                break r;
-            }
-        }
-    }
-
-    fn delete(&self, id: JobId) -> Result<(), WorkerError> {
-        self.0 .0.lock().delete(id)
-    }
-
-    fn abort(&self, job_ref: &Arc<JobRef>) {
-        self.0 .0.lock().abort(job_ref);
-        self.0 .1.notify_waiters();
-    }
 }
 
 #[cfg(test)]
@@ -392,5 +337,7 @@ mod tests {
         assert_eq!(Err(WorkerError::InvalidRequest), worker2.abort(id));
 
         assert_eq!(Ok(()), worker1.abort(id));
+        assert_eq!(Ok(()), worker1.delete(id));
+        assert_eq!(Err(WorkerError::NoJob), worker1.abort(id));
     }
 }
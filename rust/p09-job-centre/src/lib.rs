@@ -106,7 +106,7 @@
 //! - the job is explicitly aborted by the client working on it
 //!
 //! - the job is automatically aborted when the client working on it
-//! disconnects
+//!   disconnects
 //!
 //! ### delete
 //!
@@ -202,45 +202,70 @@ use std::io;
 use futures::{SinkExt, StreamExt};
 
 use tokio::io::{BufReader, BufWriter};
-use tokio::net::{TcpListener, TcpStream};
+use tokio::net::TcpStream;
 
-use tokio_util::codec::{FramedRead, FramedWrite};
+use bytes::BytesMut;
+
+use tokio_util::codec::{Decoder, FramedRead, FramedWrite};
 
 use tracing::{debug, info, instrument, warn};
 
+use protohackers_server::{IdleTimeout, Listeners, Server};
+
+#[cfg(feature = "bin")]
+pub mod cli;
 pub mod job_centre;
 pub mod protocol;
 
-use job_centre::{JobCentre, JobRef, Worker};
-use protocol::{Request, Response};
+use job_centre::{JobCentre, JobRef, Worker, WorkerError};
+use protocol::{Request, RequestError, Response};
 
-#[instrument(skip(listener))]
-pub async fn run(listener: TcpListener) -> Result<(), io::Error> {
-    let job_centre = JobCentre::new();
-
-    loop {
-        let (stream, remote_addr) = listener.accept().await?;
+/// Run the job centre, serving the clients of `listener`.
+///
+/// # Errors
+/// * Error when the listener fails.
+#[instrument(skip(listeners))]
+pub async fn run(listeners: impl Into<Listeners>) -> Result<(), io::Error> {
+    serve(Server::new(listeners)).await
+}
 
-        info!("remote: {remote_addr:?}");
+/// Run the job centre, serving the clients of a [`Server`]. There is
+/// no default idle timeout: a worker waiting for a job sends nothing.
+///
+/// # Errors
+/// * Error when the listener fails.
+#[instrument(skip(server))]
+pub async fn serve(server: Server) -> Result<(), io::Error> {
+    let job_centre = JobCentre::new();
 
-        let worker = job_centre.make_worker();
+    server
+        .serve(move |stream, remote_addr| {
+            info!("remote: {remote_addr:?}");
 
-        tokio::spawn(handle_client(stream, worker));
-    }
+            let job_centre = job_centre.clone();
+            async move { handle_client(stream, job_centre.make_worker()).await }
+        })
+        .await
 }
 
 #[instrument(skip(stream, worker))]
-async fn handle_client(mut stream: TcpStream, mut worker: Worker) -> Result<(), io::Error> {
+async fn handle_client(
+    mut stream: IdleTimeout<TcpStream>,
+    mut worker: Worker,
+) -> Result<(), io::Error> {
     debug!("start");
 
     let (read, write) = stream.split();
-    let mut read = FramedRead::new(BufReader::new(read), protocol::RequestCodec::new());
+    let mut read = FramedRead::new(
+        BufReader::new(read),
+        Requests(protocol::RequestCodec::new()),
+    );
     let mut write = FramedWrite::new(BufWriter::new(write), protocol::ResponseCodec::new());
 
     while let Some(request) = read.next().await {
         debug!("request: {request:?}");
 
-        match request {
+        match request? {
             Ok(Request::Put {
                 queue,
                 job,
@@ -276,11 +301,13 @@ async fn handle_client(mut stream: TcpStream, mut worker: Worker) -> Result<(),
             }
 
             Ok(Request::Abort { id }) => {
-                if worker.abort(id).is_ok() {
-                    write.send(Response::Ok).await.ok();
-                } else {
-                    write.send(Response::NoJob).await.ok();
-                }
+                match worker.abort(id) {
+                    Ok(()) => write.send(Response::Ok).await.ok(),
+                    Err(WorkerError::NoJob) => write.send(Response::NoJob).await.ok(),
+                    Err(err @ WorkerError::InvalidRequest) => {
+                        write.send(Response::Error(err.to_string())).await.ok()
+                    }
+                };
                 write.flush().await.ok();
             }
 
@@ -297,3 +324,21 @@ async fn handle_client(mut stream: TcpStream, mut worker: Worker) -> Result<(),
 
     Ok(())
 }
+
+/// The requests of a client, an invalid one is an item and not an
+/// error: a decode error would end the frames, while the connection
+/// must go on.
+struct Requests(protocol::RequestCodec);
+
+impl Decoder for Requests {
+    type Item = Result<Request, RequestError>;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.0.decode(src) {
+            Ok(request) => Ok(request.map(Ok)),
+            Err(RequestError::Io(err)) => Err(err),
+            Err(err) => Ok(Some(Err(err))),
+        }
+    }
+}
@@ -1,29 +1,6 @@
-use clap::Parser;
-use tokio::net::TcpListener;
-
-use tracing::info;
-
-use p09_job_centre::run;
-
-#[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
-struct Args {
-    #[arg(long, default_value = "0.0.0.0")]
-    address: String,
-
-    #[arg(long, default_value_t = 10000)]
-    port: u16,
-}
+use p09_job_centre::cli;
 
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
-    tracing_subscriber::fmt::init();
-
-    let args = Args::parse();
-
-    info!("start");
-
-    let socket = TcpListener::bind(&format!("{}:{}", args.address, args.port)).await?;
-
-    Ok(run(socket).await?)
+    cli::run(protohackers_config::parse(clap::command!(), "p09")).await
 }
@@ -2,7 +2,7 @@ use std::time::Duration;
 
 use futures::{SinkExt, StreamExt};
 
-use tokio::io::{BufReader, BufWriter};
+use tokio::io::{AsyncWriteExt, BufReader, BufWriter};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::time::timeout;
 
@@ -111,6 +111,38 @@ async fn test_session() {
     };
 }
 
+#[tokio::test]
+async fn test_invalid_request() {
+    let (address, port) = spawn_app().await;
+
+    let mut stream = TcpStream::connect(format!("{address}:{port}"))
+        .await
+        .unwrap();
+    let (read, write) = stream.split();
+    let mut read = FramedRead::new(BufReader::new(read), protocol::ResponseCodec::new());
+    let mut write = FramedWrite::new(BufWriter::new(write), protocol::RequestCodec::new());
+
+    write.get_mut().write_all(b"not json\n").await.unwrap();
+    write
+        .send(protocol::Request::Put {
+            queue: "queue1".to_string(),
+            job: serde_json::json!({}),
+            priority: 1,
+        })
+        .await
+        .unwrap();
+    write.flush().await.unwrap();
+
+    let Ok(protocol::Response::Error(_)) = timeout(TIMEOUT, read.next()).await.unwrap().unwrap()
+    else {
+        panic!("invalid response")
+    };
+    let Ok(protocol::Response::Id(_)) = timeout(TIMEOUT, read.next()).await.unwrap().unwrap()
+    else {
+        panic!("invalid response")
+    };
+}
+
 async fn spawn_app() -> (String, u16) {
     static TRACING_SUBSCRIBER_INIT: parking_lot::Once = parking_lot::Once::new();
     TRACING_SUBSCRIBER_INIT.call_once(tracing_subscriber::fmt::init);
@@ -0,0 +1,47 @@
+//! The file server flags: the directory keeping the revisions across
+//! the restarts, in memory without it.
+use std::path::PathBuf;
+
+use tracing::info;
+
+use protohackers_server::ServerArgs;
+
+use crate::storage::DiskStorage;
+use crate::vcs::Vcs;
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[command(flatten)]
+    pub server: ServerArgs,
+
+    /// Keep the files in this directory, across the restarts, instead
+    /// of in memory
+    #[arg(long)]
+    pub storage_directory: Option<PathBuf>,
+}
+
+/// Run the server configured by `args`.
+///
+/// # Errors
+/// * Error when the server can not be started or fails.
+pub async fn run(args: Args) -> Result<(), anyhow::Error> {
+    args.server.init_tracing();
+
+    info!("start");
+
+    let vcs = if let Some(directory) = args.storage_directory {
+        info!("storage directory: {}", directory.display());
+        Vcs::with_storage(DiskStorage::open(directory)?)
+    } else {
+        Vcs::new()
+    };
+
+    Ok(crate::serve(
+        args.server
+            .server()
+            .await?
+            .with_problem(env!("CARGO_PKG_NAME")),
+        vcs,
+    )
+    .await?)
+}
@@ -3,18 +3,27 @@
 use std::future::Future;
 use std::io;
 use std::sync::Arc;
+use std::time::Duration;
 
 use tokio::io::{
     AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, BufWriter,
 };
-use tokio::net::{TcpListener, TcpStream};
+use tokio::net::TcpStream;
 
 use tracing::{debug, info, instrument};
 
+use protohackers_server::{IdleTimeout, Listeners, Server};
+
+#[cfg(feature = "bin")]
+pub mod cli;
+pub mod storage;
 pub mod vcs;
 
 use vcs::{ListEntry, Path, Vcs};
 
+/// The default idle timeout of the connections.
+pub const IDLE_TIMEOUT: Duration = Duration::from_mins(5);
+
 pub trait WriteLine: AsyncWriteExt + Unpin {
     fn write_line(&mut self, line: &[u8]) -> impl Future<Output = Result<(), io::Error>> {
         async move {
@@ -27,17 +36,39 @@ pub trait WriteLine: AsyncWriteExt + Unpin {
 
 impl<T: AsyncWriteExt + Unpin> WriteLine for T {}
 
-#[instrument(skip(listener))]
-pub async fn run(listener: TcpListener) -> Result<(), io::Error> {
-    let vcs = Arc::new(Vcs::new());
-
-    loop {
-        let (stream, remote_addr) = listener.accept().await?;
+/// Run the server, keeping the files in memory.
+///
+/// # Errors
+/// * Error when the listener fails.
+pub async fn run(listeners: impl Into<Listeners>) -> Result<(), io::Error> {
+    run_with_vcs(listeners, Vcs::new()).await
+}
 
-        info!("remote: {remote_addr:?}");
+/// Run the server, keeping the files in `vcs`.
+///
+/// # Errors
+/// * Error when the listener fails.
+#[instrument(skip(listeners, vcs))]
+pub async fn run_with_vcs(listeners: impl Into<Listeners>, vcs: Vcs) -> Result<(), io::Error> {
+    serve(Server::new(listeners), vcs).await
+}
 
-        tokio::spawn(handle_client(vcs.clone(), stream));
-    }
+/// Run the server on a [`Server`], keeping the files in `vcs`.
+///
+/// # Errors
+/// * Error when the listener fails.
+#[instrument(skip(server, vcs))]
+pub async fn serve(server: Server, vcs: Vcs) -> Result<(), io::Error> {
+    let vcs = Arc::new(vcs);
+
+    server
+        .with_default_idle_timeout(IDLE_TIMEOUT)
+        .serve(move |stream, remote_addr| {
+            info!("remote: {remote_addr:?}");
+
+            handle_client(vcs.clone(), stream)
+        })
+        .await
 }
 
 async fn copy_n<'a, R, W>(
@@ -68,7 +99,8 @@ where
 }
 
 #[instrument(skip(stream, vcs))]
-async fn handle_client(vcs: Arc<Vcs>, mut stream: TcpStream) -> Result<(), io::Error> {
+#[allow(clippy::too_many_lines)]
+async fn handle_client(vcs: Arc<Vcs>, mut stream: IdleTimeout<TcpStream>) -> Result<(), io::Error> {
     debug!("start");
 
     let (read, write) = stream.split();
@@ -125,7 +157,7 @@ async fn handle_client(vcs: Arc<Vcs>, mut stream: TcpStream) -> Result<(), io::E
                         if let Ok(size) = size.parse::<usize>() {
                             if let Ok(mut write) = vcs.put(path) {
                                 copy_n(&mut reader, &mut write, size).await?;
-                                if let Ok(revision) = write.commit() {
+                                if let Ok(revision) = write.commit().await {
                                     debug!("PUT {path} {revision} -> {size}");
                                     writer
                                         .write_line(format!("OK r{}", revision + 1).as_bytes())
@@ -167,7 +199,7 @@ async fn handle_client(vcs: Arc<Vcs>, mut stream: TcpStream) -> Result<(), io::E
                             if let Ok(revision) = revision.parse::<usize>() {
                                 if revision > 0 {
                                     let revision = revision - 1;
-                                    if let Ok(mut read) = vcs.get_revision(path, revision) {
+                                    if let Ok(mut read) = vcs.get_revision(path, revision).await {
                                         let len = read.len();
                                         debug!("GET {path} {revision} -> {len}");
                                         writer.write_line(format!("OK {len}").as_bytes()).await?;
@@ -191,7 +223,7 @@ async fn handle_client(vcs: Arc<Vcs>, mut stream: TcpStream) -> Result<(), io::E
                     }
 
                     (Some(path), None, None) => {
-                        if let Ok(mut read) = vcs.get_current_revision(path) {
+                        if let Ok(mut read) = vcs.get_current_revision(path).await {
                             let len = read.len();
                             writer.write_line(format!("OK {len}").as_bytes()).await?;
                             copy_n(&mut read, &mut writer, len).await?;
@@ -0,0 +1,39 @@
+//! Where the file revisions are kept.
+use std::sync::Arc;
+
+use crate::vcs::{ListEntry, Path, Result};
+
+mod disk;
+mod memory;
+
+pub use disk::DiskStorage;
+pub use memory::MemoryStorage;
+
+/// The backend of the [`Vcs`](crate::vcs::Vcs).
+///
+/// The paths are already validated, the revisions start from zero.
+/// The [`get`](Storage::get) and [`put`](Storage::put) are called on
+/// the blocking threads, the [`list`](Storage::list) on the runtime.
+pub trait Storage: Send + Sync {
+    /// The entries of the directory `dir`, in any order; a name both
+    /// of a file and of a directory is listed as a file.
+    ///
+    /// # Errors
+    /// * [`VcsError::DirNotFound`](crate::vcs::VcsError::DirNotFound)
+    ///   when there is no such directory.
+    fn list(&self, dir: &Path) -> Result<Vec<ListEntry>>;
+
+    /// The data of `revision` of `file`, the last one when `None`.
+    ///
+    /// # Errors
+    /// * Error when there is no such file or revision, or the storage
+    ///   fails.
+    fn get(&self, file: &Path, revision: Option<usize>) -> Result<Arc<Vec<u8>>>;
+
+    /// Store `data` as a new revision of `file` and return it, or the
+    /// last revision when the data is the same.
+    ///
+    /// # Errors
+    /// * Error when the storage fails.
+    fn put(&self, file: &Path, data: Vec<u8>) -> Result<usize>;
+}
@@ -0,0 +1,286 @@
+//! The files on disk: the data in content addressed files, named by
+//! their SHA-256, and the revisions in an append only index replayed
+//! when the storage is opened, so only the hashes stay in memory.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use sha2::{Digest, Sha256};
+
+use crate::storage::Storage;
+use crate::vcs::{ListEntry, Path, Result, VcsError};
+
+/// The index file, a `<path> <hash>` line for every revision.
+const INDEX: &str = "index";
+
+/// The directory of the data files.
+const OBJECTS: &str = "objects";
+
+type Hash = [u8; 32];
+
+#[derive(Debug)]
+struct Index {
+    file: File,
+
+    /// The revisions of every file, by path.
+    files: BTreeMap<String, Vec<Hash>>,
+}
+
+/// The files kept in a directory, they survive the restarts.
+#[derive(Debug)]
+pub struct DiskStorage {
+    directory: PathBuf,
+    index: RwLock<Index>,
+
+    /// The suffix of the next partial object, unique to its writer.
+    partials: AtomicU64,
+}
+
+impl DiskStorage {
+    /// Open the storage in `directory`, created when missing.
+    ///
+    /// An incomplete last line of the index, from an interrupted
+    /// write, is dropped.
+    ///
+    /// # Errors
+    /// * Error when the directory can not be created or the index is
+    ///   invalid.
+    pub fn open(directory: impl Into<PathBuf>) -> io::Result<Self> {
+        let directory = directory.into();
+        fs::create_dir_all(directory.join(OBJECTS))?;
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .append(true)
+            .open(directory.join(INDEX))?;
+
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+
+        let complete = content.rfind('\n').map_or(0, |end| end + 1);
+        if complete < content.len() {
+            file.set_len(complete as u64)?;
+        }
+
+        let mut files = BTreeMap::<_, Vec<_>>::new();
+        for line in content[..complete].lines() {
+            let (path, hash) = line
+                .split_once(' ')
+                .and_then(|(path, hash)| Some((path, parse_hash(hash)?)))
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid index"))?;
+            files.entry(path.to_string()).or_default().push(hash);
+        }
+
+        Ok(Self {
+            directory,
+            index: RwLock::new(Index { file, files }),
+            partials: AtomicU64::new(0),
+        })
+    }
+
+    fn object(&self, hash: &Hash) -> PathBuf {
+        self.directory.join(OBJECTS).join(format_hash(hash))
+    }
+}
+
+impl Storage for DiskStorage {
+    fn list(&self, dir: &Path) -> Result<Vec<ListEntry>> {
+        let dir = key(dir);
+        let prefix = if dir == "/" {
+            dir.clone()
+        } else {
+            format!("{dir}/")
+        };
+
+        let index = self.index.read();
+
+        // the names with their revisions and their children
+        let mut entries = BTreeMap::<_, (usize, BTreeSet<_>)>::new();
+        for (path, revisions) in index
+            .files
+            .range(prefix.clone()..)
+            .take_while(|(path, _)| path.starts_with(&prefix))
+        {
+            match path[prefix.len()..].split_once('/') {
+                None => entries.entry(&path[prefix.len()..]).or_default().0 = revisions.len(),
+                Some((name, rest)) => {
+                    let child = rest.split('/').next().unwrap_or(rest);
+                    entries.entry(name).or_default().1.insert(child);
+                }
+            }
+        }
+
+        if entries.is_empty() && dir != "/" && !index.files.contains_key(&dir) {
+            return Err(VcsError::DirNotFound);
+        }
+
+        Ok(entries
+            .into_iter()
+            .map(|(name, (revisions, children))| {
+                if revisions == 0 {
+                    ListEntry::Dir(name.to_string(), children.len())
+                } else {
+                    ListEntry::File(name.to_string(), revisions)
+                }
+            })
+            .collect())
+    }
+
+    fn get(&self, file: &Path, revision: Option<usize>) -> Result<Arc<Vec<u8>>> {
+        let hash = {
+            let index = self.index.read();
+            let revisions = index.files.get(&key(file)).ok_or(VcsError::FileNotFound)?;
+            let hash = match revision {
+                Some(revision) => revisions.get(revision),
+                None => revisions.last(),
+            };
+            *hash.ok_or(VcsError::ReleaseNotFound)?
+        };
+
+        Ok(Arc::new(fs::read(self.object(&hash))?))
+    }
+
+    fn put(&self, file: &Path, data: Vec<u8>) -> Result<usize> {
+        let key = key(file);
+        let hash = Sha256::digest(&data).into();
+
+        // content addressed, written before locking the index: the
+        // writers of the same data rename the same content
+        let object = self.object(&hash);
+        if !object.exists() {
+            // a complete file or none after a crash
+            let partial = object.with_extension(format!(
+                "partial.{}",
+                self.partials.fetch_add(1, Ordering::Relaxed)
+            ));
+            fs::write(&partial, &data)?;
+            fs::rename(partial, object)?;
+        }
+
+        let mut index = self.index.write();
+        if let Some(revisions) = index.files.get(&key) {
+            if revisions.last() == Some(&hash) {
+                return Ok(revisions.len() - 1);
+            }
+        }
+
+        index
+            .file
+            .write_all(format!("{key} {}\n", format_hash(&hash)).as_bytes())?;
+
+        let revisions = index.files.entry(key).or_default();
+        revisions.push(hash);
+
+        Ok(revisions.len() - 1)
+    }
+}
+
+/// The normalized path, without the empty components.
+fn key(path: &Path) -> String {
+    let mut key = String::new();
+    for component in path.components() {
+        key.push('/');
+        key.push_str(&String::from_utf8_lossy(component));
+    }
+    if key.is_empty() {
+        key.push('/');
+    }
+    key
+}
+
+fn format_hash(hash: &Hash) -> String {
+    hash.iter().fold(String::with_capacity(64), |mut s, b| {
+        write!(s, "{b:02x}").unwrap();
+        s
+    })
+}
+
+fn parse_hash(s: &str) -> Option<Hash> {
+    if s.len() != 64 || !s.is_ascii() {
+        return None;
+    }
+
+    let mut hash = [0; 32];
+    for (byte, digits) in hash.iter_mut().zip(s.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()?;
+    }
+    Some(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn directory(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("p10-disk-{name}-{}", std::process::id()));
+        fs::remove_dir_all(&directory).ok();
+        directory
+    }
+
+    fn path(path: &str) -> &Path {
+        Path::new(path.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_hash() {
+        let hash = Sha256::digest(b"hello").into();
+        assert_eq!(Some(hash), parse_hash(&format_hash(&hash)));
+        assert_eq!(None, parse_hash("00"));
+    }
+
+    #[test]
+    fn test_reopen() {
+        let directory = directory("reopen");
+
+        {
+            let storage = DiskStorage::open(&directory).unwrap();
+            assert_eq!(0, storage.put(path("/dir/test"), b"one".to_vec()).unwrap());
+            assert_eq!(1, storage.put(path("/dir/test"), b"two".to_vec()).unwrap());
+            assert_eq!(1, storage.put(path("/dir/test"), b"two".to_vec()).unwrap());
+            assert_eq!(0, storage.put(path("/other"), b"one".to_vec()).unwrap());
+        }
+
+        // an interrupted write of the index
+        OpenOptions::new()
+            .append(true)
+            .open(directory.join(INDEX))
+            .unwrap()
+            .write_all(b"/dir/test 00")
+            .unwrap();
+
+        let storage = DiskStorage::open(&directory).unwrap();
+        assert_eq!(
+            b"one".as_slice(),
+            storage.get(path("/dir/test"), Some(0)).unwrap().as_slice()
+        );
+        assert_eq!(
+            b"two".as_slice(),
+            storage.get(path("/dir/test"), None).unwrap().as_slice()
+        );
+        assert_eq!(
+            vec![
+                ListEntry::Dir("dir".to_string(), 1),
+                ListEntry::File("other".to_string(), 1)
+            ],
+            storage.list(path("/")).unwrap()
+        );
+        assert_eq!(
+            2,
+            storage.put(path("/dir/test"), b"three".to_vec()).unwrap()
+        );
+
+        // the same data is stored once
+        assert_eq!(3, fs::read_dir(directory.join(OBJECTS)).unwrap().count());
+
+        fs::remove_dir_all(&directory).ok();
+    }
+}
@@ -0,0 +1,121 @@
+//! The in memory tree of the files.
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::storage::Storage;
+use crate::vcs::{ListEntry, Path, Result, VcsError};
+
+#[derive(Debug, Default)]
+struct Entry {
+    name: String,
+
+    /// The entries of the directory of the same name, if any.
+    subdirs: Vec<Entry>,
+
+    /// The revisions of the file of the same name, if any.
+    releases: Vec<Arc<Vec<u8>>>,
+}
+
+impl Entry {
+    fn find<'a>(entries: &'a [Entry], name: &[u8]) -> Option<&'a Entry> {
+        entries.iter().find(|entry| entry.name.as_bytes() == name)
+    }
+
+    fn find_or_insert<'a>(entries: &'a mut Vec<Entry>, name: &[u8]) -> &'a mut Entry {
+        let index = if let Some(index) = entries
+            .iter()
+            .position(|entry| entry.name.as_bytes() == name)
+        {
+            index
+        } else {
+            entries.push(Entry {
+                name: String::from_utf8_lossy(name).into_owned(),
+                ..Entry::default()
+            });
+            entries.len() - 1
+        };
+        &mut entries[index]
+    }
+}
+
+/// The files kept in memory, lost on exit.
+#[derive(Debug, Default)]
+pub struct MemoryStorage(RwLock<Vec<Entry>>);
+
+impl MemoryStorage {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn list(&self, dir: &Path) -> Result<Vec<ListEntry>> {
+        let root = self.0.read();
+
+        let mut entries = &*root;
+        for component in dir.components() {
+            entries = &Entry::find(entries, component)
+                .ok_or(VcsError::DirNotFound)?
+                .subdirs;
+        }
+
+        Ok(entries
+            .iter()
+            .map(|entry| {
+                if entry.releases.is_empty() {
+                    ListEntry::Dir(entry.name.clone(), entry.subdirs.len())
+                } else {
+                    ListEntry::File(entry.name.clone(), entry.releases.len())
+                }
+            })
+            .collect())
+    }
+
+    fn get(&self, file: &Path, revision: Option<usize>) -> Result<Arc<Vec<u8>>> {
+        let root = self.0.read();
+
+        let mut components = file.components().peekable();
+        let mut entries = &*root;
+        while let Some(component) = components.next() {
+            let entry = Entry::find(entries, component);
+            if components.peek().is_none() {
+                let entry = entry.ok_or(VcsError::FileNotFound)?;
+                let release = match revision {
+                    Some(revision) => entry.releases.get(revision),
+                    None => entry.releases.last(),
+                };
+                return release.cloned().ok_or(VcsError::ReleaseNotFound);
+            }
+
+            entries = &entry.ok_or(VcsError::DirNotFound)?.subdirs;
+        }
+
+        Err(VcsError::FileNotFound)
+    }
+
+    fn put(&self, file: &Path, data: Vec<u8>) -> Result<usize> {
+        let mut root = self.0.write();
+
+        let mut components = file.components().peekable();
+        let mut entries = &mut *root;
+        while let Some(component) = components.next() {
+            let entry = Entry::find_or_insert(entries, component);
+            if components.peek().is_none() {
+                let releases = &mut entry.releases;
+                return Ok(match releases.last() {
+                    Some(last) if **last == data => releases.len() - 1,
+                    _ => {
+                        releases.push(data.into());
+                        releases.len() - 1
+                    }
+                });
+            }
+
+            entries = &mut entry.subdirs;
+        }
+
+        Err(VcsError::FileNotFound)
+    }
+}
@@ -7,13 +7,14 @@ use std::sync::Arc;
 use std::task::{Context, Poll};
 
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::task;
 
 use tracing::{debug, instrument};
 
-use parking_lot::RwLock;
-
 use thiserror::Error;
 
+use crate::storage::{MemoryStorage, Storage};
+
 #[derive(Error, Debug, PartialEq)]
 #[allow(clippy::module_name_repetitions)]
 pub enum VcsError {
@@ -31,6 +32,15 @@ pub enum VcsError {
 
     #[error("invalid data")]
     InvalidData,
+
+    #[error("storage error: {0}")]
+    Storage(String),
+}
+
+impl From<io::Error> for VcsError {
+    fn from(err: io::Error) -> Self {
+        Self::Storage(err.to_string())
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -60,45 +70,27 @@ impl Ord for ListEntry {
     }
 }
 
-#[derive(Debug)]
-struct Entry {
-    name: String,
-    subdirs: Arc<RwLock<Vec<Entry>>>,
-    releases: Arc<RwLock<Vec<Arc<Vec<u8>>>>>,
-}
-
-impl Entry {
-    fn file(name: &[u8], data: Vec<u8>) -> Self {
-        Self {
-            name: String::from_utf8_lossy(name).into_owned(),
-            releases: Arc::new(RwLock::new(vec![data.into()])),
-            subdirs: Arc::new(RwLock::new(vec![])),
-        }
-    }
-
-    fn name(&self) -> &String {
-        &self.name
-    }
-}
-
-type Result<T> = std::result::Result<T, VcsError>;
+pub(crate) type Result<T> = std::result::Result<T, VcsError>;
 
 #[repr(transparent)]
 pub struct Path([u8]);
 
 impl Path {
-    fn components(&self) -> impl Iterator<Item = &[u8]> {
-        self.0.split(|b| *b == b'/')
+    /// The non empty components.
+    pub(crate) fn components(&self) -> impl Iterator<Item = &[u8]> {
+        self.0
+            .split(|b| *b == b'/')
+            .filter(|component| !component.is_empty())
     }
 
-    fn is_dir(&self) -> bool {
+    pub(crate) fn is_dir(&self) -> bool {
         self.0.ends_with(b"/")
     }
 
     /// # Errors
     pub fn new(path: &[u8]) -> Result<&Path> {
         if Self::is_valid(path) {
-            Ok(unsafe { &*(path as *const [u8] as *const Path) })
+            Ok(unsafe { &*(std::ptr::from_ref::<[u8]>(path) as *const Path) })
         } else {
             Err(VcsError::InvalidPath)
         }
@@ -163,9 +155,8 @@ impl AsyncRead for ReadRevision {
     }
 }
 
-#[derive(Debug)]
 pub struct WriteRevision<P> {
-    root: Arc<RwLock<Vec<Entry>>>,
+    storage: Arc<dyn Storage>,
     path: P,
     data: Vec<u8>,
 }
@@ -176,7 +167,7 @@ where
 {
     /// # Errors
     #[instrument(skip(self))]
-    pub fn commit(mut self) -> Result<usize> {
+    pub async fn commit(mut self) -> Result<usize> {
         if !self
             .data
             .iter()
@@ -193,61 +184,14 @@ where
             return Err(VcsError::InvalidPath);
         }
 
-        let mut entry = self.root.write_arc();
-        let mut components = path
-            .components()
-            .filter(|component| !component.is_empty())
-            .peekable();
-        loop {
-            match (components.next(), components.peek()) {
-                (Some(dir), Some(_)) => {
-                    if let Some(Entry { subdirs, .. }) =
-                        entry.iter().find(|entry| entry.name().as_bytes() == dir)
-                    {
-                        entry = subdirs.write_arc();
-                    } else {
-                        let es = Arc::new(RwLock::new(vec![]));
-                        let dir = Entry {
-                            name: String::from_utf8_lossy(dir).into_owned(),
-                            subdirs: es.clone(),
-                            releases: Arc::new(RwLock::new(vec![])),
-                        };
-                        entry.push(dir);
-                        entry = es.write_arc();
-                    }
-                }
-
-                (Some(file), None) => {
-                    if let Some(Entry { releases, .. }) =
-                        entry.iter().find(|entry| entry.name().as_bytes() == file)
-                    {
-                        let mut releases = releases.write();
-                        let r = releases.len();
-                        match releases.last() {
-                            Some(last) if **last == self.data => {
-                                debug!("same data: r{}", r - 1);
-                                return Ok(r - 1);
-                            }
-
-                            _ => {
-                                debug!("new data: r{}", r);
-                                releases.push(mem::take(&mut self.data).into());
-                                return Ok(r);
-                            }
-                        }
-                    }
-
-                    debug!("new data: r0");
-                    entry.push(Entry::file(file, mem::take(&mut self.data)));
-                    return Ok(0);
-                }
-
-                (None, _) => {
-                    debug!("file not found <leaf>");
-                    return Err(VcsError::FileNotFound);
-                }
-            }
-        }
+        let data = mem::take(&mut self.data);
+        let revision = blocking(&self.storage, path, move |storage, path| {
+            storage.put(path, data)
+        })
+        .await?;
+        debug!("data: r{revision}");
+
+        Ok(revision)
     }
 }
 
@@ -297,12 +241,20 @@ impl<I: Iterator<Item = ListEntry>> Iterator for List<I> {
     }
 }
 
-pub struct Vcs(Arc<RwLock<Vec<Entry>>>);
+/// The file revisions, over a [`Storage`].
+#[derive(Clone)]
+pub struct Vcs(Arc<dyn Storage>);
 
 impl Vcs {
+    /// A new, in memory, file system.
     #[must_use]
     pub fn new() -> Self {
-        Self(Arc::new(RwLock::new(vec![])))
+        Self::with_storage(MemoryStorage::new())
+    }
+
+    #[must_use]
+    pub fn with_storage(storage: impl Storage + 'static) -> Self {
+        Self(Arc::new(storage))
     }
 
     /// # Errors
@@ -311,39 +263,7 @@ impl Vcs {
     where
         P: TryInto<&'a Path, Error = VcsError>,
     {
-        let mut entry = self.0.read_arc();
-        for component in path
-            .try_into()?
-            .components()
-            .filter(|component| !component.is_empty())
-        {
-            if let Some(Entry { subdirs, .. }) = entry
-                .iter()
-                .find(|entry| entry.name().as_bytes() == component)
-            {
-                entry = subdirs.read_arc();
-            } else {
-                return Err(VcsError::DirNotFound);
-            }
-        }
-
-        let mut v = entry
-            .iter()
-            .map(
-                |Entry {
-                     name,
-                     subdirs,
-                     releases,
-                 }| {
-                    let releases = releases.read();
-                    if releases.is_empty() {
-                        ListEntry::Dir(name.clone(), subdirs.read().len())
-                    } else {
-                        ListEntry::File(name.clone(), releases.len())
-                    }
-                },
-            )
-            .collect::<Vec<_>>();
+        let mut v = self.0.list(path.try_into()?)?;
         v.sort();
 
         Ok(List {
@@ -352,64 +272,33 @@ impl Vcs {
         })
     }
 
-    fn get<F: FnOnce(&[Arc<Vec<u8>>]) -> Option<&Arc<Vec<u8>>>>(
-        &self,
-        path: &Path,
-        f: F,
-    ) -> Result<ReadRevision> {
-        let mut entry = self.0.read_arc();
-        let mut components = path
-            .components()
-            .filter(|component| !component.is_empty())
-            .peekable();
-        loop {
-            match (components.next(), components.peek()) {
-                (Some(dir), Some(_)) => {
-                    if let Some(Entry { subdirs, .. }) =
-                        entry.iter().find(|entry| entry.name().as_bytes() == dir)
-                    {
-                        entry = subdirs.read_arc();
-                    } else {
-                        return Err(VcsError::DirNotFound);
-                    }
-                }
-
-                (Some(file), None) => {
-                    if let Some(Entry { releases, .. }) =
-                        entry.iter().find(|entry| entry.name().as_bytes() == file)
-                    {
-                        if let Some(release) = f(&releases.read_arc()) {
-                            return Ok(ReadRevision {
-                                data: release.clone(),
-                                index: 0,
-                            });
-                        }
-                        return Err(VcsError::ReleaseNotFound);
-                    }
-                }
-
-                (None, _) => return Err(VcsError::FileNotFound),
-            }
-        }
+    async fn get(&self, path: &Path, revision: Option<usize>) -> Result<ReadRevision> {
+        Ok(ReadRevision {
+            data: blocking(&self.0, path, move |storage, path| {
+                storage.get(path, revision)
+            })
+            .await?,
+            index: 0,
+        })
     }
 
     /// # Errors
     #[instrument(skip(self, path))]
-    pub fn get_current_revision<'a, P>(&'a self, path: P) -> Result<ReadRevision>
+    pub async fn get_current_revision<'a, P>(&'a self, path: P) -> Result<ReadRevision>
     where
         P: TryInto<&'a Path, Error = VcsError>,
     {
-        self.get(path.try_into()?, <[_]>::last)
+        self.get(path.try_into()?, None).await
     }
 
     /// # Errors
     #[instrument(skip(self, path, revision))]
-    pub fn get_revision<'a, P, R>(&'a self, path: P, revision: R) -> Result<ReadRevision>
+    pub async fn get_revision<'a, P, R>(&'a self, path: P, revision: R) -> Result<ReadRevision>
     where
         P: TryInto<&'a Path, Error = VcsError>,
         R: Into<Revision>,
     {
-        self.get(path.try_into()?, |vs| vs.get(revision.into().0))
+        self.get(path.try_into()?, Some(revision.into().0)).await
     }
 
     /// # Errors
@@ -418,7 +307,7 @@ impl Vcs {
         P: TryInto<&'a Path, Error = VcsError>,
     {
         Ok(WriteRevision {
-            root: self.0.clone(),
+            storage: self.0.clone(),
             path,
             data: vec![],
         })
@@ -431,6 +320,19 @@ impl Default for Vcs {
     }
 }
 
+/// Run `f` on the blocking threads, the storages may do disk I/O.
+async fn blocking<T, F>(storage: &Arc<dyn Storage>, path: &Path, f: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&dyn Storage, &Path) -> Result<T> + Send + 'static,
+{
+    let storage = storage.clone();
+    let path = path.0.to_vec();
+    task::spawn_blocking(move || f(storage.as_ref(), Path::new(&path)?))
+        .await
+        .map_err(|err| VcsError::Storage(err.to_string()))?
+}
+
 #[cfg(test)]
 mod tests {
     use parking_lot::Once;
@@ -467,7 +369,7 @@ mod tests {
 
         assert_eq!(vcs.list("/").unwrap().len(), 0);
 
-        write.commit().unwrap();
+        write.commit().await.unwrap();
 
         assert_eq!(vcs.list("/").unwrap().len(), 1);
         assert_eq!(
@@ -484,11 +386,11 @@ mod tests {
 
         let mut write = vcs.put("/test").unwrap();
         write.write_all(b"Hello World!").await.unwrap();
-        let r = write.commit().unwrap();
+        let r = write.commit().await.unwrap();
 
         let mut write = vcs.put("/test").unwrap();
         write.write_all(b"Hello World!").await.unwrap();
-        assert_eq!(write.commit().unwrap(), r);
+        assert_eq!(write.commit().await.unwrap(), r);
 
         assert_eq!(vcs.list("/").unwrap().len(), 1);
         assert_eq!(
@@ -506,9 +408,9 @@ mod tests {
         let mut write = vcs.put("/test").unwrap();
 
         write.write_all(b"Hello World!").await.unwrap();
-        write.commit().unwrap();
+        write.commit().await.unwrap();
 
-        let mut read = vcs.get_current_revision("/test").unwrap();
+        let mut read = vcs.get_current_revision("/test").await.unwrap();
 
         let mut buffer = vec![];
         read.read_to_end(&mut buffer).await.unwrap();
@@ -525,14 +427,14 @@ mod tests {
         let mut write = vcs.put("/test").unwrap();
 
         write.write_all(b"Hello World! 1").await.unwrap();
-        write.commit().unwrap();
+        write.commit().await.unwrap();
 
         let mut write = vcs.put("/test").unwrap();
 
         write.write_all(b"Hello World! 2").await.unwrap();
-        write.commit().unwrap();
+        write.commit().await.unwrap();
 
-        let mut read = vcs.get_current_revision("/test").unwrap();
+        let mut read = vcs.get_current_revision("/test").await.unwrap();
 
         let mut buffer = vec![];
         read.read_to_end(&mut buffer).await.unwrap();
@@ -549,14 +451,14 @@ mod tests {
         let mut write = vcs.put("/test").unwrap();
 
         write.write_all(b"Hello World! 1").await.unwrap();
-        write.commit().unwrap();
+        write.commit().await.unwrap();
 
         let mut write = vcs.put("/test").unwrap();
 
         write.write_all(b"Hello World! 2").await.unwrap();
-        write.commit().unwrap();
+        write.commit().await.unwrap();
 
-        let mut read = vcs.get_revision("/test", 0).unwrap();
+        let mut read = vcs.get_revision("/test", 0).await.unwrap();
 
         let mut buffer = vec![];
         read.read_to_end(&mut buffer).await.unwrap();
@@ -577,15 +479,15 @@ mod tests {
 
         let mut write = vcs.put("/test").unwrap();
         write.write_all(b"Hello World! 1").await.unwrap();
-        write.commit().unwrap();
+        write.commit().await.unwrap();
 
         let mut write = vcs.put("/test").unwrap();
         write.write_all(b"Hello World! 2").await.unwrap();
-        write.commit().unwrap();
+        write.commit().await.unwrap();
 
         assert_eq!(
             VcsError::ReleaseNotFound,
-            vcs.get_revision("/test", 3).unwrap_err()
+            vcs.get_revision("/test", 3).await.unwrap_err()
         );
     }
 
@@ -603,7 +505,7 @@ mod tests {
 
         assert_eq!(vcs.list("/").unwrap().len(), 0);
 
-        write.commit().unwrap();
+        write.commit().await.unwrap();
 
         assert_eq!(vcs.list("/").unwrap().len(), 1);
         assert_eq!(
@@ -626,11 +528,11 @@ mod tests {
 
         let mut write = vcs.put("/dir/test").unwrap();
         write.write_all(b"Hello World!").await.unwrap();
-        write.commit().unwrap();
+        write.commit().await.unwrap();
 
         let mut write = vcs.put("/dir").unwrap();
         write.write_all(b"Hello World!").await.unwrap();
-        assert_eq!(Ok(0), write.commit());
+        assert_eq!(Ok(0), write.commit().await);
     }
 
     #[test]
@@ -0,0 +1,211 @@
+//! Concurrent PUT and GET consistency, against every storage.
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use p10_voracious_code_storage::run_with_vcs;
+use p10_voracious_code_storage::storage::{DiskStorage, MemoryStorage, Storage};
+use p10_voracious_code_storage::vcs::{ListEntry, Path, Vcs};
+
+const WRITERS: usize = 8;
+const PUTS: usize = 50;
+
+fn directory(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("p10-{name}-{}", std::process::id()));
+    std::fs::remove_dir_all(&directory).ok();
+    directory
+}
+
+fn path(path: &str) -> &Path {
+    Path::new(path.as_bytes()).unwrap()
+}
+
+fn data(writer: usize, put: usize) -> Vec<u8> {
+    format!("writer {writer} put {put}\n").into_bytes()
+}
+
+/// Writers put to their own file and to a shared one while readers
+/// get the shared one: every revision returned must read back the
+/// data put, the shared file must have all the revisions.
+fn check_concurrent(storage: &Arc<dyn Storage>) {
+    let readers = (0..2)
+        .map(|_| {
+            let storage = storage.clone();
+            thread::spawn(move || {
+                for _ in 0..PUTS {
+                    if let Ok(data) = storage.get(path("/shared"), None) {
+                        assert!(data.starts_with(b"writer "));
+                    }
+                    storage.list(path("/")).unwrap();
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let writers = (0..WRITERS)
+        .map(|writer| {
+            let storage = storage.clone();
+            thread::spawn(move || {
+                let own = format!("/dir/writer{writer}");
+                let mut shared = vec![];
+                for put in 0..PUTS {
+                    let revision = storage.put(path(&own), data(writer, put)).unwrap();
+                    assert_eq!(put, revision);
+                    assert_eq!(data(writer, put), *storage.get(path(&own), None).unwrap());
+
+                    let revision = storage.put(path("/shared"), data(writer, put)).unwrap();
+                    shared.push((revision, data(writer, put)));
+                }
+                shared
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let shared = writers
+        .into_iter()
+        .flat_map(|writer| writer.join().unwrap())
+        .collect::<Vec<_>>();
+    for reader in readers {
+        reader.join().unwrap();
+    }
+
+    let revisions = shared
+        .iter()
+        .map(|(revision, _)| *revision)
+        .collect::<HashSet<_>>();
+    assert_eq!(WRITERS * PUTS, revisions.len());
+    for (revision, data) in shared {
+        assert_eq!(data, *storage.get(path("/shared"), Some(revision)).unwrap());
+    }
+
+    let mut list = storage.list(path("/")).unwrap();
+    list.sort();
+    assert_eq!(
+        vec![
+            ListEntry::Dir("dir".to_string(), WRITERS),
+            ListEntry::File("shared".to_string(), WRITERS * PUTS),
+        ],
+        list
+    );
+    for writer in 0..WRITERS {
+        assert!(storage
+            .list(path("/dir"))
+            .unwrap()
+            .contains(&ListEntry::File(format!("writer{writer}"), PUTS)));
+    }
+}
+
+#[test]
+fn test_memory_concurrent() {
+    let storage: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+    check_concurrent(&storage);
+}
+
+#[test]
+fn test_disk_concurrent() {
+    let directory = directory("disk-concurrent");
+
+    let storage: Arc<dyn Storage> = Arc::new(DiskStorage::open(&directory).unwrap());
+    check_concurrent(&storage);
+
+    // all the revisions survive a restart
+    drop(storage);
+    let storage = DiskStorage::open(&directory).unwrap();
+    assert_eq!(
+        data(WRITERS - 1, PUTS - 1),
+        *storage
+            .get(path(&format!("/dir/writer{}", WRITERS - 1)), None)
+            .unwrap()
+    );
+    assert_eq!(
+        vec![ListEntry::File("shared".to_string(), WRITERS * PUTS)],
+        storage
+            .list(path("/"))
+            .unwrap()
+            .into_iter()
+            .filter(|entry| entry.name() == "shared")
+            .collect::<Vec<_>>()
+    );
+
+    std::fs::remove_dir_all(&directory).ok();
+}
+
+async fn spawn_app(vcs: Vcs) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+
+    tokio::spawn(async move {
+        run_with_vcs(listener, vcs).await.expect("run failed");
+    });
+
+    address
+}
+
+/// A client putting and getting back its own file.
+async fn client(address: String, client: usize) {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    let (read, mut write) = stream.split();
+    let mut read = BufReader::new(read);
+
+    let mut line = String::new();
+    for put in 0..PUTS / 5 {
+        let data = data(client, put);
+        write
+            .write_all(format!("PUT /client{client} {}\n", data.len()).as_bytes())
+            .await
+            .unwrap();
+        write.write_all(&data).await.unwrap();
+        write
+            .write_all(format!("GET /client{client} r{}\n", put + 1).as_bytes())
+            .await
+            .unwrap();
+
+        for expected in ["READY\n".to_string(), format!("OK r{}\n", put + 1)] {
+            line.clear();
+            read.read_line(&mut line).await.unwrap();
+            assert_eq!(expected, line);
+        }
+
+        line.clear();
+        read.read_line(&mut line).await.unwrap();
+        assert_eq!("READY\n", line);
+        line.clear();
+        read.read_line(&mut line).await.unwrap();
+        assert_eq!(format!("OK {}\n", data.len()), line);
+
+        let mut received = vec![0; data.len()];
+        read.read_exact(&mut received).await.unwrap();
+        assert_eq!(data, received);
+    }
+}
+
+async fn check_clients(vcs: Vcs) {
+    let address = spawn_app(vcs.clone()).await;
+
+    let clients = (0..WRITERS)
+        .map(|id| tokio::spawn(client(address.clone(), id)))
+        .collect::<Vec<_>>();
+    for client in clients {
+        client.await.unwrap();
+    }
+
+    assert_eq!(WRITERS, vcs.list("/").unwrap().len());
+}
+
+#[tokio::test]
+async fn test_memory_clients() {
+    check_clients(Vcs::new()).await;
+}
+
+#[tokio::test]
+async fn test_disk_clients() {
+    let directory = directory("disk-clients");
+
+    check_clients(Vcs::with_storage(DiskStorage::open(&directory).unwrap())).await;
+
+    std::fs::remove_dir_all(&directory).ok();
+}
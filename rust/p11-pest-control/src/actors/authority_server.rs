@@ -29,6 +29,7 @@ pub enum Error {
     CannotConnect,
 }
 
+#[allow(clippy::struct_field_names)]
 pub struct AuthorityServer<P> {
     site: u32,
     authority_server_provider: P,
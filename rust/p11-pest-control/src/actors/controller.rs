@@ -53,7 +53,10 @@ impl<P: Provider + Clone + Send + 'static> Controller<P> {
 
                     let authority_server =
                         AuthorityServer::new(*site, authority_server_provider, authority_server_rx);
-                    tokio::spawn(async move { authority_server.run().await });
+                    protohackers_server::task::spawn(
+                        &format!("authority server {site}"),
+                        authority_server.run(),
+                    );
 
                     authority_server_tx
                 });
@@ -0,0 +1,44 @@
+//! The pest control flags: the authority server and how to dial it.
+use tracing::info;
+
+use protohackers_server::{DialArgs, ServerArgs};
+
+use crate::DefaultProvider;
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[command(flatten)]
+    pub server: ServerArgs,
+
+    #[arg(long, default_value = "pestcontrol.protohackers.com")]
+    pub authority_server_address: String,
+
+    #[arg(long, default_value_t = 20547)]
+    pub authority_server_port: u16,
+
+    #[command(flatten)]
+    pub dial: DialArgs,
+}
+
+/// Run the server configured by `args`.
+///
+/// # Errors
+/// * Error when the server can not be started or fails.
+pub async fn run(args: Args) -> Result<(), anyhow::Error> {
+    args.server.init_tracing();
+
+    info!("start");
+
+    let authority_server_provider =
+        DefaultProvider::new(args.authority_server_address, args.authority_server_port)
+            .with_dialer(args.dial.dialer());
+
+    Ok(crate::serve(
+        args.server
+            .server()
+            .await?
+            .with_problem(env!("CARGO_PKG_NAME")),
+        authority_server_provider,
+    )
+    .await?)
+}
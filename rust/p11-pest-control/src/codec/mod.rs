@@ -137,8 +137,9 @@ impl<'a> Validator<'a> {
         }
 
         if self.data.len() > self.cursor + len {
-            let r = std::str::from_utf8(&self.data[self.cursor..self.cursor + len])
-                .expect("invalid utf8");
+            let Ok(r) = std::str::from_utf8(&self.data[self.cursor..self.cursor + len]) else {
+                return ControlFlow::Break(Err(Error::InvalidPacket));
+            };
             self.cursor += len;
             ControlFlow::Continue(r)
         } else {
@@ -19,7 +19,7 @@ impl From<PolicyAction> for u8 {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Packet {
     pub species: String,
     pub action: PolicyAction,
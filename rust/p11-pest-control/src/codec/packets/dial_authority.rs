@@ -4,7 +4,7 @@ use bytes::BytesMut;
 
 use crate::codec::{packets, Error, Parser, RawPacketDecoder, Validator, Writer};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Packet {
     pub site: u32,
 }
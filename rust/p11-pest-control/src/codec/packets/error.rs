@@ -4,7 +4,7 @@ use bytes::BytesMut;
 
 use crate::codec::{packets, Error, Parser, RawPacketDecoder, Validator, Writer};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Packet {
     pub message: String,
 }
@@ -95,6 +95,22 @@ mod tests {
         assert_eq!(Packet::new("bad"), raw_packet);
     }
 
+    #[tokio::test]
+    async fn test_read_not_utf8() {
+        init_tracing_subscriber();
+
+        let data = [
+            0x51, 0x00, 0x00, 0x00, 0x0b, 0x00, 0x00, 0x00, 0x01, 0xff, 0xa4,
+        ]
+        .as_slice();
+        let mut reader = FramedRead::new(data, PacketCodec::new());
+
+        assert!(matches!(
+            reader.try_next().await,
+            Err(crate::codec::Error::InvalidPacket)
+        ));
+    }
+
     #[tokio::test]
     async fn test_write() {
         init_tracing_subscriber();
@@ -7,7 +7,7 @@ use crate::codec::{packets, Error, Parser, RawPacketDecoder, Validator, Writer};
 pub const PESTCONTROL_PROTOCOL: &str = "pestcontrol";
 pub const PESTCONTROL_VERSION: u32 = 1;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Packet {
     pub protocol: String,
     pub version: u32,
@@ -16,7 +16,7 @@ pub mod policy_result;
 pub mod site_visit;
 pub mod target_populations;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Packet {
     Hello(hello::Packet),
     Error(error::Packet),
@@ -4,7 +4,7 @@ use bytes::BytesMut;
 
 use crate::codec::{packets, Error, Parser, RawPacketDecoder, Validator, Writer};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Packet {
     pub site: u32,
     pub populations: Vec<Population>,
@@ -31,7 +31,7 @@ impl Packet {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Population {
     pub species: String,
     pub count: u32,
@@ -1,19 +1,22 @@
 #![doc = include_str!("../README.md")]
 
+use std::convert::Infallible;
 use std::io;
+use std::time::Duration;
 
 use tokio::io::{BufReader, BufWriter};
-use tokio::net::{
-    tcp::{OwnedReadHalf, OwnedWriteHalf},
-    TcpListener, TcpStream,
-};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
 use tokio::sync::mpsc;
 
 use tokio_util::codec::{FramedRead, FramedWrite};
 
 use tracing::{info, instrument};
 
+use protohackers_server::{Dialer, Listeners, Server};
+
 pub mod actors;
+#[cfg(feature = "bin")]
+pub mod cli;
 pub mod codec;
 
 use actors::controller::Controller;
@@ -21,16 +24,30 @@ use actors::site_visitor::SiteVisitor;
 use actors::Provider;
 use codec::packets::PacketCodec;
 
+/// The default idle timeout of the site visitors.
+pub const IDLE_TIMEOUT: Duration = Duration::from_mins(1);
+
 #[derive(Clone, Debug)]
 pub struct DefaultProvider {
     address: String,
     port: u16,
+    dialer: Dialer,
 }
 
 impl DefaultProvider {
     #[must_use]
     pub fn new(address: String, port: u16) -> Self {
-        Self { address, port }
+        Self {
+            address,
+            port,
+            dialer: Dialer::direct(),
+        }
+    }
+
+    /// Connect to the authority server with `dialer`.
+    #[must_use]
+    pub fn with_dialer(self, dialer: Dialer) -> Self {
+        Self { dialer, ..self }
     }
 }
 
@@ -42,7 +59,7 @@ impl Provider for DefaultProvider {
 
     #[instrument]
     async fn connect(&mut self, _: u32) -> Result<(Self::Sink, Self::Stream), Self::Error> {
-        let socket = TcpStream::connect(&format!("{}:{}", self.address, self.port)).await?;
+        let socket = self.dialer.connect(&self.address, self.port).await?;
 
         info!("new connection to authority server");
 
@@ -54,28 +71,50 @@ impl Provider for DefaultProvider {
     }
 }
 
+/// Serve the site visits, dialing the authority servers with
+/// `authority_server_provider`.
+///
+/// # Errors
+/// * Error when the listener fails.
 #[instrument(skip_all)]
 pub async fn run<P: Provider + Clone + Send + 'static>(
-    listener: TcpListener,
+    listeners: impl Into<Listeners>,
+    authority_server_provider: P,
+) -> Result<(), io::Error> {
+    serve(Server::new(listeners), authority_server_provider).await
+}
+
+/// Serve the site visits of a [`Server`], dialing the authority
+/// servers with `authority_server_provider`.
+///
+/// # Errors
+/// * Error when the listener fails.
+#[instrument(skip_all)]
+pub async fn serve<P: Provider + Clone + Send + 'static>(
+    server: Server,
     authority_server_provider: P,
 ) -> Result<(), io::Error> {
     let (site_visits, site_visits_rx) = mpsc::channel(1000);
 
     let controller = Controller::new(authority_server_provider, site_visits_rx);
-    tokio::spawn(controller.run());
-
-    loop {
-        let (socket, remote_addr) = listener.accept().await?;
-
-        info!("remote: {remote_addr:?}");
-
-        let (read, write) = socket.into_split();
-        let reader = FramedRead::new(BufReader::new(read), PacketCodec::new());
-        let writer = FramedWrite::new(BufWriter::new(write), PacketCodec::new());
-
-        let site_visitor = SiteVisitor::new(reader, writer, site_visits.clone());
-        tokio::spawn(site_visitor.run());
-    }
+    protohackers_server::task::spawn("controller", controller.run());
+
+    server
+        .with_default_idle_timeout(IDLE_TIMEOUT)
+        .serve(move |socket, remote_addr| {
+            info!("remote: {remote_addr:?}");
+
+            let (read, write) = socket.into_split();
+            let reader = FramedRead::new(BufReader::new(read), PacketCodec::new());
+            let writer = FramedWrite::new(BufWriter::new(write), PacketCodec::new());
+
+            let site_visitor = SiteVisitor::new(reader, writer, site_visits.clone());
+            async move {
+                site_visitor.run().await;
+                Ok::<_, Infallible>(())
+            }
+        })
+        .await
 }
 
 #[cfg(test)]
@@ -0,0 +1,115 @@
+//! Wire format properties: the encoded packets decode to the same
+//! packets whatever the chunks they arrive in, and decoding any bytes
+//! never panics.
+use std::fmt;
+
+use bytes::BytesMut;
+
+use proptest::prelude::*;
+
+use tokio_util::codec::{Decoder, Encoder};
+
+use p11_pest_control::codec::packets::{
+    create_policy, delete_policy, dial_authority, error, hello, ok, policy_result, site_visit,
+    target_populations, Packet, PacketCodec,
+};
+
+use protohackers_proptest::{bytes, decode_chunks, mutated, split, string};
+
+const MAX_STR_LEN: usize = 32;
+
+fn populations<T: fmt::Debug>(
+    population: impl Strategy<Value = T>,
+) -> impl Strategy<Value = Vec<T>> {
+    prop::collection::vec(population, 0..8)
+}
+
+fn packet() -> impl Strategy<Value = Packet> {
+    prop_oneof![
+        (string(MAX_STR_LEN), any::<u32>()).prop_map(|(protocol, version)| hello::Packet {
+            protocol,
+            version
+        }
+        .into()),
+        string(MAX_STR_LEN).prop_map(|message| error::Packet::new(message).into()),
+        Just(ok::Packet.into()),
+        any::<u32>().prop_map(|site| dial_authority::Packet::new(site).into()),
+        (
+            any::<u32>(),
+            populations((string(MAX_STR_LEN), any::<u32>(), any::<u32>()).prop_map(
+                |(species, min, max)| target_populations::Population::new(species, min, max)
+            )),
+        )
+            .prop_map(|(site, populations)| {
+                target_populations::Packet::new(site, populations).into()
+            }),
+        (
+            string(MAX_STR_LEN),
+            prop_oneof![
+                Just(create_policy::PolicyAction::Conserve),
+                Just(create_policy::PolicyAction::Cull),
+            ],
+        )
+            .prop_map(|(species, action)| create_policy::Packet::new(species, action).into()),
+        any::<u32>().prop_map(|policy| delete_policy::Packet::new(policy).into()),
+        any::<u32>().prop_map(|policy| policy_result::Packet::new(policy).into()),
+        (
+            any::<u32>(),
+            populations(
+                (string(MAX_STR_LEN), any::<u32>())
+                    .prop_map(|(species, count)| site_visit::Population::new(species, count))
+            ),
+        )
+            .prop_map(|(site, populations)| site_visit::Packet::new(site, populations).into()),
+    ]
+}
+
+fn encode(packets: Vec<Packet>) -> Vec<u8> {
+    let mut codec = PacketCodec::new();
+    let mut dst = BytesMut::new();
+    for packet in packets {
+        codec.encode(packet, &mut dst).unwrap();
+    }
+    dst.to_vec()
+}
+
+/// Decode `data` until it ends or a packet is invalid, checking that
+/// the packets are within it.
+fn decode_all(data: &[u8]) -> Result<(), TestCaseError> {
+    let mut codec = PacketCodec::new();
+    let mut src = BytesMut::from(data);
+    loop {
+        let len = src.len();
+        match codec.decode(&mut src) {
+            Ok(Some(_)) => prop_assert!(src.len() < len),
+            Ok(None) | Err(_) => return Ok(()),
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn test_round_trip(
+        (packets, chunks) in prop::collection::vec(packet(), 0..8)
+            .prop_flat_map(|packets| {
+                let data = encode(packets.clone());
+                (Just(packets), split(data))
+            }),
+    ) {
+        let (decoded, left) = decode_chunks(&mut PacketCodec::new(), &chunks).unwrap();
+        prop_assert_eq!(packets, decoded);
+        prop_assert!(left.is_empty(), "{} bytes left", left.len());
+    }
+
+    #[test]
+    fn test_decode_never_panics(data in bytes(256)) {
+        decode_all(&data)?;
+    }
+
+    #[test]
+    fn test_decode_mutated_never_panics(
+        data in prop::collection::vec(packet(), 1..4).prop_flat_map(|packets| mutated(encode(packets))),
+    ) {
+        decode_all(&data)?;
+    }
+}
@@ -0,0 +1,196 @@
+//! Wire format benchmarks: decode and encode of the messages, with
+//! the throughput in bytes where the size of the message matters.
+//!
+//! The p11 decode validates the checksum over the whole packet, so
+//! its throughput is mostly the checksum one.
+use std::hint::black_box;
+
+use bytes::BytesMut;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use futures::executor::block_on;
+
+use tokio_util::codec::{Decoder, Encoder};
+
+use p02_means_to_an_end_core::{Extensions, Message, MessageDecoder, MESSAGE_LEN};
+use p06_speed_daemon::wire::{self, ReadFrom, WriteTo};
+use p07_line_reversal_core::packets::{Numeric, Packet, Payload, SyncWrite};
+use p09_job_centre::protocol::{RequestCodec, Response, ResponseCodec};
+use p11_pest_control::codec::packets::{site_visit, Packet as PestPacket, PacketCodec};
+
+fn p02(c: &mut Criterion) {
+    let mut group = c.benchmark_group("p02");
+
+    let message = *b"I\x00\x00\x30\x39\x00\x00\x00\x65";
+    group.bench_function("parse", |b| {
+        b.iter(|| Message::parse(black_box(&message)).unwrap());
+    });
+
+    let mut batch = vec![b'B', 0x01, 0x00];
+    for pair in 0..256_i32 {
+        batch.extend_from_slice(&pair.to_be_bytes());
+        batch.extend_from_slice(&(pair * 2).to_be_bytes());
+    }
+    group.throughput(Throughput::Bytes(batch.len() as u64));
+    group.bench_function("decode batch of 256", |b| {
+        let mut decoder = MessageDecoder::with_extensions(true);
+        b.iter(|| decoder.decode(black_box(&batch)).unwrap());
+    });
+
+    group.throughput(Throughput::Bytes(MESSAGE_LEN as u64));
+    group.bench_function("decode negotiated", |b| {
+        b.iter(|| {
+            let mut decoder = MessageDecoder::with_negotiation(Extensions::ALL);
+            decoder
+                .decode(black_box(b"H\x00\x00\x00\x01\x00\x00\x00\x03"))
+                .unwrap();
+            decoder.decode(black_box(&message)).unwrap()
+        });
+    });
+
+    group.finish();
+}
+
+fn p06(c: &mut Criterion) {
+    let mut group = c.benchmark_group("p06");
+
+    let plate = [0x20, 0x04, 0x55, 0x4e, 0x31, 0x58, 0x00, 0x00, 0x03, 0xe8];
+    group.bench_function("read plate", |b| {
+        b.iter(|| block_on(wire::Plate::read_from(&mut black_box(plate.as_slice()))).unwrap());
+    });
+
+    let ticket = wire::Ticket {
+        plate: "UN1X".to_string(),
+        road: 66,
+        mile1: 100,
+        timestamp1: 123_456,
+        mile2: 110,
+        timestamp2: 123_816,
+        speed: 10_000,
+    };
+    let mut buffer = vec![];
+    group.bench_function("write ticket", |b| {
+        b.iter(|| {
+            buffer.clear();
+            block_on(black_box(&ticket).write_to(&mut buffer)).unwrap();
+        });
+    });
+
+    group.finish();
+}
+
+fn lrcp(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lrcp");
+
+    // both about 900 bytes on the wire, under the 1000 bytes of a packet
+    for (name, data) in [
+        ("plain", "a".repeat(900)),
+        ("all escaped", "/\\".repeat(225)),
+    ] {
+        let packet = Packet::Data {
+            session: Numeric(1_234_567),
+            pos: Numeric(0),
+            data: Payload(data),
+        };
+        let mut buffer = vec![];
+        buffer.write_value(&packet).unwrap();
+        group.throughput(Throughput::Bytes(buffer.len() as u64));
+
+        group.bench_function(BenchmarkId::new("escape", name), |b| {
+            b.iter(|| {
+                buffer.clear();
+                buffer.write_value(black_box(&packet)).unwrap()
+            });
+        });
+
+        let escaped = buffer.clone();
+        group.bench_function(BenchmarkId::new("unescape", name), |b| {
+            b.iter(|| Packet::try_from(black_box(escaped.as_slice())).unwrap());
+        });
+    }
+
+    group.finish();
+}
+
+fn p09(c: &mut Criterion) {
+    let mut group = c.benchmark_group("p09");
+
+    let line = br#"{"request":"put","queue":"queue1","job":{"title":"example-job"},"pri":123}
+"#;
+    let mut codec = RequestCodec::new();
+    group.throughput(Throughput::Bytes(line.len() as u64));
+    group.bench_function("decode put", |b| {
+        b.iter(|| {
+            let mut src = BytesMut::from(black_box(line.as_slice()));
+            codec.decode(&mut src).unwrap()
+        });
+    });
+
+    let job = serde_json::json!({"title": "example-job"});
+    let mut codec = ResponseCodec::new();
+    let mut dst = BytesMut::new();
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("encode job", |b| {
+        b.iter(|| {
+            dst.clear();
+            let response = Response::JobRef(12_345, job.clone(), 123, "queue1".to_string());
+            codec.encode(black_box(response), &mut dst).unwrap();
+        });
+    });
+
+    group.finish();
+}
+
+fn site_visit(populations: usize) -> PestPacket {
+    site_visit::Packet::new(
+        12_345,
+        (0..populations)
+            .map(|n| site_visit::Population::new(format!("species {n}"), 100))
+            .collect(),
+    )
+    .into()
+}
+
+fn p11(c: &mut Criterion) {
+    let mut group = c.benchmark_group("p11");
+
+    for populations in [1, 100, 10_000] {
+        let mut codec = PacketCodec::new();
+        let mut packet = BytesMut::new();
+        codec.encode(site_visit(populations), &mut packet).unwrap();
+        group.throughput(Throughput::Bytes(packet.len() as u64));
+
+        group.bench_with_input(
+            BenchmarkId::new("encode site visit", populations),
+            &populations,
+            |b, populations| {
+                let mut dst = BytesMut::new();
+                b.iter_batched(
+                    || site_visit(*populations),
+                    |site_visit| {
+                        dst.clear();
+                        codec.encode(site_visit, &mut dst).unwrap();
+                    },
+                    criterion::BatchSize::LargeInput,
+                );
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("decode site visit", populations),
+            &packet,
+            |b, packet| {
+                b.iter(|| {
+                    let mut src = packet.clone();
+                    codec.decode(&mut src).unwrap().unwrap()
+                });
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, p02, p06, lrcp, p09, p11);
+criterion_main!(benches);
@@ -0,0 +1,75 @@
+//! p06 ticketing benchmarks: a plate observed on a road, against the
+//! other observations of the same car on the same road.
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+
+use p06_speed_daemon::controller::{Controller, Plate};
+
+fn plate(mile: u16, timestamp: u32) -> Plate {
+    Plate {
+        road: 66,
+        mile,
+        limit: 60,
+        plate: "UN1X".to_string(),
+        timestamp,
+    }
+}
+
+/// A controller with `observations` of the car, all within the speed
+/// limit, one per hour.
+fn controller(observations: u32) -> Controller {
+    let mut controller = Controller::new();
+    for observation in 0..observations {
+        let tickets = controller.signal(plate(
+            u16::try_from(observation % 60).unwrap(),
+            observation * 3600,
+        ));
+        assert!(tickets.is_empty());
+    }
+    controller
+}
+
+fn signal(c: &mut Criterion) {
+    let mut group = c.benchmark_group("controller");
+
+    for observations in [1, 100, 1_000] {
+        let timestamp = observations * 3600;
+
+        group.bench_with_input(
+            BenchmarkId::new("no ticket", observations),
+            &observations,
+            |b, observations| {
+                b.iter_batched(
+                    || (controller(*observations), plate(0, timestamp)),
+                    |(mut controller, plate)| controller.signal(black_box(plate)),
+                    BatchSize::LargeInput,
+                );
+            },
+        );
+
+        // 60 miles in a minute from the last observation
+        let last = observations - 1;
+        let (mile, timestamp) = (u16::try_from(last % 60).unwrap() + 60, last * 3600 + 60);
+        group.bench_with_input(
+            BenchmarkId::new("ticket", observations),
+            &observations,
+            |b, observations| {
+                b.iter_batched(
+                    || (controller(*observations), plate(mile, timestamp)),
+                    |(mut controller, plate)| {
+                        let tickets = controller.signal(black_box(plate));
+                        assert!(!tickets.is_empty());
+                        tickets
+                    },
+                    BatchSize::LargeInput,
+                );
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, signal);
+criterion_main!(benches);
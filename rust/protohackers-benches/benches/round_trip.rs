@@ -0,0 +1,375 @@
+//! Loopback round trip benchmarks, one for every problem: a request
+//! and its reply on an open connection, the connection setup is not
+//! measured. For p11 the measured exchange is the handshake of a new
+//! connection.
+//!
+//! The servers and the clients run on the same multi thread runtime,
+//! so the numbers are the latency of the whole stack more than of the
+//! server alone.
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use criterion::measurement::WallTime;
+use criterion::{criterion_group, criterion_main, BenchmarkGroup, Criterion};
+
+use futures::{SinkExt, StreamExt};
+
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::runtime::Runtime;
+
+use tokio_util::codec::Framed;
+
+use p06_speed_daemon::wire::{self, ReadFrom, WriteTo};
+use p11_pest_control::codec::packets::{hello, Packet, PacketCodec};
+
+use protohackers_benches::{runtime, spawn_tcp, spawn_udp};
+use protohackers_server::Server;
+
+type Lines = BufReader<TcpStream>;
+
+/// A xor cipher for p08: not a no-op, and the same at every position.
+const XOR: u8 = 123;
+
+/// Measure `exchange`, that runs the given number of round trips and
+/// returns their time.
+fn bench<F, Fut>(group: &mut BenchmarkGroup<WallTime>, runtime: &Runtime, name: &str, exchange: F)
+where
+    F: Fn(u64) -> Fut,
+    Fut: Future<Output = Duration>,
+{
+    group.bench_function(name, |b| {
+        b.iter_custom(|iters| runtime.block_on(exchange(iters)));
+    });
+}
+
+async fn lines(address: SocketAddr) -> Lines {
+    BufReader::new(TcpStream::connect(address).await.unwrap())
+}
+
+async fn send(lines: &mut Lines, line: &[u8]) {
+    lines.get_mut().write_all(line).await.unwrap();
+}
+
+async fn recv(lines: &mut Lines) -> String {
+    let mut line = String::new();
+    lines.read_line(&mut line).await.unwrap();
+    assert!(line.ends_with('\n'), "closed after {line:?}");
+    line
+}
+
+/// A chat client of p03, directly or through the p05 proxy.
+async fn join(address: SocketAddr, name: &str) -> Lines {
+    let mut lines = lines(address).await;
+    recv(&mut lines).await;
+    send(&mut lines, format!("{name}\n").as_bytes()).await;
+    recv(&mut lines).await;
+    lines
+}
+
+/// Two chat clients, the message of the first one reaching the
+/// second one; the names are new across the runs.
+async fn chat(address: SocketAddr, iters: u64) -> Duration {
+    static CLIENTS: AtomicU32 = AtomicU32::new(0);
+    let client = CLIENTS.fetch_add(1, Ordering::Relaxed);
+    let (alice, bob) = (format!("alice{client}"), format!("bob{client}"));
+
+    let mut alice = join(address, &alice).await;
+    let mut bob = join(address, &bob).await;
+    while !recv(&mut alice).await.contains("entered") {}
+
+    let start = Instant::now();
+    for _ in 0..iters {
+        send(&mut alice, b"hi bob\n").await;
+        // the departures of the clients of the previous runs too
+        while !recv(&mut bob).await.contains("hi bob") {}
+    }
+    start.elapsed()
+}
+
+async fn udp(address: SocketAddr) -> UdpSocket {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    socket.connect(address).await.unwrap();
+    socket
+}
+
+fn p00(group: &mut BenchmarkGroup<WallTime>, runtime: &Runtime) {
+    let address = runtime.block_on(spawn_tcp(|listener| {
+        p00_smoke_test::serve(listener, p00_smoke_test::Config::default(), None)
+    }));
+    bench(group, runtime, "p00 echo", |iters| async move {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let mut buffer = [0; 64];
+        let start = Instant::now();
+        for _ in 0..iters {
+            stream.write_all(&[b'x'; 64]).await.unwrap();
+            stream.read_exact(&mut buffer).await.unwrap();
+        }
+        start.elapsed()
+    });
+}
+
+fn p01(group: &mut BenchmarkGroup<WallTime>, runtime: &Runtime) {
+    let address = runtime.block_on(spawn_tcp(|listener| {
+        Server::new(listener).serve(|stream, _| p01_prime_time::handler(stream))
+    }));
+    bench(group, runtime, "p01 is prime", |iters| async move {
+        let mut lines = lines(address).await;
+        let start = Instant::now();
+        for _ in 0..iters {
+            send(&mut lines, b"{\"method\":\"isPrime\",\"number\":1000003}\n").await;
+            recv(&mut lines).await;
+        }
+        start.elapsed()
+    });
+}
+
+fn p02(group: &mut BenchmarkGroup<WallTime>, runtime: &Runtime) {
+    let address = runtime.block_on(spawn_tcp(|listener| {
+        Server::new(listener).serve(|stream, _| p02_means_to_an_end::handler(stream))
+    }));
+    bench(group, runtime, "p02 insert and query", |iters| async move {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let mut mean = [0; 4];
+        let start = Instant::now();
+        for timestamp in 0..iters {
+            let timestamp = i32::try_from(timestamp).unwrap().to_be_bytes();
+            let mut messages = vec![b'I'];
+            messages.extend_from_slice(&timestamp);
+            messages.extend_from_slice(&100_i32.to_be_bytes());
+            messages.push(b'Q');
+            messages.extend_from_slice(&[0; 4]);
+            messages.extend_from_slice(&timestamp);
+            stream.write_all(&messages).await.unwrap();
+            stream.read_exact(&mut mean).await.unwrap();
+        }
+        start.elapsed()
+    });
+}
+
+/// p03, and p05 proxying the same p03 server.
+fn p03(group: &mut BenchmarkGroup<WallTime>, runtime: &Runtime) {
+    let chat_address = runtime.block_on(spawn_tcp(p03_budget_chat::run));
+    bench(group, runtime, "p03 chat", |iters| {
+        chat(chat_address, iters)
+    });
+
+    let address = runtime.block_on(spawn_tcp(|listener| {
+        p05_mob_in_the_middle::run(
+            listener,
+            chat_address.ip().to_string(),
+            chat_address.port(),
+            Arc::new(p05_mob_in_the_middle::Rules::boguscoin(
+                p05_mob_in_the_middle::BOGUSCOIN,
+            )),
+        )
+    }));
+    bench(group, runtime, "p05 chat through the proxy", |iters| {
+        chat(address, iters)
+    });
+}
+
+fn p04(group: &mut BenchmarkGroup<WallTime>, runtime: &Runtime) {
+    let address = runtime.block_on(spawn_udp(p04_unusual_database_program::run));
+    bench(group, runtime, "p04 retrieve", |iters| async move {
+        let socket = udp(address).await;
+        socket.send(b"key=value").await.unwrap();
+        let mut buffer = [0; 1000];
+        let start = Instant::now();
+        for _ in 0..iters {
+            socket.send(b"key").await.unwrap();
+            socket.recv(&mut buffer).await.unwrap();
+        }
+        start.elapsed()
+    });
+}
+
+fn p06(group: &mut BenchmarkGroup<WallTime>, runtime: &Runtime) {
+    // a ticket for every new plate: the plates must be new across the
+    // runs too
+    static PLATES: AtomicU32 = AtomicU32::new(0);
+
+    let address = runtime.block_on(spawn_tcp(p06_speed_daemon::run));
+    bench(group, runtime, "p06 ticket", |iters| async move {
+        let mut dispatcher = BufReader::new(TcpStream::connect(address).await.unwrap());
+        let mut cameras = vec![];
+        let mut message = vec![];
+        wire::IAmDispatcher { roads: vec![66] }
+            .write_to(&mut message)
+            .await
+            .unwrap();
+        dispatcher.get_mut().write_all(&message).await.unwrap();
+        for mile in [0, 100] {
+            let mut camera = TcpStream::connect(address).await.unwrap();
+            message.clear();
+            let limit = 60;
+            wire::IAmCamera {
+                road: 66,
+                mile,
+                limit,
+            }
+            .write_to(&mut message)
+            .await
+            .unwrap();
+            camera.write_all(&message).await.unwrap();
+            cameras.push(camera);
+        }
+
+        let start = Instant::now();
+        for _ in 0..iters {
+            let plate = format!("B{}", PLATES.fetch_add(1, Ordering::Relaxed));
+            // 100 miles in an hour
+            for (camera, timestamp) in cameras.iter_mut().zip([0, 3600]) {
+                message.clear();
+                wire::Plate {
+                    plate: plate.clone(),
+                    timestamp,
+                }
+                .write_to(&mut message)
+                .await
+                .unwrap();
+                camera.write_all(&message).await.unwrap();
+            }
+            wire::Ticket::read_from(&mut dispatcher).await.unwrap();
+        }
+        start.elapsed()
+    });
+}
+
+fn p07(group: &mut BenchmarkGroup<WallTime>, runtime: &Runtime) {
+    // a session for every run
+    static SESSIONS: AtomicU32 = AtomicU32::new(0);
+
+    let address = runtime.block_on(spawn_udp(
+        p07_line_reversal::run::<p07_line_reversal::DefaultSocketHandler>,
+    ));
+    bench(group, runtime, "p07 reverse", |iters| async move {
+        let socket = udp(address).await;
+        let session = SESSIONS.fetch_add(1, Ordering::Relaxed);
+        let mut buffer = [0; 1000];
+        socket
+            .send(format!("/connect/{session}/").as_bytes())
+            .await
+            .unwrap();
+        socket.recv(&mut buffer).await.unwrap();
+
+        let (mut sent, mut received) = (0, 0);
+        let start = Instant::now();
+        for _ in 0..iters {
+            socket
+                .send(format!("/data/{session}/{sent}/hello\n/").as_bytes())
+                .await
+                .unwrap();
+            sent += 6;
+            // the ack of the line, then the reversed line
+            loop {
+                let len = socket.recv(&mut buffer).await.unwrap();
+                if buffer[..len].starts_with(b"/data/") {
+                    break;
+                }
+            }
+            received += 6;
+            socket
+                .send(format!("/ack/{session}/{received}/").as_bytes())
+                .await
+                .unwrap();
+        }
+        start.elapsed()
+    });
+}
+
+fn p08(group: &mut BenchmarkGroup<WallTime>, runtime: &Runtime) {
+    let address = runtime.block_on(spawn_tcp(p08_insecure_sockets_layer::run));
+    bench(group, runtime, "p08 toy", |iters| async move {
+        let mut lines = lines(address).await;
+        send(&mut lines, &[0x02, XOR, 0x00]).await;
+        let request = b"10x toy car,15x dog on a string,4x inflatable motorcycle\n"
+            .iter()
+            .map(|byte| byte ^ XOR)
+            .collect::<Vec<_>>();
+        let mut reply = vec![];
+        let start = Instant::now();
+        for _ in 0..iters {
+            send(&mut lines, &request).await;
+            reply.clear();
+            lines.read_until(b'\n' ^ XOR, &mut reply).await.unwrap();
+        }
+        start.elapsed()
+    });
+}
+
+fn p09(group: &mut BenchmarkGroup<WallTime>, runtime: &Runtime) {
+    let address = runtime.block_on(spawn_tcp(p09_job_centre::run));
+    bench(group, runtime, "p09 put and get", |iters| async move {
+        let mut lines = lines(address).await;
+        let start = Instant::now();
+        for _ in 0..iters {
+            send(
+                &mut lines,
+                b"{\"request\":\"put\",\"queue\":\"bench\",\"job\":{},\"pri\":1}\n",
+            )
+            .await;
+            recv(&mut lines).await;
+            send(
+                &mut lines,
+                b"{\"request\":\"get\",\"queues\":[\"bench\"]}\n",
+            )
+            .await;
+            recv(&mut lines).await;
+        }
+        start.elapsed()
+    });
+}
+
+fn p10(group: &mut BenchmarkGroup<WallTime>, runtime: &Runtime) {
+    let address = runtime.block_on(spawn_tcp(p10_voracious_code_storage::run));
+    bench(group, runtime, "p10 put", |iters| async move {
+        let mut lines = lines(address).await;
+        recv(&mut lines).await;
+        let start = Instant::now();
+        for _ in 0..iters {
+            send(&mut lines, b"PUT /bench.txt 6\nhello\n").await;
+            recv(&mut lines).await;
+            recv(&mut lines).await;
+        }
+        start.elapsed()
+    });
+}
+
+fn p11(group: &mut BenchmarkGroup<WallTime>, runtime: &Runtime) {
+    // the handshake only, no authority server is dialled
+    let address = runtime.block_on(spawn_tcp(|listener| {
+        p11_pest_control::run(
+            listener,
+            p11_pest_control::DefaultProvider::new("127.0.0.1".to_string(), 1),
+        )
+    }));
+    bench(group, runtime, "p11 handshake", |iters| async move {
+        let start = Instant::now();
+        for _ in 0..iters {
+            let stream = TcpStream::connect(address).await.unwrap();
+            let mut framed = Framed::new(stream, PacketCodec::new());
+            framed
+                .send(Packet::from(hello::Packet::new()))
+                .await
+                .unwrap();
+            framed.next().await.unwrap().unwrap();
+        }
+        start.elapsed()
+    });
+}
+
+fn round_trip(c: &mut Criterion) {
+    let runtime = runtime();
+    let mut group = c.benchmark_group("round trip");
+    for bench in [p00, p01, p02, p03, p04, p06, p07, p08, p09, p10, p11] {
+        bench(&mut group, &runtime);
+    }
+    group.finish();
+}
+
+criterion_group!(benches, round_trip);
+criterion_main!(benches);
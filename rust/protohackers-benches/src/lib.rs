@@ -0,0 +1,63 @@
+//! End to end benchmarks of the workspace, in `benches`:
+//!
+//! * `codecs`: the encode and decode throughput of the wire formats;
+//! * `controller`: the p06 ticketing;
+//! * `round_trip`: a request and its reply over the loopback, for
+//!   every problem.
+//!
+//! Run with `cargo bench -p protohackers-benches`, a single bench
+//! with `--bench round_trip`. The helpers here spawn the servers on
+//! the loopback, on ephemeral ports.
+use std::future::Future;
+use std::net::SocketAddr;
+
+use tokio::net::{TcpListener, UdpSocket};
+use tokio::runtime::{self, Runtime};
+
+/// The runtime of the servers and of the clients.
+///
+/// # Panics
+/// * When the runtime can not be built.
+#[must_use]
+pub fn runtime() -> Runtime {
+    runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("cannot build the runtime")
+}
+
+/// Spawn `serve` on a loopback listener, returning its address.
+///
+/// # Panics
+/// * When the listener can not be bound.
+pub async fn spawn_tcp<F, Fut>(serve: F) -> SocketAddr
+where
+    F: FnOnce(TcpListener) -> Fut,
+    Fut: Future + Send + 'static,
+    Fut::Output: Send,
+{
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("cannot bind the listener");
+    let address = listener.local_addr().expect("cannot get the address");
+    tokio::spawn(serve(listener));
+    address
+}
+
+/// Spawn `serve` on a loopback UDP socket, returning its address.
+///
+/// # Panics
+/// * When the socket can not be bound.
+pub async fn spawn_udp<F, Fut>(serve: F) -> SocketAddr
+where
+    F: FnOnce(UdpSocket) -> Fut,
+    Fut: Future + Send + 'static,
+    Fut::Output: Send,
+{
+    let socket = UdpSocket::bind("127.0.0.1:0")
+        .await
+        .expect("cannot bind the socket");
+    let address = socket.local_addr().expect("cannot get the address");
+    tokio::spawn(serve(socket));
+    address
+}
@@ -0,0 +1,303 @@
+//! The clients of the scenarios.
+use std::future::Future;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, bail, ensure, Context};
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{lookup_host, TcpStream, UdpSocket};
+use tokio::time;
+
+/// The time a server is given to stay silent, when nothing is
+/// expected.
+pub const SILENCE: Duration = Duration::from_millis(300);
+
+/// The server under check.
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub address: String,
+
+    /// The longest wait for every expected reply.
+    pub timeout: Duration,
+}
+
+impl Target {
+    #[must_use]
+    pub fn new(address: impl Into<String>, timeout: Duration) -> Self {
+        Self {
+            address: address.into(),
+            timeout,
+        }
+    }
+
+    /// Wait for `future` at most the timeout of the target.
+    ///
+    /// # Errors
+    /// * Error when the time is over, waiting for `what`.
+    pub async fn within<F: Future>(
+        &self,
+        what: &str,
+        future: F,
+    ) -> Result<F::Output, anyhow::Error> {
+        time::timeout(self.timeout, future)
+            .await
+            .map_err(|_| anyhow!("timed out waiting for {what}"))
+    }
+
+    /// # Errors
+    /// * Error when the server can not be reached.
+    pub async fn connect(&self) -> Result<TcpStream, anyhow::Error> {
+        self.within("the connection", TcpStream::connect(&self.address))
+            .await?
+            .with_context(|| format!("connecting to {}", self.address))
+    }
+
+    /// A connection exchanging lines.
+    ///
+    /// # Errors
+    /// * Error when the server can not be reached.
+    pub async fn lines(&self) -> Result<Lines, anyhow::Error> {
+        let (read, write) = self.connect().await?.into_split();
+        Ok(Lines {
+            read: BufReader::new(read),
+            write,
+            target: self.clone(),
+        })
+    }
+
+    /// A UDP socket exchanging datagrams with the server.
+    ///
+    /// # Errors
+    /// * Error when the address can not be resolved.
+    pub async fn udp(&self) -> Result<Udp, anyhow::Error> {
+        let address = lookup_host(&self.address)
+            .await?
+            .next()
+            .ok_or_else(|| anyhow!("no address for {}", self.address))?;
+        let local = match address {
+            SocketAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+            SocketAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+        };
+        let socket = UdpSocket::bind(local).await?;
+        socket.connect(address).await?;
+        Ok(Udp {
+            socket,
+            target: self.clone(),
+        })
+    }
+
+    /// Read `buffer.len()` bytes from `read`.
+    ///
+    /// # Errors
+    /// * Error when the time is over or the connection is closed.
+    pub async fn read_exact<R: AsyncRead + Unpin>(
+        &self,
+        read: &mut R,
+        buffer: &mut [u8],
+    ) -> Result<(), anyhow::Error> {
+        self.within("the reply", read.read_exact(buffer))
+            .await?
+            .context("reading the reply")?;
+        Ok(())
+    }
+
+    /// Wait for the server to close `read`, dropping the data before.
+    ///
+    /// # Errors
+    /// * Error when the time is over.
+    pub async fn expect_closed<R: AsyncRead + Unpin>(
+        &self,
+        read: &mut R,
+    ) -> Result<(), anyhow::Error> {
+        let mut buffer = vec![];
+        // a reset is a close too
+        let _ = self
+            .within("the close", read.read_to_end(&mut buffer))
+            .await?;
+        Ok(())
+    }
+}
+
+/// Nothing from `read` for [`SILENCE`].
+///
+/// # Errors
+/// * Error when some data, or the end of file, is read.
+pub async fn expect_silence<R: AsyncRead + Unpin>(read: &mut R) -> Result<(), anyhow::Error> {
+    let mut buffer = [0; 1];
+    match time::timeout(SILENCE, read.read(&mut buffer)).await {
+        Err(_) => Ok(()),
+        Ok(Ok(0)) => bail!("closed, expected silence"),
+        Ok(Ok(_)) => bail!("got 0x{:02x}, expected silence", buffer[0]),
+        Ok(Err(err)) => Err(err).context("expected silence"),
+    }
+}
+
+/// A unique number per call, to keep the runs apart on a long living
+/// server.
+#[must_use]
+pub fn unique() -> u32 {
+    static NEXT: AtomicU32 = AtomicU32::new(0);
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let base = u32::try_from(now.as_millis() % 1_000_000_000).unwrap_or_default();
+    base.wrapping_add(NEXT.fetch_add(1, Ordering::Relaxed))
+}
+
+/// A connection exchanging newline terminated lines.
+#[derive(Debug)]
+pub struct Lines {
+    read: BufReader<OwnedReadHalf>,
+    write: OwnedWriteHalf,
+    target: Target,
+}
+
+impl Lines {
+    /// Send `line` and the newline.
+    ///
+    /// # Errors
+    /// * Error when the connection fails.
+    pub async fn send(&mut self, line: &str) -> Result<(), anyhow::Error> {
+        self.send_raw(format!("{line}\n").as_bytes()).await
+    }
+
+    /// Send `data` verbatim.
+    ///
+    /// # Errors
+    /// * Error when the connection fails.
+    pub async fn send_raw(&mut self, data: &[u8]) -> Result<(), anyhow::Error> {
+        self.write.write_all(data).await.context("sending")
+    }
+
+    /// The next line, without the newline.
+    ///
+    /// # Errors
+    /// * Error when the time is over or the connection is closed.
+    pub async fn recv(&mut self) -> Result<String, anyhow::Error> {
+        let mut line = String::new();
+        self.target
+            .within("a line", self.read.read_line(&mut line))
+            .await?
+            .context("reading a line")?;
+        match line.strip_suffix('\n') {
+            Some(line) => Ok(line.to_string()),
+            None if line.is_empty() => bail!("closed, expected a line"),
+            None => bail!("closed after {line:?}, expected a newline"),
+        }
+    }
+
+    /// The next line is `expected`.
+    ///
+    /// # Errors
+    /// * Error when the line is another one.
+    pub async fn expect(&mut self, expected: &str) -> Result<(), anyhow::Error> {
+        let line = self.recv().await?;
+        ensure!(line == expected, "got {line:?}, expected {expected:?}");
+        Ok(())
+    }
+
+    /// Nothing from the server for [`SILENCE`].
+    ///
+    /// # Errors
+    /// * Error when a line, or the end of file, is read.
+    pub async fn expect_silence(&mut self) -> Result<(), anyhow::Error> {
+        expect_silence(&mut self.read).await
+    }
+
+    /// Wait for the server to close the connection, dropping the
+    /// lines before.
+    ///
+    /// # Errors
+    /// * Error when the time is over.
+    pub async fn expect_closed(&mut self) -> Result<(), anyhow::Error> {
+        self.target.expect_closed(&mut self.read).await
+    }
+
+    /// All the data up to the close, even without a final newline.
+    ///
+    /// # Errors
+    /// * Error when the time is over or the connection fails.
+    pub async fn recv_to_close(&mut self) -> Result<String, anyhow::Error> {
+        let mut data = vec![];
+        self.target
+            .within("the close", self.read.read_to_end(&mut data))
+            .await?
+            .context("reading to the close")?;
+        Ok(String::from_utf8_lossy(&data).into_owned())
+    }
+
+    /// Close the sending side.
+    ///
+    /// # Errors
+    /// * Error when the connection fails.
+    pub async fn shutdown(&mut self) -> io::Result<()> {
+        self.write.shutdown().await
+    }
+}
+
+/// A UDP socket connected to the server.
+#[derive(Debug)]
+pub struct Udp {
+    socket: UdpSocket,
+    target: Target,
+}
+
+impl Udp {
+    /// # Errors
+    /// * Error when the socket fails.
+    pub async fn send(&self, datagram: &[u8]) -> Result<(), anyhow::Error> {
+        self.socket.send(datagram).await.context("sending")?;
+        Ok(())
+    }
+
+    /// The next datagram.
+    ///
+    /// # Errors
+    /// * Error when the time is over or the socket fails.
+    pub async fn recv(&self) -> Result<Vec<u8>, anyhow::Error> {
+        let mut buffer = vec![0; 65_536];
+        let n = self
+            .target
+            .within("a datagram", self.socket.recv(&mut buffer))
+            .await?
+            .context("receiving")?;
+        buffer.truncate(n);
+        Ok(buffer)
+    }
+
+    /// The next datagram is `expected`.
+    ///
+    /// # Errors
+    /// * Error when the datagram is another one.
+    pub async fn expect(&self, expected: &[u8]) -> Result<(), anyhow::Error> {
+        let datagram = self.recv().await?;
+        ensure!(
+            datagram == expected,
+            "got {:?}, expected {:?}",
+            String::from_utf8_lossy(&datagram),
+            String::from_utf8_lossy(expected)
+        );
+        Ok(())
+    }
+
+    /// No datagram for [`SILENCE`].
+    ///
+    /// # Errors
+    /// * Error when a datagram is received.
+    pub async fn expect_silence(&self) -> Result<(), anyhow::Error> {
+        let mut buffer = vec![0; 65_536];
+        match time::timeout(SILENCE, self.socket.recv(&mut buffer)).await {
+            Err(_) => Ok(()),
+            Ok(Ok(n)) => bail!(
+                "got {:?}, expected silence",
+                String::from_utf8_lossy(&buffer[..n])
+            ),
+            Ok(Err(err)) => Err(err).context("expected silence"),
+        }
+    }
+}
@@ -0,0 +1,30 @@
+//! Conformance checks of the problem servers.
+//!
+//! Every problem has a battery of [`Scenario`]s derived from its
+//! specification, the valid sessions, the malformed inputs and the
+//! edge cases, run against a running server, e.g. to validate a local
+//! change before submitting to the official checker:
+//!
+//! ```raw
+//! protohackers-check --port 10000 p06
+//! ```
+//!
+//! The clients are written from the specifications only, they do not
+//! share any code with the servers.
+pub mod client;
+pub mod p00;
+pub mod p01;
+pub mod p02;
+pub mod p03;
+pub mod p04;
+pub mod p05;
+pub mod p06;
+pub mod p07;
+pub mod p08;
+pub mod p09;
+pub mod p10;
+pub mod p11;
+pub mod scenario;
+
+pub use client::Target;
+pub use scenario::{Kind, Outcome, Report, Scenario};
@@ -0,0 +1,147 @@
+//! Run the conformance checks of a problem against a running server:
+//!
+//! ```raw
+//! protohackers-check --address 127.0.0.1 --port 10000 p06
+//! ```
+use std::process::ExitCode;
+use std::time::Duration;
+
+use clap::Parser;
+
+use protohackers_check::{p00, p01, p02, p03, p04, p05, p06, p07, p08, p09, p10, p11};
+use protohackers_check::{Report, Scenario, Target};
+
+#[derive(clap::Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    #[arg(long, default_value = "127.0.0.1")]
+    address: String,
+
+    #[arg(long, default_value_t = 10000)]
+    port: u16,
+
+    /// Wait for every expected reply at most this number of seconds
+    #[arg(long, default_value_t = 5)]
+    timeout: u64,
+
+    /// Run only the scenarios with this text in the name
+    #[arg(long)]
+    filter: Option<String>,
+
+    #[command(subcommand)]
+    problem: Problem,
+}
+
+#[derive(clap::Subcommand, Debug, Clone, Copy)]
+enum Problem {
+    /// Smoke Test
+    P00,
+
+    /// Prime Time
+    P01,
+
+    /// Means to an End
+    P02,
+
+    /// Budget Chat
+    P03,
+
+    /// Unusual Database Program
+    P04,
+
+    /// Mob in the Middle, through the proxy
+    P05,
+
+    /// Speed Daemon
+    P06,
+
+    /// Line Reversal
+    P07,
+
+    /// Insecure Sockets Layer
+    P08,
+
+    /// Job Centre
+    P09,
+
+    /// Voracious Code Storage
+    P10,
+
+    /// Pest Control
+    P11,
+}
+
+impl Problem {
+    fn scenarios(self) -> Vec<Scenario> {
+        match self {
+            Self::P00 => p00::scenarios(),
+            Self::P01 => p01::scenarios(),
+            Self::P02 => p02::scenarios(),
+            Self::P03 => p03::scenarios(),
+            Self::P04 => p04::scenarios(),
+            Self::P05 => p05::scenarios(),
+            Self::P06 => p06::scenarios(),
+            Self::P07 => p07::scenarios(),
+            Self::P08 => p08::scenarios(),
+            Self::P09 => p09::scenarios(),
+            Self::P10 => p10::scenarios(),
+            Self::P11 => p11::scenarios(),
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = Args::parse();
+
+    let target = Target::new(
+        format!("{}:{}", args.address, args.port),
+        Duration::from_secs(args.timeout),
+    );
+    let scenarios = args
+        .problem
+        .scenarios()
+        .into_iter()
+        .filter(|scenario| {
+            args.filter
+                .as_ref()
+                .is_none_or(|filter| scenario.name.contains(filter.as_str()))
+        })
+        .collect::<Vec<_>>();
+
+    let report = Report::run(&target, &scenarios).await;
+    println!("{report}");
+
+    if report.is_success() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_command() {
+        Args::command().debug_assert();
+    }
+
+    #[test]
+    fn test_args() {
+        let args = Args::parse_from(["check", "--port", "1234", "--filter", "ticket", "p06"]);
+        assert_eq!(1234, args.port);
+        assert_eq!(Some("ticket"), args.filter.as_deref());
+        assert!(matches!(args.problem, Problem::P06));
+
+        for problem in [
+            "p00", "p01", "p02", "p03", "p04", "p05", "p06", "p07", "p08", "p09", "p10", "p11",
+        ] {
+            let args = Args::parse_from(["check", problem]);
+            assert!(!args.problem.scenarios().is_empty(), "{problem}");
+        }
+    }
+}
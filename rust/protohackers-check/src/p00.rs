@@ -0,0 +1,78 @@
+//! Smoke Test: the TCP echo service of RFC 862.
+use anyhow::ensure;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::{Kind, Scenario, Target};
+
+#[must_use]
+pub fn scenarios() -> Vec<Scenario> {
+    vec![
+        Scenario::new(Kind::Valid, "echo text", |target| {
+            Box::pin(echo(target, b"hello, world\n".to_vec()))
+        }),
+        Scenario::new(Kind::Valid, "echo binary", |target| {
+            Box::pin(echo(target, (0..=255).collect()))
+        }),
+        Scenario::new(Kind::Valid, "5 simultaneous clients", |target| {
+            Box::pin(simultaneous(target))
+        }),
+        Scenario::new(Kind::EdgeCase, "empty session", |target| {
+            Box::pin(echo(target, vec![]))
+        }),
+        Scenario::new(Kind::EdgeCase, "1 MiB while reading", |target| {
+            Box::pin(echo(target, (0..=250).cycle().take(1 << 20).collect()))
+        }),
+    ]
+}
+
+/// Send `data`, shut down the sending side and expect the same data,
+/// then the close.
+async fn echo(target: Target, data: Vec<u8>) -> Result<(), anyhow::Error> {
+    let stream = target.connect().await?;
+    let (mut read, mut write) = stream.into_split();
+
+    // write while reading, the server can echo before the end
+    let writer = tokio::spawn(async move {
+        write.write_all(&data).await?;
+        write.shutdown().await?;
+        Ok::<_, std::io::Error>(data)
+    });
+
+    let mut echoed = vec![];
+    target
+        .within("the echo", read.read_to_end(&mut echoed))
+        .await??;
+    let data = writer.await??;
+
+    ensure!(
+        echoed == data,
+        "echoed {} bytes, sent {}, first difference at {:?}",
+        echoed.len(),
+        data.len(),
+        echoed.iter().zip(&data).position(|(a, b)| a != b)
+    );
+    Ok(())
+}
+
+async fn simultaneous(target: Target) -> Result<(), anyhow::Error> {
+    let mut streams = vec![];
+    for _ in 0..5 {
+        streams.push(target.connect().await?);
+    }
+
+    for (i, stream) in streams.iter_mut().enumerate() {
+        stream.write_all(format!("client {i}\n").as_bytes()).await?;
+    }
+    for (i, stream) in streams.iter_mut().enumerate() {
+        let expected = format!("client {i}\n");
+        let mut echoed = vec![0; expected.len()];
+        target.read_exact(stream, &mut echoed).await?;
+        ensure!(
+            echoed == expected.as_bytes(),
+            "client {i} got {:?}",
+            String::from_utf8_lossy(&echoed)
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,123 @@
+//! Prime Time: JSON requests `{"method":"isPrime","number":N}`, one
+//! per line.
+use anyhow::{bail, ensure};
+
+use serde_json::Value;
+
+use crate::client::Lines;
+use crate::{Kind, Scenario, Target};
+
+#[must_use]
+pub fn scenarios() -> Vec<Scenario> {
+    vec![
+        Scenario::new(Kind::Valid, "prime", |target| {
+            Box::pin(is_prime(target, "7", true))
+        }),
+        Scenario::new(Kind::Valid, "not prime", |target| {
+            Box::pin(is_prime(target, "8", false))
+        }),
+        Scenario::new(Kind::Valid, "pipelined requests", |target| {
+            Box::pin(pipelined(target))
+        }),
+        Scenario::new(Kind::EdgeCase, "negative", |target| {
+            Box::pin(is_prime(target, "-7", false))
+        }),
+        Scenario::new(Kind::EdgeCase, "zero and one", |target| {
+            Box::pin(async move {
+                is_prime(target.clone(), "0", false).await?;
+                is_prime(target, "1", false).await
+            })
+        }),
+        Scenario::new(Kind::EdgeCase, "non integer", |target| {
+            Box::pin(is_prime(target, "7.5", false))
+        }),
+        Scenario::new(Kind::EdgeCase, "bigger than 64 bits", |target| {
+            Box::pin(is_prime(target, "18446744073709551617", false))
+        }),
+        Scenario::new(Kind::EdgeCase, "extra fields", |target| {
+            Box::pin(request(
+                target,
+                r#"{"number":13,"extra":[1,2],"method":"isPrime"}"#,
+                true,
+            ))
+        }),
+        Scenario::new(Kind::Malformed, "invalid JSON", |target| {
+            Box::pin(malformed(target, r#"{"method":"isPrime","number":7"#))
+        }),
+        Scenario::new(Kind::Malformed, "unknown method", |target| {
+            Box::pin(malformed(target, r#"{"method":"isPrim","number":7}"#))
+        }),
+        Scenario::new(Kind::Malformed, "number as string", |target| {
+            Box::pin(malformed(target, r#"{"method":"isPrime","number":"7"}"#))
+        }),
+        Scenario::new(Kind::Malformed, "missing number", |target| {
+            Box::pin(malformed(target, r#"{"method":"isPrime"}"#))
+        }),
+    ]
+}
+
+async fn is_prime(target: Target, number: &str, prime: bool) -> Result<(), anyhow::Error> {
+    request(
+        target,
+        &format!(r#"{{"method":"isPrime","number":{number}}}"#),
+        prime,
+    )
+    .await
+}
+
+async fn request(target: Target, request: &str, prime: bool) -> Result<(), anyhow::Error> {
+    let mut lines = target.lines().await?;
+    lines.send(request).await?;
+    expect_response(&mut lines, prime).await
+}
+
+async fn pipelined(target: Target) -> Result<(), anyhow::Error> {
+    let numbers = [(2, true), (4, false), (97, true), (100, false)];
+
+    let mut lines = target.lines().await?;
+    let requests = numbers
+        .iter()
+        .map(|(number, _)| format!("{{\"method\":\"isPrime\",\"number\":{number}}}\n"))
+        .collect::<Vec<_>>()
+        .concat();
+    lines.send_raw(requests.as_bytes()).await?;
+
+    for (_, prime) in numbers {
+        expect_response(&mut lines, prime).await?;
+    }
+    Ok(())
+}
+
+async fn expect_response(lines: &mut Lines, prime: bool) -> Result<(), anyhow::Error> {
+    let line = lines.recv().await?;
+    let Ok(response) = serde_json::from_str::<Value>(&line) else {
+        bail!("malformed response {line:?}");
+    };
+    ensure!(
+        response.get("method") == Some(&Value::from("isPrime")),
+        "wrong method in {line:?}"
+    );
+    ensure!(
+        response.get("prime") == Some(&Value::from(prime)),
+        "expected prime {prime} in {line:?}"
+    );
+    Ok(())
+}
+
+/// A malformed request gets a malformed response, then the close; the
+/// response may lack its newline.
+async fn malformed(target: Target, request: &str) -> Result<(), anyhow::Error> {
+    let mut lines = target.lines().await?;
+    lines.send(request).await?;
+
+    let data = lines.recv_to_close().await?;
+    ensure!(!data.is_empty(), "closed without a response");
+    let line = data.lines().next().unwrap_or_default();
+    let response = serde_json::from_str::<Value>(line).ok();
+    let well_formed = response.as_ref().is_some_and(|response| {
+        response.get("method") == Some(&Value::from("isPrime"))
+            && response.get("prime").is_some_and(Value::is_boolean)
+    });
+    ensure!(!well_formed, "well formed response {line:?}");
+    Ok(())
+}
@@ -0,0 +1,118 @@
+//! Means to an End: 9 bytes binary messages, inserts `I` and
+//! queries `Q` of timestamped prices, per session.
+use anyhow::ensure;
+
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+
+use crate::{Kind, Scenario, Target};
+
+#[must_use]
+pub fn scenarios() -> Vec<Scenario> {
+    vec![
+        Scenario::new(Kind::Valid, "specification example", |target| {
+            Box::pin(session(
+                target,
+                vec![
+                    insert(12345, 101),
+                    insert(12346, 102),
+                    insert(12347, 100),
+                    insert(40960, 5),
+                ],
+                (12288, 16384),
+                101,
+            ))
+        }),
+        Scenario::new(Kind::Valid, "sessions are separate", |target| {
+            Box::pin(separate(target))
+        }),
+        Scenario::new(Kind::EdgeCase, "negative prices", |target| {
+            Box::pin(session(
+                target,
+                vec![insert(1, -10), insert(2, -20)],
+                (0, 10),
+                -15,
+            ))
+        }),
+        Scenario::new(Kind::EdgeCase, "no prices in range", |target| {
+            Box::pin(session(target, vec![insert(100, 10)], (0, 99), 0))
+        }),
+        Scenario::new(Kind::EdgeCase, "min time after max time", |target| {
+            Box::pin(session(target, vec![insert(100, 10)], (200, 0), 0))
+        }),
+        Scenario::new(Kind::EdgeCase, "negative timestamps", |target| {
+            Box::pin(session(
+                target,
+                vec![insert(-100, 10), insert(-50, 20), insert(50, 90)],
+                (i32::MIN, 0),
+                15,
+            ))
+        }),
+        Scenario::new(Kind::EdgeCase, "message split in bytes", |target| {
+            Box::pin(split(target))
+        }),
+    ]
+}
+
+fn message(kind: u8, first: i32, second: i32) -> [u8; 9] {
+    let mut message = [kind; 9];
+    message[1..5].copy_from_slice(&first.to_be_bytes());
+    message[5..].copy_from_slice(&second.to_be_bytes());
+    message
+}
+
+fn insert(timestamp: i32, price: i32) -> [u8; 9] {
+    message(b'I', timestamp, price)
+}
+
+fn query(min_time: i32, max_time: i32) -> [u8; 9] {
+    message(b'Q', min_time, max_time)
+}
+
+async fn expect_mean(
+    target: &Target,
+    stream: &mut TcpStream,
+    mean: i32,
+) -> Result<(), anyhow::Error> {
+    let mut response = [0; 4];
+    target.read_exact(stream, &mut response).await?;
+    let response = i32::from_be_bytes(response);
+    ensure!(response == mean, "got mean {response}, expected {mean}");
+    Ok(())
+}
+
+async fn session(
+    target: Target,
+    inserts: Vec<[u8; 9]>,
+    (min_time, max_time): (i32, i32),
+    mean: i32,
+) -> Result<(), anyhow::Error> {
+    let mut stream = target.connect().await?;
+    for insert in inserts {
+        stream.write_all(&insert).await?;
+    }
+    stream.write_all(&query(min_time, max_time)).await?;
+    expect_mean(&target, &mut stream, mean).await
+}
+
+async fn separate(target: Target) -> Result<(), anyhow::Error> {
+    let mut first = target.connect().await?;
+    let mut second = target.connect().await?;
+
+    first.write_all(&insert(1, 100)).await?;
+    second.write_all(&insert(1, 200)).await?;
+
+    first.write_all(&query(0, 10)).await?;
+    expect_mean(&target, &mut first, 100).await?;
+    second.write_all(&query(0, 10)).await?;
+    expect_mean(&target, &mut second, 200).await
+}
+
+async fn split(target: Target) -> Result<(), anyhow::Error> {
+    let mut stream = target.connect().await?;
+    for byte in insert(1, 42).into_iter().chain(query(0, 10)) {
+        stream.write_all(&[byte]).await?;
+        stream.flush().await?;
+    }
+    expect_mean(&target, &mut stream, 42).await
+}
@@ -0,0 +1,151 @@
+//! Budget Chat: a single chat room, one line per message.
+use anyhow::ensure;
+
+use crate::client::{unique, Lines};
+use crate::{Kind, Scenario, Target};
+
+#[must_use]
+pub fn scenarios() -> Vec<Scenario> {
+    vec![
+        Scenario::new(Kind::Valid, "welcome", |target| Box::pin(welcome(target))),
+        Scenario::new(Kind::Valid, "join and presence", |target| {
+            Box::pin(presence(target))
+        }),
+        Scenario::new(Kind::Valid, "chat message", |target| {
+            Box::pin(message(target))
+        }),
+        Scenario::new(Kind::Valid, "leave", |target| Box::pin(leave(target))),
+        Scenario::new(Kind::EdgeCase, "not joined sees nothing", |target| {
+            Box::pin(not_joined(target))
+        }),
+        Scenario::new(Kind::Malformed, "illegal name", |target| {
+            Box::pin(illegal_name(target, "bad name!"))
+        }),
+        Scenario::new(Kind::Malformed, "empty name", |target| {
+            Box::pin(illegal_name(target, ""))
+        }),
+    ]
+}
+
+fn name(prefix: &str) -> String {
+    format!("{prefix}{}", unique() % 100_000)
+}
+
+/// Skip the lines, e.g. the notifications of the other clients,
+/// until one satisfies `predicate`.
+async fn wait_for(
+    lines: &mut Lines,
+    what: &str,
+    predicate: impl Fn(&str) -> bool,
+) -> Result<String, anyhow::Error> {
+    loop {
+        let line = lines
+            .recv()
+            .await
+            .map_err(|err| err.context(format!("waiting for {what}")))?;
+        if predicate(&line) {
+            return Ok(line);
+        }
+    }
+}
+
+/// Connect and join as `name`, returning the presence notification.
+async fn join(target: &Target, name: &str) -> Result<(Lines, String), anyhow::Error> {
+    let mut lines = target.lines().await?;
+    lines.recv().await?;
+    lines.send(name).await?;
+
+    let presence = lines.recv().await?;
+    ensure!(
+        presence.starts_with('*'),
+        "got {presence:?}, expected the presence notification"
+    );
+    Ok((lines, presence))
+}
+
+async fn welcome(target: Target) -> Result<(), anyhow::Error> {
+    let mut lines = target.lines().await?;
+    let welcome = lines.recv().await?;
+    ensure!(!welcome.is_empty(), "empty welcome message");
+    Ok(())
+}
+
+async fn presence(target: Target) -> Result<(), anyhow::Error> {
+    let (alice_name, bob_name) = (name("alice"), name("bob"));
+
+    let (mut alice, _) = join(&target, &alice_name).await?;
+    let (_bob, presence) = join(&target, &bob_name).await?;
+    ensure!(
+        presence.contains(&alice_name),
+        "{alice_name} missing in {presence:?}"
+    );
+    ensure!(
+        !presence.contains(&bob_name),
+        "{bob_name} listed in its own presence {presence:?}"
+    );
+
+    wait_for(&mut alice, "the join notification", |line| {
+        line.starts_with('*') && line.contains(&bob_name)
+    })
+    .await?;
+    Ok(())
+}
+
+async fn message(target: Target) -> Result<(), anyhow::Error> {
+    let (alice_name, bob_name) = (name("alice"), name("bob"));
+
+    let (mut alice, _) = join(&target, &alice_name).await?;
+    let (mut bob, _) = join(&target, &bob_name).await?;
+
+    bob.send("hello, alice").await?;
+    let expected = format!("[{bob_name}] hello, alice");
+    wait_for(&mut alice, "the message", |line| line == expected).await?;
+
+    // the message is not echoed to the sender
+    loop {
+        let Ok(line) = tokio::time::timeout(crate::client::SILENCE, bob.recv()).await else {
+            return Ok(());
+        };
+        let line = line?;
+        ensure!(!line.contains("hello, alice"), "echoed {line:?}");
+    }
+}
+
+async fn leave(target: Target) -> Result<(), anyhow::Error> {
+    let (alice_name, bob_name) = (name("alice"), name("bob"));
+
+    let (mut alice, _) = join(&target, &alice_name).await?;
+    let (bob, _) = join(&target, &bob_name).await?;
+    wait_for(&mut alice, "the join notification", |line| {
+        line.starts_with('*') && line.contains(&bob_name)
+    })
+    .await?;
+
+    drop(bob);
+    wait_for(&mut alice, "the leave notification", |line| {
+        line.starts_with('*') && line.contains(&bob_name)
+    })
+    .await?;
+    Ok(())
+}
+
+async fn not_joined(target: Target) -> Result<(), anyhow::Error> {
+    let mut carol = target.lines().await?;
+    carol.recv().await?;
+
+    let (alice_name, bob_name) = (name("alice"), name("bob"));
+    let (mut alice, _) = join(&target, &alice_name).await?;
+    let (_bob, _) = join(&target, &bob_name).await?;
+    alice.send("a secret").await?;
+
+    carol.expect_silence().await
+}
+
+/// An illegal name is refused closing the connection, maybe after an
+/// error message.
+async fn illegal_name(target: Target, name: &str) -> Result<(), anyhow::Error> {
+    let mut lines = target.lines().await?;
+    lines.recv().await?;
+    lines.send(name).await?;
+    lines.expect_closed().await
+}
@@ -0,0 +1,74 @@
+//! Unusual Database Program: a key-value store over UDP, inserts
+//! `key=value` and retrieves `key`.
+use anyhow::ensure;
+
+use crate::client::unique;
+use crate::{Kind, Scenario, Target};
+
+#[must_use]
+pub fn scenarios() -> Vec<Scenario> {
+    vec![
+        Scenario::new(Kind::Valid, "insert and retrieve", |target| {
+            Box::pin(insert_retrieve(target, &["{key}=bar"], "{key}=bar"))
+        }),
+        Scenario::new(Kind::Valid, "update", |target| {
+            Box::pin(insert_retrieve(
+                target,
+                &["{key}=bar", "{key}=baz"],
+                "{key}=baz",
+            ))
+        }),
+        Scenario::new(Kind::Valid, "version", |target| Box::pin(version(target))),
+        Scenario::new(Kind::EdgeCase, "equals signs in the value", |target| {
+            Box::pin(insert_retrieve(target, &["{key}==a=b="], "{key}==a=b="))
+        }),
+        Scenario::new(Kind::EdgeCase, "empty value", |target| {
+            Box::pin(insert_retrieve(target, &["{key}="], "{key}="))
+        }),
+        Scenario::new(Kind::EdgeCase, "version is read only", |target| {
+            Box::pin(read_only_version(target))
+        }),
+    ]
+}
+
+/// Send `inserts` and retrieve the key, `{key}` is replaced by a
+/// unique key.
+async fn insert_retrieve(
+    target: Target,
+    inserts: &[&str],
+    expected: &str,
+) -> Result<(), anyhow::Error> {
+    let key = format!("check-{}", unique());
+    let socket = target.udp().await?;
+    for insert in inserts {
+        socket
+            .send(insert.replace("{key}", &key).as_bytes())
+            .await?;
+    }
+    socket.send(key.as_bytes()).await?;
+    socket
+        .expect(expected.replace("{key}", &key).as_bytes())
+        .await
+}
+
+async fn version(target: Target) -> Result<(), anyhow::Error> {
+    let socket = target.udp().await?;
+    socket.send(b"version").await?;
+    let response = socket.recv().await?;
+    ensure!(
+        response.starts_with(b"version=") && response.len() > b"version=".len(),
+        "got {:?}, expected a version",
+        String::from_utf8_lossy(&response)
+    );
+    Ok(())
+}
+
+async fn read_only_version(target: Target) -> Result<(), anyhow::Error> {
+    let socket = target.udp().await?;
+    socket.send(b"version").await?;
+    let version = socket.recv().await?;
+
+    socket.send(b"version=hacked").await?;
+    socket.send(b"version").await?;
+    socket.expect(&version).await
+}
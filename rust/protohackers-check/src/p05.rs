@@ -0,0 +1,103 @@
+//! Mob in the Middle: a Budget Chat proxy rewriting the Boguscoin
+//! addresses to Tony's, checked through the proxy only.
+use anyhow::ensure;
+
+use crate::client::{unique, Lines};
+use crate::{Kind, Scenario, Target};
+
+/// Tony's address.
+pub const TONY: &str = "7YWHMfk9JZe0LM0g1ZauHuiSxhI";
+
+#[must_use]
+pub fn scenarios() -> Vec<Scenario> {
+    vec![
+        Scenario::new(Kind::Valid, "welcome relayed", |target| {
+            Box::pin(async move {
+                let mut lines = target.lines().await?;
+                lines.recv().await?;
+                Ok(())
+            })
+        }),
+        Scenario::new(Kind::Valid, "address rewritten", |target| {
+            Box::pin(relay(
+                target,
+                "Please send the payment to 7iKDZEwPZSqIvDnHvVN2r0hUWXD5rHX",
+                "Please send the payment to {TONY}",
+            ))
+        }),
+        Scenario::new(Kind::Valid, "many addresses", |target| {
+            Box::pin(relay(
+                target,
+                "7F1u3wSD5RbOHQmupo9nx4TnhQ or 7LOrwbDlS8NujgjddyogWgIM93MV5N2VR",
+                "{TONY} or {TONY}",
+            ))
+        }),
+        Scenario::new(Kind::Valid, "other messages untouched", |target| {
+            Box::pin(relay(target, "hi, how are you?", "hi, how are you?"))
+        }),
+        Scenario::new(Kind::EdgeCase, "too long", |target| {
+            Box::pin(relay(
+                target,
+                "7adNeSwJkMakpEcln9HEtthSRtxdmEHOT8T8",
+                "7adNeSwJkMakpEcln9HEtthSRtxdmEHOT8T8",
+            ))
+        }),
+        Scenario::new(Kind::EdgeCase, "too short", |target| {
+            Box::pin(relay(
+                target,
+                "7F1u3wSD5RbOHQmupo9nx4Tnh",
+                "7F1u3wSD5RbOHQmupo9nx4Tnh",
+            ))
+        }),
+        Scenario::new(Kind::EdgeCase, "not delimited by spaces", |target| {
+            Box::pin(relay(
+                target,
+                "This is a product ID, not a Boguscoin: 7YbV4CiXbZb5zxyq2PjzHOosKfE2CZa-1234",
+                "This is a product ID, not a Boguscoin: 7YbV4CiXbZb5zxyq2PjzHOosKfE2CZa-1234",
+            ))
+        }),
+    ]
+}
+
+async fn join(target: &Target, name: &str) -> Result<Lines, anyhow::Error> {
+    let mut lines = target.lines().await?;
+    lines.recv().await?;
+    lines.send(name).await?;
+    lines.recv().await?;
+    Ok(lines)
+}
+
+/// Send `message` from a client to another one, through the proxy in
+/// both directions, expecting `expected`, with `{TONY}` replaced by
+/// Tony's address.
+async fn relay(target: Target, message: &str, expected: &str) -> Result<(), anyhow::Error> {
+    let (alice_name, bob_name) = (
+        format!("alice{}", unique() % 100_000),
+        format!("bob{}", unique() % 100_000),
+    );
+
+    let mut alice = join(&target, &alice_name).await?;
+    let mut bob = join(&target, &bob_name).await?;
+
+    // bob is in the room once alice knows
+    loop {
+        let line = alice.recv().await?;
+        if line.starts_with('*') && line.contains(&bob_name) {
+            break;
+        }
+    }
+
+    bob.send(message).await?;
+    let expected = expected.replace("{TONY}", TONY);
+    let prefix = format!("[{bob_name}] ");
+    loop {
+        let line = alice.recv().await?;
+        if let Some(received) = line.strip_prefix(&prefix) {
+            ensure!(
+                received == expected,
+                "got {received:?}, expected {expected:?}"
+            );
+            return Ok(());
+        }
+    }
+}
@@ -0,0 +1,213 @@
+//! Speed Daemon: binary messages between cameras, dispatchers and
+//! the server, tickets for the average speeds over the limits.
+use anyhow::{ensure, Context};
+
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+
+use crate::client::{expect_silence, unique};
+use crate::{Kind, Scenario, Target};
+
+const ERROR: u8 = 0x10;
+const PLATE: u8 = 0x20;
+const TICKET: u8 = 0x21;
+const WANT_HEARTBEAT: u8 = 0x40;
+const HEARTBEAT: u8 = 0x41;
+const I_AM_CAMERA: u8 = 0x80;
+const I_AM_DISPATCHER: u8 = 0x81;
+
+#[must_use]
+pub fn scenarios() -> Vec<Scenario> {
+    vec![
+        Scenario::new(Kind::Valid, "ticket", |target| {
+            Box::pin(ticket(target, false))
+        }),
+        Scenario::new(Kind::Valid, "ticket to a waiting dispatcher", |target| {
+            Box::pin(ticket(target, true))
+        }),
+        Scenario::new(Kind::Valid, "heartbeat", |target| {
+            Box::pin(heartbeat(target))
+        }),
+        Scenario::new(Kind::EdgeCase, "no ticket at the limit", |target| {
+            Box::pin(at_the_limit(target))
+        }),
+        Scenario::new(Kind::Malformed, "unknown message type", |target| {
+            Box::pin(error(target, vec![0xff]))
+        }),
+        Scenario::new(
+            Kind::Malformed,
+            "plate from an unidentified client",
+            |target| Box::pin(error(target, plate("UN1X", 0))),
+        ),
+        Scenario::new(Kind::Malformed, "identified twice", |target| {
+            Box::pin(error(
+                target,
+                [camera(1, 1, 60), i_am_dispatcher(&[1])].concat(),
+            ))
+        }),
+        Scenario::new(Kind::Malformed, "heartbeat requested twice", |target| {
+            Box::pin(error(
+                target,
+                [want_heartbeat(0), want_heartbeat(0)].concat(),
+            ))
+        }),
+    ]
+}
+
+fn string(message: &mut Vec<u8>, value: &str) {
+    message.push(u8::try_from(value.len()).expect("short string"));
+    message.extend_from_slice(value.as_bytes());
+}
+
+fn plate(plate: &str, timestamp: u32) -> Vec<u8> {
+    let mut message = vec![PLATE];
+    string(&mut message, plate);
+    message.extend_from_slice(&timestamp.to_be_bytes());
+    message
+}
+
+fn want_heartbeat(interval: u32) -> Vec<u8> {
+    let mut message = vec![WANT_HEARTBEAT];
+    message.extend_from_slice(&interval.to_be_bytes());
+    message
+}
+
+fn camera(road: u16, mile: u16, limit: u16) -> Vec<u8> {
+    let mut message = vec![I_AM_CAMERA];
+    for value in [road, mile, limit] {
+        message.extend_from_slice(&value.to_be_bytes());
+    }
+    message
+}
+
+fn i_am_dispatcher(roads: &[u16]) -> Vec<u8> {
+    let mut message = vec![
+        I_AM_DISPATCHER,
+        u8::try_from(roads.len()).expect("few roads"),
+    ];
+    for road in roads {
+        message.extend_from_slice(&road.to_be_bytes());
+    }
+    message
+}
+
+async fn observe(
+    target: &Target,
+    road: u16,
+    mile: u16,
+    observed: &str,
+    timestamp: u32,
+) -> Result<TcpStream, anyhow::Error> {
+    let mut stream = target.connect().await?;
+    stream
+        .write_all(&[camera(road, mile, 60), plate(observed, timestamp)].concat())
+        .await?;
+    Ok(stream)
+}
+
+async fn dispatcher(target: &Target, road: u16) -> Result<TcpStream, anyhow::Error> {
+    let mut stream = target.connect().await?;
+    stream.write_all(&i_am_dispatcher(&[road])).await?;
+    Ok(stream)
+}
+
+async fn read_string(target: &Target, stream: &mut TcpStream) -> Result<String, anyhow::Error> {
+    let mut len = [0];
+    target.read_exact(stream, &mut len).await?;
+    let mut value = vec![0; usize::from(len[0])];
+    target.read_exact(stream, &mut value).await?;
+    Ok(String::from_utf8_lossy(&value).into_owned())
+}
+
+async fn expect_tag(target: &Target, stream: &mut TcpStream, tag: u8) -> Result<(), anyhow::Error> {
+    let mut received = [0];
+    target.read_exact(stream, &mut received).await?;
+    ensure!(
+        received[0] == tag,
+        "got message 0x{:02x}, expected 0x{tag:02x}",
+        received[0]
+    );
+    Ok(())
+}
+
+/// The plate, road, miles, timestamps and speed of the next ticket.
+async fn read_ticket(
+    target: &Target,
+    stream: &mut TcpStream,
+) -> Result<(String, [u32; 6]), anyhow::Error> {
+    expect_tag(target, stream, TICKET).await?;
+    let plate = read_string(target, stream).await?;
+    let mut fields = [0; 16];
+    target.read_exact(stream, &mut fields).await?;
+
+    let u16_at = |i: usize| u32::from(u16::from_be_bytes([fields[i], fields[i + 1]]));
+    let u32_at =
+        |i: usize| u32::from_be_bytes([fields[i], fields[i + 1], fields[i + 2], fields[i + 3]]);
+    Ok((
+        plate,
+        [
+            u16_at(0),
+            u16_at(2),
+            u32_at(4),
+            u16_at(8),
+            u32_at(10),
+            u16_at(14),
+        ],
+    ))
+}
+
+/// The specification example, on a road of its own.
+async fn ticket(target: Target, dispatcher_first: bool) -> Result<(), anyhow::Error> {
+    let road = u16::try_from(unique() % 65_536)?;
+    let observed = format!("UN{}X", unique() % 100_000);
+
+    let mut waiting = if dispatcher_first {
+        Some(dispatcher(&target, road).await?)
+    } else {
+        None
+    };
+    let _first = observe(&target, road, 8, &observed, 0).await?;
+    let _second = observe(&target, road, 9, &observed, 45).await?;
+    let mut dispatcher = match waiting.take() {
+        Some(dispatcher) => dispatcher,
+        None => dispatcher(&target, road).await?,
+    };
+
+    let ticket = read_ticket(&target, &mut dispatcher)
+        .await
+        .context("reading the ticket")?;
+    let expected = (observed, [u32::from(road), 8, 0, 9, 45, 8000]);
+    ensure!(ticket == expected, "got {ticket:?}, expected {expected:?}");
+    Ok(())
+}
+
+async fn at_the_limit(target: Target) -> Result<(), anyhow::Error> {
+    let road = u16::try_from(unique() % 65_536)?;
+    let observed = format!("LI{}M", unique() % 100_000);
+
+    let _first = observe(&target, road, 8, &observed, 0).await?;
+    let _second = observe(&target, road, 9, &observed, 60).await?;
+    let mut dispatcher = dispatcher(&target, road).await?;
+
+    expect_silence(&mut dispatcher).await
+}
+
+async fn heartbeat(target: Target) -> Result<(), anyhow::Error> {
+    let mut stream = target.connect().await?;
+    // every 100 ms
+    stream.write_all(&want_heartbeat(1)).await?;
+    for _ in 0..3 {
+        expect_tag(&target, &mut stream, HEARTBEAT).await?;
+    }
+    Ok(())
+}
+
+/// `messages` get an error message, then the close.
+async fn error(target: Target, messages: Vec<u8>) -> Result<(), anyhow::Error> {
+    let mut stream = target.connect().await?;
+    stream.write_all(&messages).await?;
+
+    expect_tag(&target, &mut stream, ERROR).await?;
+    read_string(&target, &mut stream).await?;
+    target.expect_closed(&mut stream).await
+}
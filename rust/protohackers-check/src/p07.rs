@@ -0,0 +1,175 @@
+//! Line Reversal: the LRCP sessions over UDP, every line reversed.
+use anyhow::ensure;
+
+use crate::client::{unique, Udp};
+use crate::{Kind, Scenario, Target};
+
+#[must_use]
+pub fn scenarios() -> Vec<Scenario> {
+    vec![
+        Scenario::new(Kind::Valid, "connect", |target| {
+            Box::pin(async move { connect(&target.udp().await?, session()).await })
+        }),
+        Scenario::new(Kind::Valid, "reverse a line", |target| {
+            Box::pin(reverse(target, &["hello\n"], "olleh\n"))
+        }),
+        Scenario::new(Kind::Valid, "line over many packets", |target| {
+            Box::pin(reverse(
+                target,
+                &["hel", "lo, wor", "ld\n"],
+                "dlrow ,olleh\n",
+            ))
+        }),
+        Scenario::new(Kind::Valid, "close", |target| Box::pin(close(target))),
+        Scenario::new(Kind::Valid, "retransmission", |target| {
+            Box::pin(retransmission(target))
+        }),
+        Scenario::new(Kind::EdgeCase, "escaped slashes", |target| {
+            Box::pin(reverse(
+                target,
+                &[r"foo\/bar\\baz", "\n"],
+                "zab\\\\rab\\/oof\n",
+            ))
+        }),
+        Scenario::new(Kind::EdgeCase, "duplicate connect", |target| {
+            Box::pin(async move {
+                let socket = target.udp().await?;
+                let session = session();
+                connect(&socket, session).await?;
+                connect(&socket, session).await
+            })
+        }),
+        Scenario::new(Kind::Malformed, "data without a session", |target| {
+            Box::pin(async move {
+                let socket = target.udp().await?;
+                let session = session();
+                socket
+                    .send(format!("/data/{session}/0/hello\n/").as_bytes())
+                    .await?;
+                socket.expect(format!("/close/{session}/").as_bytes()).await
+            })
+        }),
+        Scenario::new(Kind::Malformed, "illegal packets ignored", |target| {
+            Box::pin(illegal(target))
+        }),
+    ]
+}
+
+/// A session number unique to the run.
+fn session() -> u32 {
+    unique() % 2_147_483_648
+}
+
+async fn connect(socket: &Udp, session: u32) -> Result<(), anyhow::Error> {
+    socket
+        .send(format!("/connect/{session}/").as_bytes())
+        .await?;
+    socket.expect(format!("/ack/{session}/0/").as_bytes()).await
+}
+
+/// The next packet other than `skip`, e.g. the acks of the data
+/// already acknowledged.
+async fn recv_other(socket: &Udp, skip: &str) -> Result<String, anyhow::Error> {
+    loop {
+        let packet = String::from_utf8(socket.recv().await?)?;
+        if packet != skip {
+            return Ok(packet);
+        }
+    }
+}
+
+/// Send `chunks` of escaped data and expect the escaped `reversed`
+/// line back.
+async fn reverse(target: Target, chunks: &[&str], reversed: &str) -> Result<(), anyhow::Error> {
+    let socket = target.udp().await?;
+    let session = session();
+    connect(&socket, session).await?;
+
+    let mut position = 0;
+    for chunk in chunks {
+        socket
+            .send(format!("/data/{session}/{position}/{chunk}/").as_bytes())
+            .await?;
+        // the escapes are not part of the stream
+        position += chunk.replace(r"\\", "_").replace(r"\/", "_").len();
+        let ack = format!("/ack/{session}/{position}/");
+        let packet = recv_other(&socket, "").await?;
+        // the reversed line can come before the last ack
+        if packet != ack {
+            let data = format!("/data/{session}/0/{reversed}/");
+            ensure!(
+                packet == data,
+                "got {packet:?}, expected {ack:?} or {data:?}"
+            );
+            socket
+                .send(format!("/ack/{session}/{position}/").as_bytes())
+                .await?;
+            let packet = recv_other(&socket, &data).await?;
+            ensure!(packet == ack, "got {packet:?}, expected {ack:?}");
+            return Ok(());
+        }
+    }
+
+    let data = format!("/data/{session}/0/{reversed}/");
+    let packet = recv_other(&socket, "").await?;
+    ensure!(packet == data, "got {packet:?}, expected {data:?}");
+    socket
+        .send(format!("/ack/{session}/{position}/").as_bytes())
+        .await
+}
+
+async fn close(target: Target) -> Result<(), anyhow::Error> {
+    let socket = target.udp().await?;
+    let session = session();
+    connect(&socket, session).await?;
+
+    socket.send(format!("/close/{session}/").as_bytes()).await?;
+    socket.expect(format!("/close/{session}/").as_bytes()).await
+}
+
+/// The data not acknowledged is sent again.
+async fn retransmission(target: Target) -> Result<(), anyhow::Error> {
+    let socket = target.udp().await?;
+    let session = session();
+    connect(&socket, session).await?;
+
+    socket
+        .send(format!("/data/{session}/0/abc\n/").as_bytes())
+        .await?;
+    let (ack, data) = (
+        format!("/ack/{session}/4/"),
+        format!("/data/{session}/0/cba\n/"),
+    );
+    let mut received = 0;
+    while received < 2 {
+        let packet = recv_other(&socket, &ack).await?;
+        ensure!(packet == data, "got {packet:?}, expected {data:?}");
+        received += 1;
+    }
+    socket.send(format!("/ack/{session}/4/").as_bytes()).await
+}
+
+async fn illegal(target: Target) -> Result<(), anyhow::Error> {
+    let socket = target.udp().await?;
+    let session = session();
+    connect(&socket, session).await?;
+
+    for packet in [
+        "hello".to_string(),
+        "/connect/".to_string(),
+        format!("/connect/{session}"),
+        "/connect/2147483648/".to_string(),
+        format!("/ack/{session}/x/"),
+        format!("/data/{session}/0/a/b/"),
+        format!("/data/{session}/0/{}/", "x".repeat(1000)),
+    ] {
+        socket.send(packet.as_bytes()).await?;
+    }
+    socket.expect_silence().await?;
+
+    // the session goes on
+    socket
+        .send(format!("/data/{session}/0/ok\n/").as_bytes())
+        .await?;
+    socket.expect(format!("/ack/{session}/3/").as_bytes()).await
+}
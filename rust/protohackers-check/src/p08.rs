@@ -0,0 +1,180 @@
+//! Insecure Sockets Layer: a cipher spec, then toy requests lines
+//! obfuscated by the cipher, the positions counted per direction.
+use anyhow::{bail, ensure};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::{Kind, Scenario, Target};
+
+#[derive(Debug, Clone, Copy)]
+enum Operation {
+    ReverseBits,
+    Xor(u8),
+    XorPos,
+    Add(u8),
+    AddPos,
+}
+
+#[must_use]
+pub fn scenarios() -> Vec<Scenario> {
+    vec![
+        Scenario::new(Kind::Valid, "xor and reverse bits", |target| {
+            Box::pin(session(
+                target,
+                &[0x02, 0x01, 0x01, 0x00],
+                &[("4x dog,5x car\n", "5x car\n")],
+            ))
+        }),
+        Scenario::new(Kind::Valid, "add position twice", |target| {
+            Box::pin(session(
+                target,
+                &[0x05, 0x05, 0x00],
+                &[
+                    ("4x dog,5x car\n", "5x car\n"),
+                    ("3x rat,2x cat\n", "3x rat\n"),
+                ],
+            ))
+        }),
+        Scenario::new(Kind::Valid, "all the operations", |target| {
+            Box::pin(session(
+                target,
+                &[0x01, 0x02, 0x7b, 0x03, 0x04, 0x2a, 0x05, 0x00],
+                &[
+                    (
+                        "10x toy car,15x dog on a string,4x inflatable motorcycle\n",
+                        "15x dog on a string\n",
+                    ),
+                    ("1x bike\n", "1x bike\n"),
+                ],
+            ))
+        }),
+        Scenario::new(Kind::EdgeCase, "many requests in a write", |target| {
+            Box::pin(session(
+                target,
+                &[0x03, 0x00],
+                &[("1x a,2x b\n2x c,1x d\n", "2x b\n"), ("", "2x c\n")],
+            ))
+        }),
+        Scenario::new(Kind::Malformed, "empty cipher", |target| {
+            Box::pin(no_op(target, &[0x00]))
+        }),
+        Scenario::new(Kind::Malformed, "xor with zero", |target| {
+            Box::pin(no_op(target, &[0x02, 0x00, 0x00]))
+        }),
+        Scenario::new(Kind::Malformed, "reverse bits twice", |target| {
+            Box::pin(no_op(target, &[0x01, 0x01, 0x00]))
+        }),
+    ]
+}
+
+fn parse(mut spec: &[u8]) -> Result<Vec<Operation>, anyhow::Error> {
+    let mut operations = vec![];
+    loop {
+        let operation = match spec {
+            [0x00, ..] => return Ok(operations),
+            [0x01, rest @ ..] => {
+                spec = rest;
+                Operation::ReverseBits
+            }
+            [0x02, n, rest @ ..] => {
+                spec = rest;
+                Operation::Xor(*n)
+            }
+            [0x03, rest @ ..] => {
+                spec = rest;
+                Operation::XorPos
+            }
+            [0x04, n, rest @ ..] => {
+                spec = rest;
+                Operation::Add(*n)
+            }
+            [0x05, rest @ ..] => {
+                spec = rest;
+                Operation::AddPos
+            }
+            _ => bail!("invalid cipher spec"),
+        };
+        operations.push(operation);
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn encode(operations: &[Operation], byte: u8, position: usize) -> u8 {
+    operations
+        .iter()
+        .fold(byte, |byte, operation| match operation {
+            Operation::ReverseBits => byte.reverse_bits(),
+            Operation::Xor(n) => byte ^ n,
+            Operation::XorPos => byte ^ position as u8,
+            Operation::Add(n) => byte.wrapping_add(*n),
+            Operation::AddPos => byte.wrapping_add(position as u8),
+        })
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn decode(operations: &[Operation], byte: u8, position: usize) -> u8 {
+    operations
+        .iter()
+        .rev()
+        .fold(byte, |byte, operation| match operation {
+            Operation::ReverseBits => byte.reverse_bits(),
+            Operation::Xor(n) => byte ^ n,
+            Operation::XorPos => byte ^ position as u8,
+            Operation::Add(n) => byte.wrapping_sub(*n),
+            Operation::AddPos => byte.wrapping_sub(position as u8),
+        })
+}
+
+/// Send the cipher `spec`, then every request expecting its
+/// response.
+async fn session(
+    target: Target,
+    spec: &[u8],
+    exchanges: &[(&str, &str)],
+) -> Result<(), anyhow::Error> {
+    let operations = parse(spec)?;
+    let mut stream = target.connect().await?;
+    stream.write_all(spec).await?;
+
+    let (mut sent, mut received) = (0, 0);
+    for (request, response) in exchanges {
+        let encoded = request
+            .bytes()
+            .enumerate()
+            .map(|(i, byte)| encode(&operations, byte, sent + i))
+            .collect::<Vec<_>>();
+        stream.write_all(&encoded).await?;
+        sent += encoded.len();
+
+        let mut line = vec![];
+        while line.last() != Some(&b'\n') {
+            let mut byte = [0];
+            target.read_exact(&mut stream, &mut byte).await?;
+            line.push(decode(&operations, byte[0], received));
+            received += 1;
+        }
+        let line = String::from_utf8_lossy(&line);
+        ensure!(line == *response, "got {line:?}, expected {response:?}");
+    }
+    Ok(())
+}
+
+/// A cipher leaving every byte unchanged closes the connection.
+async fn no_op(target: Target, spec: &[u8]) -> Result<(), anyhow::Error> {
+    let mut stream: TcpStream = target.connect().await?;
+    stream.write_all(spec).await?;
+    stream.write_all(b"1x toy\n").await?;
+
+    let mut data = vec![];
+    target
+        .within("the close", stream.read_to_end(&mut data))
+        .await?
+        .ok();
+    ensure!(
+        data.is_empty(),
+        "got {} bytes, expected the close",
+        data.len()
+    );
+    Ok(())
+}
@@ -0,0 +1,198 @@
+//! Job Centre: JSON requests of a job queue, one per line.
+use anyhow::{anyhow, ensure};
+
+use serde_json::{json, Value};
+
+use crate::client::{unique, Lines};
+use crate::{Kind, Scenario, Target};
+
+#[must_use]
+pub fn scenarios() -> Vec<Scenario> {
+    vec![
+        Scenario::new(Kind::Valid, "put and get", |target| {
+            Box::pin(put_get(target))
+        }),
+        Scenario::new(Kind::Valid, "highest priority first", |target| {
+            Box::pin(priority(target))
+        }),
+        Scenario::new(Kind::Valid, "delete", |target| Box::pin(delete(target))),
+        Scenario::new(Kind::Valid, "abort", |target| {
+            Box::pin(abort(target, false))
+        }),
+        Scenario::new(Kind::Valid, "abort on disconnect", |target| {
+            Box::pin(abort(target, true))
+        }),
+        Scenario::new(Kind::Valid, "wait", |target| Box::pin(wait(target))),
+        Scenario::new(Kind::EdgeCase, "no job", |target| {
+            Box::pin(async move {
+                let mut lines = target.lines().await?;
+                let response = request(&mut lines, &get(&queue(), false)).await?;
+                expect_status(&response, "no-job")
+            })
+        }),
+        Scenario::new(Kind::Malformed, "invalid JSON", |target| {
+            Box::pin(error(target, "{\"request\":".to_string()))
+        }),
+        Scenario::new(Kind::Malformed, "unknown request", |target| {
+            Box::pin(error(target, json!({"request": "steal"}).to_string()))
+        }),
+        Scenario::new(Kind::Malformed, "negative priority", |target| {
+            Box::pin(error(
+                target,
+                json!({"request": "put", "queue": queue(), "job": {}, "pri": -1}).to_string(),
+            ))
+        }),
+        Scenario::new(Kind::Malformed, "abort a job of another client", |target| {
+            Box::pin(abort_other(target))
+        }),
+    ]
+}
+
+/// A queue name unique to the run.
+fn queue() -> String {
+    format!("check-{}", unique())
+}
+
+fn put(queue: &str, job: &Value, pri: u64) -> Value {
+    json!({"request": "put", "queue": queue, "job": job, "pri": pri})
+}
+
+fn get(queue: &str, wait: bool) -> Value {
+    json!({"request": "get", "queues": [queue], "wait": wait})
+}
+
+async fn request(lines: &mut Lines, request: &Value) -> Result<Value, anyhow::Error> {
+    lines.send(&request.to_string()).await?;
+    response(lines).await
+}
+
+async fn response(lines: &mut Lines) -> Result<Value, anyhow::Error> {
+    let line = lines.recv().await?;
+    serde_json::from_str(&line).map_err(|err| anyhow!("malformed response {line:?}: {err}"))
+}
+
+fn expect_status(response: &Value, status: &str) -> Result<(), anyhow::Error> {
+    ensure!(
+        response.get("status") == Some(&Value::from(status)),
+        "got {response}, expected status {status:?}"
+    );
+    Ok(())
+}
+
+/// Put a job, returning its id.
+async fn put_job(
+    lines: &mut Lines,
+    queue: &str,
+    job: &Value,
+    pri: u64,
+) -> Result<u64, anyhow::Error> {
+    let response = request(lines, &put(queue, job, pri)).await?;
+    expect_status(&response, "ok")?;
+    response
+        .get("id")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| anyhow!("missing id in {response}"))
+}
+
+fn expect_job(
+    response: &Value,
+    id: u64,
+    queue: &str,
+    job: &Value,
+    pri: u64,
+) -> Result<(), anyhow::Error> {
+    let expected = json!({"status": "ok", "id": id, "job": job, "pri": pri, "queue": queue});
+    ensure!(*response == expected, "got {response}, expected {expected}");
+    Ok(())
+}
+
+async fn put_get(target: Target) -> Result<(), anyhow::Error> {
+    let (queue, job) = (queue(), json!({"title": "check", "tags": [1, 2]}));
+    let mut lines = target.lines().await?;
+
+    let id = put_job(&mut lines, &queue, &job, 123).await?;
+    let response = request(&mut lines, &get(&queue, false)).await?;
+    expect_job(&response, id, &queue, &job, 123)
+}
+
+async fn priority(target: Target) -> Result<(), anyhow::Error> {
+    let queue = queue();
+    let mut lines = target.lines().await?;
+
+    put_job(&mut lines, &queue, &json!({"low": true}), 1).await?;
+    let id = put_job(&mut lines, &queue, &json!({"high": true}), 10).await?;
+    let response = request(&mut lines, &get(&queue, false)).await?;
+    expect_job(&response, id, &queue, &json!({"high": true}), 10)
+}
+
+async fn delete(target: Target) -> Result<(), anyhow::Error> {
+    let queue = queue();
+    let mut lines = target.lines().await?;
+
+    let id = put_job(&mut lines, &queue, &json!({}), 1).await?;
+    let delete = json!({"request": "delete", "id": id});
+    expect_status(&request(&mut lines, &delete).await?, "ok")?;
+    expect_status(&request(&mut lines, &get(&queue, false)).await?, "no-job")?;
+    expect_status(&request(&mut lines, &delete).await?, "no-job")
+}
+
+/// A job given back, explicitly or disconnecting, goes to the next
+/// client.
+async fn abort(target: Target, disconnect: bool) -> Result<(), anyhow::Error> {
+    let (queue, job) = (queue(), json!({"title": "abort"}));
+    let mut first = target.lines().await?;
+
+    let id = put_job(&mut first, &queue, &job, 5).await?;
+    expect_job(
+        &request(&mut first, &get(&queue, false)).await?,
+        id,
+        &queue,
+        &job,
+        5,
+    )?;
+    if disconnect {
+        drop(first);
+    } else {
+        let abort = json!({"request": "abort", "id": id});
+        expect_status(&request(&mut first, &abort).await?, "ok")?;
+    }
+
+    let mut second = target.lines().await?;
+    let response = request(&mut second, &get(&queue, true)).await?;
+    expect_job(&response, id, &queue, &job, 5)
+}
+
+async fn wait(target: Target) -> Result<(), anyhow::Error> {
+    let (queue, job) = (queue(), json!({"title": "wait"}));
+
+    let mut waiting = target.lines().await?;
+    waiting.send(&get(&queue, true).to_string()).await?;
+    waiting.expect_silence().await?;
+
+    let mut lines = target.lines().await?;
+    let id = put_job(&mut lines, &queue, &job, 7).await?;
+    expect_job(&response(&mut waiting).await?, id, &queue, &job, 7)
+}
+
+/// An invalid request gets an error response, the connection goes
+/// on.
+async fn error(target: Target, invalid: String) -> Result<(), anyhow::Error> {
+    let mut lines = target.lines().await?;
+    lines.send(&invalid).await?;
+    expect_status(&response(&mut lines).await?, "error")?;
+
+    put_job(&mut lines, &queue(), &json!({}), 1).await?;
+    Ok(())
+}
+
+async fn abort_other(target: Target) -> Result<(), anyhow::Error> {
+    let queue = queue();
+    let mut first = target.lines().await?;
+    let mut second = target.lines().await?;
+
+    let id = put_job(&mut first, &queue, &json!({}), 1).await?;
+    request(&mut first, &get(&queue, false)).await?;
+
+    let abort = json!({"request": "abort", "id": id});
+    expect_status(&request(&mut second, &abort).await?, "error")
+}
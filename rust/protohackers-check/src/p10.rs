@@ -0,0 +1,180 @@
+//! Voracious Code Storage: a line protocol storing revisions of text
+//! files, `READY` before every command.
+use anyhow::ensure;
+
+use crate::client::{unique, Lines};
+use crate::{Kind, Scenario, Target};
+
+#[must_use]
+pub fn scenarios() -> Vec<Scenario> {
+    vec![
+        Scenario::new(Kind::Valid, "help", |target| {
+            Box::pin(async move {
+                let mut lines = connect(&target).await?;
+                let response = command(&mut lines, "HELP").await?;
+                ensure!(response.starts_with("OK usage: "), "got {response:?}");
+                Ok(())
+            })
+        }),
+        Scenario::new(Kind::Valid, "put and get", |target| {
+            Box::pin(put_get(target))
+        }),
+        Scenario::new(Kind::Valid, "revisions", |target| {
+            Box::pin(revisions(target))
+        }),
+        Scenario::new(Kind::Valid, "list", |target| Box::pin(list(target))),
+        Scenario::new(Kind::EdgeCase, "case insensitive commands", |target| {
+            Box::pin(async move {
+                let mut lines = connect(&target).await?;
+                let response = command(&mut lines, "help").await?;
+                ensure!(response.starts_with("OK "), "got {response:?}");
+                Ok(())
+            })
+        }),
+        Scenario::new(Kind::EdgeCase, "empty file", |target| {
+            Box::pin(async move {
+                let mut lines = connect(&target).await?;
+                let file = format!("{}/empty", dir());
+                expect_ok(&mut lines, &put(&file, ""), "OK r1").await?;
+                lines.expect("READY").await?;
+                expect_ok(&mut lines, &format!("GET {file}"), "OK 0").await
+            })
+        }),
+        Scenario::new(Kind::Malformed, "illegal file name", |target| {
+            Box::pin(error(target, "PUT check.txt 1\na".to_string()))
+        }),
+        Scenario::new(Kind::Malformed, "binary data", |target| {
+            Box::pin(error(target, format!("PUT {}/binary 1\n\u{1}", dir())))
+        }),
+        Scenario::new(Kind::Malformed, "no such file", |target| {
+            Box::pin(error(target, format!("GET {}/missing", dir())))
+        }),
+        Scenario::new(Kind::Malformed, "no such revision", |target| {
+            Box::pin(async move {
+                let mut lines = connect(&target).await?;
+                let file = format!("{}/file", dir());
+                expect_ok(&mut lines, &put(&file, "a\n"), "OK r1").await?;
+                lines.expect("READY").await?;
+                let response = command(&mut lines, &format!("GET {file} r2")).await?;
+                ensure!(response.starts_with("ERR "), "got {response:?}");
+                Ok(())
+            })
+        }),
+        Scenario::new(Kind::Malformed, "illegal method", |target| {
+            Box::pin(async move {
+                let mut lines = connect(&target).await?;
+                lines.send("STEAL").await?;
+                lines.expect("ERR illegal method: STEAL").await
+            })
+        }),
+    ]
+}
+
+/// A directory unique to the run.
+fn dir() -> String {
+    format!("/check-{}", unique())
+}
+
+fn put(file: &str, data: &str) -> String {
+    format!("PUT {file} {}\n{data}", data.len())
+}
+
+async fn connect(target: &Target) -> Result<Lines, anyhow::Error> {
+    let mut lines = target.lines().await?;
+    lines.expect("READY").await?;
+    Ok(lines)
+}
+
+/// Send `command`, returning the first line of the response; a
+/// command with a newline is a `PUT` with its data, sent verbatim.
+async fn command(lines: &mut Lines, command: &str) -> Result<String, anyhow::Error> {
+    if command.contains('\n') {
+        lines.send_raw(command.as_bytes()).await?;
+    } else {
+        lines.send(command).await?;
+    }
+    lines.recv().await
+}
+
+async fn expect_ok(lines: &mut Lines, request: &str, expected: &str) -> Result<(), anyhow::Error> {
+    let response = command(lines, request).await?;
+    ensure!(
+        response == expected,
+        "got {response:?}, expected {expected:?}"
+    );
+    Ok(())
+}
+
+/// The data of a `GET`, after its `OK` and before the `READY`.
+async fn expect_data(lines: &mut Lines, data: &str) -> Result<(), anyhow::Error> {
+    for line in data.lines() {
+        lines.expect(line).await?;
+    }
+    lines.expect("READY").await
+}
+
+async fn put_get(target: Target) -> Result<(), anyhow::Error> {
+    let mut lines = connect(&target).await?;
+    let (file, data) = (format!("{}/file.txt", dir()), "hello\nworld\n");
+
+    expect_ok(&mut lines, &put(&file, data), "OK r1").await?;
+    lines.expect("READY").await?;
+
+    expect_ok(
+        &mut lines,
+        &format!("GET {file}"),
+        &format!("OK {}", data.len()),
+    )
+    .await?;
+    expect_data(&mut lines, data).await
+}
+
+async fn revisions(target: Target) -> Result<(), anyhow::Error> {
+    let mut lines = connect(&target).await?;
+    let file = format!("{}/file.txt", dir());
+
+    expect_ok(&mut lines, &put(&file, "one\n"), "OK r1").await?;
+    lines.expect("READY").await?;
+    expect_ok(&mut lines, &put(&file, "two\n"), "OK r2").await?;
+    lines.expect("READY").await?;
+    // the same data is not a new revision
+    expect_ok(&mut lines, &put(&file, "two\n"), "OK r2").await?;
+    lines.expect("READY").await?;
+
+    expect_ok(&mut lines, &format!("GET {file} r1"), "OK 4").await?;
+    expect_data(&mut lines, "one\n").await?;
+    expect_ok(&mut lines, &format!("GET {file}"), "OK 4").await?;
+    expect_data(&mut lines, "two\n").await
+}
+
+async fn list(target: Target) -> Result<(), anyhow::Error> {
+    let mut lines = connect(&target).await?;
+    let dir = dir();
+
+    expect_ok(&mut lines, &put(&format!("{dir}/file.txt"), "a\n"), "OK r1").await?;
+    lines.expect("READY").await?;
+    expect_ok(
+        &mut lines,
+        &put(&format!("{dir}/sub/file.txt"), "b\n"),
+        "OK r1",
+    )
+    .await?;
+    lines.expect("READY").await?;
+
+    expect_ok(&mut lines, &format!("LIST {dir}/"), "OK 2").await?;
+    let mut entries = vec![lines.recv().await?, lines.recv().await?];
+    entries.sort();
+    ensure!(entries == ["file.txt r1", "sub/ DIR"], "got {entries:?}");
+    lines.expect("READY").await
+}
+
+/// `request` gets an error.
+async fn error(target: Target, request: String) -> Result<(), anyhow::Error> {
+    let mut lines = connect(&target).await?;
+    let response = command(&mut lines, &request).await?;
+    ensure!(
+        response.starts_with("ERR "),
+        "got {response:?}, expected an error"
+    );
+    Ok(())
+}
@@ -0,0 +1,154 @@
+//! Pest Control: checksummed binary messages, from the `Hello`
+//! handshake on; the site visits need an authority server, they are
+//! not checked.
+use anyhow::ensure;
+
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+
+use crate::client::expect_silence;
+use crate::{Kind, Scenario, Target};
+
+const HELLO: u8 = 0x50;
+const ERROR: u8 = 0x51;
+
+const PROTOCOL: &str = "pestcontrol";
+
+#[must_use]
+pub fn scenarios() -> Vec<Scenario> {
+    vec![
+        Scenario::new(Kind::Valid, "hello", |target| {
+            Box::pin(async move {
+                let mut stream = target.connect().await?;
+                stream.write_all(&hello(PROTOCOL, 1)).await?;
+                expect_hello(&target, &mut stream).await?;
+                expect_silence(&mut stream).await
+            })
+        }),
+        Scenario::new(Kind::Valid, "hello from the server first", |target| {
+            Box::pin(async move {
+                let mut stream = target.connect().await?;
+                expect_hello(&target, &mut stream).await
+            })
+        }),
+        Scenario::new(Kind::EdgeCase, "hello split in bytes", |target| {
+            Box::pin(async move {
+                let mut stream = target.connect().await?;
+                for byte in hello(PROTOCOL, 1) {
+                    stream.write_all(&[byte]).await?;
+                    stream.flush().await?;
+                }
+                expect_hello(&target, &mut stream).await?;
+                expect_silence(&mut stream).await
+            })
+        }),
+        Scenario::new(Kind::Malformed, "bad checksum", |target| {
+            Box::pin(async move {
+                let mut message = hello(PROTOCOL, 1);
+                let checksum = message.len() - 1;
+                message[checksum] ^= 0xff;
+                error(target, message).await
+            })
+        }),
+        Scenario::new(Kind::Malformed, "wrong protocol", |target| {
+            Box::pin(error(target, hello("pestcontro1", 1)))
+        }),
+        Scenario::new(Kind::Malformed, "wrong version", |target| {
+            Box::pin(error(target, hello(PROTOCOL, 2)))
+        }),
+        Scenario::new(Kind::Malformed, "unknown message type", |target| {
+            Box::pin(error(
+                target,
+                [hello(PROTOCOL, 1), message(0x99, &[])].concat(),
+            ))
+        }),
+        Scenario::new(
+            Kind::Malformed,
+            "content longer than the length",
+            |target| {
+                Box::pin(async move {
+                    let mut content = string(PROTOCOL);
+                    content.extend_from_slice(&1_u32.to_be_bytes());
+                    content.extend_from_slice(&[0; 4]);
+                    let mut message = message(HELLO, &content);
+                    // the declared length without the extra bytes
+                    let len = u32::try_from(message.len() - 4)?;
+                    message[1..5].copy_from_slice(&len.to_be_bytes());
+                    fix_checksum(&mut message);
+                    error(target, message).await
+                })
+            },
+        ),
+    ]
+}
+
+fn string(value: &str) -> Vec<u8> {
+    let len = u32::try_from(value.len()).expect("short string");
+    [&len.to_be_bytes(), value.as_bytes()].concat()
+}
+
+/// The message of type `kind` with `content`, its length and checksum.
+fn message(kind: u8, content: &[u8]) -> Vec<u8> {
+    let len = u32::try_from(1 + 4 + content.len() + 1).expect("short message");
+    let mut message = [&[kind], &len.to_be_bytes()[..], content, &[0]].concat();
+    fix_checksum(&mut message);
+    message
+}
+
+/// Set the last byte so that the bytes sum to zero.
+fn fix_checksum(message: &mut [u8]) {
+    let (checksum, bytes) = message.split_last_mut().expect("checksum");
+    *checksum = bytes
+        .iter()
+        .fold(0_u8, |sum, byte| sum.wrapping_add(*byte))
+        .wrapping_neg();
+}
+
+fn hello(protocol: &str, version: u32) -> Vec<u8> {
+    let mut content = string(protocol);
+    content.extend_from_slice(&version.to_be_bytes());
+    message(HELLO, &content)
+}
+
+/// The type and the content of the next message.
+async fn read_message(
+    target: &Target,
+    stream: &mut TcpStream,
+) -> Result<(u8, Vec<u8>), anyhow::Error> {
+    let mut header = [0; 5];
+    target.read_exact(stream, &mut header).await?;
+    let len = usize::try_from(u32::from_be_bytes([
+        header[1], header[2], header[3], header[4],
+    ]))?;
+    ensure!((6..=1_000_000).contains(&len), "invalid length {len}");
+
+    let mut rest = vec![0; len - header.len()];
+    target.read_exact(stream, &mut rest).await?;
+    let sum = header
+        .iter()
+        .chain(&rest)
+        .fold(0_u8, |sum, byte| sum.wrapping_add(*byte));
+    ensure!(sum == 0, "invalid checksum of message 0x{:02x}", header[0]);
+
+    rest.pop();
+    Ok((header[0], rest))
+}
+
+async fn expect_hello(target: &Target, stream: &mut TcpStream) -> Result<(), anyhow::Error> {
+    let (kind, content) = read_message(target, stream).await?;
+    ensure!(kind == HELLO, "got message 0x{kind:02x}, expected hello");
+    let expected = [string(PROTOCOL), 1_u32.to_be_bytes().to_vec()].concat();
+    ensure!(content == expected, "unexpected hello content {content:?}");
+    Ok(())
+}
+
+/// `messages`, after the hello of the server, get an error.
+async fn error(target: Target, messages: Vec<u8>) -> Result<(), anyhow::Error> {
+    let mut stream = target.connect().await?;
+    expect_hello(&target, &mut stream).await?;
+    stream.write_all(&messages).await?;
+
+    let (kind, _) = read_message(&target, &mut stream).await?;
+    ensure!(kind == ERROR, "got message 0x{kind:02x}, expected an error");
+    Ok(())
+}
@@ -0,0 +1,138 @@
+//! The scenarios and their report.
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+
+use crate::Target;
+
+type Check = fn(Target) -> Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + Send>>;
+
+/// What a scenario exercises.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// A session of the specification.
+    Valid,
+
+    /// An input the server must refuse.
+    Malformed,
+
+    /// An input at the limits of the specification.
+    EdgeCase,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let kind = match self {
+            Self::Valid => "valid",
+            Self::Malformed => "malformed",
+            Self::EdgeCase => "edge case",
+        };
+        f.pad(kind)
+    }
+}
+
+/// A named check of a server.
+#[derive(Clone, Copy)]
+pub struct Scenario {
+    pub kind: Kind,
+    pub name: &'static str,
+    check: Check,
+}
+
+impl Scenario {
+    #[must_use]
+    pub fn new(kind: Kind, name: &'static str, check: Check) -> Self {
+        Self { kind, name, check }
+    }
+
+    /// Run the check against `target`.
+    ///
+    /// # Errors
+    /// * Error when the server does not behave as specified.
+    pub async fn run(&self, target: Target) -> Result<(), anyhow::Error> {
+        (self.check)(target).await
+    }
+}
+
+impl fmt::Debug for Scenario {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scenario")
+            .field("kind", &self.kind)
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The result of a scenario.
+#[derive(Debug)]
+pub struct Outcome {
+    pub kind: Kind,
+    pub name: &'static str,
+    pub elapsed: Duration,
+    pub result: Result<(), anyhow::Error>,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let status = if self.result.is_ok() { "PASS" } else { "FAIL" };
+        write!(
+            f,
+            "{status} {:<9} {} ({:?})",
+            self.kind, self.name, self.elapsed
+        )?;
+        if let Err(err) = &self.result {
+            write!(f, ": {err:#}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The outcomes of a battery of scenarios.
+#[derive(Debug, Default)]
+pub struct Report(pub Vec<Outcome>);
+
+impl Report {
+    /// Run every scenario against `target`, one after the other.
+    pub async fn run(target: &Target, scenarios: &[Scenario]) -> Self {
+        let mut outcomes = Vec::with_capacity(scenarios.len());
+        for scenario in scenarios {
+            let start = Instant::now();
+            let result = scenario.run(target.clone()).await;
+            outcomes.push(Outcome {
+                kind: scenario.kind,
+                name: scenario.name,
+                elapsed: start.elapsed(),
+                result,
+            });
+        }
+        Self(outcomes)
+    }
+
+    #[must_use]
+    pub fn passed(&self) -> usize {
+        self.0
+            .iter()
+            .filter(|outcome| outcome.result.is_ok())
+            .count()
+    }
+
+    #[must_use]
+    pub fn failed(&self) -> usize {
+        self.0.len() - self.passed()
+    }
+
+    #[must_use]
+    pub fn is_success(&self) -> bool {
+        self.failed() == 0
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        for outcome in &self.0 {
+            writeln!(f, "{outcome}")?;
+        }
+        write!(f, "{} passed, {} failed", self.passed(), self.failed())
+    }
+}
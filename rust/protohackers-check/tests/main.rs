@@ -0,0 +1,143 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::net::{TcpListener, UdpSocket};
+
+use protohackers_check::{p00, p01, p02, p03, p04, p05, p06, p07, p08, p09, p10, p11};
+use protohackers_check::{Report, Scenario, Target};
+
+use protohackers_server::Server;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+async fn listener() -> (TcpListener, String) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    (listener, address)
+}
+
+async fn udp_socket() -> (UdpSocket, String) {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let address = socket.local_addr().unwrap().to_string();
+    (socket, address)
+}
+
+async fn check(address: String, scenarios: &[Scenario]) {
+    let report = Report::run(&Target::new(address, TIMEOUT), scenarios).await;
+    assert!(report.is_success(), "{report}");
+}
+
+#[tokio::test]
+async fn test_p00() {
+    let (listener, address) = listener().await;
+    tokio::spawn(p00_smoke_test::serve(
+        listener,
+        p00_smoke_test::Config::default(),
+        None,
+    ));
+
+    check(address, &p00::scenarios()).await;
+}
+
+#[tokio::test]
+async fn test_p01() {
+    let (listener, address) = listener().await;
+    tokio::spawn(Server::new(listener).serve(|stream, _| p01_prime_time::handler(stream)));
+
+    check(address, &p01::scenarios()).await;
+}
+
+#[tokio::test]
+async fn test_p02() {
+    let (listener, address) = listener().await;
+    tokio::spawn(Server::new(listener).serve(|stream, _| p02_means_to_an_end::handler(stream)));
+
+    check(address, &p02::scenarios()).await;
+}
+
+#[tokio::test]
+async fn test_p03() {
+    let (listener, address) = listener().await;
+    tokio::spawn(p03_budget_chat::run(listener));
+
+    check(address, &p03::scenarios()).await;
+}
+
+#[tokio::test]
+async fn test_p04() {
+    let (socket, address) = udp_socket().await;
+    tokio::spawn(p04_unusual_database_program::run(socket));
+
+    check(address, &p04::scenarios()).await;
+}
+
+#[tokio::test]
+async fn test_p05() {
+    let (chat_listener, chat_address) = listener().await;
+    tokio::spawn(p03_budget_chat::run(chat_listener));
+
+    let (chat_address, chat_port) = chat_address.rsplit_once(':').unwrap();
+    let (listener, address) = listener().await;
+    tokio::spawn(p05_mob_in_the_middle::run(
+        listener,
+        chat_address.to_string(),
+        chat_port.parse().unwrap(),
+        Arc::new(p05_mob_in_the_middle::Rules::boguscoin(
+            p05_mob_in_the_middle::BOGUSCOIN,
+        )),
+    ));
+
+    check(address, &p05::scenarios()).await;
+}
+
+#[tokio::test]
+async fn test_p06() {
+    let (listener, address) = listener().await;
+    tokio::spawn(p06_speed_daemon::run(listener));
+
+    check(address, &p06::scenarios()).await;
+}
+
+#[tokio::test]
+async fn test_p07() {
+    let (socket, address) = udp_socket().await;
+    tokio::spawn(p07_line_reversal::run::<
+        p07_line_reversal::DefaultSocketHandler,
+    >(socket));
+
+    check(address, &p07::scenarios()).await;
+}
+
+#[tokio::test]
+async fn test_p08() {
+    let (listener, address) = listener().await;
+    tokio::spawn(p08_insecure_sockets_layer::run(listener));
+
+    check(address, &p08::scenarios()).await;
+}
+
+#[tokio::test]
+async fn test_p09() {
+    let (listener, address) = listener().await;
+    tokio::spawn(p09_job_centre::run(listener));
+
+    check(address, &p09::scenarios()).await;
+}
+
+#[tokio::test]
+async fn test_p10() {
+    let (listener, address) = listener().await;
+    tokio::spawn(p10_voracious_code_storage::run(listener));
+
+    check(address, &p10::scenarios()).await;
+}
+
+#[tokio::test]
+async fn test_p11() {
+    let (listener, address) = listener().await;
+    // the handshake only, no authority server is dialled
+    let provider = p11_pest_control::DefaultProvider::new("127.0.0.1".to_string(), 1);
+    tokio::spawn(p11_pest_control::run(listener, provider));
+
+    check(address, &p11::scenarios()).await;
+}
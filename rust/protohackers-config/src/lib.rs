@@ -0,0 +1,338 @@
+//! The configuration of the problem binaries: the flags of their
+//! `Args` layered over the environment and a TOML file, so that every
+//! flag of every problem can be set in the same ways.
+//!
+//! A long flag of the problem `p03`, from the highest precedence:
+//!
+//! 1. on the command line, `--max-connections 100`;
+//! 2. by the variable of the problem, `PROTOHACKERS_P03_MAX_CONNECTIONS`;
+//! 3. by the shared variable, `PROTOHACKERS_MAX_CONNECTIONS`;
+//! 4. in the `[p03]` table of the file;
+//! 5. at the top of the file;
+//! 6. by the default of the flag.
+//!
+//! The file is given by `--config` or [`CONFIG_ENV`]:
+//!
+//! ```toml
+//! log-format = "json"
+//! max-connections = 1000
+//!
+//! [p03]
+//! idle-timeout = 3600
+//!
+//! [p10]
+//! storage-directory = "/var/lib/protohackers/p10"
+//! ```
+//!
+//! The keys are the flags without the leading dashes, also with `_`
+//! for `-`; the values are strings, numbers or booleans, arrays of
+//! them for the repeated flags, checked as the flags are. The top of
+//! the file is shared by the problems and its keys unknown to one are
+//! ignored, while they are an error in the table of the problem. A
+//! boolean flag set by the environment or the file can not be unset on
+//! the command line.
+//!
+//! The `Args` of a problem are parsed with [`parse`], the ones of the
+//! launcher, a subcommand per problem, with [`parse_subcommand`]:
+//!
+//! ```no_run
+//! #[derive(clap::Args, Debug)]
+//! struct Args {
+//!     #[arg(long, default_value_t = 10000)]
+//!     port: u16,
+//! }
+//!
+//! let args: Args = protohackers_config::parse(clap::command!(), "p03");
+//! # drop(args);
+//! ```
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::path::PathBuf;
+
+use clap::error::ErrorKind;
+use clap::{Arg, ArgAction, Command};
+
+use toml::{Table, Value};
+
+/// The environment variable with the path of the file.
+pub const CONFIG_ENV: &str = "PROTOHACKERS_CONFIG";
+
+/// The prefix of the environment variables of the flags.
+pub const ENV_PREFIX: &str = "PROTOHACKERS_";
+
+const CONFIG_ARG: &str = "config";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("config {0}: {1}")]
+    Io(String, io::Error),
+
+    #[error("config {0}: {1}")]
+    Toml(String, toml::de::Error),
+
+    #[error("config: unknown key {0}")]
+    UnknownKey(String),
+
+    #[error("config: invalid {1} for {0}")]
+    InvalidValue(String, &'static str),
+
+    #[error(transparent)]
+    Clap(#[from] clap::Error),
+}
+
+/// Parse the `Args` of `problem` from the command line, the
+/// environment and the file, exiting on errors as
+/// [`clap::Parser::parse`].
+#[must_use]
+pub fn parse<T: clap::Args>(command: Command, problem: &str) -> T {
+    try_parse_from(command.clone(), problem, std::env::args_os(), env_vars())
+        .unwrap_or_else(|err| exit(command, err))
+}
+
+/// Parse the `Args` of `problem` from `args`, the first one the
+/// binary name, the variables of `env` and the file.
+///
+/// # Errors
+/// * Error when the file can not be read or has invalid keys or
+///   values, or the flags are invalid.
+pub fn try_parse_from<T, I, A, E>(
+    command: Command,
+    problem: &str,
+    args: I,
+    env: E,
+) -> Result<T, Error>
+where
+    T: clap::Args,
+    I: IntoIterator<Item = A>,
+    A: Into<OsString>,
+    E: IntoIterator<Item = (String, String)>,
+{
+    let args = args.into_iter().map(Into::into).collect::<Vec<_>>();
+    let layers = Layers::load(&args, env.into_iter().collect())?;
+
+    // the flattened `Args` would describe the command
+    let about = command.get_about().cloned();
+    let mut command = T::augment_args(command).long_about(None::<&str>);
+    if let Some(about) = about {
+        command = command.about(about);
+    }
+
+    let command = layers.apply(command, problem)?.arg(config_arg());
+    let matches = command.try_get_matches_from(args)?;
+    Ok(T::from_arg_matches(&matches)?)
+}
+
+/// Parse a subcommand per problem, named after it, from the command
+/// line, the environment and the file, exiting on errors as
+/// [`clap::Parser::parse`].
+#[must_use]
+pub fn parse_subcommand<T: clap::Subcommand>(command: Command) -> T {
+    try_parse_subcommand_from(command.clone(), std::env::args_os(), env_vars())
+        .unwrap_or_else(|err| exit(command, err))
+}
+
+/// Parse a subcommand per problem, named after it, from `args`, the
+/// first one the binary name, the variables of `env` and the file.
+///
+/// # Errors
+/// * Error when the file can not be read or has invalid keys or
+///   values, or the subcommand or its flags are invalid.
+pub fn try_parse_subcommand_from<T, I, A, E>(command: Command, args: I, env: E) -> Result<T, Error>
+where
+    T: clap::Subcommand,
+    I: IntoIterator<Item = A>,
+    A: Into<OsString>,
+    E: IntoIterator<Item = (String, String)>,
+{
+    let args = args.into_iter().map(Into::into).collect::<Vec<_>>();
+    let layers = Layers::load(&args, env.into_iter().collect())?;
+
+    let mut error = None;
+    let command = T::augment_subcommands(command)
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .mut_subcommands(|subcommand| {
+            let problem = subcommand.get_name().to_string();
+            layers
+                .apply(subcommand.clone(), &problem)
+                .unwrap_or_else(|err| {
+                    error.get_or_insert(err);
+                    subcommand
+                })
+        })
+        .arg(config_arg().global(true));
+    if let Some(err) = error {
+        return Err(err);
+    }
+
+    let matches = command.try_get_matches_from(args)?;
+    Ok(T::from_arg_matches(&matches)?)
+}
+
+/// The variables of the environment and the file.
+struct Layers {
+    env: HashMap<String, String>,
+    file: Table,
+}
+
+impl Layers {
+    fn load(args: &[OsString], env: HashMap<String, String>) -> Result<Self, Error> {
+        let path = config_path(args).or_else(|| env.get(CONFIG_ENV).map(PathBuf::from));
+        let file = match path {
+            Some(path) => {
+                let name = path.display().to_string();
+                std::fs::read_to_string(&path)
+                    .map_err(|err| Error::Io(name.clone(), err))?
+                    .parse::<Table>()
+                    .map_err(|err| Error::Toml(name, err))?
+            }
+            None => Table::new(),
+        };
+
+        Ok(Self { env, file })
+    }
+
+    /// `command` with the defaults of the flags of `problem` from the
+    /// environment and the file.
+    fn apply(&self, mut command: Command, problem: &str) -> Result<Command, Error> {
+        let table = match self.file.get(problem) {
+            Some(Value::Table(table)) => Some(table),
+            Some(value) => {
+                return Err(Error::InvalidValue(problem.to_string(), value.type_str()));
+            }
+            None => None,
+        };
+        if let Some(table) = table {
+            if let Some(key) = table.keys().find(|key| {
+                !command
+                    .get_arguments()
+                    .any(|arg| arg.get_long() == Some(&key.replace('_', "-")))
+            }) {
+                return Err(Error::UnknownKey(format!("{problem}.{key}")));
+            }
+        }
+
+        let flags = command
+            .get_arguments()
+            .filter_map(|arg| {
+                let long = arg.get_long()?;
+                let multiple = matches!(arg.get_action(), ArgAction::Append)
+                    || arg
+                        .get_num_args()
+                        .is_some_and(|range| range.max_values() > 1);
+                Some((arg.get_id().to_string(), long.to_string(), multiple))
+            })
+            .collect::<Vec<_>>();
+
+        for (id, long, multiple) in flags {
+            let shared = env_name(None, &long);
+            let values = if let Some(value) = self
+                .env
+                .get(&env_name(Some(problem), &long))
+                .or_else(|| self.env.get(&shared))
+            {
+                Some(vec![value.clone()])
+            } else if let Some(value) = table
+                .and_then(|table| lookup(table, &long))
+                .or_else(|| lookup(&self.file, &long))
+            {
+                Some(values(&long, value, multiple)?)
+            } else {
+                None
+            };
+
+            command = command.mut_arg(id, |mut arg| {
+                // the variable of the flag is a layer already, below the
+                // one of the problem
+                if arg.get_env() == Some(OsStr::new(&shared)) {
+                    arg = arg.env(None::<&str>);
+                }
+                match values {
+                    Some(values) => arg.default_values(values).required(false),
+                    None => arg,
+                }
+            });
+        }
+
+        Ok(command)
+    }
+}
+
+/// The `--config` flag, to list it in the help and accept it.
+fn config_arg() -> Arg {
+    Arg::new(CONFIG_ARG)
+        .long(CONFIG_ARG)
+        .env(CONFIG_ENV)
+        .value_name("FILE")
+        .value_parser(clap::value_parser!(PathBuf))
+        .help("Read the defaults of the flags from this TOML file")
+}
+
+/// The value of `--config` in `args`, before they are parsed.
+fn config_path(args: &[OsString]) -> Option<PathBuf> {
+    let flag = format!("--{CONFIG_ARG}");
+    let mut args = args.iter().skip(1).take_while(|arg| *arg != "--");
+    while let Some(arg) = args.next() {
+        if *arg == *flag {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg
+            .to_str()
+            .and_then(|arg| arg.strip_prefix(&flag)?.strip_prefix('='))
+        {
+            return Some(PathBuf::from(path));
+        }
+    }
+    None
+}
+
+fn env_name(problem: Option<&str>, long: &str) -> String {
+    let problem = problem
+        .map(|problem| format!("{}_", problem.to_uppercase()))
+        .unwrap_or_default();
+    format!(
+        "{ENV_PREFIX}{problem}{}",
+        long.to_uppercase().replace('-', "_")
+    )
+}
+
+fn env_vars() -> impl Iterator<Item = (String, String)> {
+    std::env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+}
+
+fn lookup<'a>(table: &'a Table, long: &str) -> Option<&'a Value> {
+    table
+        .get(long)
+        .or_else(|| table.get(&long.replace('-', "_")))
+}
+
+fn values(long: &str, value: &Value, multiple: bool) -> Result<Vec<String>, Error> {
+    match value {
+        Value::Array(values) if multiple => {
+            values.iter().map(|value| scalar(long, value)).collect()
+        }
+        value => Ok(vec![scalar(long, value)?]),
+    }
+}
+
+fn scalar(long: &str, value: &Value) -> Result<String, Error> {
+    match value {
+        Value::String(value) => Ok(value.clone()),
+        Value::Integer(value) => Ok(value.to_string()),
+        Value::Float(value) => Ok(value.to_string()),
+        Value::Boolean(value) => Ok(value.to_string()),
+        Value::Datetime(value) => Ok(value.to_string()),
+        Value::Array(_) | Value::Table(_) => {
+            Err(Error::InvalidValue(long.to_string(), value.type_str()))
+        }
+    }
+}
+
+fn exit(mut command: Command, err: Error) -> ! {
+    match err {
+        Error::Clap(err) => err.exit(),
+        err => command.error(ErrorKind::InvalidValue, err).exit(),
+    }
+}
@@ -0,0 +1,221 @@
+use std::path::PathBuf;
+
+use clap::Command;
+
+use protohackers_config::Error;
+
+#[derive(clap::Args, Debug)]
+struct Args {
+    #[arg(long, default_value_t = 10000)]
+    port: u16,
+
+    #[arg(long)]
+    max_connections: Option<usize>,
+
+    #[arg(long, value_delimiter = ',', default_value = "::")]
+    address: Vec<String>,
+
+    #[arg(long)]
+    shared: bool,
+
+    #[arg(long)]
+    storage_directory: Option<PathBuf>,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Problem {
+    P03(Args),
+    P10(Args),
+}
+
+/// A temporary TOML file.
+struct File(PathBuf);
+
+impl File {
+    fn new(test: &str, content: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "protohackers-config-{test}-{}.toml",
+            std::process::id()
+        ));
+        std::fs::write(&path, content).unwrap();
+        Self(path)
+    }
+
+    fn path(&self) -> &str {
+        self.0.to_str().unwrap()
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+fn parse(args: &[&str], env: &[(&str, &str)]) -> Result<Args, Error> {
+    protohackers_config::try_parse_from(
+        Command::new("test"),
+        "p03",
+        args,
+        env.iter()
+            .map(|(name, value)| ((*name).to_string(), (*value).to_string())),
+    )
+}
+
+#[test]
+fn test_defaults() {
+    let args = parse(&["test"], &[]).unwrap();
+    assert_eq!(10000, args.port);
+    assert_eq!(None, args.max_connections);
+    assert_eq!(vec!["::"], args.address);
+    assert!(!args.shared);
+}
+
+#[test]
+fn test_precedence() {
+    let file = File::new(
+        "precedence",
+        r#"
+        port = 1
+        max-connections = 10
+        address = ["127.0.0.1", "::1"]
+
+        [p03]
+        port = 2
+        "#,
+    );
+
+    let args = parse(&["test", "--config", file.path()], &[]).unwrap();
+    assert_eq!(2, args.port);
+    assert_eq!(Some(10), args.max_connections);
+    assert_eq!(vec!["127.0.0.1", "::1"], args.address);
+
+    let env = [
+        ("PROTOHACKERS_PORT", "3"),
+        ("PROTOHACKERS_CONFIG", file.path()),
+    ];
+    let args = parse(&["test"], &env).unwrap();
+    assert_eq!(3, args.port);
+    assert_eq!(Some(10), args.max_connections);
+
+    let env = [("PROTOHACKERS_PORT", "3"), ("PROTOHACKERS_P03_PORT", "4")];
+    let args = parse(&["test", &format!("--config={}", file.path())], &env).unwrap();
+    assert_eq!(4, args.port);
+
+    let args = parse(&["test", "--config", file.path(), "--port", "5"], &env).unwrap();
+    assert_eq!(5, args.port);
+}
+
+#[test]
+fn test_values() {
+    let file = File::new(
+        "values",
+        r#"
+        [p03]
+        shared = true
+        storage_directory = "/tmp/p03"
+        "#,
+    );
+    let args = parse(&["test", "--config", file.path()], &[]).unwrap();
+    assert!(args.shared);
+    assert_eq!(Some(PathBuf::from("/tmp/p03")), args.storage_directory);
+
+    let env = [("PROTOHACKERS_ADDRESS", "127.0.0.1,::1")];
+    let args = parse(&["test"], &env).unwrap();
+    assert_eq!(vec!["127.0.0.1", "::1"], args.address);
+
+    let env = [("PROTOHACKERS_PORT", "port")];
+    assert!(matches!(parse(&["test"], &env), Err(Error::Clap(_))));
+
+    let file = File::new("invalid", "[p03]\nport = [1, 2]\n");
+    assert!(matches!(
+        parse(&["test", "--config", file.path()], &[]),
+        Err(Error::InvalidValue(key, "array")) if key == "port"
+    ));
+}
+
+#[test]
+fn test_unknown_keys() {
+    // shared by the problems at the top, the other tables are ignored
+    let file = File::new(
+        "shared",
+        "
+        bandwidth = 100
+
+        [p10]
+        bandwidth = 100
+        ",
+    );
+    assert!(parse(&["test", "--config", file.path()], &[]).is_ok());
+
+    let file = File::new("unknown", "[p03]\nbandwidth = 100\n");
+    assert!(matches!(
+        parse(&["test", "--config", file.path()], &[]),
+        Err(Error::UnknownKey(key)) if key == "p03.bandwidth"
+    ));
+}
+
+#[test]
+fn test_file_errors() {
+    assert!(matches!(
+        parse(&["test", "--config", "/nonexistent/config.toml"], &[]),
+        Err(Error::Io(..))
+    ));
+
+    let file = File::new("toml", "port = \n");
+    assert!(matches!(
+        parse(&["test", "--config", file.path()], &[]),
+        Err(Error::Toml(..))
+    ));
+}
+
+#[test]
+fn test_subcommand() {
+    let file = File::new(
+        "subcommand",
+        r#"
+        port = 1
+
+        [p10]
+        port = 2
+        storage-directory = "/tmp/p10"
+        "#,
+    );
+
+    let parse = |args: &[&str], env: &[(&str, &str)]| -> Result<Problem, Error> {
+        protohackers_config::try_parse_subcommand_from(
+            Command::new("test"),
+            args,
+            env.iter()
+                .map(|(name, value)| ((*name).to_string(), (*value).to_string())),
+        )
+    };
+
+    assert!(matches!(
+        parse(&["test", "--config", file.path(), "p03"], &[]).unwrap(),
+        Problem::P03(Args {
+            port: 1,
+            storage_directory: None,
+            ..
+        })
+    ));
+    assert!(matches!(
+        parse(&["test", "p10", "--config", file.path()], &[]).unwrap(),
+        Problem::P10(Args {
+            port: 2,
+            storage_directory: Some(_),
+            ..
+        })
+    ));
+    assert!(matches!(
+        parse(&["test", "p10"], &[("PROTOHACKERS_P10_PORT", "3")]).unwrap(),
+        Problem::P10(Args { port: 3, .. })
+    ));
+
+    assert!(parse(&["test"], &[]).is_err());
+    let file = File::new("subcommand-unknown", "[p10]\nbandwidth = 100\n");
+    assert!(matches!(
+        parse(&["test", "--config", file.path(), "p03"], &[]),
+        Err(Error::UnknownKey(_))
+    ));
+}
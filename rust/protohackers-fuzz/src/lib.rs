@@ -0,0 +1,196 @@
+//! The fuzz targets of the wire formats.
+//!
+//! Every target decodes attacker controlled bytes: it must not panic,
+//! and what it decodes must be bounded by the input, whatever length
+//! the input declares. The targets are plain functions, so that the
+//! regression inputs run with the tests too; the `fuzz_targets` are
+//! the libFuzzer entry points, see the README.
+use bytes::BytesMut;
+
+use futures::executor::block_on;
+
+use tokio_util::codec::Decoder;
+
+use p01_prime_time::protocol::{Method, Registry};
+use p02_means_to_an_end_core::{Extensions, Message, MessageDecoder, MESSAGE_LEN};
+use p06_speed_daemon::wire::{self, ReadFrom, TaggedMessage};
+use p07_line_reversal_core::packets::Packet;
+use p11_pest_control::codec::packets::{Packet as PestPacket, PacketCodec};
+
+/// A low `factor` work limit, the fuzzer checks the parsing and not
+/// the factorization.
+const FACTOR_WORK_LIMIT: u64 = 1_000;
+
+/// A p01 request line, parsed and evaluated with all the methods.
+pub fn p01_request(data: &[u8]) {
+    let registry = Registry::new()
+        .with_method(Method::Factor)
+        .with_factor_work_limit(FACTOR_WORK_LIMIT);
+
+    if let Ok(request) = registry.parse(data) {
+        let _ = registry.evaluate(&request);
+    }
+
+    let mut line = data.to_vec();
+    if let Ok(request) = registry.parse_tagged_mut(&mut line) {
+        let _ = registry.evaluate_tagged(&request);
+    }
+}
+
+/// A p02 stream: the fixed messages, standard and extended, and the
+/// decoder with the negotiation of the extensions.
+///
+/// # Panics
+/// * When a batch has more pairs than the input bytes.
+pub fn p02_message(data: &[u8]) {
+    for message in data.chunks_exact(MESSAGE_LEN) {
+        let message = message.try_into().expect("chunks of the message length");
+        let _ = Message::parse(message);
+        let _ = Message::parse_extension(message);
+    }
+
+    let mut decoder = MessageDecoder::with_negotiation(Extensions::ALL);
+    let mut src = data;
+    while let Ok(Some((message, len))) = decoder.decode(src) {
+        assert!(0 < len && len <= src.len(), "length {len} of {}", src.len());
+        if let Message::BatchInsert(pairs) = message {
+            assert!(
+                pairs.len() * 8 < len,
+                "{} pairs in {len} bytes",
+                pairs.len()
+            );
+        }
+        src = &src[len..];
+    }
+}
+
+/// A p06 stream of messages, read as the server does: the tag, then
+/// the payload of the message type.
+///
+/// # Panics
+/// * When a message is longer than the input bytes.
+pub fn p06_wire(data: &[u8]) {
+    block_on(async {
+        let mut read = data;
+        while let Some((&tag, payload)) = read.split_first() {
+            read = payload;
+            let len = match tag {
+                wire::Error::TAG => wire::Error::read_payload_from(&mut read)
+                    .await
+                    .map(|error| error.msg.len()),
+                wire::Plate::TAG => wire::Plate::read_payload_from(&mut read)
+                    .await
+                    .map(|plate| plate.plate.len()),
+                wire::Ticket::TAG => wire::Ticket::read_payload_from(&mut read)
+                    .await
+                    .map(|ticket| ticket.plate.len()),
+                wire::WantHeartbeat::TAG => wire::WantHeartbeat::read_payload_from(&mut read)
+                    .await
+                    .map(|_| 0),
+                wire::Heartbeat::TAG => wire::Heartbeat::read_payload_from(&mut read)
+                    .await
+                    .map(|_| 0),
+                wire::IAmCamera::TAG => wire::IAmCamera::read_payload_from(&mut read)
+                    .await
+                    .map(|_| 0),
+                wire::IAmDispatcher::TAG => wire::IAmDispatcher::read_payload_from(&mut read)
+                    .await
+                    .map(|dispatcher| dispatcher.roads.len() * 2),
+                _ => break,
+            };
+            let Ok(len) = len else {
+                break;
+            };
+            // the bytes not utf8 are replacement chars, three bytes
+            assert!(len <= 3 * data.len(), "{len} bytes from {}", data.len());
+        }
+    });
+}
+
+/// A LRCP packet.
+///
+/// # Panics
+/// * When the data is longer than the packet.
+pub fn p07_packet(data: &[u8]) {
+    if let Ok(Packet::Data { data: payload, .. }) = Packet::try_from(data) {
+        // the bytes over 0x7f are two bytes chars
+        assert!(
+            payload.0.len() <= 2 * data.len(),
+            "{} bytes from {}",
+            payload.0.len(),
+            data.len()
+        );
+    }
+}
+
+/// A p11 stream, decoded as the server does, up to the first error.
+///
+/// # Panics
+/// * When the decoder grows the buffer.
+pub fn p11_packet_codec(data: &[u8]) {
+    let mut codec = PacketCodec::new();
+    let mut src = BytesMut::from(data);
+    let capacity = src.capacity();
+
+    while let Ok(Some(packet)) = codec.decode(&mut src) {
+        if let PestPacket::SiteVisit(site_visit) = packet {
+            assert!(site_visit.populations.len() * 8 <= data.len());
+        }
+    }
+
+    assert!(
+        src.capacity() <= capacity,
+        "capacity {} from {capacity}",
+        src.capacity()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_p01_request() {
+        p01_request(br#"{"method":"isPrime","number":7}"#);
+        p01_request(br#"{"method":"factor","number":18446744073709551557}"#);
+        p01_request(br#"{"method":"isPrime","number":"#);
+        p01_request(&[b'['; 1024]);
+    }
+
+    #[test]
+    fn test_p02_message() {
+        p02_message(b"I\x00\x00\x30\x39\x00\x00\x00\x65Q\x00\x00\x00\x00\x00\x00\xff\xff");
+        p02_message(b"H\x00\x00\x00\x01\x00\x00\x00\x03B\xff\xff\x00\x00\x00\x01");
+        p02_message(b"H\x00\x00\x00\x01\x00\x00\x00\x03B\x00\x01\x00\x00\x00\x01\x00\x00\x00\x02");
+    }
+
+    #[test]
+    fn test_p06_wire() {
+        p06_wire(&[0x20, 0x04, 0x55, 0x4e, 0x31, 0x58, 0x00, 0x00, 0x03, 0xe8]);
+        p06_wire(&[0x81, 0xff, 0x00, 0x42]);
+        p06_wire(&[0x10, 0xff]);
+        p06_wire(&[0x40, 0xff, 0xff, 0xff, 0xff, 0x41, 0x80]);
+        p06_wire(&[0x10, 0x04, 0x00, 0x04, 0x80, 0x80]);
+    }
+
+    #[test]
+    fn test_p07_packet() {
+        p07_packet(b"/data/1/0/hello\\/\xff/");
+        p07_packet(b"/data/1/0/");
+        p07_packet(b"/connect/2147483648/");
+        p07_packet(b"/ack/1/");
+        p07_packet(b"/data//66666666666/");
+    }
+
+    #[test]
+    fn test_p11_packet_codec() {
+        // hello
+        p11_packet_codec(b"\x50\x00\x00\x00\x19\x00\x00\x00\x0bpestcontrol\x00\x00\x00\x01\xce");
+        // a site visit declaring 2^32 - 1 populations
+        p11_packet_codec(b"\x58\x00\x00\x00\x0e\x00\x00\x00\x01\xff\xff\xff\xff\x00");
+        // a string declaring 2^32 - 1 bytes
+        p11_packet_codec(b"\x50\xff\xff\xff\xff\xff\xff\xff\xff\x00");
+        // a string not utf8
+        p11_packet_codec(b"\x51\x00\x00\x00\x0b\x00\x00\x00\x01\xff\xa4");
+    }
+}
@@ -0,0 +1,98 @@
+//! The exporters of the metrics.
+use std::fmt::Write;
+use std::future::Future;
+use std::io;
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+use tokio::time::{self, Instant};
+
+use tracing::{info, warn};
+
+use crate::registry::{Registry, Snapshot, Value};
+
+/// Where the metrics of a registry go.
+pub trait Exporter {
+    /// Export the metrics of `registry`, until an error.
+    fn export(self, registry: Registry) -> impl Future<Output = io::Result<()>> + Send;
+}
+
+/// Run `exporter` on its own task, logging its error.
+pub fn spawn<E>(exporter: E, registry: Registry) -> JoinHandle<()>
+where
+    E: Exporter + Send + 'static,
+{
+    tokio::spawn(async move {
+        if let Err(err) = exporter.export(registry).await {
+            warn!("metrics exporter: {err}");
+        }
+    })
+}
+
+/// Log all the metrics every interval, with the rate of the counters
+/// since the previous line.
+#[derive(Debug, Clone, Copy)]
+pub struct LogExporter {
+    interval: Duration,
+}
+
+impl LogExporter {
+    #[must_use]
+    pub fn new(interval: Duration) -> Self {
+        Self { interval }
+    }
+}
+
+impl Exporter for LogExporter {
+    async fn export(self, registry: Registry) -> io::Result<()> {
+        let mut interval = time::interval(self.interval);
+        interval.tick().await;
+        let (mut previous, mut last) = (registry.snapshot(), Instant::now());
+        loop {
+            interval.tick().await;
+            let (snapshot, now) = (registry.snapshot(), Instant::now());
+            info!("metrics: {}", log_line(&snapshot, &previous, now - last));
+            (previous, last) = (snapshot, now);
+        }
+    }
+}
+
+fn log_line(snapshot: &Snapshot, previous: &Snapshot, elapsed: Duration) -> String {
+    let mut line = String::new();
+    for (i, sample) in snapshot.samples().iter().enumerate() {
+        if i > 0 {
+            line.push(' ');
+        }
+        write!(line, "{}: {}", sample.name, sample.value).expect("write to string");
+        if let (Value::Counter(value), Some(Value::Counter(before))) =
+            (&sample.value, previous.get(&sample.name))
+        {
+            #[allow(clippy::cast_precision_loss)]
+            let rate = value.saturating_sub(*before) as f64 / elapsed.as_secs_f64();
+            write!(line, " ({rate:.0}/s)").expect("write to string");
+        }
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_line() {
+        let registry = Registry::new();
+        let bytes = registry.counter("bytes_total", "The bytes");
+        let active = registry.gauge("active", "The open connections");
+
+        bytes.add(100);
+        let previous = registry.snapshot();
+        bytes.add(150);
+        active.inc();
+
+        assert_eq!(
+            "bytes_total: 250 (75/s) active: 1",
+            log_line(&registry.snapshot(), &previous, Duration::from_secs(2))
+        );
+    }
+}
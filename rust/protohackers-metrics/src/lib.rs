@@ -0,0 +1,82 @@
+//! The metrics shared by the problem servers.
+//!
+//! A server registers its [`Counter`]s, [`Gauge`]s and [`Histogram`]s
+//! by name in a [`Registry`], and the exporters read them from there:
+//! the [`LogExporter`] logs them periodically, the
+//! [`PrometheusExporter`] serves them in the Prometheus text format;
+//! the resources of the [`process`] are sampled alongside.
+//! [`MetricsArgs`] are the standard flags to flatten into the `Args`
+//! of a binary:
+//!
+//! ```no_run
+//! use clap::Parser;
+//!
+//! use protohackers_metrics::{MetricsArgs, Registry};
+//!
+//! #[derive(clap::Parser, Debug)]
+//! struct Args {
+//!     #[command(flatten)]
+//!     metrics: MetricsArgs,
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), std::io::Error> {
+//!     let args = Args::parse();
+//!
+//!     let registry = Registry::new();
+//!     let requests = registry.counter("requests_total", "The requests");
+//!     args.metrics.spawn(&registry).await?;
+//!
+//!     requests.inc();
+//!     # Ok(())
+//! }
+//! ```
+use std::io;
+use std::time::Duration;
+
+pub mod export;
+pub mod metric;
+pub mod process;
+pub mod prometheus;
+pub mod registry;
+
+pub use export::{Exporter, LogExporter};
+pub use metric::{Counter, Gauge, Histogram, HistogramSnapshot, LATENCY_BUCKETS};
+pub use process::ProcessMetrics;
+pub use prometheus::PrometheusExporter;
+pub use registry::{Registry, Sample, Snapshot, Value};
+
+/// The flags of the metrics exporters.
+#[derive(clap::Args, Debug, Clone)]
+pub struct MetricsArgs {
+    /// Log the metrics every this number of seconds
+    #[arg(long, default_value_t = 60)]
+    pub metrics_interval: u64,
+
+    /// Serve the metrics in the Prometheus text format on this
+    /// address, at `/metrics`
+    #[arg(long)]
+    pub metrics_address: Option<String>,
+}
+
+impl MetricsArgs {
+    /// Spawn the exporters of `registry`, and the sampling of the
+    /// resources of the process into it.
+    ///
+    /// # Errors
+    /// * Error when the Prometheus address can not be bound.
+    pub async fn spawn(&self, registry: &Registry) -> io::Result<()> {
+        if let Some(address) = &self.metrics_address {
+            let exporter = PrometheusExporter::bind(address).await?;
+            export::spawn(exporter, registry.clone());
+        }
+
+        export::spawn(
+            LogExporter::new(Duration::from_secs(self.metrics_interval)),
+            registry.clone(),
+        );
+        process::spawn(registry, process::REPORT_INTERVAL);
+
+        Ok(())
+    }
+}
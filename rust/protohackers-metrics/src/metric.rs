@@ -0,0 +1,195 @@
+//! The metric handles.
+use std::fmt;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The default upper bounds of the latency histogram buckets.
+pub const LATENCY_BUCKETS: [Duration; 6] = [
+    Duration::from_micros(10),
+    Duration::from_micros(100),
+    Duration::from_millis(1),
+    Duration::from_millis(10),
+    Duration::from_millis(100),
+    Duration::from_secs(1),
+];
+
+/// A monotonic count, the clones share the value.
+#[derive(Debug, Clone, Default)]
+pub struct Counter(Arc<AtomicU64>);
+
+impl Counter {
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    pub fn add(&self, n: u64) {
+        self.0.fetch_add(n, Ordering::Relaxed);
+    }
+
+    #[must_use]
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// A value going up and down, the clones share the value.
+#[derive(Debug, Clone, Default)]
+pub struct Gauge(Arc<AtomicI64>);
+
+impl Gauge {
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    pub fn dec(&self) {
+        self.sub(1);
+    }
+
+    pub fn add(&self, n: i64) {
+        self.0.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn sub(&self, n: i64) {
+        self.0.fetch_sub(n, Ordering::Relaxed);
+    }
+
+    pub fn set(&self, value: i64) {
+        self.0.store(value, Ordering::Relaxed);
+    }
+
+    #[must_use]
+    pub fn get(&self) -> i64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug)]
+struct HistogramInner {
+    bounds: Vec<Duration>,
+    buckets: Vec<AtomicU64>,
+    sum: AtomicU64,
+}
+
+/// A distribution of durations, the clones share the buckets.
+#[derive(Debug, Clone)]
+pub struct Histogram(Arc<HistogramInner>);
+
+impl Histogram {
+    /// A histogram with a bucket for each upper bound, in ascending
+    /// order, plus the last one without upper bound.
+    #[must_use]
+    pub fn new(bounds: &[Duration]) -> Self {
+        Self(Arc::new(HistogramInner {
+            bounds: bounds.to_vec(),
+            buckets: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+            sum: AtomicU64::new(0),
+        }))
+    }
+
+    pub fn record(&self, value: Duration) {
+        let index = self
+            .0
+            .bounds
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(self.0.bounds.len());
+        self.0.buckets[index].fetch_add(1, Ordering::Relaxed);
+        self.0.sum.fetch_add(
+            u64::try_from(value.as_nanos()).unwrap_or(u64::MAX),
+            Ordering::Relaxed,
+        );
+    }
+
+    #[must_use]
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        HistogramSnapshot {
+            bounds: self.0.bounds.clone(),
+            counts: self
+                .0
+                .buckets
+                .iter()
+                .map(|bucket| bucket.load(Ordering::Relaxed))
+                .collect(),
+            sum: Duration::from_nanos(self.0.sum.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self::new(&LATENCY_BUCKETS)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistogramSnapshot {
+    pub bounds: Vec<Duration>,
+
+    /// The count of every bucket, not cumulative; the last one is
+    /// over the last bound.
+    pub counts: Vec<u64>,
+
+    pub sum: Duration,
+}
+
+impl HistogramSnapshot {
+    #[must_use]
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+}
+
+impl fmt::Display for HistogramSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        for (i, (bound, count)) in self.bounds.iter().zip(&self.counts).enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "<={bound:?}: {count}")?;
+        }
+        match self.bounds.last() {
+            Some(bound) => write!(f, " >{bound:?}: {}", self.counts[self.bounds.len()]),
+            None => write!(f, "{}", self.counts[0]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counter_and_gauge() {
+        let counter = Counter::default();
+        counter.clone().inc();
+        counter.add(2);
+        assert_eq!(3, counter.get());
+
+        let gauge = Gauge::default();
+        gauge.inc();
+        gauge.clone().sub(3);
+        assert_eq!(-2, gauge.get());
+        gauge.set(5);
+        gauge.dec();
+        assert_eq!(4, gauge.get());
+    }
+
+    #[test]
+    fn test_histogram() {
+        let histogram = Histogram::default();
+        histogram.record(Duration::from_micros(1));
+        histogram.record(Duration::from_micros(10));
+        histogram.clone().record(Duration::from_micros(11));
+        histogram.record(Duration::from_secs(2));
+
+        let snapshot = histogram.snapshot();
+        assert_eq!(vec![2, 1, 0, 0, 0, 0, 1], snapshot.counts);
+        assert_eq!(4, snapshot.count());
+        assert_eq!(Duration::from_micros(2_000_022), snapshot.sum);
+        assert_eq!(
+            "<=10µs: 2 <=100µs: 1 <=1ms: 0 <=10ms: 0 <=100ms: 0 <=1s: 0 >1s: 1",
+            snapshot.to_string()
+        );
+    }
+}
@@ -0,0 +1,102 @@
+//! The resources of the process, sampled into gauges on an interval:
+//! the open file descriptors and the resident memory, on Linux, and
+//! the tasks and the workers of the runtime.
+use std::time::Duration;
+
+use tokio::runtime::Handle;
+use tokio::time;
+
+use crate::metric::Gauge;
+use crate::registry::Registry;
+
+/// The default interval of the samples.
+pub const REPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+pub struct ProcessMetrics {
+    open_fds: Gauge,
+    resident_memory: Gauge,
+    alive_tasks: Gauge,
+    workers: Gauge,
+}
+
+impl ProcessMetrics {
+    #[must_use]
+    pub fn register(registry: &Registry) -> Self {
+        Self {
+            open_fds: registry.gauge("process_open_fds", "The open file descriptors"),
+            resident_memory: registry.gauge(
+                "process_resident_memory_bytes",
+                "The resident memory, in bytes",
+            ),
+            alive_tasks: registry.gauge("runtime_alive_tasks", "The tasks alive in the runtime"),
+            workers: registry.gauge("runtime_workers", "The worker threads of the runtime"),
+        }
+    }
+
+    /// Sample the resources now; the ones not available on this
+    /// platform, or outside of the runtime, are left as they are.
+    pub fn sample(&self) {
+        if let Some(open_fds) = open_fds() {
+            self.open_fds.set(saturating_i64(open_fds));
+        }
+        if let Some(resident_memory) = resident_memory() {
+            self.resident_memory.set(saturating_i64(resident_memory));
+        }
+        if let Ok(handle) = Handle::try_current() {
+            let metrics = handle.metrics();
+            self.alive_tasks
+                .set(saturating_i64(metrics.num_alive_tasks()));
+            self.workers.set(saturating_i64(metrics.num_workers()));
+        }
+    }
+}
+
+/// Sample the resources of the process into `registry` every
+/// `interval`, on its own task.
+pub fn spawn(registry: &Registry, interval: Duration) {
+    let metrics = ProcessMetrics::register(registry);
+    tokio::spawn(async move {
+        let mut interval = time::interval(interval);
+        loop {
+            interval.tick().await;
+            metrics.sample();
+        }
+    });
+}
+
+fn saturating_i64(value: impl TryInto<i64>) -> i64 {
+    value.try_into().unwrap_or(i64::MAX)
+}
+
+/// The entries of `/proc/self/fd` but the one listing them.
+#[cfg(target_os = "linux")]
+fn open_fds() -> Option<usize> {
+    let entries = std::fs::read_dir("/proc/self/fd").ok()?;
+    Some(entries.count().saturating_sub(1))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_fds() -> Option<usize> {
+    None
+}
+
+/// The `VmRSS` of `/proc/self/status`, in kB.
+#[cfg(target_os = "linux")]
+fn resident_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let kilobytes = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kilobytes * 1024)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn resident_memory() -> Option<u64> {
+    None
+}
@@ -0,0 +1,177 @@
+//! The Prometheus text exposition format, served over HTTP.
+use std::fmt;
+use std::io;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use tracing::{debug, warn};
+
+use crate::export::Exporter;
+use crate::registry::{Registry, Snapshot, Value};
+
+/// The path of the metrics.
+pub const METRICS_PATH: &str = "/metrics";
+
+const MAX_REQUEST_LEN: usize = 8 * 1024;
+
+/// The metrics in the Prometheus text format; the histograms are in
+/// seconds.
+#[must_use]
+pub fn render(snapshot: &Snapshot) -> String {
+    Text(snapshot).to_string()
+}
+
+struct Text<'a>(&'a Snapshot);
+
+impl fmt::Display for Text<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        for sample in self.0.samples() {
+            let name = &sample.name;
+            let help = sample.help.replace('\\', r"\\").replace('\n', r"\n");
+            let kind = match sample.value {
+                Value::Counter(_) => "counter",
+                Value::Gauge(_) => "gauge",
+                Value::Histogram(_) => "histogram",
+            };
+            writeln!(f, "# HELP {name} {help}")?;
+            writeln!(f, "# TYPE {name} {kind}")?;
+            match &sample.value {
+                Value::Counter(value) => writeln!(f, "{name} {value}")?,
+                Value::Gauge(value) => writeln!(f, "{name} {value}")?,
+                Value::Histogram(histogram) => {
+                    let mut cumulative = 0;
+                    for (bound, count) in histogram.bounds.iter().zip(&histogram.counts) {
+                        cumulative += count;
+                        writeln!(
+                            f,
+                            "{name}_bucket{{le=\"{}\"}} {cumulative}",
+                            bound.as_secs_f64()
+                        )?;
+                    }
+                    let count = histogram.count();
+                    writeln!(f, "{name}_bucket{{le=\"+Inf\"}} {count}")?;
+                    writeln!(f, "{name}_sum {}", histogram.sum.as_secs_f64())?;
+                    writeln!(f, "{name}_count {count}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Serve the metrics on `GET /metrics`, one request per connection.
+#[derive(Debug)]
+pub struct PrometheusExporter {
+    listener: TcpListener,
+}
+
+impl PrometheusExporter {
+    #[must_use]
+    pub fn new(listener: TcpListener) -> Self {
+        Self { listener }
+    }
+
+    /// # Errors
+    /// * Error when the address can not be bound.
+    pub async fn bind(address: &str) -> io::Result<Self> {
+        Ok(Self::new(TcpListener::bind(address).await?))
+    }
+
+    /// # Errors
+    /// * Error when the local address of the listener is not
+    ///   available.
+    pub fn local_addr(&self) -> io::Result<std::net::SocketAddr> {
+        self.listener.local_addr()
+    }
+}
+
+impl Exporter for PrometheusExporter {
+    async fn export(self, registry: Registry) -> io::Result<()> {
+        loop {
+            let (stream, peer) = self.listener.accept().await?;
+            debug!("metrics request from {peer}");
+
+            let registry = registry.clone();
+            tokio::spawn(async move {
+                if let Err(err) = handle(stream, &registry).await {
+                    warn!("metrics request from {peer}: {err}");
+                }
+            });
+        }
+    }
+}
+
+async fn handle(mut stream: TcpStream, registry: &Registry) -> io::Result<()> {
+    let mut request = vec![];
+    let mut buffer = [0; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        if request.len() > MAX_REQUEST_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "request too long",
+            ));
+        }
+        let n = stream.read(&mut buffer).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        request.extend_from_slice(&buffer[..n]);
+    }
+
+    let request_line = request.split(|b| *b == b'\r').next().unwrap_or_default();
+    let mut parts = request_line.split(|b| *b == b' ');
+    let response = match (parts.next(), parts.next()) {
+        (Some(b"GET"), Some(path)) if path == METRICS_PATH.as_bytes() => {
+            let body = render(&registry.snapshot());
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+        }
+        _ => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+    };
+
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let registry = Registry::new();
+        registry.counter("requests_total", "The requests").add(3);
+        registry.gauge("active", "The open\nconnections").dec();
+        let latency = registry.histogram(
+            "latency_seconds",
+            "The latency",
+            &[Duration::from_millis(1), Duration::from_millis(10)],
+        );
+        latency.record(Duration::from_micros(500));
+        latency.record(Duration::from_millis(5));
+        latency.record(Duration::from_secs(1));
+
+        assert_eq!(
+            r#"# HELP requests_total The requests
+# TYPE requests_total counter
+requests_total 3
+# HELP active The open\nconnections
+# TYPE active gauge
+active -1
+# HELP latency_seconds The latency
+# TYPE latency_seconds histogram
+latency_seconds_bucket{le="0.001"} 1
+latency_seconds_bucket{le="0.01"} 2
+latency_seconds_bucket{le="+Inf"} 3
+latency_seconds_sum 1.0055
+latency_seconds_count 3
+"#,
+            render(&registry.snapshot())
+        );
+    }
+}
@@ -0,0 +1,226 @@
+//! The registry of the named metrics.
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::metric::{Counter, Gauge, Histogram, HistogramSnapshot};
+
+#[derive(Debug, Clone)]
+enum Metric {
+    Counter(Counter),
+    Gauge(Gauge),
+    Histogram(Histogram),
+}
+
+#[derive(Debug)]
+struct Entry {
+    name: String,
+    help: String,
+    metric: Metric,
+}
+
+/// The metrics of a server, by name, in registration order; the
+/// clones share the metrics.
+#[derive(Debug, Clone, Default)]
+pub struct Registry(Arc<Mutex<Vec<Entry>>>);
+
+impl Registry {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The counter named `name`, registered on the first call.
+    ///
+    /// # Panics
+    /// * Panics when the name is not a valid metric name or is
+    ///   registered with another kind.
+    #[must_use]
+    pub fn counter(&self, name: &str, help: &str) -> Counter {
+        match self.register(name, help, || Metric::Counter(Counter::default())) {
+            Metric::Counter(counter) => counter,
+            _ => panic!("metric {name} is not a counter"),
+        }
+    }
+
+    /// The gauge named `name`, registered on the first call.
+    ///
+    /// # Panics
+    /// * Panics when the name is not a valid metric name or is
+    ///   registered with another kind.
+    #[must_use]
+    pub fn gauge(&self, name: &str, help: &str) -> Gauge {
+        match self.register(name, help, || Metric::Gauge(Gauge::default())) {
+            Metric::Gauge(gauge) => gauge,
+            _ => panic!("metric {name} is not a gauge"),
+        }
+    }
+
+    /// The histogram named `name`, registered with `bounds` on the
+    /// first call.
+    ///
+    /// # Panics
+    /// * Panics when the name is not a valid metric name or is
+    ///   registered with another kind.
+    #[must_use]
+    pub fn histogram(&self, name: &str, help: &str, bounds: &[Duration]) -> Histogram {
+        match self.register(name, help, || Metric::Histogram(Histogram::new(bounds))) {
+            Metric::Histogram(histogram) => histogram,
+            _ => panic!("metric {name} is not a histogram"),
+        }
+    }
+
+    fn register(&self, name: &str, help: &str, metric: impl FnOnce() -> Metric) -> Metric {
+        assert!(is_valid_name(name), "invalid metric name {name}");
+
+        let mut entries = self.0.lock().expect("metrics lock");
+        if let Some(entry) = entries.iter().find(|entry| entry.name == name) {
+            return entry.metric.clone();
+        }
+
+        let metric = metric();
+        entries.push(Entry {
+            name: name.to_string(),
+            help: help.to_string(),
+            metric: metric.clone(),
+        });
+        metric
+    }
+
+    /// The current values of all the metrics.
+    ///
+    /// # Panics
+    /// * Never, the lock is not held while panicking.
+    #[must_use]
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot(
+            self.0
+                .lock()
+                .expect("metrics lock")
+                .iter()
+                .map(|entry| Sample {
+                    name: entry.name.clone(),
+                    help: entry.help.clone(),
+                    value: match &entry.metric {
+                        Metric::Counter(counter) => Value::Counter(counter.get()),
+                        Metric::Gauge(gauge) => Value::Gauge(gauge.get()),
+                        Metric::Histogram(histogram) => Value::Histogram(histogram.snapshot()),
+                    },
+                })
+                .collect(),
+        )
+    }
+}
+
+/// The Prometheus metric names: `[a-zA-Z_:][a-zA-Z0-9_:]*`.
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == ':')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Counter(u64),
+    Gauge(i64),
+    Histogram(HistogramSnapshot),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Value::Counter(value) => write!(f, "{value}"),
+            Value::Gauge(value) => write!(f, "{value}"),
+            Value::Histogram(histogram) => write!(f, "{histogram}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sample {
+    pub name: String,
+    pub help: String,
+    pub value: Value,
+}
+
+/// The values of the metrics of a registry at some point in time.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot(pub Vec<Sample>);
+
+impl Snapshot {
+    #[must_use]
+    pub fn samples(&self) -> &[Sample] {
+        &self.0
+    }
+
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.0
+            .iter()
+            .find(|sample| sample.name == name)
+            .map(|sample| &sample.value)
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        for (i, sample) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}: {}", sample.name, sample.value)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry() {
+        let registry = Registry::new();
+        let requests = registry.counter("requests_total", "The requests");
+        let active = registry.gauge("active", "The open connections");
+        let latency = registry.histogram(
+            "latency_seconds",
+            "The latency",
+            &[Duration::from_millis(1)],
+        );
+
+        requests.inc();
+        registry.counter("requests_total", "The requests").inc();
+        active.inc();
+        latency.record(Duration::from_millis(2));
+
+        let snapshot = registry.clone().snapshot();
+        assert_eq!(Some(&Value::Counter(2)), snapshot.get("requests_total"));
+        assert_eq!(Some(&Value::Gauge(1)), snapshot.get("active"));
+        assert_eq!(None, snapshot.get("missing"));
+        assert_eq!(
+            "requests_total: 2 active: 1 latency_seconds: <=1ms: 0 >1ms: 1",
+            snapshot.to_string()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "metric requests_total is not a gauge")]
+    fn test_kind_mismatch() {
+        let registry = Registry::new();
+        let _ = registry.counter("requests_total", "The requests");
+        let _ = registry.gauge("requests_total", "The requests");
+    }
+
+    #[test]
+    fn test_names() {
+        for name in ["requests_total", "_private", "ns:name", "a1"] {
+            assert!(is_valid_name(name), "{name}");
+        }
+        for name in ["", "1a", "with space", "dash-ed", "àccent"] {
+            assert!(!is_valid_name(name), "{name}");
+        }
+    }
+}
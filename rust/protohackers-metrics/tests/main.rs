@@ -0,0 +1,102 @@
+use std::time::Duration;
+
+use clap::Parser;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::timeout;
+
+use protohackers_metrics::{
+    export, MetricsArgs, ProcessMetrics, PrometheusExporter, Registry, Value,
+};
+
+const TIMEOUT: Duration = Duration::from_millis(500);
+
+async fn get(address: &str, path: &str) -> String {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    stream
+        .write_all(format!("GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").as_bytes())
+        .await
+        .unwrap();
+
+    let mut response = String::new();
+    timeout(TIMEOUT, stream.read_to_string(&mut response))
+        .await
+        .unwrap()
+        .unwrap();
+    response
+}
+
+#[test]
+fn test_args() {
+    #[derive(clap::Parser, Debug)]
+    struct Args {
+        #[command(flatten)]
+        metrics: MetricsArgs,
+    }
+
+    let args = Args::parse_from(["test"]);
+    assert_eq!(60, args.metrics.metrics_interval);
+    assert_eq!(None, args.metrics.metrics_address);
+
+    let args = Args::parse_from([
+        "test",
+        "--metrics-interval",
+        "5",
+        "--metrics-address",
+        "127.0.0.1:9090",
+    ]);
+    assert_eq!(5, args.metrics.metrics_interval);
+    assert_eq!(
+        Some("127.0.0.1:9090"),
+        args.metrics.metrics_address.as_deref()
+    );
+}
+
+#[tokio::test]
+async fn test_prometheus_exporter() {
+    let registry = Registry::new();
+    let requests = registry.counter("requests_total", "The requests");
+
+    let exporter = PrometheusExporter::new(TcpListener::bind("127.0.0.1:0").await.unwrap());
+    let address = exporter.local_addr().unwrap().to_string();
+    export::spawn(exporter, registry.clone());
+
+    requests.add(2);
+    let response = get(&address, "/metrics").await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+    assert!(response.ends_with("\r\n\r\n# HELP requests_total The requests\n# TYPE requests_total counter\nrequests_total 2\n"), "{response}");
+
+    // registered after the start of the exporter
+    registry.gauge("active", "The open connections").inc();
+    let response = get(&address, "/metrics").await;
+    assert!(response.ends_with("active 1\n"), "{response}");
+
+    let response = get(&address, "/").await;
+    assert!(
+        response.starts_with("HTTP/1.1 404 Not Found\r\n"),
+        "{response}"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_process_metrics() {
+    let registry = Registry::new();
+    let metrics = ProcessMetrics::register(&registry);
+    let task = tokio::spawn(std::future::pending::<()>());
+
+    metrics.sample();
+    let snapshot = registry.snapshot();
+    let gauge = |name| match snapshot.get(name) {
+        Some(Value::Gauge(value)) => *value,
+        value => panic!("{name}: {value:?}"),
+    };
+    assert!(gauge("runtime_alive_tasks") >= 1);
+    assert_eq!(2, gauge("runtime_workers"));
+    if cfg!(target_os = "linux") {
+        assert!(gauge("process_open_fds") > 0);
+        assert!(gauge("process_resident_memory_bytes") > 0);
+    }
+
+    task.abort();
+}
@@ -0,0 +1,118 @@
+//! The strategies and helpers shared by the property tests of the
+//! wire formats.
+//!
+//! Every wire format has the same two properties, in the `tests` of
+//! its crate: the encoded messages decode to the same messages,
+//! whatever the chunks the bytes arrive in, and decoding arbitrary or
+//! corrupted bytes never panics:
+//!
+//! ```
+//! use proptest::prelude::*;
+//! use proptest::test_runner::TestRunner;
+//!
+//! use protohackers_proptest::{bytes, split};
+//!
+//! TestRunner::default()
+//!     .run(&bytes(64).prop_flat_map(split), |chunks| {
+//!         prop_assert!(chunks.iter().all(|chunk| !chunk.is_empty()));
+//!         Ok(())
+//!     })
+//!     .unwrap();
+//! ```
+use bytes::BytesMut;
+
+use proptest::prelude::*;
+use proptest::sample::Index;
+
+use tokio_util::codec::Decoder;
+
+/// The most chunks [`split`] cuts the bytes in.
+pub const MAX_CHUNKS: usize = 8;
+
+/// Any string of at most `max_len` bytes once UTF-8 encoded, as the
+/// length prefixed strings of the binary formats.
+pub fn string(max_len: usize) -> impl Strategy<Value = String> {
+    prop::collection::vec(any::<char>(), 0..=max_len).prop_map(move |chars| {
+        let mut string = String::new();
+        for c in chars {
+            if string.len() + c.len_utf8() > max_len {
+                break;
+            }
+            string.push(c);
+        }
+        string
+    })
+}
+
+/// Any ASCII string of at most `max_len` bytes, the control
+/// characters too.
+pub fn ascii(max_len: usize) -> impl Strategy<Value = String> {
+    prop::collection::vec(prop::char::range('\0', '\x7f'), 0..=max_len)
+        .prop_map(|chars| chars.into_iter().collect())
+}
+
+/// Any bytes, at most `max_len`.
+pub fn bytes(max_len: usize) -> impl Strategy<Value = Vec<u8>> {
+    prop::collection::vec(any::<u8>(), 0..=max_len)
+}
+
+/// `data` with a few bytes overwritten and maybe truncated: a valid
+/// message gets past the first checks of a decoder more often than
+/// random bytes do.
+pub fn mutated(data: Vec<u8>) -> impl Strategy<Value = Vec<u8>> {
+    (
+        prop::collection::vec((any::<Index>(), any::<u8>()), 0..4),
+        prop::option::of(any::<Index>()),
+    )
+        .prop_map(move |(writes, truncate)| {
+            let mut data = data.clone();
+            if !data.is_empty() {
+                for (index, byte) in writes {
+                    let index = index.index(data.len());
+                    data[index] = byte;
+                }
+            }
+            if let Some(truncate) = truncate {
+                data.truncate(truncate.index(data.len() + 1));
+            }
+            data
+        })
+}
+
+/// `data` cut in up to [`MAX_CHUNKS`] non empty chunks, as read from
+/// a stream.
+pub fn split(data: Vec<u8>) -> impl Strategy<Value = Vec<Vec<u8>>> {
+    prop::collection::vec(any::<Index>(), 0..MAX_CHUNKS).prop_map(move |cuts| {
+        let mut cuts = cuts
+            .into_iter()
+            .map(|cut| cut.index(data.len() + 1))
+            .chain([0, data.len()])
+            .collect::<Vec<_>>();
+        cuts.sort_unstable();
+        cuts.dedup();
+
+        cuts.windows(2)
+            .map(|cut| data[cut[0]..cut[1]].to_vec())
+            .collect()
+    })
+}
+
+/// Decode the items of `chunks` fed one at a time to `decoder`, as a
+/// framed reader does, returning them with the bytes left.
+///
+/// # Errors
+/// * The first error of the decoder.
+pub fn decode_chunks<D: Decoder>(
+    decoder: &mut D,
+    chunks: &[Vec<u8>],
+) -> Result<(Vec<D::Item>, BytesMut), D::Error> {
+    let mut src = BytesMut::new();
+    let mut items = vec![];
+    for chunk in chunks {
+        src.extend_from_slice(chunk);
+        while let Some(item) = decoder.decode(&mut src)? {
+            items.push(item);
+        }
+    }
+    Ok((items, src))
+}
@@ -0,0 +1,235 @@
+//! Token bucket rate limiting, per key: an IP address, a connection, a
+//! session.
+//!
+//! A [`Bucket`] holds up to `burst` tokens and is refilled at `rate`
+//! tokens per second; a new bucket is full. The tokens are counted in
+//! nanoseconds times the rate, so that the refill is exact whatever
+//! the rate: in any interval of `d` seconds a bucket gives at most
+//! `burst + d * rate` tokens.
+//!
+//! A [`RateLimiter`] keeps a bucket per key, created on the first use
+//! and dropped by [`RateLimiter::purge`] once full again, as a new one
+//! would be:
+//!
+//! ```
+//! use std::net::IpAddr;
+//!
+//! use protohackers_ratelimit::{Quota, RateLimiter};
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() {
+//! let limiter = RateLimiter::<IpAddr>::new(Quota::per_second(10).with_burst(2));
+//! let ip = IpAddr::from([127, 0, 0, 1]);
+//!
+//! assert!(limiter.try_acquire(ip, 1));
+//! assert!(limiter.try_acquire(ip, 1));
+//! assert!(!limiter.try_acquire(ip, 1));
+//!
+//! // waits for the refill, a tenth of a second
+//! limiter.acquire(ip, 1).await;
+//! # }
+//! ```
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+/// The rate and the burst of the buckets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    rate: u64,
+    burst: u64,
+}
+
+impl Quota {
+    /// `rate` tokens per second, at least one, with a burst of one
+    /// second of tokens.
+    #[must_use]
+    pub fn per_second(rate: u64) -> Self {
+        let rate = rate.max(1);
+        Self { rate, burst: rate }
+    }
+
+    /// Up to `burst` tokens at once; with a burst of zero every token
+    /// is paced at the rate.
+    #[must_use]
+    pub fn with_burst(self, burst: u64) -> Self {
+        Self { burst, ..self }
+    }
+
+    #[must_use]
+    pub fn rate(&self) -> u64 {
+        self.rate
+    }
+
+    #[must_use]
+    pub fn burst(&self) -> u64 {
+        self.burst
+    }
+}
+
+/// A single token bucket; the methods take the current time, see
+/// [`RateLimiter`] for the one of the clock.
+#[derive(Debug, Clone)]
+pub struct Bucket {
+    rate: u128,
+    capacity: u128,
+    /// Over the capacity when the tokens are reserved ahead of the
+    /// rate.
+    used: u128,
+    last: Instant,
+}
+
+impl Bucket {
+    /// A full bucket.
+    #[must_use]
+    pub fn new(quota: Quota, now: Instant) -> Self {
+        Self {
+            rate: u128::from(quota.rate),
+            capacity: u128::from(quota.burst) * NANOS_PER_SEC,
+            used: 0,
+            last: now,
+        }
+    }
+
+    /// The tokens available at `now`.
+    #[must_use]
+    pub fn available_at(&self, now: Instant) -> u64 {
+        let free = self.capacity.saturating_sub(self.used_at(now)) / NANOS_PER_SEC;
+        u64::try_from(free).unwrap_or(u64::MAX)
+    }
+
+    /// Whether the bucket is full at `now`, as a new one.
+    #[must_use]
+    pub fn is_full_at(&self, now: Instant) -> bool {
+        self.used_at(now) == 0
+    }
+
+    /// Take `tokens` if available at `now`; more than the burst never
+    /// are.
+    pub fn try_acquire_at(&mut self, tokens: u64, now: Instant) -> bool {
+        self.refill(now);
+
+        let used = self.used + u128::from(tokens) * NANOS_PER_SEC;
+        if used > self.capacity {
+            return false;
+        }
+        self.used = used;
+        true
+    }
+
+    /// Take `tokens` at `now`, even ahead of the rate, returning the
+    /// time to wait before using them.
+    pub fn reserve_at(&mut self, tokens: u64, now: Instant) -> Duration {
+        self.refill(now);
+
+        self.used += u128::from(tokens) * NANOS_PER_SEC;
+        let over = self.used.saturating_sub(self.capacity);
+        let wait = over.div_ceil(self.rate);
+        Duration::from_nanos(u64::try_from(wait).unwrap_or(u64::MAX))
+    }
+
+    fn used_at(&self, now: Instant) -> u128 {
+        let elapsed = now.saturating_duration_since(self.last).as_nanos();
+        self.used.saturating_sub(elapsed.saturating_mul(self.rate))
+    }
+
+    fn refill(&mut self, now: Instant) {
+        self.used = self.used_at(now);
+        self.last = self.last.max(now);
+    }
+}
+
+/// A bucket per key, all with the same quota.
+#[derive(Debug)]
+pub struct RateLimiter<K> {
+    quota: Quota,
+    buckets: Mutex<HashMap<K, Bucket>>,
+}
+
+impl<K: Hash + Eq> RateLimiter<K> {
+    #[must_use]
+    pub fn new(quota: Quota) -> Self {
+        Self {
+            quota,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    #[must_use]
+    pub fn quota(&self) -> Quota {
+        self.quota
+    }
+
+    /// Take `tokens` from the bucket of `key` if available.
+    ///
+    /// # Panics
+    /// * Panics when the lock is poisoned.
+    pub fn try_acquire(&self, key: K, tokens: u64) -> bool {
+        let now = Instant::now();
+        self.with_bucket(key, now, |bucket| bucket.try_acquire_at(tokens, now))
+    }
+
+    /// Take `tokens` from the bucket of `key`, returning the time to
+    /// wait before using them.
+    ///
+    /// # Panics
+    /// * Panics when the lock is poisoned.
+    pub fn reserve(&self, key: K, tokens: u64) -> Duration {
+        let now = Instant::now();
+        self.with_bucket(key, now, |bucket| bucket.reserve_at(tokens, now))
+    }
+
+    /// Take `tokens` from the bucket of `key`, waiting until they are
+    /// available; they are taken even if the future is dropped while
+    /// waiting.
+    ///
+    /// # Panics
+    /// * Panics when the lock is poisoned.
+    pub async fn acquire(&self, key: K, tokens: u64) {
+        let wait = self.reserve(key, tokens);
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Drop the buckets full again, returning how many.
+    ///
+    /// # Panics
+    /// * Panics when the lock is poisoned.
+    pub fn purge(&self) -> usize {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let len = buckets.len();
+        buckets.retain(|_, bucket| !bucket.is_full_at(now));
+        len - buckets.len()
+    }
+
+    /// The number of buckets.
+    ///
+    /// # Panics
+    /// * Panics when the lock is poisoned.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.buckets.lock().unwrap().len()
+    }
+
+    /// # Panics
+    /// * Panics when the lock is poisoned.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn with_bucket<T>(&self, key: K, now: Instant, f: impl FnOnce(&mut Bucket) -> T) -> T {
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets
+            .entry(key)
+            .or_insert_with(|| Bucket::new(self.quota, now));
+        f(bucket)
+    }
+}
@@ -0,0 +1,97 @@
+//! Token bucket properties: a new bucket gives its burst at once, a
+//! drained one is refilled at the rate, and no sequence of requests
+//! gets more than the burst plus the rate.
+use std::time::Duration;
+
+use proptest::prelude::*;
+
+use tokio::time::Instant;
+
+use protohackers_ratelimit::{Bucket, Quota};
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+fn quota() -> impl Strategy<Value = Quota> {
+    (1..10_000_u64, 0..100_u64).prop_map(|(rate, burst)| Quota::per_second(rate).with_burst(burst))
+}
+
+/// The time to refill `tokens` at `rate`, rounded up to the
+/// nanosecond.
+fn refill_time(tokens: u64, rate: u64) -> Duration {
+    let nanos = (u128::from(tokens) * NANOS_PER_SEC).div_ceil(u128::from(rate));
+    Duration::from_nanos(u64::try_from(nanos).unwrap())
+}
+
+/// The most tokens a bucket can give in `elapsed`.
+fn max_tokens(quota: Quota, elapsed: Duration) -> u128 {
+    u128::from(quota.burst()) + elapsed.as_nanos() * u128::from(quota.rate()) / NANOS_PER_SEC
+}
+
+proptest! {
+    #[test]
+    fn test_burst(quota in quota()) {
+        let now = Instant::now();
+        let mut bucket = Bucket::new(quota, now);
+        prop_assert!(bucket.is_full_at(now));
+        prop_assert_eq!(quota.burst(), bucket.available_at(now));
+
+        for _ in 0..quota.burst() {
+            prop_assert!(bucket.try_acquire_at(1, now));
+        }
+        prop_assert!(!bucket.try_acquire_at(1, now));
+        prop_assert_eq!(0, bucket.available_at(now));
+    }
+
+    #[test]
+    fn test_refill(quota in quota(), tokens in 0..200_u64) {
+        let now = Instant::now();
+        let mut bucket = Bucket::new(quota, now);
+        prop_assert!(bucket.try_acquire_at(quota.burst(), now));
+
+        let later = now + refill_time(tokens, quota.rate());
+        prop_assert_eq!(tokens.min(quota.burst()), bucket.available_at(later));
+        prop_assert_eq!(tokens >= quota.burst(), bucket.is_full_at(later));
+        if tokens > 0 {
+            let earlier = later - Duration::from_nanos(1);
+            prop_assert_eq!((tokens - 1).min(quota.burst()), bucket.available_at(earlier));
+        }
+    }
+
+    #[test]
+    fn test_never_over_rate(
+        quota in quota(),
+        requests in prop::collection::vec((0..2_000_u64, 0..120_u64), 0..64),
+    ) {
+        let start = Instant::now();
+        let mut bucket = Bucket::new(quota, start);
+
+        let (mut now, mut acquired) = (start, 0);
+        for (delay, tokens) in requests {
+            now += Duration::from_millis(delay);
+            if bucket.try_acquire_at(tokens, now) {
+                acquired += u128::from(tokens);
+            } else {
+                prop_assert!(tokens > bucket.available_at(now));
+            }
+            prop_assert!(acquired <= max_tokens(quota, now - start));
+        }
+    }
+
+    #[test]
+    fn test_reserve_paces(quota in quota(), requests in prop::collection::vec(0..120_u64, 0..32)) {
+        let now = Instant::now();
+        let mut bucket = Bucket::new(quota, now);
+
+        let mut reserved = 0;
+        for tokens in requests {
+            let wait = bucket.reserve_at(tokens, now);
+            reserved += u128::from(tokens);
+
+            // not used before the rate allows, nor waiting longer
+            prop_assert!(reserved <= max_tokens(quota, wait));
+            if let Some(earlier) = wait.checked_sub(Duration::from_nanos(1)) {
+                prop_assert!(reserved > max_tokens(quota, earlier));
+            }
+        }
+    }
+}
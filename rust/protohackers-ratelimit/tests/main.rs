@@ -0,0 +1,72 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use protohackers_ratelimit::{Quota, RateLimiter};
+
+#[tokio::test(start_paused = true)]
+async fn test_acquire() {
+    let limiter = RateLimiter::new(Quota::per_second(10).with_burst(2));
+
+    let start = Instant::now();
+    limiter.acquire("a", 2).await;
+    assert_eq!(start, Instant::now());
+
+    limiter.acquire("a", 1).await;
+    assert_eq!(Duration::from_millis(100), start.elapsed());
+
+    // ahead of the rate
+    limiter.acquire("a", 5).await;
+    assert_eq!(Duration::from_millis(600), start.elapsed());
+    assert!(!limiter.try_acquire("a", 1));
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_keys() {
+    let limiter = RateLimiter::new(Quota::per_second(1).with_burst(1));
+
+    assert!(limiter.try_acquire(1, 1));
+    assert!(!limiter.try_acquire(1, 1));
+    assert!(limiter.try_acquire(2, 1));
+    assert_eq!(2, limiter.len());
+
+    tokio::time::advance(Duration::from_secs(1)).await;
+    assert!(limiter.try_acquire(1, 1));
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_purge() {
+    let limiter = RateLimiter::new(Quota::per_second(10));
+
+    assert!(limiter.try_acquire("a", 10));
+    assert!(limiter.try_acquire("b", 1));
+    assert_eq!(0, limiter.purge());
+
+    tokio::time::advance(Duration::from_millis(100)).await;
+    assert_eq!(1, limiter.purge());
+    assert_eq!(1, limiter.len());
+
+    tokio::time::advance(Duration::from_millis(900)).await;
+    assert_eq!(1, limiter.purge());
+    assert!(limiter.is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_shared() {
+    let limiter = Arc::new(RateLimiter::new(Quota::per_second(100).with_burst(0)));
+
+    let start = Instant::now();
+    let tasks = (0..10)
+        .map(|_| {
+            let limiter = limiter.clone();
+            tokio::spawn(async move { limiter.acquire((), 10).await })
+        })
+        .collect::<Vec<_>>();
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    // 100 tokens paced at 100 per second
+    assert_eq!(Duration::from_secs(1), start.elapsed());
+}
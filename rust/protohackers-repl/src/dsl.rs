@@ -0,0 +1,219 @@
+//! The language of the messages, a line each: the name of the message
+//! then its fields as `name=value`, separated by blanks, a value with
+//! blanks in double quotes:
+//!
+//! ```raw
+//! create-policy species="long-tailed rat" action=cull
+//! site-visit site=12345 populations=dog:1,cat:2
+//! ```
+//!
+//! The lists are separated by commas, the parts of an item by colons.
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, ensure};
+
+/// A line of the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Command {
+    pub name: String,
+
+    /// The line after the name, the fields or the payload of `hex` and
+    /// `text`.
+    pub rest: String,
+}
+
+impl Command {
+    /// The command of `line`, `None` when blank or a `#` comment.
+    #[must_use]
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (name, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        Some(Self {
+            name: name.to_string(),
+            rest: rest.trim_start().to_string(),
+        })
+    }
+
+    /// # Errors
+    /// * Error when a field is not `name=value`, repeated, or a quote
+    ///   is not closed.
+    pub fn fields(&self) -> Result<Fields<'_>, anyhow::Error> {
+        let mut values = BTreeMap::new();
+        for token in tokens(&self.rest)? {
+            let (name, value) = token
+                .split_once('=')
+                .ok_or_else(|| anyhow!("no value for {token}"))?;
+            let value = value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .unwrap_or(value);
+            ensure!(
+                values.insert(name, value).is_none(),
+                "repeated field: {name}"
+            );
+        }
+        Ok(Fields { values })
+    }
+}
+
+/// The blank separated tokens of `text`, the blanks in double quotes
+/// kept.
+fn tokens(text: &str) -> Result<Vec<&str>, anyhow::Error> {
+    let mut tokens = vec![];
+    let (mut start, mut quoted) = (None, false);
+    for (i, c) in text.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if let Some(start) = start.take() {
+                    tokens.push(&text[start..i]);
+                }
+                continue;
+            }
+            _ => {}
+        }
+        start.get_or_insert(i);
+    }
+    ensure!(!quoted, "unclosed quote");
+    if let Some(start) = start {
+        tokens.push(&text[start..]);
+    }
+    Ok(tokens)
+}
+
+/// The fields of a command, taken one by one by the encoders.
+#[derive(Debug)]
+pub struct Fields<'a> {
+    values: BTreeMap<&'a str, &'a str>,
+}
+
+impl<'a> Fields<'a> {
+    /// Take the field `name`.
+    ///
+    /// # Errors
+    /// * Error when the field is missing or invalid.
+    pub fn get<T: FromStr>(&mut self, name: &str) -> Result<T, anyhow::Error> {
+        let value = self
+            .values
+            .remove(name)
+            .ok_or_else(|| anyhow!("missing field: {name}"))?;
+        parse(name, value)
+    }
+
+    /// Take the field `name`, `default` when missing.
+    ///
+    /// # Errors
+    /// * Error when the field is invalid.
+    pub fn get_or<T: FromStr>(&mut self, name: &str, default: T) -> Result<T, anyhow::Error> {
+        match self.values.remove(name) {
+            Some(value) => parse(name, value),
+            None => Ok(default),
+        }
+    }
+
+    /// Take the comma separated items of the field `name`, none when
+    /// missing.
+    #[must_use]
+    pub fn list(&mut self, name: &str) -> Vec<&'a str> {
+        self.values
+            .remove(name)
+            .map(|value| value.split(',').filter(|item| !item.is_empty()).collect())
+            .unwrap_or_default()
+    }
+
+    /// All the fields have been taken.
+    ///
+    /// # Errors
+    /// * Error when some field is unknown.
+    pub fn finish(self) -> Result<(), anyhow::Error> {
+        if let Some(name) = self.values.keys().next() {
+            bail!("unknown field: {name}");
+        }
+        Ok(())
+    }
+}
+
+fn parse<T: FromStr>(name: &str, value: &str) -> Result<T, anyhow::Error> {
+    value
+        .parse()
+        .map_err(|_| anyhow!("invalid {name}: {value}"))
+}
+
+/// The `N` colon separated parts of a list item.
+///
+/// # Errors
+/// * Error when the item has another number of parts.
+pub fn parts<const N: usize>(item: &str) -> Result<[&str; N], anyhow::Error> {
+    let parts = item.split(':').collect::<Vec<_>>();
+    parts
+        .try_into()
+        .map_err(|_| anyhow!("expected {N} parts separated by ':' in {item}"))
+}
+
+/// The bytes of the hex digits of `text`, the blanks ignored.
+///
+/// # Errors
+/// * Error when a digit is invalid or the last one is alone.
+pub fn parse_hex(text: &str) -> Result<Vec<u8>, anyhow::Error> {
+    let digits = text
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| {
+            c.to_digit(16)
+                .ok_or_else(|| anyhow!("invalid hex digit: {c}"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    ensure!(digits.len() % 2 == 0, "odd number of hex digits");
+    Ok(digits
+        .chunks(2)
+        .map(|pair| u8::try_from(pair[0] << 4 | pair[1]).unwrap_or_default())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(None, Command::parse("  "));
+        assert_eq!(None, Command::parse("# a comment"));
+
+        let command =
+            Command::parse(" create-policy species=\"long-tailed rat\"  action=cull").unwrap();
+        assert_eq!("create-policy", command.name);
+
+        let mut fields = command.fields().unwrap();
+        assert_eq!("long-tailed rat", fields.get::<String>("species").unwrap());
+        assert!(fields.get::<u32>("species").is_err());
+        assert_eq!(7, fields.get_or("policy", 7).unwrap());
+        assert!(fields.finish().is_err());
+
+        let command = Command::parse("site-visit site=1 populations=dog:1,cat:2").unwrap();
+        let mut fields = command.fields().unwrap();
+        assert_eq!(1, fields.get::<u32>("site").unwrap());
+        let populations = fields.list("populations");
+        assert_eq!(vec!["dog:1", "cat:2"], populations);
+        assert_eq!(["cat", "2"], parts::<2>(populations[1]).unwrap());
+        assert!(parts::<3>(populations[1]).is_err());
+        fields.finish().unwrap();
+
+        assert!(Command::parse("plate plate").unwrap().fields().is_err());
+        assert!(Command::parse("plate a=1 a=2").unwrap().fields().is_err());
+        assert!(Command::parse("error msg=\"oops")
+            .unwrap()
+            .fields()
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_hex() {
+        assert_eq!(vec![0x49, 0x00, 0xff], parse_hex("49 00\tFf").unwrap());
+        assert!(parse_hex("4").is_err());
+        assert!(parse_hex("zz").is_err());
+    }
+}
@@ -0,0 +1,29 @@
+//! A client to explore the protocols by hand, without throwaway
+//! scripts: the messages are typed in a small [language](dsl), sent to
+//! a running server, and its replies are printed decoded with the wire
+//! types of the problems:
+//!
+//! ```raw
+//! $ protohackers-repl --port 10000 p06
+//! i-am-camera road=66 mile=100 limit=60
+//! > 80 00 42 00 64 00 3c
+//! plate plate=UN1X timestamp=0
+//! > 20 04 55 4e 31 58 00 00 00 00
+//! hex ff
+//! > ff
+//! < Error { msg: "invalid msg: 0xff" }
+//! closed
+//! ```
+//!
+//! Every protocol sends raw bytes with `hex` and a line with `text`,
+//! `help` lists its messages.
+pub mod dsl;
+pub mod p02;
+pub mod p06;
+pub mod p11;
+pub mod protocol;
+pub mod session;
+
+pub use dsl::Command;
+pub use protocol::Protocol;
+pub use session::run;
@@ -0,0 +1,72 @@
+//! Send hand written messages to a running server:
+//!
+//! ```raw
+//! protohackers-repl --address 127.0.0.1 --port 10000 p11
+//! ```
+use std::time::Duration;
+
+use anyhow::Context;
+
+use clap::Parser;
+
+use tokio::io::{self, BufReader};
+use tokio::net::TcpStream;
+
+use protohackers_repl::Protocol;
+
+#[derive(clap::Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    #[arg(long, default_value = "127.0.0.1")]
+    address: String,
+
+    #[arg(long, default_value_t = 10000)]
+    port: u16,
+
+    /// At the end of the input, wait for the replies until this number
+    /// of seconds of silence
+    #[arg(long, default_value_t = 1)]
+    linger: u64,
+
+    #[arg(value_enum)]
+    protocol: Protocol,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), anyhow::Error> {
+    let args = Args::parse();
+
+    let address = format!("{}:{}", args.address, args.port);
+    let stream = TcpStream::connect(&address)
+        .await
+        .with_context(|| format!("connecting to {address}"))?;
+
+    protohackers_repl::run(
+        stream,
+        args.protocol,
+        BufReader::new(io::stdin()),
+        &mut io::stdout(),
+        Duration::from_secs(args.linger),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_command() {
+        Args::command().debug_assert();
+    }
+
+    #[test]
+    fn test_args() {
+        let args = Args::parse_from(["repl", "--port", "1234", "p06"]);
+        assert_eq!(1234, args.port);
+        assert_eq!(1, args.linger);
+        assert_eq!(Protocol::P06, args.protocol);
+    }
+}
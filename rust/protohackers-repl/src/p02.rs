@@ -0,0 +1,128 @@
+//! Means to an End: the 9 bytes messages, the extensions, and the
+//! `int32` answers.
+use anyhow::bail;
+
+use bytes::{Buf, BytesMut};
+
+use p02_means_to_an_end_core::{Aggregate, Extensions, BATCH_INSERT, HELLO, PROTOCOL_VERSION};
+
+use crate::dsl::{self, Command};
+
+pub const HELP: &str = "\
+insert timestamp=N price=N
+query mintime=N maxtime=N
+min|max|count mintime=N maxtime=N
+batch-insert prices=TIMESTAMP:PRICE,...
+hello [version=N] [extensions=BITS]";
+
+/// # Errors
+/// * Error when the message is unknown or its fields invalid.
+pub fn encode(command: &Command) -> Result<Vec<u8>, anyhow::Error> {
+    let mut fields = command.fields()?;
+    let mut message = vec![];
+    match command.name.as_str() {
+        "insert" => {
+            message.push(b'I');
+            message.extend(fields.get::<i32>("timestamp")?.to_be_bytes());
+            message.extend(fields.get::<i32>("price")?.to_be_bytes());
+        }
+        name @ ("query" | "min" | "max" | "count") => {
+            message.push(match name {
+                "min" => Aggregate::Min.kind(),
+                "max" => Aggregate::Max.kind(),
+                "count" => Aggregate::Count.kind(),
+                _ => b'Q',
+            });
+            message.extend(fields.get::<i32>("mintime")?.to_be_bytes());
+            message.extend(fields.get::<i32>("maxtime")?.to_be_bytes());
+        }
+        "batch-insert" => {
+            let prices = fields.list("prices");
+            let Ok(count) = u16::try_from(prices.len()) else {
+                bail!("too many prices: {}", prices.len());
+            };
+            message.push(BATCH_INSERT);
+            message.extend(count.to_be_bytes());
+            for price in prices {
+                let [timestamp, price] = dsl::parts(price)?;
+                for value in [timestamp, price] {
+                    let Ok(value) = value.parse::<i32>() else {
+                        bail!("invalid price: {timestamp}:{price}");
+                    };
+                    message.extend(value.to_be_bytes());
+                }
+            }
+        }
+        "hello" => {
+            message.push(HELLO);
+            message.extend(fields.get_or("version", PROTOCOL_VERSION)?.to_be_bytes());
+            message.extend(
+                fields
+                    .get_or("extensions", Extensions::ALL.bits())?
+                    .to_be_bytes(),
+            );
+        }
+        name => bail!("unknown message: {name}"),
+    }
+    fields.finish()?;
+    Ok(message)
+}
+
+/// The next answer of `buffer`.
+pub fn decode(buffer: &mut BytesMut) -> Option<String> {
+    (buffer.len() >= 4).then(|| buffer.get_i32().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use p02_means_to_an_end_core::{Message, MessageDecoder};
+
+    use super::*;
+
+    fn decode_message(line: &str) -> Message {
+        let bytes = encode(&Command::parse(line).unwrap()).unwrap();
+        let (message, len) = MessageDecoder::with_negotiation(Extensions::ALL)
+            .decode(&bytes)
+            .unwrap()
+            .unwrap();
+        assert_eq!(bytes.len(), len);
+        message
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(
+            Message::Insert {
+                timestamp: 12345,
+                price: 101
+            },
+            decode_message("insert timestamp=12345 price=101")
+        );
+        assert_eq!(
+            Message::Query {
+                mintime: 1000,
+                maxtime: 100_000
+            },
+            decode_message("query mintime=1000 maxtime=100000")
+        );
+        assert_eq!(
+            Message::Hello {
+                version: PROTOCOL_VERSION,
+                accepted: Extensions::ALL
+            },
+            decode_message("hello")
+        );
+
+        assert!(encode(&Command::parse("insert timestamp=1").unwrap()).is_err());
+        assert!(encode(&Command::parse("insert timestamp=1 price=2 x=3").unwrap()).is_err());
+        assert!(encode(&Command::parse("delete timestamp=1").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_decode() {
+        let mut buffer = BytesMut::from(&[0, 0, 0, 101, 0xff, 0xff][..]);
+        assert_eq!(Some("101".to_string()), decode(&mut buffer));
+        assert_eq!(None, decode(&mut buffer));
+        assert_eq!(2, buffer.len());
+    }
+}
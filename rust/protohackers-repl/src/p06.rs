@@ -0,0 +1,170 @@
+//! Speed Daemon: the messages of the cameras, the dispatchers and the
+//! server, as the [`wire`](p06_speed_daemon::wire) types.
+use std::fmt::Debug;
+use std::io;
+
+use anyhow::bail;
+
+use bytes::{Buf, BytesMut};
+
+use p06_speed_daemon::wire::{
+    Error, Heartbeat, IAmCamera, IAmDispatcher, Plate, ReadError, ReadFrom, TaggedMessage, Ticket,
+    WantHeartbeat, WriteTo,
+};
+
+use crate::dsl::Command;
+
+pub const HELP: &str = "\
+i-am-camera road=N mile=N limit=N
+i-am-dispatcher roads=N,...
+plate plate=S timestamp=N
+want-heartbeat interval=DECISECONDS
+ticket plate=S road=N mile1=N timestamp1=N mile2=N timestamp2=N speed=N
+heartbeat
+error msg=S";
+
+/// # Errors
+/// * Error when the message is unknown or its fields invalid.
+pub async fn encode(command: &Command) -> Result<Vec<u8>, anyhow::Error> {
+    let mut fields = command.fields()?;
+    let mut message = vec![];
+    match command.name.as_str() {
+        "i-am-camera" => {
+            IAmCamera {
+                road: fields.get("road")?,
+                mile: fields.get("mile")?,
+                limit: fields.get("limit")?,
+            }
+            .write_to(&mut message)
+            .await?;
+        }
+        "i-am-dispatcher" => {
+            let roads = fields
+                .list("roads")
+                .into_iter()
+                .map(str::parse)
+                .collect::<Result<_, _>>();
+            let Ok(roads) = roads else {
+                bail!("invalid roads");
+            };
+            IAmDispatcher { roads }.write_to(&mut message).await?;
+        }
+        "plate" => {
+            Plate {
+                plate: fields.get("plate")?,
+                timestamp: fields.get("timestamp")?,
+            }
+            .write_to(&mut message)
+            .await?;
+        }
+        "want-heartbeat" => {
+            WantHeartbeat {
+                interval: fields.get("interval")?,
+            }
+            .write_to(&mut message)
+            .await?;
+        }
+        "ticket" => {
+            Ticket {
+                plate: fields.get("plate")?,
+                road: fields.get("road")?,
+                mile1: fields.get("mile1")?,
+                timestamp1: fields.get("timestamp1")?,
+                mile2: fields.get("mile2")?,
+                timestamp2: fields.get("timestamp2")?,
+                speed: fields.get("speed")?,
+            }
+            .write_to(&mut message)
+            .await?;
+        }
+        "heartbeat" => Heartbeat.write_to(&mut message).await?,
+        "error" => {
+            Error {
+                msg: fields.get("msg")?,
+            }
+            .write_to(&mut message)
+            .await?;
+        }
+        name => bail!("unknown message: {name}"),
+    }
+    fields.finish()?;
+    Ok(message)
+}
+
+/// The next message of `buffer`, `None` when more bytes are needed.
+///
+/// # Errors
+/// * Error when the type is unknown.
+pub async fn decode(buffer: &mut BytesMut) -> Result<Option<String>, anyhow::Error> {
+    match buffer.first() {
+        None => Ok(None),
+        Some(&Error::TAG) => read::<Error>(buffer).await,
+        Some(&Plate::TAG) => read::<Plate>(buffer).await,
+        Some(&Ticket::TAG) => read::<Ticket>(buffer).await,
+        Some(&WantHeartbeat::TAG) => read::<WantHeartbeat>(buffer).await,
+        Some(&Heartbeat::TAG) => read::<Heartbeat>(buffer).await,
+        Some(&IAmCamera::TAG) => read::<IAmCamera>(buffer).await,
+        Some(&IAmDispatcher::TAG) => read::<IAmDispatcher>(buffer).await,
+        Some(&tag) => Err(ReadError::InvalidMessage(tag).into()),
+    }
+}
+
+async fn read<M: ReadFrom + Debug>(buffer: &mut BytesMut) -> Result<Option<String>, anyhow::Error> {
+    let mut src = &buffer[..];
+    match M::read_from(&mut src).await {
+        Ok(message) => {
+            let len = buffer.len() - src.len();
+            buffer.advance(len);
+            Ok(Some(format!("{message:?}")))
+        }
+        Err(ReadError::InternalError(err)) if err.kind() == io::ErrorKind::UnexpectedEof => {
+            Ok(None)
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_encode() {
+        assert_eq!(
+            vec![0x20, 0x04, 0x55, 0x4e, 0x31, 0x58, 0x00, 0x00, 0x03, 0xe8],
+            encode(&Command::parse("plate plate=UN1X timestamp=1000").unwrap())
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            vec![0x81, 0x03, 0x00, 0x42, 0x01, 0x70, 0x13, 0x88],
+            encode(&Command::parse("i-am-dispatcher roads=66,368,5000").unwrap())
+                .await
+                .unwrap()
+        );
+        assert!(encode(&Command::parse("i-am-camera road=66").unwrap())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_decode() {
+        let mut buffer = BytesMut::from(&[0x10, 0x03, 0x62, 0x61][..]);
+        assert_eq!(None, decode(&mut buffer).await.unwrap());
+        assert_eq!(4, buffer.len());
+
+        buffer.extend_from_slice(&[0x64, 0x41]);
+        assert_eq!(
+            Some("Error { msg: \"bad\" }".to_string()),
+            decode(&mut buffer).await.unwrap()
+        );
+        assert_eq!(
+            Some("Heartbeat".to_string()),
+            decode(&mut buffer).await.unwrap()
+        );
+        assert!(buffer.is_empty());
+
+        buffer.extend_from_slice(&[0xff]);
+        assert!(decode(&mut buffer).await.is_err());
+    }
+}
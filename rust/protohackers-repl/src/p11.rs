@@ -0,0 +1,143 @@
+//! Pest Control: the packets of the site visitors and the authority
+//! servers, through the [`PacketCodec`] of the server.
+use anyhow::bail;
+
+use bytes::BytesMut;
+
+use tokio_util::codec::{Decoder, Encoder};
+
+use p11_pest_control::codec::packets::create_policy::PolicyAction;
+use p11_pest_control::codec::packets::{
+    create_policy, delete_policy, dial_authority, error, hello, ok, policy_result, site_visit,
+    target_populations, Packet, PacketCodec,
+};
+
+use crate::dsl::{self, Command};
+
+pub const HELP: &str = "\
+hello [protocol=S] [version=N]
+error message=S
+ok
+dial-authority site=N
+target-populations site=N populations=SPECIES:MIN:MAX,...
+create-policy species=S action=cull|conserve
+delete-policy policy=N
+policy-result policy=N
+site-visit site=N populations=SPECIES:COUNT,...";
+
+/// # Errors
+/// * Error when the message is unknown or its fields invalid.
+pub fn encode(command: &Command) -> Result<Vec<u8>, anyhow::Error> {
+    let mut fields = command.fields()?;
+    let packet = match command.name.as_str() {
+        "hello" => Packet::Hello(hello::Packet {
+            protocol: fields.get_or("protocol", hello::PESTCONTROL_PROTOCOL.to_string())?,
+            version: fields.get_or("version", hello::PESTCONTROL_VERSION)?,
+        }),
+        "error" => error::Packet::new(fields.get::<String>("message")?).into(),
+        "ok" => ok::Packet.into(),
+        "dial-authority" => dial_authority::Packet::new(fields.get("site")?).into(),
+        "target-populations" => {
+            let site = fields.get("site")?;
+            let populations = fields
+                .list("populations")
+                .into_iter()
+                .map(|population| {
+                    let [species, min, max] = dsl::parts(population)?;
+                    match (min.parse(), max.parse()) {
+                        (Ok(min), Ok(max)) => {
+                            Ok(target_populations::Population::new(species, min, max))
+                        }
+                        _ => bail!("invalid population: {population}"),
+                    }
+                })
+                .collect::<Result<_, _>>()?;
+            target_populations::Packet::new(site, populations).into()
+        }
+        "create-policy" => {
+            let species = fields.get::<String>("species")?;
+            let action = match fields.get::<String>("action")?.as_str() {
+                "cull" => PolicyAction::Cull,
+                "conserve" => PolicyAction::Conserve,
+                action => bail!("invalid action: {action}"),
+            };
+            create_policy::Packet::new(species, action).into()
+        }
+        "delete-policy" => delete_policy::Packet::new(fields.get("policy")?).into(),
+        "policy-result" => policy_result::Packet::new(fields.get("policy")?).into(),
+        "site-visit" => {
+            let site = fields.get("site")?;
+            let populations = fields
+                .list("populations")
+                .into_iter()
+                .map(|population| {
+                    let [species, count] = dsl::parts(population)?;
+                    match count.parse() {
+                        Ok(count) => Ok(site_visit::Population::new(species, count)),
+                        Err(_) => bail!("invalid population: {population}"),
+                    }
+                })
+                .collect::<Result<_, _>>()?;
+            site_visit::Packet::new(site, populations).into()
+        }
+        name => bail!("unknown message: {name}"),
+    };
+    fields.finish()?;
+
+    let mut message = BytesMut::new();
+    PacketCodec::new().encode(packet, &mut message)?;
+    Ok(message.to_vec())
+}
+
+/// The next packet of `buffer`, `None` when more bytes are needed.
+///
+/// # Errors
+/// * Error when the packet is unknown or invalid.
+pub fn decode(buffer: &mut BytesMut) -> Result<Option<String>, anyhow::Error> {
+    let packet = PacketCodec::new().decode(buffer)?;
+    Ok(packet.map(|packet| format!("{packet:?}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(line: &str) -> String {
+        let mut buffer = BytesMut::from(&encode(&Command::parse(line).unwrap()).unwrap()[..]);
+        let packet = decode(&mut buffer).unwrap().unwrap();
+        assert!(buffer.is_empty());
+        packet
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(
+            "Hello(Packet { protocol: \"pestcontrol\", version: 1 })",
+            roundtrip("hello")
+        );
+        assert_eq!(
+            "CreatePolicy(Packet { species: \"long-tailed rat\", action: Cull })",
+            roundtrip("create-policy species=\"long-tailed rat\" action=cull")
+        );
+        assert_eq!(
+            "SiteVisit(Packet { site: 12345, populations: [\
+             Population { species: \"dog\", count: 1 }, \
+             Population { species: \"cat\", count: 2 }] })",
+            roundtrip("site-visit site=12345 populations=dog:1,cat:2")
+        );
+        assert!(encode(&Command::parse("create-policy species=dog action=pet").unwrap()).is_err());
+        assert!(encode(&Command::parse("site-visit site=1 populations=dog").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_decode() {
+        let mut buffer = BytesMut::from(&[0x52, 0x00, 0x00][..]);
+        assert_eq!(None, decode(&mut buffer).unwrap());
+
+        buffer.extend_from_slice(&[0x00, 0x06, 0xa8]);
+        assert_eq!(Some("Ok(Packet)".to_string()), decode(&mut buffer).unwrap());
+
+        buffer.extend_from_slice(&[0x99]);
+        assert!(decode(&mut buffer).is_err());
+    }
+}
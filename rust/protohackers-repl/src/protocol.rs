@@ -0,0 +1,96 @@
+//! The protocols spoken by the client.
+use anyhow::bail;
+
+use bytes::BytesMut;
+
+use protohackers_server::hex_dump::hex_dump;
+
+use crate::dsl::{self, Command};
+use crate::{p02, p06, p11};
+
+/// The longest hex dump of a reply.
+const DUMP_LIMIT: usize = 1024;
+
+const HELP: &str = "\
+hex DIGITS      the bytes, e.g. hex 49 00 00 30 39
+text LINE       the line and a newline
+help            this help
+quit            close the connection";
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    /// Only `hex` and `text`, the replies as hex dumps
+    Raw,
+
+    /// Only `hex` and `text`, the replies as lines
+    Lines,
+
+    /// Means to an End
+    P02,
+
+    /// Speed Daemon
+    P06,
+
+    /// Pest Control
+    P11,
+}
+
+impl Protocol {
+    /// The messages of the protocol, one per line.
+    #[must_use]
+    pub fn help(self) -> String {
+        let messages = match self {
+            Self::Raw | Self::Lines => "",
+            Self::P02 => p02::HELP,
+            Self::P06 => p06::HELP,
+            Self::P11 => p11::HELP,
+        };
+        format!("{messages}\n{HELP}").trim_start().to_string()
+    }
+
+    /// The bytes of `command`.
+    ///
+    /// # Errors
+    /// * Error when the message is unknown or its fields invalid.
+    pub async fn encode(self, command: &Command) -> Result<Vec<u8>, anyhow::Error> {
+        match (command.name.as_str(), self) {
+            ("hex", _) => dsl::parse_hex(&command.rest),
+            ("text", _) => Ok(format!("{}\n", command.rest).into_bytes()),
+            (_, Self::P02) => p02::encode(command),
+            (_, Self::P06) => p06::encode(command).await,
+            (_, Self::P11) => p11::encode(command),
+            (name, Self::Raw | Self::Lines) => bail!("unknown message: {name}"),
+        }
+    }
+
+    /// The replies in `buffer`, removed from it; the bytes after an
+    /// invalid reply are dumped and dropped.
+    pub async fn decode(self, buffer: &mut BytesMut) -> Vec<String> {
+        let mut replies = vec![];
+        loop {
+            let reply = match self {
+                Self::Raw if buffer.is_empty() => Ok(None),
+                Self::Raw => Ok(Some(hex_dump(&buffer.split(), DUMP_LIMIT))),
+                Self::Lines => Ok(decode_line(buffer)),
+                Self::P02 => Ok(p02::decode(buffer)),
+                Self::P06 => p06::decode(buffer).await,
+                Self::P11 => p11::decode(buffer),
+            };
+            match reply {
+                Ok(Some(reply)) => replies.push(reply),
+                Ok(None) => break,
+                Err(err) => {
+                    replies.push(format!("{err}\n{}", hex_dump(&buffer.split(), DUMP_LIMIT)));
+                    break;
+                }
+            }
+        }
+        replies
+    }
+}
+
+fn decode_line(buffer: &mut BytesMut) -> Option<String> {
+    let end = buffer.iter().position(|&b| b == b'\n')?;
+    let line = buffer.split_to(end + 1);
+    Some(String::from_utf8_lossy(&line[..end]).into_owned())
+}
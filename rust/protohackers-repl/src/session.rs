@@ -0,0 +1,98 @@
+//! The session with the server: the commands of the input sent as
+//! they are read, the replies printed as they arrive.
+use std::time::Duration;
+
+use anyhow::Context;
+
+use bytes::BytesMut;
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time;
+
+use crate::dsl::Command;
+use crate::Protocol;
+
+/// Run the commands of `input` against the server of `stream`, writing
+/// the bytes sent, as `> ...`, and the replies, as `< ...`, to
+/// `output`; at the end of the input, the replies are waited for up to
+/// `linger` of silence.
+///
+/// # Errors
+/// * Error when the connection fails, or the output can not be
+///   written.
+pub async fn run<I, O>(
+    stream: TcpStream,
+    protocol: Protocol,
+    input: I,
+    output: &mut O,
+    linger: Duration,
+) -> Result<(), anyhow::Error>
+where
+    I: AsyncBufRead + Unpin,
+    O: AsyncWrite + Unpin,
+{
+    let (mut read, mut write) = stream.into_split();
+    let mut lines = input.lines();
+    let mut buffer = BytesMut::with_capacity(4096);
+    let mut ended = false;
+
+    loop {
+        tokio::select! {
+            line = lines.next_line(), if !ended => {
+                // the end of the input quits
+                let line = line
+                    .context("reading the input")?
+                    .unwrap_or_else(|| "quit".to_string());
+                match Command::parse(&line) {
+                    None => {}
+                    Some(command) if command.name == "quit" => {
+                        write.shutdown().await.context("closing")?;
+                        ended = true;
+                    }
+                    Some(command) if command.name == "help" => {
+                        print(output, &protocol.help()).await?;
+                    }
+                    Some(command) => match protocol.encode(&command).await {
+                        Ok(message) => {
+                            write.write_all(&message).await.context("sending")?;
+                            print(output, &format!("> {}", hex(&message))).await?;
+                        }
+                        Err(err) => print(output, &format!("error: {err}")).await?,
+                    },
+                }
+            }
+
+            n = read.read_buf(&mut buffer) => {
+                let n = n.context("receiving")?;
+                for reply in protocol.decode(&mut buffer).await {
+                    print(output, &format!("< {reply}")).await?;
+                }
+                if n == 0 {
+                    if !buffer.is_empty() {
+                        print(output, &format!("< incomplete: {}", hex(&buffer))).await?;
+                    }
+                    print(output, "closed").await?;
+                    break;
+                }
+            }
+
+            () = time::sleep(linger), if ended => break,
+        }
+    }
+    Ok(())
+}
+
+async fn print<O: AsyncWrite + Unpin>(output: &mut O, text: &str) -> Result<(), anyhow::Error> {
+    output.write_all(format!("{text}\n").as_bytes()).await?;
+    output.flush().await?;
+    Ok(())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
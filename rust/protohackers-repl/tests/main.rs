@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+use tokio::net::{TcpListener, TcpStream};
+
+use protohackers_repl::Protocol;
+
+use protohackers_server::Server;
+
+const LINGER: Duration = Duration::from_millis(300);
+
+async fn listener() -> (TcpListener, String) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    (listener, address)
+}
+
+/// The output of the session of `input` with the server at `address`.
+async fn session(address: &str, protocol: Protocol, input: &str) -> String {
+    let stream = TcpStream::connect(address).await.unwrap();
+    let mut output = vec![];
+    protohackers_repl::run(stream, protocol, input.as_bytes(), &mut output, LINGER)
+        .await
+        .unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+#[tokio::test]
+async fn test_p02() {
+    let (listener, address) = listener().await;
+    tokio::spawn(Server::new(listener).serve(|stream, _| p02_means_to_an_end::handler(stream)));
+
+    let output = session(
+        &address,
+        Protocol::P02,
+        "# the example session\n\
+         insert timestamp=12345 price=101\n\
+         insert timestamp=12346 price=102\n\
+         insert timestamp=12347 price=100\n\
+         insert timestamp=40960 price=5\n\
+         insert price=1\n\
+         query mintime=12288 maxtime=16384\n",
+    )
+    .await;
+
+    assert_eq!(
+        "> 49 00 00 30 39 00 00 00 65\n\
+         > 49 00 00 30 3a 00 00 00 66\n\
+         > 49 00 00 30 3b 00 00 00 64\n\
+         > 49 00 00 a0 00 00 00 00 05\n\
+         error: missing field: timestamp\n\
+         > 51 00 00 30 00 00 00 40 00\n\
+         < 101\n\
+         closed\n",
+        output
+    );
+}
+
+#[tokio::test]
+async fn test_p06() {
+    let (listener, address) = listener().await;
+    tokio::spawn(p06_speed_daemon::run(listener));
+
+    let output = session(
+        &address,
+        Protocol::P06,
+        "i-am-camera road=66 mile=100 limit=60\nhex ff\n",
+    )
+    .await;
+
+    assert_eq!(
+        "> 80 00 42 00 64 00 3c\n\
+         > ff\n\
+         < Error { msg: \"invalid msg: 0xff\" }\n\
+         closed\n",
+        output
+    );
+}
+
+#[tokio::test]
+async fn test_lines() {
+    let (listener, address) = listener().await;
+    tokio::spawn(Server::new(listener).serve(|stream, _| async move {
+        let (mut read, mut write) = tokio::io::split(stream);
+        tokio::io::copy(&mut read, &mut write).await.map(drop)
+    }));
+
+    let output = session(&address, Protocol::Lines, "hello\ntext hello\n").await;
+
+    assert_eq!(
+        "error: unknown message: hello\n\
+         > 68 65 6c 6c 6f 0a\n\
+         < hello\n\
+         closed\n",
+        output
+    );
+}
@@ -0,0 +1,122 @@
+//! The runtime traits of the problem cores.
+//!
+//! The byte streams, the timers and the task spawning the cores need,
+//! so that a protocol is written once and runs both on tokio and on
+//! the WASI runtime:
+//! * `tokio`: the [`tokio`](crate::tokio) adapters;
+//! * `wasi`: the [`wasi`](crate::wasi) adapters, only on the WASI
+//!   targets.
+use std::future::Future;
+use std::io;
+use std::time::Duration;
+
+#[cfg(feature = "tokio")]
+pub mod tokio;
+
+#[cfg(all(feature = "wasi", target_os = "wasi"))]
+pub mod wasi;
+
+/// A source of bytes.
+pub trait Read {
+    /// Read into `buf`, returning the number of bytes; zero is the end
+    /// of the stream.
+    fn read(&mut self, buf: &mut [u8]) -> impl Future<Output = io::Result<usize>>;
+}
+
+/// A sink of bytes.
+pub trait Write {
+    /// Write all of `data`.
+    fn write_all(&mut self, data: &[u8]) -> impl Future<Output = io::Result<()>>;
+
+    fn flush(&mut self) -> impl Future<Output = io::Result<()>>;
+
+    /// Flush and close the writing side.
+    fn shutdown(&mut self) -> impl Future<Output = io::Result<()>>;
+}
+
+/// The clock of a runtime.
+pub trait Timer {
+    /// Wait until `duration` has elapsed.
+    fn sleep(&self, duration: Duration) -> impl Future<Output = ()>;
+}
+
+/// Run a task in the background, detached.
+///
+/// Generic over the future, the tokio tasks must be [`Send`] while
+/// the WASI ones need not.
+pub trait Spawn<F: Future<Output = ()>> {
+    fn spawn(&self, future: F);
+}
+
+impl Read for &[u8] {
+    async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.len());
+        let (data, rest) = self.split_at(len);
+        buf[..len].copy_from_slice(data);
+        *self = rest;
+        Ok(len)
+    }
+}
+
+impl Write for Vec<u8> {
+    async fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        self.extend_from_slice(data);
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    async fn shutdown(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<T: Read + ?Sized> Read for &mut T {
+    fn read(&mut self, buf: &mut [u8]) -> impl Future<Output = io::Result<usize>> {
+        (**self).read(buf)
+    }
+}
+
+impl<T: Write + ?Sized> Write for &mut T {
+    fn write_all(&mut self, data: &[u8]) -> impl Future<Output = io::Result<()>> {
+        (**self).write_all(data)
+    }
+
+    fn flush(&mut self) -> impl Future<Output = io::Result<()>> {
+        (**self).flush()
+    }
+
+    fn shutdown(&mut self) -> impl Future<Output = io::Result<()>> {
+        (**self).shutdown()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Read, Write};
+
+    #[tokio::test]
+    async fn test_slice() {
+        let mut read = b"hello".as_slice();
+        let mut buf = [0; 3];
+
+        assert_eq!(3, read.read(&mut buf).await.unwrap());
+        assert_eq!(b"hel", &buf);
+        assert_eq!(2, read.read(&mut buf).await.unwrap());
+        assert_eq!(b"lo", &buf[..2]);
+        assert_eq!(0, read.read(&mut buf).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_vec() {
+        let mut write = vec![];
+
+        write.write_all(b"hel").await.unwrap();
+        write.write_all(b"lo").await.unwrap();
+        write.shutdown().await.unwrap();
+
+        assert_eq!(b"hello", write.as_slice());
+    }
+}
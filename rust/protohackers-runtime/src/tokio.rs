@@ -0,0 +1,100 @@
+//! The tokio runtime.
+use std::future::Future;
+use std::io;
+use std::time::Duration;
+
+use ::tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{Read, Spawn, Timer, Write};
+
+/// The [`Read`] and [`Write`] adapter of the tokio streams.
+#[derive(Debug)]
+pub struct Compat<T>(pub T);
+
+impl<T> Compat<T> {
+    pub fn new(inner: T) -> Self {
+        Self(inner)
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T: AsyncRead + Unpin> Read for Compat<T> {
+    async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf).await
+    }
+}
+
+impl<T: AsyncWrite + Unpin> Write for Compat<T> {
+    async fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        self.0.write_all(data).await
+    }
+
+    async fn flush(&mut self) -> io::Result<()> {
+        self.0.flush().await
+    }
+
+    async fn shutdown(&mut self) -> io::Result<()> {
+        self.0.shutdown().await
+    }
+}
+
+/// The current tokio runtime.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Tokio;
+
+impl Timer for Tokio {
+    async fn sleep(&self, duration: Duration) {
+        ::tokio::time::sleep(duration).await;
+    }
+}
+
+impl<F: Future<Output = ()> + Send + 'static> Spawn<F> for Tokio {
+    fn spawn(&self, future: F) {
+        ::tokio::spawn(future);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::tokio::sync::oneshot;
+    use ::tokio::time::Instant;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_compat() {
+        let (client, server) = ::tokio::io::duplex(64);
+        let (mut read, mut write) = (Compat::new(server), Compat::new(client));
+
+        write.write_all(b"hello").await.unwrap();
+        write.shutdown().await.unwrap();
+
+        let mut buf = [0; 8];
+        assert_eq!(5, read.read(&mut buf).await.unwrap());
+        assert_eq!(b"hello", &buf[..5]);
+        assert_eq!(0, read.read(&mut buf).await.unwrap());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sleep() {
+        let start = Instant::now();
+
+        Tokio.sleep(Duration::from_secs(3)).await;
+
+        assert!(start.elapsed() >= Duration::from_secs(3));
+    }
+
+    #[tokio::test]
+    async fn test_spawn() {
+        let (sender, receiver) = oneshot::channel();
+
+        Tokio.spawn(async move {
+            sender.send(42).unwrap();
+        });
+
+        assert_eq!(42, receiver.await.unwrap());
+    }
+}
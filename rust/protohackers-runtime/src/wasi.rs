@@ -0,0 +1,67 @@
+//! The WASI runtime.
+use std::future::Future;
+use std::io;
+use std::time::Duration;
+
+use wasi_async::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use wasi_async_runtime::Reactor;
+
+use crate::{Read, Spawn, Timer, Write};
+
+/// The [`Read`] and [`Write`] adapter of the WASI streams.
+#[derive(Debug)]
+pub struct Compat<T>(pub T);
+
+impl<T> Compat<T> {
+    pub fn new(inner: T) -> Self {
+        Self(inner)
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T: AsyncRead> Read for Compat<T> {
+    async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.0.read(buf.len() as u64).await {
+            Ok(data) => {
+                buf[..data.len()].copy_from_slice(&data);
+                Ok(data.len())
+            }
+            Err(err) if err.is_closed() => Ok(0),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+impl<T: AsyncWrite> Write for Compat<T> {
+    async fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        Ok(self.0.write_all(data).await?)
+    }
+
+    async fn flush(&mut self) -> io::Result<()> {
+        Ok(self.0.flush().await?)
+    }
+
+    async fn shutdown(&mut self) -> io::Result<()> {
+        self.flush().await?;
+        Ok(self.0.close().await?)
+    }
+}
+
+/// The WASI runtime of a [`Reactor`].
+#[derive(Clone)]
+pub struct Wasi(pub Reactor);
+
+impl Timer for Wasi {
+    async fn sleep(&self, duration: Duration) {
+        wasi_async::time::sleep(self.0.clone(), duration).await;
+    }
+}
+
+impl<F: Future<Output = ()> + 'static> Spawn<F> for Wasi {
+    fn spawn(&self, future: F) {
+        self.0.spawn(future);
+    }
+}
@@ -0,0 +1,186 @@
+//! The systemd socket activation: the service manager binds the
+//! sockets and passes them from the file descriptor 3 on, announced by
+//! the `LISTEN_PID` and `LISTEN_FDS` environment variables, see
+//! `sd_listen_fds(3)`.
+//!
+//! The sockets are taken at most once per process, the later calls
+//! find none. A TCP server takes all of them, e.g. one per address
+//! family, a UDP server only the first one. Once taken, the sockets
+//! are closed on exec, so that the children do not inherit them. The
+//! variables are left alone: the sockets are taken inside the runtime,
+//! where changing the environment races with the other threads, and a
+//! child does not match `LISTEN_PID` anyway.
+use std::io;
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use socket2::{Socket, Type};
+
+use tokio::net::{TcpListener, UdpSocket};
+
+/// The first passed file descriptor.
+pub const LISTEN_FDS_START: RawFd = 3;
+
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// The number of sockets passed to this process.
+///
+/// # Errors
+/// * Error when the variables are set for this process but invalid.
+pub fn listen_fds() -> io::Result<usize> {
+    parse(
+        std::env::var("LISTEN_PID").ok().as_deref(),
+        std::env::var("LISTEN_FDS").ok().as_deref(),
+        std::process::id(),
+    )
+}
+
+/// The variables are for another process when `LISTEN_PID` is not
+/// this one, e.g. inherited from a parent.
+fn parse(listen_pid: Option<&str>, listen_fds: Option<&str>, pid: u32) -> io::Result<usize> {
+    let (Some(listen_pid), Some(listen_fds)) = (listen_pid, listen_fds) else {
+        return Ok(0);
+    };
+    if listen_pid.parse::<u32>().map_err(invalid)? != pid {
+        return Ok(0);
+    }
+    listen_fds.parse::<usize>().map_err(invalid)
+}
+
+fn invalid(err: std::num::ParseIntError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, err)
+}
+
+/// The first passed socket, as a TCP listener.
+///
+/// # Errors
+/// * Error when the variables are invalid or the socket is not a
+///   listening socket.
+pub fn tcp_listener() -> io::Result<Option<TcpListener>> {
+    Ok(tcp_listeners()?.into_iter().next())
+}
+
+/// All the passed sockets, as TCP listeners.
+///
+/// # Errors
+/// * Error when the variables are invalid or a socket is not a
+///   listening stream socket.
+pub fn tcp_listeners() -> io::Result<Vec<TcpListener>> {
+    take_all()?
+        .into_iter()
+        .map(|socket| {
+            let socket = checked(socket, Type::STREAM)?;
+            if !is_listening(&socket)? {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "passed socket not listening",
+                ));
+            }
+            socket.set_nonblocking(true)?;
+            TcpListener::from_std(socket.into())
+        })
+        .collect()
+}
+
+/// The first passed socket, as a UDP socket.
+///
+/// # Errors
+/// * Error when the variables are invalid or the socket is not a
+///   datagram socket.
+pub fn udp_socket() -> io::Result<Option<UdpSocket>> {
+    take_all()?
+        .into_iter()
+        .next()
+        .map(|socket| {
+            let socket = checked(socket, Type::DGRAM)?;
+            socket.set_nonblocking(true)?;
+            UdpSocket::from_std(socket.into())
+        })
+        .transpose()
+}
+
+/// The passed `socket`, of type `ty`.
+fn checked(socket: Socket, ty: Type) -> io::Result<Socket> {
+    let found = socket.r#type()?;
+    if found != ty {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("passed socket of type {found:?}, not {ty:?}"),
+        ));
+    }
+    Ok(socket)
+}
+
+/// The `SO_ACCEPTCONN` of `socket`.
+fn is_listening(socket: &Socket) -> io::Result<bool> {
+    let mut value: libc::c_int = 0;
+    let mut len = libc::socklen_t::try_from(mem::size_of::<libc::c_int>())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    // SAFETY: the option is written into `value`, of `len` bytes
+    let result = unsafe {
+        libc::getsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_ACCEPTCONN,
+            (&raw mut value).cast(),
+            &raw mut len,
+        )
+    };
+    if result == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(value != 0)
+}
+
+fn take_all() -> io::Result<Vec<Socket>> {
+    let count = listen_fds()?;
+    if count == 0 || TAKEN.swap(true, Ordering::Relaxed) {
+        return Ok(vec![]);
+    }
+
+    let count =
+        RawFd::try_from(count).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    (LISTEN_FDS_START..LISTEN_FDS_START + count)
+        .map(|fd| {
+            // SAFETY: the service manager passed the descriptors to
+            // this process and `TAKEN` makes this the only owner
+            let socket = Socket::from(unsafe { OwnedFd::from_raw_fd(fd) });
+            socket.set_cloexec(true)?;
+            Ok(socket)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(0, parse(None, None, 42).unwrap());
+        assert_eq!(0, parse(Some("42"), None, 42).unwrap());
+        assert_eq!(0, parse(None, Some("1"), 42).unwrap());
+        assert_eq!(1, parse(Some("42"), Some("1"), 42).unwrap());
+        assert_eq!(2, parse(Some("42"), Some("2"), 42).unwrap());
+
+        // for another process
+        assert_eq!(0, parse(Some("41"), Some("1"), 42).unwrap());
+
+        assert!(parse(Some("pid"), Some("1"), 42).is_err());
+        assert!(parse(Some("42"), Some("many"), 42).is_err());
+    }
+
+    #[test]
+    fn test_checked() {
+        let listener = Socket::from(std::net::TcpListener::bind("127.0.0.1:0").unwrap());
+        let listener = checked(listener, Type::STREAM).unwrap();
+        assert!(is_listening(&listener).unwrap());
+
+        let stream = Socket::new(socket2::Domain::IPV4, Type::STREAM, None).unwrap();
+        assert!(!is_listening(&stream).unwrap());
+
+        let datagram = Socket::from(std::net::UdpSocket::bind("127.0.0.1:0").unwrap());
+        assert!(checked(datagram, Type::STREAM).is_err());
+    }
+}
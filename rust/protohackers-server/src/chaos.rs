@@ -0,0 +1,352 @@
+//! Fault injection, for testing: the connections of a server started
+//! with `--chaos seed=42,latency=20,short=0.5,reset=0.001` are delayed
+//! by up to 20 ms before every read and write, read and write short
+//! half of the times, and reset once in a thousand operations; the
+//! same seed, with the same connections, injects the same faults.
+//!
+//! The handlers see the faults through the [`IdleTimeout`] stream
+//! they are given, any other stream can be wrapped in a [`Chaos`].
+//!
+//! [`IdleTimeout`]: crate::IdleTimeout
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{self, Sleep};
+
+use tracing::debug;
+
+/// The faults to inject, parsed from
+/// `seed=N,latency=MILLISECONDS,short=P,reset=P`, every field
+/// optional; `short` and `reset` are the probabilities for every read
+/// and write.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ChaosConfig {
+    pub seed: u64,
+    pub latency: Duration,
+    pub short: f64,
+    pub reset: f64,
+}
+
+impl ChaosConfig {
+    /// The faults of the connection `id`.
+    pub(crate) fn faults(self, id: u64) -> Faults {
+        Faults {
+            config: self,
+            rng: StdRng::seed_from_u64(self.seed ^ id),
+            read: Delay::default(),
+            write: Delay::default(),
+            reset: false,
+        }
+    }
+}
+
+impl FromStr for ChaosConfig {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let mut config = Self::default();
+        for field in spec.split(',').filter(|field| !field.is_empty()) {
+            let (key, value) = field
+                .split_once('=')
+                .ok_or_else(|| format!("no value for {field}"))?;
+            let invalid = |_| format!("invalid {key}: {value}");
+            match key {
+                "seed" => config.seed = value.parse().map_err(invalid)?,
+                "latency" => {
+                    config.latency = Duration::from_millis(value.parse().map_err(invalid)?);
+                }
+                "short" | "reset" => {
+                    let probability = value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|probability| (0.0..=1.0).contains(probability))
+                        .ok_or_else(|| format!("invalid {key}: {value}"))?;
+                    if key == "short" {
+                        config.short = probability;
+                    } else {
+                        config.reset = probability;
+                    }
+                }
+                key => return Err(format!("unknown fault: {key}")),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// A stream with the faults of a [`ChaosConfig`].
+#[derive(Debug)]
+pub struct Chaos<S> {
+    inner: S,
+    faults: Faults,
+}
+
+impl<S> Chaos<S> {
+    /// Wrap `inner`, the connection `id`, seeding its faults with it.
+    pub fn new(inner: S, config: ChaosConfig, id: u64) -> Self {
+        Self {
+            inner,
+            faults: config.faults(id),
+        }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Chaos<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.faults.poll_read(Pin::new(&mut this.inner), cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Chaos<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        this.faults.poll_write(Pin::new(&mut this.inner), cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.faults.check()?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.faults.check()?;
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+/// The state of the faults of a stream.
+#[derive(Debug)]
+pub(crate) struct Faults {
+    config: ChaosConfig,
+    rng: StdRng,
+    read: Delay,
+    write: Delay,
+    /// Every operation fails after a reset.
+    reset: bool,
+}
+
+/// The latency before the pending operation in a direction.
+#[derive(Debug, Default)]
+struct Delay {
+    sleep: Option<Pin<Box<Sleep>>>,
+    elapsed: bool,
+}
+
+impl Delay {
+    fn poll(&mut self, cx: &mut Context<'_>, latency: Duration, rng: &mut StdRng) -> Poll<()> {
+        if self.elapsed || latency.is_zero() {
+            return Poll::Ready(());
+        }
+        let sleep = self
+            .sleep
+            .get_or_insert_with(|| Box::pin(time::sleep(rng.gen_range(Duration::ZERO..=latency))));
+        ready!(sleep.as_mut().poll(cx));
+        self.sleep = None;
+        self.elapsed = true;
+        Poll::Ready(())
+    }
+
+    /// The operation completed, the next one is delayed again.
+    fn done<T>(&mut self, poll: Poll<T>) -> Poll<T> {
+        if poll.is_ready() {
+            self.elapsed = false;
+        }
+        poll
+    }
+}
+
+impl Faults {
+    /// The faults of a half, seeded by the ones of the whole stream.
+    pub(crate) fn fork(&mut self) -> Self {
+        Self {
+            config: self.config,
+            rng: StdRng::seed_from_u64(self.rng.gen()),
+            read: Delay::default(),
+            write: Delay::default(),
+            reset: self.reset,
+        }
+    }
+
+    pub(crate) fn check(&self) -> io::Result<()> {
+        if self.reset {
+            Err(io::Error::new(
+                io::ErrorKind::ConnectionReset,
+                "chaos: connection reset",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Maybe reset the stream, before an operation.
+    fn roll_reset(&mut self) -> io::Result<()> {
+        if !self.reset && self.config.reset > 0.0 && self.rng.gen_bool(self.config.reset) {
+            debug!("chaos: reset");
+            self.reset = true;
+        }
+        self.check()
+    }
+
+    /// The length of the operation on `len` bytes, maybe shorter.
+    fn roll_len(&mut self, len: usize) -> usize {
+        if len > 1 && self.config.short > 0.0 && self.rng.gen_bool(self.config.short) {
+            self.rng.gen_range(1..len)
+        } else {
+            len
+        }
+    }
+
+    pub(crate) fn poll_read<S: AsyncRead>(
+        &mut self,
+        inner: Pin<&mut S>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        self.check()?;
+        ready!(self.read.poll(cx, self.config.latency, &mut self.rng));
+        self.roll_reset()?;
+
+        let len = self.roll_len(buf.remaining());
+        let poll = if len < buf.remaining() {
+            let mut short = vec![0; len];
+            let mut short_buf = ReadBuf::new(&mut short);
+            let poll = inner.poll_read(cx, &mut short_buf);
+            buf.put_slice(short_buf.filled());
+            poll
+        } else {
+            inner.poll_read(cx, buf)
+        };
+        self.read.done(poll)
+    }
+
+    pub(crate) fn poll_write<S: AsyncWrite>(
+        &mut self,
+        inner: Pin<&mut S>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.check()?;
+        ready!(self.write.poll(cx, self.config.latency, &mut self.rng));
+        self.roll_reset()?;
+
+        let len = self.roll_len(buf.len());
+        let poll = inner.poll_write(cx, &buf[..len]);
+        self.write.done(poll)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    const DATA: &[u8] = b"the quick brown fox jumps over the lazy dog";
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Ok(ChaosConfig {
+                seed: 42,
+                latency: Duration::from_millis(20),
+                short: 0.5,
+                reset: 0.001,
+            }),
+            "seed=42,latency=20,short=0.5,reset=0.001".parse()
+        );
+        assert_eq!(Ok(ChaosConfig::default()), "".parse());
+        assert!("short=2".parse::<ChaosConfig>().is_err());
+        assert!("drop=0.1".parse::<ChaosConfig>().is_err());
+        assert!("seed".parse::<ChaosConfig>().is_err());
+    }
+
+    /// The sizes of the reads of `DATA` through the faults of `config`.
+    async fn reads(config: ChaosConfig) -> Vec<usize> {
+        let (mut client, server) = tokio::io::duplex(DATA.len());
+        client.write_all(DATA).await.unwrap();
+        drop(client);
+
+        let mut server = Chaos::new(server, config, 1);
+        let (mut data, mut reads) = (vec![], vec![]);
+        let mut buffer = [0; 16];
+        loop {
+            let n = server.read(&mut buffer).await.unwrap();
+            if n == 0 {
+                break;
+            }
+            data.extend_from_slice(&buffer[..n]);
+            reads.push(n);
+        }
+        assert_eq!(DATA, data);
+        reads
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_short_reads() {
+        let config = ChaosConfig {
+            seed: 7,
+            latency: Duration::from_millis(10),
+            short: 1.0,
+            ..ChaosConfig::default()
+        };
+
+        let first = reads(config).await;
+        assert!(first.len() > DATA.len().div_ceil(16), "{first:?}");
+        assert_eq!(first, reads(config).await);
+        assert_ne!(first, reads(ChaosConfig { seed: 8, ..config }).await);
+    }
+
+    #[tokio::test]
+    async fn test_short_writes() {
+        let (client, mut server) = tokio::io::duplex(DATA.len());
+        let config = ChaosConfig {
+            short: 1.0,
+            ..ChaosConfig::default()
+        };
+        let mut client = Chaos::new(client, config, 1);
+
+        client.write_all(DATA).await.unwrap();
+        client.shutdown().await.unwrap();
+        let mut data = vec![];
+        server.read_to_end(&mut data).await.unwrap();
+        assert_eq!(DATA, data);
+    }
+
+    #[tokio::test]
+    async fn test_reset() {
+        let (client, _server) = tokio::io::duplex(DATA.len());
+        let config = ChaosConfig {
+            reset: 1.0,
+            ..ChaosConfig::default()
+        };
+        let mut client = Chaos::new(client, config, 1);
+
+        let err = client.write_all(DATA).await.unwrap_err();
+        assert_eq!(io::ErrorKind::ConnectionReset, err.kind());
+        let err = client.flush().await.unwrap_err();
+        assert_eq!(io::ErrorKind::ConnectionReset, err.kind());
+    }
+}
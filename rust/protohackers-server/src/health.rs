@@ -0,0 +1,313 @@
+//! The health endpoint, on its own port, for the load balancers and
+//! the orchestrators: minimal HTTP, one request per connection.
+//!
+//! * `GET /health/live`: always 200 while the process answers.
+//! * `GET /health/ready`: 200 while the server accepts connections,
+//!   503 before and after, e.g. during the graceful shutdown.
+//!
+//! Both with the connection stats as a JSON body; the stats can be
+//! [`report`]ed as metrics too.
+use std::fmt;
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time;
+
+use protohackers_metrics::Registry;
+
+use tracing::{debug, warn};
+
+pub const LIVE_PATH: &str = "/health/live";
+pub const READY_PATH: &str = "/health/ready";
+
+const MAX_REQUEST_LEN: usize = 8 * 1024;
+
+/// The connection stats of a [`Server`](crate::Server).
+#[derive(Debug)]
+pub struct Stats {
+    started: Instant,
+    ready: AtomicBool,
+    accepted: AtomicU64,
+    active: AtomicU64,
+    queued: AtomicU64,
+    rejected: AtomicU64,
+    crashed: AtomicU64,
+    failed: AtomicU64,
+}
+
+impl Stats {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            ready: AtomicBool::new(false),
+            accepted: AtomicU64::new(0),
+            active: AtomicU64::new(0),
+            queued: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+            crashed: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn set_ready(&self, ready: bool) {
+        self.ready.store(ready, Ordering::Relaxed);
+    }
+
+    pub(crate) fn accept(&self) {
+        self.accepted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count an active connection until the guard is dropped.
+    pub(crate) fn open(self: &Arc<Self>) -> Guard {
+        Guard::new(self.clone(), |stats| &stats.active)
+    }
+
+    /// Count a queued connection until the guard is dropped.
+    pub(crate) fn enqueue(self: &Arc<Self>) -> Guard {
+        Guard::new(self.clone(), |stats| &stats.queued)
+    }
+
+    pub(crate) fn reject(&self) {
+        self.rejected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn crash(&self) {
+        self.crashed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn fail(&self) {
+        self.failed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[must_use]
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            ready: self.ready.load(Ordering::Relaxed),
+            uptime: self.started.elapsed(),
+            accepted: self.accepted.load(Ordering::Relaxed),
+            active: self.active.load(Ordering::Relaxed),
+            queued: self.queued.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+            crashed: self.crashed.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Decrement a gauge of the stats on drop, also when the task is
+/// aborted.
+pub(crate) struct Guard {
+    stats: Arc<Stats>,
+    gauge: fn(&Stats) -> &AtomicU64,
+}
+
+impl Guard {
+    fn new(stats: Arc<Stats>, gauge: fn(&Stats) -> &AtomicU64) -> Self {
+        gauge(&stats).fetch_add(1, Ordering::Relaxed);
+        Self { stats, gauge }
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        (self.gauge)(&self.stats).fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Snapshot {
+    /// The server accepts connections.
+    pub ready: bool,
+
+    pub uptime: Duration,
+
+    /// The accepted connections, including the rejected ones.
+    pub accepted: u64,
+
+    /// The connections being handled.
+    pub active: u64,
+
+    /// The connections waiting for a free slot.
+    pub queued: u64,
+
+    /// The connections closed over the cap.
+    pub rejected: u64,
+
+    /// The connections whose handler panicked.
+    pub crashed: u64,
+
+    /// The failed accepts, e.g. out of file descriptors.
+    pub failed: u64,
+}
+
+/// The JSON body of the replies.
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            r#"{{"ready":{},"uptime":{},"accepted":{},"active":{},"queued":{},"rejected":{},"crashed":{},"failed":{}}}"#,
+            self.ready,
+            self.uptime.as_secs(),
+            self.accepted,
+            self.active,
+            self.queued,
+            self.rejected,
+            self.crashed,
+            self.failed
+        )
+    }
+}
+
+/// Copy `stats` into the `server_connections_*` metrics of `registry`
+/// every `interval`, on its own task.
+pub fn report(stats: Arc<Stats>, registry: &Registry, interval: Duration) {
+    let accepted = registry.counter(
+        "server_connections_accepted_total",
+        "The accepted connections, including the rejected ones",
+    );
+    let rejected = registry.counter(
+        "server_connections_rejected_total",
+        "The connections closed over the cap",
+    );
+    let crashed = registry.counter(
+        "server_connections_crashed_total",
+        "The connections whose handler panicked",
+    );
+    let failed = registry.counter(
+        "server_accepts_failed_total",
+        "The failed accepts, e.g. out of file descriptors",
+    );
+    let active = registry.gauge("server_connections_active", "The connections being handled");
+    let queued = registry.gauge(
+        "server_connections_queued",
+        "The connections waiting for a free slot",
+    );
+
+    crate::task::spawn("connection stats", async move {
+        let mut interval = time::interval(interval);
+        loop {
+            interval.tick().await;
+
+            let snapshot = stats.snapshot();
+            for (counter, value) in [
+                (&accepted, snapshot.accepted),
+                (&rejected, snapshot.rejected),
+                (&crashed, snapshot.crashed),
+                (&failed, snapshot.failed),
+            ] {
+                counter.add(value.saturating_sub(counter.get()));
+            }
+            active.set(i64::try_from(snapshot.active).unwrap_or(i64::MAX));
+            queued.set(i64::try_from(snapshot.queued).unwrap_or(i64::MAX));
+        }
+    });
+}
+
+/// Answer the health checks of `listener` with `stats`.
+///
+/// # Errors
+/// * Error when the listener fails.
+pub async fn serve(listener: TcpListener, stats: Arc<Stats>) -> io::Result<()> {
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        debug!("health check from {peer}");
+
+        let stats = stats.clone();
+        tokio::spawn(async move {
+            if let Err(err) = handle(stream, &stats).await {
+                warn!("health check from {peer}: {err}");
+            }
+        });
+    }
+}
+
+async fn handle(mut stream: TcpStream, stats: &Stats) -> io::Result<()> {
+    let mut request = vec![];
+    let mut buffer = [0; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        if request.len() > MAX_REQUEST_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "request too long",
+            ));
+        }
+        let n = stream.read(&mut buffer).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        request.extend_from_slice(&buffer[..n]);
+    }
+
+    let request_line = request.split(|b| *b == b'\r').next().unwrap_or_default();
+    let mut parts = request_line.split(|b| *b == b' ');
+    let snapshot = stats.snapshot();
+    let (code, body) = match (parts.next(), parts.next()) {
+        (Some(b"GET"), Some(path)) if path == LIVE_PATH.as_bytes() => {
+            ("200 OK", snapshot.to_string())
+        }
+        (Some(b"GET"), Some(path)) if path == READY_PATH.as_bytes() => {
+            let code = if snapshot.ready {
+                "200 OK"
+            } else {
+                "503 Service Unavailable"
+            };
+            (code, snapshot.to_string())
+        }
+        _ => ("404 Not Found", String::new()),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {code}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot() {
+        let stats = Arc::new(Stats::new());
+        stats.set_ready(true);
+        stats.accept();
+        stats.accept();
+        let _active = stats.open();
+        drop(stats.enqueue());
+        stats.reject();
+        stats.crash();
+        stats.fail();
+
+        let snapshot = stats.snapshot();
+        assert_eq!(
+            Snapshot {
+                ready: true,
+                uptime: snapshot.uptime,
+                accepted: 2,
+                active: 1,
+                queued: 0,
+                rejected: 1,
+                crashed: 1,
+                failed: 1,
+            },
+            snapshot
+        );
+        assert_eq!(
+            r#"{"ready":true,"uptime":0,"accepted":2,"active":1,"queued":0,"rejected":1,"crashed":1,"failed":1}"#,
+            snapshot.to_string()
+        );
+    }
+}
@@ -0,0 +1,211 @@
+//! The bytes of the connections, logged as hex dumps, to diagnose the
+//! protocol mismatches with the clients: a server started with
+//! `--hex-dump 64` logs, at `info`, the first 64 bytes of every read
+//! and write, with the direction and the connection id:
+//!
+//! ```raw
+//! in 18 bytes at 0
+//! 00000000  7b 22 6d 65 74 68 6f 64  22 3a 22 69 73 50 72 69  |{"method":"isPri|
+//! 00000010  6d 65                                             |me| id=3
+//! ```
+//!
+//! The handlers see the dumps through the [`IdleTimeout`] stream they
+//! are given, any other stream can be wrapped in a [`HexDump`].
+//!
+//! [`IdleTimeout`]: crate::IdleTimeout
+use std::fmt::Write as _;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use tracing::info;
+
+const LINE_LEN: usize = 16;
+
+/// A stream logging the hex dumps of its bytes.
+#[derive(Debug)]
+pub struct HexDump<S> {
+    inner: S,
+    dump: Dump,
+}
+
+impl<S> HexDump<S> {
+    /// Wrap `inner`, the connection `id`, dumping up to `limit` bytes
+    /// per read or write.
+    pub fn new(inner: S, limit: usize, id: u64) -> Self {
+        Self {
+            inner,
+            dump: Dump::new(limit, id),
+        }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for HexDump<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let filled = buf.filled().len();
+        let poll = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = poll {
+            this.dump.read(&buf.filled()[filled..]);
+        }
+        poll
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for HexDump<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(n)) = poll {
+            this.dump.write(&buf[..n]);
+        }
+        poll
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+/// The dumps of a stream, with the bytes gone through so far in each
+/// direction.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Dump {
+    limit: usize,
+    id: u64,
+    read: u64,
+    written: u64,
+}
+
+impl Dump {
+    pub(crate) fn new(limit: usize, id: u64) -> Self {
+        Self {
+            limit,
+            id,
+            read: 0,
+            written: 0,
+        }
+    }
+
+    pub(crate) fn read(&mut self, bytes: &[u8]) {
+        if !bytes.is_empty() {
+            self.log("in", self.read, bytes);
+            self.read += bytes.len() as u64;
+        }
+    }
+
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        if !bytes.is_empty() {
+            self.log("out", self.written, bytes);
+            self.written += bytes.len() as u64;
+        }
+    }
+
+    /// The first `n` bytes of `bufs`, written at once.
+    pub(crate) fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>], n: usize) {
+        let mut bytes = Vec::with_capacity(n.min(self.limit));
+        for buf in bufs {
+            let len = (n - bytes.len()).min(buf.len());
+            bytes.extend_from_slice(&buf[..len]);
+            if bytes.len() == n {
+                break;
+            }
+        }
+        self.write(&bytes);
+    }
+
+    fn log(&self, direction: &str, offset: u64, bytes: &[u8]) {
+        info!(
+            id = self.id,
+            "{direction} {} bytes at {offset}\n{}",
+            bytes.len(),
+            hex_dump(bytes, self.limit)
+        );
+    }
+}
+
+/// The hex dump of up to `limit` bytes of `bytes`, 16 per line with
+/// their offset and the printable ASCII ones, then the count of the
+/// truncated ones.
+#[must_use]
+pub fn hex_dump(bytes: &[u8], limit: usize) -> String {
+    let mut dump = String::new();
+    for (i, line) in bytes[..bytes.len().min(limit)].chunks(LINE_LEN).enumerate() {
+        if i > 0 {
+            dump.push('\n');
+        }
+        let _ = write!(dump, "{:08x} ", i * LINE_LEN);
+        for j in 0..LINE_LEN {
+            if j % 8 == 0 {
+                dump.push(' ');
+            }
+            match line.get(j) {
+                Some(b) => {
+                    let _ = write!(dump, "{b:02x} ");
+                }
+                None => dump.push_str("   "),
+            }
+        }
+        dump.push_str(" |");
+        dump.extend(line.iter().map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                char::from(b)
+            } else {
+                '.'
+            }
+        }));
+        dump.push('|');
+    }
+    if bytes.len() > limit {
+        let _ = write!(dump, "\n... {} more bytes", bytes.len() - limit);
+    }
+    dump
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_dump() {
+        assert_eq!(
+            "00000000  7b 22 6d 65 74 68 6f 64  22 3a 22 69 73 50 72 69  |{\"method\":\"isPri|\n\
+             00000010  6d 65 0a                                          |me.|",
+            hex_dump(b"{\"method\":\"isPrime\n", 64)
+        );
+        assert_eq!(
+            "00000000  00 01 02 03                                       |....|\n\
+             ... 2 more bytes",
+            hex_dump(&[0, 1, 2, 3, 4, 5], 4)
+        );
+        assert_eq!("", hex_dump(b"", 4));
+    }
+
+    #[test]
+    fn test_write_vectored() {
+        let mut dump = Dump::new(64, 1);
+        let bufs = [io::IoSlice::new(b"abc"), io::IoSlice::new(b"def")];
+        dump.write_vectored(&bufs, 4);
+        assert_eq!(4, dump.written);
+        dump.read(b"xy");
+        assert_eq!(2, dump.read);
+    }
+}
@@ -0,0 +1,441 @@
+//! The idle timeout of the connections: a stream without bytes in
+//! either direction for the timeout fails with
+//! [`io::ErrorKind::TimedOut`].
+//!
+//! The halves of a split stream share the last activity, a connection
+//! reading while writing is not idle.
+//!
+//! The stream of the connections of a server injects the faults of its
+//! [`chaos`](crate::chaos) and logs the [`hex_dump`](crate::hex_dump)s
+//! of the bytes too, if any.
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf, ReadHalf, WriteHalf};
+use tokio::net::TcpStream;
+use tokio::time::{self, Instant, Sleep};
+
+use crate::chaos::{ChaosConfig, Faults};
+use crate::hex_dump::Dump;
+
+/// A stream failing after `timeout` without bytes read or written;
+/// never without a timeout.
+#[derive(Debug)]
+pub struct IdleTimeout<S> {
+    inner: S,
+    idle: Option<Idle>,
+    chaos: Option<Faults>,
+    dump: Option<Dump>,
+}
+
+#[derive(Debug)]
+struct Idle {
+    timeout: Duration,
+    activity: Arc<Activity>,
+    /// Created on the first wait, within the runtime.
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+/// The last time bytes went through, shared by the halves.
+#[derive(Debug)]
+struct Activity {
+    start: Instant,
+    /// Nanoseconds since `start`.
+    last: AtomicU64,
+}
+
+impl Activity {
+    fn touch(&self) {
+        let elapsed = u64::try_from(self.start.elapsed().as_nanos()).unwrap_or(u64::MAX);
+        self.last.fetch_max(elapsed, Ordering::Relaxed);
+    }
+
+    fn last(&self) -> Instant {
+        self.start + Duration::from_nanos(self.last.load(Ordering::Relaxed))
+    }
+}
+
+impl Idle {
+    fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            activity: Arc::new(Activity {
+                start: Instant::now(),
+                last: AtomicU64::new(0),
+            }),
+            sleep: None,
+        }
+    }
+
+    /// The same activity, for a half.
+    fn share(&self) -> Self {
+        Self {
+            timeout: self.timeout,
+            activity: self.activity.clone(),
+            sleep: None,
+        }
+    }
+
+    /// Pending until the timeout since the last activity, then an
+    /// error.
+    fn poll_expired(&mut self, cx: &mut Context<'_>) -> Poll<io::Error> {
+        loop {
+            let deadline = self.activity.last() + self.timeout;
+            let sleep = self
+                .sleep
+                .get_or_insert_with(|| Box::pin(time::sleep_until(deadline)));
+            if sleep.deadline() != deadline {
+                sleep.as_mut().reset(deadline);
+            }
+            ready!(sleep.as_mut().poll(cx));
+
+            // the other half may have been active meanwhile
+            if self.activity.last() + self.timeout <= Instant::now() {
+                return Poll::Ready(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("idle for {:?}", self.timeout),
+                ));
+            }
+        }
+    }
+}
+
+impl<S> IdleTimeout<S> {
+    /// Wrap `inner`, failing after `timeout` without bytes; never when
+    /// `None`.
+    pub fn new(inner: S, timeout: Option<Duration>) -> Self {
+        Self {
+            inner,
+            idle: timeout.map(Idle::new),
+            chaos: None,
+            dump: None,
+        }
+    }
+
+    /// Inject the faults of `config`, seeded by the connection `id`.
+    #[must_use]
+    pub(crate) fn with_chaos(self, config: Option<ChaosConfig>, id: u64) -> Self {
+        Self {
+            chaos: config.map(|config| config.faults(id)),
+            ..self
+        }
+    }
+
+    /// Log the hex dumps of up to `limit` bytes per read or write, of
+    /// the connection `id`.
+    #[must_use]
+    pub(crate) fn with_hex_dump(self, limit: Option<usize>, id: u64) -> Self {
+        Self {
+            dump: limit.map(|limit| Dump::new(limit, id)),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn timeout(&self) -> Option<Duration> {
+        self.idle.as_ref().map(|idle| idle.timeout)
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// The wrapped stream; the bytes read or written through it do
+    /// not count as activity.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    /// The activity of this stream, for a half.
+    fn share(&self) -> Option<Idle> {
+        self.idle.as_ref().map(Idle::share)
+    }
+
+    /// The faults of this stream, for a half.
+    fn fork(&mut self) -> Option<Faults> {
+        self.chaos.as_mut().map(Faults::fork)
+    }
+
+    fn touch(&self) {
+        if let Some(idle) = &self.idle {
+            idle.activity.touch();
+        }
+    }
+
+    fn poll_expired<T>(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<T>> {
+        match &mut self.idle {
+            Some(idle) => idle.poll_expired(cx).map(Err),
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl<S> From<S> for IdleTimeout<S> {
+    /// Without a timeout.
+    fn from(inner: S) -> Self {
+        Self::new(inner, None)
+    }
+}
+
+impl IdleTimeout<TcpStream> {
+    /// # Errors
+    /// * Error when the address is not available.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.peer_addr()
+    }
+
+    /// # Errors
+    /// * Error when the address is not available.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    /// Split in the borrowed read and write halves, as
+    /// [`TcpStream::split`].
+    pub fn split(&mut self) -> (IdleTimeout<ReadHalf<'_>>, IdleTimeout<WriteHalf<'_>>) {
+        let (read_idle, write_idle) = (self.share(), self.share());
+        let (read_chaos, write_chaos) = (self.fork(), self.fork());
+        let (read, write) = self.inner.split();
+        (
+            IdleTimeout {
+                inner: read,
+                idle: read_idle,
+                chaos: read_chaos,
+                dump: self.dump,
+            },
+            IdleTimeout {
+                inner: write,
+                idle: write_idle,
+                chaos: write_chaos,
+                dump: self.dump,
+            },
+        )
+    }
+
+    /// Split in the owned read and write halves, as
+    /// [`TcpStream::into_split`].
+    #[must_use]
+    pub fn into_split(mut self) -> (IdleTimeout<OwnedReadHalf>, IdleTimeout<OwnedWriteHalf>) {
+        let (read_idle, write_idle) = (self.share(), self.share());
+        let (read_chaos, write_chaos) = (self.fork(), self.fork());
+        let (read, write) = self.inner.into_split();
+        (
+            IdleTimeout {
+                inner: read,
+                idle: read_idle,
+                chaos: read_chaos,
+                dump: self.dump,
+            },
+            IdleTimeout {
+                inner: write,
+                idle: write_idle,
+                chaos: write_chaos,
+                dump: self.dump,
+            },
+        )
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for IdleTimeout<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let filled = buf.filled().len();
+        let poll = match &mut this.chaos {
+            Some(chaos) => chaos.poll_read(Pin::new(&mut this.inner), cx, buf),
+            None => Pin::new(&mut this.inner).poll_read(cx, buf),
+        };
+        match poll {
+            Poll::Ready(Ok(())) => {
+                if buf.filled().len() > filled {
+                    this.touch();
+                    if let Some(dump) = &mut this.dump {
+                        dump.read(&buf.filled()[filled..]);
+                    }
+                }
+                Poll::Ready(Ok(()))
+            }
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Pending => this.poll_expired(cx),
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for IdleTimeout<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let poll = match &mut this.chaos {
+            Some(chaos) => chaos.poll_write(Pin::new(&mut this.inner), cx, buf),
+            None => Pin::new(&mut this.inner).poll_write(cx, buf),
+        };
+        match poll {
+            Poll::Ready(Ok(n)) => {
+                if n > 0 {
+                    this.touch();
+                    if let Some(dump) = &mut this.dump {
+                        dump.write(&buf[..n]);
+                    }
+                }
+                Poll::Ready(Ok(n))
+            }
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Pending => this.poll_expired(cx),
+        }
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let poll = match &mut this.chaos {
+            // one buffer at a time, as the default
+            Some(chaos) => {
+                let buf = bufs.iter().find(|buf| !buf.is_empty());
+                chaos.poll_write(Pin::new(&mut this.inner), cx, buf.map_or(&[], |buf| buf))
+            }
+            None => Pin::new(&mut this.inner).poll_write_vectored(cx, bufs),
+        };
+        match poll {
+            Poll::Ready(Ok(n)) => {
+                if n > 0 {
+                    this.touch();
+                    if let Some(dump) = &mut this.dump {
+                        dump.write_vectored(bufs, n);
+                    }
+                }
+                Poll::Ready(Ok(n))
+            }
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Pending => this.poll_expired(cx),
+        }
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if let Some(chaos) = &this.chaos {
+            chaos.check()?;
+        }
+        match Pin::new(&mut this.inner).poll_flush(cx) {
+            Poll::Pending => this.poll_expired(cx),
+            ready @ Poll::Ready(_) => ready,
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if let Some(chaos) = &this.chaos {
+            chaos.check()?;
+        }
+        match Pin::new(&mut this.inner).poll_shutdown(cx) {
+            Poll::Pending => this.poll_expired(cx),
+            ready @ Poll::Ready(_) => ready,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    #[tokio::test(start_paused = true)]
+    async fn test_idle() {
+        let (client, server) = tokio::io::duplex(64);
+        let mut server = IdleTimeout::new(server, Some(TIMEOUT));
+
+        let start = Instant::now();
+        let err = server.read_u8().await.unwrap_err();
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+        assert_eq!(TIMEOUT, start.elapsed());
+        drop(client);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_activity() {
+        let (mut client, server) = tokio::io::duplex(64);
+        let mut server = IdleTimeout::new(server, Some(TIMEOUT));
+
+        let start = Instant::now();
+        let writer = tokio::spawn(async move {
+            for _ in 0..3 {
+                time::sleep(TIMEOUT / 2).await;
+                client.write_u8(1).await.unwrap();
+            }
+            client
+        });
+        for _ in 0..3 {
+            assert_eq!(1, server.read_u8().await.unwrap());
+        }
+        let _client = writer.await.unwrap();
+
+        let err = server.read_u8().await.unwrap_err();
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+        assert_eq!(TIMEOUT / 2 * 3 + TIMEOUT, start.elapsed());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_halves_share_activity() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        let server = IdleTimeout::new(server, Some(TIMEOUT));
+        let (mut read, mut write) = server.into_split();
+
+        let start = Instant::now();
+        let writer = tokio::spawn(async move {
+            time::sleep(TIMEOUT / 2).await;
+            write.write_u8(1).await.unwrap();
+            write
+        });
+
+        // the write delays the timeout of the read
+        let err = read.read_u8().await.unwrap_err();
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+        assert_eq!(TIMEOUT / 2 + TIMEOUT, start.elapsed());
+
+        assert_eq!(1, client.read_u8().await.unwrap());
+        drop(writer.await.unwrap());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_no_timeout() {
+        let (mut client, server) = tokio::io::duplex(64);
+        let mut server = IdleTimeout::from(server);
+        assert_eq!(None, server.timeout());
+
+        tokio::spawn(async move {
+            time::sleep(TIMEOUT * 100).await;
+            client.write_u8(1).await.unwrap();
+        });
+        assert_eq!(1, server.read_u8().await.unwrap());
+    }
+}
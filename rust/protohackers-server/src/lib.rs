@@ -0,0 +1,563 @@
+//! The bootstrap shared by the problem binaries.
+//!
+//! The standard flags, [`ServerArgs`], to flatten into the `Args` of a
+//! binary, the tracing initialization, the binding, also by systemd
+//! socket activation or for several workers, the [`Server`] accept
+//! loop, with the connections cap, the [`idle`] timeout and the
+//! graceful shutdown on the [`signals`], and its optional [`health`]
+//! endpoint, its connection stats and the resources of the process
+//! exported as metrics; the [`log_filter`] can be replaced while
+//! running. The servers dialing an upstream open their outbound connections with a
+//! [`Dialer`], optionally through a proxy:
+//!
+//! ```no_run
+//! use clap::Parser;
+//!
+//! use protohackers_server::ServerArgs;
+//!
+//! #[derive(clap::Parser, Debug)]
+//! struct Args {
+//!     #[command(flatten)]
+//!     server: ServerArgs,
+//! }
+//!
+//! # async fn handle(_: protohackers_server::IdleTimeout<tokio::net::TcpStream>) -> Result<(), std::io::Error> { Ok(()) }
+//! #[tokio::main]
+//! async fn main() -> Result<(), std::io::Error> {
+//!     let args = Args::parse();
+//!
+//!     args.server.init_tracing();
+//!
+//!     protohackers_server::serve(&args.server, |stream, _| handle(stream)).await
+//! }
+//! ```
+//!
+//! The problem crates keep their `Args` and `run` in a `cli` module,
+//! shared by their own binary and the `protohackers` launcher.
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::net::{IpAddr, SocketAddr, SocketAddrV6};
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::time::Duration;
+
+use socket2::{Domain, Socket, Type};
+
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+
+use tracing::{info, warn};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, EnvFilter, Layer};
+
+use protohackers_metrics::{MetricsArgs, Registry};
+
+#[cfg(unix)]
+pub mod activation;
+pub mod chaos;
+pub mod dial;
+pub mod health;
+pub mod hex_dump;
+pub mod idle;
+pub mod log_filter;
+mod server;
+pub mod signals;
+pub mod task;
+
+pub use chaos::{Chaos, ChaosConfig};
+pub use dial::{DialArgs, Dialer};
+pub use hex_dump::HexDump;
+pub use idle::IdleTimeout;
+pub use server::{Listeners, Server, SHUTDOWN_TIMEOUT};
+
+/// The default address, the IPv6 wildcard: also on IPv4 unless the
+/// IPv4 wildcard is bound too, only on IPv4 when IPv6 is not
+/// available.
+pub const DUAL_STACK_ADDRESS: &str = "::";
+
+/// The environment variable overriding the default log format.
+pub const LOG_FORMAT_ENV: &str = "PROTOHACKERS_LOG_FORMAT";
+
+/// The format of the logs.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// One line per event, with the spans
+    #[default]
+    #[value(alias = "text")]
+    Full,
+
+    /// One shorter line per event
+    Compact,
+
+    /// One JSON object per event, with the fields of the spans, e.g.
+    /// the problem and the connection id
+    Json,
+}
+
+/// The flags of every server.
+#[derive(clap::Args, Debug, Clone)]
+pub struct ServerArgs {
+    /// The addresses to bind, comma separated or repeated; `::` for
+    /// all the IPv6 and IPv4 ones
+    #[arg(long, value_delimiter = ',', default_value = DUAL_STACK_ADDRESS)]
+    pub address: Vec<String>,
+
+    #[arg(long, default_value_t = 10000)]
+    pub port: u16,
+
+    #[arg(long, env = LOG_FORMAT_ENV, value_enum, default_value_t = LogFormat::Full)]
+    pub log_format: LogFormat,
+
+    /// On SIGUSR2, replace the log filter with the directives of this
+    /// file, the `RUST_LOG` ones when it is empty or missing
+    #[cfg(unix)]
+    #[arg(long)]
+    pub log_filter_file: Option<PathBuf>,
+
+    /// Close the connections over this number of concurrent ones
+    #[arg(long)]
+    pub max_connections: Option<usize>,
+
+    /// Let this number of connections over the maximum wait for a free
+    /// slot instead of closing them
+    #[arg(long, default_value_t = 0, requires = "max_connections")]
+    pub connection_queue: usize,
+
+    /// Close the queued connections still waiting after this number
+    /// of seconds
+    #[arg(long, requires = "connection_queue")]
+    pub queue_timeout: Option<u64>,
+
+    /// On shutdown, wait for the open connections at most this number
+    /// of seconds; the process exits 5 seconds later anyway
+    #[arg(long, default_value_t = SHUTDOWN_TIMEOUT.as_secs())]
+    pub shutdown_timeout: u64,
+
+    /// Close the connections without bytes in either direction for
+    /// this number of seconds, 0 for never; the default depends on the
+    /// problem
+    #[arg(long)]
+    pub idle_timeout: Option<u64>,
+
+    /// Answer the health checks, `/health/live` and `/health/ready`,
+    /// over HTTP on this address
+    #[arg(long)]
+    pub health_address: Option<String>,
+
+    /// Accept the connections on this number of listeners per address,
+    /// sharing it with `SO_REUSEPORT`, each on its own task
+    #[arg(long, default_value_t = 1)]
+    pub workers: usize,
+
+    /// Log the hex dumps of the bytes of the connections, up to this
+    /// number per read or write
+    #[arg(long, value_name = "BYTES", num_args = 0..=1, default_missing_value = "256")]
+    pub hex_dump: Option<usize>,
+
+    /// For testing: inject faults into the connections,
+    /// `seed=N,latency=MILLISECONDS,short=P,reset=P`
+    #[arg(long, hide = true)]
+    pub chaos: Option<ChaosConfig>,
+
+    #[command(flatten)]
+    pub metrics: MetricsArgs,
+}
+
+impl ServerArgs {
+    /// The addresses to bind, `address:port`.
+    #[must_use]
+    pub fn socket_addresses(&self) -> Vec<String> {
+        self.address
+            .iter()
+            .map(|address| socket_address(address, self.port))
+            .collect()
+    }
+
+    /// Install the global tracing subscriber, filtered by `RUST_LOG`,
+    /// and reload its filter on SIGUSR2 when there is a filter file.
+    ///
+    /// # Panics
+    /// * Panics outside of the runtime when there is a filter file.
+    pub fn init_tracing(&self) {
+        init_tracing(self.log_format);
+
+        #[cfg(unix)]
+        if let Some(path) = &self.log_filter_file {
+            if let Err(err) = log_filter::reload_on_signal(path.clone()) {
+                warn!("log filter file {}: {err}", path.display());
+            }
+        }
+    }
+
+    /// Bind a listener on every address, or take the ones passed by
+    /// systemd socket activation, ignoring the addresses.
+    ///
+    /// # Errors
+    /// * Error when an address can not be bound.
+    pub async fn bind(&self) -> io::Result<Listeners> {
+        bind_all(&self.socket_addresses()).await
+    }
+
+    /// Bind the UDP socket, or take the one passed by systemd socket
+    /// activation, ignoring the address.
+    ///
+    /// # Errors
+    /// * Error when the address can not be bound, or there is more
+    ///   than one.
+    pub async fn bind_udp(&self) -> io::Result<UdpSocket> {
+        match self.socket_addresses().as_slice() {
+            [address] => bind_udp(address).await,
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a UDP server binds a single address",
+            )),
+        }
+    }
+
+    /// Bind a [`Server`] with the connections cap and queue, shut
+    /// down by SIGINT or SIGTERM, its health endpoint when requested,
+    /// and its metrics; SIGQUIT prints the state of the server.
+    ///
+    /// # Errors
+    /// * Error when the addresses can not be bound.
+    pub async fn server(&self) -> io::Result<Server> {
+        self.server_with_metrics(&Registry::new()).await
+    }
+
+    /// Bind a [`Server`] as [`server`](Self::server), exporting its
+    /// metrics with the ones of the problem in `registry`.
+    ///
+    /// # Errors
+    /// * Error when the addresses can not be bound.
+    pub async fn server_with_metrics(&self, registry: &Registry) -> io::Result<Server> {
+        let mut listeners = bind_workers(&self.socket_addresses(), self.workers).await?;
+        let first = listeners.remove(0);
+        self.metrics.spawn(registry).await?;
+
+        let server = Server::new(first)
+            .with_workers(listeners)
+            .with_metrics(registry)
+            .with_max_connections(self.max_connections)
+            .with_queue(self.connection_queue)
+            .with_queue_timeout(self.queue_timeout.map(Duration::from_secs))
+            .with_shutdown_timeout(Duration::from_secs(self.shutdown_timeout))
+            .with_idle_timeout(self.idle_timeout.map(Duration::from_secs))
+            .with_chaos(self.chaos)
+            .with_hex_dump(self.hex_dump)
+            .with_shutdown_signal(signals::shutdown(
+                Duration::from_secs(self.shutdown_timeout) + signals::EXIT_GRACE,
+            ));
+
+        #[cfg(unix)]
+        signals::dump_on_signal(Some(server.stats()))?;
+
+        if let Some(health_address) = &self.health_address {
+            let listener = TcpListener::bind(health_address).await?;
+            info!("health checks on {}", listener.local_addr()?);
+
+            let stats = server.stats();
+            task::spawn("health checks", async move {
+                if let Err(err) = health::serve(listener, stats).await {
+                    warn!("health checks: {err}");
+                }
+            });
+        }
+
+        Ok(server)
+    }
+}
+
+/// The socket address of `address` and `port`, the IPv6 addresses in
+/// brackets.
+#[must_use]
+pub fn socket_address(address: &str, port: u16) -> String {
+    match address.parse::<IpAddr>() {
+        Ok(IpAddr::V6(_)) => format!("[{address}]:{port}"),
+        _ => format!("{address}:{port}"),
+    }
+}
+
+/// Bind a listener on `address`, or take the first one passed by
+/// systemd socket activation; the IPv6 wildcard is also on IPv4.
+///
+/// # Errors
+/// * Error when the address can not be bound or the passed socket is
+///   invalid.
+pub async fn bind(address: &str) -> io::Result<TcpListener> {
+    #[cfg(unix)]
+    if let Some(listener) = activation::tcp_listener()? {
+        info!("socket activated on {}", listener.local_addr()?);
+        return Ok(listener);
+    }
+
+    listen(address, true, false).await
+}
+
+/// Bind a listener on every address, or take the ones passed by
+/// systemd socket activation.
+///
+/// The IPv6 wildcard is also on IPv4 unless the IPv4 wildcard is
+/// among the addresses.
+///
+/// # Errors
+/// * Error when an address can not be bound or a passed socket is
+///   invalid.
+pub async fn bind_all(addresses: &[String]) -> io::Result<Listeners> {
+    #[cfg(unix)]
+    if let Some(listeners) = activated()? {
+        return Ok(listeners);
+    }
+
+    let listeners = listen_all(addresses, false).await?;
+    for address in listeners.local_addrs()? {
+        info!("listening on {address}");
+    }
+    Ok(listeners)
+}
+
+/// Bind a listener on every address for each of the `workers`, or take
+/// the ones passed by systemd socket activation for a single worker.
+///
+/// The listeners of the workers share the addresses with
+/// `SO_REUSEPORT`, the kernel spreading the connections among them;
+/// the port 0 is the one bound by the first worker.
+///
+/// # Errors
+/// * Error when an address can not be bound or a passed socket is
+///   invalid.
+pub async fn bind_workers(addresses: &[String], workers: usize) -> io::Result<Vec<Listeners>> {
+    #[cfg(unix)]
+    if let Some(listeners) = activated()? {
+        if workers > 1 {
+            warn!("socket activated, accepting on a single worker");
+        }
+        return Ok(vec![listeners]);
+    }
+
+    if workers <= 1 {
+        return Ok(vec![bind_all(addresses).await?]);
+    }
+
+    let first = listen_all(addresses, true).await?;
+    let addresses = first
+        .local_addrs()?
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    for address in &addresses {
+        info!("listening on {address}, {workers} workers");
+    }
+
+    let mut listeners = vec![first];
+    for _ in 1..workers {
+        listeners.push(listen_all(&addresses, true).await?);
+    }
+    Ok(listeners)
+}
+
+/// The listeners passed by systemd socket activation, if any.
+#[cfg(unix)]
+fn activated() -> io::Result<Option<Listeners>> {
+    let listeners = activation::tcp_listeners()?;
+    if listeners.is_empty() {
+        return Ok(None);
+    }
+    for listener in &listeners {
+        info!("socket activated on {}", listener.local_addr()?);
+    }
+    Ok(Some(listeners.into()))
+}
+
+/// Bind a listener on every address; the IPv6 wildcard is also on
+/// IPv4 unless the IPv4 wildcard is among the addresses.
+async fn listen_all(addresses: &[String], reuse_port: bool) -> io::Result<Listeners> {
+    let dual_stack = !addresses.iter().any(|address| {
+        address
+            .parse::<SocketAddr>()
+            .is_ok_and(|address| address.is_ipv4() && address.ip().is_unspecified())
+    });
+
+    let mut listeners = Vec::with_capacity(addresses.len());
+    for address in addresses {
+        listeners.push(listen(address, dual_stack, reuse_port).await?);
+    }
+    Ok(listeners.into())
+}
+
+/// Bind a UDP socket on `address`, or take the one passed by systemd
+/// socket activation; the IPv6 wildcard is also on IPv4.
+///
+/// # Errors
+/// * Error when the address can not be bound or the passed socket is
+///   invalid.
+pub async fn bind_udp(address: &str) -> io::Result<UdpSocket> {
+    #[cfg(unix)]
+    if let Some(socket) = activation::udp_socket()? {
+        info!("socket activated on {}", socket.local_addr()?);
+        return Ok(socket);
+    }
+
+    let Some(wildcard) = ipv6_wildcard(address) else {
+        return UdpSocket::bind(address).await;
+    };
+
+    match dual_stack_socket(wildcard, Type::DGRAM, true, false) {
+        Ok(socket) => UdpSocket::from_std(socket.into()),
+        Err(err) if ipv6_unavailable(&err) => {
+            warn!("IPv6 not available, binding IPv4 only: {err}");
+            UdpSocket::bind((IpAddr::from([0, 0, 0, 0]), wildcard.port())).await
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Bind `address`, the IPv6 wildcard also on IPv4 when `dual_stack`,
+/// shared with `SO_REUSEPORT` when `reuse_port`.
+async fn listen(address: &str, dual_stack: bool, reuse_port: bool) -> io::Result<TcpListener> {
+    let Some(wildcard) = ipv6_wildcard(address) else {
+        if !reuse_port {
+            return TcpListener::bind(address).await;
+        }
+        let address = tokio::net::lookup_host(address)
+            .await?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address"))?;
+        return listen_socket(reuse_port_socket(address)?);
+    };
+
+    match dual_stack_socket(wildcard, Type::STREAM, dual_stack, reuse_port) {
+        Ok(socket) => listen_socket(socket),
+        Err(err) if dual_stack && ipv6_unavailable(&err) => {
+            warn!("IPv6 not available, listening on IPv4 only: {err}");
+            let address = SocketAddr::from(([0, 0, 0, 0], wildcard.port()));
+            if reuse_port {
+                listen_socket(reuse_port_socket(address)?)
+            } else {
+                TcpListener::bind(address).await
+            }
+        }
+        Err(err) => Err(err),
+    }
+}
+
+fn listen_socket(socket: Socket) -> io::Result<TcpListener> {
+    socket.listen(1024)?;
+    TcpListener::from_std(socket.into())
+}
+
+fn ipv6_wildcard(address: &str) -> Option<SocketAddrV6> {
+    match address.parse() {
+        Ok(SocketAddr::V6(address)) if address.ip().is_unspecified() => Some(address),
+        _ => None,
+    }
+}
+
+/// Any error but the address in use or not allowed: no IPv6 socket or
+/// address on this host.
+fn ipv6_unavailable(err: &io::Error) -> bool {
+    !matches!(
+        err.kind(),
+        io::ErrorKind::AddrInUse | io::ErrorKind::PermissionDenied
+    )
+}
+
+fn dual_stack_socket(
+    address: SocketAddrV6,
+    ty: Type,
+    dual_stack: bool,
+    reuse_port: bool,
+) -> io::Result<Socket> {
+    let socket = Socket::new(Domain::IPV6, ty, None)?;
+    socket.set_only_v6(!dual_stack)?;
+    // as the tokio listeners, not to wait for the closed connections
+    #[cfg(unix)]
+    if ty == Type::STREAM {
+        socket.set_reuse_address(true)?;
+    }
+    #[cfg(unix)]
+    socket.set_reuse_port(reuse_port)?;
+    #[cfg(not(unix))]
+    let _ = reuse_port;
+    socket.set_nonblocking(true)?;
+    socket.bind(&SocketAddr::V6(address).into())?;
+    Ok(socket)
+}
+
+/// A stream socket bound to `address` with `SO_REUSEPORT`, on unix.
+fn reuse_port_socket(address: SocketAddr) -> io::Result<Socket> {
+    let socket = Socket::new(Domain::for_address(address), Type::STREAM, None)?;
+    #[cfg(unix)]
+    {
+        socket.set_reuse_address(true)?;
+        socket.set_reuse_port(true)?;
+    }
+    socket.set_nonblocking(true)?;
+    socket.bind(&address.into())?;
+    Ok(socket)
+}
+
+/// The layer serving `tokio-console`, with all the events of the
+/// runtime whatever the filter of the logs.
+#[cfg(feature = "console")]
+fn console<S>() -> impl Layer<S>
+where
+    S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+{
+    console_subscriber::spawn()
+}
+
+#[cfg(not(feature = "console"))]
+fn console<S: tracing::Subscriber>() -> impl Layer<S> {
+    tracing_subscriber::layer::Identity::new()
+}
+
+/// Install the global tracing subscriber, the logs filtered by
+/// `RUST_LOG`, the filter replaceable through [`log_filter::get`];
+/// with the `console` feature, serving `tokio-console` too.
+pub fn init_tracing(format: LogFormat) {
+    let initial = std::env::var(EnvFilter::DEFAULT_ENV).unwrap_or_default();
+    let (filter, handle) = reload::Layer::new(EnvFilter::builder().parse_lossy(&initial));
+    log_filter::install(log_filter::LogFilter::new(handle, initial));
+
+    let registry = tracing_subscriber::registry();
+    match format {
+        LogFormat::Full => registry
+            .with(tracing_subscriber::fmt::layer().with_filter(filter))
+            .with(console())
+            .init(),
+        LogFormat::Compact => registry
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .compact()
+                    .with_filter(filter),
+            )
+            .with(console())
+            .init(),
+        LogFormat::Json => registry
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .flatten_event(true)
+                    .with_current_span(false)
+                    .with_span_list(true)
+                    .with_filter(filter),
+            )
+            .with(console())
+            .init(),
+    }
+}
+
+/// Bind the server of `args` and handle every connection with
+/// `handler`, until ctrl-c.
+///
+/// # Errors
+/// * Error when the address can not be bound or the listener fails.
+pub async fn serve<F, Fut, E>(args: &ServerArgs, handler: F) -> io::Result<()>
+where
+    F: Fn(IdleTimeout<TcpStream>, SocketAddr) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<(), E>> + Send + 'static,
+    E: fmt::Display + 'static,
+{
+    args.server().await?.serve(handler).await
+}
@@ -0,0 +1,151 @@
+//! The filter of the logs, replaceable on a running server, e.g. to
+//! enable `debug` for a single module:
+//!
+//! ```raw
+//! echo 'info,p11_pest_control::actors::controller=debug' > p11.filter
+//! kill -USR2 <pid>
+//! ```
+//!
+//! On SIGUSR2 the filter is read from the `--log-filter-file` of the
+//! server; an empty or missing file restores the `RUST_LOG` one.
+use std::io;
+use std::path::Path;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+#[cfg(unix)]
+use tracing::{info, warn};
+
+static LOG_FILTER: OnceLock<LogFilter> = OnceLock::new();
+
+/// The handle of the filter of the global subscriber.
+#[derive(Debug)]
+pub struct LogFilter {
+    handle: reload::Handle<EnvFilter, Registry>,
+    initial: String,
+}
+
+impl LogFilter {
+    pub(crate) fn new(handle: reload::Handle<EnvFilter, Registry>, initial: String) -> Self {
+        Self { handle, initial }
+    }
+
+    /// The directives of the current filter.
+    #[must_use]
+    pub fn current(&self) -> String {
+        self.handle
+            .with_current(ToString::to_string)
+            .unwrap_or_default()
+    }
+
+    /// Replace the filter with `directives`, as `RUST_LOG`.
+    ///
+    /// # Errors
+    /// * Error when the directives are invalid or the subscriber is
+    ///   gone.
+    pub fn set(&self, directives: &str) -> io::Result<()> {
+        let filter = EnvFilter::try_new(directives)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        self.handle.reload(filter).map_err(io::Error::other)
+    }
+
+    /// Restore the filter of `RUST_LOG`.
+    ///
+    /// # Errors
+    /// * Error when the subscriber is gone.
+    pub fn reset(&self) -> io::Result<()> {
+        let filter = EnvFilter::builder().parse_lossy(&self.initial);
+        self.handle.reload(filter).map_err(io::Error::other)
+    }
+
+    /// Replace the filter with the directives of the file at `path`,
+    /// restore the one of `RUST_LOG` when it is empty or missing.
+    ///
+    /// # Errors
+    /// * Error when the file can not be read or its directives are
+    ///   invalid.
+    pub fn load(&self, path: &Path) -> io::Result<()> {
+        match std::fs::read_to_string(path) {
+            Ok(directives) if !directives.trim().is_empty() => self.set(directives.trim()),
+            Ok(_) => self.reset(),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => self.reset(),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// The filter of the global subscriber, once installed by
+/// [`init_tracing`](crate::init_tracing).
+pub fn get() -> Option<&'static LogFilter> {
+    LOG_FILTER.get()
+}
+
+pub(crate) fn install(log_filter: LogFilter) {
+    let _ = LOG_FILTER.set(log_filter);
+}
+
+/// Load the filter from `path` on every SIGUSR2, within the runtime.
+///
+/// # Errors
+/// * Error when the signal handler can not be installed.
+#[cfg(unix)]
+pub fn reload_on_signal(path: PathBuf) -> io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut signal = signal(SignalKind::user_defined2())?;
+    crate::task::spawn("log filter reload", async move {
+        while signal.recv().await.is_some() {
+            let Some(log_filter) = get() else {
+                continue;
+            };
+            match log_filter.load(&path) {
+                Ok(()) => info!("log filter: {}", log_filter.current()),
+                Err(err) => warn!("log filter {}: {err}", path.display()),
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing::Level;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+
+    #[test]
+    fn test_reload() {
+        let (filter, handle) = reload::Layer::new(EnvFilter::new("info"));
+        let log_filter = LogFilter::new(handle, "info".to_string());
+        let subscriber = tracing_subscriber::registry().with(filter);
+
+        tracing::subscriber::with_default(subscriber, || {
+            assert!(!tracing::enabled!(Level::DEBUG));
+
+            log_filter.set("info,protohackers_server=debug").unwrap();
+            assert_eq!("protohackers_server=debug,info", log_filter.current());
+            assert!(tracing::enabled!(Level::DEBUG));
+            assert!(!tracing::enabled!(target: "other", Level::DEBUG));
+
+            assert!(log_filter.set("info,=debug=").is_err());
+            assert_eq!("protohackers_server=debug,info", log_filter.current());
+
+            let path = std::env::temp_dir().join(format!(
+                "protohackers-server-log-filter-{}",
+                std::process::id()
+            ));
+            std::fs::write(&path, "debug\n").unwrap();
+            log_filter.load(&path).unwrap();
+            assert!(tracing::enabled!(target: "other", Level::DEBUG));
+
+            std::fs::remove_file(&path).unwrap();
+            log_filter.load(&path).unwrap();
+            assert_eq!("info", log_filter.current());
+            assert!(!tracing::enabled!(Level::DEBUG));
+        });
+    }
+}
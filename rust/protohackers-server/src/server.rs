@@ -0,0 +1,621 @@
+//! The accept loop.
+use std::any::Any;
+use std::fmt;
+use std::future::{self, Future};
+use std::io;
+use std::iter;
+use std::mem;
+use std::net::SocketAddr;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::task::Poll;
+use std::time::Duration;
+
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::task::JoinSet;
+use tokio::time;
+
+use tokio_util::sync::CancellationToken;
+
+use futures::future::Either;
+use futures::FutureExt;
+
+use tracing::{debug, error, info, info_span, warn, Instrument};
+
+use protohackers_metrics::{process, Registry};
+
+use crate::chaos::ChaosConfig;
+use crate::health::{self, Stats};
+use crate::idle::IdleTimeout;
+
+/// The default time to wait for the open connections on shutdown.
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The pause of the accepts once out of file descriptors, as the
+/// pending connections would fail again at once.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+type OnReject = Arc<dyn Fn(SocketAddr) + Send + Sync>;
+type OnCrash = Arc<dyn Fn(SocketAddr) + Send + Sync>;
+
+/// The listeners of a [`Server`]: a single one, or one per bound
+/// address, e.g. an IPv4 and an IPv6 one.
+#[derive(Debug, Default)]
+pub struct Listeners {
+    listeners: Vec<TcpListener>,
+
+    /// The first listener polled by the next accept, so that a busy
+    /// listener does not starve the others.
+    next: usize,
+}
+
+impl Listeners {
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.listeners.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.listeners.is_empty()
+    }
+
+    /// # Errors
+    /// * Error when the local address of a listener is not available.
+    pub fn local_addrs(&self) -> io::Result<Vec<SocketAddr>> {
+        self.listeners.iter().map(TcpListener::local_addr).collect()
+    }
+
+    /// Accept a connection from any of the listeners, at least one.
+    async fn accept(&mut self) -> io::Result<(TcpStream, SocketAddr)> {
+        let len = self.listeners.len();
+        let accepted = future::poll_fn(|cx| {
+            for i in 0..len {
+                let i = (self.next + i) % len;
+                if let Poll::Ready(accepted) = self.listeners[i].poll_accept(cx) {
+                    return Poll::Ready((i, accepted));
+                }
+            }
+            Poll::Pending
+        })
+        .await;
+
+        let (i, accepted) = accepted;
+        self.next = (i + 1) % len;
+        accepted
+    }
+}
+
+impl From<TcpListener> for Listeners {
+    fn from(listener: TcpListener) -> Self {
+        vec![listener].into()
+    }
+}
+
+impl From<Vec<TcpListener>> for Listeners {
+    fn from(listeners: Vec<TcpListener>) -> Self {
+        Self { listeners, next: 0 }
+    }
+}
+
+/// The listeners and how their connections are accepted.
+///
+/// Over the connections cap, up to the queue length connections wait
+/// for a free slot, at most the queue timeout; the others are closed
+/// right after the accept.
+///
+/// A connection panicking is logged and counted, its slot freed; the
+/// other connections and the accept loop go on. So does a failed
+/// accept, e.g. out of file descriptors, after a pause.
+///
+/// With workers, every set of listeners accepts, admits and spawns
+/// its connections on its own task.
+pub struct Server {
+    listeners: Listeners,
+    workers: Vec<Listeners>,
+    max_connections: Option<usize>,
+    queue: usize,
+    queue_timeout: Option<Duration>,
+    on_reject: Option<OnReject>,
+    on_crash: Option<OnCrash>,
+    shutdown: CancellationToken,
+    shutdown_timeout: Duration,
+    idle_timeout: Option<Duration>,
+    default_idle_timeout: Option<Duration>,
+    chaos: Option<ChaosConfig>,
+    hex_dump: Option<usize>,
+    stats: Arc<Stats>,
+    problem: Option<&'static str>,
+}
+
+impl Server {
+    #[must_use]
+    pub fn new(listeners: impl Into<Listeners>) -> Self {
+        Self {
+            listeners: listeners.into(),
+            workers: Vec::new(),
+            max_connections: None,
+            queue: 0,
+            queue_timeout: None,
+            on_reject: None,
+            on_crash: None,
+            shutdown: CancellationToken::new(),
+            shutdown_timeout: SHUTDOWN_TIMEOUT,
+            idle_timeout: None,
+            default_idle_timeout: None,
+            chaos: None,
+            hex_dump: None,
+            stats: Arc::new(Stats::new()),
+            problem: None,
+        }
+    }
+
+    /// Accept also on the listeners of other `workers`, e.g. bound by
+    /// [`bind_workers`](crate::bind_workers), each set on its own task.
+    #[must_use]
+    pub fn with_workers(self, workers: Vec<Listeners>) -> Self {
+        Self { workers, ..self }
+    }
+
+    /// Close the connections over this number of concurrent ones
+    /// right after the accept, no cap when `None`.
+    #[must_use]
+    pub fn with_max_connections(self, max_connections: Option<usize>) -> Self {
+        Self {
+            max_connections,
+            ..self
+        }
+    }
+
+    /// Let up to `queue` connections over the cap wait for a free
+    /// slot instead of closing them.
+    #[must_use]
+    pub fn with_queue(self, queue: usize) -> Self {
+        Self { queue, ..self }
+    }
+
+    /// Close the queued connections still waiting after
+    /// `queue_timeout`, no timeout when `None`.
+    #[must_use]
+    pub fn with_queue_timeout(self, queue_timeout: Option<Duration>) -> Self {
+        Self {
+            queue_timeout,
+            ..self
+        }
+    }
+
+    /// Call `on_reject` for every connection closed over the cap.
+    #[must_use]
+    pub fn with_on_reject(self, on_reject: impl Fn(SocketAddr) + Send + Sync + 'static) -> Self {
+        Self {
+            on_reject: Some(Arc::new(on_reject)),
+            ..self
+        }
+    }
+
+    /// Call `on_crash` for every connection whose handler panicked.
+    #[must_use]
+    pub fn with_on_crash(self, on_crash: impl Fn(SocketAddr) + Send + Sync + 'static) -> Self {
+        Self {
+            on_crash: Some(Arc::new(on_crash)),
+            ..self
+        }
+    }
+
+    /// Wait for the open connections at most `shutdown_timeout` on
+    /// shutdown, then abort them.
+    #[must_use]
+    pub fn with_shutdown_timeout(self, shutdown_timeout: Duration) -> Self {
+        Self {
+            shutdown_timeout,
+            ..self
+        }
+    }
+
+    /// Close the connections without bytes in either direction for
+    /// `idle_timeout`, never when zero; the default of the problem
+    /// when `None`.
+    #[must_use]
+    pub fn with_idle_timeout(self, idle_timeout: Option<Duration>) -> Self {
+        Self {
+            idle_timeout,
+            ..self
+        }
+    }
+
+    /// The idle timeout of the problem, when not configured.
+    #[must_use]
+    pub fn with_default_idle_timeout(self, default_idle_timeout: Duration) -> Self {
+        Self {
+            default_idle_timeout: Some(default_idle_timeout),
+            ..self
+        }
+    }
+
+    /// The idle timeout of the connections, if any.
+    #[must_use]
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        self.idle_timeout
+            .or(self.default_idle_timeout)
+            .filter(|idle_timeout| !idle_timeout.is_zero())
+    }
+
+    /// Inject the faults of `chaos` into the connections, for testing.
+    #[must_use]
+    pub fn with_chaos(self, chaos: Option<ChaosConfig>) -> Self {
+        if let Some(chaos) = &chaos {
+            warn!("chaos: {chaos:?}");
+        }
+        Self { chaos, ..self }
+    }
+
+    /// Log the hex dumps of up to `limit` bytes per read or write of
+    /// the connections.
+    #[must_use]
+    pub fn with_hex_dump(self, hex_dump: Option<usize>) -> Self {
+        Self { hex_dump, ..self }
+    }
+
+    /// Report the connection stats into `registry`, every
+    /// [`REPORT_INTERVAL`](protohackers_metrics::process::REPORT_INTERVAL).
+    #[must_use]
+    pub fn with_metrics(self, registry: &Registry) -> Self {
+        health::report(self.stats.clone(), registry, process::REPORT_INTERVAL);
+        self
+    }
+
+    /// Label the logs of the server, and of its connections, with
+    /// `problem`.
+    #[must_use]
+    pub fn with_problem(self, problem: &'static str) -> Self {
+        Self {
+            problem: Some(problem),
+            ..self
+        }
+    }
+
+    /// Shut down when `signal` completes.
+    #[must_use]
+    pub fn with_shutdown_signal(self, signal: impl Future<Output = ()> + Send + 'static) -> Self {
+        let shutdown = self.shutdown.clone();
+        crate::task::spawn("shutdown signal", async move {
+            signal.await;
+            info!("shutdown requested");
+            shutdown.cancel();
+        });
+        self
+    }
+
+    /// The token shutting down the server when cancelled; the handlers
+    /// can watch it to end their connections early.
+    #[must_use]
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown.clone()
+    }
+
+    /// The connection stats, ready while the server accepts the
+    /// connections.
+    #[must_use]
+    pub fn stats(&self) -> Arc<Stats> {
+        self.stats.clone()
+    }
+
+    /// The address of the first listener.
+    ///
+    /// # Errors
+    /// * Error when the local address of the listener is not
+    ///   available or there are no listeners.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listeners
+            .listeners
+            .first()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no listeners"))?
+            .local_addr()
+    }
+
+    /// The addresses of all the listeners.
+    ///
+    /// # Errors
+    /// * Error when the local address of a listener is not available.
+    pub fn local_addrs(&self) -> io::Result<Vec<SocketAddr>> {
+        self.listeners.local_addrs()
+    }
+
+    /// Handle every connection accepted by any of the listeners with
+    /// `handler`, on its own task, until the shutdown; then wait for
+    /// the open connections. The streams fail once idle for the idle
+    /// timeout.
+    ///
+    /// The future of a queued connection is polled only once it
+    /// gets a free slot, and dropped if it does not. With workers,
+    /// `handler` is called on the tasks of all of them.
+    ///
+    /// The accept loop runs in a `server` span, with the problem
+    /// label, and every connection in a `connection` span, with its
+    /// id and peer.
+    ///
+    /// # Errors
+    /// * None, the failed accepts are logged and counted instead.
+    pub async fn serve<F, Fut, E>(self, handler: F) -> io::Result<()>
+    where
+        F: Fn(IdleTimeout<TcpStream>, SocketAddr) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: fmt::Display + 'static,
+    {
+        let span = info_span!("server", problem = self.problem);
+        self.accept_loop(handler).instrument(span).await
+    }
+
+    async fn accept_loop<F, Fut, E>(mut self, handler: F) -> io::Result<()>
+    where
+        F: Fn(IdleTimeout<TcpStream>, SocketAddr) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: fmt::Display + 'static,
+    {
+        let mut workers = iter::once(mem::take(&mut self.listeners))
+            .chain(mem::take(&mut self.workers))
+            .collect::<Vec<_>>();
+        let shared = Arc::new(self.shared(handler, workers.len()));
+
+        self.stats.set_ready(true);
+        if workers.len() == 1 {
+            shared.accept(workers.remove(0), 0).await;
+        } else {
+            let mut tasks = JoinSet::new();
+            for (worker, listeners) in workers.into_iter().enumerate() {
+                crate::task::spawn_in(
+                    &mut tasks,
+                    &format!("accept worker {worker}"),
+                    shared
+                        .clone()
+                        .accept(listeners, worker)
+                        .instrument(info_span!("worker", worker)),
+                );
+            }
+            while tasks.join_next().await.is_some() {}
+        }
+
+        Ok(())
+    }
+
+    fn shared<F>(&self, handler: F, workers: usize) -> Shared<F> {
+        Shared {
+            handler,
+            workers,
+            connections: Arc::new(Semaphore::new(
+                self.max_connections.unwrap_or(Semaphore::MAX_PERMITS),
+            )),
+            queue: Arc::new(Semaphore::new(self.queue)),
+            queue_timeout: self.queue_timeout,
+            on_reject: self.on_reject.clone(),
+            on_crash: self.on_crash.clone(),
+            shutdown: self.shutdown.clone(),
+            shutdown_timeout: self.shutdown_timeout,
+            idle_timeout: self.idle_timeout(),
+            chaos: self.chaos,
+            hex_dump: self.hex_dump,
+            stats: self.stats.clone(),
+        }
+    }
+}
+
+/// What the accept loops of the workers share: the handler and the
+/// settings, read only, the caps, the stats and the shutdown.
+struct Shared<F> {
+    handler: F,
+    workers: usize,
+    connections: Arc<Semaphore>,
+    queue: Arc<Semaphore>,
+    queue_timeout: Option<Duration>,
+    on_reject: Option<OnReject>,
+    on_crash: Option<OnCrash>,
+    shutdown: CancellationToken,
+    shutdown_timeout: Duration,
+    idle_timeout: Option<Duration>,
+    chaos: Option<ChaosConfig>,
+    hex_dump: Option<usize>,
+    stats: Arc<Stats>,
+}
+
+impl<F, Fut, E> Shared<F>
+where
+    F: Fn(IdleTimeout<TcpStream>, SocketAddr) -> Fut,
+    Fut: Future<Output = Result<(), E>> + Send + 'static,
+    E: fmt::Display,
+{
+    /// Accept, admit and spawn the connections of `listeners` until
+    /// the shutdown, then wait for them; the ids of the `worker` are
+    /// apart from the ones of the others.
+    async fn accept(self: Arc<Self>, mut listeners: Listeners, worker: usize) {
+        let mut tasks = JoinSet::new();
+        let mut ids = (worker as u64..).step_by(self.workers);
+        loop {
+            tokio::select! {
+                () = self.shutdown.cancelled() => break,
+
+                Some(_) = tasks.join_next(), if !tasks.is_empty() => {}
+
+                accepted = listeners.accept(), if !listeners.is_empty() => match accepted {
+                    Ok((stream, peer)) => {
+                        let id = ids.next().unwrap_or_default();
+                        self.admit(&mut tasks, stream, peer, id);
+                    }
+                    Err(err) => accept_failed(&self.stats, &err).await,
+                },
+            }
+        }
+
+        self.stats.set_ready(false);
+        drop(listeners);
+
+        info!("waiting for {} connections", tasks.len());
+        let drained = time::timeout(self.shutdown_timeout, async {
+            while tasks.join_next().await.is_some() {}
+        })
+        .await;
+        if drained.is_err() {
+            warn!("aborting {} connections", tasks.len());
+            tasks.shutdown().await;
+        }
+    }
+
+    /// Spawn the connection `id` on `tasks`, as accepted or queued, or
+    /// close it over the caps.
+    fn admit(&self, tasks: &mut JoinSet<()>, stream: TcpStream, peer: SocketAddr, id: u64) {
+        self.stats.accept();
+
+        let span = info_span!("connection", id, %peer);
+
+        let admission = if let Ok(permit) = self.connections.clone().try_acquire_owned() {
+            Admission::Accepted(permit)
+        } else if let Ok(queued) = self.queue.clone().try_acquire_owned() {
+            Admission::Queued(queued)
+        } else {
+            span.in_scope(|| reject(&self.stats, self.on_reject.as_ref(), peer));
+            return;
+        };
+
+        let stream = IdleTimeout::new(stream, self.idle_timeout)
+            .with_chaos(self.chaos, id)
+            .with_hex_dump(self.hex_dump, id);
+        let connection = span
+            .in_scope(|| panic::catch_unwind(AssertUnwindSafe(|| (self.handler)(stream, peer))));
+        let connection = match connection {
+            Ok(connection) => connection,
+            Err(payload) => {
+                span.in_scope(|| crash(&self.stats, self.on_crash.as_ref(), peer, &*payload));
+                return;
+            }
+        };
+
+        let (stats, on_crash) = (self.stats.clone(), self.on_crash.clone());
+        let task = async move {
+            let result = AssertUnwindSafe(connection).catch_unwind().await;
+            supervise(result, peer, &stats, on_crash.as_ref());
+        };
+
+        let task = match admission {
+            Admission::Accepted(permit) => {
+                span.in_scope(|| debug!("accepted {peer}"));
+
+                let active = self.stats.open();
+                Either::Left(async move {
+                    task.await;
+                    drop((permit, active));
+                })
+            }
+            Admission::Queued(queued) => {
+                span.in_scope(|| debug!("queued {peer}"));
+
+                let connections = self.connections.clone();
+                let (stats, on_reject) = (self.stats.clone(), self.on_reject.clone());
+                let waiting = stats.enqueue();
+                let (shutdown, queue_timeout) = (self.shutdown.clone(), self.queue_timeout);
+                Either::Right(async move {
+                    let permit = tokio::select! {
+                        () = shutdown.cancelled() => None,
+                        permit = timeout(queue_timeout, connections.acquire_owned()) => {
+                            permit.and_then(Result::ok)
+                        }
+                    };
+                    drop((queued, waiting));
+
+                    if let Some(permit) = permit {
+                        debug!("dequeued {peer}");
+                        let active = stats.open();
+                        task.await;
+                        drop((permit, active));
+                    } else {
+                        reject(&stats, on_reject.as_ref(), peer);
+                    }
+                })
+            }
+        };
+        crate::task::spawn_in(tasks, &format!("connection {id}"), task.instrument(span));
+    }
+}
+
+enum Admission {
+    Accepted(OwnedSemaphorePermit),
+    Queued(OwnedSemaphorePermit),
+}
+
+/// Wait for `future` at most `duration`, forever when `None`.
+async fn timeout<F: Future>(duration: Option<Duration>, future: F) -> Option<F::Output> {
+    match duration {
+        Some(duration) => time::timeout(duration, future).await.ok(),
+        None => Some(future.await),
+    }
+}
+
+/// Log the error or the panic ending the connection of `peer`.
+fn supervise<E: fmt::Display>(
+    result: Result<Result<(), E>, Box<dyn Any + Send>>,
+    peer: SocketAddr,
+    stats: &Stats,
+    on_crash: Option<&OnCrash>,
+) {
+    match result {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => warn!("{peer}: {err}"),
+        Err(payload) => crash(stats, on_crash, peer, &*payload),
+    }
+}
+
+/// Log the panic of the handler of `peer`, in the connection span,
+/// and count it.
+fn crash(stats: &Stats, on_crash: Option<&OnCrash>, peer: SocketAddr, payload: &(dyn Any + Send)) {
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic");
+    error!("{peer}: panicked: {message}");
+    stats.crash();
+    if let Some(on_crash) = on_crash {
+        on_crash(peer);
+    }
+}
+
+/// Log and count the failed accept, pausing when out of file
+/// descriptors.
+async fn accept_failed(stats: &Stats, err: &io::Error) {
+    error!("accept: {err}");
+    stats.fail();
+    if out_of_fds(err) {
+        time::sleep(ACCEPT_BACKOFF).await;
+    }
+}
+
+#[cfg(unix)]
+fn out_of_fds(err: &io::Error) -> bool {
+    matches!(err.raw_os_error(), Some(libc::EMFILE | libc::ENFILE))
+}
+
+#[cfg(not(unix))]
+fn out_of_fds(_: &io::Error) -> bool {
+    false
+}
+
+fn reject(stats: &Stats, on_reject: Option<&OnReject>, peer: SocketAddr) {
+    warn!("too many connections, closing {peer}");
+    stats.reject();
+    if let Some(on_reject) = on_reject {
+        on_reject(peer);
+    }
+}
+
+impl fmt::Debug for Server {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Server")
+            .field("listeners", &self.listeners.listeners)
+            .field("workers", &self.workers.len())
+            .field("max_connections", &self.max_connections)
+            .field("queue", &self.queue)
+            .field("queue_timeout", &self.queue_timeout)
+            .field("shutdown_timeout", &self.shutdown_timeout)
+            .field("idle_timeout", &self.idle_timeout())
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,151 @@
+//! The signals of the servers: SIGINT and SIGTERM shut down
+//! gracefully, a second one, or the passing of the deadline, exits at
+//! once; SIGQUIT prints a summary of the tasks, the runtime and the
+//! connections, the server going on:
+//!
+//! ```raw
+//! kill -QUIT <pid>
+//! ```
+use std::fmt::Write as _;
+use std::future::{self, Future};
+use std::io;
+use std::process;
+#[cfg(unix)]
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use tokio::runtime::Handle;
+
+use tracing::{error, info, warn};
+
+use crate::health::Stats;
+
+/// The time past the shutdown timeout of the connections before the
+/// process exits anyway, e.g. stuck on a blocking task.
+pub const EXIT_GRACE: Duration = Duration::from_secs(5);
+
+/// The exit code of a forced exit.
+const FORCED_EXIT: i32 = 1;
+
+/// Complete on the first SIGINT or SIGTERM, then exit the process on
+/// a second one or after `deadline`, whatever the shutdown is doing.
+pub async fn shutdown(deadline: Duration) {
+    let mut signals = match Signals::new() {
+        Ok(signals) => signals,
+        Err(err) => {
+            warn!("shutdown signals: {err}");
+            return future::pending().await;
+        }
+    };
+
+    let signal = signals.recv().await;
+    info!("{signal}: shutting down, exiting in at most {deadline:?}");
+
+    // a thread, as the tasks are dropped with the runtime
+    thread::spawn(move || {
+        thread::sleep(deadline);
+        error!("shutdown deadline of {deadline:?} passed, exiting");
+        process::exit(FORCED_EXIT);
+    });
+    crate::task::spawn("forced exit", async move {
+        let signal = signals.recv().await;
+        warn!("{signal}: exiting now");
+        process::exit(FORCED_EXIT);
+    });
+}
+
+/// Run `future` until SIGINT or SIGTERM, for the servers without a
+/// graceful shutdown of their own, `None` when interrupted.
+pub async fn until_shutdown<F: Future>(future: F) -> Option<F::Output> {
+    tokio::select! {
+        output = future => Some(output),
+        () = shutdown(EXIT_GRACE) => None,
+    }
+}
+
+/// The SIGINT and SIGTERM signals, ctrl-c only but on unix.
+struct Signals {
+    #[cfg(unix)]
+    interrupt: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    terminate: tokio::signal::unix::Signal,
+}
+
+impl Signals {
+    #[cfg(unix)]
+    fn new() -> io::Result<Self> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        Ok(Self {
+            interrupt: signal(SignalKind::interrupt())?,
+            terminate: signal(SignalKind::terminate())?,
+        })
+    }
+
+    #[cfg(not(unix))]
+    fn new() -> io::Result<Self> {
+        Ok(Self {})
+    }
+
+    /// The name of the next signal.
+    #[cfg(unix)]
+    async fn recv(&mut self) -> &'static str {
+        tokio::select! {
+            _ = self.interrupt.recv() => "SIGINT",
+            _ = self.terminate.recv() => "SIGTERM",
+        }
+    }
+
+    #[cfg(not(unix))]
+    async fn recv(&mut self) -> &'static str {
+        if tokio::signal::ctrl_c().await.is_err() {
+            future::pending::<()>().await;
+        }
+        "ctrl-c"
+    }
+}
+
+/// Print the [`summary`] to stderr on every SIGQUIT, within the
+/// runtime, whatever the filter of the logs.
+///
+/// # Errors
+/// * Error when the signal handler can not be installed.
+#[cfg(unix)]
+pub fn dump_on_signal(stats: Option<Arc<Stats>>) -> io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut signal = signal(SignalKind::quit())?;
+    crate::task::spawn("state dump", async move {
+        while signal.recv().await.is_some() {
+            eprintln!("SIGQUIT: {}", summary(stats.as_deref()));
+        }
+    });
+    Ok(())
+}
+
+/// The tasks alive by name, the ones of the runtime, and the
+/// connections of `stats`, if any.
+#[must_use]
+pub fn summary(stats: Option<&Stats>) -> String {
+    let tasks = crate::task::alive()
+        .iter()
+        .map(|(name, count)| format!("{name}: {count}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut summary = format!("tasks {{{tasks}}}");
+    if let Ok(handle) = Handle::try_current() {
+        let metrics = handle.metrics();
+        let _ = write!(
+            summary,
+            ", runtime {{alive tasks: {}, workers: {}, global queue: {}}}",
+            metrics.num_alive_tasks(),
+            metrics.num_workers(),
+            metrics.global_queue_depth()
+        );
+    }
+    if let Some(stats) = stats {
+        let _ = write!(summary, ", connections {}", stats.snapshot());
+    }
+    summary
+}
@@ -0,0 +1,138 @@
+//! The long-lived tasks, named for `tokio-console`.
+//!
+//! Built with the `console` feature the global subscriber serves
+//! `tokio-console` too, and with `--cfg tokio_unstable` the tasks are
+//! named after what they do, e.g. `connection 12` or `controller`:
+//!
+//! ```raw
+//! RUSTFLAGS="--cfg tokio_unstable" cargo run --features console --bin p11-pest-control
+//! tokio-console
+//! ```
+//!
+//! Otherwise the tasks are spawned as by [`tokio::spawn`], without a
+//! name. Either way, the tasks alive are counted by name, for the
+//! summary logged on SIGQUIT.
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Mutex, PoisonError};
+
+use tokio::task::{AbortHandle, JoinHandle, JoinSet};
+
+/// Spawn `future` on its own task named `name`.
+///
+/// # Panics
+/// * Panics outside of the runtime, as [`tokio::spawn`].
+pub fn spawn<F>(name: &str, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let future = Alive::track(name, future);
+
+    #[cfg(all(feature = "console", tokio_unstable))]
+    return tokio::task::Builder::new()
+        .name(name)
+        .spawn(future)
+        .unwrap_or_else(|err| panic!("spawning {name}: {err}"));
+
+    #[cfg(not(all(feature = "console", tokio_unstable)))]
+    {
+        let _ = name;
+        tokio::spawn(future)
+    }
+}
+
+/// Spawn `future` on its own task named `name`, in `tasks`.
+///
+/// # Panics
+/// * Panics outside of the runtime, as [`JoinSet::spawn`].
+pub fn spawn_in<T, F>(tasks: &mut JoinSet<T>, name: &str, future: F) -> AbortHandle
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    let future = Alive::track(name, future);
+
+    #[cfg(all(feature = "console", tokio_unstable))]
+    return tasks
+        .build_task()
+        .name(name)
+        .spawn(future)
+        .unwrap_or_else(|err| panic!("spawning {name}: {err}"));
+
+    #[cfg(not(all(feature = "console", tokio_unstable)))]
+    {
+        let _ = name;
+        tasks.spawn(future)
+    }
+}
+
+static ALIVE: Mutex<BTreeMap<String, usize>> = Mutex::new(BTreeMap::new());
+
+/// The tasks alive, by name without the trailing number, e.g.
+/// `connection` for `connection 12`.
+#[must_use]
+pub fn alive() -> Vec<(String, usize)> {
+    let alive = ALIVE.lock().unwrap_or_else(PoisonError::into_inner);
+    alive
+        .iter()
+        .map(|(kind, count)| (kind.clone(), *count))
+        .collect()
+}
+
+/// A task counted alive until dropped, also when aborted.
+struct Alive(String);
+
+impl Alive {
+    fn track<F: Future>(name: &str, future: F) -> impl Future<Output = F::Output> {
+        let kind = name
+            .trim_end_matches(|c: char| c.is_ascii_digit())
+            .trim_end();
+        let alive = Self(if kind.is_empty() { name } else { kind }.to_string());
+        *ALIVE
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(alive.0.clone())
+            .or_default() += 1;
+
+        async move {
+            let _alive = alive;
+            future.await
+        }
+    }
+}
+
+impl Drop for Alive {
+    fn drop(&mut self) {
+        let mut alive = ALIVE.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(count) = alive.get_mut(&self.0) {
+            *count -= 1;
+            if *count == 0 {
+                alive.remove(&self.0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_alive() {
+        let (sender, receiver) = tokio::sync::oneshot::channel::<()>();
+        let handle = spawn("test alive 7", async move {
+            let _ = receiver.await;
+        });
+        let mut tasks = JoinSet::new();
+        spawn_in(&mut tasks, "test alive 8", std::future::pending::<()>());
+        assert!(alive().contains(&("test alive".to_string(), 2)));
+
+        sender.send(()).unwrap();
+        handle.await.unwrap();
+        assert!(alive().contains(&("test alive".to_string(), 1)));
+
+        tasks.shutdown().await;
+        assert!(!alive().iter().any(|(kind, _)| kind == "test alive"));
+    }
+}
@@ -0,0 +1,74 @@
+//! A process of its own, as the limit of the file descriptors is
+//! lowered for all the threads.
+#![cfg(unix)]
+
+use std::fs::File;
+use std::os::fd::AsRawFd;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{self, timeout};
+
+use protohackers_server::{IdleTimeout, Server};
+
+const TIMEOUT: Duration = Duration::from_millis(500);
+
+async fn echo(mut stream: IdleTimeout<TcpStream>) -> Result<(), std::io::Error> {
+    let (mut read, mut write) = stream.split();
+    tokio::io::copy(&mut read, &mut write).await?;
+    write.shutdown().await
+}
+
+fn nofile() -> libc::rlimit {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    // SAFETY: a valid pointer to a rlimit
+    assert_eq!(0, unsafe {
+        libc::getrlimit(libc::RLIMIT_NOFILE, &raw mut limit)
+    });
+    limit
+}
+
+fn set_nofile(limit: &libc::rlimit) {
+    // SAFETY: a valid pointer to a rlimit
+    assert_eq!(0, unsafe { libc::setrlimit(libc::RLIMIT_NOFILE, limit) });
+}
+
+#[tokio::test]
+async fn test_out_of_fds() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let server = Server::new(listener);
+    let stats = server.stats();
+    tokio::spawn(server.serve(|stream, _| echo(stream)));
+
+    // the client takes the last descriptor, none left for the accept
+    let free = File::open("/dev/null").unwrap().as_raw_fd();
+    let limit = nofile();
+    set_nofile(&libc::rlimit {
+        rlim_cur: libc::rlim_t::try_from(free + 1).unwrap(),
+        ..limit
+    });
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    timeout(Duration::from_secs(5), async {
+        while stats.snapshot().failed == 0 {
+            time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    set_nofile(&limit);
+
+    // accepted after the pause
+    stream.write_all(b"hello").await.unwrap();
+    let mut buffer = [0; 5];
+    timeout(TIMEOUT, stream.read_exact(&mut buffer))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(b"hello", &buffer);
+    assert_eq!(1, stats.snapshot().accepted);
+}
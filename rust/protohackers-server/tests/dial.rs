@@ -0,0 +1,244 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use tokio::io::{self, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::timeout;
+
+use protohackers_server::dial::{Proxy, PROXY_ENV};
+use protohackers_server::{DialArgs, Dialer};
+
+const TIMEOUT: Duration = Duration::from_millis(500);
+
+async fn echo_server() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            tokio::spawn(async move {
+                let (mut read, mut write) = stream.split();
+                io::copy(&mut read, &mut write).await
+            });
+        }
+    });
+    address
+}
+
+/// A SOCKS5 proxy accepting `credentials`, or no authentication when
+/// `None`, and only the connections to `localhost`.
+async fn socks5_proxy(credentials: Option<(&'static str, &'static str)>) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            tokio::spawn(async move {
+                let method = if credentials.is_some() { 0x02 } else { 0x00 };
+                let mut greeting = [0; 3];
+                stream.read_exact(&mut greeting).await?;
+                assert_eq!([0x05, 0x01, method], greeting);
+                stream.write_all(&[0x05, method]).await?;
+
+                if let Some((user, password)) = credentials {
+                    let mut request = vec![0; 3 + user.len() + password.len()];
+                    stream.read_exact(&mut request).await?;
+                    let mut expected = vec![0x01, u8::try_from(user.len()).unwrap()];
+                    expected.extend_from_slice(user.as_bytes());
+                    expected.push(u8::try_from(password.len()).unwrap());
+                    expected.extend_from_slice(password.as_bytes());
+                    if request != expected {
+                        return stream.write_all(&[0x01, 0x01]).await;
+                    }
+                    stream.write_all(&[0x01, 0x00]).await?;
+                }
+
+                let mut request = [0; 5];
+                stream.read_exact(&mut request).await?;
+                assert_eq!([0x05, 0x01, 0x00, 0x03], request[..4]);
+                let mut host = vec![0; usize::from(request[4])];
+                stream.read_exact(&mut host).await?;
+                let port = stream.read_u16().await?;
+                if host != b"localhost" {
+                    return stream
+                        .write_all(&[0x05, 0x02, 0x00, 0x01, 0, 0, 0, 0, 0, 0])
+                        .await;
+                }
+
+                let mut upstream = TcpStream::connect(("127.0.0.1", port)).await?;
+                stream
+                    .write_all(&[0x05, 0x00, 0x00, 0x01, 127, 0, 0, 1, 0, 0])
+                    .await?;
+                io::copy_bidirectional(&mut stream, &mut upstream)
+                    .await
+                    .map(|_| ())
+            });
+        }
+    });
+    address
+}
+
+/// An HTTP proxy answering `CONNECT` with the credentials
+/// `dXNlcjpwYXNz`, `user:pass`, and only to `localhost`.
+async fn http_proxy() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            tokio::spawn(async move {
+                let mut stream = BufReader::new(stream);
+                let mut head = vec![];
+                loop {
+                    let mut line = String::new();
+                    stream.read_line(&mut line).await?;
+                    if line == "\r\n" {
+                        break;
+                    }
+                    head.push(line);
+                }
+
+                let authorized = head
+                    .iter()
+                    .any(|line| line == "Proxy-Authorization: Basic dXNlcjpwYXNz\r\n");
+                let port = head[0]
+                    .strip_prefix("CONNECT localhost:")
+                    .and_then(|rest| rest.strip_suffix(" HTTP/1.1\r\n"))
+                    .and_then(|port| port.parse::<u16>().ok());
+                let stream = stream.get_mut();
+                match (authorized, port) {
+                    (false, _) => {
+                        stream
+                            .write_all(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n")
+                            .await
+                    }
+                    (true, None) => stream.write_all(b"HTTP/1.1 403 Forbidden\r\n\r\n").await,
+                    (true, Some(port)) => {
+                        let mut upstream = TcpStream::connect(("127.0.0.1", port)).await?;
+                        stream
+                            .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+                            .await?;
+                        io::copy_bidirectional(stream, &mut upstream)
+                            .await
+                            .map(|_| ())
+                    }
+                }
+            });
+        }
+    });
+    address
+}
+
+async fn round_trip(dialer: &Dialer, port: u16) {
+    let mut stream = timeout(TIMEOUT, dialer.connect("localhost", port))
+        .await
+        .unwrap()
+        .unwrap();
+    stream.write_all(b"hello").await.unwrap();
+    let mut buffer = [0; 5];
+    timeout(TIMEOUT, stream.read_exact(&mut buffer))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(b"hello", &buffer);
+}
+
+fn dialer(url: &str) -> Dialer {
+    Dialer::with_proxy(Some(url.parse::<Proxy>().unwrap()))
+}
+
+#[tokio::test]
+async fn test_direct() {
+    let echo = echo_server().await;
+    round_trip(&Dialer::direct(), echo.port()).await;
+}
+
+#[tokio::test]
+async fn test_socks5() {
+    let echo = echo_server().await;
+
+    let proxy = socks5_proxy(None).await;
+    round_trip(&dialer(&format!("socks5://{proxy}")), echo.port()).await;
+
+    let proxy = socks5_proxy(Some(("user", "pass"))).await;
+    round_trip(&dialer(&format!("socks5://user:pass@{proxy}")), echo.port()).await;
+    assert!(dialer(&format!("socks5://user:wrong@{proxy}"))
+        .connect("localhost", echo.port())
+        .await
+        .is_err());
+    // no credentials offered
+    assert!(dialer(&format!("socks5://{proxy}"))
+        .connect("localhost", echo.port())
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_http() {
+    let echo = echo_server().await;
+    let proxy = http_proxy().await;
+
+    round_trip(&dialer(&format!("http://user:pass@{proxy}")), echo.port()).await;
+
+    let err = dialer(&format!("http://{proxy}"))
+        .connect("localhost", echo.port())
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("407"), "{err}");
+    let err = dialer(&format!("http://user:pass@{proxy}"))
+        .connect("127.0.0.1", echo.port())
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("403"), "{err}");
+}
+
+#[test]
+fn test_args() {
+    use clap::Parser;
+
+    #[derive(clap::Parser, Debug)]
+    struct Args {
+        #[command(flatten)]
+        dial: DialArgs,
+    }
+
+    assert!(Args::parse_from(["test"]).dial.dialer().proxy().is_none());
+
+    let args = Args::parse_from(["test", "--proxy", "socks5://127.0.0.1:1080"]);
+    assert_eq!(
+        Some("127.0.0.1"),
+        args.dial.dialer().proxy().map(|proxy| proxy.host.as_str())
+    );
+    assert!(Args::try_parse_from(["test", "--proxy", "ftp://127.0.0.1"]).is_err());
+}
+
+#[test]
+fn test_args_env() {
+    use clap::Parser;
+
+    #[derive(clap::Parser, Debug)]
+    struct Args {
+        #[command(flatten)]
+        dial: DialArgs,
+    }
+
+    const PROXY: &str = "http://proxy:3128";
+
+    // the environment is shared by the parallel tests, the variable is
+    // set only for a child process running this test
+    if std::env::var(PROXY_ENV).as_deref() != Ok(PROXY) {
+        let status = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["test_args_env", "--exact", "--quiet"])
+            .env(PROXY_ENV, PROXY)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        return;
+    }
+
+    // the flag wins over the environment
+    let args = Args::parse_from(["test"]);
+    assert_eq!(Some(3128), args.dial.proxy.as_ref().map(|proxy| proxy.port));
+    let args = Args::parse_from(["test", "--proxy", "socks5://proxy"]);
+    assert_eq!(Some(1080), args.dial.proxy.as_ref().map(|proxy| proxy.port));
+}
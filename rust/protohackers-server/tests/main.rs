@@ -0,0 +1,632 @@
+use std::convert::Infallible;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use clap::Parser;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::oneshot;
+use tokio::time::{self, timeout};
+
+use protohackers_metrics::{Registry, Value};
+
+use protohackers_server::health::{self, LIVE_PATH, READY_PATH};
+use protohackers_server::signals;
+use protohackers_server::{
+    ChaosConfig, IdleTimeout, LogFormat, Server, ServerArgs, LOG_FORMAT_ENV,
+};
+
+const TIMEOUT: Duration = Duration::from_millis(500);
+
+async fn echo(mut stream: IdleTimeout<TcpStream>) -> Result<(), std::io::Error> {
+    let (mut read, mut write) = stream.split();
+    tokio::io::copy(&mut read, &mut write).await?;
+    write.shutdown().await
+}
+
+async fn bind() -> (Server, String) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server = Server::new(listener);
+    let address = server.local_addr().unwrap().to_string();
+    (server, address)
+}
+
+async fn round_trip(stream: &mut TcpStream, data: &[u8]) {
+    stream.write_all(data).await.unwrap();
+    let mut buffer = vec![0; data.len()];
+    timeout(TIMEOUT, stream.read_exact(&mut buffer))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(data, buffer);
+}
+
+async fn assert_closed(stream: &mut TcpStream) {
+    let mut buffer = vec![];
+    assert!(matches!(
+        timeout(TIMEOUT, stream.read_to_end(&mut buffer))
+            .await
+            .unwrap(),
+        Ok(0) | Err(_)
+    ));
+}
+
+/// The status line and the body of a GET.
+async fn get(address: &str, path: &str) -> (String, String) {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    stream
+        .write_all(format!("GET {path} HTTP/1.1\r\nHost: test\r\n\r\n").as_bytes())
+        .await
+        .unwrap();
+    let mut response = String::new();
+    timeout(TIMEOUT, stream.read_to_string(&mut response))
+        .await
+        .unwrap()
+        .unwrap();
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.lines().next().unwrap().to_string();
+    (status, body.to_string())
+}
+
+#[test]
+fn test_args() {
+    #[derive(clap::Parser, Debug)]
+    struct Args {
+        #[command(flatten)]
+        server: ServerArgs,
+    }
+
+    let args = Args::parse_from(["test"]);
+    assert_eq!(vec!["[::]:10000"], args.server.socket_addresses());
+    assert_eq!(LogFormat::Full, args.server.log_format);
+    assert_eq!(None, args.server.max_connections);
+    assert_eq!(0, args.server.connection_queue);
+    assert_eq!(None, args.server.queue_timeout);
+    assert_eq!(None, args.server.health_address);
+    assert_eq!(None, args.server.idle_timeout);
+    assert_eq!(None, args.server.hex_dump);
+    assert_eq!(1, args.server.workers);
+
+    let args = Args::parse_from([
+        "test",
+        "--address",
+        "127.0.0.1",
+        "--port",
+        "1234",
+        "--log-format",
+        "compact",
+        "--max-connections",
+        "10",
+        "--connection-queue",
+        "5",
+        "--queue-timeout",
+        "2",
+        "--health-address",
+        "127.0.0.1:8080",
+        "--idle-timeout",
+        "30",
+    ]);
+    assert_eq!(vec!["127.0.0.1:1234"], args.server.socket_addresses());
+    assert_eq!(LogFormat::Compact, args.server.log_format);
+    assert_eq!(Some(10), args.server.max_connections);
+    assert_eq!(5, args.server.connection_queue);
+    assert_eq!(Some(2), args.server.queue_timeout);
+    assert_eq!(
+        Some("127.0.0.1:8080"),
+        args.server.health_address.as_deref()
+    );
+    assert_eq!(Some(30), args.server.idle_timeout);
+
+    let args = Args::parse_from(["test", "--address", "127.0.0.1,::1", "--address", "::"]);
+    assert_eq!(
+        vec!["127.0.0.1:10000", "[::1]:10000", "[::]:10000"],
+        args.server.socket_addresses()
+    );
+
+    let args = Args::parse_from(["test", "--hex-dump"]);
+    assert_eq!(Some(256), args.server.hex_dump);
+    let args = Args::parse_from(["test", "--hex-dump", "16"]);
+    assert_eq!(Some(16), args.server.hex_dump);
+
+    #[cfg(unix)]
+    {
+        let args = Args::parse_from(["test", "--log-filter-file", "p03.filter"]);
+        assert_eq!(
+            Some(std::path::Path::new("p03.filter")),
+            args.server.log_filter_file.as_deref()
+        );
+    }
+
+    // a queue needs a cap
+    assert!(Args::try_parse_from(["test", "--connection-queue", "5"]).is_err());
+
+    let args = Args::parse_from(["test", "--log-format", "json"]);
+    assert_eq!(LogFormat::Json, args.server.log_format);
+    let args = Args::parse_from(["test", "--log-format", "text"]);
+    assert_eq!(LogFormat::Full, args.server.log_format);
+}
+
+#[test]
+fn test_args_env() {
+    #[derive(clap::Parser, Debug)]
+    struct Args {
+        #[command(flatten)]
+        server: ServerArgs,
+    }
+
+    // the environment is shared by the parallel tests, the variable is
+    // set only for a child process running this test
+    if std::env::var(LOG_FORMAT_ENV).as_deref() != Ok("json") {
+        let status = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["test_args_env", "--exact", "--quiet"])
+            .env(LOG_FORMAT_ENV, "json")
+            .status()
+            .unwrap();
+        assert!(status.success());
+        return;
+    }
+
+    // the flag wins over the environment
+    let args = Args::parse_from(["test"]);
+    assert_eq!(LogFormat::Json, args.server.log_format);
+    let args = Args::parse_from(["test", "--log-format", "compact"]);
+    assert_eq!(LogFormat::Compact, args.server.log_format);
+}
+
+#[derive(Clone, Default)]
+struct Logs(Arc<Mutex<Vec<u8>>>);
+
+impl io::Write for Logs {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_log_spans() {
+    let logs = Logs::default();
+    let subscriber = tracing_subscriber::fmt()
+        .json()
+        .flatten_event(true)
+        .with_current_span(false)
+        .with_span_list(true)
+        .with_writer({
+            let logs = logs.clone();
+            move || logs.clone()
+        })
+        .finish();
+    // the current thread runtime runs the connections on this thread
+    let _default = tracing::subscriber::set_default(subscriber);
+
+    let (server, address) = bind().await;
+    let server = server.with_problem("p00-test");
+    tokio::spawn(server.serve(|mut stream, _| async move {
+        tracing::info!("hello");
+        stream.shutdown().await
+    }));
+
+    for _ in 0..2 {
+        let mut stream = TcpStream::connect(&address).await.unwrap();
+        assert_closed(&mut stream).await;
+    }
+
+    let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+    let hellos = logs
+        .lines()
+        .filter(|line| line.contains(r#""message":"hello""#))
+        .collect::<Vec<_>>();
+    assert_eq!(2, hellos.len(), "{logs}");
+    for (id, hello) in hellos.into_iter().enumerate() {
+        assert!(
+            hello.contains(r#"{"problem":"p00-test","name":"server"}"#),
+            "{hello}"
+        );
+        assert!(
+            hello.contains(&format!(r#"{{"id":{id},"peer":"#)),
+            "{hello}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_max_connections() {
+    let (server, address) = bind().await;
+    let rejected = Arc::new(AtomicUsize::new(0));
+    let server = server.with_max_connections(Some(1)).with_on_reject({
+        let rejected = rejected.clone();
+        move |_| {
+            rejected.fetch_add(1, Ordering::Relaxed);
+        }
+    });
+    tokio::spawn(server.serve(|stream, _| echo(stream)));
+
+    let mut first = TcpStream::connect(&address).await.unwrap();
+    round_trip(&mut first, b"first").await;
+
+    // closed right after the accept
+    let mut second = TcpStream::connect(&address).await.unwrap();
+    assert_closed(&mut second).await;
+    assert_eq!(1, rejected.load(Ordering::Relaxed));
+
+    drop(first);
+    time::sleep(Duration::from_millis(50)).await;
+
+    let mut third = TcpStream::connect(&address).await.unwrap();
+    round_trip(&mut third, b"third").await;
+}
+
+#[tokio::test]
+async fn test_queue() {
+    let (server, address) = bind().await;
+    let rejected = Arc::new(AtomicUsize::new(0));
+    let server = server
+        .with_max_connections(Some(1))
+        .with_queue(1)
+        .with_on_reject({
+            let rejected = rejected.clone();
+            move |_| {
+                rejected.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+    tokio::spawn(server.serve(|stream, _| echo(stream)));
+
+    let mut first = TcpStream::connect(&address).await.unwrap();
+    round_trip(&mut first, b"first").await;
+
+    // waits for the first one
+    let mut second = TcpStream::connect(&address).await.unwrap();
+    second.write_all(b"second").await.unwrap();
+    let mut buffer = [0; 6];
+    assert!(
+        timeout(Duration::from_millis(100), second.read_exact(&mut buffer))
+            .await
+            .is_err()
+    );
+
+    // over the queue
+    let mut third = TcpStream::connect(&address).await.unwrap();
+    assert_closed(&mut third).await;
+    assert_eq!(1, rejected.load(Ordering::Relaxed));
+
+    drop(first);
+    timeout(TIMEOUT, second.read_exact(&mut buffer))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(b"second", &buffer);
+}
+
+#[tokio::test]
+async fn test_queue_timeout() {
+    let (server, address) = bind().await;
+    let rejected = Arc::new(AtomicUsize::new(0));
+    let server = server
+        .with_max_connections(Some(1))
+        .with_queue(1)
+        .with_queue_timeout(Some(Duration::from_millis(50)))
+        .with_on_reject({
+            let rejected = rejected.clone();
+            move |_| {
+                rejected.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+    tokio::spawn(server.serve(|stream, _| echo(stream)));
+
+    let mut first = TcpStream::connect(&address).await.unwrap();
+    round_trip(&mut first, b"first").await;
+
+    let mut second = TcpStream::connect(&address).await.unwrap();
+    assert_closed(&mut second).await;
+    assert_eq!(1, rejected.load(Ordering::Relaxed));
+
+    // the queue is free again
+    let mut third = TcpStream::connect(&address).await.unwrap();
+    time::sleep(Duration::from_millis(10)).await;
+    drop(first);
+    round_trip(&mut third, b"third").await;
+}
+
+#[tokio::test]
+async fn test_crash() {
+    let (server, address) = bind().await;
+    let crashed = Arc::new(AtomicUsize::new(0));
+    let server = server.with_max_connections(Some(1)).with_on_crash({
+        let crashed = crashed.clone();
+        move |_| {
+            crashed.fetch_add(1, Ordering::Relaxed);
+        }
+    });
+    let stats = server.stats();
+
+    let connections = AtomicUsize::new(0);
+    tokio::spawn(server.serve(move |stream, _| {
+        let connections = connections.fetch_add(1, Ordering::Relaxed) + 1;
+        assert!(connections != 1, "panic creating the connection");
+        async move {
+            assert!(connections != 2, "panic handling the connection");
+            echo(stream).await
+        }
+    }));
+
+    for _ in 0..2 {
+        let mut stream = TcpStream::connect(&address).await.unwrap();
+        assert_closed(&mut stream).await;
+    }
+    assert_eq!(2, crashed.load(Ordering::Relaxed));
+
+    // the slots are free, the accept loop goes on
+    let mut stream = TcpStream::connect(&address).await.unwrap();
+    round_trip(&mut stream, b"alive").await;
+
+    let snapshot = stats.snapshot();
+    assert_eq!(2, snapshot.crashed);
+    assert_eq!(1, snapshot.active);
+}
+
+#[tokio::test]
+async fn test_chaos() {
+    let (server, address) = bind().await;
+    let chaos = "seed=42,latency=2,short=1".parse::<ChaosConfig>().unwrap();
+    tokio::spawn(
+        server
+            .with_chaos(Some(chaos))
+            .serve(|stream, _| echo(stream)),
+    );
+
+    // the short reads and writes are invisible to the client
+    let mut stream = TcpStream::connect(&address).await.unwrap();
+    round_trip(&mut stream, b"the quick brown fox jumps over the lazy dog").await;
+
+    let (server, address) = bind().await;
+    let chaos = "reset=1".parse::<ChaosConfig>().unwrap();
+    tokio::spawn(
+        server
+            .with_chaos(Some(chaos))
+            .serve(|stream, _| echo(stream)),
+    );
+
+    let mut stream = TcpStream::connect(&address).await.unwrap();
+    stream.write_all(b"reset").await.unwrap();
+    assert_closed(&mut stream).await;
+}
+
+#[tokio::test]
+async fn test_report() {
+    let (server, address) = bind().await;
+    let server = server.with_max_connections(Some(1));
+    let registry = Registry::new();
+    health::report(server.stats(), &registry, Duration::from_millis(10));
+    tokio::spawn(server.serve(|stream, _| echo(stream)));
+
+    let mut stream = TcpStream::connect(&address).await.unwrap();
+    round_trip(&mut stream, b"active").await;
+    let mut rejected = TcpStream::connect(&address).await.unwrap();
+    assert_closed(&mut rejected).await;
+    time::sleep(Duration::from_millis(50)).await;
+
+    let snapshot = registry.snapshot();
+    assert_eq!(
+        Some(&Value::Counter(2)),
+        snapshot.get("server_connections_accepted_total")
+    );
+    assert_eq!(
+        Some(&Value::Counter(1)),
+        snapshot.get("server_connections_rejected_total")
+    );
+    assert_eq!(
+        Some(&Value::Gauge(1)),
+        snapshot.get("server_connections_active")
+    );
+}
+
+#[tokio::test]
+async fn test_summary() {
+    let (server, address) = bind().await;
+    let stats = server.stats();
+    tokio::spawn(server.serve(|stream, _| echo(stream)));
+
+    let mut stream = TcpStream::connect(&address).await.unwrap();
+    round_trip(&mut stream, b"summary").await;
+
+    let summary = signals::summary(Some(&stats));
+    // the connections of the other tests are counted too
+    assert!(summary.contains("connection: "), "{summary}");
+    assert!(summary.contains(r#""accepted":1,"active":1"#), "{summary}");
+
+    assert_eq!(Some(42), signals::until_shutdown(async { 42 }).await);
+}
+
+#[tokio::test]
+async fn test_health() {
+    let (server, address) = bind().await;
+    let (shutdown, signal) = oneshot::channel::<()>();
+    let server = server
+        .with_max_connections(Some(1))
+        .with_shutdown_signal(async {
+            signal.await.ok();
+        });
+
+    let health_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let health_address = health_listener.local_addr().unwrap().to_string();
+    tokio::spawn(health::serve(health_listener, server.stats()));
+
+    // not ready before the accept loop
+    let (status, _) = get(&health_address, READY_PATH).await;
+    assert_eq!("HTTP/1.1 503 Service Unavailable", status);
+    let (status, _) = get(&health_address, LIVE_PATH).await;
+    assert_eq!("HTTP/1.1 200 OK", status);
+
+    tokio::spawn(server.serve(|stream, _| echo(stream)));
+    time::sleep(Duration::from_millis(50)).await;
+
+    let mut first = TcpStream::connect(&address).await.unwrap();
+    round_trip(&mut first, b"first").await;
+    let mut second = TcpStream::connect(&address).await.unwrap();
+    assert_closed(&mut second).await;
+
+    let (status, body) = get(&health_address, READY_PATH).await;
+    assert_eq!("HTTP/1.1 200 OK", status);
+    assert!(
+        body.starts_with(r#"{"ready":true,"#)
+            && body.ends_with(
+                r#""accepted":2,"active":1,"queued":0,"rejected":1,"crashed":0,"failed":0}"#
+            ),
+        "{body}"
+    );
+
+    let (status, _) = get(&health_address, "/unknown").await;
+    assert_eq!("HTTP/1.1 404 Not Found", status);
+
+    // not ready while draining
+    shutdown.send(()).unwrap();
+    time::sleep(Duration::from_millis(50)).await;
+    let (status, body) = get(&health_address, READY_PATH).await;
+    assert_eq!("HTTP/1.1 503 Service Unavailable", status);
+    assert!(body.contains(r#""active":1"#), "{body}");
+}
+
+#[tokio::test]
+async fn test_graceful_shutdown() {
+    let (server, address) = bind().await;
+    let (shutdown, signal) = oneshot::channel::<()>();
+    let server = server.with_shutdown_signal(async {
+        signal.await.ok();
+    });
+    let serve = tokio::spawn(server.serve(|stream, _| echo(stream)));
+
+    let mut stream = TcpStream::connect(&address).await.unwrap();
+    round_trip(&mut stream, b"before").await;
+
+    shutdown.send(()).unwrap();
+    time::sleep(Duration::from_millis(50)).await;
+
+    // no more accepted connections, the open one goes on
+    assert!(TcpStream::connect(&address).await.is_err());
+    round_trip(&mut stream, b"after").await;
+    assert!(!serve.is_finished());
+
+    stream.shutdown().await.unwrap();
+    timeout(TIMEOUT, serve).await.unwrap().unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_shutdown_timeout() {
+    let (server, address) = bind().await;
+    let server = server.with_shutdown_timeout(Duration::from_millis(50));
+    let shutdown = server.shutdown_token();
+    let serve = tokio::spawn(server.serve(|_stream, _| async {
+        std::future::pending::<()>().await;
+        Ok::<_, Infallible>(())
+    }));
+
+    let _stream = TcpStream::connect(&address).await.unwrap();
+    time::sleep(Duration::from_millis(50)).await;
+
+    shutdown.cancel();
+    timeout(TIMEOUT, serve).await.unwrap().unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_listeners() {
+    let listeners = vec![
+        TcpListener::bind("127.0.0.1:0").await.unwrap(),
+        TcpListener::bind("127.0.0.1:0").await.unwrap(),
+    ];
+    let server = Server::new(listeners);
+    let addresses = server.local_addrs().unwrap();
+    assert_eq!(2, addresses.len());
+    assert_eq!(addresses[0], server.local_addr().unwrap());
+
+    tokio::spawn(server.serve(|stream, _| echo(stream)));
+
+    for address in addresses {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        round_trip(&mut stream, b"hello").await;
+    }
+}
+
+#[tokio::test]
+async fn test_dual_stack() {
+    let listeners = protohackers_server::bind_all(&["[::]:0".to_string()])
+        .await
+        .unwrap();
+    let server = Server::new(listeners);
+    let port = server.local_addr().unwrap().port();
+
+    tokio::spawn(server.serve(|stream, _| echo(stream)));
+
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    round_trip(&mut stream, b"ipv4").await;
+    let mut stream = TcpStream::connect(("::1", port)).await.unwrap();
+    round_trip(&mut stream, b"ipv6").await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_workers() {
+    let mut listeners = protohackers_server::bind_workers(&["127.0.0.1:0".to_string()], 4)
+        .await
+        .unwrap();
+    assert_eq!(4, listeners.len());
+    let address = listeners[0].local_addrs().unwrap()[0];
+    for listeners in &listeners {
+        assert_eq!(vec![address], listeners.local_addrs().unwrap());
+    }
+
+    let first = listeners.remove(0);
+    let server = Server::new(first)
+        .with_workers(listeners)
+        .with_max_connections(Some(32));
+    let stats = server.stats();
+    tokio::spawn(server.serve(|stream, _| echo(stream)));
+
+    let clients = (0..32)
+        .map(|i| {
+            tokio::spawn(async move {
+                let mut stream = TcpStream::connect(address).await.unwrap();
+                round_trip(&mut stream, format!("client {i}").as_bytes()).await;
+            })
+        })
+        .collect::<Vec<_>>();
+    for client in clients {
+        client.await.unwrap();
+    }
+    assert_eq!(32, stats.snapshot().accepted);
+}
+
+#[tokio::test]
+async fn test_idle_timeout() {
+    let (server, address) = bind().await;
+    let server = server
+        .with_default_idle_timeout(Duration::from_mins(1))
+        .with_idle_timeout(Some(Duration::from_millis(100)));
+    assert_eq!(Some(Duration::from_millis(100)), server.idle_timeout());
+
+    tokio::spawn(server.serve(|stream, _| echo(stream)));
+
+    let mut stream = TcpStream::connect(&address).await.unwrap();
+    for _ in 0..3 {
+        time::sleep(Duration::from_millis(50)).await;
+        round_trip(&mut stream, b"hello").await;
+    }
+    assert_closed(&mut stream).await;
+}
+
+#[tokio::test]
+async fn test_default_idle_timeout() {
+    let (server, _) = bind().await;
+    let server = server.with_default_idle_timeout(Duration::from_mins(1));
+    assert_eq!(Some(Duration::from_mins(1)), server.idle_timeout());
+
+    // zero disables the default
+    let server = server.with_idle_timeout(Some(Duration::ZERO));
+    assert_eq!(None, server.idle_timeout());
+}
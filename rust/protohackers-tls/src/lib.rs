@@ -0,0 +1,223 @@
+//! The TLS shared by the problem servers: the rustls configurations
+//! loaded from PEM files and the handshakes over any stream.
+//!
+//! A server builds a [`TlsAcceptor`] from its certificate chain and
+//! private key, optionally requiring the client certificates signed by
+//! a CA, and wraps the accepted streams with [`wrap_listener`]; a
+//! client builds a [`TlsConnector`] trusting a CA, optionally with its
+//! own certificate, and wraps the connected streams with
+//! [`wrap_connect`]. [`TlsArgs`] are the standard flags to flatten into
+//! the `Args` of a binary:
+//!
+//! ```no_run
+//! use clap::Parser;
+//!
+//! use tokio::net::TcpListener;
+//!
+//! use protohackers_tls::TlsArgs;
+//!
+//! #[derive(clap::Parser, Debug)]
+//! struct Args {
+//!     #[command(flatten)]
+//!     tls: TlsArgs,
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), anyhow::Error> {
+//!     let args = Args::parse();
+//!     let acceptor = args.tls.acceptor()?.expect("no certificate");
+//!
+//!     let listener = TcpListener::bind("127.0.0.1:10000").await?;
+//!     let (stream, _) = listener.accept().await?;
+//!     let stream = protohackers_tls::wrap_listener(&acceptor, stream).await?;
+//!     # drop(stream);
+//!     # Ok(())
+//! }
+//! ```
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use tokio_rustls::rustls::server::{VerifierBuilderError, WebPkiClientVerifier};
+use tokio_rustls::rustls::{ClientConfig, RootCertStore, ServerConfig};
+
+pub use tokio_rustls::{client, server, TlsAcceptor, TlsConnector};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("no certificates in {0}")]
+    NoCertificates(String),
+
+    #[error("no private key in {0}")]
+    NoPrivateKey(String),
+
+    #[error("client verifier: {0}")]
+    Verifier(#[from] VerifierBuilderError),
+
+    #[error("tls: {0}")]
+    Tls(#[from] tokio_rustls::rustls::Error),
+
+    #[error("io: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// The PEM certificate chain in `path`, at least one.
+///
+/// # Errors
+/// * Error when the file cannot be read or has no certificates.
+pub fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, Error> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(path)?))
+        .collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(Error::NoCertificates(path.display().to_string()));
+    }
+    Ok(certs)
+}
+
+/// The first PEM private key in `path`.
+///
+/// # Errors
+/// * Error when the file cannot be read or has no private key.
+pub fn load_key(path: &Path) -> Result<PrivateKeyDer<'static>, Error> {
+    rustls_pemfile::private_key(&mut BufReader::new(File::open(path)?))?
+        .ok_or_else(|| Error::NoPrivateKey(path.display().to_string()))
+}
+
+/// The certificates in the PEM file `path` as trust anchors.
+///
+/// # Errors
+/// * Error when the file cannot be read or has no valid certificates.
+pub fn load_roots(path: &Path) -> Result<RootCertStore, Error> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(path)? {
+        roots.add(cert)?;
+    }
+    Ok(roots)
+}
+
+/// The server configuration with the certificate chain `cert` and the
+/// private key `key`, requiring a client certificate signed by the
+/// CAs in `client_ca` if given.
+///
+/// # Errors
+/// * Error when a file cannot be read or does not contain valid
+///   certificates or private key.
+pub fn server_config(
+    cert: &Path,
+    key: &Path,
+    client_ca: Option<&Path>,
+) -> Result<ServerConfig, Error> {
+    let (certs, key) = (load_certs(cert)?, load_key(key)?);
+
+    let config = if let Some(client_ca) = client_ca {
+        let verifier = WebPkiClientVerifier::builder(Arc::new(load_roots(client_ca)?)).build()?;
+        ServerConfig::builder()
+            .with_client_cert_verifier(verifier)
+            .with_single_cert(certs, key)?
+    } else {
+        ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(certs, key)?
+    };
+
+    Ok(config)
+}
+
+/// The client configuration trusting the CAs in `ca`, authenticating
+/// with the certificate chain and private key of `identity` if given.
+///
+/// # Errors
+/// * Error when a file cannot be read or does not contain valid
+///   certificates or private key.
+pub fn client_config(ca: &Path, identity: Option<(&Path, &Path)>) -> Result<ClientConfig, Error> {
+    let builder = ClientConfig::builder().with_root_certificates(load_roots(ca)?);
+
+    let config = if let Some((cert, key)) = identity {
+        builder.with_client_auth_cert(load_certs(cert)?, load_key(key)?)?
+    } else {
+        builder.with_no_client_auth()
+    };
+
+    Ok(config)
+}
+
+/// An acceptor, see [`server_config`].
+///
+/// # Errors
+/// * Error when the configuration cannot be loaded.
+pub fn acceptor(cert: &Path, key: &Path, client_ca: Option<&Path>) -> Result<TlsAcceptor, Error> {
+    Ok(TlsAcceptor::from(Arc::new(server_config(
+        cert, key, client_ca,
+    )?)))
+}
+
+/// A connector, see [`client_config`].
+///
+/// # Errors
+/// * Error when the configuration cannot be loaded.
+pub fn connector(ca: &Path, identity: Option<(&Path, &Path)>) -> Result<TlsConnector, Error> {
+    Ok(TlsConnector::from(Arc::new(client_config(ca, identity)?)))
+}
+
+/// The server side of the handshake over an accepted stream.
+///
+/// # Errors
+/// * Error when the handshake fails or the stream returns an error.
+pub async fn wrap_listener<S>(acceptor: &TlsAcceptor, stream: S) -> io::Result<server::TlsStream<S>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    acceptor.accept(stream).await
+}
+
+/// The client side of the handshake over a connected stream, checking
+/// that the server certificate is valid for `server_name`.
+///
+/// # Errors
+/// * Error when the server name is invalid, the handshake fails or
+///   the stream returns an error.
+pub async fn wrap_connect<S>(
+    connector: &TlsConnector,
+    server_name: &str,
+    stream: S,
+) -> io::Result<client::TlsStream<S>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let server_name = ServerName::try_from(server_name.to_string())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    connector.connect(server_name, stream).await
+}
+
+/// The TLS flags of a server.
+#[derive(clap::Args, Debug, Clone)]
+pub struct TlsArgs {
+    /// Terminate TLS with this PEM certificate chain
+    #[arg(long, requires = "tls_key")]
+    pub tls_cert: Option<PathBuf>,
+
+    /// PEM private key of the TLS certificate
+    #[arg(long, requires = "tls_cert")]
+    pub tls_key: Option<PathBuf>,
+
+    /// Require a client certificate signed by the CAs in this PEM file
+    #[arg(long, requires = "tls_cert")]
+    pub tls_client_ca: Option<PathBuf>,
+}
+
+impl TlsArgs {
+    /// The acceptor, when a certificate is given.
+    ///
+    /// # Errors
+    /// * Error when the configuration cannot be loaded.
+    pub fn acceptor(&self) -> Result<Option<TlsAcceptor>, Error> {
+        match (&self.tls_cert, &self.tls_key) {
+            (Some(cert), Some(key)) => acceptor(cert, key, self.tls_client_ca.as_deref()).map(Some),
+            _ => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use std::path::{Path, PathBuf};
+
+use clap::Parser;
+
+use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
+
+use protohackers_tls::{Error, TlsArgs, TlsConnector};
+
+/// A temporary directory with a self signed certificate for
+/// `localhost` and its key, `{name}.pem` and `{name}-key.pem`, one per
+/// name.
+struct Certificates(PathBuf);
+
+impl Certificates {
+    fn new(test: &str, names: &[&str]) -> Self {
+        let directory =
+            std::env::temp_dir().join(format!("protohackers-tls-{test}-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        for name in names {
+            let certificate =
+                rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+            std::fs::write(
+                directory.join(format!("{name}.pem")),
+                certificate.serialize_pem().unwrap(),
+            )
+            .unwrap();
+            std::fs::write(
+                directory.join(format!("{name}-key.pem")),
+                certificate.serialize_private_key_pem(),
+            )
+            .unwrap();
+        }
+        Self(directory)
+    }
+
+    fn cert(&self, name: &str) -> PathBuf {
+        self.0.join(format!("{name}.pem"))
+    }
+
+    fn key(&self, name: &str) -> PathBuf {
+        self.0.join(format!("{name}-key.pem"))
+    }
+}
+
+impl Drop for Certificates {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Handshake over an in memory stream and echo a message, the errors
+/// of the server and the client.
+async fn handshake(
+    certificates: &Certificates,
+    client_ca: Option<&Path>,
+    connector: &TlsConnector,
+) -> (io::Result<()>, io::Result<()>) {
+    let acceptor = protohackers_tls::acceptor(
+        &certificates.cert("server"),
+        &certificates.key("server"),
+        client_ca,
+    )
+    .unwrap();
+
+    let (server, client) = io::duplex(16 * 1024);
+
+    let server = async {
+        let mut stream = protohackers_tls::wrap_listener(&acceptor, server).await?;
+        let mut buffer = [0; 5];
+        stream.read_exact(&mut buffer).await?;
+        stream.write_all(&buffer).await?;
+        stream.shutdown().await
+    };
+
+    let client = async {
+        let mut stream = protohackers_tls::wrap_connect(connector, "localhost", client).await?;
+        stream.write_all(b"hello").await?;
+        let mut buffer = vec![];
+        stream.read_to_end(&mut buffer).await?;
+        assert_eq!(b"hello", buffer.as_slice());
+        Ok(())
+    };
+
+    tokio::join!(server, client)
+}
+
+#[tokio::test]
+async fn test_handshake() {
+    let certificates = Certificates::new("handshake", &["server"]);
+    let connector = protohackers_tls::connector(&certificates.cert("server"), None).unwrap();
+
+    let (server, client) = handshake(&certificates, None, &connector).await;
+    server.unwrap();
+    client.unwrap();
+}
+
+#[tokio::test]
+async fn test_client_auth() {
+    let certificates = Certificates::new("client-auth", &["server", "client", "other"]);
+    let client_ca = certificates.cert("client");
+
+    let connector = protohackers_tls::connector(
+        &certificates.cert("server"),
+        Some((&certificates.cert("client"), &certificates.key("client"))),
+    )
+    .unwrap();
+    let (server, client) = handshake(&certificates, Some(&client_ca), &connector).await;
+    server.unwrap();
+    client.unwrap();
+
+    // no certificate
+    let connector = protohackers_tls::connector(&certificates.cert("server"), None).unwrap();
+    let (server, _) = handshake(&certificates, Some(&client_ca), &connector).await;
+    assert!(server.is_err());
+
+    // a certificate of another CA
+    let connector = protohackers_tls::connector(
+        &certificates.cert("server"),
+        Some((&certificates.cert("other"), &certificates.key("other"))),
+    )
+    .unwrap();
+    let (server, _) = handshake(&certificates, Some(&client_ca), &connector).await;
+    assert!(server.is_err());
+}
+
+#[tokio::test]
+async fn test_untrusted_server() {
+    let certificates = Certificates::new("untrusted", &["server", "other"]);
+    let connector = protohackers_tls::connector(&certificates.cert("other"), None).unwrap();
+
+    let (_, client) = handshake(&certificates, None, &connector).await;
+    assert!(client.is_err());
+}
+
+#[test]
+fn test_missing_files() {
+    let certificates = Certificates::new("missing", &["server"]);
+    std::fs::write(certificates.key("empty"), "").unwrap();
+
+    assert!(matches!(
+        protohackers_tls::acceptor(
+            &certificates.cert("server"),
+            &certificates.key("empty"),
+            None
+        ),
+        Err(Error::NoPrivateKey(_))
+    ));
+    assert!(matches!(
+        protohackers_tls::connector(&certificates.key("empty"), None),
+        Err(Error::NoCertificates(_))
+    ));
+    assert!(matches!(
+        protohackers_tls::acceptor(
+            &certificates.cert("none"),
+            &certificates.key("server"),
+            None
+        ),
+        Err(Error::Io(_))
+    ));
+}
+
+#[test]
+fn test_args() {
+    #[derive(clap::Parser, Debug)]
+    struct Args {
+        #[command(flatten)]
+        tls: TlsArgs,
+    }
+
+    assert!(Args::parse_from(["test"]).tls.acceptor().unwrap().is_none());
+
+    let certificates = Certificates::new("args", &["server"]);
+    let (cert, key) = (certificates.cert("server"), certificates.key("server"));
+    let args = Args::parse_from([
+        "test",
+        "--tls-cert",
+        cert.to_str().unwrap(),
+        "--tls-key",
+        key.to_str().unwrap(),
+        "--tls-client-ca",
+        cert.to_str().unwrap(),
+    ]);
+    assert!(args.tls.acceptor().unwrap().is_some());
+
+    assert!(Args::try_parse_from(["test", "--tls-cert", "cert.pem"]).is_err());
+    assert!(Args::try_parse_from(["test", "--tls-client-ca", "ca.pem"]).is_err());
+}
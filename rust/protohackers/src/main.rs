@@ -0,0 +1,128 @@
+//! All the solutions in a single binary, the problem is selected by
+//! the subcommand:
+//!
+//! ```raw
+//! protohackers p06 --port 10000
+//! ```
+//!
+//! The flags can also be set by the environment or a file, the tables
+//! of the file named after the subcommands, see
+//! [`protohackers_config`].
+#[derive(clap::Subcommand, Debug)]
+enum Problem {
+    /// Smoke Test
+    P00(p00_smoke_test::cli::Args),
+
+    /// Prime Time
+    P01(p01_prime_time::cli::Args),
+
+    /// Means to an End
+    P02(p02_means_to_an_end::cli::Args),
+
+    /// Budget Chat
+    P03(p03_budget_chat::cli::Args),
+
+    /// Unusual Database Program
+    P04(p04_unusual_database_program::cli::Args),
+
+    /// Mob in the Middle
+    P05(p05_mob_in_the_middle::cli::Args),
+
+    /// Speed Daemon
+    P06(p06_speed_daemon::cli::Args),
+
+    /// Line Reversal
+    P07(p07_line_reversal::cli::Args),
+
+    /// Insecure Sockets Layer
+    P08(p08_insecure_sockets_layer::cli::Args),
+
+    /// Job Centre
+    P09(p09_job_centre::cli::Args),
+
+    /// Voracious Code Storage
+    P10(p10_voracious_code_storage::cli::Args),
+
+    /// Pest Control
+    P11(p11_pest_control::cli::Args),
+}
+
+impl Problem {
+    async fn run(self) -> Result<(), anyhow::Error> {
+        match self {
+            Self::P00(args) => p00_smoke_test::cli::run(args).await,
+            Self::P01(args) => p01_prime_time::cli::run(args).await,
+            Self::P02(args) => p02_means_to_an_end::cli::run(args).await,
+            Self::P03(args) => p03_budget_chat::cli::run(args).await,
+            Self::P04(args) => p04_unusual_database_program::cli::run(args).await,
+            Self::P05(args) => p05_mob_in_the_middle::cli::run(args).await,
+            Self::P06(args) => p06_speed_daemon::cli::run(args).await,
+            Self::P07(args) => p07_line_reversal::cli::run(args).await,
+            Self::P08(args) => p08_insecure_sockets_layer::cli::run(args).await,
+            Self::P09(args) => p09_job_centre::cli::run(args).await,
+            Self::P10(args) => p10_voracious_code_storage::cli::run(args).await,
+            Self::P11(args) => p11_pest_control::cli::run(args).await,
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), anyhow::Error> {
+    protohackers_config::parse_subcommand::<Problem>(clap::command!())
+        .run()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{Command, Subcommand};
+
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Problem, protohackers_config::Error> {
+        protohackers_config::try_parse_subcommand_from(Command::new("protohackers"), args, [])
+    }
+
+    #[test]
+    fn test_command() {
+        Problem::augment_subcommands(Command::new("protohackers")).debug_assert();
+    }
+
+    #[test]
+    fn test_subcommands() {
+        let problem = parse(&["protohackers", "p06", "--port", "10001"]).unwrap();
+        assert!(matches!(
+            problem,
+            Problem::P06(p06_speed_daemon::cli::Args { server }) if server.port == 10001
+        ));
+
+        let problem = parse(&["protohackers", "p10", "--storage-directory", "/tmp/p10"]).unwrap();
+        assert!(matches!(
+            problem,
+            Problem::P10(p10_voracious_code_storage::cli::Args { server, storage_directory: Some(directory) })
+                if server.port == 10000 && directory.to_str() == Some("/tmp/p10")
+        ));
+
+        // the TCP problems share the server flags
+        for problem in [
+            "p00", "p01", "p02", "p03", "p05", "p06", "p08", "p09", "p10", "p11",
+        ] {
+            assert!(
+                parse(&[
+                    "protohackers",
+                    problem,
+                    "--max-connections",
+                    "10",
+                    "--connection-queue",
+                    "5"
+                ])
+                .is_ok(),
+                "{problem}"
+            );
+        }
+
+        assert!(parse(&["protohackers"]).is_err());
+        assert!(parse(&["protohackers", "p12"]).is_err());
+        assert!(parse(&["protohackers", "p03", "--storage-directory", "/tmp"]).is_err());
+    }
+}
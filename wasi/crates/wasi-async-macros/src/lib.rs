@@ -0,0 +1,73 @@
+use proc_macro::TokenStream;
+
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, FnArg, ItemFn};
+
+/// Run an async test on a new reactor, shut down at the end of the
+/// test so that its tasks and pollables are dropped.
+///
+/// The test can take the reactor as its only argument:
+///
+/// ```ignore
+/// #[wasi_async::test]
+/// async fn test_sleep(reactor: Reactor) {
+///     time::sleep(reactor, Duration::from_millis(1)).await;
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn test(args: TokenStream, item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as ItemFn);
+
+    if !args.is_empty() {
+        let args = proc_macro2::TokenStream::from(args);
+        return quote_spanned! {args.span()=>
+            compile_error!("the test attribute takes no arguments");
+        }
+        .into();
+    }
+
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = input;
+
+    if sig.asyncness.is_none() {
+        return quote_spanned! {sig.fn_token.span()=>
+            compile_error!("the test function must be async");
+        }
+        .into();
+    }
+
+    let reactor = match (sig.inputs.len(), sig.inputs.first()) {
+        (0, _) => quote! {},
+        (1, Some(FnArg::Typed(arg))) => {
+            let (pat, ty) = (&arg.pat, &arg.ty);
+            quote! { let #pat: #ty = __reactor.clone(); }
+        }
+        _ => {
+            return quote_spanned! {sig.inputs.span()=>
+                compile_error!("the test function takes at most the reactor");
+            }
+            .into();
+        }
+    };
+
+    let (name, output) = (&sig.ident, &sig.output);
+
+    quote! {
+        #[::core::prelude::v1::test]
+        #(#attrs)*
+        #vis fn #name() #output {
+            ::wasi_async::__runtime::block_on(|__reactor| async move {
+                #reactor
+                let result = async move #block.await;
+                __reactor.shutdown();
+                result
+            })
+        }
+    }
+    .into()
+}
@@ -0,0 +1,136 @@
+use std::future::{self, Future};
+use std::pin::Pin;
+use std::task::Poll;
+
+use crate::reactor::JoinHandle;
+use crate::Reactor;
+
+/// A group of spawned tasks, e.g. the connections of a server: the
+/// completions are awaited in any order and the remaining tasks are
+/// aborted when the set is dropped.
+pub struct JoinSet {
+    reactor: Reactor,
+    handles: Vec<JoinHandle>,
+}
+
+impl JoinSet {
+    pub fn new(reactor: Reactor) -> Self {
+        Self {
+            reactor,
+            handles: vec![],
+        }
+    }
+
+    pub fn spawn(&mut self, f: impl Future<Output = ()> + 'static) {
+        let handle = self.reactor.spawn(f);
+        self.handles.push(handle);
+    }
+
+    /// The tasks not yet joined.
+    pub fn len(&self) -> usize {
+        self.handles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handles.is_empty()
+    }
+
+    /// Wait for the next task to complete, `None` when the set is
+    /// empty.
+    pub async fn join_next(&mut self) -> Option<()> {
+        future::poll_fn(|cx| {
+            if self.handles.is_empty() {
+                return Poll::Ready(None);
+            }
+
+            let completed = self
+                .handles
+                .iter_mut()
+                .position(|handle| Pin::new(handle).poll(cx).is_ready());
+            match completed {
+                Some(index) => {
+                    self.handles.swap_remove(index);
+                    Poll::Ready(Some(()))
+                }
+                None => Poll::Pending,
+            }
+        })
+        .await
+    }
+
+    /// Wait for all the tasks to complete, e.g. to drain the
+    /// connections on shutdown.
+    pub async fn join_all(&mut self) {
+        while self.join_next().await.is_some() {}
+    }
+
+    /// Abort all the tasks, they are dropped right away.
+    pub fn abort_all(&mut self) {
+        for handle in self.handles.drain(..) {
+            handle.abort();
+        }
+    }
+}
+
+impl Drop for JoinSet {
+    fn drop(&mut self) {
+        self.abort_all();
+    }
+}
+
+#[cfg(all(test, not(feature = "wasip3")))]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use wasi::clocks::monotonic_clock;
+
+    use crate::block_on;
+
+    use super::JoinSet;
+
+    #[test]
+    fn test_join_next() {
+        block_on(|reactor| async move {
+            let mut set = JoinSet::new(reactor.clone());
+            for i in 0..3 {
+                let reactor = reactor.clone();
+                set.spawn(async move {
+                    let short = monotonic_clock::subscribe_duration(i * 1_000_000);
+                    reactor.wait_for(short).await;
+                });
+            }
+
+            assert_eq!(3, set.len());
+            set.join_all().await;
+            assert!(set.is_empty());
+            assert_eq!(None, set.join_next().await);
+        });
+    }
+
+    #[test]
+    fn test_abort_on_drop() {
+        block_on(|reactor| async move {
+            let completed = Rc::new(Cell::new(false));
+
+            let mut set = JoinSet::new(reactor.clone());
+            set.spawn({
+                let reactor = reactor.clone();
+                let completed = completed.clone();
+                async move {
+                    let long = monotonic_clock::subscribe_duration(60_000_000_000);
+                    reactor.wait_for(long).await;
+                    completed.set(true);
+                }
+            });
+
+            // the task is waiting
+            reactor.spawn(async {}).await;
+            drop(set);
+
+            let short = monotonic_clock::subscribe_duration(1_000_000);
+            reactor.wait_for(short).await;
+            assert!(!completed.get());
+        });
+    }
+}
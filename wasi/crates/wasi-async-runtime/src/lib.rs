@@ -1,3 +1,11 @@
+//! A single threaded runtime for WASI components.
+//!
+//! The tasks wait on the WASI 0.2 pollables, polled by the reactor;
+//! with the `wasip3` feature they await the WASI 0.3 async streams and
+//! futures, and the reactor runs inside the executor of the component
+//! model bindings.
+#[cfg(feature = "wasip3")]
+use std::future::poll_fn;
 use std::future::Future;
 use std::pin::pin;
 use std::task::{Context, Poll};
@@ -5,12 +13,18 @@ use std::task::{Context, Poll};
 #[allow(warnings)]
 mod bindings;
 
+mod join_set;
+mod metrics;
+#[cfg(not(feature = "wasip3"))]
 mod poller;
 mod reactor;
 pub mod sync;
 
-pub use reactor::Reactor;
+pub use join_set::JoinSet;
+pub use metrics::RuntimeMetrics;
+pub use reactor::{JoinHandle, Reactor, Shutdown};
 
+#[cfg(not(feature = "wasip3"))]
 pub fn block_on<F, Fut>(f: F) -> Fut::Output
 where
     F: FnOnce(Reactor) -> Fut,
@@ -31,3 +45,52 @@ where
         }
     }
 }
+
+/// Run the main task and the spawned ones on the executor of the
+/// bindings: the host events wake it up and it polls the reactor
+/// again, a tick at a time, yielding to the host while a task is
+/// ready.
+#[cfg(feature = "wasip3")]
+pub fn block_on<F, Fut>(f: F) -> Fut::Output
+where
+    F: FnOnce(Reactor) -> Fut,
+    Fut: Future,
+    Fut::Output: 'static,
+{
+    use wasip3::wit_bindgen;
+
+    let (reactor, waker) = Reactor::new();
+
+    let fut = (f)(reactor.clone());
+
+    // a read kept pending on a stream of our own: the executor yields
+    // to the host only once it has something to wait on
+    let (writer, mut reader) = wasip3::wit_stream::new::<u8>();
+
+    wit_bindgen::block_on(async move {
+        let mut fut = pin!(fut);
+        let mut idle = Box::pin(reader.read(Vec::with_capacity(1)));
+
+        let mut cx = Context::from_waker(&waker);
+
+        let res = poll_fn(|outer| {
+            let _ = idle.as_mut().poll(outer);
+
+            reactor.reset_main_task_state();
+            if let Poll::Ready(res) = fut.as_mut().poll(&mut cx) {
+                return Poll::Ready(res);
+            }
+
+            if reactor.tick() {
+                outer.waker().wake_by_ref();
+            }
+            Poll::Pending
+        })
+        .await;
+
+        // the read is cancelled before its stream is gone
+        drop(idle);
+        drop(writer);
+        res
+    })
+}
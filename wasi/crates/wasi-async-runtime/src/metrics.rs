@@ -0,0 +1,41 @@
+use std::fmt;
+use std::time::Duration;
+
+/// The counters of a reactor, see [`Reactor::metrics`](crate::Reactor::metrics).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RuntimeMetrics {
+    pub tasks_spawned: u64,
+    pub tasks_completed: u64,
+
+    /// The polls of the spawned tasks.
+    pub task_polls: u64,
+
+    /// The pollable and host poll counters stay at zero under WASI
+    /// 0.3, the host is waited on by its own executor.
+    pub pollables_registered: u64,
+
+    /// The pollables found ready by the host polls.
+    pub pollable_wakes: u64,
+
+    /// The host polls, blocking only when no task is ready.
+    pub polls: u64,
+
+    /// The time spent in the host polls.
+    pub poll_time: Duration,
+}
+
+impl fmt::Display for RuntimeMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "tasks spawned: {} completed: {} polls: {} pollables registered: {} wakes: {} host polls: {} in {:?}",
+            self.tasks_spawned,
+            self.tasks_completed,
+            self.task_polls,
+            self.pollables_registered,
+            self.pollable_wakes,
+            self.polls,
+            self.poll_time,
+        )
+    }
+}
@@ -2,13 +2,26 @@ use slab::Slab;
 
 use tracing::{instrument, trace};
 
+use wasi::clocks::monotonic_clock;
 use wasi::io::poll::poll;
 
 use crate::reactor::Pollable;
 
+#[derive(Debug)]
+struct Target {
+    pollable: Pollable,
+
+    /// The position in the registrations.
+    position: usize,
+}
+
+/// The pollables waited for. The registrations are kept in a list
+/// updated on insert and remove, so that a poll only collects the
+/// references to the pollables.
 #[derive(Debug)]
 pub(crate) struct Poller {
-    pub(crate) targets: Slab<Pollable>,
+    targets: Slab<Target>,
+    registrations: Vec<EventKey>,
 }
 
 #[repr(transparent)]
@@ -19,50 +32,103 @@ impl Poller {
     pub(crate) fn new() -> Self {
         Self {
             targets: Slab::new(),
+            registrations: Vec::new(),
         }
     }
 
     #[instrument(skip_all)]
-    pub(crate) fn insert(&mut self, target: Pollable) -> EventKey {
-        trace!("target: {target:?}");
-        let key = self.targets.insert(target);
-        trace!("key: {key}");
-        EventKey(key as u32)
+    pub(crate) fn insert(&mut self, pollable: Pollable) -> EventKey {
+        trace!("target: {pollable:?}");
+        let key = EventKey(self.targets.insert(Target {
+            pollable,
+            position: self.registrations.len(),
+        }) as u32);
+        self.registrations.push(key);
+        trace!("key: {key:?}");
+        key
     }
 
     pub(crate) fn get(&self, key: &EventKey) -> Option<&Pollable> {
-        self.targets.get(key.0 as usize)
+        self.targets
+            .get(key.0 as usize)
+            .map(|target| &target.pollable)
     }
 
     pub(crate) fn remove(&mut self, key: EventKey) -> Option<Pollable> {
-        self.targets.try_remove(key.0 as usize)
+        let Target { pollable, position } = self.targets.try_remove(key.0 as usize)?;
+
+        self.registrations.swap_remove(position);
+        if let Some(moved) = self.registrations.get(position) {
+            self.targets[moved.0 as usize].position = position;
+        }
+
+        Some(pollable)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.registrations.is_empty()
+    }
+
+    /// Drop all the pollables.
+    pub(crate) fn clear(&mut self) {
+        self.targets.clear();
+        self.registrations.clear();
     }
 
+    /// The ready pollables, waiting for at least one when `wait`.
     #[instrument(skip_all)]
-    pub(crate) fn block_until(&mut self) -> Vec<EventKey> {
-        let mut indexes = Vec::with_capacity(self.targets.len());
-        let mut targets = Vec::with_capacity(self.targets.len());
-
-        for (index, target) in self.targets.iter() {
-            match target {
-                Pollable::Wasi(pollable) => {
-                    indexes.push(index);
-                    targets.push(pollable);
-                }
-            }
+    pub(crate) fn block_until(&mut self, wait: bool) -> Vec<EventKey> {
+        if self.is_empty() {
+            return vec![];
         }
 
+        // an always ready pollable makes the poll return right away
+        let now = (!wait).then(|| monotonic_clock::subscribe_duration(0));
+
+        let targets = self
+            .registrations
+            .iter()
+            .map(|key| match &self.targets[key.0 as usize].pollable {
+                Pollable::Wasi(pollable) => pollable,
+            })
+            .chain(now.as_ref())
+            .collect::<Vec<_>>();
+
         trace!("start poll {targets:?}");
-        let ready_indexes = if targets.is_empty() {
-            vec![]
-        } else {
-            poll(&targets)
-        };
+        let ready_indexes = poll(&targets);
         trace!("done poll {ready_indexes:?}");
 
         ready_indexes
             .into_iter()
-            .map(|index| EventKey(indexes[index as usize] as u32))
+            .filter_map(|index| self.registrations.get(index as usize).copied())
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use wasi::clocks::monotonic_clock;
+
+    use super::*;
+
+    #[test]
+    fn test_registrations() {
+        let mut poller = Poller::new();
+
+        let keys = (0..4)
+            .map(|_| poller.insert(monotonic_clock::subscribe_duration(0).into()))
+            .collect::<Vec<_>>();
+
+        assert!(poller.remove(keys[1]).is_some());
+        assert!(poller.remove(keys[1]).is_none());
+        assert!(poller.remove(keys[3]).is_some());
+
+        let mut ready = poller.block_until(true);
+        ready.sort();
+        assert_eq!(vec![keys[0], keys[2]], ready);
+
+        poller.clear();
+        assert!(poller.is_empty());
+        assert!(poller.block_until(true).is_empty());
+    }
+}
@@ -1,50 +1,67 @@
-use std::cell::RefCell;
-use std::future::{self, Future};
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::future::Future;
 use std::mem;
 use std::pin::Pin;
 use std::rc::Rc;
 use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+#[cfg(not(feature = "wasip3"))]
+use std::time::Instant;
 
 use hashbrown::{HashMap, HashSet};
 
+#[cfg(not(feature = "wasip3"))]
 use wasi::io::poll::Pollable as WasiPollable;
 
-use tracing::{instrument, trace};
+use tracing::{instrument, trace, trace_span};
 
+use crate::metrics::RuntimeMetrics;
+#[cfg(not(feature = "wasip3"))]
 use crate::poller::{EventKey, Poller};
 
-pub(crate) fn task_waker(state: Rc<RefCell<bool>>) -> Waker {
+/// The wake state of a task: the task is ready and the reactor is
+/// told that a task was woken, so that a wake from another task, not
+/// from a pollable, does not leave the reactor blocked in the poll.
+pub(crate) struct TaskState {
+    ready: Cell<bool>,
+    woken: Rc<Cell<bool>>,
+}
+
+impl TaskState {
+    fn new(woken: Rc<Cell<bool>>) -> Self {
+        Self {
+            ready: Cell::new(true),
+            woken,
+        }
+    }
+
+    fn wake(&self) {
+        self.ready.set(true);
+        self.woken.set(true);
+    }
+}
+
+pub(crate) fn task_waker(state: Rc<TaskState>) -> Waker {
     const VTABLE: RawWakerVTable = {
         unsafe fn clone(ptr: *const ()) -> RawWaker {
-            let ptr = ptr as *const RefCell<bool>;
+            let ptr = ptr as *const TaskState;
             Rc::increment_strong_count(ptr);
 
-            let state = Rc::from_raw(ptr);
-
-            let state = state.clone();
-
-            RawWaker::new(Rc::into_raw(state) as _, &VTABLE)
+            RawWaker::new(ptr as _, &VTABLE)
         }
 
         unsafe fn wake(ptr: *const ()) {
-            let state = Rc::from_raw(ptr as *const RefCell<bool>);
-            if let Ok(state) = state.try_borrow_mut().as_mut() {
-                **state = true;
-            };
+            let state = Rc::from_raw(ptr as *const TaskState);
+            state.wake();
         }
 
         unsafe fn wake_by_ref(ptr: *const ()) {
-            let ptr = ptr as *const RefCell<bool>;
-            Rc::increment_strong_count(ptr);
-
-            let state = Rc::from_raw(ptr);
-            if let Ok(state) = state.try_borrow_mut().as_mut() {
-                **state = true;
-            };
+            let state = &*(ptr as *const TaskState);
+            state.wake();
         }
 
         unsafe fn drop(ptr: *const ()) {
-            let _ = Rc::from_raw(ptr as *const RefCell<bool>);
+            let _ = Rc::from_raw(ptr as *const TaskState);
         }
 
         RawWakerVTable::new(clone, wake, wake_by_ref, drop)
@@ -52,15 +69,18 @@ pub(crate) fn task_waker(state: Rc<RefCell<bool>>) -> Waker {
 
     let raw = RawWaker::new(Rc::into_raw(state) as _, &VTABLE);
 
-    // Safety: check for safety...
+    // Safety: the vtable functions keep the reference count of the
+    // state balanced
     unsafe { Waker::from_raw(raw) }
 }
 
+#[cfg(not(feature = "wasip3"))]
 #[derive(Debug)]
 pub enum Pollable {
     Wasi(WasiPollable),
 }
 
+#[cfg(not(feature = "wasip3"))]
 impl Pollable {
     pub fn ready(&self) -> bool {
         match self {
@@ -69,147 +89,290 @@ impl Pollable {
     }
 }
 
+#[cfg(not(feature = "wasip3"))]
 impl From<WasiPollable> for Pollable {
     fn from(pollable: WasiPollable) -> Self {
         Self::Wasi(pollable)
     }
 }
 
+/// The task polls of a tick, before checking the pollables.
+const POLL_BUDGET: usize = 128;
+
 #[derive(Clone)]
 pub struct Reactor {
     inner: Rc<RefCell<InnerReactor>>,
 }
 
+/// The state of a task seen by its join handle.
+enum HandleState {
+    Waiting(Option<Waker>),
+    Complete,
+}
+
 type TaskInfo = (
     usize,
-    Rc<RefCell<bool>>,
+    Rc<TaskState>,
     Pin<Box<dyn Future<Output = ()> + 'static>>,
 );
 
 struct InnerReactor {
     next_id: usize,
-    main_task_state: Rc<RefCell<bool>>,
+    main_task_state: Rc<TaskState>,
+
+    /// A task was woken in the current tick.
+    woken: Rc<Cell<bool>>,
+    #[cfg(not(feature = "wasip3"))]
     poller: Poller,
+    #[cfg(not(feature = "wasip3"))]
     wakers: HashMap<EventKey, Waker>,
     tasks: Vec<TaskInfo>,
-    complete: HashMap<usize, Option<Waker>>,
+
+    /// The tasks with a live join handle, the entry is removed when
+    /// the handle is dropped.
+    handles: HashMap<usize, HandleState>,
+
+    /// The tasks aborted while out of the queue, during a tick.
+    aborted: HashSet<usize>,
+    shutdown: bool,
+    metrics: RuntimeMetrics,
+}
+
+/// The reactor was shut down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shutdown;
+
+impl std::error::Error for Shutdown {}
+
+impl fmt::Display for Shutdown {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(fmt, "reactor shutdown")
+    }
 }
 
 impl Reactor {
     pub(crate) fn new() -> (Self, Waker) {
-        let main_task_state = Rc::new(RefCell::new(true));
+        let woken = Rc::new(Cell::new(false));
+        let main_task_state = Rc::new(TaskState::new(woken.clone()));
         (
             Self {
                 inner: Rc::new(RefCell::new(InnerReactor {
                     next_id: 0,
                     main_task_state: main_task_state.clone(),
+                    woken,
+                    #[cfg(not(feature = "wasip3"))]
                     poller: Poller::new(),
+                    #[cfg(not(feature = "wasip3"))]
                     wakers: HashMap::new(),
                     tasks: Vec::new(),
-                    complete: HashMap::new(),
+                    handles: HashMap::new(),
+                    aborted: HashSet::new(),
+                    shutdown: false,
+                    metrics: RuntimeMetrics::default(),
                 })),
             },
             task_waker(main_task_state),
         )
     }
 
+    /// Wait for the pollable, returning right away after a
+    /// [`shutdown`](Self::shutdown).
+    #[cfg(not(feature = "wasip3"))]
     #[instrument(skip_all)]
     pub async fn wait_for<P: Into<Pollable>>(&self, pollable: P) {
-        let mut pollable = Some(pollable.into());
-        let mut key = None;
+        self.try_wait_for(pollable).await.ok();
+    }
+
+    /// Wait for the pollable.
+    ///
+    /// # Errors
+    /// * [`Shutdown`] when the reactor is shut down before the
+    ///   pollable is ready.
+    #[cfg(not(feature = "wasip3"))]
+    #[instrument(skip_all)]
+    pub async fn try_wait_for<P: Into<Pollable>>(&self, pollable: P) -> Result<(), Shutdown> {
+        WaitFor {
+            reactor: self,
+            pollable: Some(pollable.into()),
+            key: None,
+        }
+        .await
+    }
 
-        future::poll_fn(|cx| {
+    /// Stop the reactor: the spawned tasks are dropped, with their
+    /// pollables, the join handles complete and the pending waits of
+    /// the main task fail with [`Shutdown`]. The main task is
+    /// expected to return, nothing can be waited for anymore.
+    ///
+    /// Under WASI 0.3 the host operations of the dropped tasks are
+    /// cancelled, those of the main task are left to complete.
+    #[instrument(skip_all)]
+    pub fn shutdown(&self) {
+        let (tasks, wakers) = {
             let mut reactor = self.inner.borrow_mut();
+            if reactor.shutdown {
+                return;
+            }
+            reactor.shutdown = true;
+
+            let reactor = &mut *reactor;
+            let wakers = reactor
+                .take_pollable_wakers()
+                .into_iter()
+                .chain(
+                    reactor
+                        .handles
+                        .drain()
+                        .filter_map(|(_, state)| match state {
+                            HandleState::Waiting(waker) => waker,
+                            HandleState::Complete => None,
+                        }),
+                )
+                .collect::<Vec<_>>();
+
+            (mem::take(&mut reactor.tasks), wakers)
+        };
 
-            let key = key.get_or_insert_with(|| reactor.poller.insert(pollable.take().unwrap()));
-            reactor.wakers.insert(*key, cx.waker().clone());
+        trace!("dropping {} tasks", tasks.len());
 
-            if reactor.poller.get(key).unwrap().ready() {
-                trace!("{key:?} is ready");
-                reactor.poller.remove(*key);
-                reactor.wakers.remove(key);
-                Poll::Ready(())
-            } else {
-                Poll::Pending
-            }
-        })
-        .await;
+        // outside the borrow, the tasks remove their pollables on drop
+        drop(tasks);
+
+        #[cfg(not(feature = "wasip3"))]
+        self.inner.borrow_mut().poller.clear();
+
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    /// A snapshot of the counters.
+    pub fn metrics(&self) -> RuntimeMetrics {
+        self.inner.borrow().metrics
     }
 
+    pub fn is_shutdown(&self) -> bool {
+        self.inner.borrow().shutdown
+    }
+
+    /// Run a tick: every ready task is polled once, in FIFO order, up
+    /// to [`POLL_BUDGET`] polls, so that an always ready task cannot
+    /// starve the others. Returns whether a task, or the main task, is
+    /// still ready.
     #[instrument(skip_all)]
-    pub(crate) fn block_until(&self) {
-        let mut tasks = {
+    pub(crate) fn tick(&self) -> bool {
+        let tasks = {
             let mut reactor = self.inner.borrow_mut();
+            reactor.woken.set(false);
 
             mem::take(&mut reactor.tasks)
         };
 
         let mut complete = HashSet::new();
-        let mut pending = loop {
-            let mut pending = vec![];
-            while let Some((task_id, state, task)) = tasks.pop() {
-                let Some((state, mut task)) = ({
-                    let s = { *state.borrow() };
-                    if s {
-                        let state = state.clone();
-                        *state.borrow_mut() = false;
-                        Some((state, task))
-                    } else {
-                        pending.push((task_id, state, task));
-                        None
-                    }
-                }) else {
-                    continue;
-                };
+        let mut polled = Vec::with_capacity(tasks.len());
+        let mut budget = POLL_BUDGET;
+        let mut tasks = tasks.into_iter();
+        for (task_id, state, mut task) in tasks.by_ref() {
+            if self.is_shutdown() {
+                break;
+            }
 
-                let waker = task_waker(state.clone());
-                let mut cx = Context::from_waker(&waker);
+            if self.inner.borrow_mut().aborted.remove(&task_id) {
+                trace!("task {task_id} aborted");
+                complete.insert(task_id);
+                continue;
+            }
 
-                if task.as_mut().poll(&mut cx).is_pending() {
-                    pending.push((task_id, state, task));
-                } else {
-                    complete.insert(task_id);
-                }
+            if !state.ready.replace(false) {
+                polled.push((task_id, state, task));
+                continue;
             }
 
-            let ready = pending
-                .iter()
-                .filter(|(_, state, _)| *state.borrow())
-                .count();
-
-            trace!(
-                "pending {:?} ready: {ready}",
-                pending
-                    .iter()
-                    .map(|(task_id, ..)| task_id)
-                    .collect::<Vec<_>>(),
-            );
-
-            if ready == 0 {
-                break pending;
+            let waker = task_waker(state.clone());
+            let mut cx = Context::from_waker(&waker);
+
+            let poll = trace_span!("task", task_id).in_scope(|| task.as_mut().poll(&mut cx));
+            self.inner.borrow_mut().metrics.task_polls += 1;
+            if poll.is_pending() {
+                polled.push((task_id, state, task));
+            } else {
+                complete.insert(task_id);
+                self.inner.borrow_mut().metrics.tasks_completed += 1;
             }
 
-            tasks = pending;
-        };
+            budget -= 1;
+            if budget == 0 {
+                trace!("poll budget exhausted");
+                break;
+            }
+        }
+
+        if self.is_shutdown() {
+            trace!("shutdown, dropping {} tasks", polled.len() + tasks.len());
+            drop(polled);
+            drop(tasks);
+            return true;
+        }
+
+        // the tasks aborted by themselves or by a later task
+        let mut aborted = mem::take(&mut self.inner.borrow_mut().aborted);
+        if !aborted.is_empty() {
+            let (dropped, kept): (Vec<_>, Vec<_>) = polled
+                .into_iter()
+                .partition(|(task_id, ..)| aborted.remove(task_id));
+            complete.extend(dropped.iter().map(|(task_id, ..)| *task_id));
+            drop(dropped);
+            polled = kept;
+        }
 
         let mut reactor = self.inner.borrow_mut();
-        reactor.tasks.append(&mut pending);
+        reactor.aborted.extend(aborted);
+
+        // the tasks left by the budget first, then the polled ones and
+        // the spawned ones
+        let mut queue = tasks.collect::<Vec<_>>();
+        queue.append(&mut polled);
+        queue.append(&mut reactor.tasks);
+        reactor.tasks = queue;
+
         for task_id in complete {
-            if let Some(waker) = reactor.complete.get_mut(&task_id) {
-                if let Some(waker) = waker.take() {
-                    waker.wake_by_ref();
-                }
-            } else {
-                reactor.complete.insert(task_id, None);
-            }
+            reactor.complete_task(task_id);
         }
 
-        if *reactor.main_task_state.borrow() {
-            trace!("main task ready");
+        // the tasks left by the budget are still ready
+        let ready = budget == 0 || reactor.woken.get() || reactor.main_task_state.ready.get();
+
+        trace!(
+            "tasks {:?} ready: {ready}",
+            reactor
+                .tasks
+                .iter()
+                .map(|(task_id, ..)| task_id)
+                .collect::<Vec<_>>(),
+        );
+
+        ready
+    }
+
+    /// Run a tick, then poll the host, blocking only when nothing is
+    /// ready.
+    #[cfg(not(feature = "wasip3"))]
+    #[instrument(skip_all)]
+    pub(crate) fn block_until(&self) {
+        let ready = self.tick();
+
+        let mut reactor = self.inner.borrow_mut();
+        if ready && reactor.poller.is_empty() {
             return;
         }
 
-        for key in reactor.poller.block_until() {
+        let start = Instant::now();
+        let keys = trace_span!("poll").in_scope(|| reactor.poller.block_until(!ready));
+        reactor.metrics.polls += 1;
+        reactor.metrics.poll_time += start.elapsed();
+        reactor.metrics.pollable_wakes += keys.len() as u64;
+
+        for key in keys {
             match reactor.wakers.get(&key) {
                 Some(waker) => waker.wake_by_ref(),
                 None => panic!("tried to wake the waker for non-existent `{key:?}`"),
@@ -218,19 +381,59 @@ impl Reactor {
     }
 
     pub(crate) fn reset_main_task_state(&self) {
-        *self.inner.borrow_mut().main_task_state.borrow_mut() = false;
+        self.inner.borrow().main_task_state.ready.set(false);
+    }
+
+    /// Drop the task, completing its join handle.
+    fn abort(&self, task_id: usize) {
+        let task = {
+            let mut reactor = self.inner.borrow_mut();
+            if reactor.shutdown
+                || matches!(reactor.handles.get(&task_id), Some(HandleState::Complete))
+            {
+                return;
+            }
+
+            if let Some(position) = reactor.tasks.iter().position(|(id, ..)| *id == task_id) {
+                let task = reactor.tasks.remove(position);
+                reactor.complete_task(task_id);
+                Some(task)
+            } else {
+                // out of the queue during a tick, dropped by the tick
+                reactor.aborted.insert(task_id);
+                None
+            }
+        };
+
+        // outside the borrow, the task removes its pollables on drop
+        drop(task);
     }
 
+    /// Spawn a task, it is dropped right away after a
+    /// [`shutdown`](Self::shutdown).
+    ///
+    /// The task is queued after the others and first polled in the
+    /// next tick; a task spawned by a running task, e.g. a connection
+    /// handler spawned by an accept loop, is polled after the tasks
+    /// already queued, in spawn order. Dropping the handle detaches
+    /// the task.
     pub fn spawn(&self, f: impl Future<Output = ()> + 'static) -> JoinHandle {
         let mut reactor = self.inner.borrow_mut();
 
         let task_id = reactor.next_id;
 
         reactor.next_id += 1;
+        reactor.metrics.tasks_spawned += 1;
 
-        reactor
-            .tasks
-            .push((task_id, Rc::new(RefCell::new(true)), Box::pin(f)));
+        if reactor.shutdown {
+            drop(reactor);
+            drop(f);
+        } else {
+            let state = Rc::new(TaskState::new(reactor.woken.clone()));
+            reactor.woken.set(true);
+            reactor.tasks.push((task_id, state, Box::pin(f)));
+            reactor.handles.insert(task_id, HandleState::Waiting(None));
+        }
 
         JoinHandle {
             reactor: self.clone(),
@@ -239,25 +442,356 @@ impl Reactor {
     }
 }
 
+impl InnerReactor {
+    /// Take the wakers of the pollables waited for.
+    #[cfg(not(feature = "wasip3"))]
+    fn take_pollable_wakers(&mut self) -> Vec<Waker> {
+        self.wakers.drain().map(|(_, waker)| waker).collect()
+    }
+
+    /// The tasks wait on the host futures, not on the reactor.
+    #[cfg(feature = "wasip3")]
+    fn take_pollable_wakers(&mut self) -> Vec<Waker> {
+        Vec::new()
+    }
+
+    /// Mark the task complete, waking its join handle, if any.
+    fn complete_task(&mut self, task_id: usize) {
+        if let Some(state) = self.handles.get_mut(&task_id) {
+            if let HandleState::Waiting(Some(waker)) = mem::replace(state, HandleState::Complete) {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// The wait for a pollable: the pollable is removed from the poller
+/// when ready or when the wait is dropped, so that a lost race (e.g.
+/// a timeout) does not leave it behind to wake up the poll forever.
+#[cfg(not(feature = "wasip3"))]
+struct WaitFor<'a> {
+    reactor: &'a Reactor,
+    pollable: Option<Pollable>,
+    key: Option<EventKey>,
+}
+
+#[cfg(not(feature = "wasip3"))]
+impl Future for WaitFor<'_> {
+    type Output = Result<(), Shutdown>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut reactor = this.reactor.inner.borrow_mut();
+
+        if reactor.shutdown {
+            // the registration is gone with the poller targets
+            this.key = None;
+            return Poll::Ready(Err(Shutdown));
+        }
+
+        let key = *this.key.get_or_insert_with(|| {
+            reactor.metrics.pollables_registered += 1;
+            reactor.poller.insert(this.pollable.take().unwrap())
+        });
+        reactor.wakers.insert(key, cx.waker().clone());
+
+        if reactor.poller.get(&key).unwrap().ready() {
+            trace!("{key:?} is ready");
+            reactor.poller.remove(key);
+            reactor.wakers.remove(&key);
+            this.key = None;
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(not(feature = "wasip3"))]
+impl Drop for WaitFor<'_> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            if let Ok(mut reactor) = self.reactor.inner.try_borrow_mut() {
+                if reactor.shutdown {
+                    return;
+                }
+                trace!("{key:?} dropped");
+                reactor.poller.remove(key);
+                reactor.wakers.remove(&key);
+            }
+        }
+    }
+}
+
 pub struct JoinHandle {
     reactor: Reactor,
     task_id: usize,
 }
 
+impl JoinHandle {
+    /// Drop the task, if not complete yet; the handle completes.
+    pub fn abort(&self) {
+        self.reactor.abort(self.task_id);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        let reactor = self.reactor.inner.borrow();
+        reactor.shutdown
+            || matches!(
+                reactor.handles.get(&self.task_id),
+                Some(HandleState::Complete)
+            )
+    }
+}
+
+impl Drop for JoinHandle {
+    fn drop(&mut self) {
+        self.reactor
+            .inner
+            .borrow_mut()
+            .handles
+            .remove(&self.task_id);
+    }
+}
+
 impl Future for JoinHandle {
     type Output = ();
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
         let this = self.get_mut();
         let mut reactor = this.reactor.inner.borrow_mut();
-        if reactor.complete.contains_key(&this.task_id) {
-            Poll::Ready(())
-        } else {
-            reactor
-                .complete
-                .entry(this.task_id)
-                .or_insert_with(|| Some(cx.waker().clone()));
-            Poll::Pending
+        if reactor.shutdown {
+            return Poll::Ready(());
+        }
+
+        match reactor.handles.get_mut(&this.task_id) {
+            Some(HandleState::Waiting(Some(waker))) => {
+                waker.clone_from(cx.waker());
+                Poll::Pending
+            }
+            Some(HandleState::Waiting(waker)) => {
+                *waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+            Some(HandleState::Complete) | None => Poll::Ready(()),
         }
     }
 }
+
+#[cfg(all(test, not(feature = "wasip3")))]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::future::{self, Future};
+    use std::rc::Rc;
+    use std::task::Poll;
+    use std::time::{Duration, Instant};
+
+    use futures_concurrency::future::Race;
+
+    use wasi::clocks::monotonic_clock;
+
+    use crate::block_on;
+    use crate::sync::oneshot;
+
+    use super::Shutdown;
+
+    /// Always ready again, like a stream with continuous data.
+    fn yield_now() -> impl Future<Output = ()> {
+        let mut yielded = false;
+        future::poll_fn(move |cx| {
+            if yielded {
+                Poll::Ready(())
+            } else {
+                yielded = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        })
+    }
+
+    #[test]
+    fn test_wait_for_dropped() {
+        block_on(|reactor| async move {
+            let long = monotonic_clock::subscribe_duration(60_000_000_000);
+            let short = monotonic_clock::subscribe_duration(1_000_000);
+
+            (reactor.wait_for(long), reactor.wait_for(short))
+                .race()
+                .await;
+
+            let inner = reactor.inner.borrow();
+            assert!(inner.poller.is_empty());
+            assert!(inner.wakers.is_empty());
+        });
+    }
+
+    #[test]
+    fn test_metrics() {
+        block_on(|reactor| async move {
+            reactor.spawn(async {}).await;
+
+            let short = monotonic_clock::subscribe_duration(1_000_000);
+            reactor.wait_for(short).await;
+
+            let metrics = reactor.metrics();
+            assert_eq!(1, metrics.tasks_spawned);
+            assert_eq!(1, metrics.tasks_completed);
+            assert_eq!(1, metrics.task_polls);
+            assert_eq!(1, metrics.pollables_registered);
+            assert!(metrics.polls >= 1);
+            assert!(metrics.pollable_wakes >= 1);
+        });
+    }
+
+    #[test]
+    fn test_shutdown() {
+        block_on(|reactor| async move {
+            let handle = {
+                let reactor = reactor.clone();
+                reactor.clone().spawn(async move {
+                    let long = monotonic_clock::subscribe_duration(60_000_000_000);
+                    reactor.wait_for(long).await;
+                    unreachable!("dropped by the shutdown");
+                })
+            };
+
+            let short = monotonic_clock::subscribe_duration(1_000_000);
+            reactor.wait_for(short).await;
+
+            reactor.shutdown();
+            handle.await;
+
+            let long = monotonic_clock::subscribe_duration(60_000_000_000);
+            assert_eq!(Err(Shutdown), reactor.try_wait_for(long).await);
+
+            let inner = reactor.inner.borrow();
+            assert!(inner.tasks.is_empty());
+            assert!(inner.poller.is_empty());
+        });
+    }
+
+    #[test]
+    fn test_fair_scheduling() {
+        block_on(|reactor| async move {
+            let busy = Rc::new(Cell::new(true));
+            reactor.spawn({
+                let busy = busy.clone();
+                async move {
+                    while busy.get() {
+                        yield_now().await;
+                    }
+                }
+            });
+
+            let start = Instant::now();
+            reactor
+                .spawn({
+                    let reactor = reactor.clone();
+                    async move {
+                        let short = monotonic_clock::subscribe_duration(1_000_000);
+                        reactor.wait_for(short).await;
+                    }
+                })
+                .await;
+            assert!(start.elapsed() < Duration::from_secs(1));
+
+            busy.set(false);
+
+            let metrics = reactor.metrics();
+            assert!(metrics.task_polls > 2);
+        });
+    }
+
+    #[test]
+    fn test_cross_task_wake() {
+        block_on(|reactor| async move {
+            let (sender, receiver) = oneshot::channel();
+
+            let start = Instant::now();
+            let handle = {
+                let reactor = reactor.clone();
+                reactor.clone().spawn(async move {
+                    let long = monotonic_clock::subscribe_duration(60_000_000_000);
+                    let received = async { receiver.await.ok() };
+                    let timeout = async {
+                        reactor.wait_for(long).await;
+                        None
+                    };
+                    assert_eq!(Some(1), (received, timeout).race().await);
+                })
+            };
+
+            reactor.spawn(async move {
+                sender.send(1).unwrap();
+            });
+
+            handle.await;
+            assert!(start.elapsed() < Duration::from_secs(1));
+        });
+    }
+
+    #[test]
+    fn test_abort() {
+        block_on(|reactor| async move {
+            let handle = {
+                let reactor = reactor.clone();
+                reactor.clone().spawn(async move {
+                    let long = monotonic_clock::subscribe_duration(60_000_000_000);
+                    reactor.wait_for(long).await;
+                    unreachable!("aborted");
+                })
+            };
+
+            // the task is waiting
+            reactor.spawn(async {}).await;
+            assert!(!handle.is_finished());
+
+            handle.abort();
+            assert!(handle.is_finished());
+            handle.await;
+
+            let inner = reactor.inner.borrow();
+            assert!(inner.tasks.is_empty());
+            assert!(inner.poller.is_empty());
+        });
+    }
+
+    #[test]
+    fn test_nested_spawn() {
+        const TASKS: usize = 5_000;
+
+        block_on(|reactor| async move {
+            let order = Rc::new(RefCell::new(vec![]));
+
+            // an accept loop spawning a short handler per connection
+            reactor
+                .spawn({
+                    let reactor = reactor.clone();
+                    let order = order.clone();
+                    async move {
+                        for i in 0..TASKS {
+                            let order = order.clone();
+                            drop(reactor.spawn(async move {
+                                order.borrow_mut().push(i);
+                            }));
+                        }
+                    }
+                })
+                .await;
+
+            while order.borrow().len() < TASKS {
+                yield_now().await;
+            }
+
+            // in spawn order
+            assert!(order.borrow().iter().copied().eq(0..TASKS));
+
+            let metrics = reactor.metrics();
+            assert_eq!(TASKS as u64 + 1, metrics.tasks_completed);
+
+            let inner = reactor.inner.borrow();
+            assert!(inner.tasks.is_empty());
+            assert!(inner.handles.is_empty());
+        });
+    }
+}
@@ -1,9 +1,14 @@
+pub mod broadcast;
+pub mod cancellation;
+pub mod mpsc;
 pub mod mutex;
 pub mod notify;
+pub mod oneshot;
 pub mod rwlock;
 pub mod semaphore;
 
+pub use cancellation::CancellationToken;
 pub use mutex::Mutex;
 pub use notify::Notify;
 pub use rwlock::RwLock;
-pub use semaphore::Semaphore;
+pub use semaphore::{OwnedSemaphorePermit, Semaphore, SemaphorePermit};
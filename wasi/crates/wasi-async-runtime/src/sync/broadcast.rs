@@ -0,0 +1,288 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::{self, Future};
+use std::rc::Rc;
+use std::task::{Poll, Waker};
+
+#[derive(Debug)]
+struct Shared<T> {
+    /// The last values sent, `buffer[0]` is at the position `head`.
+    buffer: VecDeque<T>,
+    head: u64,
+    capacity: usize,
+    senders: usize,
+    receivers: usize,
+    wakers: Vec<Waker>,
+}
+
+impl<T> Shared<T> {
+    fn tail(&self) -> u64 {
+        self.head + self.buffer.len() as u64
+    }
+
+    fn wake_all(&mut self) {
+        for waker in self.wakers.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+/// There are no receivers, the value is given back.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+impl<T: fmt::Debug> std::error::Error for SendError<T> {}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(fmt, "channel closed")
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RecvError {
+    /// All the senders were dropped.
+    Closed,
+
+    /// The receiver was too slow, the oldest values were overwritten;
+    /// the next receive starts from the oldest value kept.
+    Lagged(u64),
+}
+
+impl std::error::Error for RecvError {}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            RecvError::Closed => write!(fmt, "channel closed"),
+            RecvError::Lagged(n) => write!(fmt, "channel lagged by {n}"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TryRecvError {
+    Empty,
+    Closed,
+    Lagged(u64),
+}
+
+impl std::error::Error for TryRecvError {}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            TryRecvError::Empty => write!(fmt, "channel empty"),
+            TryRecvError::Closed => write!(fmt, "channel closed"),
+            TryRecvError::Lagged(n) => write!(fmt, "channel lagged by {n}"),
+        }
+    }
+}
+
+/// A channel where every receiver gets every value sent after it
+/// subscribed; the last `capacity` values are kept for the slow
+/// receivers, the older ones are lost.
+///
+/// # Panics
+/// * Panics when `capacity` is zero.
+pub fn channel<T: Clone>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "broadcast channel requires capacity > 0");
+    let shared = Rc::new(RefCell::new(Shared {
+        buffer: VecDeque::with_capacity(capacity),
+        head: 0,
+        capacity,
+        senders: 1,
+        receivers: 1,
+        wakers: vec![],
+    }));
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver { shared, next: 0 },
+    )
+}
+
+#[derive(Debug)]
+pub struct Sender<T> {
+    shared: Rc<RefCell<Shared<T>>>,
+}
+
+impl<T: Clone> Sender<T> {
+    /// Send a value to all the receivers, returning how many they are.
+    ///
+    /// # Errors
+    /// * Error when there are no receivers.
+    pub fn send(&self, value: T) -> Result<usize, SendError<T>> {
+        // single thread
+        let mut shared = self.shared.borrow_mut();
+        if shared.receivers == 0 {
+            return Err(SendError(value));
+        }
+
+        if shared.buffer.len() == shared.capacity {
+            shared.buffer.pop_front();
+            shared.head += 1;
+        }
+        shared.buffer.push_back(value);
+        shared.wake_all();
+
+        Ok(shared.receivers)
+    }
+
+    /// A new receiver, getting the values sent from now on.
+    pub fn subscribe(&self) -> Receiver<T> {
+        let mut shared = self.shared.borrow_mut();
+        shared.receivers += 1;
+        Receiver {
+            shared: self.shared.clone(),
+            next: shared.tail(),
+        }
+    }
+
+    pub fn receiver_count(&self) -> usize {
+        self.shared.borrow().receivers
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.borrow_mut().senders += 1;
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut shared = self.shared.borrow_mut();
+        shared.senders -= 1;
+        if shared.senders == 0 {
+            shared.wake_all();
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Receiver<T> {
+    shared: Rc<RefCell<Shared<T>>>,
+    next: u64,
+}
+
+impl<T: Clone> Receiver<T> {
+    /// Receive the next value, waiting for it.
+    pub fn recv(&mut self) -> impl Future<Output = Result<T, RecvError>> + '_ {
+        future::poll_fn(move |cx| match self.try_recv() {
+            Ok(value) => Poll::Ready(Ok(value)),
+            Err(TryRecvError::Closed) => Poll::Ready(Err(RecvError::Closed)),
+            Err(TryRecvError::Lagged(n)) => Poll::Ready(Err(RecvError::Lagged(n))),
+            Err(TryRecvError::Empty) => {
+                let mut shared = self.shared.borrow_mut();
+                if !shared.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                    shared.wakers.push(cx.waker().clone());
+                }
+                Poll::Pending
+            }
+        })
+    }
+
+    /// Receive the next value without waiting.
+    ///
+    /// # Errors
+    /// * Error when there is no value, the senders were dropped or the
+    ///   receiver lagged behind.
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let shared = self.shared.borrow();
+        if self.next < shared.head {
+            let lagged = shared.head - self.next;
+            self.next = shared.head;
+            return Err(TryRecvError::Lagged(lagged));
+        }
+
+        match shared.buffer.get((self.next - shared.head) as usize) {
+            Some(value) => {
+                self.next += 1;
+                Ok(value.clone())
+            }
+            None if shared.senders == 0 => Err(TryRecvError::Closed),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.shared.borrow_mut().receivers -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_concurrency::future::Join;
+
+    use crate::block_on;
+
+    use super::*;
+
+    #[test]
+    fn test_broadcast() {
+        block_on(|reactor| async move {
+            let (sender, receiver_1) = channel(16);
+            let receiver_2 = sender.subscribe();
+
+            let handle = reactor.spawn(async move {
+                for i in 0..10 {
+                    assert_eq!(Ok(2), sender.send(i));
+                }
+            });
+
+            let receive = |mut receiver: Receiver<i32>| async move {
+                let mut values = vec![];
+                while let Ok(value) = receiver.recv().await {
+                    values.push(value);
+                }
+                values
+            };
+
+            let ((), values_1, values_2) = (handle, receive(receiver_1), receive(receiver_2))
+                .join()
+                .await;
+
+            assert_eq!((0..10).collect::<Vec<_>>(), values_1);
+            assert_eq!(values_1, values_2);
+        });
+    }
+
+    #[test]
+    fn test_lagged() {
+        let (sender, mut receiver) = channel(2);
+
+        for i in 0..5 {
+            sender.send(i).unwrap();
+        }
+
+        assert_eq!(Err(TryRecvError::Lagged(3)), receiver.try_recv());
+        assert_eq!(Ok(3), receiver.try_recv());
+        assert_eq!(Ok(4), receiver.try_recv());
+        assert_eq!(Err(TryRecvError::Empty), receiver.try_recv());
+
+        // a late subscriber sees only the new values
+        let mut late = sender.subscribe();
+        sender.send(5).unwrap();
+        assert_eq!(Ok(5), late.try_recv());
+
+        drop(sender);
+        assert_eq!(Ok(5), receiver.try_recv());
+        assert_eq!(Err(TryRecvError::Closed), receiver.try_recv());
+    }
+
+    #[test]
+    fn test_no_receivers() {
+        let (sender, receiver) = channel(1);
+        drop(receiver);
+
+        assert_eq!(Err(SendError(1)), sender.send(1));
+    }
+}
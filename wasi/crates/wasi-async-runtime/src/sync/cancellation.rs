@@ -0,0 +1,157 @@
+use std::cell::RefCell;
+use std::future::{self, Future};
+use std::rc::{Rc, Weak};
+use std::task::{Poll, Waker};
+
+#[derive(Debug, Default)]
+struct Node {
+    cancelled: bool,
+    wakers: Vec<Waker>,
+    children: Vec<Weak<RefCell<Node>>>,
+}
+
+fn cancel(node: &RefCell<Node>) {
+    // single thread
+    let children = {
+        let mut node = node.borrow_mut();
+        if node.cancelled {
+            return;
+        }
+        node.cancelled = true;
+        for waker in node.wakers.drain(..) {
+            waker.wake();
+        }
+        std::mem::take(&mut node.children)
+    };
+
+    for child in children.iter().filter_map(Weak::upgrade) {
+        cancel(&child);
+    }
+}
+
+/// A token to ask the tasks to stop: the clones share the
+/// cancellation, the child tokens are cancelled with their parent but
+/// not the other way around.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    node: Rc<RefCell<Node>>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A token cancelled when this one is, already cancelled if this
+    /// one is.
+    pub fn child_token(&self) -> Self {
+        let child = Self::new();
+
+        let mut node = self.node.borrow_mut();
+        if node.cancelled {
+            child.node.borrow_mut().cancelled = true;
+        } else {
+            node.children.retain(|child| child.strong_count() > 0);
+            node.children.push(Rc::downgrade(&child.node));
+        }
+
+        child
+    }
+
+    /// Cancel this token and all its children.
+    pub fn cancel(&self) {
+        cancel(&self.node);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.node.borrow().cancelled
+    }
+
+    /// Wait for the cancellation.
+    pub fn cancelled(&self) -> impl Future<Output = ()> + '_ {
+        future::poll_fn(move |cx| {
+            let mut node = self.node.borrow_mut();
+            if node.cancelled {
+                Poll::Ready(())
+            } else {
+                // once per task, a future polled again does not pile
+                // up its wakers
+                if !node.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                    node.wakers.push(cx.waker().clone());
+                }
+                Poll::Pending
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::pin::pin;
+    use std::sync::Arc;
+    use std::task::{Context, Wake};
+
+    use crate::block_on;
+
+    use super::*;
+
+    #[test]
+    fn test_cancel() {
+        block_on(|reactor| async move {
+            let token = CancellationToken::new();
+
+            let handle = {
+                let token = token.clone();
+                reactor.spawn(async move {
+                    token.cancelled().await;
+                })
+            };
+
+            token.cancel();
+            handle.await;
+
+            assert!(token.is_cancelled());
+        });
+    }
+
+    #[test]
+    fn test_cancelled_polled_again() {
+        struct Noop;
+
+        impl Wake for Noop {
+            fn wake(self: Arc<Self>) {}
+        }
+
+        // the clones of `Waker::noop` are not known to wake it
+        let waker = Waker::from(Arc::new(Noop));
+        let mut cx = Context::from_waker(&waker);
+        let token = CancellationToken::new();
+
+        let mut cancelled = pin!(token.cancelled());
+        for _ in 0..3 {
+            assert!(cancelled.as_mut().poll(&mut cx).is_pending());
+        }
+        assert_eq!(1, token.node.borrow().wakers.len());
+
+        token.cancel();
+        assert!(cancelled.as_mut().poll(&mut cx).is_ready());
+        assert!(token.node.borrow().wakers.is_empty());
+    }
+
+    #[test]
+    fn test_children() {
+        let parent = CancellationToken::new();
+        let child = parent.child_token();
+        let grandchild = child.child_token();
+        let sibling = parent.child_token();
+
+        child.cancel();
+        assert!(!parent.is_cancelled());
+        assert!(!sibling.is_cancelled());
+        assert!(grandchild.is_cancelled());
+
+        parent.cancel();
+        assert!(sibling.is_cancelled());
+        assert!(parent.child_token().is_cancelled());
+    }
+}
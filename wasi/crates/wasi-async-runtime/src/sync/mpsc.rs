@@ -0,0 +1,405 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::{self, Future};
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+#[derive(Debug)]
+struct Chan<T> {
+    queue: VecDeque<T>,
+    capacity: Option<usize>,
+    senders: usize,
+    receiver: bool,
+    recv_waker: Option<Waker>,
+    send_wakers: Vec<Waker>,
+}
+
+impl<T> Chan<T> {
+    fn push(&mut self, value: T) {
+        self.queue.push_back(value);
+        if let Some(waker) = self.recv_waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Register a sender waiting for room, once per task, so that a
+    /// sender polled again while full does not pile up its wakers.
+    fn wait_for_room(&mut self, waker: &Waker) {
+        if !self.send_wakers.iter().any(|w| w.will_wake(waker)) {
+            self.send_wakers.push(waker.clone());
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.capacity
+            .is_some_and(|capacity| self.queue.len() >= capacity)
+    }
+}
+
+/// The receiver was dropped, the value is given back.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+impl<T: fmt::Debug> std::error::Error for SendError<T> {}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(fmt, "channel closed")
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TrySendError<T> {
+    Full(T),
+    Closed(T),
+}
+
+impl<T: fmt::Debug> std::error::Error for TrySendError<T> {}
+
+impl<T> fmt::Display for TrySendError<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            TrySendError::Full(_) => write!(fmt, "channel full"),
+            TrySendError::Closed(_) => write!(fmt, "channel closed"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TryRecvError {
+    Empty,
+    Disconnected,
+}
+
+impl std::error::Error for TryRecvError {}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            TryRecvError::Empty => write!(fmt, "channel empty"),
+            TryRecvError::Disconnected => write!(fmt, "channel disconnected"),
+        }
+    }
+}
+
+/// A channel holding up to `capacity` values, the senders wait when
+/// it is full.
+///
+/// # Panics
+/// * Panics when `capacity` is zero.
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "mpsc bounded channel requires capacity > 0");
+    let chan = new_chan(Some(capacity));
+    (Sender { chan: chan.clone() }, Receiver { chan })
+}
+
+/// A channel without limits, the senders never wait.
+pub fn unbounded_channel<T>() -> (UnboundedSender<T>, Receiver<T>) {
+    let chan = new_chan(None);
+    (UnboundedSender { chan: chan.clone() }, Receiver { chan })
+}
+
+fn new_chan<T>(capacity: Option<usize>) -> Rc<RefCell<Chan<T>>> {
+    Rc::new(RefCell::new(Chan {
+        queue: VecDeque::new(),
+        capacity,
+        senders: 1,
+        receiver: true,
+        recv_waker: None,
+        send_wakers: vec![],
+    }))
+}
+
+#[derive(Debug)]
+pub struct Sender<T> {
+    chan: Rc<RefCell<Chan<T>>>,
+}
+
+impl<T> Sender<T> {
+    /// Send a value, waiting for room in the channel.
+    pub fn send(&self, value: T) -> impl Future<Output = Result<(), SendError<T>>> + '_ {
+        let mut value = Some(value);
+        future::poll_fn(move |cx| {
+            // single thread
+            let mut chan = self.chan.borrow_mut();
+            if !chan.receiver {
+                Poll::Ready(Err(SendError(value.take().unwrap())))
+            } else if chan.is_full() {
+                chan.wait_for_room(cx.waker());
+                Poll::Pending
+            } else {
+                chan.push(value.take().unwrap());
+                Poll::Ready(Ok(()))
+            }
+        })
+    }
+
+    /// Send a value if there is room in the channel.
+    ///
+    /// # Errors
+    /// * Error when the channel is full or the receiver was dropped.
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        let mut chan = self.chan.borrow_mut();
+        if !chan.receiver {
+            Err(TrySendError::Closed(value))
+        } else if chan.is_full() {
+            Err(TrySendError::Full(value))
+        } else {
+            chan.push(value);
+            Ok(())
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        !self.chan.borrow().receiver
+    }
+
+    /// The number of values that can be sent without waiting.
+    pub fn capacity(&self) -> usize {
+        let chan = self.chan.borrow();
+        self.max_capacity().saturating_sub(chan.queue.len())
+    }
+
+    /// The capacity the channel was created with.
+    pub fn max_capacity(&self) -> usize {
+        self.chan
+            .borrow()
+            .capacity
+            .expect("bounded channel has a capacity")
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.chan.borrow_mut().senders += 1;
+        Self {
+            chan: self.chan.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        drop_sender(&self.chan);
+    }
+}
+
+#[derive(Debug)]
+pub struct UnboundedSender<T> {
+    chan: Rc<RefCell<Chan<T>>>,
+}
+
+impl<T> UnboundedSender<T> {
+    /// Send a value without waiting.
+    ///
+    /// # Errors
+    /// * Error when the receiver was dropped.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let mut chan = self.chan.borrow_mut();
+        if chan.receiver {
+            chan.push(value);
+            Ok(())
+        } else {
+            Err(SendError(value))
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        !self.chan.borrow().receiver
+    }
+}
+
+impl<T> Clone for UnboundedSender<T> {
+    fn clone(&self) -> Self {
+        self.chan.borrow_mut().senders += 1;
+        Self {
+            chan: self.chan.clone(),
+        }
+    }
+}
+
+impl<T> Drop for UnboundedSender<T> {
+    fn drop(&mut self) {
+        drop_sender(&self.chan);
+    }
+}
+
+fn drop_sender<T>(chan: &RefCell<Chan<T>>) {
+    let mut chan = chan.borrow_mut();
+    chan.senders -= 1;
+    if chan.senders == 0 {
+        if let Some(waker) = chan.recv_waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Receiver<T> {
+    chan: Rc<RefCell<Chan<T>>>,
+}
+
+impl<T> Receiver<T> {
+    /// Receive the next value, `None` when the channel is empty and
+    /// all the senders were dropped.
+    pub fn recv(&mut self) -> impl Future<Output = Option<T>> + '_ {
+        future::poll_fn(move |cx| self.poll_recv(cx))
+    }
+
+    pub fn poll_recv(&mut self, cx: &mut Context) -> Poll<Option<T>> {
+        match self.try_recv() {
+            Ok(value) => Poll::Ready(Some(value)),
+            Err(TryRecvError::Disconnected) => Poll::Ready(None),
+            Err(TryRecvError::Empty) => {
+                self.chan.borrow_mut().recv_waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Receive the next value without waiting.
+    ///
+    /// # Errors
+    /// * Error when the channel is empty.
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let mut chan = self.chan.borrow_mut();
+        if let Some(value) = chan.queue.pop_front() {
+            for waker in chan.send_wakers.drain(..) {
+                waker.wake();
+            }
+            Ok(value)
+        } else if chan.senders == 0 {
+            Err(TryRecvError::Disconnected)
+        } else {
+            Err(TryRecvError::Empty)
+        }
+    }
+
+    /// Stop accepting values, the ones already sent can still be
+    /// received.
+    pub fn close(&mut self) {
+        let mut chan = self.chan.borrow_mut();
+        chan.receiver = false;
+        for waker in chan.send_wakers.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_concurrency::future::Join;
+
+    use crate::block_on;
+
+    use super::*;
+
+    #[test]
+    fn test_unbounded() {
+        block_on(|reactor| async move {
+            let (sender, mut receiver) = unbounded_channel();
+
+            let handle = {
+                let sender = sender.clone();
+                reactor.spawn(async move {
+                    for i in 0..10 {
+                        sender.send(i).unwrap();
+                    }
+                })
+            };
+            drop(sender);
+
+            let mut values = vec![];
+            while let Some(value) = receiver.recv().await {
+                values.push(value);
+            }
+            handle.await;
+
+            assert_eq!((0..10).collect::<Vec<_>>(), values);
+        });
+    }
+
+    #[test]
+    fn test_bounded() {
+        block_on(|reactor| async move {
+            let (sender, mut receiver) = channel(2);
+
+            let handle = reactor.spawn(async move {
+                for i in 0..10 {
+                    sender.send(i).await.unwrap();
+                }
+            });
+
+            let receive = async move {
+                let mut values = vec![];
+                while let Some(value) = receiver.recv().await {
+                    values.push(value);
+                }
+                values
+            };
+
+            let ((), values) = (handle, receive).join().await;
+
+            assert_eq!((0..10).collect::<Vec<_>>(), values);
+        });
+    }
+
+    #[test]
+    fn test_backpressure() {
+        block_on(|reactor| async move {
+            let (sender, mut receiver) = channel(2);
+            let sent = Rc::new(std::cell::Cell::new(0));
+
+            let handle = {
+                let sent = sent.clone();
+                reactor.spawn(async move {
+                    for i in 0..5 {
+                        sender.send(i).await.unwrap();
+                        sent.set(sent.get() + 1);
+                    }
+                })
+            };
+
+            // the producer stops when the channel is full
+            reactor.spawn(async {}).await;
+            reactor.spawn(async {}).await;
+            assert_eq!(2, sent.get());
+
+            assert_eq!(Some(0), receiver.recv().await);
+            reactor.spawn(async {}).await;
+            assert_eq!(3, sent.get());
+
+            let mut values = vec![];
+            while let Some(value) = receiver.recv().await {
+                values.push(value);
+            }
+            handle.await;
+
+            assert_eq!(vec![1, 2, 3, 4], values);
+        });
+    }
+
+    #[test]
+    fn test_try() {
+        let (sender, mut receiver) = channel(1);
+
+        assert_eq!(Err(TryRecvError::Empty), receiver.try_recv());
+        assert_eq!(1, sender.capacity());
+        assert_eq!(Ok(()), sender.try_send(1));
+        assert_eq!(0, sender.capacity());
+        assert_eq!(Err(TrySendError::Full(2)), sender.try_send(2));
+        assert_eq!(Ok(1), receiver.try_recv());
+        assert_eq!(1, sender.max_capacity());
+
+        drop(receiver);
+        assert!(sender.is_closed());
+        assert_eq!(Err(TrySendError::Closed(3)), sender.try_send(3));
+    }
+}
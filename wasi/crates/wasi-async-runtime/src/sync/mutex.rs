@@ -23,6 +23,14 @@ impl<T> Mutex<T> {
         // SAFETY: protect by a semaphore
         MutexGuard::new(permit, unsafe { &mut *self.value.get() })
     }
+
+    /// The guard if the mutex is free and nobody is waiting for it.
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        let permit = self.semaphore.try_acquire()?;
+
+        // SAFETY: protect by a semaphore
+        Some(MutexGuard::new(permit, unsafe { &mut *self.value.get() }))
+    }
 }
 
 #[derive(Debug)]
@@ -0,0 +1,140 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+#[derive(Debug)]
+struct Inner<T> {
+    value: Option<T>,
+    sender: bool,
+    receiver: bool,
+    waker: Option<Waker>,
+}
+
+/// The sender was dropped without sending a value.
+#[derive(Debug, PartialEq, Eq)]
+pub struct RecvError;
+
+impl std::error::Error for RecvError {}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(fmt, "channel closed")
+    }
+}
+
+/// A channel for a single value.
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let inner = Rc::new(RefCell::new(Inner {
+        value: None,
+        sender: true,
+        receiver: true,
+        waker: None,
+    }));
+    (
+        Sender {
+            inner: inner.clone(),
+        },
+        Receiver { inner },
+    )
+}
+
+#[derive(Debug)]
+pub struct Sender<T> {
+    inner: Rc<RefCell<Inner<T>>>,
+}
+
+impl<T> Sender<T> {
+    /// Send the value.
+    ///
+    /// # Errors
+    /// * Error with the value when the receiver was dropped.
+    pub fn send(self, value: T) -> Result<(), T> {
+        let mut inner = self.inner.borrow_mut();
+        if inner.receiver {
+            inner.value = Some(value);
+            Ok(())
+        } else {
+            Err(value)
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        !self.inner.borrow().receiver
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        // single thread
+        let mut inner = self.inner.borrow_mut();
+        inner.sender = false;
+        if let Some(waker) = inner.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// The future of the value.
+#[derive(Debug)]
+pub struct Receiver<T> {
+    inner: Rc<RefCell<Inner<T>>>,
+}
+
+impl<T> Future for Receiver<T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut inner = self.inner.borrow_mut();
+        if let Some(value) = inner.value.take() {
+            Poll::Ready(Ok(value))
+        } else if inner.sender {
+            inner.waker = Some(cx.waker().clone());
+            Poll::Pending
+        } else {
+            Poll::Ready(Err(RecvError))
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.inner.borrow_mut().receiver = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::block_on;
+
+    use super::*;
+
+    #[test]
+    fn test_oneshot() {
+        block_on(|reactor| async move {
+            let (sender, receiver) = channel();
+
+            reactor.spawn(async move {
+                sender.send(42).unwrap();
+            });
+
+            assert_eq!(Ok(42), receiver.await);
+        });
+    }
+
+    #[test]
+    fn test_dropped() {
+        block_on(|_| async move {
+            let (sender, receiver) = channel::<()>();
+            drop(sender);
+            assert_eq!(Err(RecvError), receiver.await);
+
+            let (sender, receiver) = channel();
+            drop(receiver);
+            assert!(sender.is_closed());
+            assert_eq!(Err(1), sender.send(1));
+        });
+    }
+}
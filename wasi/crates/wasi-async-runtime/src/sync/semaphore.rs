@@ -1,13 +1,44 @@
 use std::cell::RefCell;
-use std::future::{self, Future};
-use std::task::{Poll, Waker};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+#[derive(Debug)]
+struct Waiter {
+    id: u64,
+    waker: Waker,
+
+    /// The permit was handed over by a release.
+    granted: bool,
+}
 
 #[derive(Debug)]
 struct SemaphoreInner {
     permits: usize,
-    queue: Vec<Waker>,
+    next_id: u64,
+    queue: VecDeque<Waiter>,
 }
 
+impl SemaphoreInner {
+    /// Hand the free permits to the waiters, in arrival order.
+    fn release(&mut self, permits: usize) {
+        self.permits += permits;
+
+        for waiter in self.queue.iter_mut().filter(|waiter| !waiter.granted) {
+            if self.permits == 0 {
+                break;
+            }
+            self.permits -= 1;
+            waiter.granted = true;
+            waiter.waker.wake_by_ref();
+        }
+    }
+}
+
+/// A fair semaphore: the permits are given to the waiters in arrival
+/// order, a new acquire cannot overtake the queued ones.
 #[derive(Debug)]
 pub struct Semaphore {
     inner: RefCell<SemaphoreInner>,
@@ -18,22 +49,114 @@ impl Semaphore {
         Self {
             inner: RefCell::new(SemaphoreInner {
                 permits,
-                queue: vec![],
+                next_id: 0,
+                queue: VecDeque::new(),
             }),
         }
     }
 
-    pub fn acquire(&self) -> impl Future<Output = SemaphorePermit> {
-        future::poll_fn(move |cx| {
-            let mut this = self.inner.borrow_mut();
-            if this.permits == 0 {
-                this.queue.push(cx.waker().clone());
-                Poll::Pending
-            } else {
-                this.permits -= 1;
-                Poll::Ready(SemaphorePermit { this: self })
+    pub fn available_permits(&self) -> usize {
+        self.inner.borrow().permits
+    }
+
+    pub fn add_permits(&self, permits: usize) {
+        self.inner.borrow_mut().release(permits);
+    }
+
+    pub fn acquire(&self) -> Acquire<'_> {
+        Acquire {
+            semaphore: self,
+            id: None,
+        }
+    }
+
+    /// A permit if one is free and nobody is waiting.
+    pub fn try_acquire(&self) -> Option<SemaphorePermit<'_>> {
+        let mut this = self.inner.borrow_mut();
+        if this.permits > 0 && this.queue.is_empty() {
+            this.permits -= 1;
+            Some(SemaphorePermit { this: self })
+        } else {
+            None
+        }
+    }
+
+    /// Acquire a permit not borrowing the semaphore, e.g. to be moved
+    /// to a spawned task.
+    pub async fn acquire_owned(self: Rc<Self>) -> OwnedSemaphorePermit {
+        self.acquire().await.forget();
+        OwnedSemaphorePermit { semaphore: self }
+    }
+}
+
+/// The future of [`Semaphore::acquire`]; when dropped it leaves the
+/// queue, giving back the permit if already granted.
+#[derive(Debug)]
+pub struct Acquire<'a> {
+    semaphore: &'a Semaphore,
+    id: Option<u64>,
+}
+
+impl<'a> Future for Acquire<'a> {
+    type Output = SemaphorePermit<'a>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut inner = this.semaphore.inner.borrow_mut();
+
+        let Some(id) = this.id else {
+            if inner.permits > 0 && inner.queue.is_empty() {
+                inner.permits -= 1;
+                return Poll::Ready(SemaphorePermit {
+                    this: this.semaphore,
+                });
+            }
+
+            let id = inner.next_id;
+            inner.next_id += 1;
+            inner.queue.push_back(Waiter {
+                id,
+                waker: cx.waker().clone(),
+                granted: false,
+            });
+            this.id = Some(id);
+            return Poll::Pending;
+        };
+
+        let position = inner
+            .queue
+            .iter()
+            .position(|waiter| waiter.id == id)
+            .expect("waiter in the queue");
+        if inner.queue[position].granted {
+            inner.queue.remove(position);
+            this.id = None;
+            Poll::Ready(SemaphorePermit {
+                this: this.semaphore,
+            })
+        } else {
+            inner.queue[position].waker.clone_from(cx.waker());
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for Acquire<'_> {
+    fn drop(&mut self) {
+        let Some(id) = self.id else {
+            return;
+        };
+
+        let mut inner = self.semaphore.inner.borrow_mut();
+        if let Some(position) = inner.queue.iter().position(|waiter| waiter.id == id) {
+            if inner
+                .queue
+                .remove(position)
+                .is_some_and(|waiter| waiter.granted)
+            {
+                inner.release(1);
             }
-        })
+        }
     }
 }
 
@@ -42,15 +165,95 @@ pub struct SemaphorePermit<'a> {
     this: &'a Semaphore,
 }
 
+impl SemaphorePermit<'_> {
+    /// Keep the permit taken, without giving it back on drop.
+    pub fn forget(self) {
+        std::mem::forget(self);
+    }
+}
+
 impl Drop for SemaphorePermit<'_> {
     fn drop(&mut self) {
         // single thread
-        let mut this = self.this.inner.borrow_mut();
+        self.this.inner.borrow_mut().release(1);
+    }
+}
 
-        this.permits += 1;
+#[derive(Debug)]
+pub struct OwnedSemaphorePermit {
+    semaphore: Rc<Semaphore>,
+}
 
-        for waker in this.queue.drain(..) {
-            waker.wake();
-        }
+impl Drop for OwnedSemaphorePermit {
+    fn drop(&mut self) {
+        self.semaphore.inner.borrow_mut().release(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use futures_concurrency::future::Join;
+
+    use crate::block_on;
+
+    use super::*;
+
+    #[test]
+    fn test_fair() {
+        block_on(|reactor| async move {
+            let semaphore = Rc::new(Semaphore::new(1));
+            let order = Rc::new(RefCell::new(vec![]));
+
+            let permit = semaphore.try_acquire().unwrap();
+
+            let handles = (0..3)
+                .map(|i| {
+                    let semaphore = semaphore.clone();
+                    let order = order.clone();
+                    reactor.spawn(async move {
+                        let _permit = semaphore.acquire().await;
+                        order.borrow_mut().push(i);
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            // the waiters are queued
+            reactor.spawn(async {}).await;
+            assert!(semaphore.try_acquire().is_none());
+
+            drop(permit);
+
+            let [a, b, c]: [_; 3] = handles.try_into().ok().unwrap();
+            (a, b, c).join().await;
+
+            assert_eq!(vec![0, 1, 2], *order.borrow());
+            assert_eq!(1, semaphore.available_permits());
+        });
+    }
+
+    #[test]
+    fn test_acquire_dropped() {
+        block_on(|_| async move {
+            let semaphore = Semaphore::new(0);
+            {
+                let mut acquire = std::pin::pin!(semaphore.acquire());
+                let waker = std::task::Waker::noop();
+                assert!(acquire
+                    .as_mut()
+                    .poll(&mut Context::from_waker(waker))
+                    .is_pending());
+
+                semaphore.add_permits(1);
+            }
+
+            // the granted permit is given back
+            assert_eq!(1, semaphore.available_permits());
+
+            let owned = Rc::new(semaphore).acquire_owned().await;
+            drop(owned);
+        });
     }
 }
@@ -1,3 +1,4 @@
+use std::fmt;
 use std::future::Future;
 use std::pin::{pin, Pin};
 use std::task::{Context, Poll};
@@ -6,51 +7,77 @@ use futures::{ready, Sink, Stream};
 
 use tracing::{error, instrument, trace, warn};
 
-use wasi::io::streams::StreamError;
-
 use bytes::BytesMut;
 
-use crate::io::{AsyncRead, AsyncWrite};
+use crate::io::{self, AsyncRead, AsyncWrite};
 
 const INITIAL_CAPACITY: usize = 1024 * 8;
 
 pub trait Decoder {
     type Item;
-    type Error: From<StreamError>;
+    type Error: From<io::Error>;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error>;
 
     fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
         match self.decode(src) {
             Ok(Some(value)) => Ok(Some(value)),
-            Ok(None) => Err(StreamError::Closed.into()),
+            Ok(None) => Err(io::Error::from(io::ErrorKind::Closed).into()),
             Err(e) => Err(e),
         }
     }
 }
 
 pub trait Encoder<Item> {
-    type Error: From<StreamError>;
+    type Error: From<io::Error>;
 
     fn encode(&mut self, item: Item, dst: &mut BytesMut) -> Result<(), Self::Error>;
 }
 
-pub struct FramedRead<R, D> {
+/// A frame over the maximum length of a [`FramedRead`].
+#[derive(Debug, PartialEq)]
+pub struct FrameTooLong(pub usize);
+
+impl std::error::Error for FrameTooLong {}
+
+impl fmt::Display for FrameTooLong {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(fmt, "frame too long: {} bytes", self.0)
+    }
+}
+
+type FrameTooLongError<E> = fn(FrameTooLong) -> E;
+
+pub struct FramedRead<R, D: Decoder> {
     read: R,
     decoder: D,
     buffer: BytesMut,
     eof: bool,
+    max_frame_length: Option<(usize, FrameTooLongError<D::Error>)>,
 }
 
-impl<R, D> FramedRead<R, D> {
+impl<R, D: Decoder> FramedRead<R, D> {
     pub fn new(read: R, decoder: D) -> Self {
         Self {
             read,
             decoder,
             buffer: BytesMut::new(),
             eof: false,
+            max_frame_length: None,
         }
     }
+
+    /// Fail with [`FrameTooLong`] when the decoder holds or reserves
+    /// more than `max` bytes for a frame, so that a peer cannot make
+    /// the buffer grow without bounds.
+    #[must_use]
+    pub fn with_max_frame_length(mut self, max: usize) -> Self
+    where
+        D::Error: From<FrameTooLong>,
+    {
+        self.max_frame_length = Some((max, D::Error::from));
+        self
+    }
 }
 
 impl<R: AsyncRead + Unpin, D: Decoder + Unpin> FramedRead<R, D>
@@ -67,7 +94,7 @@ where
         match self.decoder.decode_eof(&mut self.buffer) {
             Ok(None) => {
                 error!("decoder eof returned Ok(None)");
-                return Poll::Ready(Some(Err(StreamError::Closed.into())));
+                return Poll::Ready(Some(Err(io::Error::from(io::ErrorKind::Closed).into())));
             }
             Ok(Some(v)) => {
                 trace!("some data");
@@ -94,20 +121,34 @@ impl<R: AsyncRead + Unpin, D: Decoder + Unpin> Stream for FramedRead<R, D> {
             }
 
             trace!("decode {}", this.buffer.len());
+            let capacity = this.buffer.capacity();
             match this.decoder.decode(&mut this.buffer) {
                 Ok(Some(value)) => return Poll::Ready(Some(Ok(value))),
                 Err(e) => return Poll::Ready(Some(Err(e))),
                 Ok(None) => {}
             }
 
+            let mut len = this.buffer.capacity().max(1);
+            if let Some((max, error)) = this.max_frame_length {
+                // a reservation by the decoder over the maximum
+                let reserved = this.buffer.capacity() > capacity.max(max);
+                if this.buffer.len() >= max || reserved {
+                    warn!("frame too long, max {max}");
+                    return Poll::Ready(Some(Err(error(FrameTooLong(
+                        this.buffer.len().max(this.buffer.capacity()),
+                    )))));
+                }
+                len = len.min(max - this.buffer.len());
+            }
+
             let read = &mut this.read;
-            let len = this.buffer.capacity().max(1) as u64;
+            let len = len as u64;
             let (data, eof) = {
                 trace!("read {len}/{}", this.buffer.len());
                 let f = pin!(read.read(len));
                 match f.poll(cx) {
                     Poll::Ready(Ok(data)) => (Some(data), false),
-                    Poll::Ready(Err(StreamError::Closed)) => (None, true),
+                    Poll::Ready(Err(err)) if err.is_closed() => (None, true),
                     Poll::Ready(Err(e)) => return Poll::Ready(Some(Err(e.into()))),
                     Poll::Pending => return Poll::Pending,
                 }
@@ -221,7 +262,7 @@ impl Default for LinesDecoder {
 
 impl Decoder for LinesDecoder {
     type Item = Vec<u8>;
-    type Error = StreamError;
+    type Error = io::Error;
 
     #[instrument]
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
@@ -264,14 +305,70 @@ impl<const SIZE: usize> Default for ChunksDecoder<SIZE> {
 
 impl<const SIZE: usize> Decoder for ChunksDecoder<SIZE> {
     type Item = [u8; SIZE];
-    type Error = StreamError;
+    type Error = io::Error;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        if src.len() < SIZE {
-            Ok(None)
-        } else {
-            let chunk = src.split_to(SIZE);
-            Ok(Some(chunk[..].try_into().unwrap()))
-        }
+        Ok(split_chunk(src, SIZE).map(|chunk| chunk[..].try_into().unwrap()))
+    }
+}
+
+/// The chunks of a size known at runtime, see [`ChunksDecoder`] for
+/// the size known at compile time.
+#[derive(Debug)]
+pub struct SizedChunksDecoder {
+    size: usize,
+}
+
+impl SizedChunksDecoder {
+    /// # Panics
+    /// * Panics when `size` is zero.
+    pub fn new(size: usize) -> Self {
+        assert!(size > 0, "chunk size zero");
+        Self { size }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl Decoder for SizedChunksDecoder {
+    type Item = BytesMut;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        Ok(split_chunk(src, self.size))
+    }
+}
+
+fn split_chunk(src: &mut BytesMut, size: usize) -> Option<BytesMut> {
+    if src.len() < size {
+        src.reserve(size - src.len());
+        None
+    } else {
+        Some(src.split_to(size))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+
+    use super::{ChunksDecoder, Decoder, SizedChunksDecoder};
+
+    #[test]
+    fn test_chunks() {
+        let mut src = BytesMut::from(&b"0123456"[..]);
+
+        let mut decoder = SizedChunksDecoder::new(3);
+        assert_eq!(&b"012"[..], decoder.decode(&mut src).unwrap().unwrap());
+        assert_eq!(&b"345"[..], decoder.decode(&mut src).unwrap().unwrap());
+        assert!(decoder.decode(&mut src).unwrap().is_none());
+
+        src.extend_from_slice(b"78");
+        assert_eq!(
+            Some(*b"678"),
+            ChunksDecoder::<3>::new().decode(&mut src).unwrap()
+        );
     }
 }
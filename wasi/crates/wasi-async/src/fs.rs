@@ -0,0 +1,257 @@
+//! The files of the preopened directories of `wasi:filesystem`, read
+//! and written through their streams.
+use tracing::{instrument, trace};
+
+use wasi::filesystem::preopens::get_directories;
+use wasi::filesystem::types::{Descriptor, DescriptorFlags, ErrorCode, OpenFlags, PathFlags};
+use wasi::io::streams::{InputStream, OutputStream, StreamError};
+
+use wasi_async_runtime::Reactor;
+
+use crate::io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+enum Mode {
+    Read,
+    Write,
+    Append,
+}
+
+pub struct File {
+    reactor: Reactor,
+    // the streams are children of the descriptor, they must be
+    // dropped before it
+    input_stream: Option<InputStream>,
+    output_stream: Option<OutputStream>,
+    descriptor: Descriptor,
+    mode: Mode,
+}
+
+impl File {
+    /// Open an existing file for reading.
+    ///
+    /// # Errors
+    /// * Error when the file is not in a preopened directory or cannot
+    ///   be opened.
+    pub fn open(reactor: Reactor, path: &str) -> Result<Self, io::Error> {
+        Self::open_with(
+            reactor,
+            path,
+            OpenFlags::empty(),
+            DescriptorFlags::READ,
+            Mode::Read,
+        )
+    }
+
+    /// Open a file for writing, creating or truncating it.
+    ///
+    /// # Errors
+    /// * Error when the file is not in a preopened directory or cannot
+    ///   be created.
+    pub fn create(reactor: Reactor, path: &str) -> Result<Self, io::Error> {
+        Self::open_with(
+            reactor,
+            path,
+            OpenFlags::CREATE | OpenFlags::TRUNCATE,
+            DescriptorFlags::WRITE,
+            Mode::Write,
+        )
+    }
+
+    /// Open a file for writing at its end, creating it.
+    ///
+    /// # Errors
+    /// * Error when the file is not in a preopened directory or cannot
+    ///   be created.
+    pub fn append(reactor: Reactor, path: &str) -> Result<Self, io::Error> {
+        Self::open_with(
+            reactor,
+            path,
+            OpenFlags::CREATE,
+            DescriptorFlags::WRITE,
+            Mode::Append,
+        )
+    }
+
+    #[instrument(skip(reactor, open_flags, flags, mode))]
+    fn open_with(
+        reactor: Reactor,
+        path: &str,
+        open_flags: OpenFlags,
+        flags: DescriptorFlags,
+        mode: Mode,
+    ) -> Result<Self, io::Error> {
+        let (directory, path) = preopened(path)?;
+
+        let descriptor = directory.open_at(PathFlags::SYMLINK_FOLLOW, &path, open_flags, flags)?;
+
+        Ok(Self {
+            reactor,
+            input_stream: None,
+            output_stream: None,
+            descriptor,
+            mode,
+        })
+    }
+
+    /// The size of the file in bytes.
+    ///
+    /// # Errors
+    /// * Error when the host cannot read the metadata.
+    pub fn size(&self) -> Result<u64, io::Error> {
+        Ok(self.descriptor.stat()?.size)
+    }
+
+    /// Wait for the written data to reach the storage.
+    ///
+    /// # Errors
+    /// * Error when the data cannot be flushed or synced.
+    pub async fn sync_data(&mut self) -> Result<(), io::Error> {
+        self.flush().await?;
+        Ok(self.descriptor.sync_data()?)
+    }
+
+    fn input_stream(&mut self) -> Result<&InputStream, io::Error> {
+        if self.input_stream.is_none() {
+            let Mode::Read = self.mode else {
+                return Err(io::ErrorKind::Closed.into());
+            };
+            let input_stream = self.descriptor.read_via_stream(0)?;
+            self.input_stream = Some(input_stream);
+        }
+
+        Ok(self.input_stream.as_ref().unwrap())
+    }
+
+    fn output_stream(&mut self) -> Result<&OutputStream, io::Error> {
+        if self.output_stream.is_none() {
+            let output_stream = match self.mode {
+                Mode::Read => return Err(io::ErrorKind::Closed.into()),
+                Mode::Write => self.descriptor.write_via_stream(0),
+                Mode::Append => self.descriptor.append_via_stream(),
+            }?;
+            self.output_stream = Some(output_stream);
+        }
+
+        Ok(self.output_stream.as_ref().unwrap())
+    }
+}
+
+impl AsyncRead for File {
+    #[instrument(skip_all)]
+    async fn read(&mut self, len: u64) -> Result<Vec<u8>, io::Error> {
+        let reactor = self.reactor.clone();
+        let input_stream = self.input_stream()?;
+        loop {
+            match input_stream.read(len) {
+                Ok(data) if data.is_empty() && len > 0 => {
+                    let subscription = input_stream.subscribe();
+                    trace!("file input stream subscription {subscription:?}");
+                    reactor.wait_for(subscription).await;
+                }
+                Ok(data) => return Ok(data),
+                Err(StreamError::Closed) => return Ok(vec![]),
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
+
+impl AsyncWrite for File {
+    #[instrument(skip_all)]
+    async fn write(&mut self, data: &[u8]) -> Result<u64, io::Error> {
+        if data.is_empty() {
+            return Ok(0);
+        }
+
+        let reactor = self.reactor.clone();
+        let output_stream = self.output_stream()?;
+        let len = loop {
+            let len = output_stream.check_write()?;
+            if len > 0 {
+                break len;
+            }
+            let subscription = output_stream.subscribe();
+            trace!("file output stream subscription {subscription:?}");
+            reactor.wait_for(subscription).await;
+        };
+
+        let len = data.len().min(len as usize);
+
+        output_stream.write(&data[0..len])?;
+
+        Ok(len as u64)
+    }
+
+    #[instrument(skip_all)]
+    async fn flush(&mut self) -> Result<(), io::Error> {
+        let Some(output_stream) = &self.output_stream else {
+            return Ok(());
+        };
+
+        output_stream.flush()?;
+        while output_stream.check_write()? == 0 {
+            let subscription = output_stream.subscribe();
+            trace!("file output stream subscription {subscription:?}");
+            self.reactor.wait_for(subscription).await;
+        }
+        Ok(())
+    }
+
+    async fn close(&mut self) -> Result<(), io::Error> {
+        self.flush().await?;
+        self.output_stream = None;
+        Ok(())
+    }
+}
+
+/// Read the whole file.
+///
+/// # Errors
+/// * Error when the file cannot be opened or read.
+pub async fn read(reactor: Reactor, path: &str) -> Result<Vec<u8>, io::Error> {
+    let mut file = File::open(reactor, path)?;
+    let mut data = vec![];
+    file.read_to_end(&mut data).await?;
+    Ok(data)
+}
+
+/// Replace the content of the file with `data`, creating it.
+///
+/// # Errors
+/// * Error when the file cannot be created or written.
+pub async fn write(reactor: Reactor, path: &str, data: &[u8]) -> Result<(), io::Error> {
+    let mut file = File::create(reactor, path)?;
+    file.write_all(data).await?;
+    file.close().await?;
+    Ok(())
+}
+
+/// The preopened directory containing `path` and the path relative to
+/// it; the relative paths are in the first preopened directory.
+fn preopened(path: &str) -> Result<(Descriptor, String), ErrorCode> {
+    let mut directories = get_directories();
+
+    let found = directories
+        .iter()
+        .enumerate()
+        .filter_map(|(index, (_, name))| {
+            let name = name.trim_end_matches('/');
+            let relative = if name.is_empty() || name == "." {
+                path.strip_prefix("./")
+                    .or((!path.starts_with('/')).then_some(path))
+            } else {
+                path.strip_prefix(name)
+                    .and_then(|relative| relative.strip_prefix('/'))
+            }?;
+            Some((index, name.len(), relative.to_string()))
+        })
+        .max_by_key(|(_, len, _)| *len);
+
+    match found {
+        Some((index, _, relative)) => Ok((directories.swap_remove(index).0, relative)),
+        None if !path.starts_with('/') && !directories.is_empty() => {
+            Ok((directories.swap_remove(0).0, path.to_string()))
+        }
+        None => Err(ErrorCode::NoEntry),
+    }
+}
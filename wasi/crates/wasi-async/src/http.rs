@@ -0,0 +1,263 @@
+//! A minimal HTTP client over the `wasi:http` outgoing handler, e.g.
+//! to push the metrics and the health reports to a collector.
+use std::fmt;
+
+use tracing::{instrument, trace};
+
+use wasi::http::outgoing_handler;
+use wasi::http::types::{
+    ErrorCode, Fields, HeaderError, IncomingBody, IncomingResponse, Method, OutgoingBody,
+    OutgoingRequest, Scheme,
+};
+use wasi::io::streams::{InputStream, OutputStream, StreamError};
+
+use wasi_async_runtime::Reactor;
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidUrl(String),
+    InvalidHeader(HeaderError),
+
+    /// The request cannot be built or its body written.
+    InvalidRequest,
+
+    Http(ErrorCode),
+    Stream(crate::io::Error),
+}
+
+impl std::error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Error::InvalidUrl(url) => write!(fmt, "invalid url: {url}"),
+            Error::InvalidHeader(err) => write!(fmt, "invalid header: {err:?}"),
+            Error::InvalidRequest => write!(fmt, "invalid request"),
+            Error::Http(err) => write!(fmt, "http error: {err}"),
+            Error::Stream(err) => write!(fmt, "stream error: {err}"),
+        }
+    }
+}
+
+impl From<ErrorCode> for Error {
+    fn from(err: ErrorCode) -> Self {
+        Self::Http(err)
+    }
+}
+
+impl From<StreamError> for Error {
+    fn from(err: StreamError) -> Self {
+        Self::Stream(err.into())
+    }
+}
+
+impl From<HeaderError> for Error {
+    fn from(err: HeaderError) -> Self {
+        Self::InvalidHeader(err)
+    }
+}
+
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+/// `GET` the `url`.
+///
+/// # Errors
+/// * Error when the request cannot be sent or the response read.
+pub async fn get(reactor: &Reactor, url: &str) -> Result<Response, Error> {
+    send(reactor, Method::Get, url, &[], &[]).await
+}
+
+/// `POST` the `body` to the `url`.
+///
+/// # Errors
+/// * Error when the request cannot be sent or the response read.
+pub async fn post(
+    reactor: &Reactor,
+    url: &str,
+    content_type: &str,
+    body: &[u8],
+) -> Result<Response, Error> {
+    send(
+        reactor,
+        Method::Post,
+        url,
+        &[("content-type", content_type.as_bytes())],
+        body,
+    )
+    .await
+}
+
+/// Send a request to an `http` or `https` url, reading the whole
+/// response.
+///
+/// # Errors
+/// * Error when the request cannot be sent or the response read.
+#[instrument(skip(reactor, headers, body))]
+pub async fn send(
+    reactor: &Reactor,
+    method: Method,
+    url: &str,
+    headers: &[(&str, &[u8])],
+    body: &[u8],
+) -> Result<Response, Error> {
+    let (scheme, authority, path_with_query) = split_url(url)?;
+
+    let mut fields = headers
+        .iter()
+        .map(|(name, value)| ((*name).to_string(), value.to_vec()))
+        .collect::<Vec<_>>();
+    if !body.is_empty() {
+        fields.push((
+            "content-length".to_string(),
+            body.len().to_string().into_bytes(),
+        ));
+    }
+
+    let request = OutgoingRequest::new(Fields::from_list(&fields)?);
+    request
+        .set_method(&method)
+        .and_then(|()| request.set_scheme(Some(&scheme)))
+        .and_then(|()| request.set_authority(Some(authority)))
+        .and_then(|()| request.set_path_with_query(Some(&path_with_query)))
+        .map_err(|()| Error::InvalidRequest)?;
+
+    let outgoing_body = request.body().map_err(|()| Error::InvalidRequest)?;
+
+    // the body is sent while the request is handled
+    let response = outgoing_handler::handle(request, None)?;
+
+    {
+        let output_stream = outgoing_body.write().map_err(|()| Error::InvalidRequest)?;
+        write_all(reactor, &output_stream, body).await?;
+    }
+    OutgoingBody::finish(outgoing_body, None)?;
+
+    let response = loop {
+        match response.get() {
+            Some(Ok(response)) => break response?,
+            Some(Err(())) => return Err(Error::InvalidRequest),
+            None => {
+                let subscription = response.subscribe();
+                trace!("response subscription {subscription:?}");
+                reactor.wait_for(subscription).await;
+            }
+        }
+    };
+
+    read_response(reactor, &response).await
+}
+
+async fn read_response(reactor: &Reactor, response: &IncomingResponse) -> Result<Response, Error> {
+    let status = response.status();
+
+    let incoming_body = response.consume().map_err(|()| Error::InvalidRequest)?;
+    let body = {
+        let input_stream = incoming_body.stream().map_err(|()| Error::InvalidRequest)?;
+        read_to_end(reactor, &input_stream).await?
+    };
+    drop(IncomingBody::finish(incoming_body));
+
+    Ok(Response { status, body })
+}
+
+async fn write_all(
+    reactor: &Reactor,
+    output_stream: &OutputStream,
+    mut data: &[u8],
+) -> Result<(), StreamError> {
+    while !data.is_empty() {
+        let len = output_stream.check_write()?;
+        if len == 0 {
+            let subscription = output_stream.subscribe();
+            trace!("body output stream subscription {subscription:?}");
+            reactor.wait_for(subscription).await;
+            continue;
+        }
+
+        let len = data.len().min(len as usize);
+        output_stream.write(&data[..len])?;
+        data = &data[len..];
+    }
+
+    output_stream.flush()?;
+    while output_stream.check_write()? == 0 {
+        let subscription = output_stream.subscribe();
+        trace!("body output stream subscription {subscription:?}");
+        reactor.wait_for(subscription).await;
+    }
+
+    Ok(())
+}
+
+async fn read_to_end(
+    reactor: &Reactor,
+    input_stream: &InputStream,
+) -> Result<Vec<u8>, StreamError> {
+    let mut body = vec![];
+    loop {
+        match input_stream.read(crate::io::DEFAULT_BUF_SIZE as u64) {
+            Ok(data) if data.is_empty() => {
+                let subscription = input_stream.subscribe();
+                trace!("body input stream subscription {subscription:?}");
+                reactor.wait_for(subscription).await;
+            }
+            Ok(data) => body.extend_from_slice(&data),
+            Err(StreamError::Closed) => return Ok(body),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// The scheme, the authority and the path with the query of the url.
+fn split_url(url: &str) -> Result<(Scheme, &str, String), Error> {
+    let invalid = || Error::InvalidUrl(url.to_string());
+
+    let (scheme, rest) = url.split_once("://").ok_or_else(invalid)?;
+    let scheme = match scheme {
+        "http" => Scheme::Http,
+        "https" => Scheme::Https,
+        _ => return Err(invalid()),
+    };
+
+    let (authority, path_with_query) = match rest.find(['/', '?']) {
+        Some(index) if rest.as_bytes()[index] == b'/' => {
+            (&rest[..index], rest[index..].to_string())
+        }
+        Some(index) => (&rest[..index], format!("/{}", &rest[index..])),
+        None => (rest, "/".to_string()),
+    };
+    if authority.is_empty() {
+        return Err(invalid());
+    }
+
+    Ok((scheme, authority, path_with_query))
+}
+
+#[cfg(test)]
+mod tests {
+    use wasi::http::types::Scheme;
+
+    use super::split_url;
+
+    #[test]
+    fn test_split_url() {
+        let (scheme, authority, path) = split_url("https://collector:8080/metrics?id=1").unwrap();
+        assert!(matches!(scheme, Scheme::Https));
+        assert_eq!("collector:8080", authority);
+        assert_eq!("/metrics?id=1", path);
+
+        let (_, authority, path) = split_url("http://collector?id=1").unwrap();
+        assert_eq!("collector", authority);
+        assert_eq!("/?id=1", path);
+
+        let (_, _, path) = split_url("http://collector").unwrap();
+        assert_eq!("/", path);
+
+        assert!(split_url("ftp://collector").is_err());
+        assert!(split_url("http:///metrics").is_err());
+    }
+}
@@ -1,21 +1,114 @@
 use std::future::{self, Future};
 
-use wasi::io::streams::StreamError;
+mod buf_read;
+mod buf_reader;
+mod buf_writer;
+pub mod compat;
+mod error;
+#[cfg(test)]
+mod mock;
+
+pub use buf_read::{AsyncBufRead, AsyncBufReadExt};
+pub use buf_reader::BufReader;
+pub use buf_writer::BufWriter;
+pub use error::{Error, ErrorKind};
+
+/// The default capacity of the buffered reader and writer.
+pub const DEFAULT_BUF_SIZE: usize = 8 * 1024;
 
 pub trait AsyncRead {
-    fn read(&mut self, len: u64) -> impl Future<Output = Result<Vec<u8>, StreamError>>;
+    fn read(&mut self, len: u64) -> impl Future<Output = Result<Vec<u8>, Error>>;
 }
 
 pub trait AsyncWrite {
-    fn write(&mut self, data: &[u8]) -> impl Future<Output = Result<u64, StreamError>>;
+    fn write(&mut self, data: &[u8]) -> impl Future<Output = Result<u64, Error>>;
+
+    /// Write from the buffers in order, returning the number of bytes;
+    /// by default only the first non empty buffer is written.
+    fn write_vectored(&mut self, bufs: &[&[u8]]) -> impl Future<Output = Result<u64, Error>> {
+        async move {
+            match bufs.iter().find(|buf| !buf.is_empty()) {
+                Some(buf) => self.write(buf).await,
+                None => Ok(0),
+            }
+        }
+    }
 
-    fn flush(&mut self) -> impl Future<Output = Result<(), StreamError>>;
+    fn flush(&mut self) -> impl Future<Output = Result<(), Error>>;
 
-    fn close(&mut self) -> impl Future<Output = Result<(), StreamError>>;
+    fn close(&mut self) -> impl Future<Output = Result<(), Error>>;
 }
 
+pub trait AsyncReadExt: AsyncRead {
+    /// Fill `buffer`, failing with [`ErrorKind::Closed`] when the
+    /// stream ends before.
+    fn read_exact(&mut self, buffer: &mut [u8]) -> impl Future<Output = Result<(), Error>> {
+        async move {
+            let mut filled = 0;
+            while filled < buffer.len() {
+                let data = self.read((buffer.len() - filled) as u64).await?;
+                if data.is_empty() {
+                    return Err(ErrorKind::Closed.into());
+                }
+
+                buffer[filled..filled + data.len()].copy_from_slice(&data);
+                filled += data.len();
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Append all the data up to the end of the stream to `buffer`,
+    /// returning the number of bytes.
+    fn read_to_end(&mut self, buffer: &mut Vec<u8>) -> impl Future<Output = Result<usize, Error>> {
+        async move {
+            let mut read = 0;
+            loop {
+                match self.read(DEFAULT_BUF_SIZE as u64).await {
+                    Ok(data) if data.is_empty() => return Ok(read),
+                    Ok(data) => {
+                        buffer.extend_from_slice(&data);
+                        read += data.len();
+                    }
+                    Err(err) if err.is_closed() => return Ok(read),
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+    }
+
+    fn read_u8(&mut self) -> impl Future<Output = Result<u8, Error>> {
+        async move {
+            let mut buffer = [0; 1];
+            self.read_exact(&mut buffer).await?;
+            Ok(buffer[0])
+        }
+    }
+
+    /// Read a big endian `u16`.
+    fn read_u16(&mut self) -> impl Future<Output = Result<u16, Error>> {
+        async move {
+            let mut buffer = [0; 2];
+            self.read_exact(&mut buffer).await?;
+            Ok(u16::from_be_bytes(buffer))
+        }
+    }
+
+    /// Read a big endian `u32`.
+    fn read_u32(&mut self) -> impl Future<Output = Result<u32, Error>> {
+        async move {
+            let mut buffer = [0; 4];
+            self.read_exact(&mut buffer).await?;
+            Ok(u32::from_be_bytes(buffer))
+        }
+    }
+}
+
+impl<T: AsyncRead> AsyncReadExt for T {}
+
 pub trait AsyncWriteExt: AsyncWrite {
-    fn write_all(&mut self, mut data: &[u8]) -> impl Future<Output = Result<(), StreamError>> {
+    fn write_all(&mut self, mut data: &[u8]) -> impl Future<Output = Result<(), Error>> {
         async move {
             while !data.is_empty() {
                 let len = self.write(data).await?;
@@ -31,12 +124,41 @@ pub trait AsyncWriteExt: AsyncWrite {
             Ok(())
         }
     }
+
+    /// Write all the buffers, in order; `bufs` is left in an
+    /// unspecified state.
+    fn write_all_vectored(
+        &mut self,
+        mut bufs: &mut [&[u8]],
+    ) -> impl Future<Output = Result<(), Error>> {
+        async move {
+            loop {
+                let skip = bufs.iter().take_while(|buf| buf.is_empty()).count();
+                bufs = &mut bufs[skip..];
+                if bufs.is_empty() {
+                    return Ok(());
+                }
+
+                let mut len = self.write_vectored(bufs).await?;
+                assert!(len > 0, "write_all_vectored len zero");
+
+                for buf in bufs.iter_mut() {
+                    let n = buf.len().min(len as usize);
+                    *buf = &buf[n..];
+                    len -= n as u64;
+                    if len == 0 {
+                        break;
+                    }
+                }
+            }
+        }
+    }
 }
 
 impl<T: AsyncWrite> AsyncWriteExt for T {}
 
 impl AsyncRead for &[u8] {
-    fn read(&mut self, len: u64) -> impl Future<Output = Result<Vec<u8>, StreamError>> {
+    fn read(&mut self, len: u64) -> impl Future<Output = Result<Vec<u8>, Error>> {
         let len = len as usize;
         let len = len.min(self.len());
         let r = self[0..len].to_vec();
@@ -46,16 +168,61 @@ impl AsyncRead for &[u8] {
 }
 
 impl AsyncWrite for &mut Vec<u8> {
-    async fn write(&mut self, data: &[u8]) -> Result<u64, StreamError> {
+    async fn write(&mut self, data: &[u8]) -> Result<u64, Error> {
         self.extend_from_slice(data);
         Ok(data.len() as u64)
     }
 
-    async fn flush(&mut self) -> Result<(), StreamError> {
+    async fn write_vectored(&mut self, bufs: &[&[u8]]) -> Result<u64, Error> {
+        let mut len = 0;
+        for buf in bufs {
+            self.extend_from_slice(buf);
+            len += buf.len() as u64;
+        }
+        Ok(len)
+    }
+
+    async fn flush(&mut self) -> Result<(), Error> {
         Ok(())
     }
 
-    async fn close(&mut self) -> Result<(), StreamError> {
+    async fn close(&mut self) -> Result<(), Error> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::mock::Reader;
+    use super::{AsyncReadExt, ErrorKind, DEFAULT_BUF_SIZE};
+
+    #[test]
+    fn test_read_exact_eof() {
+        let mut buffer = [0; 4];
+
+        let mut reader = Reader::new(&[b"ab", b"c"]);
+        let err = block_on(reader.read_exact(&mut buffer)).unwrap_err();
+        assert_eq!(ErrorKind::Closed, err.kind());
+        assert_eq!(b"abc", &buffer[..3]);
+
+        let mut reader = &b"ab"[..];
+        let err = block_on(reader.read_exact(&mut buffer)).unwrap_err();
+        assert_eq!(ErrorKind::Closed, err.kind());
+    }
+
+    #[test]
+    fn test_read_to_end() {
+        let mut reader = Reader::new(&[b"ab", b"cde"]);
+        let mut buffer = b"_".to_vec();
+
+        assert_eq!(5, block_on(reader.read_to_end(&mut buffer)).unwrap());
+        assert_eq!(b"_abcde", &buffer[..]);
+        assert_eq!(vec![DEFAULT_BUF_SIZE as u64; 3], reader.reads);
+
+        let mut reader = &b"fg"[..];
+        assert_eq!(2, block_on(reader.read_to_end(&mut buffer)).unwrap());
+        assert_eq!(b"_abcdefg", &buffer[..]);
+    }
+}
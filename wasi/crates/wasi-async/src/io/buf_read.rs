@@ -0,0 +1,99 @@
+use std::future::{self, Future};
+
+use futures::{stream, Stream};
+
+use crate::io::{AsyncRead, Error};
+
+/// A reader with an inner buffer, the data can be looked at before
+/// being consumed.
+pub trait AsyncBufRead: AsyncRead {
+    /// The buffered data, read from the inner reader when empty. It
+    /// is empty at the end of the stream.
+    fn fill_buf(&mut self) -> impl Future<Output = Result<&[u8], Error>>;
+
+    /// Mark `amt` bytes of the buffered data as read.
+    fn consume(&mut self, amt: usize);
+}
+
+pub trait AsyncBufReadExt: AsyncBufRead {
+    /// Append the data up to and including `byte` to `buffer`,
+    /// returning the number of bytes, zero at the end of the stream.
+    fn read_until(
+        &mut self,
+        byte: u8,
+        buffer: &mut Vec<u8>,
+    ) -> impl Future<Output = Result<usize, Error>> {
+        async move {
+            let mut read = 0;
+            loop {
+                let available = self.fill_buf().await?;
+                if available.is_empty() {
+                    return Ok(read);
+                }
+
+                let (len, done) = match available.iter().position(|b| *b == byte) {
+                    Some(index) => (index + 1, true),
+                    None => (available.len(), false),
+                };
+                buffer.extend_from_slice(&available[..len]);
+                self.consume(len);
+                read += len;
+
+                if done {
+                    return Ok(read);
+                }
+            }
+        }
+    }
+
+    /// Append a line, with the trailing `\n`, to `line`, returning
+    /// the number of bytes, zero at the end of the stream; a line not
+    /// in UTF-8 is an [`ErrorKind::InvalidData`](crate::io::ErrorKind::InvalidData)
+    /// error.
+    fn read_line(&mut self, line: &mut String) -> impl Future<Output = Result<usize, Error>> {
+        async move {
+            let mut buffer = vec![];
+            let len = self.read_until(b'\n', &mut buffer).await?;
+            line.push_str(&String::from_utf8(buffer)?);
+            Ok(len)
+        }
+    }
+
+    /// The lines, without the trailing `\n` or `\r\n`, the stream ends
+    /// after the first error.
+    fn lines(self) -> impl Stream<Item = Result<String, Error>> + Unpin
+    where
+        Self: Sized,
+    {
+        Box::pin(stream::unfold(Some(self), |reader| async move {
+            let mut reader = reader?;
+
+            let mut line = String::new();
+            match reader.read_line(&mut line).await {
+                Ok(0) => None,
+                Ok(_) => {
+                    if line.ends_with('\n') {
+                        line.pop();
+                        if line.ends_with('\r') {
+                            line.pop();
+                        }
+                    }
+                    Some((Ok(line), Some(reader)))
+                }
+                Err(err) => Some((Err(err), None)),
+            }
+        }))
+    }
+}
+
+impl<T: AsyncBufRead> AsyncBufReadExt for T {}
+
+impl AsyncBufRead for &[u8] {
+    fn fill_buf(&mut self) -> impl Future<Output = Result<&[u8], Error>> {
+        future::ready(Ok(*self))
+    }
+
+    fn consume(&mut self, amt: usize) {
+        *self = &self[amt..];
+    }
+}
@@ -0,0 +1,131 @@
+use crate::io::{AsyncBufRead, AsyncRead, Error, DEFAULT_BUF_SIZE};
+
+/// Read from the inner reader in chunks of `capacity` bytes, so that
+/// the small reads do not cost a host call each.
+pub struct BufReader<R> {
+    inner: R,
+    buffer: Vec<u8>,
+    pos: usize,
+    capacity: usize,
+}
+
+impl<R: AsyncRead> BufReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self::with_capacity(DEFAULT_BUF_SIZE, inner)
+    }
+
+    pub fn with_capacity(capacity: usize, inner: R) -> Self {
+        Self {
+            inner,
+            buffer: Vec::new(),
+            pos: 0,
+            capacity,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// The inner reader, the buffered data is lost.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// The data read from the inner reader and not yet consumed.
+    pub fn buffer(&self) -> &[u8] {
+        &self.buffer[self.pos..]
+    }
+}
+
+impl<R: AsyncRead> AsyncRead for BufReader<R> {
+    async fn read(&mut self, len: u64) -> Result<Vec<u8>, Error> {
+        if self.pos == self.buffer.len() {
+            // a large read skips the buffer
+            if len >= self.capacity as u64 {
+                return self.inner.read(len).await;
+            }
+
+            self.buffer = self.inner.read(self.capacity as u64).await?;
+            self.pos = 0;
+        }
+
+        let len = self
+            .buffer()
+            .len()
+            .min(usize::try_from(len).unwrap_or(usize::MAX));
+        let data = self.buffer()[..len].to_vec();
+        self.pos += len;
+
+        Ok(data)
+    }
+}
+
+impl<R: AsyncRead> AsyncBufRead for BufReader<R> {
+    async fn fill_buf(&mut self) -> Result<&[u8], Error> {
+        if self.pos == self.buffer.len() {
+            self.buffer = match self.inner.read(self.capacity as u64).await {
+                Ok(data) => data,
+                Err(err) if err.is_closed() => vec![],
+                Err(err) => return Err(err),
+            };
+            self.pos = 0;
+        }
+
+        Ok(self.buffer())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.buffer.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use crate::io::mock::Reader;
+    use crate::io::{AsyncBufRead, AsyncRead};
+
+    use super::BufReader;
+
+    #[test]
+    fn test_partial_fill() {
+        let mut reader = BufReader::with_capacity(8, Reader::new(&[b"abc", b"def"]));
+
+        assert_eq!(b"abc", block_on(reader.fill_buf()).unwrap());
+        assert_eq!(b"abc", block_on(reader.fill_buf()).unwrap());
+        assert_eq!(vec![8], reader.get_ref().reads);
+    }
+
+    #[test]
+    fn test_consume() {
+        let mut reader = BufReader::with_capacity(8, Reader::new(&[b"abcdef"]));
+
+        assert_eq!(b"abcdef", block_on(reader.fill_buf()).unwrap());
+        reader.consume(2);
+        assert_eq!(b"cdef", reader.buffer());
+        assert_eq!(b"cd", &block_on(reader.read(2)).unwrap()[..]);
+
+        reader.consume(10);
+        assert!(reader.buffer().is_empty());
+        assert!(block_on(reader.fill_buf()).unwrap().is_empty());
+        assert_eq!(vec![8, 8], reader.get_ref().reads);
+    }
+
+    #[test]
+    fn test_read_larger_than_buffer() {
+        let mut reader = BufReader::with_capacity(4, Reader::new(&[b"abcdefgh", b"ij"]));
+
+        assert_eq!(b"abcdefgh", &block_on(reader.read(10)).unwrap()[..]);
+        assert!(reader.buffer().is_empty());
+
+        assert_eq!(b"i", &block_on(reader.read(1)).unwrap()[..]);
+        assert_eq!(b"j", reader.buffer());
+        assert_eq!(vec![10, 4], reader.get_ref().reads);
+    }
+}
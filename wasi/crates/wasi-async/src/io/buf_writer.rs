@@ -0,0 +1,147 @@
+use crate::io::{AsyncWrite, AsyncWriteExt, Error, DEFAULT_BUF_SIZE};
+
+/// Collect the writes up to `capacity` bytes before writing to the
+/// inner writer, the data reaches the inner writer only when the
+/// buffer is full or on `flush` and `close`.
+pub struct BufWriter<W> {
+    inner: W,
+    buffer: Vec<u8>,
+    capacity: usize,
+}
+
+impl<W: AsyncWrite> BufWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self::with_capacity(DEFAULT_BUF_SIZE, inner)
+    }
+
+    pub fn with_capacity(capacity: usize, inner: W) -> Self {
+        Self {
+            inner,
+            buffer: Vec::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// The inner writer, the buffered data is lost: `flush` first.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// The data not yet written to the inner writer.
+    pub fn buffer(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    async fn flush_buffer(&mut self) -> Result<(), Error> {
+        if !self.buffer.is_empty() {
+            self.inner.write_all(&self.buffer).await?;
+            self.buffer.clear();
+        }
+        Ok(())
+    }
+}
+
+impl<W: AsyncWrite> AsyncWrite for BufWriter<W> {
+    async fn write(&mut self, data: &[u8]) -> Result<u64, Error> {
+        if self.buffer.len() + data.len() > self.capacity {
+            self.flush_buffer().await?;
+        }
+
+        // a large write skips the buffer
+        if data.len() >= self.capacity {
+            self.inner.write(data).await
+        } else {
+            self.buffer.extend_from_slice(data);
+            Ok(data.len() as u64)
+        }
+    }
+
+    async fn write_vectored(&mut self, bufs: &[&[u8]]) -> Result<u64, Error> {
+        let total = bufs.iter().map(|buf| buf.len()).sum::<usize>();
+        if self.buffer.len() + total > self.capacity {
+            self.flush_buffer().await?;
+        }
+
+        if total >= self.capacity {
+            self.inner.write_vectored(bufs).await
+        } else {
+            for buf in bufs {
+                self.buffer.extend_from_slice(buf);
+            }
+            Ok(total as u64)
+        }
+    }
+
+    async fn flush(&mut self) -> Result<(), Error> {
+        self.flush_buffer().await?;
+        self.inner.flush().await
+    }
+
+    async fn close(&mut self) -> Result<(), Error> {
+        self.flush().await?;
+        self.inner.close().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use crate::io::mock::{Event, Writer};
+    use crate::io::AsyncWrite;
+
+    use super::BufWriter;
+
+    #[test]
+    fn test_flush() {
+        let mut writer = BufWriter::with_capacity(8, Writer::default());
+
+        assert_eq!(2, block_on(writer.write(b"ab")).unwrap());
+        assert_eq!(2, block_on(writer.write(b"cd")).unwrap());
+        assert!(writer.get_ref().events.borrow().is_empty());
+        assert_eq!(b"abcd", writer.buffer());
+
+        block_on(writer.flush()).unwrap();
+        assert!(writer.buffer().is_empty());
+        assert_eq!(
+            vec![Event::Write(b"abcd".to_vec()), Event::Flush],
+            *writer.get_ref().events.borrow()
+        );
+    }
+
+    #[test]
+    fn test_close() {
+        let mut writer = BufWriter::with_capacity(8, Writer::default());
+
+        block_on(writer.write(b"ab")).unwrap();
+        block_on(writer.close()).unwrap();
+        assert_eq!(
+            vec![Event::Write(b"ab".to_vec()), Event::Flush, Event::Close],
+            *writer.get_ref().events.borrow()
+        );
+    }
+
+    #[test]
+    fn test_large_write() {
+        let mut writer = BufWriter::with_capacity(4, Writer::default());
+
+        block_on(writer.write(b"ab")).unwrap();
+        assert_eq!(6, block_on(writer.write(b"cdefgh")).unwrap());
+        assert!(writer.buffer().is_empty());
+        assert_eq!(
+            vec![
+                Event::Write(b"ab".to_vec()),
+                Event::Write(b"cdefgh".to_vec())
+            ],
+            *writer.get_ref().events.borrow()
+        );
+    }
+}
@@ -0,0 +1,272 @@
+//! Adapters to the poll based `futures::io` traits, so that the code
+//! written against them runs over the WASI streams.
+//!
+//! The `async fn` reads and writes are driven by boxed futures owning
+//! the inner reader or writer, which is given back when they are done.
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use crate::io::{AsyncRead, AsyncWrite, Error};
+
+type Pending<T, O> = Pin<Box<dyn Future<Output = (T, Result<O, Error>)>>>;
+
+enum State<T, O> {
+    Idle(T),
+    Busy(Pending<T, O>),
+    Invalid,
+}
+
+// the inner value is never pinned, only the boxed future is
+impl<T, O> Unpin for State<T, O> {}
+
+impl<T: 'static, O> State<T, O> {
+    fn poll_with<F>(
+        &mut self,
+        cx: &mut Context,
+        start: impl FnOnce(T) -> F,
+    ) -> Poll<Result<O, Error>>
+    where
+        F: Future<Output = (T, Result<O, Error>)> + 'static,
+    {
+        if let State::Idle(_) = self {
+            let State::Idle(inner) = std::mem::replace(self, State::Invalid) else {
+                unreachable!()
+            };
+            *self = State::Busy(Box::pin(start(inner)));
+        }
+
+        self.poll_busy(cx)
+    }
+
+    /// Drive the started operation to completion.
+    fn poll_busy(&mut self, cx: &mut Context) -> Poll<Result<O, Error>> {
+        let State::Busy(future) = self else {
+            panic!("invalid state");
+        };
+
+        let (inner, result) = ready!(future.as_mut().poll(cx));
+        *self = State::Idle(inner);
+
+        Poll::Ready(result)
+    }
+
+    fn into_inner(self) -> Option<T> {
+        match self {
+            State::Idle(inner) => Some(inner),
+            _ => None,
+        }
+    }
+}
+
+fn io_error(err: Error) -> io::Error {
+    if err.is_closed() {
+        // a write on a closed stream
+        io::ErrorKind::BrokenPipe.into()
+    } else {
+        err.into()
+    }
+}
+
+/// A [`futures::io::AsyncRead`] over an [`AsyncRead`].
+pub struct CompatRead<R: AsyncRead> {
+    state: State<R, Vec<u8>>,
+
+    /// The data read for a larger buffer than the current one.
+    leftover: Vec<u8>,
+}
+
+impl<R: AsyncRead + 'static> CompatRead<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            state: State::Idle(inner),
+            leftover: vec![],
+        }
+    }
+
+    /// The inner reader, `None` in the middle of a read.
+    pub fn into_inner(self) -> Option<R> {
+        self.state.into_inner()
+    }
+}
+
+impl<R: AsyncRead + 'static> futures::io::AsyncRead for CompatRead<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        if this.leftover.is_empty() {
+            let len = buf.len() as u64;
+            this.leftover = match ready!(this.state.poll_with(cx, |mut inner| async move {
+                let result = inner.read(len).await;
+                (inner, result)
+            })) {
+                Ok(data) => data,
+                Err(err) if err.is_closed() => vec![],
+                Err(err) => return Poll::Ready(Err(io_error(err))),
+            };
+        }
+
+        let len = buf.len().min(this.leftover.len());
+        buf[..len].copy_from_slice(&this.leftover[..len]);
+        this.leftover.drain(..len);
+
+        Poll::Ready(Ok(len))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Operation {
+    Write,
+    Flush,
+    Close,
+}
+
+/// A [`futures::io::AsyncWrite`] over an [`AsyncWrite`].
+///
+/// As usual with the poll based writers, after a pending write the
+/// next call must be made with the same data; a flush or a close
+/// after it completes the write first, so that its data is sent.
+pub struct CompatWrite<W: AsyncWrite> {
+    state: State<W, u64>,
+    operation: Option<Operation>,
+}
+
+impl<W: AsyncWrite + 'static> CompatWrite<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            state: State::Idle(inner),
+            operation: None,
+        }
+    }
+
+    /// The inner writer, `None` in the middle of a write.
+    pub fn into_inner(self) -> Option<W> {
+        self.state.into_inner()
+    }
+
+    fn poll_operation<F>(
+        &mut self,
+        cx: &mut Context,
+        operation: Operation,
+        start: impl FnOnce(W) -> F,
+    ) -> Poll<io::Result<u64>>
+    where
+        F: Future<Output = (W, Result<u64, Error>)> + 'static,
+    {
+        match self.operation {
+            Some(pending) if pending != operation => {
+                let result = ready!(self.state.poll_busy(cx));
+                self.operation = None;
+                result.map_err(io_error)?;
+            }
+            _ => {}
+        }
+
+        self.operation = Some(operation);
+        let result = ready!(self.state.poll_with(cx, start));
+        self.operation = None;
+        Poll::Ready(result.map_err(io_error))
+    }
+}
+
+impl<W: AsyncWrite + 'static> futures::io::AsyncWrite for CompatWrite<W> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let data = buf.to_vec();
+        let len = ready!(self
+            .get_mut()
+            .poll_operation(cx, Operation::Write, |mut inner| {
+                async move {
+                    let result = inner.write(&data).await;
+                    (inner, result)
+                }
+            }))?;
+        Poll::Ready(Ok(usize::try_from(len).unwrap_or(usize::MAX)))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        ready!(self
+            .get_mut()
+            .poll_operation(cx, Operation::Flush, |mut inner| {
+                async move {
+                    let result = inner.flush().await.map(|()| 0);
+                    (inner, result)
+                }
+            }))?;
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        ready!(self
+            .get_mut()
+            .poll_operation(cx, Operation::Close, |mut inner| {
+                async move {
+                    let result = inner.close().await.map(|()| 0);
+                    (inner, result)
+                }
+            }))?;
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use futures::io::AsyncWrite;
+    use futures::task::noop_waker_ref;
+
+    use crate::io::mock::{Event, Writer};
+
+    use super::CompatWrite;
+
+    #[test]
+    fn test_write_then_flush() {
+        let writer = Writer::default();
+        let events = writer.events.clone();
+        let mut compat = CompatWrite::new(writer);
+        let mut cx = Context::from_waker(noop_waker_ref());
+
+        assert!(Pin::new(&mut compat)
+            .poll_write(&mut cx, b"abc")
+            .is_pending());
+        assert!(Pin::new(&mut compat).poll_flush(&mut cx).is_pending());
+        assert!(matches!(
+            Pin::new(&mut compat).poll_flush(&mut cx),
+            Poll::Ready(Ok(()))
+        ));
+
+        assert_eq!(
+            vec![Event::Write(b"abc".to_vec()), Event::Flush],
+            *events.borrow()
+        );
+    }
+
+    #[test]
+    fn test_write_then_close() {
+        let writer = Writer::default();
+        let events = writer.events.clone();
+        let mut compat = CompatWrite::new(writer);
+        let mut cx = Context::from_waker(noop_waker_ref());
+
+        assert!(Pin::new(&mut compat)
+            .poll_write(&mut cx, b"abc")
+            .is_pending());
+        assert!(Pin::new(&mut compat).poll_close(&mut cx).is_pending());
+        assert!(matches!(
+            Pin::new(&mut compat).poll_close(&mut cx),
+            Poll::Ready(Ok(()))
+        ));
+
+        assert_eq!(
+            vec![Event::Write(b"abc".to_vec()), Event::Close],
+            *events.borrow()
+        );
+        assert!(compat.into_inner().is_some());
+    }
+}
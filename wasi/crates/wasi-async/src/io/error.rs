@@ -0,0 +1,227 @@
+use std::fmt;
+use std::io;
+use std::string::FromUtf8Error;
+
+use wasi::filesystem::types::ErrorCode as FsErrorCode;
+use wasi::io::streams::StreamError;
+use wasi::sockets::network::ErrorCode as SocketErrorCode;
+#[cfg(feature = "wasip3")]
+use wasip3::sockets::ip_name_lookup::ErrorCode as LookupErrorCode;
+#[cfg(feature = "wasip3")]
+use wasip3::sockets::types::ErrorCode as P3SocketErrorCode;
+
+use crate::codec::FrameTooLong;
+use crate::time::Elapsed;
+
+/// The kind of an [`Error`], mapped to the [`std::io::ErrorKind`] ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The stream is closed.
+    Closed,
+    WouldBlock,
+    NotFound,
+    AlreadyExists,
+    PermissionDenied,
+    ConnectionRefused,
+    ConnectionReset,
+    ConnectionAborted,
+    AddrInUse,
+    AddrNotAvailable,
+    TimedOut,
+    InvalidInput,
+    InvalidData,
+    Unsupported,
+    OutOfMemory,
+    Other,
+}
+
+impl From<ErrorKind> for io::ErrorKind {
+    fn from(kind: ErrorKind) -> Self {
+        match kind {
+            ErrorKind::Closed => io::ErrorKind::UnexpectedEof,
+            ErrorKind::WouldBlock => io::ErrorKind::WouldBlock,
+            ErrorKind::NotFound => io::ErrorKind::NotFound,
+            ErrorKind::AlreadyExists => io::ErrorKind::AlreadyExists,
+            ErrorKind::PermissionDenied => io::ErrorKind::PermissionDenied,
+            ErrorKind::ConnectionRefused => io::ErrorKind::ConnectionRefused,
+            ErrorKind::ConnectionReset => io::ErrorKind::ConnectionReset,
+            ErrorKind::ConnectionAborted => io::ErrorKind::ConnectionAborted,
+            ErrorKind::AddrInUse => io::ErrorKind::AddrInUse,
+            ErrorKind::AddrNotAvailable => io::ErrorKind::AddrNotAvailable,
+            ErrorKind::TimedOut => io::ErrorKind::TimedOut,
+            ErrorKind::InvalidInput => io::ErrorKind::InvalidInput,
+            ErrorKind::InvalidData => io::ErrorKind::InvalidData,
+            ErrorKind::Unsupported => io::ErrorKind::Unsupported,
+            ErrorKind::OutOfMemory => io::ErrorKind::OutOfMemory,
+            ErrorKind::Other => io::ErrorKind::Other,
+        }
+    }
+}
+
+/// The error of the WASI operations, with a kind and the original
+/// error as the source, so that the protocol code can be written
+/// against a single error type.
+///
+/// It is `Send` and `Sync`, like [`std::io::Error`]: of the host
+/// errors that are resources only the message is kept.
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    source: Option<Box<dyn std::error::Error + Send + Sync>>,
+}
+
+impl Error {
+    /// An error of `kind` caused by `source`, an error or a message.
+    pub fn new(
+        kind: ErrorKind,
+        source: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+    ) -> Self {
+        Self {
+            kind,
+            source: Some(source.into()),
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// The end of the stream, not a failure for most readers.
+    pub fn is_closed(&self) -> bool {
+        self.kind == ErrorKind::Closed
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source
+            .as_deref()
+            .map(|source| source as &(dyn std::error::Error + 'static))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match &self.source {
+            Some(source) => write!(fmt, "{:?}: {source}", self.kind),
+            None => write!(fmt, "{:?}", self.kind),
+        }
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Self { kind, source: None }
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(err: Error) -> Self {
+        match err.source {
+            Some(source) => io::Error::new(err.kind.into(), source),
+            None => io::ErrorKind::from(err.kind).into(),
+        }
+    }
+}
+
+impl From<StreamError> for Error {
+    fn from(err: StreamError) -> Self {
+        match err {
+            StreamError::Closed => ErrorKind::Closed.into(),
+            StreamError::LastOperationFailed(err) => {
+                Self::new(ErrorKind::Other, err.to_debug_string())
+            }
+        }
+    }
+}
+
+impl From<SocketErrorCode> for Error {
+    fn from(err: SocketErrorCode) -> Self {
+        let kind = match err {
+            SocketErrorCode::WouldBlock => ErrorKind::WouldBlock,
+            SocketErrorCode::AccessDenied => ErrorKind::PermissionDenied,
+            SocketErrorCode::NotSupported => ErrorKind::Unsupported,
+            SocketErrorCode::InvalidArgument => ErrorKind::InvalidInput,
+            SocketErrorCode::OutOfMemory => ErrorKind::OutOfMemory,
+            SocketErrorCode::Timeout => ErrorKind::TimedOut,
+            SocketErrorCode::AddressInUse => ErrorKind::AddrInUse,
+            SocketErrorCode::AddressNotBindable => ErrorKind::AddrNotAvailable,
+            SocketErrorCode::ConnectionRefused => ErrorKind::ConnectionRefused,
+            SocketErrorCode::ConnectionReset => ErrorKind::ConnectionReset,
+            SocketErrorCode::ConnectionAborted => ErrorKind::ConnectionAborted,
+            SocketErrorCode::NameUnresolvable => ErrorKind::NotFound,
+            _ => ErrorKind::Other,
+        };
+        Self::new(kind, err)
+    }
+}
+
+#[cfg(feature = "wasip3")]
+impl From<P3SocketErrorCode> for Error {
+    fn from(err: P3SocketErrorCode) -> Self {
+        let kind = match err {
+            P3SocketErrorCode::AccessDenied => ErrorKind::PermissionDenied,
+            P3SocketErrorCode::NotSupported => ErrorKind::Unsupported,
+            P3SocketErrorCode::InvalidArgument => ErrorKind::InvalidInput,
+            P3SocketErrorCode::OutOfMemory => ErrorKind::OutOfMemory,
+            P3SocketErrorCode::Timeout => ErrorKind::TimedOut,
+            P3SocketErrorCode::AddressInUse => ErrorKind::AddrInUse,
+            P3SocketErrorCode::AddressNotBindable => ErrorKind::AddrNotAvailable,
+            P3SocketErrorCode::ConnectionRefused => ErrorKind::ConnectionRefused,
+            P3SocketErrorCode::ConnectionReset => ErrorKind::ConnectionReset,
+            P3SocketErrorCode::ConnectionAborted => ErrorKind::ConnectionAborted,
+            _ => ErrorKind::Other,
+        };
+        Self::new(kind, err)
+    }
+}
+
+#[cfg(feature = "wasip3")]
+impl From<LookupErrorCode> for Error {
+    fn from(err: LookupErrorCode) -> Self {
+        let kind = match err {
+            LookupErrorCode::AccessDenied => ErrorKind::PermissionDenied,
+            LookupErrorCode::InvalidArgument => ErrorKind::InvalidInput,
+            LookupErrorCode::NameUnresolvable => ErrorKind::NotFound,
+            _ => ErrorKind::Other,
+        };
+        Self::new(kind, err)
+    }
+}
+
+impl From<FsErrorCode> for Error {
+    fn from(err: FsErrorCode) -> Self {
+        let kind = match err {
+            FsErrorCode::WouldBlock => ErrorKind::WouldBlock,
+            FsErrorCode::Access | FsErrorCode::NotPermitted | FsErrorCode::ReadOnly => {
+                ErrorKind::PermissionDenied
+            }
+            FsErrorCode::Exist => ErrorKind::AlreadyExists,
+            FsErrorCode::NoEntry => ErrorKind::NotFound,
+            FsErrorCode::Invalid => ErrorKind::InvalidInput,
+            FsErrorCode::InsufficientMemory => ErrorKind::OutOfMemory,
+            FsErrorCode::Unsupported => ErrorKind::Unsupported,
+            _ => ErrorKind::Other,
+        };
+        Self::new(kind, err)
+    }
+}
+
+impl From<FromUtf8Error> for Error {
+    fn from(err: FromUtf8Error) -> Self {
+        Self::new(ErrorKind::InvalidData, err)
+    }
+}
+
+impl From<FrameTooLong> for Error {
+    fn from(err: FrameTooLong) -> Self {
+        Self::new(ErrorKind::InvalidData, err)
+    }
+}
+
+impl From<Elapsed> for Error {
+    fn from(err: Elapsed) -> Self {
+        Self::new(ErrorKind::TimedOut, err)
+    }
+}
@@ -0,0 +1,86 @@
+//! In memory readers and writers for the tests.
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::poll_fn;
+use std::rc::Rc;
+use std::task::Poll;
+
+use crate::io::{AsyncRead, AsyncWrite, Error, ErrorKind};
+
+/// A reader of the chunks in order, at most the length asked at a
+/// time, then closed; the lengths asked are logged.
+#[derive(Default)]
+pub(crate) struct Reader {
+    chunks: VecDeque<Vec<u8>>,
+    pub(crate) reads: Vec<u64>,
+}
+
+impl Reader {
+    pub(crate) fn new(chunks: &[&[u8]]) -> Self {
+        Self {
+            chunks: chunks.iter().map(|chunk| chunk.to_vec()).collect(),
+            reads: vec![],
+        }
+    }
+}
+
+impl AsyncRead for Reader {
+    async fn read(&mut self, len: u64) -> Result<Vec<u8>, Error> {
+        self.reads.push(len);
+
+        let mut chunk = self.chunks.pop_front().ok_or(ErrorKind::Closed)?;
+        if chunk.len() as u64 > len {
+            self.chunks.push_front(chunk.split_off(len as usize));
+        }
+
+        Ok(chunk)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Event {
+    Write(Vec<u8>),
+    Flush,
+    Close,
+}
+
+/// A writer logging the operations, each one pending once before it
+/// is done.
+#[derive(Default)]
+pub(crate) struct Writer {
+    pub(crate) events: Rc<RefCell<Vec<Event>>>,
+}
+
+async fn yield_now() {
+    let mut yielded = false;
+    poll_fn(|cx| {
+        if yielded {
+            Poll::Ready(())
+        } else {
+            yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    })
+    .await;
+}
+
+impl AsyncWrite for Writer {
+    async fn write(&mut self, data: &[u8]) -> Result<u64, Error> {
+        yield_now().await;
+        self.events.borrow_mut().push(Event::Write(data.to_vec()));
+        Ok(data.len() as u64)
+    }
+
+    async fn flush(&mut self) -> Result<(), Error> {
+        yield_now().await;
+        self.events.borrow_mut().push(Event::Flush);
+        Ok(())
+    }
+
+    async fn close(&mut self) -> Result<(), Error> {
+        yield_now().await;
+        self.events.borrow_mut().push(Event::Close);
+        Ok(())
+    }
+}
@@ -2,6 +2,18 @@
 mod bindings;
 
 pub mod codec;
+#[cfg(not(feature = "wasip3"))]
+pub mod fs;
+#[cfg(not(feature = "wasip3"))]
+pub mod http;
 pub mod io;
 pub mod net;
+pub mod select;
 pub mod time;
+
+pub use wasi_async_macros::test;
+pub use wasi_async_runtime::sync;
+pub use wasi_async_runtime::{JoinHandle, JoinSet};
+
+#[doc(hidden)]
+pub use wasi_async_runtime as __runtime;
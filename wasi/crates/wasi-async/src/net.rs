@@ -1,25 +1,164 @@
-use wasi::sockets::network::{IpAddressFamily, IpSocketAddress};
-
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6};
+
+#[cfg(not(feature = "wasip3"))]
+use wasi::sockets::instance_network::instance_network;
+#[cfg(not(feature = "wasip3"))]
+use wasi::sockets::ip_name_lookup::resolve_addresses;
+#[cfg(not(feature = "wasip3"))]
+use wasi::sockets::network::ErrorCode;
+#[cfg(not(feature = "wasip3"))]
+pub use wasi::sockets::network::{
+    IpAddress, IpAddressFamily, IpSocketAddress, Ipv4SocketAddress, Ipv6SocketAddress,
+};
+#[cfg(feature = "wasip3")]
+use wasip3::sockets::ip_name_lookup::resolve_addresses;
+#[cfg(feature = "wasip3")]
+pub use wasip3::sockets::types::{
+    IpAddress, IpAddressFamily, IpSocketAddress, Ipv4SocketAddress, Ipv6SocketAddress,
+};
+
+use wasi_async_runtime::Reactor;
+
+use tracing::instrument;
+#[cfg(not(feature = "wasip3"))]
+use tracing::trace;
+
+use crate::io;
+
+#[cfg(feature = "wasip3")]
+mod p3;
+#[cfg(not(feature = "wasip3"))]
 pub mod tcp;
+#[cfg(not(feature = "wasip3"))]
 pub mod udp;
 
+#[cfg(feature = "wasip3")]
+pub use p3::{tcp, udp};
+
 pub use tcp::{TcpListener, TcpStream};
 pub use udp::UdpSocket;
 
-pub struct LocalSocketAddress(u16);
+/// The address a socket is bound to, with the port assigned by the
+/// host when bound to the port zero.
+pub struct LocalSocketAddress(IpSocketAddress);
 
 impl LocalSocketAddress {
     pub fn port(&self) -> u16 {
+        match self.0 {
+            IpSocketAddress::Ipv4(Ipv4SocketAddress { port, .. })
+            | IpSocketAddress::Ipv6(Ipv6SocketAddress { port, .. }) => port,
+        }
+    }
+
+    pub fn address(&self) -> IpSocketAddress {
         self.0
     }
 }
 
+/// The std socket address, e.g. to be used as a map key.
+pub fn std_socket_addr(address: IpSocketAddress) -> SocketAddr {
+    match address {
+        IpSocketAddress::Ipv4(Ipv4SocketAddress {
+            port,
+            address: (a, b, c, d),
+        }) => SocketAddr::from((Ipv4Addr::new(a, b, c, d), port)),
+        IpSocketAddress::Ipv6(Ipv6SocketAddress {
+            port,
+            address: (a, b, c, d, e, f, g, h),
+            flow_info,
+            scope_id,
+        }) => SocketAddr::V6(SocketAddrV6::new(
+            Ipv6Addr::new(a, b, c, d, e, f, g, h),
+            port,
+            flow_info,
+            scope_id,
+        )),
+    }
+}
+
 #[allow(private_bounds)]
 pub trait ToSocketAddrs: sealed::ToSocketAddrs {}
 
 impl ToSocketAddrs for IpSocketAddress {}
 impl ToSocketAddrs for String {}
 
+/// Resolve a `host:port` name to all its addresses, IPv4 and IPv6.
+///
+/// # Errors
+/// * Error when the name is invalid or cannot be resolved.
+pub async fn lookup_host(reactor: &Reactor, host: &str) -> Result<Vec<IpSocketAddress>, io::Error> {
+    let (host, port) = split_host_port(host)?;
+
+    let addresses = resolve(reactor, host).await?;
+    if addresses.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no address for {host}"),
+        ));
+    }
+
+    Ok(addresses
+        .into_iter()
+        .map(|address| match address {
+            IpAddress::Ipv4(address) => IpSocketAddress::Ipv4(Ipv4SocketAddress { address, port }),
+            IpAddress::Ipv6(address) => IpSocketAddress::Ipv6(Ipv6SocketAddress {
+                address,
+                port,
+                flow_info: 0,
+                scope_id: 0,
+            }),
+        })
+        .collect())
+}
+
+/// Split `host:port`, an IPv6 address is in brackets, `[::1]:port`,
+/// and is returned without them.
+pub(crate) fn split_host_port(address: &str) -> Result<(&str, u16), io::Error> {
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid address {address}"),
+        )
+    };
+
+    let (host, port) = address.rsplit_once(':').ok_or_else(invalid)?;
+    let host = match host.strip_prefix('[') {
+        Some(host) => host.strip_suffix(']').ok_or_else(invalid)?,
+        None if host.contains(':') => return Err(invalid()),
+        None => host,
+    };
+    let port = port.parse().map_err(|_| invalid())?;
+    Ok((host, port))
+}
+
+/// All the addresses of a name, waiting on the lookup pollable.
+#[cfg(not(feature = "wasip3"))]
+#[instrument(skip(reactor))]
+pub(crate) async fn resolve(reactor: &Reactor, host: &str) -> Result<Vec<IpAddress>, io::Error> {
+    let addresses = resolve_addresses(&instance_network(), host)?;
+
+    let mut resolved = vec![];
+    loop {
+        match addresses.resolve_next_address() {
+            Err(ErrorCode::WouldBlock) => {
+                let subscription = addresses.subscribe();
+                trace!("addresses subscribe {subscription:?}");
+                reactor.wait_for(subscription).await;
+            }
+            Ok(Some(address)) => resolved.push(address),
+            Ok(None) => return Ok(resolved),
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+/// All the addresses of a name, the lookup is awaited on the host.
+#[cfg(feature = "wasip3")]
+#[instrument(skip(_reactor))]
+pub(crate) async fn resolve(_reactor: &Reactor, host: &str) -> Result<Vec<IpAddress>, io::Error> {
+    Ok(resolve_addresses(host.to_string()).await?)
+}
+
 pub(crate) fn ip_address_family(socket_address: &IpSocketAddress) -> IpAddressFamily {
     match socket_address {
         IpSocketAddress::Ipv4(..) => IpAddressFamily::Ipv4,
@@ -30,65 +169,65 @@ pub(crate) fn ip_address_family(socket_address: &IpSocketAddress) -> IpAddressFa
 pub(crate) mod sealed {
     use std::future::Future;
 
-    use wasi::sockets::ip_name_lookup::resolve_addresses;
-    use wasi::sockets::network::{self, IpAddress, IpSocketAddress, Ipv4SocketAddress, Network};
-
     use wasi_async_runtime::Reactor;
 
-    use tracing::{instrument, trace, warn};
+    use tracing::{instrument, warn};
+
+    use crate::io;
+
+    use super::{IpAddress, IpSocketAddress, Ipv4SocketAddress};
 
     #[doc(hidden)]
     pub(crate) trait ToSocketAddrs {
         fn to_socket_addr(
             &self,
             reactor: &Reactor,
-            network: &Network,
-        ) -> impl Future<Output = Result<IpSocketAddress, network::ErrorCode>>;
+        ) -> impl Future<Output = Result<IpSocketAddress, io::Error>>;
     }
 
     impl ToSocketAddrs for IpSocketAddress {
-        async fn to_socket_addr(
-            &self,
-            _: &Reactor,
-            _: &Network,
-        ) -> Result<IpSocketAddress, network::ErrorCode> {
+        async fn to_socket_addr(&self, _: &Reactor) -> Result<IpSocketAddress, io::Error> {
             Ok(*self)
         }
     }
 
     impl ToSocketAddrs for String {
         #[instrument(skip_all)]
-        async fn to_socket_addr(
-            &self,
-            reactor: &Reactor,
-            network: &Network,
-        ) -> Result<IpSocketAddress, network::ErrorCode> {
-            let (address, port) = self
-                .split_once(':')
-                .ok_or(network::ErrorCode::InvalidArgument)?;
-
-            let port = port
-                .parse()
-                .map_err(|_| network::ErrorCode::InvalidArgument)?;
-
-            let addresses = resolve_addresses(network, address)?;
-            loop {
-                match addresses.resolve_next_address() {
-                    Err(network::ErrorCode::WouldBlock) => {
-                        let subscription = addresses.subscribe();
-                        trace!("addresses subscribe {subscription:?}");
-                        reactor.wait_for(subscription).await;
-                    }
-                    Ok(Some(IpAddress::Ipv4(address))) => {
-                        return Ok(IpSocketAddress::Ipv4(Ipv4SocketAddress { address, port }))
+        async fn to_socket_addr(&self, reactor: &Reactor) -> Result<IpSocketAddress, io::Error> {
+            let (address, port) = super::split_host_port(self)?;
+
+            for address in super::resolve(reactor, address).await? {
+                match address {
+                    IpAddress::Ipv4(address) => {
+                        return Ok(IpSocketAddress::Ipv4(Ipv4SocketAddress { address, port }));
                     }
-                    Ok(Some(IpAddress::Ipv6(addr))) => {
-                        warn!("ignoring ipv6 address {addr:?}");
+                    IpAddress::Ipv6(address) => {
+                        warn!("ignoring ipv6 address {address:?}");
                     }
-                    Ok(None) => return Err(network::ErrorCode::NameUnresolvable),
-                    Err(err) => return Err(err),
                 }
             }
+
+            Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no ipv4 address for {self}"),
+            ))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::split_host_port;
+
+    #[test]
+    fn test_split_host_port() {
+        assert_eq!(("127.0.0.1", 80), split_host_port("127.0.0.1:80").unwrap());
+        assert_eq!(("localhost", 80), split_host_port("localhost:80").unwrap());
+        assert_eq!(("::1", 80), split_host_port("[::1]:80").unwrap());
+
+        assert!(split_host_port("::1:80").is_err());
+        assert!(split_host_port("[::1:80").is_err());
+        assert!(split_host_port("localhost").is_err());
+        assert!(split_host_port("localhost:http").is_err());
+    }
+}
@@ -0,0 +1,5 @@
+//! The sockets of WASI 0.3: the data flows through the streams of the
+//! component model and the operations are awaited on the host, with
+//! the same API as the WASI 0.2 ones.
+pub mod tcp;
+pub mod udp;
@@ -0,0 +1,521 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::{stream, Stream};
+
+use tracing::{instrument, trace};
+
+use wasip3::sockets::types::{ErrorCode, IpSocketAddress, TcpSocket};
+use wasip3::wit_bindgen::{FutureReader, StreamRead, StreamReader, StreamResult, StreamWriter};
+use wasip3::wit_stream;
+
+use wasi_async_runtime::sync::{oneshot, Mutex};
+use wasi_async_runtime::{JoinHandle, Reactor};
+
+use crate::io::{self, AsyncRead, AsyncWrite, AsyncWriteExt};
+use crate::net::{ip_address_family, LocalSocketAddress, ToSocketAddrs};
+use crate::time;
+
+/// The largest read, its buffer is allocated up front.
+const MAX_READ: u64 = 64 * 1024;
+
+/// The socket options, mapped to the `wasi:sockets/types` ones; there
+/// is no no-delay option, the hosts already disable the Nagle
+/// algorithm.
+macro_rules! socket_options {
+    ($socket:ident) => {
+        pub fn keep_alive(&self) -> Result<bool, io::Error> {
+            Ok(self.$socket()?.get_keep_alive_enabled()?)
+        }
+
+        pub fn set_keep_alive(&self, enabled: bool) -> Result<(), io::Error> {
+            Ok(self.$socket()?.set_keep_alive_enabled(enabled)?)
+        }
+
+        /// The idle time before the first keep-alive probe.
+        pub fn keep_alive_idle_time(&self) -> Result<std::time::Duration, io::Error> {
+            Ok(std::time::Duration::from_nanos(
+                self.$socket()?.get_keep_alive_idle_time()?,
+            ))
+        }
+
+        pub fn set_keep_alive_idle_time(&self, time: std::time::Duration) -> Result<(), io::Error> {
+            Ok(self
+                .$socket()?
+                .set_keep_alive_idle_time(u64::try_from(time.as_nanos()).unwrap_or(u64::MAX))?)
+        }
+
+        pub fn send_buffer_size(&self) -> Result<u64, io::Error> {
+            Ok(self.$socket()?.get_send_buffer_size()?)
+        }
+
+        /// A hint, the host may round or clamp the size.
+        pub fn set_send_buffer_size(&self, size: u64) -> Result<(), io::Error> {
+            Ok(self.$socket()?.set_send_buffer_size(size)?)
+        }
+
+        pub fn receive_buffer_size(&self) -> Result<u64, io::Error> {
+            Ok(self.$socket()?.get_receive_buffer_size()?)
+        }
+
+        /// A hint, the host may round or clamp the size.
+        pub fn set_receive_buffer_size(&self, size: u64) -> Result<(), io::Error> {
+            Ok(self.$socket()?.set_receive_buffer_size(size)?)
+        }
+    };
+}
+
+pub struct TcpListener {
+    reactor: Reactor,
+    socket: TcpSocket,
+    incoming: Mutex<StreamReader<TcpSocket>>,
+}
+
+impl TcpListener {
+    /// Bind and listen; with the port zero the host assigns a free
+    /// port, see [`local_addr`](Self::local_addr). There is no address
+    /// reuse option in `wasi:sockets`, the hosts enable it on the
+    /// listeners.
+    #[instrument(skip_all)]
+    pub async fn bind(reactor: Reactor, address: impl ToSocketAddrs) -> Result<Self, io::Error> {
+        Self::bind_with_backlog(reactor, address, None).await
+    }
+
+    /// Bind with the given size of the queue of the pending
+    /// connections, the host default when `None`.
+    #[instrument(skip_all)]
+    pub async fn bind_with_backlog(
+        reactor: Reactor,
+        address: impl ToSocketAddrs,
+        backlog: Option<u64>,
+    ) -> Result<Self, io::Error> {
+        let socket_address = address.to_socket_addr(&reactor).await?;
+
+        let socket = TcpSocket::create(ip_address_family(&socket_address))?;
+        socket.bind(socket_address)?;
+
+        if let Some(backlog) = backlog {
+            socket.set_listen_backlog_size(backlog)?;
+        }
+
+        let incoming = Mutex::new(socket.listen()?);
+
+        Ok(Self {
+            reactor,
+            socket,
+            incoming,
+        })
+    }
+
+    /// The accepted connections, forever.
+    pub fn into_stream(
+        self,
+    ) -> impl Stream<Item = Result<(TcpStream, IpSocketAddress), io::Error>> + Unpin {
+        Box::pin(stream::unfold(self, |listener| async move {
+            let accepted = listener.accept().await;
+            Some((accepted, listener))
+        }))
+    }
+
+    /// The accepted connections, forever, borrowing the listener.
+    pub fn incoming(
+        &self,
+    ) -> impl Stream<Item = Result<(TcpStream, IpSocketAddress), io::Error>> + Unpin + '_ {
+        Box::pin(stream::unfold((), move |()| async move {
+            Some((self.accept().await, ()))
+        }))
+    }
+
+    #[instrument(skip_all)]
+    pub async fn accept(&self) -> Result<(TcpStream, IpSocketAddress), io::Error> {
+        let socket = self
+            .incoming
+            .lock()
+            .await
+            .next()
+            .await
+            .ok_or(io::ErrorKind::Closed)?;
+
+        let address = socket.get_remote_address()?;
+        trace!("accepted {address:?}");
+
+        Ok((TcpStream::new(self.reactor.clone(), socket), address))
+    }
+
+    pub fn local_addr(&self) -> Result<LocalSocketAddress, io::Error> {
+        Ok(LocalSocketAddress(self.socket.get_local_address()?))
+    }
+
+    // The accepted connections inherit the options of the listener.
+    socket_options!(socket);
+
+    fn socket(&self) -> Result<&TcpSocket, ErrorCode> {
+        Ok(&self.socket)
+    }
+}
+
+/// A read of the receive stream that, when dropped before completing,
+/// keeps what was already received, so that a read losing a race,
+/// e.g. to a timeout, loses no data.
+struct Read<'a> {
+    read: Pin<Box<StreamRead<'a, u8>>>,
+    done: bool,
+    pending: &'a mut Vec<u8>,
+}
+
+impl Future for Read<'_> {
+    type Output = (StreamResult, Vec<u8>);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let poll = this.read.as_mut().poll(cx);
+        this.done = poll.is_ready();
+        poll
+    }
+}
+
+impl Drop for Read<'_> {
+    fn drop(&mut self) {
+        if !self.done {
+            let (_, data) = self.read.as_mut().cancel();
+            self.pending.extend(data);
+        }
+    }
+}
+
+/// The receiving side of a connection: the stream of the data and the
+/// result of the receive, awaited at the end of the stream.
+struct Receiver {
+    stream: StreamReader<u8>,
+    result: Option<FutureReader<Result<(), ErrorCode>>>,
+
+    /// The data of a cancelled read.
+    pending: Vec<u8>,
+}
+
+impl Receiver {
+    async fn read(&mut self, len: u64) -> Result<Vec<u8>, io::Error> {
+        if !self.pending.is_empty() || len == 0 {
+            let len = self.pending.len().min(len as usize);
+            return Ok(self.pending.drain(..len).collect());
+        }
+
+        loop {
+            let read = Read {
+                read: Box::pin(
+                    self.stream
+                        .read(Vec::with_capacity(len.min(MAX_READ) as usize)),
+                ),
+                done: false,
+                pending: &mut self.pending,
+            };
+            match read.await {
+                (StreamResult::Complete(_), data) if !data.is_empty() => return Ok(data),
+                (StreamResult::Complete(_), _) => {}
+                (StreamResult::Dropped | StreamResult::Cancelled, _) => {
+                    if let Some(result) = self.result.take() {
+                        result.await?;
+                    }
+                    return Err(io::ErrorKind::Closed.into());
+                }
+            }
+        }
+    }
+}
+
+/// The sending side of a connection: the data is written to a stream
+/// that a spawned task hands over to the host, the result of the send
+/// is reported at the end of the stream.
+struct Sender {
+    stream: Option<StreamWriter<u8>>,
+    result: Option<oneshot::Receiver<Result<(), ErrorCode>>>,
+    task: JoinHandle,
+}
+
+impl Sender {
+    fn new(reactor: &Reactor, socket: Rc<TcpSocket>) -> Self {
+        let (stream, data) = wit_stream::new::<u8>();
+        let (result_sender, result) = oneshot::channel();
+        let task = reactor.spawn(async move {
+            result_sender.send(socket.send(data).await).ok();
+        });
+
+        Self {
+            stream: Some(stream),
+            result: Some(result),
+            task,
+        }
+    }
+
+    /// Not cancel safe, a dropped write may have sent part of the data.
+    async fn write(&mut self, data: &[u8]) -> Result<u64, io::Error> {
+        let Some(stream) = &mut self.stream else {
+            return Err(io::ErrorKind::Closed.into());
+        };
+
+        if data.is_empty() {
+            return Ok(0);
+        }
+
+        match stream.write(data.to_vec()).await {
+            (StreamResult::Complete(len), _) => Ok(len as u64),
+            (StreamResult::Dropped | StreamResult::Cancelled, _) => {
+                self.stream = None;
+                self.result().await?;
+                Err(io::ErrorKind::Closed.into())
+            }
+        }
+    }
+
+    /// End the stream, the host sends the data left and shuts down the
+    /// write side.
+    async fn close(&mut self) -> Result<(), io::Error> {
+        self.stream = None;
+        self.result().await
+    }
+
+    /// The result of the send, once; a task dropped by a shutdown of
+    /// the reactor is not an error.
+    async fn result(&mut self) -> Result<(), io::Error> {
+        match self.result.take() {
+            Some(result) => match result.await {
+                Ok(result) => Ok(result?),
+                Err(_) => Ok(()),
+            },
+            None => Ok(()),
+        }
+    }
+}
+
+pub struct TcpStream {
+    reactor: Reactor,
+    // the streams are dropped before the socket
+    receiver: Receiver,
+    sender: Sender,
+    socket: Rc<TcpSocket>,
+}
+
+impl TcpStream {
+    /// Start the receive and the send of a connected socket.
+    fn new(reactor: Reactor, socket: TcpSocket) -> Self {
+        let socket = Rc::new(socket);
+
+        let (stream, result) = socket.receive();
+        let receiver = Receiver {
+            stream,
+            result: Some(result),
+            pending: Vec::new(),
+        };
+        let sender = Sender::new(&reactor, socket.clone());
+
+        Self {
+            reactor,
+            receiver,
+            sender,
+            socket,
+        }
+    }
+
+    /// Connect to `remote_address`; dropping the future aborts the
+    /// connection attempt, the socket is released.
+    #[instrument(skip_all)]
+    pub async fn connect(
+        reactor: Reactor,
+        remote_address: impl ToSocketAddrs,
+    ) -> Result<Self, io::Error> {
+        let socket_address = remote_address.to_socket_addr(&reactor).await?;
+
+        let socket = TcpSocket::create(ip_address_family(&socket_address))?;
+        socket.connect(socket_address).await?;
+
+        Ok(Self::new(reactor, socket))
+    }
+
+    /// Connect to `remote_address` giving up after `duration`, the
+    /// name resolution included.
+    ///
+    /// # Errors
+    /// * An [`ErrorKind::TimedOut`](io::ErrorKind::TimedOut) error when
+    ///   the time is over, the connection attempt is aborted.
+    pub async fn connect_timeout(
+        reactor: Reactor,
+        remote_address: impl ToSocketAddrs,
+        duration: Duration,
+    ) -> Result<Self, io::Error> {
+        time::timeout(
+            reactor.clone(),
+            duration,
+            Self::connect(reactor, remote_address),
+        )
+        .await
+        .unwrap_or_else(|elapsed| Err(elapsed.into()))
+    }
+
+    /// Split into independently owned halves, that can be moved to
+    /// different spawned tasks; dropping a half shuts down its
+    /// direction of the connection.
+    pub fn into_split(self) -> (OwnedReadHalf, OwnedWriteHalf) {
+        let Self {
+            receiver,
+            sender,
+            socket,
+            ..
+        } = self;
+
+        let read = OwnedReadHalf {
+            receiver,
+            _socket: socket.clone(),
+        };
+        let write = OwnedWriteHalf {
+            sender,
+            _socket: socket,
+        };
+        (read, write)
+    }
+
+    /// Split into halves borrowing the stream, to be used in the same
+    /// task.
+    pub fn split(&mut self) -> (ReadHalf<'_>, WriteHalf<'_>) {
+        (ReadHalf(&mut self.receiver), WriteHalf(&mut self.sender))
+    }
+
+    socket_options!(socket);
+
+    fn socket(&self) -> Result<&TcpSocket, ErrorCode> {
+        Ok(&self.socket)
+    }
+
+    /// Close gracefully: end the output, the host sends what is left
+    /// and shuts down the write side, and, with a `linger` time, wait
+    /// up to it for the end of stream of the peer, discarding what it
+    /// still sends; then the streams and the socket are released. A
+    /// `linger` time running out is not an error.
+    ///
+    /// # Errors
+    /// * Error when the output cannot be sent.
+    #[instrument(skip_all)]
+    pub async fn close_gracefully(mut self, linger: Option<Duration>) -> Result<(), io::Error> {
+        let reactor = self.reactor.clone();
+
+        let (mut read, mut write) = self.split();
+        write.close().await?;
+
+        if let Some(linger) = linger {
+            let drain = async {
+                loop {
+                    match read.read(io::DEFAULT_BUF_SIZE as u64).await {
+                        Ok(_) => {}
+                        Err(err) if err.is_closed() => return Ok(()),
+                        Err(err) => return Err(err),
+                    }
+                }
+            };
+            match time::timeout(reactor, linger, drain).await {
+                Ok(result) => result?,
+                Err(_) => trace!("linger time elapsed"),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Shut down both directions right away, the pending output can be
+    /// lost; see [`TcpStream::close_gracefully`].
+    pub async fn close(self) -> Result<(), io::Error> {
+        self.sender.task.abort();
+        Ok(())
+    }
+}
+
+pub struct ReadHalf<'a>(&'a mut Receiver);
+
+impl AsyncRead for ReadHalf<'_> {
+    #[instrument(skip_all)]
+    async fn read(&mut self, len: u64) -> Result<Vec<u8>, io::Error> {
+        self.0.read(len).await
+    }
+}
+
+pub struct WriteHalf<'a>(&'a mut Sender);
+
+impl AsyncWrite for WriteHalf<'_> {
+    #[instrument(skip_all)]
+    async fn write(&mut self, data: &[u8]) -> Result<u64, io::Error> {
+        self.0.write(data).await
+    }
+
+    /// The data is handed over to the host by the write itself.
+    async fn flush(&mut self) -> Result<(), io::Error> {
+        Ok(())
+    }
+
+    /// End the output, the peer reads the end of the stream once the
+    /// data left is sent.
+    async fn close(&mut self) -> Result<(), io::Error> {
+        self.0.close().await
+    }
+}
+
+impl WriteHalf<'_> {
+    #[instrument(skip_all)]
+    pub async fn splice(&mut self, read: &mut ReadHalf<'_>, len: u64) -> Result<u64, io::Error> {
+        if len == 0 {
+            return Ok(0);
+        }
+
+        let data = read.read(len).await?;
+        self.write_all(&data).await?;
+        Ok(data.len() as u64)
+    }
+}
+
+pub struct OwnedReadHalf {
+    receiver: Receiver,
+    _socket: Rc<TcpSocket>,
+}
+
+impl AsyncRead for OwnedReadHalf {
+    #[instrument(skip_all)]
+    async fn read(&mut self, len: u64) -> Result<Vec<u8>, io::Error> {
+        self.receiver.read(len).await
+    }
+}
+
+pub struct OwnedWriteHalf {
+    sender: Sender,
+    _socket: Rc<TcpSocket>,
+}
+
+impl AsyncWrite for OwnedWriteHalf {
+    #[instrument(skip_all)]
+    async fn write(&mut self, data: &[u8]) -> Result<u64, io::Error> {
+        self.sender.write(data).await
+    }
+
+    /// The data is handed over to the host by the write itself.
+    async fn flush(&mut self) -> Result<(), io::Error> {
+        Ok(())
+    }
+
+    /// End the output, the peer reads the end of the stream once the
+    /// data left is sent.
+    async fn close(&mut self) -> Result<(), io::Error> {
+        self.sender.close().await
+    }
+}
+
+impl OwnedWriteHalf {
+    #[instrument(skip_all)]
+    pub async fn splice(&mut self, read: &mut OwnedReadHalf, len: u64) -> Result<u64, io::Error> {
+        if len == 0 {
+            return Ok(0);
+        }
+
+        let data = read.read(len).await?;
+        self.write_all(&data).await?;
+        Ok(data.len() as u64)
+    }
+}
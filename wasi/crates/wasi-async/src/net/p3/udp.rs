@@ -0,0 +1,70 @@
+use wasip3::sockets::types::{IpSocketAddress, UdpSocket as WasiUdpSocket};
+
+use wasi_async_runtime::Reactor;
+
+use tracing::{instrument, trace};
+
+use crate::io;
+use crate::net::{ip_address_family, LocalSocketAddress, ToSocketAddrs};
+
+pub struct UdpSocket {
+    reactor: Reactor,
+    socket: WasiUdpSocket,
+}
+
+impl UdpSocket {
+    /// Bind; with the port zero the host assigns a free port, see
+    /// [`local_addr`](Self::local_addr).
+    #[instrument(skip_all)]
+    pub async fn bind(reactor: Reactor, address: impl ToSocketAddrs) -> Result<Self, io::Error> {
+        let socket_address = address.to_socket_addr(&reactor).await?;
+
+        let socket = WasiUdpSocket::create(ip_address_family(&socket_address))?;
+        socket.bind(socket_address)?;
+
+        Ok(Self { reactor, socket })
+    }
+
+    #[instrument(skip_all)]
+    pub async fn connect(&self, address: impl ToSocketAddrs) -> Result<(), io::Error> {
+        let socket_address = address.to_socket_addr(&self.reactor).await?;
+
+        Ok(self.socket.connect(socket_address)?)
+    }
+
+    pub fn local_addr(&self) -> Result<LocalSocketAddress, io::Error> {
+        Ok(LocalSocketAddress(self.socket.get_local_address()?))
+    }
+
+    #[instrument(skip_all)]
+    pub async fn send(&self, data: Vec<u8>) -> Result<usize, io::Error> {
+        trace!("send {}", data.len());
+        let len = data.len();
+        self.socket.send(data, None).await?;
+        Ok(len)
+    }
+
+    #[instrument(skip_all)]
+    pub async fn send_to(
+        &self,
+        data: Vec<u8>,
+        address: impl ToSocketAddrs,
+    ) -> Result<usize, io::Error> {
+        let socket_address = address.to_socket_addr(&self.reactor).await?;
+
+        let len = data.len();
+        self.socket.send(data, Some(socket_address)).await?;
+        Ok(len)
+    }
+
+    #[instrument(skip_all)]
+    pub async fn recv(&self) -> Result<Vec<u8>, io::Error> {
+        let (data, _) = self.socket.receive().await?;
+        Ok(data)
+    }
+
+    #[instrument(skip_all)]
+    pub async fn recv_from(&self) -> Result<(Vec<u8>, IpSocketAddress), io::Error> {
+        Ok(self.socket.receive().await?)
+    }
+}
@@ -1,24 +1,68 @@
-use std::future::Future;
 use std::mem::ManuallyDrop;
-use std::pin::pin;
 use std::rc::Rc;
-use std::task::Poll;
+use std::time::Duration;
 
 use futures::{stream, Stream};
 
 use tracing::{instrument, trace};
 
 use wasi::sockets::instance_network::instance_network;
-use wasi::sockets::network::{ErrorCode, IpSocketAddress, Ipv4SocketAddress, Ipv6SocketAddress};
+use wasi::sockets::network::{ErrorCode, IpSocketAddress};
 use wasi::sockets::tcp::{ShutdownType, TcpSocket};
 use wasi::sockets::tcp_create_socket::create_tcp_socket;
 
-use wasi::io::streams::{InputStream, OutputStream, StreamError};
+use wasi::io::streams::{InputStream, OutputStream};
 
 use wasi_async_runtime::Reactor;
 
-use crate::io::{AsyncRead, AsyncWrite};
+use crate::io::{self, AsyncRead, AsyncWrite};
 use crate::net::{ip_address_family, LocalSocketAddress, ToSocketAddrs};
+use crate::time;
+
+/// The socket options, mapped to the `wasi:sockets/tcp` ones; there is
+/// no no-delay option, the hosts already disable the Nagle algorithm.
+macro_rules! socket_options {
+    ($socket:ident) => {
+        pub fn keep_alive(&self) -> Result<bool, io::Error> {
+            Ok(self.$socket()?.keep_alive_enabled()?)
+        }
+
+        pub fn set_keep_alive(&self, enabled: bool) -> Result<(), io::Error> {
+            Ok(self.$socket()?.set_keep_alive_enabled(enabled)?)
+        }
+
+        /// The idle time before the first keep-alive probe.
+        pub fn keep_alive_idle_time(&self) -> Result<std::time::Duration, io::Error> {
+            Ok(std::time::Duration::from_nanos(
+                self.$socket()?.keep_alive_idle_time()?,
+            ))
+        }
+
+        pub fn set_keep_alive_idle_time(&self, time: std::time::Duration) -> Result<(), io::Error> {
+            Ok(self
+                .$socket()?
+                .set_keep_alive_idle_time(u64::try_from(time.as_nanos()).unwrap_or(u64::MAX))?)
+        }
+
+        pub fn send_buffer_size(&self) -> Result<u64, io::Error> {
+            Ok(self.$socket()?.send_buffer_size()?)
+        }
+
+        /// A hint, the host may round or clamp the size.
+        pub fn set_send_buffer_size(&self, size: u64) -> Result<(), io::Error> {
+            Ok(self.$socket()?.set_send_buffer_size(size)?)
+        }
+
+        pub fn receive_buffer_size(&self) -> Result<u64, io::Error> {
+            Ok(self.$socket()?.receive_buffer_size()?)
+        }
+
+        /// A hint, the host may round or clamp the size.
+        pub fn set_receive_buffer_size(&self, size: u64) -> Result<(), io::Error> {
+            Ok(self.$socket()?.set_receive_buffer_size(size)?)
+        }
+    };
+}
 
 pub struct TcpListener {
     reactor: Reactor,
@@ -26,11 +70,26 @@ pub struct TcpListener {
 }
 
 impl TcpListener {
+    /// Bind and listen; with the port zero the host assigns a free
+    /// port, see [`local_addr`](Self::local_addr). There is no address
+    /// reuse option in `wasi:sockets`, the hosts enable it on the
+    /// listeners.
     #[instrument(skip_all)]
-    pub async fn bind(reactor: Reactor, address: impl ToSocketAddrs) -> Result<Self, ErrorCode> {
+    pub async fn bind(reactor: Reactor, address: impl ToSocketAddrs) -> Result<Self, io::Error> {
+        Self::bind_with_backlog(reactor, address, None).await
+    }
+
+    /// Bind with the given size of the queue of the pending
+    /// connections, the host default when `None`.
+    #[instrument(skip_all)]
+    pub async fn bind_with_backlog(
+        reactor: Reactor,
+        address: impl ToSocketAddrs,
+        backlog: Option<u64>,
+    ) -> Result<Self, io::Error> {
         let network = instance_network();
 
-        let socket_address = address.to_socket_addr(&reactor, &network).await?;
+        let socket_address = address.to_socket_addr(&reactor).await?;
 
         let family = ip_address_family(&socket_address);
 
@@ -44,11 +103,15 @@ impl TcpListener {
                     trace!("socket subscription finish bind {subscription:?}");
                     reactor.wait_for(subscription).await;
                 }
-                Err(err) => return Err(err),
+                Err(err) => return Err(err.into()),
                 Ok(()) => break,
             }
         }
 
+        if let Some(backlog) = backlog {
+            socket.set_listen_backlog_size(backlog)?;
+        }
+
         socket.start_listen()?;
         loop {
             match socket.finish_listen() {
@@ -57,7 +120,7 @@ impl TcpListener {
                     trace!("socket subscription finish listener {subscription:?}");
                     reactor.wait_for(subscription).await;
                 }
-                Err(err) => return Err(err),
+                Err(err) => return Err(err.into()),
                 Ok(()) => break,
             }
         }
@@ -68,48 +131,37 @@ impl TcpListener {
         })
     }
 
-    #[instrument(skip_all)]
+    /// The accepted connections, forever.
     pub fn into_stream(
         self,
-    ) -> impl Stream<Item = Result<(TcpStream, IpSocketAddress), ErrorCode>> {
-        stream::poll_fn(move |cx| {
-            let reactor = self.reactor.clone();
-            let subscription = self.socket.subscribe();
-            trace!("socket subscription {subscription:?}");
-            let wait_for = pin!(reactor.wait_for(subscription));
-            if wait_for.poll(cx).is_pending() {
-                return Poll::Pending;
-            }
+    ) -> impl Stream<Item = Result<(TcpStream, IpSocketAddress), io::Error>> + Unpin {
+        Box::pin(stream::unfold(self, |listener| async move {
+            let accepted = listener.accept().await;
+            Some((accepted, listener))
+        }))
+    }
 
-            match self.socket.accept() {
-                Ok((socket, input_stream, output_stream)) => {
-                    let address = match socket.remote_address() {
-                        Ok(address) => address,
-                        Err(err) => return Poll::Ready(Some(Err(err))),
-                    };
-
-                    Poll::Ready(Some(Ok((
-                        TcpStream(Some(TcpStreamInner {
-                            reactor: self.reactor.clone(),
-                            socket: ManuallyDrop::new(socket),
-                            input_stream: ManuallyDrop::new(input_stream),
-                            output_stream: ManuallyDrop::new(output_stream),
-                        })),
-                        address,
-                    ))))
-                }
-                Err(err) => Poll::Ready(Some(Err(err))),
-            }
-        })
+    /// The accepted connections, forever, borrowing the listener.
+    pub fn incoming(
+        &self,
+    ) -> impl Stream<Item = Result<(TcpStream, IpSocketAddress), io::Error>> + Unpin + '_ {
+        Box::pin(stream::unfold((), move |()| async move {
+            Some((self.accept().await, ()))
+        }))
     }
 
     #[instrument(skip_all)]
-    pub async fn accept(&self) -> Result<(TcpStream, IpSocketAddress), ErrorCode> {
-        let subscription = self.socket.subscribe();
-        trace!("socket subscription {subscription:?}");
-        self.reactor.wait_for(subscription).await;
-
-        let (socket, input_stream, output_stream) = self.socket.accept()?;
+    pub async fn accept(&self) -> Result<(TcpStream, IpSocketAddress), io::Error> {
+        let (socket, input_stream, output_stream) = loop {
+            match self.socket.accept() {
+                Err(ErrorCode::WouldBlock) => {
+                    let subscription = self.socket.subscribe();
+                    trace!("socket subscription {subscription:?}");
+                    self.reactor.wait_for(subscription).await;
+                }
+                result => break result?,
+            }
+        };
 
         let address = socket.remote_address()?;
 
@@ -124,11 +176,15 @@ impl TcpListener {
         ))
     }
 
-    pub fn local_addr(&self) -> Result<LocalSocketAddress, ErrorCode> {
-        match self.socket.local_address()? {
-            IpSocketAddress::Ipv4(Ipv4SocketAddress { port, .. })
-            | IpSocketAddress::Ipv6(Ipv6SocketAddress { port, .. }) => Ok(LocalSocketAddress(port)),
-        }
+    pub fn local_addr(&self) -> Result<LocalSocketAddress, io::Error> {
+        Ok(LocalSocketAddress(self.socket.local_address()?))
+    }
+
+    // The accepted connections inherit the options of the listener.
+    socket_options!(socket);
+
+    fn socket(&self) -> Result<&TcpSocket, ErrorCode> {
+        Ok(&self.socket)
     }
 }
 
@@ -143,14 +199,16 @@ struct TcpStreamInner {
 pub struct TcpStream(Option<TcpStreamInner>);
 
 impl TcpStream {
+    /// Connect to `remote_address`; dropping the future aborts the
+    /// connection attempt, the socket is released.
     #[instrument(skip_all)]
     pub async fn connect(
         reactor: Reactor,
         remote_address: impl ToSocketAddrs,
-    ) -> Result<Self, ErrorCode> {
+    ) -> Result<Self, io::Error> {
         let network = instance_network();
 
-        let socket_address = remote_address.to_socket_addr(&reactor, &network).await?;
+        let socket_address = remote_address.to_socket_addr(&reactor).await?;
 
         let family = ip_address_family(&socket_address);
 
@@ -158,11 +216,16 @@ impl TcpStream {
 
         socket.start_connect(&network, socket_address)?;
 
-        let subscription = socket.subscribe();
-        trace!("socket subscription {subscription:?}");
-        reactor.wait_for(subscription).await;
-
-        let (input_stream, output_stream) = socket.finish_connect()?;
+        let (input_stream, output_stream) = loop {
+            match socket.finish_connect() {
+                Err(ErrorCode::WouldBlock) => {
+                    let subscription = socket.subscribe();
+                    trace!("socket subscription {subscription:?}");
+                    reactor.wait_for(subscription).await;
+                }
+                result => break result?,
+            }
+        };
 
         Ok(Self(Some(TcpStreamInner {
             reactor,
@@ -172,6 +235,29 @@ impl TcpStream {
         })))
     }
 
+    /// Connect to `remote_address` giving up after `duration`, the
+    /// name resolution included.
+    ///
+    /// # Errors
+    /// * An [`ErrorKind::TimedOut`](io::ErrorKind::TimedOut) error when
+    ///   the time is over, the connection attempt is aborted.
+    pub async fn connect_timeout(
+        reactor: Reactor,
+        remote_address: impl ToSocketAddrs,
+        duration: Duration,
+    ) -> Result<Self, io::Error> {
+        time::timeout(
+            reactor.clone(),
+            duration,
+            Self::connect(reactor, remote_address),
+        )
+        .await
+        .unwrap_or_else(|elapsed| Err(elapsed.into()))
+    }
+
+    /// Split into independently owned halves, that can be moved to
+    /// different spawned tasks; dropping a half shuts down its
+    /// direction of the connection.
     pub fn into_split(mut self) -> (OwnedReadHalf, OwnedWriteHalf) {
         let TcpStreamInner {
             socket,
@@ -194,6 +280,8 @@ impl TcpStream {
         (read, write)
     }
 
+    /// Split into halves borrowing the stream, to be used in the same
+    /// task.
     pub fn split(&mut self) -> (ReadHalf, WriteHalf) {
         let this = self.0.as_mut().unwrap();
 
@@ -209,9 +297,74 @@ impl TcpStream {
         (read, write)
     }
 
-    pub async fn close(self) -> Result<(), ErrorCode> {
+    socket_options!(socket);
+
+    fn socket(&self) -> Result<&TcpSocket, ErrorCode> {
+        self.0
+            .as_ref()
+            .map(|inner| &*inner.socket)
+            .ok_or(ErrorCode::InvalidState)
+    }
+
+    /// Close gracefully: flush the output, shut down the write side and,
+    /// with a `linger` time, wait up to it for the end of stream of the
+    /// peer, discarding what it still sends; then the connection is
+    /// shut down and the host resources are released, the streams
+    /// before the socket. A `linger` time running out is not an error.
+    ///
+    /// # Errors
+    /// * Error when the output cannot be flushed.
+    #[instrument(skip_all)]
+    pub async fn close_gracefully(mut self, linger: Option<Duration>) -> Result<(), io::Error> {
+        let Some(TcpStreamInner { reactor, .. }) = &self.0 else {
+            return Ok(());
+        };
+        let reactor = reactor.clone();
+
+        let (mut read, mut write) = self.split();
+        let result = async {
+            write.close().await?;
+
+            if let Some(linger) = linger {
+                let drain = async {
+                    loop {
+                        match read.read(io::DEFAULT_BUF_SIZE as u64).await {
+                            Ok(_) => {}
+                            Err(err) if err.is_closed() => return Ok(()),
+                            Err(err) => return Err(err),
+                        }
+                    }
+                };
+                match time::timeout(reactor, linger, drain).await {
+                    Ok(result) => result?,
+                    Err(_) => trace!("linger time elapsed"),
+                }
+            }
+
+            Ok(())
+        }
+        .await;
+
+        let TcpStreamInner {
+            socket,
+            input_stream,
+            output_stream,
+            ..
+        } = self.0.take().unwrap();
+        socket.shutdown(ShutdownType::Both).ok();
+        // the streams are children of the socket, they go first
+        drop(ManuallyDrop::into_inner(input_stream));
+        drop(ManuallyDrop::into_inner(output_stream));
+        drop(ManuallyDrop::into_inner(socket));
+
+        result
+    }
+
+    /// Shut down both directions right away, the pending output can be
+    /// lost; see [`TcpStream::close_gracefully`].
+    pub async fn close(self) -> Result<(), io::Error> {
         if let Some(TcpStreamInner { socket, .. }) = &self.0 {
-            socket.shutdown(ShutdownType::Both)
+            Ok(socket.shutdown(ShutdownType::Both)?)
         } else {
             Ok(())
         }
@@ -226,6 +379,43 @@ impl Drop for TcpStream {
     }
 }
 
+/// Write as many buffers as the stream accepts, with a single wait
+/// for the write permit.
+async fn write_vectored(
+    reactor: &Reactor,
+    output_stream: &OutputStream,
+    bufs: &[&[u8]],
+) -> Result<u64, io::Error> {
+    if bufs.iter().all(|buf| buf.is_empty()) {
+        return Ok(0);
+    }
+
+    let mut permit = loop {
+        let len = output_stream.check_write()?;
+        if len > 0 {
+            break len;
+        }
+        let subscription = output_stream.subscribe();
+        trace!("output stream subscription {subscription:?}");
+        reactor.wait_for(subscription).await;
+    };
+
+    let mut written = 0;
+    for buf in bufs {
+        let len = buf.len().min(permit as usize);
+        if len > 0 {
+            output_stream.write(&buf[0..len])?;
+            written += len as u64;
+            permit -= len as u64;
+        }
+        if permit == 0 {
+            break;
+        }
+    }
+
+    Ok(written)
+}
+
 pub struct ReadHalf<'a> {
     reactor: Reactor,
     input_stream: &'a mut InputStream,
@@ -233,7 +423,7 @@ pub struct ReadHalf<'a> {
 
 impl<'a> AsyncRead for ReadHalf<'a> {
     #[instrument(skip_all)]
-    async fn read(&mut self, len: u64) -> Result<Vec<u8>, StreamError> {
+    async fn read(&mut self, len: u64) -> Result<Vec<u8>, io::Error> {
         loop {
             let data = self.input_stream.read(len)?;
             if !data.is_empty() {
@@ -260,7 +450,7 @@ pub struct WriteHalf<'a> {
 
 impl<'a> AsyncWrite for WriteHalf<'a> {
     #[instrument(skip_all)]
-    async fn write(&mut self, data: &[u8]) -> Result<u64, StreamError> {
+    async fn write(&mut self, data: &[u8]) -> Result<u64, io::Error> {
         if data.is_empty() {
             return Ok(0);
         }
@@ -284,7 +474,12 @@ impl<'a> AsyncWrite for WriteHalf<'a> {
     }
 
     #[instrument(skip_all)]
-    async fn flush(&mut self) -> Result<(), StreamError> {
+    async fn write_vectored(&mut self, bufs: &[&[u8]]) -> Result<u64, io::Error> {
+        write_vectored(&self.reactor, self.output_stream, bufs).await
+    }
+
+    #[instrument(skip_all)]
+    async fn flush(&mut self) -> Result<(), io::Error> {
         self.output_stream.flush()?;
         while self.output_stream.check_write()? == 0 {
             let subscription = self.output_stream.subscribe();
@@ -294,15 +489,18 @@ impl<'a> AsyncWrite for WriteHalf<'a> {
         Ok(())
     }
 
-    async fn close(&mut self) -> Result<(), StreamError> {
-        self.socket.shutdown(ShutdownType::Send).ok(); // TODO
+    /// Flush the pending output and then shut down the write side, the
+    /// peer reads the end of the stream.
+    async fn close(&mut self) -> Result<(), io::Error> {
+        self.flush().await?;
+        self.socket.shutdown(ShutdownType::Send).ok();
         Ok(())
     }
 }
 
 impl<'a> WriteHalf<'a> {
     #[instrument(skip_all)]
-    pub async fn splice(&mut self, read: &mut ReadHalf<'_>, len: u64) -> Result<u64, StreamError> {
+    pub async fn splice(&mut self, read: &mut ReadHalf<'_>, len: u64) -> Result<u64, io::Error> {
         if len == 0 {
             return Ok(0);
         }
@@ -339,7 +537,7 @@ impl Drop for OwnedReadHalf {
 
 impl AsyncRead for OwnedReadHalf {
     #[instrument(skip_all)]
-    async fn read(&mut self, len: u64) -> Result<Vec<u8>, StreamError> {
+    async fn read(&mut self, len: u64) -> Result<Vec<u8>, io::Error> {
         loop {
             let data = self.input_stream.read(len)?;
             if !data.is_empty() {
@@ -372,7 +570,7 @@ impl Drop for OwnedWriteHalf {
 
 impl AsyncWrite for OwnedWriteHalf {
     #[instrument(skip_all)]
-    async fn write(&mut self, data: &[u8]) -> Result<u64, StreamError> {
+    async fn write(&mut self, data: &[u8]) -> Result<u64, io::Error> {
         let len = loop {
             let len = self.output_stream.check_write()?;
             if len > 0 {
@@ -391,7 +589,12 @@ impl AsyncWrite for OwnedWriteHalf {
     }
 
     #[instrument(skip_all)]
-    async fn flush(&mut self) -> Result<(), StreamError> {
+    async fn write_vectored(&mut self, bufs: &[&[u8]]) -> Result<u64, io::Error> {
+        write_vectored(&self.reactor, &self.output_stream, bufs).await
+    }
+
+    #[instrument(skip_all)]
+    async fn flush(&mut self) -> Result<(), io::Error> {
         self.output_stream.flush()?;
         while self.output_stream.check_write()? == 0 {
             let subscription = self.output_stream.subscribe();
@@ -401,15 +604,18 @@ impl AsyncWrite for OwnedWriteHalf {
         Ok(())
     }
 
-    async fn close(&mut self) -> Result<(), StreamError> {
-        self.socket.shutdown(ShutdownType::Send).ok(); // TODO
+    /// Flush the pending output and then shut down the write side, the
+    /// peer reads the end of the stream.
+    async fn close(&mut self) -> Result<(), io::Error> {
+        self.flush().await?;
+        self.socket.shutdown(ShutdownType::Send).ok();
         Ok(())
     }
 }
 
 impl OwnedWriteHalf {
     #[instrument(skip_all)]
-    pub async fn splice(&mut self, read: &mut OwnedReadHalf, len: u64) -> Result<u64, StreamError> {
+    pub async fn splice(&mut self, read: &mut OwnedReadHalf, len: u64) -> Result<u64, io::Error> {
         if len == 0 {
             return Ok(0);
         }
@@ -1,7 +1,7 @@
 use std::mem::ManuallyDrop;
 
 use wasi::sockets::instance_network::instance_network;
-use wasi::sockets::network::{self, IpSocketAddress, Ipv4SocketAddress, Ipv6SocketAddress};
+use wasi::sockets::network::{self, IpSocketAddress};
 use wasi::sockets::udp::{
     IncomingDatagram, IncomingDatagramStream, OutgoingDatagram, OutgoingDatagramStream,
     UdpSocket as WasiUdpSocket,
@@ -13,11 +13,11 @@ use wasi_async_runtime::Reactor;
 
 use tracing::{instrument, trace, warn};
 
+use crate::io;
 use crate::net::{ip_address_family, LocalSocketAddress, ToSocketAddrs};
 
 pub struct UdpSocket {
     reactor: Reactor,
-    network: network::Network,
     socket: ManuallyDrop<WasiUdpSocket>,
     inner: RwLock<UdpSocketInner>,
 }
@@ -37,14 +37,13 @@ enum UdpSocketInner {
 }
 
 impl UdpSocket {
+    /// Bind; with the port zero the host assigns a free port, see
+    /// [`local_addr`](Self::local_addr).
     #[instrument(skip_all)]
-    pub async fn bind(
-        reactor: Reactor,
-        address: impl ToSocketAddrs,
-    ) -> Result<Self, network::ErrorCode> {
+    pub async fn bind(reactor: Reactor, address: impl ToSocketAddrs) -> Result<Self, io::Error> {
         let network = instance_network();
 
-        let socket_address = address.to_socket_addr(&reactor, &network).await?;
+        let socket_address = address.to_socket_addr(&reactor).await?;
 
         let family = ip_address_family(&socket_address);
 
@@ -56,7 +55,7 @@ impl UdpSocket {
                 Err(network::ErrorCode::WouldBlock) => {
                     reactor.wait_for(socket.subscribe()).await;
                 }
-                Err(err) => return Err(err),
+                Err(err) => return Err(err.into()),
                 Ok(()) => break,
             }
         }
@@ -65,7 +64,6 @@ impl UdpSocket {
 
         Ok(Self {
             reactor,
-            network,
             socket: ManuallyDrop::new(socket),
             inner: RwLock::new(UdpSocketInner::Bind {
                 incoming_datagram_stream,
@@ -75,8 +73,8 @@ impl UdpSocket {
     }
 
     #[instrument(skip_all)]
-    pub async fn connect(&self, address: impl ToSocketAddrs) -> Result<(), network::ErrorCode> {
-        let socket_address = address.to_socket_addr(&self.reactor, &self.network).await?;
+    pub async fn connect(&self, address: impl ToSocketAddrs) -> Result<(), io::Error> {
+        let socket_address = address.to_socket_addr(&self.reactor).await?;
 
         let inner = &mut *self.inner.write().await;
         *inner = UdpSocketInner::Uninitialized;
@@ -92,15 +90,12 @@ impl UdpSocket {
         Ok(())
     }
 
-    pub fn local_addr(&self) -> Result<LocalSocketAddress, network::ErrorCode> {
-        match self.socket.local_address()? {
-            IpSocketAddress::Ipv4(Ipv4SocketAddress { port, .. })
-            | IpSocketAddress::Ipv6(Ipv6SocketAddress { port, .. }) => Ok(LocalSocketAddress(port)),
-        }
+    pub fn local_addr(&self) -> Result<LocalSocketAddress, io::Error> {
+        Ok(LocalSocketAddress(self.socket.local_address()?))
     }
 
     #[instrument(skip_all)]
-    pub async fn send(&self, data: Vec<u8>) -> Result<usize, network::ErrorCode> {
+    pub async fn send(&self, data: Vec<u8>) -> Result<usize, io::Error> {
         trace!("send {}", data.len());
         match &*self.inner.read().await {
             UdpSocketInner::Connect {
@@ -119,13 +114,13 @@ impl UdpSocket {
                 }])? == 0
                 {
                     warn!("???");
-                    return Err(network::ErrorCode::InvalidState);
+                    return Err(network::ErrorCode::InvalidState.into());
                 }
                 Ok(len)
             }
             _ => {
                 warn!("invalid state");
-                return Err(network::ErrorCode::InvalidState);
+                return Err(network::ErrorCode::InvalidState.into());
             }
         }
     }
@@ -135,8 +130,8 @@ impl UdpSocket {
         &self,
         data: Vec<u8>,
         address: impl ToSocketAddrs,
-    ) -> Result<usize, network::ErrorCode> {
-        let socket_address = address.to_socket_addr(&self.reactor, &self.network).await?;
+    ) -> Result<usize, io::Error> {
+        let socket_address = address.to_socket_addr(&self.reactor).await?;
 
         match &*self.inner.read().await {
             UdpSocketInner::Connect {
@@ -158,19 +153,19 @@ impl UdpSocket {
                     remote_address: Some(socket_address),
                 }])? == 0
                 {
-                    return Err(network::ErrorCode::InvalidState);
+                    return Err(network::ErrorCode::InvalidState.into());
                 }
                 Ok(len)
             }
             _ => {
                 warn!("invalid state");
-                return Err(network::ErrorCode::InvalidState);
+                return Err(network::ErrorCode::InvalidState.into());
             }
         }
     }
 
     #[instrument(skip_all)]
-    pub async fn recv(&self) -> Result<Vec<u8>, network::ErrorCode> {
+    pub async fn recv(&self) -> Result<Vec<u8>, io::Error> {
         match &*self.inner.read().await {
             UdpSocketInner::Connect {
                 incoming_datagram_stream,
@@ -195,13 +190,13 @@ impl UdpSocket {
             }
             _ => {
                 warn!("invalid state");
-                return Err(network::ErrorCode::InvalidState);
+                return Err(network::ErrorCode::InvalidState.into());
             }
         }
     }
 
     #[instrument(skip_all)]
-    pub async fn recv_from(&self) -> Result<(Vec<u8>, IpSocketAddress), network::ErrorCode> {
+    pub async fn recv_from(&self) -> Result<(Vec<u8>, IpSocketAddress), io::Error> {
         match &*self.inner.read().await {
             UdpSocketInner::Connect {
                 incoming_datagram_stream,
@@ -225,7 +220,7 @@ impl UdpSocket {
             },
             _ => {
                 warn!("invalid state");
-                return Err(network::ErrorCode::InvalidState);
+                return Err(network::ErrorCode::InvalidState.into());
             }
         }
     }
@@ -0,0 +1,60 @@
+//! Wait on the first of some futures, like `tokio::select!`.
+//!
+//! The futures are polled in order at every wake, so the earlier ones
+//! win when more are ready: put the socket before the timer to drain
+//! it first. The losing futures are dropped.
+use std::future::{self, Future};
+use std::pin::pin;
+use std::task::Poll;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Either<A, B> {
+    Left(A),
+    Right(B),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Either3<A, B, C> {
+    First(A),
+    Second(B),
+    Third(C),
+}
+
+/// The output of the first future to complete.
+pub async fn select2<A: Future, B: Future>(a: A, b: B) -> Either<A::Output, B::Output> {
+    let (mut a, mut b) = (pin!(a), pin!(b));
+
+    future::poll_fn(|cx| {
+        if let Poll::Ready(output) = a.as_mut().poll(cx) {
+            return Poll::Ready(Either::Left(output));
+        }
+        if let Poll::Ready(output) = b.as_mut().poll(cx) {
+            return Poll::Ready(Either::Right(output));
+        }
+        Poll::Pending
+    })
+    .await
+}
+
+/// The output of the first future to complete.
+pub async fn select3<A: Future, B: Future, C: Future>(
+    a: A,
+    b: B,
+    c: C,
+) -> Either3<A::Output, B::Output, C::Output> {
+    let (mut a, mut b, mut c) = (pin!(a), pin!(b), pin!(c));
+
+    future::poll_fn(|cx| {
+        if let Poll::Ready(output) = a.as_mut().poll(cx) {
+            return Poll::Ready(Either3::First(output));
+        }
+        if let Poll::Ready(output) = b.as_mut().poll(cx) {
+            return Poll::Ready(Either3::Second(output));
+        }
+        if let Poll::Ready(output) = c.as_mut().poll(cx) {
+            return Poll::Ready(Either3::Third(output));
+        }
+        Poll::Pending
+    })
+    .await
+}
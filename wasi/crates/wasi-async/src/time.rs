@@ -8,7 +8,10 @@ use futures_concurrency::prelude::*;
 
 use tracing::{instrument, trace};
 
+#[cfg(not(feature = "wasip3"))]
 use wasi::clocks::monotonic_clock;
+#[cfg(feature = "wasip3")]
+use wasip3::clocks::monotonic_clock;
 
 use wasi_async_runtime::Reactor;
 
@@ -23,19 +26,80 @@ impl fmt::Display for Elapsed {
     }
 }
 
+#[cfg(not(feature = "wasip3"))]
+async fn wait_for(reactor: &Reactor, duration: Duration) {
+    let subscription = monotonic_clock::subscribe_duration(duration.as_nanos() as u64);
+    trace!("subscribe duration {subscription:?}");
+    reactor.wait_for(subscription).await;
+}
+
+#[cfg(not(feature = "wasip3"))]
+async fn wait_until(reactor: &Reactor, deadline: Instant) {
+    let subscription = monotonic_clock::subscribe_instant(deadline.0);
+    trace!("subscribe instant {subscription:?}");
+    reactor.wait_for(subscription).await;
+}
+
+/// The clock of WASI 0.3 is waited on by the host, the reactor only
+/// polls the task.
+#[cfg(feature = "wasip3")]
+async fn wait_for(_reactor: &Reactor, duration: Duration) {
+    trace!("wait for {duration:?}");
+    monotonic_clock::wait_for(duration.as_nanos() as u64).await;
+}
+
+#[cfg(feature = "wasip3")]
+async fn wait_until(_reactor: &Reactor, deadline: Instant) {
+    trace!("wait until {:?}", deadline.0);
+    monotonic_clock::wait_until(deadline.0).await;
+}
+
+/// Wait until `duration` has elapsed.
+#[instrument(skip_all)]
+pub async fn sleep(reactor: Reactor, duration: Duration) {
+    wait_for(&reactor, duration).await;
+}
+
+/// Wait until `deadline` is reached.
+#[instrument(skip_all)]
+pub async fn sleep_until(reactor: Reactor, deadline: Instant) {
+    wait_until(&reactor, deadline).await;
+}
+
+/// Run `future` for at most `duration`.
+///
+/// # Errors
+/// * [`Elapsed`] when the time is over, the future is dropped.
 #[instrument(skip_all)]
 pub async fn timeout<F: Future>(
     reactor: Reactor,
     duration: Duration,
     future: F,
 ) -> Result<F::Output, Elapsed> {
-    let subscription = monotonic_clock::subscribe_duration(duration.as_nanos() as u64);
-    trace!("subscribe duration {subscription:?}");
-    let wait_for = reactor.wait_for(subscription).map(|_| Err(Elapsed));
+    // the clock pollable is removed from the reactor when the future
+    // wins the race, so it cannot wake the poll after the timeout
+    let sleep = sleep(reactor, duration).map(|()| Err(Elapsed));
+
+    let future = future.map(Ok);
+
+    (sleep, future).race().await
+}
+
+/// Run `future` up to `deadline`.
+///
+/// # Errors
+/// * [`Elapsed`] when the deadline is reached, the future is dropped.
+#[instrument(skip_all)]
+pub async fn timeout_at<F: Future>(
+    reactor: Reactor,
+    deadline: Instant,
+    future: F,
+) -> Result<F::Output, Elapsed> {
+    let sleep = sleep_until(reactor, deadline).map(|()| Err(Elapsed));
 
     let future = future.map(Ok);
 
-    (wait_for, future).race().await
+    (sleep, future).race().await
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -46,6 +110,15 @@ impl Instant {
     pub fn now() -> Self {
         Self(monotonic_clock::now())
     }
+
+    /// The time elapsed from `earlier`, zero when `earlier` is later.
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        Duration::from_nanos(self.0.saturating_sub(earlier.0))
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        Instant::now().duration_since(*self)
+    }
 }
 
 impl ops::Add<Duration> for Instant {
@@ -62,12 +135,54 @@ impl ops::AddAssign<Duration> for Instant {
     }
 }
 
+/// An interval ticking right away and then every `period`.
+///
+/// # Panics
+/// * Panics when `period` is zero.
+pub fn interval(reactor: Reactor, period: Duration) -> Interval {
+    interval_at(reactor, Instant::now(), period)
+}
+
+/// An interval ticking at `start` and then every `period`.
+///
+/// # Panics
+/// * Panics when `period` is zero.
 pub fn interval_at(reactor: Reactor, start: Instant, period: Duration) -> Interval {
-    assert!(period != Duration::from_nanos(0));
+    assert!(period != Duration::from_nanos(0), "interval period zero");
     Interval {
         reactor,
         current: start,
         period,
+        missed_tick_behavior: MissedTickBehavior::default(),
+    }
+}
+
+/// What an [`Interval`] does when a tick is late by more than a
+/// period, as in tokio.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MissedTickBehavior {
+    /// Tick right away until caught up with the schedule.
+    #[default]
+    Burst,
+
+    /// Restart the schedule from the late tick.
+    Delay,
+
+    /// Skip the missed ticks, keeping the schedule.
+    Skip,
+}
+
+impl MissedTickBehavior {
+    fn next(self, scheduled: Instant, now: Instant, period: Duration) -> Instant {
+        match self {
+            MissedTickBehavior::Burst => scheduled + period,
+            MissedTickBehavior::Delay => now + period,
+            MissedTickBehavior::Skip => {
+                let period = period.as_nanos() as u64;
+                let late = now.0 - scheduled.0;
+                Instant(now.0 + period - late % period)
+            }
+        }
     }
 }
 
@@ -75,6 +190,7 @@ pub struct Interval {
     reactor: Reactor,
     current: Instant,
     period: Duration,
+    missed_tick_behavior: MissedTickBehavior,
 }
 
 impl Interval {
@@ -82,11 +198,60 @@ impl Interval {
         self.period
     }
 
+    pub fn missed_tick_behavior(&self) -> MissedTickBehavior {
+        self.missed_tick_behavior
+    }
+
+    pub fn set_missed_tick_behavior(&mut self, behavior: MissedTickBehavior) {
+        self.missed_tick_behavior = behavior;
+    }
+
+    /// Restart the schedule, the next tick is a period from now.
+    pub fn reset(&mut self) {
+        self.current = Instant::now() + self.period;
+    }
+
+    /// Wait for the next tick, returning its scheduled instant; it is
+    /// cancel safe, a dropped tick does not advance the schedule.
     #[instrument(skip_all)]
-    pub async fn tick(&mut self) {
-        let subscription = monotonic_clock::subscribe_instant(self.current.0);
-        trace!("subscribe instant {subscription:?}");
-        self.reactor.wait_for(subscription).await;
-        self.current += self.period;
+    pub async fn tick(&mut self) -> Instant {
+        wait_until(&self.reactor, self.current).await;
+
+        let scheduled = self.current;
+        let now = Instant::now();
+        self.current = if now.duration_since(scheduled) >= self.period {
+            self.missed_tick_behavior.next(scheduled, now, self.period)
+        } else {
+            scheduled + self.period
+        };
+
+        scheduled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Instant, MissedTickBehavior};
+
+    const PERIOD: Duration = Duration::from_nanos(10);
+
+    #[test]
+    fn test_missed_tick_behavior() {
+        let (scheduled, now) = (Instant(100), Instant(125));
+
+        assert_eq!(
+            Instant(110),
+            MissedTickBehavior::Burst.next(scheduled, now, PERIOD)
+        );
+        assert_eq!(
+            Instant(135),
+            MissedTickBehavior::Delay.next(scheduled, now, PERIOD)
+        );
+        assert_eq!(
+            Instant(130),
+            MissedTickBehavior::Skip.next(scheduled, now, PERIOD)
+        );
     }
 }
@@ -1,24 +1,18 @@
 #![doc = include_str!("../README.md")]
 
-use wasi::io::streams::StreamError;
-use wasi::sockets::network::{ErrorCode, IpSocketAddress};
-
 use thiserror::Error;
 use tracing::{info, instrument};
 
 use wasi_async::io::AsyncWrite;
-use wasi_async::net::TcpStream;
+use wasi_async::net::{IpSocketAddress, TcpStream};
 
 #[allow(warnings)]
 mod bindings;
 
 #[derive(Error, Debug)]
 pub enum Error {
-    #[error("stream error: {0}")]
-    Stream(#[from] StreamError),
-
-    #[error("tcp socket error: {0}")]
-    TcpSocket(#[from] ErrorCode),
+    #[error("io error: {0}")]
+    Io(#[from] wasi_async::io::Error),
 }
 
 #[instrument(skip(stream))]
@@ -37,7 +31,8 @@ pub async fn run(address: IpSocketAddress, mut stream: TcpStream) -> Result<(),
     stream.close().await.ok();
 
     match r {
-        Err(Error::Stream(StreamError::Closed)) | Ok(_) => Ok(()),
+        Err(Error::Io(err)) if err.is_closed() => Ok(()),
+        Ok(_) => Ok(()),
         Err(e) => Err(e),
     }
 }
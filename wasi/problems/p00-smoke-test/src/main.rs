@@ -1,5 +1,3 @@
-use wasi::sockets::network::ErrorCode;
-
 use wasi_async::net::TcpListener;
 use wasi_async_runtime::block_on;
 
@@ -25,7 +23,7 @@ fn main() -> Result<(), anyhow::Error> {
 
     let args = Args::parse();
 
-    let result: Result<_, ErrorCode> = block_on(|reactor| async move {
+    let result: Result<_, wasi_async::io::Error> = block_on(|reactor| async move {
         let socket =
             TcpListener::bind(reactor.clone(), format!("{}:{}", args.address, args.port)).await?;
 
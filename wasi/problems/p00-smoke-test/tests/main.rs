@@ -1,19 +1,15 @@
 use std::sync::Once;
 
 use wasi_async::net::TcpStream;
-use wasi_async_runtime::{block_on, Reactor};
+use wasi_async_runtime::Reactor;
 
 use tracing::info;
 
 use wasi_async::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
 use wasi_async::net::TcpListener;
 
-#[test]
-fn test_session() {
-    block_on(async_test_session);
-}
-
-async fn async_test_session(reactor: Reactor) {
+#[wasi_async::test]
+async fn test_session(reactor: Reactor) {
     let (address, port) = spawn_app(reactor.clone()).await;
 
     let mut stream = TcpStream::connect(reactor, format!("{address}:{port}"))
@@ -2,9 +2,6 @@
 
 use futures::StreamExt;
 
-use wasi::io::streams::StreamError;
-use wasi::sockets::network::{self, IpSocketAddress};
-
 use thiserror::Error;
 
 use tracing::{debug, info, instrument, warn};
@@ -13,7 +10,7 @@ use serde::{Deserialize, Serialize};
 
 use wasi_async::codec::{FramedRead, LinesDecoder};
 use wasi_async::io::{AsyncWrite, AsyncWriteExt};
-use wasi_async::net::TcpStream;
+use wasi_async::net::{IpSocketAddress, TcpStream};
 
 #[allow(warnings)]
 mod bindings;
@@ -34,11 +31,8 @@ pub struct Response<'a> {
 
 #[derive(Error, Debug)]
 pub enum Error {
-    #[error("stream error: {0}")]
-    Stream(#[from] StreamError),
-
-    #[error("tcp socket error: {0}")]
-    TcpSocket(#[from] network::ErrorCode),
+    #[error("io error: {0}")]
+    Io(#[from] wasi_async::io::Error),
 
     #[error("serde json error: {0}")]
     Serde(#[from] serde_json::Error),
@@ -80,7 +74,8 @@ pub async fn run(address: IpSocketAddress, mut stream: TcpStream) -> Result<(),
     stream.close().await.ok();
 
     match r {
-        Err(Error::Stream(StreamError::Closed)) | Ok(()) => Ok(()),
+        Err(Error::Io(err)) if err.is_closed() => Ok(()),
+        Ok(()) => Ok(()),
         Err(e) => Err(e),
     }
 }
@@ -1,5 +1,3 @@
-use wasi::sockets::network;
-
 use wasi_async::net::TcpListener;
 
 use tracing::{debug, info, instrument};
@@ -23,7 +21,7 @@ fn main() -> Result<(), anyhow::Error> {
 
     let args = Args::parse();
 
-    let result: Result<_, network::ErrorCode> =
+    let result: Result<_, wasi_async::io::Error> =
         wasi_async_runtime::block_on(|reactor| async move {
             let socket =
                 TcpListener::bind(reactor.clone(), format!("{}:{}", args.address, args.port))
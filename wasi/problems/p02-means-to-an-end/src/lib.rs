@@ -1,139 +1,88 @@
 #![doc = include_str!("../README.md")]
 
-use std::array::TryFromSliceError;
-use std::collections::BTreeMap;
-use std::mem;
-use std::num::TryFromIntError;
+use wasi_async::net::{IpSocketAddress, TcpStream};
 
-use futures::{SinkExt, StreamExt};
-
-use wasi::io::streams::StreamError;
-use wasi::sockets::network::{self, IpSocketAddress};
-
-use wasi_async::codec::{ChunksDecoder, Encoder, FramedRead, FramedWrite};
-use wasi_async::net::TcpStream;
+use protohackers_runtime::wasi::Compat;
 
 use thiserror::Error;
 
-use tracing::{debug, info, instrument, warn};
+use tracing::{info, instrument, warn};
 
-use bytes::BytesMut;
+pub use p02_means_to_an_end_core::{
+    DuplicatePolicy, Extensions, LimitPolicy, Message, MessageDecoder, PriceLimit, Prices, Session,
+    BATCH_INSERT, HELLO, MESSAGE_LEN, PROTOCOL_VERSION,
+};
 
 #[allow(warnings)]
 mod bindings;
 
-#[derive(Debug, PartialEq)]
-pub enum Message {
-    Insert { timestamp: i32, price: i32 },
-
-    Query { mintime: i32, maxtime: i32 },
-}
-
 #[derive(Error, Debug)]
 pub enum Error {
-    #[error("stream error {0}")]
-    Stream(#[from] StreamError),
+    #[error("io error {0}")]
+    Io(#[from] wasi_async::io::Error),
 
-    #[error("tcp socket error {0}")]
-    TcpSocket(#[from] network::ErrorCode),
+    #[error("{0}")]
+    Message(#[from] p02_means_to_an_end_core::Error),
+}
 
-    #[error("message invalid")]
-    MessageInvalid,
+/// The server options, the standard protocol by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Config {
+    /// Limit the number of prices of every session.
+    pub price_limit: Option<PriceLimit>,
 
-    #[error("invalid slice: {0}")]
-    Slice(#[from] TryFromSliceError),
+    /// Let the clients negotiate the protocol extensions with a
+    /// leading hello.
+    pub negotiation: bool,
+}
 
-    #[error("mean out of range: {0}")]
-    Mean(#[from] TryFromIntError),
+/// Handle a client session.
+///
+/// # Errors
+/// * Error when the socket returns an error or the message is invalid.
+pub async fn run(address: IpSocketAddress, stream: TcpStream) -> Result<(), Error> {
+    run_with_config(address, stream, Config::default()).await
 }
 
+/// Handle a client session, using the given configuration.
+///
+/// # Errors
+/// * Error when the socket returns an error, the message is invalid
+///   or the prices are beyond the limit with [`LimitPolicy::Close`].
 #[instrument(skip(stream))]
-pub async fn run(address: IpSocketAddress, mut stream: TcpStream) -> Result<(), Error> {
+pub async fn run_with_config(
+    address: IpSocketAddress,
+    mut stream: TcpStream,
+    config: Config,
+) -> Result<(), Error> {
     info!("run");
 
-    let mut items = BTreeMap::new();
+    let mut session = Session::new();
+    if let Some(price_limit) = config.price_limit {
+        session = session.with_price_limit(price_limit);
+    }
+
+    let decoder = if config.negotiation {
+        MessageDecoder::with_negotiation(Extensions::ALL)
+    } else {
+        MessageDecoder::new()
+    };
 
     let (read, write) = stream.split();
-    let r = async move {
-        let mut read = FramedRead::new(read, ChunksDecoder::<9>::new()).map(parse);
-        let mut write = FramedWrite::new(write, I32Encoder::new());
-
-        while let Some(value) = read.next().await {
-            match value? {
-                Message::Insert { timestamp, price } => {
-                    debug!("message: I {timestamp} {price}");
-                    items.insert(timestamp, price);
-                }
-
-                Message::Query { mintime, maxtime } if maxtime >= mintime => {
-                    let mut count = 0;
-                    let mut sum = 0;
-                    for (_, value) in items.range(mintime..=maxtime) {
-                        count += 1;
-                        sum += i64::from(*value);
-                    }
-                    let mean = if count > 0 { sum / count } else { 0 };
-                    debug!("Q {mintime} {maxtime}: {mean}");
-                    write.send(i32::try_from(mean)?).await?;
-                }
-
-                Message::Query { mintime, maxtime } => {
-                    debug!("Q {mintime} {maxtime} invalid range");
-                    write.send(0).await?;
-                }
-            }
-        }
-
-        Ok(())
-    }
+    let r = p02_means_to_an_end_core::serve(
+        Compat::new(read),
+        Compat::new(write),
+        decoder,
+        &mut session,
+        &mut (),
+    )
     .await;
 
     stream.close().await.ok();
 
-    match r {
-        Err(Error::Stream(StreamError::Closed)) | Ok(()) => Ok(()),
-        Err(e) => Err(e),
-    }
-}
-
-fn parse(chunk: Result<[u8; 9], StreamError>) -> Result<Message, Error> {
-    let chunk = chunk?;
-
-    match (
-        chunk[0],
-        i32::from_be_bytes(chunk[1..=mem::size_of::<i32>()].try_into()?),
-        i32::from_be_bytes(chunk[1 + mem::size_of::<i32>()..].try_into()?),
-    ) {
-        (b'I', timestamp, price) => Ok(Message::Insert { timestamp, price }),
-        (b'Q', mintime, maxtime) => Ok(Message::Query { mintime, maxtime }),
-        _ => {
-            warn!("invalid request");
-            Err(Error::MessageInvalid)
-        }
-    }
-}
-
-pub struct I32Encoder;
-
-impl I32Encoder {
-    #[must_use]
-    pub fn new() -> Self {
-        Self
-    }
-}
-
-impl Default for I32Encoder {
-    fn default() -> Self {
-        Self::new()
+    if let Err(err) = &r {
+        warn!("invalid request: {err}");
     }
-}
 
-impl Encoder<i32> for I32Encoder {
-    type Error = StreamError;
-
-    fn encode(&mut self, item: i32, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        dst.extend_from_slice(item.to_be_bytes().as_slice());
-
-        Ok(())
-    }
+    Ok(r?)
 }
@@ -1,11 +1,11 @@
-use wasi::sockets::network;
-
 use wasi_async::net::TcpListener;
 
 use tracing::{debug, info, instrument};
 
 use clap::Parser;
 
+use p02_means_to_an_end::{Config, LimitPolicy, PriceLimit};
+
 #[derive(Parser, Debug)]
 struct Args {
     #[arg(long, default_value = "0.0.0.0")]
@@ -13,6 +13,21 @@ struct Args {
 
     #[arg(long, default_value_t = 10000)]
     port: u16,
+
+    /// Maximum number of prices of a session, the oldest ones are
+    /// evicted beyond it
+    #[arg(long)]
+    max_prices: Option<usize>,
+
+    /// Close the sessions beyond the maximum number of prices instead
+    /// of evicting the oldest ones
+    #[arg(long)]
+    close_over_limit: bool,
+
+    /// Let the clients negotiate the protocol extensions with a
+    /// leading hello
+    #[arg(long)]
+    negotiation: bool,
 }
 
 #[instrument]
@@ -23,7 +38,19 @@ fn main() -> Result<(), anyhow::Error> {
 
     let args = Args::parse();
 
-    let result: Result<_, network::ErrorCode> =
+    let config = Config {
+        price_limit: args.max_prices.map(|max_prices| PriceLimit {
+            max_prices,
+            policy: if args.close_over_limit {
+                LimitPolicy::Close
+            } else {
+                LimitPolicy::EvictOldest
+            },
+        }),
+        negotiation: args.negotiation,
+    };
+
+    let result: Result<_, wasi_async::io::Error> =
         wasi_async_runtime::block_on(|reactor| async move {
             let socket =
                 TcpListener::bind(reactor.clone(), format!("{}:{}", args.address, args.port))
@@ -35,7 +62,8 @@ fn main() -> Result<(), anyhow::Error> {
                 debug!("new client: {address:?}");
 
                 reactor.clone().spawn(async move {
-                    let result = p02_means_to_an_end::run(address, stream).await;
+                    let result =
+                        p02_means_to_an_end::run_with_config(address, stream, config).await;
                     info!("result: {result:?}");
                 });
             }
@@ -1,269 +1,146 @@
-use std::fmt::Write;
-use std::marker::PhantomData;
-use std::sync::Arc;
+use std::cell::RefCell;
+use std::rc::Rc;
 
-use bytes::{BufMut, BytesMut};
+use futures::FutureExt;
+use futures_concurrency::future::Race;
 
-use futures::{channel::mpsc, stream::FusedStream, Sink, SinkExt, Stream, StreamExt, TryStreamExt};
+use tracing::{debug, error, info, instrument, warn};
 
-use tracing::{debug, error, info, instrument, trace};
-
-use wasi::io::streams::StreamError;
-
-use wasi_async::codec::{Encoder, FramedRead, FramedWrite, LinesDecoder};
+use wasi_async::io::{self, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use wasi_async::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
 use wasi_async::net::TcpStream;
+use wasi_async_runtime::sync::broadcast;
 
-use crate::{ClientMessage, Error, Id, ServerMessage};
+use p03_budget_chat_core::{text, Room};
 
-struct WaitingWelcome;
-struct Joining;
-struct Joined;
-struct Chatting;
+use crate::{Error, Event, Id};
 
-struct Client<'a, R, W, T> {
-    id: Id,
-    read: R,
-    write: W,
-    server: &'a mut mpsc::UnboundedSender<ClientMessage>,
-    #[allow(clippy::struct_field_names)]
-    client: mpsc::UnboundedReceiver<ServerMessage>,
-    _state: PhantomData<T>,
+enum Input {
+    Line(Result<Option<String>, io::Error>),
+    Event(Result<Event, broadcast::RecvError>),
 }
 
-impl<'a, R, W, T> Client<'a, R, W, T> {
-    fn into<D>(self) -> Client<'a, R, W, D> {
-        Client {
-            id: self.id,
-            read: self.read,
-            write: self.write,
-            server: self.server,
-            client: self.client,
-            _state: PhantomData,
-        }
-    }
-}
+/// Client iterations.
+///
+/// Join the room and run the chat, leaving the room at the end.
+#[instrument(skip(stream, room, events))]
+pub(crate) async fn handle_client(
+    id: Id,
+    stream: TcpStream,
+    room: Rc<RefCell<Room<Id>>>,
+    events: broadcast::Sender<Event>,
+) {
+    info!("start {id}");
 
-impl<'a, R, W> Client<'a, R, W, WaitingWelcome>
-where
-    R: Stream<Item = Result<String, StreamError>>,
-    W: Sink<String, Error = StreamError> + Unpin,
-{
-    #[instrument(skip_all)]
-    async fn welcome(mut self) -> Result<Option<Client<'a, R, W, Joining>>, Error> {
-        debug!("state: waiting welcome message");
-        match self.client.next().await {
-            Some(ServerMessage::Welcome) => {
-                debug!("got welcome message");
-                self.write
-                    .send("Welcome to budgetchat! What shall I call you?".to_string())
-                    .await?;
-                Ok(Some(self.into()))
-            }
-            None => Ok(None),
-            message => unreachable!("invalid server message {:?}", message),
-        }
+    if let Err(err) = chat(id, stream, &room, &events).await {
+        error!("error {err:?}");
     }
-}
-
-impl<'a, R, W> Client<'a, R, W, Joining>
-where
-    R: Stream<Item = Result<String, StreamError>> + Unpin,
-    W: Sink<String, Error = StreamError> + Unpin,
-{
-    #[instrument(skip_all)]
-    async fn joining(mut self) -> Result<Option<Client<'a, R, W, Joined>>, Error> {
-        debug!("state: joining");
-        match self.read.next().await {
-            Some(Ok(username)) => {
-                debug!("got username");
-                self.server
-                    .send(ClientMessage::SetUsername(self.id, username))
-                    .await?;
-            }
-            _ => return Ok(None),
-        }
 
-        match self.client.next().await {
-            Some(ServerMessage::UsernameAccepted) => {
-                self.server.send(ClientMessage::Joined(self.id)).await?;
-                Ok(Some(self.into()))
-            }
-            Some(ServerMessage::UsernameInvalid) => {
-                self.write.send("Invalid username".to_string()).await?;
-                Err(Error::InvalidUsername)
-            }
-            None => Ok(None),
-            message => unreachable!("invalid server message {:?}", message),
-        }
+    let username = room.borrow_mut().leave(id);
+    if let Some(username) = username {
+        let line = text::left(&username).into();
+        events.send(Event { from: id, line }).ok();
     }
+
+    info!("done {id}");
 }
 
-impl<'a, R, W> Client<'a, R, W, Joined>
-where
-    R: Stream<Item = Result<String, StreamError>>,
-    W: Sink<String, Error = StreamError> + Unpin,
-{
-    #[instrument(skip(self))]
-    async fn joined(mut self) -> Result<Option<Client<'a, R, W, Chatting>>, Error> {
-        debug!("state: joined");
-        match self.client.next().await {
-            Some(ServerMessage::Users(users)) => {
-                let mut message = String::new();
-                message.push_str("* The room contains:");
-                if !users.is_empty() {
-                    message.push(' ');
-                    let mut iter = users.iter();
-                    message.push_str(iter.next().unwrap());
-                    for user in iter {
-                        message.push_str(", ");
-                        message.push_str(user);
-                    }
-                }
+async fn chat(
+    id: Id,
+    stream: TcpStream,
+    room: &RefCell<Room<Id>>,
+    events: &broadcast::Sender<Event>,
+) -> Result<(), Error> {
+    let (read, mut write) = stream.into_split();
+    let mut read = BufReader::new(read);
+    let mut buffer = vec![];
+
+    debug!("state: welcome");
+    write_line(&mut write, text::WELCOME).await?;
+
+    debug!("state: joining");
+    let Some(username) = read_line(&mut read, &mut buffer).await? else {
+        return Ok(());
+    };
 
-                self.write.send(message).await?;
-                Ok(Some(self.into()))
-            }
-            None => Ok(None),
-            message => unreachable!("invalid server message {:?}", message),
+    let joined = room.borrow_mut().join(id, &username);
+    let username = match joined {
+        Ok(username) => username,
+        Err(err) => {
+            write_line(&mut write, text::INVALID_USERNAME).await?;
+            return Err(err.into());
         }
-    }
-}
+    };
 
-impl<'a, R, W> Client<'a, R, W, Chatting>
-where
-    R: Stream<Item = Result<String, StreamError>> + Unpin + FusedStream,
-    W: Sink<String, Error = StreamError> + Unpin,
-{
-    #[instrument(skip(self))]
-    async fn chatting(mut self) -> Result<(), Error> {
-        loop {
-            trace!("state: main loop");
-            futures::select_biased! {
-                message = self.client.next() => {
-                    trace!("got server message: {message:?}");
-                    match message {
-                        Some(ServerMessage::AnnounceUser(user)) => {
-                            let mut message = String::new();
-                            write!(&mut message, "* {} has entered the room", *user).ok();
-                            self.write.send(message).await?;
-                        }
-                        Some(ServerMessage::Message(user, msg)) => {
-                            let mut message = String::new();
-                            write!(&mut message, "[{}] {}", *user, *msg).ok();
-                            self.write.send(message).await?;
-                        }
-                        Some(ServerMessage::Disconnected(user)) => {
-                            let mut message = String::new();
-                            write!(&mut message, "* {} has left the room", *user).ok();
-                            self.write.send(message).await?;
-                        }
-                        None => break,
-                        message => unreachable!("invalid server message {:?}", message),
-                    }
-                }
+    // subscribed before the announce, no event after the join is lost
+    let mut receiver = events.subscribe();
+    let line = text::entered(&username).into();
+    events.send(Event { from: id, line }).ok();
 
-                segment = self.read.next() => {
-                    trace!("got client message: {segment:?}");
-                    match segment {
-                        Some(Ok(message)) => {
-                            self.server.unbounded_send(ClientMessage::Message(self.id, Arc::new(message))).unwrap();
-                        }
-                        _ => break,
-                    }
-                }
-            }
-        }
-        debug!("done chatting");
+    let users = text::room_contains(room.borrow().others(id).map(|(_, user)| &**user));
+    write_line(&mut write, &users).await?;
 
-        Ok(())
-    }
-}
+    debug!("state: chatting");
+    loop {
+        let line = read_line(&mut read, &mut buffer).map(Input::Line);
+        let event = receiver.recv().map(Input::Event);
 
-/// Client iterations.
-///
-/// Listen for a client and run the chat.
-///
-/// # Errors:
-/// * Error when socket returns an error.
-#[instrument(skip(stream, server, client))]
-pub(crate) async fn handle_client(
-    id: Id,
-    mut stream: TcpStream,
-    mut server: mpsc::UnboundedSender<ClientMessage>,
-    client: mpsc::UnboundedReceiver<ServerMessage>,
-) {
-    info!("start {id}");
+        match (line, event).race().await {
+            Input::Line(line) => {
+                let Some(line) = line? else {
+                    break;
+                };
 
-    let (read, write) = stream.split();
-    let client = Client {
-        id,
-        read: FramedRead::new(read, LinesDecoder::new())
-            .map_ok(|line| String::from_utf8_lossy(&line).into_owned())
-            .fuse(),
-        write: FramedWrite::new(write, LinesEncoder),
-        server: &mut server,
-        client,
-        _state: PhantomData::<WaitingWelcome>,
-    };
+                let line = text::message(&username, &line).into();
+                events.send(Event { from: id, line }).ok();
+            }
 
-    let client = match client.welcome().await {
-        Ok(Some(client)) => client,
-        Ok(None) => {
-            server.unbounded_send(ClientMessage::Disconnect(id)).ok();
-            return;
-        }
-        Err(err) => {
-            error!("error {:?}", err);
-            server.unbounded_send(ClientMessage::Disconnect(id)).ok();
-            return;
-        }
-    };
+            Input::Event(Ok(Event { from, line })) => {
+                if from != id {
+                    write_line(&mut write, &line).await?;
+                }
+            }
 
-    let client = match client.joining().await {
-        Ok(Some(client)) => client,
-        Ok(None) => {
-            server.unbounded_send(ClientMessage::Disconnect(id)).ok();
-            return;
-        }
-        Err(err) => {
-            error!("error {:?}", err);
-            server.unbounded_send(ClientMessage::Disconnect(id)).ok();
-            return;
-        }
-    };
+            Input::Event(Err(broadcast::RecvError::Lagged(n))) => {
+                warn!("lost {n} events");
+            }
 
-    let client = match client.joined().await {
-        Ok(Some(client)) => client,
-        Ok(None) => {
-            server.unbounded_send(ClientMessage::Disconnect(id)).ok();
-            return;
-        }
-        Err(err) => {
-            error!("error {:?}", err);
-            server.unbounded_send(ClientMessage::Disconnect(id)).ok();
-            return;
+            Input::Event(Err(broadcast::RecvError::Closed)) => break,
         }
-    };
-
-    if let Err(err) = client.chatting().await {
-        error!("error {:?}", err);
-        server.unbounded_send(ClientMessage::Disconnect(id)).ok();
-    } else {
-        debug!("state: done");
-        server.unbounded_send(ClientMessage::Disconnect(id)).ok();
     }
+    debug!("done chatting");
 
-    info!("done {id}");
+    Ok(())
 }
 
-struct LinesEncoder;
+/// The next complete line, without the trailing `\n` or `\r\n`; the
+/// data read stays in `buffer` when the future is dropped, an
+/// unterminated line at the end of the stream is ignored.
+async fn read_line(
+    read: &mut BufReader<OwnedReadHalf>,
+    buffer: &mut Vec<u8>,
+) -> Result<Option<String>, io::Error> {
+    match read.read_until(b'\n', buffer).await {
+        Ok(_) if buffer.last() == Some(&b'\n') => {
+            buffer.pop();
+            if buffer.last() == Some(&b'\r') {
+                buffer.pop();
+            }
+            let line = String::from_utf8_lossy(buffer).into_owned();
+            buffer.clear();
+            Ok(Some(line))
+        }
+        Err(err) if err.is_closed() => Ok(None),
+        Ok(_) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
 
-impl Encoder<String> for LinesEncoder {
-    type Error = StreamError;
+async fn write_line(write: &mut OwnedWriteHalf, line: &str) -> Result<(), io::Error> {
+    let mut data = Vec::with_capacity(line.len() + 1);
+    data.extend_from_slice(line.as_bytes());
+    data.push(b'\n');
 
-    fn encode(&mut self, line: String, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        dst.extend_from_slice(line.as_bytes());
-        dst.put_u8(b'\n');
-        Ok(())
-    }
+    write.write_all(&data).await?;
+    write.flush().await
 }
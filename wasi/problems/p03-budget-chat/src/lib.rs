@@ -1,11 +1,8 @@
 #![doc = include_str!("../README.md")]
 
-use std::sync::Arc;
+use std::rc::Rc;
 
-use futures::channel::mpsc;
-
-use wasi::io::streams::StreamError;
-use wasi::sockets::network;
+use p03_budget_chat_core::JoinError;
 
 use thiserror::Error;
 
@@ -20,38 +17,18 @@ pub use server::run;
 
 #[derive(Error, Debug)]
 pub enum Error {
-    #[error("stream error {0}")]
-    Stream(#[from] StreamError),
-
-    #[error("tcp socket error {0}")]
-    TcpSocket(#[from] network::ErrorCode),
+    #[error("io error {0}")]
+    Io(#[from] wasi_async::io::Error),
 
-    #[error("send error {0}")]
-    Send(#[from] mpsc::SendError),
-
-    #[error("invalid username")]
-    InvalidUsername,
+    #[error("join error {0}")]
+    Join(#[from] JoinError),
 }
 
 type Id = usize;
-type Username = String;
-type Message = String;
-
-#[derive(Debug)]
-enum ClientMessage {
-    SetUsername(Id, Username),
-    Joined(Id),
-    Message(Id, Arc<Message>),
-    Disconnect(Id),
-}
 
-#[derive(Debug)]
-enum ServerMessage {
-    Welcome,
-    UsernameAccepted,
-    UsernameInvalid,
-    AnnounceUser(Arc<Username>),
-    Users(Vec<Arc<Username>>),
-    Message(Arc<Username>, Arc<Message>),
-    Disconnected(Arc<Username>),
+/// A line for all the joined clients but `from`.
+#[derive(Clone, Debug)]
+struct Event {
+    from: Id,
+    line: Rc<str>,
 }
@@ -1,138 +1,45 @@
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::cell::RefCell;
+use std::rc::Rc;
 
-use futures::{channel::mpsc, StreamExt};
+use futures::StreamExt;
 
-use tracing::{debug, instrument, trace, warn};
+use tracing::{debug, instrument};
 
 use wasi_async::net::TcpListener;
+use wasi_async_runtime::sync::broadcast;
 
-use crate::{handle_client, ClientMessage, Error, Id, ServerMessage};
+use p03_budget_chat_core::Room;
 
-struct ClientInfo {
-    id: Id,
-    sender: mpsc::UnboundedSender<ServerMessage>,
-    username: Option<Arc<String>>,
-}
+use crate::{handle_client, Error};
 
-impl ClientInfo {
-    fn is_joined(&self) -> bool {
-        self.username.is_some()
-    }
-}
-
-fn is_username_valid(clients: &HashMap<Id, ClientInfo>, username: &str) -> bool {
-    !username.is_empty()
-        && username.chars().all(char::is_alphanumeric)
-        && clients.values().all(
-            |ClientInfo {
-                 username: other_username,
-                 ..
-             }| {
-                other_username
-                    .as_ref()
-                    .map_or(true, |other_username| **other_username != username)
-            },
-        )
-}
+/// The events kept for the slow clients.
+const EVENTS_CAPACITY: usize = 256;
 
 /// Run the main loop.
 ///
-/// Listen for clients and run the chat.
+/// Listen for clients and run the chat: the room is shared by the
+/// clients, the events go through a broadcast channel.
 ///
 /// # Errors
 /// * Error when socket returns an error.
-///
-/// # Panics
-/// * None
 #[instrument(skip_all)]
 pub async fn run(reactor: wasi_async_runtime::Reactor, listener: TcpListener) -> Result<(), Error> {
-    let mut id = 0;
-    let mut clients = HashMap::new();
-    let (server_sender, mut receiver) = mpsc::unbounded();
-
-    let mut incoming_clients = listener.into_stream().fuse();
-    loop {
-        trace!("main loop");
-        futures::select_biased! {
-            client_message = receiver.next() => {
-                trace!("client message: {client_message:?}");
-
-                // Panic: nobody closes this channel, unwrap ok
-                match client_message.expect("invalid state") {
-                    ClientMessage::SetUsername(id, username) => {
-                        if is_username_valid(&clients, &username) {
-                            let client = clients.get_mut(&id).unwrap();
-
-                            assert!(client.username.is_none());
+    let room = Rc::new(RefCell::new(Room::new()));
+    let (events, _) = broadcast::channel(EVENTS_CAPACITY);
 
-                            client.username = Some(Arc::new(username));
-                            client.sender.unbounded_send(ServerMessage::UsernameAccepted).ok();
-
-                            let mut users = vec![];
-                            for ClientInfo { username, .. } in clients.values().filter(|c| c.is_joined() && c.id != id) {
-                                // username must be set
-                                users.push(username.as_ref().unwrap().clone());
-                            }
-                            clients.get(&id).unwrap().sender.unbounded_send(ServerMessage::Users(users)).ok();
-                        } else {
-                            clients.get(&id).unwrap().sender.unbounded_send(ServerMessage::UsernameInvalid).ok();
-                        }
-                    }
-
-                    ClientMessage::Joined(id) => {
-                        if let Some(user) = clients[&id].username.clone() {
-                            for ClientInfo { sender, .. } in clients.values().filter(|c| c.is_joined() && c.id != id) {
-                                sender.unbounded_send(ServerMessage::AnnounceUser(user.clone())).ok();
-                            }
-                        } else {
-                            warn!("joined from invalid id {id}");
-                        }
-                    }
-
-                    ClientMessage::Message(id, message) => {
-                        if let Some(user) = clients[&id].username.clone() {
-                            for ClientInfo { sender, .. } in clients.values().filter(|c| c.is_joined() && c.id != id) {
-                                sender.unbounded_send(ServerMessage::Message(user.clone(), message.clone())).ok();
-                            }
-                        } else {
-                            warn!("message from invalid id {id}");
-                        }
-                    }
-
-                    ClientMessage::Disconnect(id) => {
-                        if let Some(client_info) = clients.get(&id) {
-                            if let Some(user) = client_info.username.clone() {
-                                for ClientInfo { sender, .. } in clients.values().filter(|c| c.is_joined() && c.id != id) {
-                                    sender.unbounded_send(ServerMessage::Disconnected(user.clone())).ok();
-                                }
-                            }
-                            clients.remove(&id);
-                        }
-                    }
-                }
-            }
-
-            client = incoming_clients.next() => {
-                let Some(Ok((socket, remote_address))) = client else { break Ok(()) };
-
-                debug!("new client: {remote_address:?}");
-
-                id += 1;
-
-                let (sender, receiver) = mpsc::unbounded();
+    let mut id = 0;
+    let mut incoming_clients = listener.into_stream();
+    while let Some(client) = incoming_clients.next().await {
+        let (stream, remote_address) = client?;
 
-                reactor.clone().spawn(handle_client(id, socket, server_sender.clone(), receiver));
+        debug!("new client: {remote_address:?}");
 
-                sender.unbounded_send(ServerMessage::Welcome).ok();
+        id += 1;
 
-                clients.insert(id,
-                               ClientInfo {
-                                   id,
-                                   sender,
-                                   username: None,
-                               });
-            }
-        }
+        reactor
+            .clone()
+            .spawn(handle_client(id, stream, room.clone(), events.clone()));
     }
+
+    Ok(())
 }
@@ -58,6 +58,32 @@ fn test_session() {
     });
 }
 
+#[test]
+fn test_too_long() {
+    wasi_async_runtime::block_on(|reactor| async move {
+        let (address, port) = spawn_app(reactor.clone()).await;
+
+        let socket = UdpSocket::bind(reactor.clone(), "127.0.0.1:0".to_string())
+            .await
+            .unwrap();
+
+        socket.connect(format!("{address}:{port}")).await.unwrap();
+
+        // 1000 bytes, dropped
+        let mut packet = b"long=".to_vec();
+        packet.resize(1000, b'x');
+        socket.send(packet).await.unwrap();
+
+        socket.send(b"long".to_vec()).await.unwrap();
+
+        // the first response is for foo, long has none
+        socket.send(b"foo=bar".to_vec()).await.unwrap();
+        socket.send(b"foo".to_vec()).await.unwrap();
+        let data = socket.recv().await.unwrap();
+        assert_eq!("foo=bar", std::str::from_utf8(&data).unwrap());
+    });
+}
+
 async fn spawn_app(reactor: wasi_async_runtime::Reactor) -> (String, u16) {
     static INIT_TRACING_SUBSCRIBER: Once = Once::new();
     INIT_TRACING_SUBSCRIBER.call_once(tracing_subscriber::fmt::init);
@@ -1,6 +1,5 @@
 #![doc = include_str!("../README.md")]
 
-use std::borrow::Cow;
 use std::future::Future;
 use std::pin::{pin, Pin};
 use std::rc::Rc;
@@ -12,9 +11,6 @@ use tracing::{debug, info, instrument, trace};
 
 use thiserror::Error;
 
-use wasi::io::streams::StreamError;
-use wasi::sockets::network;
-
 use wasi_async::codec::{FramedRead, LinesDecoder};
 use wasi_async::io::{AsyncWrite, AsyncWriteExt};
 use wasi_async::net::{TcpListener, TcpStream};
@@ -23,31 +19,33 @@ use wasi_async_runtime::Reactor;
 #[allow(warnings)]
 mod bindings;
 
-pub const BOGUSCOIN: &str = "7YWHMfk9JZe0LM0g1ZauHuiSxhI";
+pub use p05_mob_in_the_middle_core::{Rule, RuleConfig, Rules, BOGUSCOIN};
 
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("tcp error {0}")]
-    Tcp(#[from] network::ErrorCode),
-
-    #[error("stream error {0}")]
-    Stream(#[from] StreamError),
+    Tcp(#[from] wasi_async::io::Error),
 }
 
-type ActionResult = Option<Result<Vec<u8>, StreamError>>;
+type ActionResult = Option<Result<Vec<u8>, wasi_async::io::Error>>;
 
 enum Action {
     Client(ActionResult),
     Chat(ActionResult),
 }
 
+/// Run the proxy, rewriting the messages in both directions with
+/// `rules`.
+///
+/// # Errors
+/// * Error when the listener fails.
 #[instrument(skip(reactor, listener))]
 pub async fn run(
     reactor: Reactor,
     listener: TcpListener,
     chat_address: Rc<String>,
     chat_port: u16,
-    boguscoin: Rc<String>,
+    rules: Rc<Rules>,
 ) -> Result<(), Error> {
     loop {
         let (stream, remote_address) = listener.accept().await?;
@@ -55,10 +53,10 @@ pub async fn run(
         info!("client: {remote_address:?}");
 
         let chat_address = chat_address.clone();
-        let boguscoin = boguscoin.clone();
+        let rules = rules.clone();
         let c_reactor = reactor.clone();
         reactor.spawn(async move {
-            handle(c_reactor, stream, chat_address, chat_port, boguscoin)
+            handle(c_reactor, stream, chat_address, chat_port, rules)
                 .await
                 .ok();
         });
@@ -71,31 +69,10 @@ async fn handle(
     mut stream: TcpStream,
     chat_address: Rc<String>,
     chat_port: u16,
-    boguscoin: Rc<String>,
+    rules: Rc<Rules>,
 ) -> Result<(), Error> {
-    let transform = |message: &[u8]| -> String {
-        String::from_utf8_lossy(message)
-            .split_ascii_whitespace()
-            .map(|word| {
-                if word.starts_with('7')
-                    && (26..=35).contains(&word.len())
-                    && word.chars().all(char::is_alphanumeric)
-                {
-                    Cow::Borrowed(boguscoin.as_str())
-                } else {
-                    Cow::Borrowed(word)
-                }
-            })
-            .fold(String::new(), |mut s, word| {
-                if s.is_empty() {
-                    word.into_owned()
-                } else {
-                    s.push(' ');
-                    s.push_str(&word);
-                    s
-                }
-            })
-    };
+    let transform =
+        |message: &[u8]| -> String { rules.apply(&String::from_utf8_lossy(message)).into_owned() };
 
     let (client_read, mut client_write) = stream.split();
     let mut client_read = FramedRead::new(client_read, LinesDecoder::new());
@@ -1,3 +1,4 @@
+use std::path::PathBuf;
 use std::rc::Rc;
 
 use wasi_async::net::TcpListener;
@@ -6,7 +7,7 @@ use clap::Parser;
 
 use tracing::info;
 
-use p05_mob_in_the_middle::{run, BOGUSCOIN};
+use p05_mob_in_the_middle::{run, Rules, BOGUSCOIN};
 
 #[derive(clap::Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -25,6 +26,11 @@ struct Args {
 
     #[arg(long, default_value_t = BOGUSCOIN.to_string())]
     boguscoin: String,
+
+    /// The TOML file of the rewriting rules, instead of the Boguscoin
+    /// address replacement
+    #[arg(long, conflicts_with = "boguscoin")]
+    rules: Option<PathBuf>,
 }
 
 fn main() -> Result<(), anyhow::Error> {
@@ -34,6 +40,13 @@ fn main() -> Result<(), anyhow::Error> {
 
     info!("start");
 
+    let rules = if let Some(path) = args.rules {
+        info!("rules: {}", path.display());
+        Rules::from_toml(&std::fs::read_to_string(path)?)?
+    } else {
+        Rules::boguscoin(&args.boguscoin)
+    };
+
     let result = wasi_async_runtime::block_on(|reactor| async move {
         let listener =
             TcpListener::bind(reactor.clone(), format!("{}:{}", args.address, args.port)).await?;
@@ -43,7 +56,7 @@ fn main() -> Result<(), anyhow::Error> {
             listener,
             Rc::new(args.chat_address),
             args.chat_port,
-            Rc::new(args.boguscoin),
+            Rc::new(rules),
         )
         .await
     });
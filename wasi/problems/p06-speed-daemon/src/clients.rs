@@ -8,10 +8,8 @@ use futures_concurrency::future::Race;
 
 use tracing::{debug, info, instrument, warn};
 
-use wasi::sockets::network::IpSocketAddress;
-
 use wasi_async::codec::{FramedRead, FramedWrite};
-use wasi_async::net::TcpStream;
+use wasi_async::net::{IpSocketAddress, TcpStream};
 use wasi_async_runtime::Reactor;
 
 use crate::{wire, Cameras, ControllerMessage, Error};
@@ -10,7 +10,6 @@ use thiserror::Error;
 
 use tracing::{debug, info, instrument, warn};
 
-use wasi::sockets::network;
 
 use wasi_async::net::TcpListener;
 use wasi_async_runtime::Reactor;
@@ -30,7 +29,7 @@ type Cameras = Rc<RefCell<HashMap<u16, (u16, usize)>>>;
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("tcp socket error {0}")]
-    TcpSocket(#[from] network::ErrorCode),
+    TcpSocket(#[from] wasi_async::io::Error),
 
     #[error("dispatchers error {0}")]
     Dispatchers(#[from] dispatchers::Error),
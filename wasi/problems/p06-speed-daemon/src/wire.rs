@@ -2,8 +2,6 @@ use std::mem;
 
 use bytes::{Buf, BufMut, BytesMut};
 
-use wasi::io::streams::StreamError;
-
 use wasi_async::codec::{Decoder, Encoder};
 
 pub const ERROR_TAG: u8 = 0x10;
@@ -16,8 +14,8 @@ pub const I_AM_DISPATCHER_TAG: u8 = 0x81;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
-    #[error("stream error")]
-    StreamError(#[from] StreamError),
+    #[error("io error {0}")]
+    Io(#[from] wasi_async::io::Error),
 
     #[error("invalid message: 0x{0:2x}")]
     InvalidMessage(u8),
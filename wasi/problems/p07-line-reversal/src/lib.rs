@@ -0,0 +1,144 @@
+#![doc = include_str!("../README.md")]
+
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use futures::FutureExt;
+use futures_concurrency::prelude::*;
+
+use wasi_async::net::{self as net, IpSocketAddress, UdpSocket};
+use wasi_async::time;
+use wasi_async_runtime::Reactor;
+
+use p07_line_reversal_core::packets::SyncWrite;
+use p07_line_reversal_core::{LineReversal, Packet, Session};
+
+use thiserror::Error;
+
+use tracing::{debug, instrument, warn};
+
+/// How often the sessions check their timers.
+const TICK_PERIOD: Duration = Duration::from_millis(100);
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("udp socket error {0}")]
+    UdpSocket(#[from] wasi_async::io::Error),
+
+    #[error("io error {0}")]
+    Io(#[from] io::Error),
+}
+
+struct Connection {
+    address: IpSocketAddress,
+    session: Session,
+    line_reversal: LineReversal,
+}
+
+enum Event {
+    Datagram(Result<(Vec<u8>, IpSocketAddress), wasi_async::io::Error>),
+    Tick,
+}
+
+/// Serve the LRCP sessions, the protocol is in the runtime agnostic
+/// core shared with the tokio server.
+///
+/// # Errors
+/// * Error when a packet can not be encoded, the socket errors are
+///   logged.
+#[instrument(skip_all)]
+pub async fn run(reactor: Reactor, socket: UdpSocket) -> Result<(), Error> {
+    let mut connections = HashMap::<(SocketAddr, u32), Connection>::new();
+
+    let mut ticks = time::interval(reactor, TICK_PERIOD);
+    loop {
+        let datagram = socket.recv_from().map(Event::Datagram);
+        let tick = ticks.tick().map(|_| Event::Tick);
+
+        match (datagram, tick).race().await {
+            Event::Datagram(datagram) => {
+                let (data, address) = match datagram {
+                    Ok(datagram) => datagram,
+                    Err(err) => {
+                        warn!("cannot receive packet: {err}");
+                        continue;
+                    }
+                };
+
+                let packet = match Packet::try_from(data.as_slice()) {
+                    Ok(packet) => packet,
+                    Err(err) => {
+                        debug!(
+                            "[{:?}] ignored packet: {err}",
+                            net::std_socket_addr(address)
+                        );
+                        continue;
+                    }
+                };
+
+                let key = (net::std_socket_addr(address), packet.session().0);
+                let connection = match connections.get_mut(&key) {
+                    Some(connection) => connection,
+                    None if matches!(packet, Packet::Connect { .. }) => {
+                        debug!("[{:?}] new session {}", key.0, key.1);
+                        connections.entry(key).or_insert_with(|| Connection {
+                            address,
+                            session: Session::new(packet.session(), Instant::now()),
+                            line_reversal: LineReversal::new(),
+                        })
+                    }
+                    None => {
+                        let close = Packet::Close {
+                            session: packet.session(),
+                        };
+                        send(&socket, address, &[close]).await?;
+                        continue;
+                    }
+                };
+
+                let now = Instant::now();
+                let mut packets = connection.session.handle(packet, now);
+
+                let input = connection.session.read();
+                let output = connection.line_reversal.feed(&input);
+                packets.extend(connection.session.write(&output, now));
+
+                send(&socket, connection.address, &packets).await?;
+
+                if connection.session.is_closed() {
+                    debug!("[{:?}] closed session {}", key.0, key.1);
+                    connections.remove(&key);
+                }
+            }
+
+            Event::Tick => {
+                let now = Instant::now();
+                for connection in connections.values_mut() {
+                    let packets = connection.session.tick(now);
+                    send(&socket, connection.address, &packets).await?;
+                }
+
+                connections.retain(|_, connection| !connection.session.is_closed());
+            }
+        }
+    }
+}
+
+async fn send(
+    socket: &UdpSocket,
+    address: IpSocketAddress,
+    packets: &[Packet],
+) -> Result<(), Error> {
+    for packet in packets {
+        let mut data = vec![];
+        data.write_value(packet)?;
+
+        if let Err(err) = socket.send_to(data, address).await {
+            warn!("cannot send packet: {err}");
+        }
+    }
+
+    Ok(())
+}
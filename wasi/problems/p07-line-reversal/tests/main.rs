@@ -0,0 +1,78 @@
+use std::sync::Once;
+
+use tracing::info;
+
+use wasi_async::net::UdpSocket;
+
+#[test]
+fn test_session() {
+    wasi_async_runtime::block_on(|reactor| async move {
+        let (address, port) = spawn_app(reactor.clone()).await;
+
+        let socket = UdpSocket::bind(reactor.clone(), "127.0.0.1:0".to_string())
+            .await
+            .unwrap();
+
+        socket.connect(format!("{address}:{port}")).await.unwrap();
+
+        socket.send(b"/connect/12345/".to_vec()).await.unwrap();
+        assert_eq!(b"/ack/12345/0/".to_vec(), socket.recv().await.unwrap());
+
+        socket
+            .send(b"/data/12345/0/hello\n/".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(b"/ack/12345/6/".to_vec(), socket.recv().await.unwrap());
+        assert_eq!(
+            b"/data/12345/0/olleh\n/".to_vec(),
+            socket.recv().await.unwrap()
+        );
+
+        socket.send(b"/ack/12345/6/".to_vec()).await.unwrap();
+
+        socket
+            .send(b"/data/12345/6/Hello, world!\n/".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(b"/ack/12345/20/".to_vec(), socket.recv().await.unwrap());
+        assert_eq!(
+            b"/data/12345/6/!dlrow ,olleH\n/".to_vec(),
+            socket.recv().await.unwrap()
+        );
+
+        socket.send(b"/ack/12345/20/".to_vec()).await.unwrap();
+
+        socket.send(b"/close/12345/".to_vec()).await.unwrap();
+        assert_eq!(b"/close/12345/".to_vec(), socket.recv().await.unwrap());
+
+        // the session is gone
+        socket.send(b"/data/12345/20/x/".to_vec()).await.unwrap();
+        assert_eq!(b"/close/12345/".to_vec(), socket.recv().await.unwrap());
+    });
+}
+
+async fn spawn_app(reactor: wasi_async_runtime::Reactor) -> (String, u16) {
+    static INIT_TRACING_SUBSCRIBER: Once = Once::new();
+    INIT_TRACING_SUBSCRIBER.call_once(tracing_subscriber::fmt::init);
+
+    let address = "127.0.0.1";
+
+    let socket = UdpSocket::bind(reactor.clone(), format!("{address}:0"))
+        .await
+        .unwrap();
+    let port = socket
+        .local_addr()
+        .expect("cannot get local address")
+        .port();
+
+    reactor.spawn({
+        let reactor = reactor.clone();
+        async move {
+            p07_line_reversal::run(reactor, socket).await.unwrap();
+        }
+    });
+
+    info!("spawned app {address}:{port}");
+
+    (address.to_string(), port)
+}